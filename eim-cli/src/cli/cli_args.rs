@@ -0,0 +1,1329 @@
+use clap::builder::styling::{AnsiColor, Color, Style, Styles};
+use clap::{arg, command, value_parser, ColorChoice, Parser, Subcommand};
+use clap_complete::aot::Shell;
+use idf_im_lib::to_absolute_path;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+fn custom_styles() -> Styles {
+    Styles::styled()
+        .header(
+            Style::new()
+                .bold()
+                .underline()
+                .fg_color(Some(Color::Ansi(AnsiColor::Green))),
+        )
+        .usage(
+            Style::new()
+                .bold()
+                .fg_color(Some(Color::Ansi(AnsiColor::Green))),
+        )
+        .literal(Style::new().fg_color(Some(Color::Ansi(AnsiColor::Cyan))))
+        .placeholder(Style::new().fg_color(Some(Color::Ansi(AnsiColor::Blue))))
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(
+    name = "eim",
+    author,
+    version = VERSION,
+    about = "ESP-IDF Installation Manager",
+    long_about = "All you need to manage your ESP-IDF installations",
+    color = ColorChoice::Auto,
+    styles = custom_styles()
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+
+    #[arg(short, long, help = "Set the language for the wizard (en, cn)")]
+    pub locale: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        action = clap::ArgAction::Count,
+        help = "Increase verbosity level (can be used multiple times)"
+    )]
+    pub verbose: u8,
+
+    #[arg(long, help = "file in which logs will be stored (default: eim.log)")]
+    pub log_file: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "EIM_LOG_LEVEL",
+        help = "Log levels, a default level and per-module levels, e.g. 'info,idf_im_lib::idf_tools=trace'"
+    )]
+    pub log_level: Option<String>,
+
+    #[arg(
+        long,
+        help = "If set to true, the installer will not send any usage data. Default is false.",
+        default_value_t = false,
+        action = clap::ArgAction::Set
+    )]
+    pub do_not_track: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Print errors as JSON including their kind and exit code"
+    )]
+    pub json: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Read the proxy password from the first line of stdin for this run instead of the keyring"
+    )]
+    pub stdin_password: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Plain ASCII output without colors or animated progress, also used with NO_COLOR, TERM=dumb or when stdout is not a terminal"
+    )]
+    pub plain: bool,
+
+    #[arg(
+        long,
+        global = true,
+        env = "EIM_SCREEN_READER",
+        help = "Screen reader friendly output: plain output without percentage lines, with one line announcing each stage of an installation"
+    )]
+    pub screen_reader: bool,
+
+    #[arg(
+        long,
+        global = true,
+        env = "EIM_METRICS_PORT",
+        help = "Serve Prometheus metrics of the run on http://127.0.0.1:<PORT>/metrics"
+    )]
+    pub metrics_port: Option<u16>,
+}
+
+// todo: add fix command which will reinstall using the existing IDF repository
+#[derive(Subcommand, Debug, Clone)]
+pub enum Commands {
+    /// Install ESP-IDF versions
+    Install(InstallArgs),
+
+    /// List installed ESP-IDF versions
+    List,
+
+    /// Select an ESP-IDF version as active
+    Select {
+        #[arg(help = "Version to select as active")]
+        version: Option<String>,
+    },
+
+    /// Print the command activating an ESP-IDF version in the current shell, preferring the
+    /// installation of the project in the current directory. Use with eval "$(eim activate)"
+    Activate {
+        #[arg(help = "Version to activate, default: the selected one")]
+        version: Option<String>,
+    },
+
+    /// Start a shell with the environment of an ESP-IDF version applied and the version in its
+    /// prompt, preferring the installation of the project in the current directory
+    Shell {
+        #[arg(help = "Version to activate, default: the selected one")]
+        version: Option<String>,
+
+        #[arg(long, help = "Shell to start, e.g. zsh or pwsh, default: $SHELL, or PowerShell on Windows")]
+        shell: Option<String>,
+    },
+
+    /// Print where idf.py, the compilers, python and openocd of a version are, and whether the shell uses them
+    Which {
+        #[arg(help = "Tool to look up, e.g. openocd; default: idf.py, python, the compilers and openocd")]
+        tool: Option<String>,
+
+        #[arg(long, help = "Version to look in, default: the one activated in this shell, or the selected one")]
+        version: Option<String>,
+    },
+
+    /// Check an installation and the PATH for problems, e.g. other ESP-IDF toolchains shadowing its tools
+    Doctor {
+        #[arg(long, help = "Version to check, default: the one activated in this shell, or the selected one")]
+        version: Option<String>,
+    },
+
+    /// Move installations following a version alias (latest, stable, lts) to the version it stands for now
+    Upgrade {
+        #[arg(help = "Installation to upgrade, default: all tracked installations, or the selected one with --track")]
+        version: Option<String>,
+
+        #[arg(long, value_name = "ALIAS", help = "Make the installation follow this alias from now on: latest, stable or lts")]
+        track: Option<String>,
+    },
+
+    /// Print a summary of eim, the installed versions and the network setup, e.g. for bug reports
+    Status {
+        #[arg(long, help = "Configuration file with the proxy and mirrors, default: eim_config.toml if present")]
+        config: Option<String>,
+    },
+
+    /// Inspect the environment the activation of an ESP-IDF version sets up
+    Env {
+        #[command(subcommand)]
+        command: EnvCommands,
+    },
+
+    /// Check the download mirrors of ESP-IDF, the tools and Python packages
+    Mirrors {
+        #[command(subcommand)]
+        command: MirrorsCommands,
+    },
+
+    /// Check whether point releases supersede the installed ESP-IDF versions
+    CheckUpdates {
+        #[arg(long, value_name = "VERSION", help = "Stop notifying about this release, e.g. v5.3.3")]
+        mute: Option<String>,
+
+        #[arg(long, value_name = "VERSION", help = "Notify about a muted release again")]
+        unmute: Option<String>,
+
+        #[arg(long, conflicts_with = "enable", help = "Turn off the daily check done by other commands")]
+        disable: bool,
+
+        #[arg(long, help = "Turn the daily check done by other commands back on")]
+        enable: bool,
+    },
+
+    /// Wait for the install, upgrade or repair running in another eim process (GUI or CLI) and exit with its result
+    Wait,
+
+    /// Stream the progress of the install, upgrade or repair running in another eim process and exit with its result
+    Attach,
+
+    /// Show the support status and the release notes of an ESP-IDF version
+    Info {
+        #[arg(help = "Version to show, e.g. v5.3.2; the aliases latest, stable and lts are resolved")]
+        version: String,
+        #[arg(long, help = "Show the chip targets, their revisions and the tool versions the release supports")]
+        targets: bool,
+        #[arg(
+            long,
+            help = "Show the download and disk size of installing the release with the configured targets, components and paths"
+        )]
+        size: bool,
+    },
+
+    /// Discover available ESP-IDF versions (not implemented yet)
+    Discover,
+
+    /// Remove specific ESP-IDF version
+    Remove {
+        #[arg(help = "Version to remove")]
+        version: Option<String>,
+    },
+
+    /// Rename specific ESP-IDF version
+    Rename {
+        #[arg(help = "Version to rename")]
+        version: Option<String>,
+        new_name: Option<String>,
+    },
+
+    /// Attach a label or note to an installed ESP-IDF version, shown by list and the GUI
+    Label {
+        #[arg(help = "Version to label, its name or ID")]
+        version: String,
+
+        #[arg(help = "The label, e.g. \"customer-X firmware\"; without it the current label is shown")]
+        label: Option<String>,
+
+        #[arg(long, conflicts_with = "label", help = "Remove the label")]
+        clear: bool,
+    },
+
+    /// Import existing ESP-IDF installation using tools_set_config.json, or a tarball of eim export tarball
+    Import {
+        #[arg(help = "Import using existing config file, or install an exported .tar.zst tarball")]
+        path: Option<String>,
+
+        #[arg(long, help = "Base folder to install a tarball into, default: the default installation path")]
+        install_path: Option<String>,
+    },
+
+    /// Purge all ESP-IDF installations
+    Purge,
+
+    /// List the leftovers of failed installations and offer to remove them
+    Cleanup,
+
+    /// Run the ESP-IDF Installer Wizard
+    Wizard(InstallArgs),
+
+    /// Run the ESP-IDF Installer GUI with arguments passed through command line
+    Gui(InstallArgs),
+
+    /// Fix the ESP-IDF installation by reinstalling the tools and dependencies
+    Fix {
+        #[arg(help = "Fix IDF on a specific path")]
+        path: Option<String>,
+    },
+
+    /// Install drivers for ESP-IDF. This is only available on Windows platforms.
+    InstallDrivers,
+
+    /// Detect and install USB drivers for Espressif devices. This is only available on Windows platforms.
+    Drivers {
+        #[command(subcommand)]
+        command: DriversCommands,
+    },
+
+    /// Serve the installer over JSON-RPC for IDE plugins and automation
+    Serve {
+        #[arg(
+            long,
+            help = "Unix socket path or TCP port (bound to 127.0.0.1) to listen on"
+        )]
+        socket: Option<String>,
+
+        #[cfg(feature = "http-server")]
+        #[arg(
+            long,
+            help = "Address to serve the REST API on, e.g. 127.0.0.1:9900"
+        )]
+        http: Option<String>,
+
+        #[cfg(feature = "http-server")]
+        #[arg(
+            long,
+            requires = "http",
+            help = "Serve the REST API on other than loopback addresses, although the token and the install parameters travel unencrypted. Put a reverse proxy terminating TLS in front of it."
+        )]
+        allow_remote_plaintext: bool,
+
+        #[arg(
+            long,
+            env = "EIM_SERVE_TOKEN",
+            help = "Token clients of a TCP port or of the REST API must present. A random token is generated and printed if not set."
+        )]
+        token: Option<String>,
+    },
+
+    /// List the serial ports of connected boards with their driver and permission status
+    Devices,
+
+    /// Check that the tools of an installation are complete, optionally flashing an example to a board
+    Verify {
+        #[arg(help = "Version to verify, all installed versions if not set (the selected one with --flash)")]
+        version: Option<String>,
+
+        #[arg(
+            long,
+            help = "Build and flash the hello_world example to a connected board and check its boot log"
+        )]
+        flash: bool,
+
+        #[arg(long, requires = "flash", help = "Serial port of the board, the first Espressif board found if not set")]
+        port: Option<String>,
+
+        #[arg(long, requires = "flash", help = "Chip target of the board, detected with esptool if not set")]
+        target: Option<String>,
+    },
+
+    /// Register installations of the legacy ESP-IDF Tools Installer or install.sh with eim
+    Migrate {
+        #[arg(long, help = "Only list the legacy installations and the PATH entries which would be removed")]
+        dry_run: bool,
+
+        #[arg(short, long, help = "Migrate every legacy installation found and clean up the PATH without asking")]
+        yes: bool,
+
+        #[arg(long, help = "Also run the uninstaller of the legacy ESP-IDF Tools Installer (Windows)")]
+        uninstall_legacy: bool,
+    },
+
+    /// Hard-link identical tool files shared by the installed ESP-IDF versions
+    Dedupe {
+        #[arg(long, help = "Only report how much disk space would be saved")]
+        dry_run: bool,
+    },
+
+    /// Remove tool versions no installed ESP-IDF version uses anymore, e.g. after upgrades
+    Prune {
+        #[arg(long, help = "Only list the unused tool versions and the disk space they take")]
+        dry_run: bool,
+    },
+
+    /// Install optional tools into an installed ESP-IDF version
+    Tools {
+        #[command(subcommand)]
+        command: ToolsCommands,
+    },
+
+    /// Export an installed ESP-IDF version for IDEs and build tools
+    Export {
+        #[command(subcommand)]
+        command: ExportCommands,
+    },
+
+    /// Install, remove and list plugins providing additional components
+    Plugin {
+        #[command(subcommand)]
+        command: PluginCommands,
+    },
+
+    /// Register self-built toolchains, e.g. crosstool-NG builds, to be used instead of the official ones
+    Toolchain {
+        #[command(subcommand)]
+        command: ToolchainCommands,
+    },
+
+    /// Capture an installation in a relocatable archive and restore it, e.g. to cache a ready toolchain in CI
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommands,
+    },
+
+    /// Store or remove proxy passwords and private mirror tokens in the keyring of the operating system
+    Credentials {
+        #[command(subcommand)]
+        command: CredentialsCommands,
+    },
+
+    /// Schedule a background cleanup of the caches, update check and health check, notifying only when action is needed
+    Maintenance {
+        #[command(subcommand)]
+        command: MaintenanceCommands,
+    },
+
+    /// Generate shell completion script to stdout
+    Completions {
+        #[arg(help = "Shell for which to generate completion.", value_parser = value_parser!(Shell))]
+        shell: Shell,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum PluginCommands {
+    /// Install a plugin from the URL or path of its manifest
+    Add {
+        #[arg(help = "URL or path of the plugin manifest (TOML, YAML or JSON)")]
+        source: String,
+
+        #[arg(long, help = "Version to install the plugin into, the selected one if not set")]
+        version: Option<String>,
+    },
+
+    /// Remove a plugin
+    Remove {
+        #[arg(help = "Name of the plugin")]
+        name: String,
+
+        #[arg(long, help = "Version to remove the plugin from, the selected one if not set")]
+        version: Option<String>,
+    },
+
+    /// List installed plugins
+    List,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ToolchainCommands {
+    /// Register a toolchain after checking that its compilers run on this machine
+    Register {
+        #[arg(long, help = "Name of the toolchain, e.g. my-gcc")]
+        name: String,
+
+        #[arg(long, help = "Directory of the toolchain, or of its bin folder")]
+        path: String,
+
+        #[arg(long, value_delimiter = ',', required = true, help = "Targets the toolchain builds for, e.g. esp32,esp32s3")]
+        targets: Vec<String>,
+    },
+
+    /// Unregister a toolchain, the official one is used again
+    Remove {
+        #[arg(help = "Name of the toolchain")]
+        name: String,
+    },
+
+    /// List registered toolchains
+    List,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SnapshotCommands {
+    /// Write an installed version with its tools and Python environment to a .tar.zst archive
+    Create {
+        #[arg(help = "Version to capture, the selected one if not set")]
+        version: Option<String>,
+
+        #[arg(long, help = "Archive to write, e.g. snap.tar.zst")]
+        out: String,
+    },
+
+    /// Install a version from a snapshot archive, fixing up its paths
+    Restore {
+        #[arg(help = "Snapshot archive created by eim snapshot create")]
+        archive: String,
+
+        #[arg(short, long, help = "Base folder to restore into, default: the installation path from the configuration")]
+        path: Option<String>,
+
+        #[arg(short, long, help = "Configuration file providing the installation paths")]
+        config: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum MaintenanceCommands {
+    /// Register the maintenance with the task scheduler of the OS, weekly unless --daily is given
+    Enable {
+        #[arg(long, conflicts_with = "weekly", help = "Run every day")]
+        daily: bool,
+
+        #[arg(long, help = "Run every week, the default")]
+        weekly: bool,
+    },
+
+    /// Remove the maintenance from the task scheduler
+    Disable,
+
+    /// Show the schedule and the result of the last run
+    Status,
+
+    /// Run the maintenance now, as the scheduler does
+    Run,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CredentialsCommands {
+    /// Store a secret, prompting for it unless --stdin-password is given
+    Set {
+        #[arg(help = "Kind of secret", value_parser = ["proxy", "mirror-token"])]
+        kind: String,
+
+        #[arg(long, required_if_eq("kind", "mirror-token"), help = "Host of the mirror the token is sent to")]
+        host: Option<String>,
+    },
+
+    /// Remove a stored secret
+    Remove {
+        #[arg(help = "Kind of secret", value_parser = ["proxy", "mirror-token"])]
+        kind: String,
+
+        #[arg(long, required_if_eq("kind", "mirror-token"), help = "Host of the mirror")]
+        host: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DriversCommands {
+    /// Download, verify and silently install the USB drivers
+    Install {
+        #[arg(
+            long,
+            help = "Install only the drivers needed by connected devices which currently lack them"
+        )]
+        missing_only: bool,
+    },
+
+    /// Report connected Espressif devices and whether their drivers are installed
+    Detect,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum EnvCommands {
+    /// Show which variables the activation would add or change in the current shell, and what
+    /// in it conflicts with the version, e.g. the IDF_PATH of another installation
+    Diff {
+        #[arg(help = "Version to compare with, default: the selected one")]
+        version: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum MirrorsCommands {
+    /// Probe every known mirror for availability and for the latest release, to tell a mirror
+    /// outage from a network problem
+    Status {
+        #[arg(long, help = "Configuration file with the proxy and mirrors, default: eim_config.toml if present")]
+        config: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ExportCommands {
+    /// Write CMakeUserPresets.json and an environment file for CLion, Visual Studio and plain CMake
+    Cmake {
+        #[arg(help = "Version to export, the selected one if not set")]
+        version: Option<String>,
+
+        #[arg(long, help = "Directory to write the files to, usually the project root", default_value = ".")]
+        out: String,
+
+        #[arg(long, help = "Chip to configure the preset for", default_value = "esp32")]
+        target: String,
+    },
+
+    /// Write Espressif-IDE/Eclipse preferences pointing at the installed tools
+    Eclipse {
+        #[arg(help = "Version to export, the selected one if not set")]
+        version: Option<String>,
+
+        #[arg(long, help = "Directory to write the .epf preferences file to", default_value = ".")]
+        out: String,
+
+        #[arg(long, help = "Eclipse workspace to write the preferences into directly")]
+        workspace: Option<String>,
+    },
+
+    /// Write a devcontainer.json and Dockerfile mirroring an ESP-IDF setup in a container
+    Devcontainer {
+        #[arg(long, help = "ESP-IDF version to set up in the container, the selected one if not set")]
+        version: Option<String>,
+
+        #[arg(long, help = "Directory to write the files to", default_value = ".devcontainer")]
+        out: String,
+
+        #[arg(long, help = "Bind-mount the host installation instead of installing in the container (Linux only)")]
+        mount_host: bool,
+    },
+
+    /// Append the environment of an installation to $GITHUB_ENV and $GITHUB_PATH in GitHub Actions
+    GithubEnv {
+        #[arg(help = "Version to export, the selected one if not set")]
+        version: Option<String>,
+    },
+
+    /// Write the environment of an installation into the .env file of a project
+    Dotenv {
+        #[arg(help = "Version to export, the selected one if not set")]
+        version: Option<String>,
+
+        #[arg(long, help = "Directory to write the .env file to", default_value = ".")]
+        out: String,
+    },
+
+    /// Write the environment of an installation into the .envrc file of a project for direnv
+    Direnv {
+        #[arg(help = "Version to export, the selected one if not set")]
+        version: Option<String>,
+
+        #[arg(long, help = "Directory to write the .envrc file to", default_value = ".")]
+        out: String,
+    },
+
+    /// Write a relocatable .tar.zst tarball of an installation, to install it offline with eim import
+    Tarball {
+        #[arg(help = "Version to export, the selected one if not set")]
+        version: Option<String>,
+
+        #[arg(long, help = "Tarball to write, default: idf-<version>-<platform>.tar.zst")]
+        out: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ToolsCommands {
+    /// Download an optional tool, check that it works and add it to the activation script
+    Install {
+        #[arg(help = "Tool to install", value_parser = ["qemu"])]
+        tool: String,
+
+        #[arg(long, help = "Version to install the tool into, the selected one if not set")]
+        version: Option<String>,
+
+        #[arg(long, help = "Mirror to download the tool from")]
+        mirror: Option<String>,
+    },
+}
+
+#[derive(Parser, Debug, Clone, Default)]
+pub struct InstallArgs {
+    #[arg(
+        short,
+        long,
+        help = "Base Path to which all the files and folder will be installed",
+        value_parser = |s: &str| -> Result<String, String> {
+            to_absolute_path(s).map_err(|e| e.to_string())
+        }
+    )]
+    path: Option<String>,
+
+    #[arg(
+        long,
+        help = "Absolute path to save eim_idf.json file. Default is $HOME/.espressif/tools/eim_idf.json on POSIX systems and C:\\Espressif\\tools\\eim_idf.json on Windows systems"
+    )]
+    esp_idf_json_path: Option<String>,
+
+    #[arg(short, long, value_name = "FILE", help = "Configuration file in TOML, YAML or JSON format")]
+    pub config: Option<PathBuf>,
+
+    #[arg(
+        short,
+        long,
+        help = "You can provide multiple targets separated by comma"
+    )]
+    target: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "you can provide multiple versions of ESP-IDF separated by comma, you can also specify exact commit hash"
+    )]
+    pub idf_versions: Option<String>,
+
+    #[arg(long)]
+    pub tool_download_folder_name: Option<String>,
+
+    #[arg(long)]
+    pub tool_install_folder_name: Option<String>,
+
+    #[arg(
+        long,
+        help = "Path to tools.json file relative from ESP-IDF installation folder"
+    )]
+    pub tools_json_file: Option<String>,
+
+    #[arg(short, long)]
+    pub non_interactive: Option<bool>,
+
+    #[arg(
+        short,
+        long,
+        help = "URL for tools download mirror to be used instead of github.com"
+    )]
+    pub mirror: Option<String>,
+
+    #[arg(
+        long,
+        help = "URL for ESP-IDF download mirror to be used instead of github.com"
+    )]
+    pub idf_mirror: Option<String>,
+
+    #[arg(
+        long,
+        help = "URL for PyPI mirror to be used instead of https://pypi.org/simple"
+    )]
+    pub pypi_mirror: Option<String>,
+
+    #[arg(
+        long = "pypi-fallback-mirror",
+        value_name = "URL",
+        action = clap::ArgAction::Append,
+        help = "PyPI index tried when the Python packages can't be installed from the PyPI mirror because it is unreachable. Can be repeated, the indexes are tried in order."
+    )]
+    pub pypi_fallback_mirrors: Option<Vec<String>>,
+
+    #[arg(
+        short,
+        long,
+        action = clap::ArgAction::Count,
+        help = "Increase verbosity level (can be used multiple times)"
+    )]
+    pub verbose: u8,
+
+    #[arg(short, long, help = "Set the language for the wizard (en, cn)")]
+    pub locale: Option<String>,
+
+    #[arg(long, help = "file in which logs will be stored (default: eim.log)")]
+    pub log_file: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "Should the installer recurse into submodules of the ESP-IDF repository (default true) "
+    )]
+    pub recurse_submodules: Option<bool>,
+
+    #[arg(
+        short = 'a',
+        long,
+        help = "Should the installer attempt to install all missing prerequisites (default false). This flag only affects Windows platforms as we do not offer prerequisites for other platforms. "
+    )]
+    pub install_all_prerequisites: Option<bool>,
+
+    #[arg(
+        long,
+        help = "if set, the installer will as it's very last move save the configuration to the specified file path. This file can than be used to repeat the installation with the same settings."
+    )]
+    pub config_file_save_path: Option<String>,
+
+    #[arg(
+        long,
+        help = "Comma separated list of additional IDF features (ci, docs, pytests, etc.) to be installed with ESP-IDF."
+    )]
+    pub idf_features: Option<String>,
+
+    #[arg(
+        long,
+        help = "Repo stub to be used in case you want to use a custom repository. This is the 'espressif/esp-idf' part of the repository URL."
+    )]
+    pub repo_stub: Option<String>,
+
+    #[arg(
+        long,
+        help = "Skip prerequisites check. This is useful if you are sure that all prerequisites are already installed and you want to skip the check. This is not recommended unless you know what you are doing, as it can result in a non-functional installation. Use at your own risk."
+    )]
+    pub skip_prerequisites_check: Option<bool>,
+
+    #[arg(
+        long,
+        alias = "name",
+        help = "Version name to be used for the installation. If not provided, the version will be derived from the ESP-IDF repository tag or commit hash. Installing a version again under another name keeps a separate copy with its own Python environment, e.g. `eim install -i v5.2 --name customer-a`"
+    )]
+    pub version_name: Option<String>,
+
+    #[arg(
+        long,
+        help = "Folder name to be used for the python environments. If not provided, it will default to `python`."
+    )]
+    pub python_env_folder_name: Option<String>,
+
+    #[arg(
+        long,
+        help = "Path to a local archive for offline installation. This is useful if you have already downloaded the ESP-IDF zst archive and want to use it for installation without downloading it again."
+    )]
+    pub use_local_archive: Option<PathBuf>, // Path to a local archive for offline installation
+
+    #[arg(
+        long,
+        help = "Optional override for activation script path. This allows specifying a custom path for the activation script to be saved to instead of the default one."
+    )]
+    pub activation_script_path_override: Option<String>, // Optional override for activation script path
+
+    #[arg(
+        long,
+        help = "Optional override for Python version to install when installing prerequisites. This allows specifying a custom Python version instead of the default one. the accepted format is without dots like 'python313' for Python 3.13",
+        value_parser = is_valid_python_version
+    )]
+    pub python_version_override: Option<String>, // Optional override for Python version to install when installing prerequisites
+
+    #[arg(
+        long,
+        help = "Install USB drivers for Espressif devices (CP210x, FTDI, USB-JTAG, CH341) as part of the installation. Only affects Windows platforms. If not set, the wizard will ask."
+    )]
+    pub install_drivers: Option<bool>,
+
+    #[arg(
+        long,
+        help = "URL or path of a custom tools.json to use instead of the one shipped with ESP-IDF, e.g. a manifest hosted on an internal Artifactory."
+    )]
+    pub tools_manifest: Option<String>,
+
+    #[arg(
+        long,
+        help = "Rewrite tool download URLs, in the form <original prefix>=<new prefix>. Applied after --mirror, e.g. 'https://github.com/espressif=https://artifactory.example.com/espressif'."
+    )]
+    pub tools_url_rewrite: Option<String>,
+
+    #[arg(
+        long = "override",
+        value_name = "TOOL=VERSION",
+        action = clap::ArgAction::Append,
+        value_parser = parse_tool_version_override,
+        help = "Install a specific version of a tool instead of the one pinned by tools.json, in the form <tool>=<version>[@<sha256>], e.g. openocd-esp32=v0.12.0-esp32-20240821. The tool may be shortened to a prefix matching a single tool in tools.json, e.g. openocd. Can be repeated. The checksum is required when the version is not listed in tools.json."
+    )]
+    pub tool_version_overrides: Option<Vec<(String, String)>>,
+
+    #[arg(
+        long,
+        help = "Skip the network preflight check which verifies DNS, proxy and HTTPS access to every download endpoint before the installation starts."
+    )]
+    pub skip_network_preflight: Option<bool>,
+
+    #[arg(
+        long = "ca-certificate",
+        value_name = "PATH",
+        action = clap::ArgAction::Append,
+        help = "Additional root CA certificate (PEM or DER file) to trust for all HTTPS connections, e.g. the CA of a corporate TLS inspecting proxy. Can be repeated."
+    )]
+    pub ca_certificates: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        help = "Trust the certificates from the operating system trust store in addition to the bundled root certificates."
+    )]
+    pub use_system_trust_store: Option<bool>,
+
+    #[arg(
+        long,
+        help = "How links from tool archives are created on disk: auto (symlink, then junction, then copy), symlink, junction or copy"
+    )]
+    pub link_strategy: Option<String>,
+
+    #[arg(
+        long,
+        help = "Hard-link tool files identical to those of other installed versions instead of storing duplicates. Enabled by default."
+    )]
+    pub dedupe_tools: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Install ccache and enable it for ESP-IDF builds (IDF_CCACHE_ENABLE=1) for faster incremental builds."
+    )]
+    pub enable_ccache: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Key file used to sign the JSON installation report (HMAC-SHA256). The report is not signed if not set."
+    )]
+    pub report_signing_key: Option<String>,
+
+    #[arg(
+        long,
+        help = "Install the Initialize-Idf function into the PowerShell profile and an ESP-IDF (eim) Windows Terminal profile activating the selected version (Windows only)."
+    )]
+    pub powershell_integration: Option<bool>,
+
+    #[arg(
+        long,
+        value_name = "DISTRO",
+        help = "Run the installation inside the given WSL distro instead of on Windows, installing eim there first if needed (Windows only)."
+    )]
+    pub wsl: Option<String>,
+
+    #[arg(
+        long,
+        help = "Pick the targets and versions in a terminal UI and follow the installation of each tool there, e.g. over SSH where the GUI can't run"
+    )]
+    pub tui: bool,
+
+    #[arg(
+        long,
+        help = "Also install the Rust toolchain for ESP chips (Xtensa rustc fork, ldproxy, espflash) with espup and add it to the activation scripts. Requires rustup."
+    )]
+    pub rust_toolchain: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Also install Espressif's QEMU builds for running emulated ESP32 and ESP32-C3 targets. Not installed by default."
+    )]
+    pub install_qemu: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Proxy URL for downloads and clones, e.g. http://user@proxy:3128 or socks5h://user@localhost:1080; the password is read from the keyring, one given in the URL is moved there when the config is saved",
+        value_parser = is_valid_proxy
+    )]
+    pub proxy: Option<String>,
+
+    #[arg(
+        long,
+        help = "Comma separated list of optional components to install: examples, docs, qemu. Components not listed are left out. Default: examples,docs"
+    )]
+    pub components: Option<String>,
+
+    #[arg(
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Install system-wide into /opt/esp for all users, with group writable permissions and activation aliases in /etc/profile.d. Requires root"
+    )]
+    pub system: bool,
+
+    #[arg(
+        long,
+        help = "Group the system-wide installation is handed to, e.g. a group of all students. Default: the group of the install directory"
+    )]
+    pub system_group: Option<String>,
+
+    #[arg(
+        long = "project",
+        value_name = "DIR",
+        help = "Install into the .espressif folder of this project directory, so the project vendors its exact ESP-IDF and tools. eim activate picks it up inside the project"
+    )]
+    pub project_dir: Option<String>,
+
+    #[arg(
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Download the managed components used by the examples into a local registry mirror, so idf.py add-dependency works offline"
+    )]
+    pub component_mirror: bool,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Registry of the IDF Component Manager, e.g. a company registry"
+    )]
+    pub component_registry_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Storage URLs of the IDF Component Manager separated by ';', e.g. a mirror created with compote registry sync"
+    )]
+    pub component_storage_url: Option<String>,
+
+    #[arg(
+        long,
+        help = "Number of ESP-IDF submodules fetched at the same time (default 8)"
+    )]
+    pub submodule_jobs: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Attempts of a download or submodule fetch before giving up (default 3)"
+    )]
+    pub retry_max_attempts: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Seconds to wait after the first failed attempt, doubled after every further one (default 2)"
+    )]
+    pub retry_backoff_base: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Seconds without receiving data before a download is aborted and retried, 0 waits forever (default 60)"
+    )]
+    pub stall_timeout: Option<u64>,
+
+    #[arg(
+        long = "retry-host",
+        value_name = "HOST:KEY=VALUE",
+        action = clap::ArgAction::Append,
+        help = "Retry settings for a single host, in the form <host>:<key>=<value>[,<key>=<value>...] with the keys max_attempts, backoff_base and stall_timeout, e.g. 'github.com:max_attempts=6,stall_timeout=120'. Can be repeated."
+    )]
+    pub retry_host_overrides: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        help = "Only download and verify ESP-IDF, the tool archives and the Python packages into the download folder, without installing prerequisites, extracting tools or registering the version. Run the same install command again later, also without network, to finish the installation."
+    )]
+    pub download_only: bool,
+
+    #[arg(
+        long,
+        help = "Hash cached tool archives again even if they were verified before and did not change since. By default an unchanged verified archive is not hashed again."
+    )]
+    pub paranoid: bool,
+
+    #[arg(
+        long,
+        help = "Patch the cached archive of a previous tool version instead of downloading the whole archive, when the mirror publishes patches (default false)"
+    )]
+    pub differential_downloads: Option<bool>,
+
+    #[arg(
+        long,
+        help = "What downloads files: reqwest (the built-in client, default), curl (the system curl with its own proxy configuration) or aria2c"
+    )]
+    pub download_backend: Option<String>,
+
+    #[arg(
+        long,
+        help = "Name or id of an existing installation whose targets, mirrors and paths pre-fill this installation, explicit options still take precedence"
+    )]
+    pub from_installation: Option<String>,
+
+    #[arg(
+        long,
+        help = "Comma separated list of tools found on the PATH to use instead of downloading them: cmake, ninja, or auto for every tool with a version ESP-IDF supports"
+    )]
+    pub use_system_tools: Option<String>,
+
+    #[arg(
+        long,
+        conflicts_with = "tui",
+        help = "Never prompt, and when the installation fails or exceeds --max-duration, write a failure summary and a support bundle into --failure-dir. Meant for CI jobs nobody watches"
+    )]
+    pub unattended: bool,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = idf_im_lib::unattended::parse_duration,
+        help = "Stop the installation when it takes longer than this, e.g. 40m or 1h30m, and fail with exit code 19"
+    )]
+    pub max_duration: Option<std::time::Duration>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Folder the failure summary and the support bundle of --unattended are written to (default: the reports folder next to eim_idf.json)"
+    )]
+    pub failure_dir: Option<PathBuf>,
+}
+
+impl IntoIterator for InstallArgs {
+    type Item = (String, Option<config::Value>);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        vec![
+            ("path".to_string(), self.path.map(Into::into)),
+            (
+                "esp_idf_json_path".to_string(),
+                self.esp_idf_json_path.map(Into::into),
+            ),
+            (
+                "config".to_string(),
+                self.config.map(|p| p.to_str().unwrap().into()),
+            ),
+            (
+                "non_interactive".to_string(),
+                self.non_interactive.map(Into::into),
+            ),
+            (
+                "target".to_string(),
+                self.target.map(|s| {
+                    if !s.is_empty() {
+                        s.split(',').collect::<Vec<&str>>().into()
+                    } else {
+                        s.into()
+                    }
+                }),
+            ),
+            (
+                "idf_versions".to_string(),
+                self.idf_versions.map(|s| {
+                    if !s.is_empty() {
+                        s.split(',').collect::<Vec<&str>>().into()
+                    } else {
+                        s.into()
+                    }
+                }),
+            ),
+            (
+                "tool_download_folder_name".to_string(),
+                self.tool_download_folder_name.map(Into::into),
+            ),
+            (
+                "tool_install_folder_name".to_string(),
+                self.tool_install_folder_name.map(Into::into),
+            ),
+            (
+                "tools_json_file".to_string(),
+                self.tools_json_file.map(Into::into),
+            ),
+            ("mirror".to_string(), self.mirror.map(Into::into)),
+            ("idf_mirror".to_string(), self.idf_mirror.map(Into::into)),
+            ("pypi_mirror".to_string(), self.pypi_mirror.map(Into::into)),
+            (
+                "pypi_fallback_mirrors".to_string(),
+                self.pypi_fallback_mirrors.map(Into::into),
+            ),
+            (
+                "recurse_submodules".to_string(),
+                self.recurse_submodules.map(Into::into),
+            ),
+            (
+                "install_all_prerequisites".to_string(),
+                self.install_all_prerequisites.map(Into::into),
+            ),
+            (
+                "config_file_save_path".to_string(),
+                self.config_file_save_path.map(Into::into),
+            ),
+            (
+                "idf_features".to_string(),
+                self.idf_features
+                    .map(|s| s.split(',').collect::<Vec<&str>>().into()),
+            ),
+            ("repo_stub".to_string(), self.repo_stub.map(Into::into)),
+            (
+                "skip_prerequisites_check".to_string(),
+                self.skip_prerequisites_check.map(Into::into),
+            ),
+            (
+                "version_name".to_string(),
+                self.version_name.map(Into::into),
+            ),
+            (
+                "python_env_folder_name".to_string(),
+                self.python_env_folder_name.map(Into::into),
+            ),
+            (
+                "use_local_archive".to_string(),
+                self.use_local_archive.map(|p| p.to_str().unwrap().into()),
+            ),
+            (
+                "activation_script_path_override".to_string(),
+                self.activation_script_path_override.map(Into::into),
+            ),
+            (
+                "python_version_override".to_string(),
+                self.python_version_override.map(Into::into),
+            ),
+            (
+                "install_drivers".to_string(),
+                self.install_drivers.map(Into::into),
+            ),
+            (
+                "tools_manifest".to_string(),
+                self.tools_manifest.map(Into::into),
+            ),
+            (
+                "tools_url_rewrite".to_string(),
+                self.tools_url_rewrite.map(Into::into),
+            ),
+            (
+                "tool_version_overrides".to_string(),
+                self.tool_version_overrides.map(|overrides| {
+                    overrides
+                        .into_iter()
+                        .collect::<HashMap<String, String>>()
+                        .into()
+                }),
+            ),
+            (
+                "skip_network_preflight".to_string(),
+                self.skip_network_preflight.map(Into::into),
+            ),
+            (
+                "ca_certificates".to_string(),
+                self.ca_certificates.map(Into::into),
+            ),
+            (
+                "use_system_trust_store".to_string(),
+                self.use_system_trust_store.map(Into::into),
+            ),
+            (
+                "link_strategy".to_string(),
+                self.link_strategy.map(Into::into),
+            ),
+            (
+                "dedupe_tools".to_string(),
+                self.dedupe_tools.map(Into::into),
+            ),
+            (
+                "enable_ccache".to_string(),
+                self.enable_ccache.map(Into::into),
+            ),
+            (
+                "report_signing_key".to_string(),
+                self.report_signing_key.map(Into::into),
+            ),
+            (
+                "powershell_integration".to_string(),
+                self.powershell_integration.map(Into::into),
+            ),
+            (
+                "rust_toolchain".to_string(),
+                self.rust_toolchain.map(Into::into),
+            ),
+            (
+                "install_qemu".to_string(),
+                self.install_qemu.map(Into::into),
+            ),
+            (
+                "proxy".to_string(),
+                self.proxy.map(Into::into),
+            ),
+            (
+                "components".to_string(),
+                self.components.map(|s| s.split(',').collect::<Vec<&str>>().into()),
+            ),
+            (
+                "system_install".to_string(),
+                self.system.then_some(true).map(Into::into),
+            ),
+            (
+                "system_group".to_string(),
+                self.system_group.map(Into::into),
+            ),
+            (
+                "project_dir".to_string(),
+                self.project_dir.map(Into::into),
+            ),
+            (
+                "component_mirror".to_string(),
+                self.component_mirror.then_some(true).map(Into::into),
+            ),
+            (
+                "component_registry_url".to_string(),
+                self.component_registry_url.map(Into::into),
+            ),
+            (
+                "component_storage_url".to_string(),
+                self.component_storage_url.map(Into::into),
+            ),
+            (
+                "submodule_jobs".to_string(),
+                self.submodule_jobs.map(|n| (n as i64).into()),
+            ),
+            (
+                "retry_max_attempts".to_string(),
+                self.retry_max_attempts.map(|n| (n as i64).into()),
+            ),
+            (
+                "retry_backoff_base".to_string(),
+                self.retry_backoff_base.map(|n| (n as i64).into()),
+            ),
+            (
+                "stall_timeout".to_string(),
+                self.stall_timeout.map(|n| (n as i64).into()),
+            ),
+            (
+                "retry_host_overrides".to_string(),
+                self.retry_host_overrides.map(|overrides| {
+                    overrides
+                        .iter()
+                        .filter_map(|o| idf_im_lib::retry_policy::parse_host_override(o))
+                        .map(|(host, values)| {
+                            let values: HashMap<String, i64> = values.into_iter().map(|(k, v)| (k, v as i64)).collect();
+                            (host, values)
+                        })
+                        .collect::<HashMap<String, HashMap<String, i64>>>()
+                        .into()
+                }),
+            ),
+            (
+                "download_only".to_string(),
+                Some(self.download_only).filter(|enabled| *enabled).map(Into::into),
+            ),
+            (
+                "paranoid".to_string(),
+                Some(self.paranoid).filter(|enabled| *enabled).map(Into::into),
+            ),
+            (
+                "differential_downloads".to_string(),
+                self.differential_downloads.map(Into::into),
+            ),
+            (
+                "download_backend".to_string(),
+                self.download_backend.map(Into::into),
+            ),
+            (
+                "from_installation".to_string(),
+                self.from_installation.map(Into::into),
+            ),
+            (
+                "use_system_tools".to_string(),
+                self.use_system_tools.map(|s| s.split(',').collect::<Vec<&str>>().into()),
+            ),
+        ]
+        .into_iter()
+    }
+}
+
+fn is_valid_proxy(s: &str) -> Result<String, String> {
+    idf_im_lib::http_client::validate_proxy(s).map(|_| s.to_string())
+}
+
+fn parse_tool_version_override(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((tool, version)) if !tool.trim().is_empty() && !version.trim().is_empty() => {
+            Ok((tool.trim().to_string(), version.trim().to_string()))
+        }
+        _ => Err(format!(
+            "Invalid tool override: '{}'. Expected format is <tool>=<version>[@<sha256>].",
+            s
+        )),
+    }
+}
+
+fn is_valid_python_version(s: &str) -> Result<String, String> {
+    if s.len() > 6 && s.starts_with("python") && s[6..].chars().all(char::is_numeric) {
+        Ok(s.to_string())
+    } else {
+        Err(format!(
+            "Invalid Python version format: '{}'. Expected format is like 'python313'.",
+            s
+        ))
+    }
+}
@@ -1,9 +1,12 @@
 use console::Style;
-use dialoguer::{theme::ColorfulTheme, Confirm, Input, MultiSelect, Select};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, MultiSelect, Password, Select};
+use idf_im_lib::release_notes::{notes_for_terminal, ReleaseInfo, SupportStatus};
 use idf_im_lib::telemetry::track_event;
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 use log::debug;
 use rust_i18n::t;
+
+use crate::cli::terminal::CliProgress;
 use std::{
     fmt::Write,
     time::{Duration, Instant},
@@ -13,6 +16,16 @@ pub fn run_with_spinner<F, T>(func: F) -> T
 where
     F: FnOnce() -> T,
 {
+    let start_time = Instant::now();
+    if crate::cli::tui::is_active() {
+        return func();
+    }
+    if !crate::cli::terminal::capabilities().animate {
+        println!("{}", t!("wizard.spinner.message"));
+        let result = func();
+        debug!("Function completed in: {:?}", start_time.elapsed());
+        return result;
+    }
     let spinner = ProgressBar::new_spinner();
     spinner.set_style(
         ProgressStyle::default_spinner()
@@ -22,7 +35,6 @@ where
     );
 
     spinner.enable_steady_tick(Duration::from_millis(50));
-    let start_time = Instant::now();
     let result = func();
     spinner.finish_and_clear();
     debug!("Function completed in: {:?}", start_time.elapsed());
@@ -68,6 +80,21 @@ pub fn generic_confirm_with_default(
         .interact()
 }
 
+pub fn generic_password(prompt_key: &str) -> Result<String, dialoguer::Error> {
+    Password::with_theme(&create_theme())
+        .with_prompt(t!(prompt_key))
+        .with_confirmation(t!("credentials.confirm"), t!("credentials.mismatch"))
+        .interact()
+}
+
+/// Asks for a secret once, an empty answer is allowed so the user can give up.
+pub fn generic_password_optional(prompt_key: &str) -> Result<String, dialoguer::Error> {
+    Password::with_theme(&create_theme())
+        .with_prompt(t!(prompt_key))
+        .allow_empty_password(true)
+        .interact()
+}
+
 pub fn generic_multiselect(
     prompt_key: &str,
     options: &[String],
@@ -104,27 +131,49 @@ pub fn generic_input(prompt_key: &str, error_key: &str, default: &str) -> Result
         .map_err(|e| format!("{} :{:?}", t!(error_key), e))
 }
 
-pub fn create_progress_bar() -> ProgressBar {
-    let pb = ProgressBar::new(100);
-    pb.set_style(
+pub fn create_progress_bar() -> CliProgress {
+    CliProgress::new(100, || {
         ProgressStyle::with_template(
             "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] ({eta})",
         )
         .unwrap()
         .with_key("eta", |state: &ProgressState, w: &mut dyn Write| {
-            write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
+            write!(w, "{}", idf_im_lib::display_format::format_eta(state.eta().as_secs())).unwrap()
         })
-        .progress_chars("#>-"),
-    );
-    pb
+        .progress_chars("#>-")
+    })
 }
 
-pub fn update_progress_bar_number(pb: &ProgressBar, value: u64) {
+pub fn update_progress_bar_number(pb: &CliProgress, value: u64) {
     pb.set_position(value);
 }
 
 const EIM_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Prints the support status and the release notes of a version, at most `max_lines` lines of them.
+pub fn print_release_info(info: &ReleaseInfo, max_lines: Option<usize>) {
+    let support = match &info.support {
+        SupportStatus::PreRelease => t!("release_notes.pre_release"),
+        SupportStatus::Supported { until } => t!("release_notes.supported", until = until),
+        SupportStatus::EndOfLife { since } => t!("release_notes.end_of_life", since = since),
+        SupportStatus::Unknown => t!("release_notes.unknown_support"),
+    };
+    match &info.published {
+        Some(published) => println!("{}", t!("release_notes.title_published", version = info.version, date = published)),
+        None => println!("{}", t!("release_notes.title", version = info.version)),
+    }
+    println!("{}", support);
+    let Some(notes) = &info.notes else {
+        println!("{}", t!("release_notes.no_notes"));
+        return;
+    };
+    let (text, shortened) = notes_for_terminal(notes, max_lines);
+    println!("\n{}", text);
+    if let Some(url) = info.url.as_ref().filter(|_| shortened) {
+        println!("{}", t!("release_notes.more", url = url));
+    }
+}
+
 pub async fn track_cli_event(event_name: &str, additional_data: Option<serde_json::Value>) {
   let info = os_info::get();
   let system_info = format!("OS: {} {} | Architecture: {} | Kernel: {}",
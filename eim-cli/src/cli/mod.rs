@@ -0,0 +1,2124 @@
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use cli_args::Cli;
+use cli_args::Commands;
+use cli_args::CredentialsCommands;
+use cli_args::DriversCommands;
+use cli_args::EnvCommands;
+use cli_args::ExportCommands;
+use cli_args::MirrorsCommands;
+use cli_args::ToolchainCommands;
+use cli_args::ToolsCommands;
+use clap::CommandFactory;
+use clap_complete::generate;
+use cli_args::InstallArgs;
+use cli_args::PluginCommands;
+use cli_args::MaintenanceCommands;
+use cli_args::SnapshotCommands;
+use config::ConfigError;
+use helpers::generic_input;
+use helpers::generic_select;
+use idf_im_lib::credentials::Credential;
+use idf_im_lib::display_format::{format_eta, format_size, format_timestamp};
+use idf_im_lib::errors::{EimError, ErrorKind};
+use idf_im_lib::flash_test::FlashTestStep;
+use idf_im_lib::logging::{apply_log_level_setting, init_logging, LoggingOptions, CLI_LOG_FILE_NAME};
+use idf_im_lib::idf_versions;
+use idf_im_lib::install_lock::InstallLock;
+use idf_im_lib::idf_versions::get_latest_idf_version;
+use idf_im_lib::idf_versions::VersionAlias;
+use idf_im_lib::settings::Settings;
+use idf_im_lib::utils::is_valid_idf_directory;
+use idf_im_lib::version_manager::get_selected_version;
+use idf_im_lib::version_manager::prepare_settings_for_fix_idf_installation;
+use idf_im_lib::version_manager::remove_single_idf_version;
+use idf_im_lib::version_manager::select_idf_version;
+use idf_im_lib::version_tracking::TrackedInstallation;
+use idf_im_lib::telemetry::track_event;
+use idf_im_lib::update_notifications;
+use log::debug;
+use log::error;
+use log::info;
+use log::warn;
+use log::LevelFilter;
+use serde_json::json;
+use rust_i18n::t;
+
+use crate::cli::helpers::track_cli_event;
+
+/// Starts the GUI in this process with the console log level and the `--log-level` spec. Only
+/// the `eim-gui` binary, which links Tauri, passes one to [`run_cli`].
+pub type GuiLauncher = fn(LevelFilter, Option<String>);
+
+pub mod cli_args;
+pub mod helpers;
+pub mod prompts;
+pub mod serve;
+#[cfg(feature = "http-server")]
+pub mod serve_http;
+pub mod terminal;
+pub mod tui;
+pub mod wizard;
+
+fn setup_logging(cli: &cli_args::Cli, non_interactive: bool) -> anyhow::Result<()> {
+    let console_log_level = match (cli.verbose, non_interactive) {
+        (0, false) => LevelFilter::Info,
+        (0, true) => LevelFilter::Debug, // At least Debug level for non-interactive mode
+        (1, _) => LevelFilter::Debug,
+        (_, _) => LevelFilter::Trace,
+    };
+
+    let mut options = LoggingOptions::new(CLI_LOG_FILE_NAME, cli.log_file.clone().map(PathBuf::from));
+    options.console_level = Some(console_log_level);
+    if let Some(spec) = &cli.log_level {
+        options.levels = spec.parse().map_err(|e: anyhow::Error| EimError::new(ErrorKind::Usage, e.to_string()))?;
+    }
+    // The TUI shows the log records in its log pane, the console would draw over it
+    if matches!(&cli.command, Some(Commands::Install(args) | Commands::Wizard(args)) if args.tui) {
+        options.console_level = None;
+        options.extra_appenders.push(("tui".to_string(), LevelFilter::Info, || -> Box<dyn log4rs::append::Append> { Box::new(tui::TuiLogAppender) }));
+    }
+    // Log records of `eim serve` installs are also streamed to the RPC client
+    if matches!(cli.command, Some(Commands::Serve { .. })) {
+        options.extra_appenders.push(("rpc".to_string(), LevelFilter::Info, || -> Box<dyn log4rs::append::Append> { Box::new(serve::RpcProgressAppender) }));
+    }
+    let log_file = options.log_file.clone();
+    init_logging(options).map_err(|e| ConfigError::Message(e.to_string()))?;
+    idf_im_lib::crash_report::install("eim", log_file.clone());
+
+    // Log the configuration to verify settings
+    debug!(
+        "Logging initialized with console level: {:?}, log file: {}",
+        console_log_level,
+        log_file.display()
+    );
+    debug!("Non-interactive mode: {}", non_interactive);
+    debug!("Verbosity level: {}", cli.verbose);
+
+    Ok(())
+}
+
+
+
+fn is_mutating_command(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Install(_)
+            | Commands::Wizard(_)
+            | Commands::Fix { .. }
+            | Commands::Remove { .. }
+            | Commands::Rename { .. }
+            | Commands::Label { label: Some(_), .. }
+            | Commands::Label { clear: true, .. }
+            | Commands::Select { .. }
+            | Commands::Upgrade { .. }
+            | Commands::Import { .. }
+            | Commands::Purge
+            | Commands::Cleanup
+            | Commands::Dedupe { dry_run: false }
+            | Commands::Prune { dry_run: false }
+            | Commands::Migrate { dry_run: false, .. }
+            | Commands::Tools { .. }
+            | Commands::Plugin {
+                command: PluginCommands::Add { .. } | PluginCommands::Remove { .. }
+            }
+            | Commands::Snapshot {
+                command: SnapshotCommands::Restore { .. }
+            }
+            | Commands::Toolchain {
+                command: ToolchainCommands::Register { .. } | ToolchainCommands::Remove { .. }
+            }
+    )
+}
+
+fn print_status(status: &idf_im_lib::status::StatusReport) {
+    let not_set = t!("status.not_set").to_string();
+    println!("{}", t!("status.eim", version = status.eim_version, os = status.os, arch = status.arch));
+    if let Some(config) = &status.config_file {
+        println!("{}", t!("status.config", path = config.display()));
+    }
+    println!("{}", t!("status.selected", name = status.selected.clone().unwrap_or_else(|| not_set.clone())));
+    println!("{}", t!("status.installations", count = status.installations.len(), broken = status.broken_count()));
+    for installation in &status.installations {
+        let marker = if installation.selected { "*" } else { " " };
+        if installation.problems.is_empty() {
+            println!("{}", t!("status.installation_ok", marker = marker, name = installation.name, path = installation.path));
+        } else {
+            println!("{}", t!("status.installation_broken", marker = marker, name = installation.name, path = installation.path));
+            for problem in &installation.problems {
+                println!("      - {}", problem);
+            }
+        }
+    }
+    println!("{}", t!("status.proxy", proxy = status.proxy.clone().unwrap_or_else(|| not_set.clone())));
+    println!("{}", t!("status.idf_mirror", mirror = status.idf_mirror.clone().unwrap_or_else(|| not_set.clone())));
+    println!("{}", t!("status.tools_mirror", mirror = status.tools_mirror.clone().unwrap_or_else(|| not_set.clone())));
+    println!("{}", t!("status.pypi_mirror", mirror = status.pypi_mirror.clone().unwrap_or_else(|| not_set.clone())));
+    let cache_size = format_size(status.cache_size());
+    println!("{}", t!("status.cache", size = cache_size, count = status.caches.len()));
+    if status.pending_updates.is_empty() {
+        println!("{}", t!("status.no_updates"));
+    }
+    for update in &status.pending_updates {
+        println!("{}", t!("status.update", name = update.installation_name, available = update.available));
+    }
+    if !status.read_only.is_empty() {
+        println!("{}", t!("status.read_only"));
+        for permission in &status.read_only {
+            println!("    - {}", permission.path.display());
+        }
+    }
+}
+
+/// Blocks until the run of another eim process ends and returns its result, printing its log with `stream`.
+async fn follow_running_install(stream: bool) -> anyhow::Result<()> {
+    let Some(run) = idf_im_lib::run_monitor::find_running() else {
+        println!("{}", t!("attach.none"));
+        return Ok(());
+    };
+    println!("{}", t!("attach.following", holder = run.holder));
+    idf_im_lib::run_monitor::follow(run, |line| {
+        if stream {
+            println!("{}", line);
+        }
+    })
+    .await?;
+    println!("{}", t!("attach.succeeded"));
+    Ok(())
+}
+
+fn print_support_matrix(matrix: &idf_im_lib::support_matrix::SupportMatrix) {
+    println!("{}", t!("support_matrix.title", version = matrix.version));
+    for target in &matrix.targets {
+        let revisions = if target.revisions.is_empty() {
+            t!("support_matrix.revisions_unknown").to_string()
+        } else {
+            target.revisions.join(", ")
+        };
+        let preview = if target.preview { t!("support_matrix.preview").to_string() } else { String::new() };
+        println!("  {:<12} {}{}", target.target, revisions, preview);
+    }
+    if matrix.tools.is_empty() {
+        return;
+    }
+    println!("\n{}", t!("support_matrix.tools"));
+    for tool in &matrix.tools {
+        let targets = if tool.targets.is_empty() {
+            t!("support_matrix.all_targets").to_string()
+        } else {
+            tool.targets.join(", ")
+        };
+        println!("  {:<28} {:<24} {}", tool.name, tool.version, targets);
+    }
+}
+
+fn print_size_estimate(estimate: &idf_im_lib::size_estimate::SizeEstimate) {
+    use idf_im_lib::size_estimate::SizeSource;
+    println!(
+        "{}",
+        t!("size_estimate.title", version = estimate.version, targets = estimate.targets.join(", "))
+    );
+    for item in &estimate.items {
+        let source = match item.source {
+            SizeSource::Measured => t!("size_estimate.source.measured"),
+            SizeSource::Metadata => t!("size_estimate.source.metadata"),
+            SizeSource::Estimated => t!("size_estimate.source.estimated"),
+        };
+        println!(
+            "  {:<28} {:<24} {:>10} {:>10}  ({})",
+            item.name,
+            item.version,
+            format_size(item.download_bytes),
+            format_size(item.disk_bytes),
+            source
+        );
+    }
+    println!(
+        "\n{}",
+        t!(
+            "size_estimate.total",
+            download = format_size(estimate.download_bytes),
+            disk = format_size(estimate.disk_bytes)
+        )
+    );
+}
+
+fn print_mirrors(reports: &[idf_im_lib::mirror_health::MirrorReport], diagnosis: idf_im_lib::mirror_health::Diagnosis) {
+    use idf_im_lib::mirror_health::Diagnosis;
+    for report in reports {
+        let marker = if report.configured { "*" } else { " " };
+        let state = match (report.available, report.up_to_date) {
+            (false, _) => t!("mirrors.down", error = report.error.clone().unwrap_or_default()).to_string(),
+            (true, Some(false)) => t!("mirrors.stale", latest = report.latest.clone().unwrap_or_default()).to_string(),
+            (true, _) => t!("mirrors.ok", latency = report.latency_ms.unwrap_or_default()).to_string(),
+        };
+        println!("{} {:<8} {:<45} {}", marker, report.kind.to_string(), report.url, state);
+    }
+    println!();
+    println!(
+        "{}",
+        match diagnosis {
+            Diagnosis::Healthy => t!("mirrors.diagnosis.healthy"),
+            Diagnosis::MirrorProblem => t!("mirrors.diagnosis.mirror"),
+            Diagnosis::NetworkProblem => t!("mirrors.diagnosis.network"),
+        }
+    );
+}
+
+fn schedule_name(schedule: idf_im_lib::maintenance::Schedule) -> String {
+    match schedule {
+        idf_im_lib::maintenance::Schedule::Daily => t!("maintenance.daily").to_string(),
+        idf_im_lib::maintenance::Schedule::Weekly => t!("maintenance.weekly").to_string(),
+    }
+}
+
+fn print_maintenance_report(report: &idf_im_lib::maintenance::MaintenanceReport) {
+    match report.cleaned {
+        Some(count) => println!("{}", t!("maintenance.report.cleaned", count = count, size = format_size(report.reclaimed))),
+        None => println!("{}", t!("maintenance.report.cleanup_skipped")),
+    }
+    if !report.unregistered.is_empty() {
+        println!("{}", t!("maintenance.report.unregistered", count = report.unregistered.len()));
+        for path in &report.unregistered {
+            println!("  {}", path.display());
+        }
+    }
+    for update in &report.updates {
+        println!(
+            "{}",
+            t!("check_updates.available", name = update.installation_name, installed = update.installed, available = update.available)
+        );
+    }
+    for health in &report.unhealthy {
+        println!("{}", t!("maintenance.report.unhealthy", name = health.name));
+        for problem in &health.problems {
+            println!("  ! {}", problem);
+        }
+    }
+    for error in &report.errors {
+        println!("{}", t!("maintenance.report.error", error = error));
+    }
+    if !report.needs_action() {
+        println!("{}", t!("maintenance.report.nothing_to_do"));
+    }
+}
+
+/// One line per thing to do, short enough for a desktop notification.
+fn maintenance_notification_body(report: &idf_im_lib::maintenance::MaintenanceReport) -> String {
+    let mut lines: Vec<String> = report
+        .updates
+        .iter()
+        .map(|update| t!("maintenance.notification.update", name = update.installation_name, available = update.available).to_string())
+        .collect();
+    lines.extend(
+        report
+            .unhealthy
+            .iter()
+            .map(|health| t!("maintenance.notification.repair", name = health.name).to_string()),
+    );
+    if !report.unregistered.is_empty() {
+        lines.push(t!("maintenance.notification.unregistered", count = report.unregistered.len()).to_string());
+    }
+    lines.join("\n")
+}
+
+fn print_doctor(name: &str, problems: &[String], conflicts: &[idf_im_lib::path_conflicts::PathConflict]) {
+    use idf_im_lib::path_conflicts::ConflictSource;
+    println!("{}", t!("doctor.title", name = name));
+    if problems.is_empty() && conflicts.is_empty() {
+        println!("{}", t!("doctor.healthy"));
+        return;
+    }
+    for problem in problems {
+        println!("  ! {}", problem);
+    }
+    if conflicts.is_empty() {
+        return;
+    }
+    println!("{}", t!("doctor.path_conflicts"));
+    for conflict in conflicts {
+        let source = match &conflict.source {
+            ConflictSource::Msys2 => t!("doctor.source.msys2"),
+            ConflictSource::PlatformIo => t!("doctor.source.platformio"),
+            ConflictSource::OtherInstallation { name } => t!("doctor.source.other_installation", name = name),
+            ConflictSource::LegacyInstaller => t!("doctor.source.legacy_installer"),
+            ConflictSource::Other => t!("doctor.source.other"),
+        };
+        println!(
+            "  ! {}",
+            t!(
+                "doctor.shadowed",
+                tool = conflict.tool,
+                path = conflict.shadowing.display(),
+                source = source
+            )
+        );
+        println!("    {}", t!("doctor.instead_of", path = conflict.installation.display()));
+        if conflict.installation_on_path {
+            println!("    {}", t!("doctor.move_entry", entry = conflict.entry));
+        } else {
+            println!("    {}", t!("doctor.remove_entry", entry = conflict.entry, name = name));
+        }
+    }
+}
+
+fn print_env_diff(diff: &idf_im_lib::env_diff::EnvDiff) {
+    use idf_im_lib::env_diff::{ChangeKind, Conflict};
+    println!("{}", t!("env_diff.title", version = diff.version));
+    for change in &diff.variables {
+        match change.kind {
+            ChangeKind::Added => println!("  + {}={}", change.name, change.activated),
+            ChangeKind::Changed => println!(
+                "  ~ {}={}  ({})",
+                change.name,
+                change.activated,
+                t!("env_diff.was", value = change.current.clone().unwrap_or_default())
+            ),
+            ChangeKind::Unchanged => println!("    {}={}", change.name, change.activated),
+        }
+    }
+    println!("{}", t!("env_diff.path"));
+    for entry in &diff.path_added {
+        println!("  + {}", entry);
+    }
+    for entry in &diff.path_present {
+        println!("    {}  ({})", entry, t!("env_diff.on_path"));
+    }
+    println!();
+    if diff.conflicts.is_empty() {
+        println!("{}", t!("env_diff.no_conflicts"));
+        return;
+    }
+    println!("{}", t!("env_diff.conflicts"));
+    for conflict in &diff.conflicts {
+        let text = match conflict {
+            Conflict::OtherInstallation { variable, value } => {
+                t!("env_diff.conflict.other_installation", variable = variable, value = value)
+            }
+            Conflict::OtherInstallationOnPath { entry, installation } => {
+                t!("env_diff.conflict.other_on_path", entry = entry, installation = installation)
+            }
+            Conflict::OtherPythonEnvironment { variable, value } => {
+                t!("env_diff.conflict.python_environment", variable = variable, value = value)
+            }
+            Conflict::PythonOverride { variable, value } => {
+                t!("env_diff.conflict.python_override", variable = variable, value = value)
+            }
+        };
+        println!("  ! {}", text);
+    }
+}
+
+/// Asks for a new password when the proxy rejects the current one in the middle of the downloads.
+fn prompt_proxy_password(user: &str) -> Option<String> {
+    println!("{}", t!("proxy_auth.expired", user = user));
+    helpers::generic_password_optional("proxy_auth.prompt").ok()
+}
+
+/// Offers to remove what crashed installations left behind. Runs only while holding the install
+/// lock, so no running installation loses its staging folders.
+fn offer_remnant_cleanup(cli: &Cli, command: &Commands) {
+    let remnants = idf_im_lib::remnants::find_remnants(idf_im_lib::remnants::DEFAULT_MIN_AGE);
+    if remnants.is_empty() {
+        return;
+    }
+    let size = format_size(idf_im_lib::remnants::total_size(&remnants));
+    let non_interactive =
+        matches!(command, Commands::Install(args) if args.non_interactive != Some(false) || args.unattended);
+    if cli.json || non_interactive || !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        info!("{}", t!("remnants.found", count = remnants.len(), size = size));
+        return;
+    }
+    println!("{}", t!("remnants.found", count = remnants.len(), size = size));
+    for remnant in &remnants {
+        println!("  {}", remnant.path.display());
+    }
+    match helpers::generic_confirm_with_default("remnants.prompt", true) {
+        Ok(true) => {
+            let reclaimed = idf_im_lib::remnants::remove_remnants(&remnants);
+            let reclaimed = format_size(reclaimed);
+            println!("{}", t!("remnants.removed", size = reclaimed));
+        }
+        Ok(false) => println!("{}", t!("remnants.kept")),
+        Err(err) => debug!("Remnant cleanup prompt failed: {}", err),
+    }
+}
+
+/// Token of `eim serve`, a random one is generated and printed if none was given.
+fn server_token(token: Option<String>) -> String {
+    token.unwrap_or_else(|| {
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        println!("{}", t!("serve.generated_token", token = token));
+        token
+    })
+}
+
+/// The installation of the project in the current directory, or the given or selected one.
+fn find_installation_for_current_dir(version: Option<&str>) -> anyhow::Result<idf_im_lib::idf_config::IdfInstallation> {
+    let current_dir = std::env::current_dir()?;
+    Ok(match idf_im_lib::project_install::find_project(&current_dir) {
+        Some(project_dir) => {
+            debug!("Using the installation of the project {}", project_dir.display());
+            idf_im_lib::project_install::find_installation(&project_dir, version)?
+        }
+        None => idf_im_lib::version_manager::find_installation(version)?,
+    })
+}
+
+/// Registers the installations of the legacy installer and of install.sh, then offers to remove
+/// their PATH entries and the legacy installer itself.
+fn migrate_legacy_installations(dry_run: bool, yes: bool, uninstall_legacy: bool) -> anyhow::Result<()> {
+    use idf_im_lib::legacy_migration;
+
+    let found = legacy_migration::detect();
+    let uninstallers = if uninstall_legacy {
+        legacy_migration::find_legacy_uninstallers()
+    } else {
+        Vec::new()
+    };
+    if found.is_empty() && uninstallers.is_empty() {
+        println!("{}", t!("migrate.none"));
+        return Ok(());
+    }
+    let describe = |installation: &legacy_migration::LegacyInstallation| {
+        t!(
+            "migrate.item",
+            name = installation.name,
+            path = installation.idf_path.display(),
+            tools = installation.tools_path.display()
+        )
+        .to_string()
+    };
+    if !found.is_empty() {
+        println!("{}", t!("migrate.found", count = found.len()));
+        for installation in &found {
+            println!("  {}", describe(installation));
+        }
+    }
+    if dry_run {
+        let entries: Vec<String> = std::env::var_os("PATH")
+            .map(|path| std::env::split_paths(&path).map(|p| p.to_string_lossy().into_owned()).collect())
+            .unwrap_or_default();
+        let (_, removed) = legacy_migration::split_path_entries(&entries, &found);
+        for entry in removed {
+            println!("{}", t!("migrate.dry_run_path", entry = entry));
+        }
+        for uninstaller in &uninstallers {
+            println!("{}", t!("migrate.dry_run_uninstall", name = uninstaller.name));
+        }
+        return Ok(());
+    }
+
+    // without a terminal to ask on, everything is migrated and nothing else is changed
+    let interactive = std::io::stdin().is_terminal();
+    let confirm = |key: &str, default: bool| {
+        yes || (interactive && helpers::generic_confirm_with_default(key, default).unwrap_or(false))
+    };
+    let selected = if yes || !interactive || found.len() < 2 {
+        found
+    } else {
+        let options: Vec<String> = found.iter().map(describe).collect();
+        let chosen = helpers::generic_multiselect("migrate.select", &options, &vec![true; options.len()])
+            .map_err(|err| anyhow::anyhow!(err))?;
+        found.into_iter().filter(|installation| chosen.contains(&describe(installation))).collect()
+    };
+    let mut migrated = Vec::new();
+    for installation in selected {
+        match legacy_migration::migrate(&installation) {
+            Ok(registered) => {
+                println!("{}", t!("migrate.migrated", name = registered.name, script = registered.activation_script));
+                migrated.push(installation);
+            }
+            Err(err) => error!("{}", t!("migrate.failed", name = installation.name, error = err)),
+        }
+    }
+
+    if !migrated.is_empty() && confirm("migrate.clean_path_prompt", true) {
+        let cleanup = legacy_migration::clean_path(&migrated)?;
+        for entry in &cleanup.removed_entries {
+            println!("{}", t!("migrate.path_removed", entry = entry));
+        }
+        for (file, line) in &cleanup.disabled_lines {
+            println!("{}", t!("migrate.rc_disabled", file = file.display(), line = line));
+        }
+    }
+
+    for uninstaller in &uninstallers {
+        println!("{}", t!("migrate.uninstaller_found", name = uninstaller.name));
+        if !confirm("migrate.uninstall_prompt", false) {
+            continue;
+        }
+        match legacy_migration::run_uninstaller(uninstaller) {
+            Ok(()) => println!("{}", t!("migrate.uninstalled", name = uninstaller.name)),
+            Err(err) => error!("{}", t!("migrate.uninstall_failed", name = uninstaller.name, error = err)),
+        }
+    }
+    Ok(())
+}
+
+/// Mentions point releases of the installed versions, checking the release feed at most once a day.
+///
+/// Only shown on a terminal; failures are logged, they must not fail the command.
+async fn print_patch_update_notice() {
+    if !std::io::stdout().is_terminal() {
+        return;
+    }
+    let pending = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        update_notifications::pending_updates(),
+    )
+    .await;
+    match pending {
+        Ok(Ok(updates)) => {
+            for update in updates {
+                println!(
+                    "{}",
+                    t!("check_updates.notice", name = update.installation_name, available = update.available)
+                );
+            }
+        }
+        Ok(Err(err)) => debug!("Checking for patch releases failed: {}", err),
+        Err(_) => debug!("Checking for patch releases timed out"),
+    }
+}
+
+/// Installs the version the alias of `tracked` stands for now and replaces the tracked installation with it.
+async fn upgrade_tracked_installation(tracked: &TrackedInstallation) -> anyhow::Result<()> {
+    let installation = idf_im_lib::version_manager::get_esp_ide_config()?
+        .idf_installed
+        .into_iter()
+        .find(|installation| installation.id == tracked.installation_id);
+    let Some(installation) = installation else {
+        warn!("{}", t!("upgrade.missing", id = tracked.installation_id));
+        return idf_im_lib::version_tracking::untrack(&tracked.installation_id);
+    };
+    let alias = VersionAlias::from_name(&tracked.alias).ok_or_else(|| {
+        EimError::new(ErrorKind::Usage, t!("upgrade.unknown_alias", alias = tracked.alias).to_string())
+    })?;
+    let target = idf_versions::resolve_alias(alias)
+        .await
+        .map_err(|err| EimError::new(ErrorKind::Network, err))?;
+    let current = idf_im_lib::install_manifest::load_manifest_for(&installation.path)
+        .map(|manifest| manifest.idf_version)
+        .unwrap_or_else(|| installation.name.clone());
+    if current == target {
+        println!("{}", t!("upgrade.up_to_date", name = installation.name, alias = alias.name(), version = target));
+        return Ok(());
+    }
+    println!("{}", t!("upgrade.upgrading", name = installation.name, from = current, to = target));
+    let was_selected = get_selected_version().is_some_and(|selected| selected.id == installation.id);
+
+    let mut settings = Settings::default();
+    settings.idf_versions = Some(vec![target.clone()]);
+    settings.install_all_prerequisites = Some(true);
+    settings.config_file_save_path = None;
+    // next to the upgraded version, <base>/<version>/esp-idf
+    if let Some(base) = std::path::Path::new(&installation.path).parent().and_then(|p| p.parent()) {
+        settings.path = Some(base.to_path_buf());
+    }
+    wizard::run_wizzard_run(settings).await.map_err(|err| anyhow::anyhow!(err))?;
+
+    let upgraded = idf_im_lib::version_manager::find_installation(Some(&target))?;
+    idf_im_lib::version_tracking::track(&upgraded.id, alias)?;
+    remove_single_idf_version(&installation.id, false)?;
+    if was_selected {
+        select_idf_version(&upgraded.id)?;
+    }
+    println!("{}", t!("upgrade.done", name = installation.name, version = target));
+    Ok(())
+}
+
+async fn acquire_install_lock(command: &Commands) -> anyhow::Result<InstallLock> {
+    let command_name = format!("{:?}", command)
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    InstallLock::acquire(&format!("eim {}", command_name), None, |holder| {
+        warn!(
+            "{}",
+            t!(
+                "lock.waiting",
+                pid = holder.pid,
+                command = holder.command,
+                started = format_timestamp(&holder.started)
+            )
+        );
+    })
+    .await
+    .map_err(|err| anyhow::anyhow!(err))
+}
+
+/// Cancels the running command on Ctrl+C or termination, e.g. by the GUI. The subprocesses run in
+/// process groups of their own (see `cancellation::own_process_group`) and don't see either, so
+/// they are terminated and their staging folders removed before eim exits.
+fn forward_interruptions() {
+    tokio::spawn(async {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let Ok(mut terminate) = signal(SignalKind::terminate()) else {
+                return;
+            };
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+        }
+        #[cfg(not(unix))]
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        warn!("{}", t!("cli.interrupted"));
+        let _ = tokio::task::spawn_blocking(|| {
+            idf_im_lib::cancellation::cancel_and_wait();
+            idf_im_lib::cancellation::clean_up_after_cancel();
+        })
+        .await;
+        std::process::exit(130);
+    });
+}
+
+/// What `command` writes, `None` for commands which only inspect installations.
+fn required_access(command: &Commands) -> Option<idf_im_lib::read_only::Access> {
+    use idf_im_lib::read_only::Access;
+    if !is_mutating_command(command) {
+        return None;
+    }
+    Some(match command {
+        // fills the download folder and takes the lock, the list of installations stays as it is
+        Commands::Install(args) | Commands::Wizard(args) if args.download_only => Access::Lock,
+        Commands::Fix { .. }
+        | Commands::Remove { .. }
+        | Commands::Upgrade { .. }
+        | Commands::Purge
+        | Commands::Dedupe { .. }
+        | Commands::Prune { .. }
+        | Commands::Tools { .. } => Access::Tools,
+        _ => Access::Registry,
+    })
+}
+
+/// Fails with what the current user can't write when `command` would change a shared,
+/// read-only installation, before it changes anything. Inspecting installations keeps working.
+fn check_write_access(command: &Commands) -> anyhow::Result<()> {
+    use idf_im_lib::read_only::{self, Purpose};
+    let Some(access) = required_access(command) else {
+        return Ok(());
+    };
+    let missing = read_only::missing_permissions(access);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let mut message = t!("read_only.denied").to_string();
+    for permission in &missing {
+        let path = permission.path.display();
+        let line = match &permission.purpose {
+            Purpose::Registry => t!("read_only.registry", path = path),
+            Purpose::RegistryFolder => t!("read_only.registry_folder", path = path),
+            Purpose::Tools { installations } => {
+                t!("read_only.tools", path = path, installations = installations.join(", "))
+            }
+        };
+        message.push_str(&format!("\n  - {}", line));
+    }
+    message.push_str(&format!("\n{}", t!("read_only.hint")));
+    Err(EimError::new(ErrorKind::Permissions, message).into())
+}
+
+/// Prints the error a command failed with and returns the exit code of its class.
+///
+/// With `--json`, the error is printed to stdout as
+/// `{"error": {"kind": "network", "code": 10, "message": "..."}}` for automation.
+pub fn report_error(err: &anyhow::Error, json_output: bool) -> i32 {
+    let kind = idf_im_lib::errors::classify(err);
+    if json_output {
+        println!(
+            "{}",
+            json!({ "error": { "kind": kind, "code": kind.exit_code(), "message": format!("{:#}", err) } })
+        );
+    } else {
+        eprintln!("Error executing CLI: {}", err);
+    }
+    kind.exit_code()
+}
+
+/// Maps the `kind` and `--host` arguments of `eim credentials` to the keyring entry.
+fn credential_from_args(kind: &str, host: Option<String>) -> Credential {
+    match kind {
+        "mirror-token" => Credential::MirrorToken {
+            host: host.unwrap_or_default(),
+        },
+        _ => Credential::ProxyPassword,
+    }
+}
+
+/// Offers to bundle the crash reports written since eim last ran, to attach to an issue.
+fn offer_crash_bundle(cli: &Cli, command: &Commands) {
+    let reports = idf_im_lib::crash_report::pending_reports();
+    let Some(last) = reports.last() else {
+        return;
+    };
+    let found = t!(
+        "crash.found",
+        count = reports.len(),
+        time = last.report.time,
+        message = last.report.message
+    );
+    let non_interactive =
+        matches!(command, Commands::Install(args) if args.non_interactive != Some(false) || args.unattended);
+    if cli.json || non_interactive || !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        warn!("{}", found);
+        return;
+    }
+    println!("{}", found);
+    match helpers::generic_confirm_with_default("crash.prompt", true) {
+        Ok(true) => {
+            let dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+            let settings = Settings::new(None, std::iter::empty()).unwrap_or_default();
+            match idf_im_lib::crash_report::bundle_pending_reports(&dir, &reports, &settings) {
+                Ok(bundle) => println!("{}", t!("crash.bundle_written", path = bundle.display())),
+                Err(err) => warn!("Failed to write the crash support bundle: {}", err),
+            }
+        }
+        Ok(false) => {
+            idf_im_lib::crash_report::mark_seen(&reports);
+            if let Some(dir) = idf_im_lib::crash_report::get_crashes_directory() {
+                println!("{}", t!("crash.kept", path = dir.display()));
+            }
+        }
+        Err(err) => debug!("Crash bundle prompt failed: {}", err),
+    }
+}
+
+/// Runs the `eim-gui` binary next to this one with the same arguments, so that `eim` itself
+/// does not link or load the webview libraries.
+fn run_gui_binary() -> anyhow::Result<()> {
+    let gui = std::env::current_exe()?.with_file_name(format!("eim-gui{}", std::env::consts::EXE_SUFFIX));
+    if !gui.exists() {
+        return Err(EimError::new(ErrorKind::Usage, t!("gui.not_installed", path = gui.display()).to_string()).into());
+    }
+    let status = std::process::Command::new(&gui).args(std::env::args_os().skip(1)).status()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(t!("gui.failed", status = status)));
+    }
+    Ok(())
+}
+
+pub async fn run_cli(cli: Cli, gui: Option<GuiLauncher>) -> anyhow::Result<()> {
+  let do_not_track = cli.do_not_track;
+    terminal::init(cli.plain, cli.screen_reader);
+    // Initial tracking of CLI start
+    let command = match cli.clone().command {
+        Some(command) => command,
+        // the GUI binary starts the GUI when run without a command
+        None if gui.is_some() => Commands::Gui(InstallArgs::default()),
+        None => {
+            Cli::command()
+                .print_help()
+                .expect(&t!("cli.no_command"));
+            return Ok(());
+        }
+    };
+    match command {
+        Commands::Gui(_) => {
+            println!("{}", t!("gui.running"));
+        }
+        _ => {
+            setup_logging(&cli, false).context("Failed to setup logging")?;
+            idf_im_lib::crash_report::set_state("command", std::env::args().nth(1).unwrap_or_default());
+            offer_crash_bundle(&cli, &command);
+        }
+    }
+    if let Some(port) = cli.metrics_port {
+        let address = idf_im_lib::metrics::serve(port)
+            .map_err(|e| EimError::new(ErrorKind::Usage, t!("metrics.bind_failed", port = port, error = e.to_string()).to_string()))?;
+        println!("{}", t!("metrics.serving", address = address));
+    }
+    if cli.stdin_password && !matches!(command, Commands::Credentials { .. }) {
+        let password = idf_im_lib::credentials::read_secret_from_stdin()?;
+        idf_im_lib::credentials::set_session_secret(Credential::ProxyPassword, password);
+    }
+    // unattended runs fail instead of waiting for an answer nobody gives
+    let unattended = matches!(&command, Commands::Install(args) if args.unattended);
+    if !cli.json && !unattended && std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+        idf_im_lib::proxy_auth::set_prompt(Some(std::sync::Arc::new(prompt_proxy_password)));
+    }
+    if !do_not_track {
+        track_cli_event("CLI started", Some(json!({
+          "command": format!("{:?}", command)
+        }))).await;
+    }
+    // Held until the command finishes, so concurrent eim runs can't corrupt the shared tools directory
+    let _install_lock = if is_mutating_command(&command) {
+        check_write_access(&command)?;
+        Some(acquire_install_lock(&command).await?)
+    } else {
+        None
+    };
+    if _install_lock.is_some() {
+        forward_interruptions();
+    }
+    // eim cleanup makes the same offer itself
+    if _install_lock.is_some() && !matches!(command, Commands::Cleanup) {
+        offer_remnant_cleanup(&cli, &command);
+        // only a system-wide installation is meant to run as root
+        if !matches!(&command, Commands::Install(args) | Commands::Wizard(args) if args.system) {
+            idf_im_lib::elevation::warn_if_root();
+        }
+    }
+    // every install, upgrade and repair gets a log of its own, to attach to an issue
+    let run_log = match &command {
+        Commands::Install(args) | Commands::Wizard(args) if args.wsl.is_none() => Some("install"),
+        Commands::Upgrade { .. } => Some("upgrade"),
+        Commands::Fix { .. } => Some("repair"),
+        _ => None,
+    }
+    .and_then(idf_im_lib::logging::start_run_log);
+    if let Some(path) = &run_log {
+        println!("{}", t!("cli.run_log", path = path.display()));
+    }
+    let result = match command {
+        Commands::Completions { shell } => {
+            let mut cmd = Cli::command();
+            generate(shell, &mut cmd, "eim", &mut std::io::stdout());
+            return Ok(());
+        }
+        Commands::Install(install_args) => {
+            if let Some(distro) = &install_args.wsl {
+                if std::env::consts::OS != "windows" {
+                    return Err(EimError::new(ErrorKind::Usage, t!("wsl.windows_only").to_string()).into());
+                }
+                let args: Vec<String> = std::env::args().skip(1).collect();
+                info!("{}", t!("wsl.forwarding", distro = distro));
+                return idf_im_lib::wsl::run_eim_in_distro(distro, &idf_im_lib::wsl::strip_wsl_argument(&args));
+            }
+            let settings = Settings::new(
+                install_args.config.clone(),
+                install_args.clone().into_iter(),
+            );
+            info!("Returned settings: {:?}", settings);
+            match settings {
+                Ok(mut settings) => {
+                  if cli.log_level.is_none() {
+                    apply_log_level_setting(settings.log_level.as_deref());
+                  }
+                  debug!("Settings before adjustments: {:?}", settings);
+                  if install_args.install_all_prerequisites.is_none() { // if cli argument is not set
+                    settings.install_all_prerequisites = Some(true); // The non-interactive install will always install all prerequisites
+                  }
+                  if install_args.unattended {
+                    settings.non_interactive = Some(true);
+                  }
+                  debug!("Settings after adjustments: {:?}", settings);
+                  let time = std::time::SystemTime::now();
+                  if !do_not_track {
+                      track_cli_event("CLI installation started", Some(json!({
+                        "versions": format!("{:?}", settings.idf_versions),
+                      }))).await;
+                  }
+                    let failure_settings = install_args.unattended.then(|| settings.clone());
+                    let started = std::time::Instant::now();
+                    let install = async {
+                        if install_args.tui {
+                            tui::run_install(settings).await
+                        } else {
+                            wizard::run_wizzard_run(settings).await
+                        }
+                    };
+                    let result = match install_args.max_duration {
+                        Some(limit) => tokio::time::timeout(limit, install).await.unwrap_or_else(|_| {
+                            Err(EimError::new(
+                                ErrorKind::TimedOut,
+                                t!("unattended.timed_out", duration = format_eta(limit.as_secs())).to_string(),
+                            ))
+                        }),
+                        None => install.await,
+                    };
+                    if let (Err(err), Some(settings)) = (&result, &failure_settings) {
+                        let summary = idf_im_lib::unattended::FailureSummary::new(
+                            err,
+                            err.kind == ErrorKind::TimedOut,
+                            install_args.max_duration,
+                            started.elapsed(),
+                        );
+                        let dir = install_args
+                            .failure_dir
+                            .clone()
+                            .unwrap_or_else(idf_im_lib::install_report::get_reports_directory);
+                        match idf_im_lib::unattended::write_failure_artifacts(&dir, summary, settings) {
+                            Ok(path) => println!("{}", t!("unattended.summary_written", path = path.display())),
+                            Err(e) => error!("{}", t!("unattended.summary_failed", error = e.to_string())),
+                        }
+                    }
+                    match result {
+                        Ok(r) => {
+                            info!("{}", t!("install.wizard_result", r = "Ok".to_string()));
+                            info!("{}", t!("install.success"));
+                            info!("{}", t!("install.ready"));
+                            if !do_not_track {
+                              track_cli_event("CLI installation finished", Some(json!({
+                                "duration": format!("{:?}", time.elapsed().unwrap_or_default()),
+                              }))).await;
+                            }
+                            Ok(())
+                        }
+                        Err(err) => {
+                          if !do_not_track {
+                            track_cli_event("CLI installation failed", Some(json!({
+                              "duration": format!("{:?}", time.elapsed().unwrap_or_default()),
+                              "error": format!("{:?}", err),
+                            }))).await;
+                          }
+                            Err(anyhow::anyhow!(err))
+                        }
+                    }
+                }
+                Err(err) => Err(anyhow::anyhow!(err))
+            }
+        }
+        Commands::List => {
+            info!("{}", t!("list.title"));
+            match idf_im_lib::version_manager::get_esp_ide_config() {
+                Ok(config) => {
+                    if config.idf_installed.is_empty() {
+                        warn!("{}", t!("list.no_versions"));
+                        Ok(())
+                    } else {
+                        println!("{}", t!("list.installed_title"));
+                        for version in config.idf_installed {
+                            if version.id == config.idf_selected_id {
+                                println!("{}", t!("list.version_selected", name = version.name, path = version.path));
+                            } else {
+                                println!("{}", t!("list.version", name = version.name, path = version.path));
+                            }
+                            if let Some(idf_version) = &version.idf_version {
+                                println!("{}", t!("list.copy_of", version = idf_version));
+                            }
+                            if let Some(label) = &version.label {
+                                println!("{}", t!("list.label", label = label));
+                            }
+                            for plugin in idf_im_lib::plugins::plugins_of(&version.id) {
+                                println!("{}", t!("list.plugin", name = plugin.name, version = plugin.version));
+                            }
+                        }
+                        print_patch_update_notice().await;
+                        Ok(())
+                    }
+                }
+                Err(err) => {
+                    info!("{}", t!("list.no_versions"));
+                    debug!("Error: {}", err);
+                    Ok(())
+                }
+            }
+        }
+        Commands::Select { version } => {
+            if version.is_none() {
+                match idf_im_lib::version_manager::list_installed_versions() {
+                    Ok(versions) => {
+                        if versions.is_empty() {
+                            warn!("{}", t!("select.no_versions"));
+                            Ok(())
+                        } else {
+                            println!("{}", t!("select.available_title"));
+                            let options = versions.iter().map(|v| v.name.clone()).collect();
+                            match generic_select(&t!("select.prompt"), &options) {
+                                Ok(selected) => match select_idf_version(&selected) {
+                                    Ok(_) => {
+                                        println!("{}", t!("select.success", version = selected));
+                                        if let Some(selected) = get_selected_version() {
+                                          println!("{}", t!("wizard.separator.line"));
+                                          println!("{}", t!("cli.select.activation_instructions"));
+                                          println!("source {}",selected.activation_script );
+                                          println!("{}", t!("wizard.separator.line"));
+                                        } else {
+                                          warn!("{}", t!("select.unable_to_get_selected"));
+                                        }
+                                        Ok(())
+                                    }
+                                    Err(err) => Err(anyhow::anyhow!(err)),
+                                },
+                                Err(err) => Err(anyhow::anyhow!(err)),
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        error!("{}", t!("list.no_versions"));
+                        debug!("Error: {}", err);
+                        Err(anyhow::anyhow!(err))
+                    }
+                }
+            } else {
+                match select_idf_version(&version.clone().unwrap()) {
+                    Ok(_) => {
+                        info!("{}", t!("select.success", version = version.clone().unwrap()));
+                        if let Some(selected) = get_selected_version() {
+                          info!("{}", t!("wizard.separator.line"));
+                          info!("{}", t!("cli.select.activation_instructions"));
+                          info!("source {}",selected.activation_script );
+                          info!("{}", t!("wizard.separator.line"));
+                        } else {
+                          warn!("{}", t!("select.unable_to_get_selected"));
+                        }
+                        Ok(())
+                    }
+                    Err(err) => Err(anyhow::anyhow!(err)),
+                }
+            }
+        }
+        Commands::Label { version, label, clear } => {
+            if label.is_none() && !clear {
+                let config = idf_im_lib::version_manager::get_esp_ide_config()?;
+                let installation = config
+                    .idf_installed
+                    .iter()
+                    .find(|install| install.id == version || install.name == version)
+                    .ok_or_else(|| anyhow::anyhow!(t!("label.not_installed", version = version)))?;
+                match &installation.label {
+                    Some(label) => println!("{}", label),
+                    None => println!("{}", t!("label.none", version = version)),
+                }
+                return Ok(());
+            }
+            idf_im_lib::version_manager::set_installation_label(&version, label.clone())?;
+            match label {
+                Some(label) => println!("{}", t!("label.success", version = version, label = label)),
+                None => println!("{}", t!("label.cleared", version = version)),
+            }
+            Ok(())
+        }
+        Commands::Rename { version, new_name } => {
+            if version.is_none() {
+                match idf_im_lib::version_manager::list_installed_versions() {
+                    Ok(versions) => {
+                        if versions.is_empty() {
+                            warn!("{}", t!("rename.no_versions"));
+                            Ok(())
+                        } else {
+                            let options = versions.iter().map(|v| v.name.clone()).collect();
+                            let version = match helpers::generic_select(
+                                &t!("rename.prompt"),
+                                &options,
+                            ) {
+                                Ok(selected) => selected,
+                                Err(err) => {
+                                    error!("Error: {}", err);
+                                    return Err(anyhow::anyhow!(err));
+                                }
+                            };
+
+                            let new_name = match generic_input(
+                                &t!("rename.new_name_prompt"),
+                                &t!("rename.new_name_required"),
+                                "",
+                            ) {
+                                Ok(name) => {
+                                    if name.is_empty() {
+                                        warn!("{}", t!("rename.using_default"));
+                                        version.clone()
+                                    } else {
+                                        name
+                                    }
+                                }
+                                Err(err) => {
+                                    error!("Error: {}", err);
+                                    version.clone()
+                                }
+                            };
+                            match idf_im_lib::version_manager::rename_idf_version(
+                                &version, new_name,
+                            ) {
+                                Ok(_) => {
+                                    println!("{}", t!("rename.success"));
+                                    Ok(())
+                                }
+                                Err(err) => Err(anyhow::anyhow!(err)),
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        debug!("Error: {}", err);
+                        error!("{}", t!("list.no_versions"));
+                        Err(anyhow::anyhow!(err))
+                    }
+                }
+            } else if new_name.is_none() {
+                let new_name =
+                    match generic_input(&t!("rename.new_name_prompt"), &t!("rename.new_name_required"), "") {
+                        Ok(name) => {
+                            if name.is_empty() {
+                                warn!("{}", t!("rename.using_default"));
+                                version.clone().unwrap()
+                            } else {
+                                name
+                            }
+                        }
+                        Err(err) => {
+                            error!("Error: {}", err);
+                            version.clone().unwrap()
+                        }
+                    };
+                match idf_im_lib::version_manager::rename_idf_version(
+                    &version.clone().unwrap(),
+                    new_name,
+                ) {
+                    Ok(_) => {
+                        println!("{}", t!("rename.success"));
+                        Ok(())
+                    }
+                    Err(err) => Err(anyhow::anyhow!(err)),
+                }
+            } else {
+                match idf_im_lib::version_manager::rename_idf_version(
+                    &version.clone().unwrap(),
+                    new_name.clone().unwrap(),
+                ) {
+                    Ok(_) => {
+                        println!("{}", t!("rename.success"));
+                        Ok(())
+                    }
+                    Err(err) => Err(anyhow::anyhow!(err)),
+                }
+            }
+        }
+        Commands::Discover => {
+            // TODO:Implement version discovery
+            unimplemented!("Version discovery not implemented yet");
+            println!("{}", t!("discover.title"));
+            let idf_dirs = idf_im_lib::version_manager::find_esp_idf_folders("/");
+            for dir in idf_dirs {
+                println!("{}", t!("discover.found", dir = dir));
+            }
+            Ok(())
+        }
+        Commands::Import { path, install_path } => match path {
+            Some(tarball) if idf_im_lib::snapshot::is_tarball(Path::new(&tarball)) => {
+                let mut settings = Settings::default();
+                if let Some(install_path) = install_path {
+                    settings.path = Some(idf_im_lib::expand_tilde(&PathBuf::from(install_path)));
+                }
+                let tarball = idf_im_lib::expand_tilde(&PathBuf::from(tarball));
+                println!("{}", t!("import.tarball", path = tarball.display()));
+                let installation = idf_im_lib::snapshot::import_tarball(&tarball, &settings)?;
+                println!("{}", t!("snapshot.restored", name = installation.name, path = installation.path));
+                println!("source {}", installation.activation_script);
+                Ok(())
+            }
+            Some(config_file) => {
+                info!("{}", t!("import.using_config", config = format!("{:?}", config_file)));
+                match idf_im_lib::utils::parse_tool_set_config(&config_file) {
+                    Ok(_) => {
+                        info!("{}", t!("import.success"));
+                        Ok(())
+                    }
+                    Err(err) => Err(anyhow::anyhow!(err)),
+                }
+            }
+            None => {
+                info!("{}", t!("import.no_config"));
+                Ok(())
+            }
+        },
+        Commands::Remove { version } => {
+            // todo: add spinner
+            if version.is_none() {
+                match idf_im_lib::version_manager::list_installed_versions() {
+                    Ok(versions) => {
+                        if versions.is_empty() {
+                            info!("{}", t!("remove.no_versions"));
+                            Ok(())
+                        } else {
+                            println!("{}", t!("remove.available_title"));
+                            let options = versions.iter().map(|v| v.name.clone()).collect();
+                            match generic_select(&t!("remove.prompt"), &options) {
+                                Ok(selected) => match remove_single_idf_version(&selected, false) {
+                                    Ok(_) => {
+                                        info!("{}", t!("remove.success", version = selected));
+                                        Ok(())
+                                    }
+                                    Err(err) => Err(anyhow::anyhow!(err)),
+                                },
+                                Err(err) => Err(anyhow::anyhow!(err)),
+                            }
+                        }
+                    }
+                    Err(err) => Err(anyhow::anyhow!(err)),
+                }
+            } else {
+                match remove_single_idf_version(&version.clone().unwrap(), false) {
+                    Ok(_) => {
+                        println!("{}", t!("remove.success", version = version.clone().unwrap()));
+                        Ok(())
+                    }
+                    Err(err) => Err(anyhow::anyhow!(err)),
+                }
+            }
+        }
+        Commands::Cleanup => {
+            let remnants = idf_im_lib::remnants::find_remnants(idf_im_lib::remnants::DEFAULT_MIN_AGE);
+            if remnants.is_empty() {
+                println!("{}", t!("remnants.none"));
+            } else {
+                offer_remnant_cleanup(&cli, &command);
+            }
+            Ok(())
+        }
+        Commands::Purge => {
+            // Todo: offer to run discovery first
+            println!("{}", t!("purge.title"));
+            match idf_im_lib::version_manager::list_installed_versions() {
+                Ok(versions) => {
+                    if versions.is_empty() {
+                        println!("{}", t!("purge.no_versions"));
+                        Ok(())
+                    } else {
+                        let mut failed = false;
+                        for version in versions {
+                            info!("{}", t!("purge.removing", version = version.name));
+                            match remove_single_idf_version(&version.name, false) {
+                                Ok(_) => {
+                                    info!("{}", t!("purge.removed", version = version.name));
+                                }
+                                Err(err) => {
+                                  error!("{}", t!("purge.failed", version = version.name, error = err));
+                                  failed = true;
+                                }
+                            }
+                        }
+                        if failed {
+                            return Err(anyhow::anyhow!(t!("purge.some_failed")));
+                        } else {
+                            info!("{}", t!("purge.all_success"));
+                        }
+                        Ok(())
+                    }
+                }
+                Err(err) => Err(anyhow::anyhow!(err)),
+            }
+        }
+        Commands::Wizard(install_args) => {
+            info!("{}", t!("wizard.title"));
+            let settings = Settings::new(
+                install_args.config.clone(),
+                install_args.clone().into_iter(),
+            );
+            match settings {
+                Ok(mut settings) => {
+                    if cli.log_level.is_none() {
+                        apply_log_level_setting(settings.log_level.as_deref());
+                    }
+                    settings.non_interactive = Some(false);
+                    let time = std::time::SystemTime::now();
+                    if !do_not_track {
+                      track_cli_event("CLI wizard started", Some(json!({}))).await;
+                    }
+                    let result = if install_args.tui {
+                        tui::run_install(settings).await
+                    } else {
+                        wizard::run_wizzard_run(settings).await
+                    };
+                    match result {
+                        Ok(r) => {
+                            info!("{}", t!("install.wizard_result"));
+                            info!("{}", t!("install.success"));
+                            info!("{}", t!("install.ready"));
+                            if !do_not_track {
+                              track_cli_event("CLI wizard finished", Some(json!({
+                                "duration": format!("{:?}", time.elapsed().unwrap_or_default()),
+                              }))).await;
+                            }
+                            Ok(())
+                        }
+                        Err(err) => {
+                          if !do_not_track {
+                            track_cli_event("CLI wizard failed", Some(json!({
+                              "duration": format!("{:?}", time.elapsed().unwrap_or_default()),
+                              "error": format!("{:?}", err),
+                            }))).await;
+                          }
+                          Err(anyhow::anyhow!(err))
+                        }
+                    }
+                }
+                Err(err) => Err(anyhow::anyhow!(err)),
+            }
+        }
+        Commands::Fix { path } => {
+          let path_to_fix = if path.is_some() {
+              // If a path is provided, fix the IDF installation at that path
+              let path = path.unwrap();
+               if is_valid_idf_directory(&path) {
+                PathBuf::from(path)
+               } else {
+                error!("{}", t!("fix.invalid_directory", path = path));
+                return Err(anyhow::anyhow!(t!("fix.invalid_directory", path = path)));
+               }
+            } else {
+              match idf_im_lib::version_manager::list_installed_versions() {
+                Ok(versions) => {
+                  if versions.is_empty() {
+                      warn!("{}", t!("fix.no_versions"));
+                      return Ok(());
+                  } else {
+                    let options = versions.iter().map(|v| v.path.clone()).collect();
+                    let version_path = match helpers::generic_select(
+                        &t!("fix.prompt"),
+                        &options,
+                    ) {
+                        Ok(selected) => selected,
+                        Err(err) => {
+                            error!("Error: {}", err);
+                            return Err(anyhow::anyhow!(err));
+                        }
+                    };
+                    PathBuf::from(version_path)
+                  }
+                }
+                Err(err) => {
+                  debug!("Error: {}", err);
+                  return Err(anyhow::anyhow!(t!("fix.no_versions_found")));
+                }
+            }
+          };
+          info!("{}", t!("fix.fixing", path = path_to_fix.display()));
+          // The fix logic is just instalation with use of existing repository
+          let settings = prepare_settings_for_fix_idf_installation(path_to_fix.clone()).await?;
+          let result = wizard::run_wizzard_run(settings).await;
+          match result {
+            Ok(r) => {
+              info!("{}", t!("fix.result"));
+              info!("{}", t!("fix.success", path = path_to_fix.display()));
+            }
+            Err(err) => {
+              error!("{}", t!("fix.failed", error = err));
+              return Err(anyhow::anyhow!(err));
+            }
+          }
+          info!("{}", t!("fix.ready"));
+          Ok(())
+        }
+        Commands::Gui(_install_args) => {
+            let Some(launch_gui) = gui else {
+                return run_gui_binary();
+            };
+            let log_level = match cli.verbose {
+                0 => LevelFilter::Info,
+                1 => LevelFilter::Debug,
+                _ => LevelFilter::Trace,
+            };
+            launch_gui(log_level, cli.log_level.clone());
+            Ok(())
+        }
+        #[cfg(feature = "http-server")]
+        Commands::Serve { http: Some(address), token, allow_remote_plaintext, .. } => {
+            serve_http::serve_http(&address, &server_token(token), allow_remote_plaintext).await
+        }
+        Commands::Serve { socket: Some(socket), token, .. } => {
+            // only a TCP port asks for it, the socket file is only accessible to its owner
+            let token = if socket.parse::<u16>().is_ok() { server_token(token) } else { String::new() };
+            serve::serve(&socket, &token).await
+        }
+        Commands::Serve { .. } => Err(anyhow::anyhow!(t!("serve.no_address"))),
+        Commands::Upgrade { version, track } => {
+            if let Some(alias) = track {
+                let alias = VersionAlias::from_name(&alias).ok_or_else(|| {
+                    EimError::new(ErrorKind::Usage, t!("upgrade.unknown_alias", alias = alias).to_string())
+                })?;
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                idf_im_lib::version_tracking::track(&installation.id, alias)?;
+                println!("{}", t!("upgrade.tracking", name = installation.name, alias = alias.name()));
+            }
+            let tracked: Vec<_> = idf_im_lib::version_tracking::list_tracked()?
+                .into_iter()
+                .filter(|t| match &version {
+                    Some(version) => idf_im_lib::version_manager::find_installation(Some(version))
+                        .is_ok_and(|installation| installation.id == t.installation_id),
+                    None => true,
+                })
+                .collect();
+            if tracked.is_empty() {
+                warn!("{}", t!("upgrade.nothing_tracked"));
+                return Ok(());
+            }
+            for tracked in tracked {
+                upgrade_tracked_installation(&tracked).await?;
+            }
+            Ok(())
+        }
+        Commands::Which { tool, version } => {
+            let installation = match version {
+                Some(version) => idf_im_lib::version_manager::find_installation(Some(&version))?,
+                None => idf_im_lib::which::active_installation()?,
+            };
+            let environment = idf_im_lib::ide_export::InstallationEnvironment::from_installation(&installation)?;
+            let tools: Vec<String> = match tool {
+                Some(tool) => vec![tool],
+                None => idf_im_lib::which::DEFAULT_TOOLS.iter().map(|t| t.to_string()).collect(),
+            };
+            let locations: Vec<_> = tools
+                .iter()
+                .map(|tool| idf_im_lib::which::locate(&environment, tool))
+                .collect();
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&json!({ "installation": installation.name, "tools": locations }))?);
+                return Ok(());
+            }
+            println!("{}", t!("which.installation", name = installation.name, path = installation.path));
+            for location in &locations {
+                let Some(path) = &location.installation else {
+                    println!("{}", t!("which.missing", tool = location.tool));
+                    continue;
+                };
+                println!("{}", t!("which.found", tool = location.tool, path = path.display()));
+                match &location.shell {
+                    _ if location.shell_matches() => {}
+                    Some(shell) => println!("{}", t!("which.shell_differs", path = shell.display())),
+                    None => println!("{}", t!("which.shell_missing")),
+                }
+            }
+            if locations.iter().any(|l| l.installation.is_some() && !l.shell_matches()) {
+                println!("{}", t!("which.activate_hint", name = installation.name));
+            }
+            Ok(())
+        }
+        Commands::Doctor { version } => {
+            let installation = match version {
+                Some(version) => idf_im_lib::version_manager::find_installation(Some(&version))?,
+                None => idf_im_lib::which::active_installation()?,
+            };
+            let health = idf_im_lib::health_check::check_installation(&installation);
+            let conflicts = idf_im_lib::path_conflicts::find_conflicts(&installation)?;
+            if cli.json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&json!({ "installation": installation.name, "problems": health.problems, "path_conflicts": conflicts }))?
+                );
+            } else {
+                print_doctor(&installation.name, &health.problems, &conflicts);
+            }
+            if health.problems.is_empty() && conflicts.is_empty() {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!(t!("doctor.failed", name = installation.name)))
+            }
+        }
+        Commands::Status { config } => {
+            let config = config.map(PathBuf::from).or_else(|| {
+                Some(PathBuf::from("eim_config.toml")).filter(|path| path.exists())
+            });
+            let settings = Settings::new(config, std::iter::empty())?;
+            let status = idf_im_lib::status::collect(&settings);
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+                return Ok(());
+            }
+            print_status(&status);
+            Ok(())
+        }
+        Commands::Env { command } => match command {
+            EnvCommands::Diff { version } => {
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                let diff = idf_im_lib::env_diff::diff_current_environment(&installation)?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&diff)?);
+                } else {
+                    print_env_diff(&diff);
+                }
+                Ok(())
+            }
+        },
+        Commands::Mirrors { command } => match command {
+            MirrorsCommands::Status { config } => {
+                let config = config.map(PathBuf::from).or_else(|| {
+                    Some(PathBuf::from("eim_config.toml")).filter(|path| path.exists())
+                });
+                let settings = Settings::new(config, std::iter::empty())?;
+                if !cli.json {
+                    println!("{}", t!("mirrors.checking"));
+                }
+                let reports = idf_im_lib::mirror_health::check_mirrors(&settings).await;
+                let diagnosis = idf_im_lib::mirror_health::diagnose(&reports);
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&json!({ "mirrors": reports, "diagnosis": diagnosis }))?);
+                } else {
+                    print_mirrors(&reports, diagnosis);
+                }
+                match diagnosis {
+                    idf_im_lib::mirror_health::Diagnosis::NetworkProblem => {
+                        Err(EimError::new(ErrorKind::Network, t!("mirrors.diagnosis.network").to_string()).into())
+                    }
+                    _ => Ok(()),
+                }
+            }
+        },
+        Commands::CheckUpdates { mute, unmute, disable, enable } => {
+            if let Some(version) = &mute {
+                update_notifications::mute(version)?;
+                println!("{}", t!("check_updates.muted", version = version));
+            }
+            if let Some(version) = &unmute {
+                update_notifications::unmute(version)?;
+                println!("{}", t!("check_updates.unmuted", version = version));
+            }
+            if disable || enable {
+                update_notifications::set_enabled(enable)?;
+                println!("{}", if enable { t!("check_updates.enabled") } else { t!("check_updates.disabled") });
+            }
+            if mute.is_some() || unmute.is_some() || disable || enable {
+                return Ok(());
+            }
+            let updates = update_notifications::check_for_updates()
+                .await
+                .map_err(|err| EimError::new(ErrorKind::Network, err.to_string()))?;
+            if updates.is_empty() {
+                println!("{}", t!("check_updates.up_to_date"));
+            }
+            for update in updates {
+                println!(
+                    "{}",
+                    t!("check_updates.available", name = update.installation_name, installed = update.installed, available = update.available)
+                );
+            }
+            Ok(())
+        }
+        Commands::Maintenance { command } => match command {
+            MaintenanceCommands::Enable { daily, weekly: _ } => {
+                let schedule = if daily {
+                    idf_im_lib::maintenance::Schedule::Daily
+                } else {
+                    idf_im_lib::maintenance::Schedule::Weekly
+                };
+                idf_im_lib::maintenance::enable(schedule)?;
+                println!("{}", t!("maintenance.enabled", schedule = schedule_name(schedule)));
+                Ok(())
+            }
+            MaintenanceCommands::Disable => {
+                idf_im_lib::maintenance::disable()?;
+                println!("{}", t!("maintenance.disabled"));
+                Ok(())
+            }
+            MaintenanceCommands::Status => {
+                let state = idf_im_lib::maintenance::load_state()?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&state)?);
+                    return Ok(());
+                }
+                match state.schedule {
+                    Some(schedule) => println!("{}", t!("maintenance.scheduled", schedule = schedule_name(schedule))),
+                    None => println!("{}", t!("maintenance.not_scheduled")),
+                }
+                match (&state.last_run, &state.last_report) {
+                    (Some(last_run), Some(report)) => {
+                        println!("{}", t!("maintenance.last_run", time = format_timestamp(&last_run.to_rfc3339())));
+                        print_maintenance_report(report);
+                    }
+                    _ => println!("{}", t!("maintenance.never_run")),
+                }
+                Ok(())
+            }
+            MaintenanceCommands::Run => {
+                let report = idf_im_lib::maintenance::run().await?;
+                if report.needs_action() {
+                    idf_im_lib::maintenance::notify(
+                        &t!("maintenance.notification.title"),
+                        &maintenance_notification_body(&report),
+                    );
+                }
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                } else {
+                    print_maintenance_report(&report);
+                }
+                Ok(())
+            }
+        },
+        Commands::Wait => follow_running_install(false).await,
+        Commands::Attach => follow_running_install(true).await,
+        Commands::Info { version, targets, size } => {
+            let version = idf_versions::resolve_version_aliases(vec![version])
+                .await
+                .map_err(|err| EimError::new(ErrorKind::Network, err))?
+                .remove(0);
+            if size {
+                let config = Some(PathBuf::from("eim_config.toml")).filter(|path| path.exists());
+                let settings = Settings::new(config, std::iter::empty())?;
+                let estimate = idf_im_lib::size_estimate::estimate(&settings, &version)
+                    .await
+                    .map_err(|err| EimError::new(ErrorKind::Network, err.to_string()))?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&estimate)?);
+                } else {
+                    print_size_estimate(&estimate);
+                }
+                return Ok(());
+            }
+            if targets {
+                let config = Some(PathBuf::from("eim_config.toml")).filter(|path| path.exists());
+                let settings = Settings::new(config, std::iter::empty())?;
+                let matrix = idf_im_lib::support_matrix::get_support_matrix(&version, settings.idf_mirror.as_deref())
+                    .await
+                    .map_err(|err| EimError::new(ErrorKind::Network, err.to_string()))?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&matrix)?);
+                } else {
+                    print_support_matrix(&matrix);
+                }
+                return Ok(());
+            }
+            let info = idf_im_lib::release_notes::get_release_info(&version)
+                .await
+                .map_err(|err| EimError::new(ErrorKind::Network, err.to_string()))?;
+            helpers::print_release_info(&info, None);
+            Ok(())
+        }
+        Commands::Activate { version } => {
+            // stdout is evaluated by the shell, so nothing but the command is printed
+            let installation = find_installation_for_current_dir(version.as_deref())?;
+            println!("{}", idf_im_lib::project_install::activation_command(&installation));
+            Ok(())
+        }
+        Commands::Shell { version, shell } => {
+            let installation = find_installation_for_current_dir(version.as_deref())?;
+            let environment = idf_im_lib::ide_export::InstallationEnvironment::from_installation(&installation)?;
+            if let Ok(active) = std::env::var(idf_im_lib::shell::SHELL_MARKER_VARIABLE) {
+                warn!("{}", t!("shell.nested", name = active));
+            }
+            let program = match shell {
+                Some(shell) => {
+                    let path_var = std::env::var_os("PATH").unwrap_or_default();
+                    idf_im_lib::which::find_in_path(&shell, &path_var).unwrap_or_else(|| PathBuf::from(&shell))
+                }
+                None => idf_im_lib::shell::default_shell(),
+            };
+            let launch = idf_im_lib::shell::prepare(&environment, &program)?;
+            println!("{}", t!("shell.entering", name = installation.name, shell = program.display()));
+            let code = launch.run()?;
+            println!("{}", t!("shell.left", name = installation.name));
+            debug!("The shell exited with {}", code);
+            Ok(())
+        }
+        Commands::Export { command } => match command {
+            ExportCommands::Cmake { version, out, target } => {
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                let environment = idf_im_lib::ide_export::InstallationEnvironment::from_installation(&installation)?;
+                let out_dir = idf_im_lib::expand_tilde(&PathBuf::from(out));
+                for path in idf_im_lib::ide_export::export_cmake(&environment, &target, &out_dir)? {
+                    println!("{}", t!("export.written", path = path.display()));
+                }
+                println!("{}", t!("export.cmake.hint", preset = format!("esp-idf-{}-{}", environment.name, target)));
+                Ok(())
+            }
+            ExportCommands::Eclipse { version, out, workspace } => {
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                let environment = idf_im_lib::ide_export::InstallationEnvironment::from_installation(&installation)?;
+                let out_dir = idf_im_lib::expand_tilde(&PathBuf::from(out));
+                let workspace = workspace.map(|w| idf_im_lib::expand_tilde(&PathBuf::from(w)));
+                for path in idf_im_lib::ide_export::export_eclipse(&environment, &out_dir, workspace.as_deref())? {
+                    println!("{}", t!("export.written", path = path.display()));
+                }
+                if workspace.is_none() {
+                    println!("{}", t!("export.eclipse.hint"));
+                }
+                Ok(())
+            }
+            ExportCommands::Devcontainer { version, out, mount_host } => {
+                let out_dir = idf_im_lib::expand_tilde(&PathBuf::from(out));
+                let written = if mount_host {
+                    let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                    let environment = idf_im_lib::ide_export::InstallationEnvironment::from_installation(&installation)?;
+                    idf_im_lib::ide_export::export_devcontainer(&environment.idf_version, Some(&environment), &out_dir)?
+                } else {
+                    let idf_version = match version {
+                        Some(version) => version,
+                        None => {
+                            let installation = idf_im_lib::version_manager::find_installation(None)?;
+                            installation.idf_version.unwrap_or(installation.name)
+                        }
+                    };
+                    idf_im_lib::ide_export::export_devcontainer(&idf_version, None, &out_dir)?
+                };
+                for path in written {
+                    println!("{}", t!("export.written", path = path.display()));
+                }
+                Ok(())
+            }
+            ExportCommands::GithubEnv { version } => {
+                let (Ok(github_env), Ok(github_path)) = (std::env::var("GITHUB_ENV"), std::env::var("GITHUB_PATH")) else {
+                    return Err(anyhow::anyhow!(t!("export.github.not_in_actions")));
+                };
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                let environment = idf_im_lib::ide_export::InstallationEnvironment::from_installation(&installation)?;
+                idf_im_lib::ide_export::export_github_env(&environment, &PathBuf::from(github_env), &PathBuf::from(github_path))?;
+                println!("{}", t!("export.github.done", version = installation.name));
+                Ok(())
+            }
+            ExportCommands::Dotenv { version, out } => {
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                let environment = idf_im_lib::ide_export::InstallationEnvironment::from_installation(&installation)?;
+                let path = idf_im_lib::ide_export::export_dotenv(&environment, &idf_im_lib::expand_tilde(&PathBuf::from(out)))?;
+                println!("{}", t!("export.written", path = path.display()));
+                Ok(())
+            }
+            ExportCommands::Direnv { version, out } => {
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                let environment = idf_im_lib::ide_export::InstallationEnvironment::from_installation(&installation)?;
+                let path = idf_im_lib::ide_export::export_direnv(&environment, &idf_im_lib::expand_tilde(&PathBuf::from(out)))?;
+                println!("{}", t!("export.written", path = path.display()));
+                println!("{}", t!("export.direnv.hint"));
+                Ok(())
+            }
+            ExportCommands::Tarball { version, out } => {
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                let out = match out {
+                    Some(out) => idf_im_lib::expand_tilde(&PathBuf::from(out)),
+                    None => PathBuf::from(idf_im_lib::snapshot::tarball_file_name(&installation.name)?),
+                };
+                println!("{}", t!("snapshot.creating", name = installation.name));
+                let (manifest, sha256) = idf_im_lib::snapshot::export_tarball(&installation, &out)?;
+                println!("{}", t!("snapshot.created", name = manifest.name, path = out.display(), tools = manifest.tool_dirs.len()));
+                println!("{}", t!("export.tarball.hint", sha256 = sha256, path = out.display()));
+                Ok(())
+            }
+        },
+        Commands::Devices => {
+            let devices = idf_im_lib::serial_devices::list_serial_devices()?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&devices)?);
+                return Ok(());
+            }
+            if devices.is_empty() {
+                println!("{}", t!("devices.none"));
+            }
+            for device in &devices {
+                let ids = match (device.vid, device.pid) {
+                    (Some(vid), Some(pid)) => format!(" [{:04x}:{:04x}]", vid, pid),
+                    _ => String::new(),
+                };
+                let port = device.port.clone().unwrap_or_else(|| t!("devices.no_port").to_string());
+                let status = if device.is_usable() {
+                    t!("devices.ok")
+                } else if !device.driver_ok {
+                    t!("devices.driver_missing")
+                } else {
+                    t!("devices.no_access")
+                };
+                let marker = if device.is_espressif { "*" } else { " " };
+                println!("{} {:<24} {}{} - {}", marker, port, device.description, ids, status);
+                if let Some(hint) = &device.hint {
+                    println!("    {}", hint);
+                }
+            }
+            if devices.iter().any(|d| d.is_espressif) {
+                println!("{}", t!("devices.legend"));
+            }
+            Ok(())
+        }
+        Commands::Verify { version, flash: false, .. } => {
+            let result = serve::method_verify(&json!({ "version": version }))
+                .map_err(|e| anyhow::anyhow!(e.message))?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            }
+            let mut all_ok = true;
+            for installation in result["installations"].as_array().cloned().unwrap_or_default() {
+                let ok = installation["ok"].as_bool().unwrap_or(false);
+                all_ok &= ok;
+                if cli.json {
+                    continue;
+                }
+                let name = installation["name"].as_str().unwrap_or_default();
+                if ok {
+                    println!("{}", t!("verify.ok", version = name));
+                    continue;
+                }
+                println!("{}", t!("verify.broken", version = name));
+                for tool in installation["tools"].as_array().cloned().unwrap_or_default() {
+                    if tool["result"]["status"] != "ok" {
+                        println!("    {} {}: {}", tool["tool"].as_str().unwrap_or_default(), tool["version"].as_str().unwrap_or_default(), tool["result"]["status"].as_str().unwrap_or_default());
+                    }
+                }
+            }
+            if all_ok {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!(t!("verify.failed")))
+            }
+        }
+        Commands::Verify { version, flash: true, port, target } => {
+            let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+            let report = idf_im_lib::flash_test::run_flash_test(&installation, port.as_deref(), target.as_deref(), |step| {
+                let message = match step {
+                    FlashTestStep::DetectChip => t!("verify.flash.step.detect_chip"),
+                    FlashTestStep::Build => t!("verify.flash.step.build"),
+                    FlashTestStep::Flash => t!("verify.flash.step.flash"),
+                    FlashTestStep::ReadBootLog => t!("verify.flash.step.read_boot_log"),
+                };
+                info!("{}", message);
+            })?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                println!("{}", report.boot_log.trim_end());
+            }
+            if report.success {
+                println!("{}", t!("verify.flash.success", port = report.port, target = report.target));
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!(t!("verify.flash.no_boot", port = report.port)))
+            }
+        }
+        Commands::Tools { command } => match command {
+            ToolsCommands::Install { tool, version, mirror } => {
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                println!("{}", t!("tools.install.start", tool = tool, version = installation.name));
+                let installed = idf_im_lib::qemu::install_qemu(&installation, mirror.as_deref(), None).await?;
+                for (name, tool_version) in installed {
+                    println!("{}", t!("tools.install.installed", tool = name, version = tool_version));
+                }
+                println!("{}", t!("tools.install.done"));
+                Ok(())
+            }
+        },
+        Commands::Toolchain { command } => match command {
+            ToolchainCommands::Register { name, path, targets } => {
+                let path = idf_im_lib::expand_tilde(&PathBuf::from(path));
+                let toolchain = idf_im_lib::custom_toolchains::register(&name, &path, &targets)?;
+                println!(
+                    "{}",
+                    t!("toolchain.registered", name = toolchain.name, version = toolchain.version, targets = toolchain.targets.join(", "))
+                );
+                println!("{}", t!("toolchain.registered_hint"));
+                Ok(())
+            }
+            ToolchainCommands::Remove { name } => {
+                idf_im_lib::custom_toolchains::unregister(&name)?;
+                println!("{}", t!("toolchain.removed", name = name));
+                Ok(())
+            }
+            ToolchainCommands::List => {
+                let toolchains = idf_im_lib::custom_toolchains::list_toolchains()?;
+                if toolchains.is_empty() {
+                    println!("{}", t!("toolchain.none"));
+                }
+                for toolchain in toolchains {
+                    println!(
+                        "{}",
+                        t!("toolchain.entry", name = toolchain.name, version = toolchain.version, targets = toolchain.targets.join(", "), path = toolchain.bin_path)
+                    );
+                }
+                Ok(())
+            }
+        },
+        Commands::Snapshot { command } => match command {
+            SnapshotCommands::Create { version, out } => {
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                let out = idf_im_lib::expand_tilde(&PathBuf::from(out));
+                println!("{}", t!("snapshot.creating", name = installation.name));
+                let manifest = idf_im_lib::snapshot::create_snapshot(&installation, &out)?;
+                println!("{}", t!("snapshot.created", name = manifest.name, path = out.display(), tools = manifest.tool_dirs.len()));
+                Ok(())
+            }
+            SnapshotCommands::Restore { archive, path, config } => {
+                let mut settings = match config {
+                    Some(config) => Settings::new(Some(PathBuf::from(config)), std::iter::empty())?,
+                    None => Settings::default(),
+                };
+                if let Some(path) = path {
+                    settings.path = Some(idf_im_lib::expand_tilde(&PathBuf::from(path)));
+                }
+                let archive = idf_im_lib::expand_tilde(&PathBuf::from(archive));
+                println!("{}", t!("snapshot.restoring", path = archive.display()));
+                let installation = idf_im_lib::snapshot::restore_snapshot(&archive, &settings)?;
+                println!("{}", t!("snapshot.restored", name = installation.name, path = installation.path));
+                println!("source {}", installation.activation_script);
+                Ok(())
+            }
+        },
+        Commands::Plugin { command } => match command {
+            PluginCommands::Add { source, version } => {
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                let manifest = idf_im_lib::plugins::load_manifest(&source).await?;
+                println!("{}", t!("plugin.installing", name = manifest.name, version = manifest.version, idf = installation.name));
+                let plugin = idf_im_lib::plugins::install_plugin(&installation, &manifest).await?;
+                println!("{}", t!("plugin.installed", name = plugin.name, path = plugin.path));
+                Ok(())
+            }
+            PluginCommands::Remove { name, version } => {
+                let installation = idf_im_lib::version_manager::find_installation(version.as_deref())?;
+                idf_im_lib::plugins::uninstall_plugin(&installation, &name)?;
+                println!("{}", t!("plugin.removed", name = name, idf = installation.name));
+                Ok(())
+            }
+            PluginCommands::List => {
+                let plugins = idf_im_lib::plugins::list_plugins()?;
+                if plugins.is_empty() {
+                    println!("{}", t!("plugin.none"));
+                }
+                let installations = idf_im_lib::version_manager::list_installed_versions().unwrap_or_default();
+                for plugin in plugins {
+                    let idf = installations
+                        .iter()
+                        .find(|i| i.id == plugin.installation_id)
+                        .map(|i| i.name.clone())
+                        .unwrap_or(plugin.installation_id.clone());
+                    println!("{}", t!("plugin.entry", name = plugin.name, version = plugin.version, idf = idf));
+                }
+                Ok(())
+            }
+        },
+        Commands::Credentials { command } => match command {
+            CredentialsCommands::Set { kind, host } => {
+                let credential = credential_from_args(&kind, host);
+                let secret = if cli.stdin_password {
+                    idf_im_lib::credentials::read_secret_from_stdin()?
+                } else {
+                    helpers::generic_password("credentials.prompt")?
+                };
+                idf_im_lib::credentials::store_secret(&credential, &secret)?;
+                println!("{}", t!("credentials.stored", account = credential.account()));
+                Ok(())
+            }
+            CredentialsCommands::Remove { kind, host } => {
+                let credential = credential_from_args(&kind, host);
+                idf_im_lib::credentials::delete_secret(&credential)?;
+                println!("{}", t!("credentials.removed", account = credential.account()));
+                Ok(())
+            }
+        },
+        Commands::Migrate {
+            dry_run,
+            yes,
+            uninstall_legacy,
+        } => migrate_legacy_installations(dry_run, yes, uninstall_legacy),
+        Commands::Dedupe { dry_run } => {
+            let tool_dirs = idf_im_lib::dedupe::installed_tool_dirs()?;
+            if tool_dirs.len() < 2 {
+                println!("{}", t!("dedupe.nothing_to_do"));
+                return Ok(());
+            }
+            info!("{}", t!("dedupe.scanning", count = tool_dirs.len()));
+            let report = idf_im_lib::dedupe::dedupe_tool_dirs(&tool_dirs, dry_run)?;
+            let saved = format_size(report.bytes_saved);
+            if dry_run {
+                println!("{}", t!("dedupe.dry_run_result", files = report.files_linked, size = saved));
+            } else {
+                println!("{}", t!("dedupe.result", files = report.files_linked, size = saved));
+            }
+            Ok(())
+        }
+        Commands::Prune { dry_run } => {
+            let unused = idf_im_lib::prune::find_unused_tools()?;
+            if unused.is_empty() {
+                println!("{}", t!("prune.nothing_to_do"));
+                return Ok(());
+            }
+            for tool in &unused {
+                println!("{}", t!("prune.entry", tool = tool.tool, version = tool.version, size = format_size(tool.size)));
+            }
+            if dry_run {
+                let total = format_size(idf_im_lib::prune::total_size(&unused));
+                println!("{}", t!("prune.dry_run_result", count = unused.len(), size = total));
+            } else {
+                let reclaimed = idf_im_lib::prune::remove_unused_tools(&unused);
+                println!("{}", t!("prune.result", count = unused.len(), size = format_size(reclaimed)));
+            }
+            Ok(())
+        }
+        Commands::InstallDrivers => {
+          match std::env::consts::OS {
+            "windows" => {
+
+              info!("{}", t!("drivers.installing"));
+              if let Err(err) = idf_im_lib::install_drivers().await {
+                error!("{}", t!("drivers.failed", error = err));
+                return Err(anyhow::anyhow!(err));
+              }
+              info!("{}", t!("drivers.success"));
+              Ok(())
+            }
+            _ => {
+              return Err(anyhow::anyhow!(t!("drivers.windows_only")));
+            }
+          }
+        }
+        Commands::Drivers { command } => {
+          if std::env::consts::OS != "windows" {
+            return Err(anyhow::anyhow!(t!("drivers.windows_only")));
+          }
+          match command {
+            DriversCommands::Detect => {
+              let devices = idf_im_lib::drivers::detect_usb_devices()?;
+              if devices.is_empty() {
+                info!("{}", t!("drivers.detect.none"));
+              }
+              for device in devices {
+                if device.has_driver {
+                  println!("{}", t!("drivers.detect.ok", name = device.friendly_name, driver = device.driver));
+                } else {
+                  println!("{}", t!("drivers.detect.missing", name = device.friendly_name, driver = device.driver));
+                }
+              }
+              Ok(())
+            }
+            DriversCommands::Install { missing_only } => {
+              prompts::install_missing_drivers(missing_only)
+                .await
+                .map_err(|err| anyhow::anyhow!(err))
+            }
+          }
+        }
+    };
+    if let Some(path) = &run_log {
+        idf_im_lib::logging::finish_run_log();
+        let failure = result.as_ref().err().map(idf_im_lib::errors::classify);
+        if let Err(err) = idf_im_lib::run_monitor::record_result(path, failure) {
+            warn!("Failed to record the result in {}: {}", path.display(), err);
+        }
+        println!("{}", t!("cli.run_log", path = path.display()));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use idf_im_lib::read_only::Access;
+
+    fn access(args: &[&str]) -> Option<Access> {
+        let cli = Cli::try_parse_from(std::iter::once("eim").chain(args.iter().copied())).unwrap();
+        required_access(&cli.command.unwrap())
+    }
+
+    #[test]
+    fn test_inspecting_needs_no_write_access() {
+        for args in [
+            &["list"][..],
+            &["status"],
+            &["activate", "v5.3"],
+            &["shell", "v5.3"],
+            &["which"],
+            &["env", "diff", "v5.3"],
+            &["export", "cmake", "v5.3"],
+            &["label", "v5.3"],
+            &["dedupe", "--dry-run"],
+            &["prune", "--dry-run"],
+            &["migrate", "--dry-run"],
+        ] {
+            assert_eq!(access(args), None, "eim {}", args.join(" "));
+        }
+    }
+
+    #[test]
+    fn test_write_access_per_command() {
+        assert_eq!(access(&["install", "--download-only", "-i", "v5.3"]), Some(Access::Lock));
+        assert_eq!(access(&["install", "-i", "v5.3"]), Some(Access::Registry));
+        assert_eq!(access(&["label", "v5.3", "customer-X firmware"]), Some(Access::Registry));
+        assert_eq!(access(&["label", "v5.3", "--clear"]), Some(Access::Registry));
+        assert_eq!(access(&["remove", "v5.3"]), Some(Access::Tools));
+        assert_eq!(access(&["prune"]), Some(Access::Tools));
+    }
+}
@@ -1,10 +1,12 @@
 use std::path::PathBuf;
 
 use crate::cli::helpers::{
-    first_defaulted_multiselect, generic_confirm, generic_input, generic_select, run_with_spinner,
+    first_defaulted_multiselect, generic_confirm, generic_input, generic_multiselect, generic_select,
+    run_with_spinner,
 };
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::MultiSelect;
+use idf_im_lib::components::{Component, OPTIONAL_COMPONENTS};
 use idf_im_lib::idf_features::FeatureInfo;
 use idf_im_lib::{idf_features::RequirementsMetadata, settings::Settings};
 use idf_im_lib::system_dependencies;
@@ -12,6 +14,10 @@ use log::{debug, info};
 use rust_i18n::t;
 use idf_im_lib::utils::calculate_mirrors_latency;
 use crate::cli::helpers::generic_confirm_with_default;
+use crate::cli::helpers::print_release_info;
+
+/// Lines of release notes shown per version before installing, `eim info` shows all of them
+const RELEASE_NOTES_PREVIEW_LINES: usize = 25;
 
 
 pub async fn select_target() -> Result<Vec<String>, String> {
@@ -117,7 +123,7 @@ fn python_sanity_check(python: Option<&str>) -> Result<(), String> {
             Ok(_) => {}
             Err(err) => {
                 all_ok = false;
-                println!("{:?}", err)
+                crate::cli::tui::print_line(format!("{:?}", err))
             }
         }
     }
@@ -183,6 +189,124 @@ pub fn check_and_install_python(
     Ok(())
 }
 
+/// Reports connected Espressif devices without drivers and installs the missing ones.
+///
+/// When the process is not elevated, the installation is delegated to an elevated copy of eim.
+pub async fn install_missing_drivers(missing_only: bool) -> Result<(), String> {
+    let only = if missing_only {
+        let missing = idf_im_lib::drivers::detect_devices_missing_drivers().map_err(|e| e.to_string())?;
+        if missing.is_empty() {
+            info!("{}", t!("drivers.detect.none_missing"));
+            return Ok(());
+        }
+        for device in &missing {
+            info!("{}", t!("drivers.detect.missing", name = device.friendly_name, driver = device.driver));
+        }
+        let mut names: Vec<String> = missing.into_iter().map(|d| d.driver).collect();
+        names.sort();
+        names.dedup();
+        Some(names)
+    } else {
+        None
+    };
+
+    if !idf_im_lib::utils::is_elevated().unwrap_or(false) {
+        info!("{}", t!("drivers.elevation_required"));
+        return idf_im_lib::drivers::run_elevated_driver_install(missing_only).map_err(|e| e.to_string());
+    }
+
+    info!("{}", t!("drivers.installing"));
+    idf_im_lib::install_selected_drivers(only.as_deref())
+        .await
+        .map_err(|e| t!("drivers.failed", error = e.to_string()).to_string())?;
+    info!("{}", t!("drivers.success"));
+    Ok(())
+}
+
+/// Optional wizard step offering USB driver installation on Windows.
+pub async fn select_and_install_drivers(
+    non_interactive: bool,
+    install_drivers: Option<bool>,
+) -> Result<(), String> {
+    if std::env::consts::OS != "windows" {
+        return Ok(());
+    }
+    let install = match install_drivers {
+        Some(value) => value,
+        None if non_interactive => false,
+        None => generic_confirm("wizard.drivers.prompt").map_err(|e| e.to_string())?,
+    };
+    if install {
+        install_missing_drivers(false).await
+    } else {
+        debug!("{}", t!("wizard.drivers.skipped"));
+        Ok(())
+    }
+}
+
+/// Optional wizard step choosing the optional components (examples, docs, QEMU) to install.
+pub fn select_components(mut config: Settings) -> Result<Settings, String> {
+    if config.components.is_none() && !config.non_interactive.unwrap_or_default() {
+        let options: Vec<String> = OPTIONAL_COMPONENTS
+            .iter()
+            .map(|c| component_label(*c))
+            .collect();
+        let defaults: Vec<bool> = OPTIONAL_COMPONENTS
+            .iter()
+            .map(|c| match c {
+                Component::Qemu => config.install_qemu.unwrap_or(false),
+                _ => c.default_selected(),
+            })
+            .collect();
+        let selected = generic_multiselect("wizard.components.prompt", &options, &defaults)?;
+        config.components = Some(
+            OPTIONAL_COMPONENTS
+                .iter()
+                .filter(|c| selected.contains(&component_label(**c)))
+                .map(|c| c.name().to_string())
+                .collect(),
+        );
+    }
+    idf_im_lib::components::apply_to_settings(&mut config).map_err(|e| e.to_string())?;
+    Ok(config)
+}
+
+/// Offers the tools found on the `PATH` with a version ESP-IDF supports instead of downloading them.
+pub fn select_system_tools(mut config: Settings) -> Result<Settings, String> {
+    if config.use_system_tools.is_some() || config.non_interactive.unwrap_or_default() {
+        return Ok(config);
+    }
+    let found: Vec<_> = idf_im_lib::system_tools::detect(&config)
+        .into_iter()
+        .filter(|tool| tool.is_acceptable())
+        .collect();
+    if found.is_empty() {
+        return Ok(config);
+    }
+    let options: Vec<String> = found
+        .iter()
+        .map(|tool| t!("wizard.system_tools.option", name = tool.name, version = tool.version, path = tool.path).to_string())
+        .collect();
+    let selected = generic_multiselect("wizard.system_tools.prompt", &options, &vec![true; options.len()])?;
+    config.use_system_tools = Some(
+        found
+            .iter()
+            .zip(&options)
+            .filter(|(_, option)| selected.contains(option))
+            .map(|(tool, _)| tool.name.clone())
+            .collect(),
+    );
+    Ok(config)
+}
+
+fn component_label(component: Component) -> String {
+    match component {
+        Component::Examples => t!("wizard.components.examples").to_string(),
+        Component::Docs => t!("wizard.components.docs").to_string(),
+        Component::Qemu => t!("wizard.components.qemu").to_string(),
+    }
+}
+
 async fn select_single_mirror<FGet, FSet>(
     config: &mut Settings,
     field_name: &str,    // e.g. "idf_mirror"
@@ -303,6 +427,23 @@ pub fn select_installation_path(mut config: Settings) -> Result<Settings, String
     Ok(config)
 }
 
+/// Shows what is about to be installed: the support status and the release notes of each version.
+///
+/// Returns false when the user does not want to go on. Non-interactive runs don't ask.
+pub async fn confirm_versions(config: &Settings) -> Result<bool, String> {
+    if config.non_interactive.unwrap_or_default() {
+        return Ok(true);
+    }
+    for version in config.idf_versions.clone().unwrap_or_default() {
+        match idf_im_lib::release_notes::get_release_info(&version).await {
+            Ok(info) => print_release_info(&info, Some(RELEASE_NOTES_PREVIEW_LINES)),
+            Err(err) => debug!("No release information for {}: {}", version, err),
+        }
+        println!();
+    }
+    generic_confirm_with_default("wizard.confirm_versions.prompt", true).map_err(|e| e.to_string())
+}
+
 pub fn save_config_if_desired(config: &Settings) -> Result<(), String> {
     let res =
         if config.non_interactive.unwrap_or_default() && config.config_file_save_path.is_some() {
@@ -318,7 +459,7 @@ pub fn save_config_if_desired(config: &Settings) -> Result<(), String> {
         config
             .save()
             .map_err(|e| format!("{} {:?}", t!("wizard.after_install.config.save_failed"), e))?;
-        println!("{}", t!("wizard.after_install.config.saved"));
+        crate::cli::tui::print_line(t!("wizard.after_install.config.saved").to_string());
     }
     Ok(())
 }
@@ -340,7 +481,7 @@ pub fn select_features(
 ) -> Result<Vec<FeatureInfo>, String> {
     if non_interactive {
         // Non-interactive mode: return all required features
-        println!("Non-interactive mode: selecting all required features by default");
+        crate::cli::tui::print_line("Non-interactive mode: selecting all required features by default".to_string());
         let required = metadata
             .required_features()
             .into_iter()
@@ -519,10 +660,10 @@ pub fn select_features_advanced(
 
     if options.non_interactive {
         // Return all filtered features in non-interactive mode
-        println!(
+        crate::cli::tui::print_line(format!(
             "Non-interactive mode: selecting {} filtered feature(s)",
             filtered_features.len()
-        );
+        ));
         Ok(filtered_features.into_iter().cloned().collect())
     } else {
         // Interactive selection from filtered features
@@ -0,0 +1,563 @@
+//! JSON-RPC 2.0 automation server for IDE plugins and internal tooling.
+//!
+//! Requests and responses are single-line JSON documents separated by newlines. While an
+//! `install` request runs, the log messages of the installation are streamed to the client
+//! as `progress` notifications carrying the id of the request.
+//!
+//! Log records of other requests, and of the server itself, are kept out of that stream.
+//!
+//! Every client is served by its own task. On a TCP port, which every local user can connect
+//! to, the first request of a connection has to be `authenticate` with the server token.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use idf_im_lib::idf_tools::{self, ToolStatus};
+use idf_im_lib::install_lock::{InstallLock, LockError};
+use idf_im_lib::settings::Settings;
+use idf_im_lib::version_manager;
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::oneshot;
+
+use crate::cli::wizard;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+pub(crate) const SERVER_ERROR: i64 = -32000;
+/// Another eim process holds the installation lock
+pub(crate) const LOCKED: i64 = -32001;
+/// The connection did not authenticate with the server token
+const UNAUTHORIZED: i64 = -32002;
+
+/// Where progress notifications of the running request go: (request id, writer channel).
+static PROGRESS_SINK: Lazy<Mutex<Option<(Value, UnboundedSender<String>)>>> = Lazy::new(|| Mutex::new(None));
+
+thread_local! {
+    /// Set on threads serving a request other than the install, e.g. a `list` of another client.
+    static OTHER_REQUEST: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs a request other than the install, keeping its log records out of the progress
+/// notifications of the running install.
+pub(crate) fn without_progress<T>(method: impl FnOnce() -> T) -> T {
+    let previous = OTHER_REQUEST.with(|flag| flag.replace(true));
+    let result = method();
+    OTHER_REQUEST.with(|flag| flag.set(previous));
+    result
+}
+
+/// Log appender forwarding log records to the client of the running request.
+#[derive(Debug)]
+pub struct RpcProgressAppender;
+
+impl log4rs::append::Append for RpcProgressAppender {
+    fn append(&self, record: &log::Record) -> anyhow::Result<()> {
+        // connections and requests of the server itself, including the REST mode
+        if record.target().starts_with(module_path!()) || OTHER_REQUEST.with(Cell::get) {
+            return Ok(());
+        }
+        if let Ok(sink) = PROGRESS_SINK.lock() {
+            if let Some((id, sender)) = sink.as_ref() {
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "progress",
+                    "params": {
+                        "request_id": id,
+                        "level": record.level().to_string().to_lowercase(),
+                        "message": record.args().to_string(),
+                    }
+                });
+                let _ = sender.send(notification.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
+
+pub(crate) struct RpcError {
+    pub(crate) code: i64,
+    pub(crate) message: String,
+}
+
+impl RpcError {
+    pub(crate) fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Converts a JSON parameter to a configuration value, as if it came from the config file.
+fn json_to_config_value(value: &Value) -> Option<config::Value> {
+    match value {
+        Value::Null => None,
+        Value::Bool(b) => Some((*b).into()),
+        Value::Number(n) => n
+            .as_i64()
+            .map(Into::into)
+            .or_else(|| n.as_f64().map(Into::into)),
+        Value::String(s) => Some(s.clone().into()),
+        Value::Array(items) => Some(
+            items
+                .iter()
+                .filter_map(json_to_config_value)
+                .collect::<Vec<config::Value>>()
+                .into(),
+        ),
+        Value::Object(map) => Some(
+            map.iter()
+                .filter_map(|(k, v)| json_to_config_value(v).map(|v| (k.clone(), v)))
+                .collect::<HashMap<String, config::Value>>()
+                .into(),
+        ),
+    }
+}
+
+/// Compares a token sent by a client in constant time, so the time taken does not tell how much of it matched.
+pub(crate) fn token_matches(expected: &str, given: &str) -> bool {
+    let (expected, given) = (expected.as_bytes(), given.as_bytes());
+    expected.len() == given.len() && expected.iter().zip(given).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Runs a method which does file IO or spawns processes on the blocking thread pool.
+async fn run_blocking<F>(method: F) -> Result<Value, RpcError>
+where
+    F: FnOnce() -> Result<Value, RpcError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || without_progress(method))
+        .await
+        .unwrap_or_else(|e| Err(RpcError::new(SERVER_ERROR, e.to_string())))
+}
+
+pub(crate) fn acquire_lock(command: &str) -> Result<InstallLock, RpcError> {
+    InstallLock::try_acquire(command).map_err(|err| match err {
+        LockError::Held(holder) => RpcError::new(LOCKED, format!("Installation locked by {}", holder)),
+        other => RpcError::new(SERVER_ERROR, other.to_string()),
+    })
+}
+
+pub(crate) fn method_list() -> Result<Value, RpcError> {
+    let versions =
+        version_manager::list_installed_versions().map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?;
+    let selected = version_manager::get_selected_version().map(|v| v.id);
+    let versions: Vec<Value> = versions
+        .into_iter()
+        .map(|v| {
+            json!({
+                "id": v.id,
+                "name": v.name,
+                "path": v.path,
+                "tools_path": v.idf_tools_path,
+                "python": v.python,
+                "activation_script": v.activation_script,
+                "selected": selected.as_deref() == Some(v.id.as_str()),
+            })
+        })
+        .collect();
+    Ok(json!({ "versions": versions }))
+}
+
+/// Configuration keys a client may set: what is installed, where and from which mirrors.
+/// Keys naming scripts or local files eim would run or read, like `hooks` and `config`, are
+/// refused, as every client able to authenticate could otherwise run commands as this user.
+const CLIENT_SETTINGS: &[&str] = &[
+    "idf_versions",
+    "target",
+    "path",
+    "version_name",
+    "mirror",
+    "idf_mirror",
+    "pypi_mirror",
+    "idf_features",
+    "idf_features_per_version",
+    "components",
+    "recurse_submodules",
+    "install_qemu",
+    "rust_toolchain",
+    "enable_ccache",
+    "submodule_jobs",
+    "skip_network_preflight",
+];
+
+/// Builds installation settings from request parameters, which use the same keys as the
+/// configuration file, e.g. `{"idf_versions": ["v5.4"], "target": ["esp32"], "path": "/opt/esp"}`.
+/// Only the keys in [`CLIENT_SETTINGS`] are accepted.
+pub(crate) fn settings_from_params(params: &Value) -> Result<Settings, RpcError> {
+    let params = match params {
+        Value::Object(map) => map.clone(),
+        Value::Null => serde_json::Map::new(),
+        _ => return Err(RpcError::new(INVALID_PARAMS, "params must be an object")),
+    };
+    if let Some(key) = params.keys().find(|key| !CLIENT_SETTINGS.contains(&key.as_str())) {
+        return Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("'{}' can't be set by a client, allowed keys: {}", key, CLIENT_SETTINGS.join(", ")),
+        ));
+    }
+    let overrides: Vec<(String, Option<config::Value>)> = params
+        .iter()
+        .map(|(key, value)| (key.clone(), json_to_config_value(value)))
+        .chain([
+            ("non_interactive".to_string(), Some(true.into())),
+            ("install_all_prerequisites".to_string(), Some(true.into())),
+        ])
+        .collect();
+    Settings::new(None, overrides.into_iter()).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+/// Runs an installation, sending its log messages as progress notifications of request `id`.
+pub(crate) async fn run_install(settings: Settings, id: Value, sender: UnboundedSender<String>) -> Result<Value, RpcError> {
+    let _lock = acquire_lock("eim serve install")?;
+    *PROGRESS_SINK.lock().unwrap() = Some((id, sender));
+    let result = wizard::run_wizzard_run(settings.clone()).await;
+    *PROGRESS_SINK.lock().unwrap() = None;
+
+    result.map_err(|e| RpcError::new(SERVER_ERROR, e.message))?;
+    Ok(json!({ "installed": settings.idf_versions.unwrap_or_default() }))
+}
+
+async fn method_install(id: &Value, params: &Value, sender: &UnboundedSender<String>) -> Result<Value, RpcError> {
+    let settings = settings_from_params(params)?;
+    let (id, sender) = (id.clone(), sender.clone());
+    let (result_sender, result) = oneshot::channel();
+    // the installer blocks while it runs, so it gets its own thread and runtime as in the REST mode
+    std::thread::spawn(move || {
+        let result = match tokio::runtime::Runtime::new() {
+            Ok(runtime) => runtime.block_on(run_install(settings, id, sender)),
+            Err(e) => Err(RpcError::new(SERVER_ERROR, e.to_string())),
+        };
+        let _ = result_sender.send(result);
+    });
+    result
+        .await
+        .unwrap_or_else(|_| Err(RpcError::new(SERVER_ERROR, "The installation stopped unexpectedly")))
+}
+
+fn method_uninstall(params: &Value) -> Result<Value, RpcError> {
+    let version = params
+        .get("version")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing 'version'"))?;
+    let _lock = acquire_lock("eim serve uninstall")?;
+    let message = version_manager::remove_single_idf_version(version, false)
+        .map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?;
+    Ok(json!({ "removed": version, "message": message }))
+}
+
+/// Checks the tools of an installation against the `tools.json` of its ESP-IDF.
+pub(crate) fn method_verify(params: &Value) -> Result<Value, RpcError> {
+    let installations =
+        version_manager::list_installed_versions().map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?;
+    let requested = params.get("version").and_then(|v| v.as_str());
+    let mut results = Vec::new();
+    for installation in installations
+        .iter()
+        .filter(|i| requested.map_or(true, |r| i.id == r || i.name == r))
+    {
+        let tools_json = PathBuf::from(&installation.path).join("tools").join("tools.json");
+        let tools = idf_tools::read_and_parse_tools_file(&tools_json.to_string_lossy())
+            .map_err(|e| RpcError::new(SERVER_ERROR, format!("{}: {}", tools_json.display(), e)))?;
+        let install_dir = PathBuf::from(&installation.idf_tools_path);
+        let mut tool_results = Vec::new();
+        let mut ok = true;
+        for (tool_name, (version, _)) in
+            idf_tools::get_list_of_tools_to_download(tools.clone(), vec!["all".to_string()], None)
+        {
+            let status = match idf_tools::verify_tool_installation(&tool_name, &tools, &install_dir, &version) {
+                Ok(ToolStatus::Correct { .. }) => json!({ "status": "ok" }),
+                Ok(ToolStatus::DifferentVersion { installed, expected }) => {
+                    ok = false;
+                    json!({ "status": "different_version", "installed": installed, "expected": expected })
+                }
+                Ok(ToolStatus::Missing) => {
+                    ok = false;
+                    json!({ "status": "missing" })
+                }
+                Err(e) => {
+                    ok = false;
+                    json!({ "status": "error", "error": e.to_string() })
+                }
+            };
+            tool_results.push(json!({ "tool": tool_name, "version": version, "result": status }));
+        }
+        results.push(json!({ "id": installation.id, "name": installation.name, "ok": ok, "tools": tool_results }));
+    }
+    if let (Some(requested), true) = (requested, results.is_empty()) {
+        return Err(RpcError::new(INVALID_PARAMS, format!("Version '{}' is not installed", requested)));
+    }
+    Ok(json!({ "installations": results }))
+}
+
+/// Handles one request line and returns the response, `None` for notifications.
+async fn handle_request(line: &str, sender: &UnboundedSender<String>) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return Some(json!({
+                "jsonrpc": "2.0",
+                "id": Value::Null,
+                "error": { "code": PARSE_ERROR, "message": e.to_string() },
+            }))
+        }
+    };
+    let id = request.get("id").cloned();
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    let result = match request.get("method").and_then(|m| m.as_str()) {
+        // the connection is already authenticated, or does not need to be
+        Some("authenticate") => Ok(json!({ "authenticated": true })),
+        Some("list") => run_blocking(method_list).await,
+        Some("install") => method_install(id.as_ref().unwrap_or(&Value::Null), &params, sender).await,
+        Some("uninstall") => run_blocking(move || method_uninstall(&params)).await,
+        Some("verify") => run_blocking(move || method_verify(&params)).await,
+        Some(other) => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", other))),
+        None => Err(RpcError::new(INVALID_REQUEST, "missing 'method'")),
+    };
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    })
+}
+
+/// Checks the first request of a connection which needs `token`, returning the response to it
+/// and whether the connection may go on.
+fn authenticate(line: &str, token: &str) -> (Value, bool) {
+    let request: Value = serde_json::from_str(line).unwrap_or(Value::Null);
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let authenticated = request.get("method").and_then(|m| m.as_str()) == Some("authenticate")
+        && request
+            .pointer("/params/token")
+            .and_then(|t| t.as_str())
+            .is_some_and(|given| token_matches(token, given));
+    let response = if authenticated {
+        json!({ "jsonrpc": "2.0", "id": id, "result": { "authenticated": true } })
+    } else {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": UNAUTHORIZED, "message": "authenticate with the server token first" },
+        })
+    };
+    (response, authenticated)
+}
+
+/// Serves one client until it disconnects.
+///
+/// Requests of a client are processed one after another; responses and progress
+/// notifications are written by a separate task so they are sent while an install runs.
+/// With a `token`, the connection is closed unless its first request authenticates.
+async fn serve_connection<S>(stream: S, token: Option<Arc<str>>)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let (sender, mut receiver) = unbounded_channel::<String>();
+    let writer_task = tokio::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            if writer.write_all(message.as_bytes()).await.is_err()
+                || writer.write_all(b"\n").await.is_err()
+                || writer.flush().await.is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(reader).lines();
+    let mut authenticated = token.is_none();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let (false, Some(token)) = (authenticated, token.as_deref()) {
+            let (response, accepted) = authenticate(&line, token);
+            let _ = sender.send(response.to_string());
+            if !accepted {
+                warn!("Closing an RPC connection which did not authenticate");
+                break;
+            }
+            authenticated = true;
+            continue;
+        }
+        debug!("RPC request: {}", line);
+        if let Some(response) = handle_request(&line, &sender).await {
+            let _ = sender.send(response.to_string());
+        }
+    }
+    drop(sender);
+    let _ = writer_task.await;
+}
+
+/// Runs the server on a TCP port of the loopback interface, where clients have to authenticate
+/// with `token`, or on a Unix domain socket only its owner can connect to.
+pub async fn serve(socket: &str, token: &str) -> anyhow::Result<()> {
+    if let Ok(port) = socket.parse::<u16>() {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await?;
+        info!("JSON-RPC server listening on 127.0.0.1:{}", listener.local_addr()?.port());
+        let token: Arc<str> = Arc::from(token);
+        loop {
+            let (stream, address) = listener.accept().await?;
+            info!("RPC client connected from {}", address);
+            tokio::spawn(serve_connection(stream, Some(token.clone())));
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = PathBuf::from(socket);
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                return Err(anyhow::anyhow!("Another server is already listening on {}", path.display()));
+            }
+            // left over from a previous run
+            std::fs::remove_file(&path)?;
+        }
+        let listener = tokio::net::UnixListener::bind(&path)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        info!("JSON-RPC server listening on {}", path.display());
+        loop {
+            let (stream, _) = listener.accept().await?;
+            info!("RPC client connected");
+            tokio::spawn(serve_connection(stream, None));
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        Err(anyhow::anyhow!(
+            "'{}' is not a port number, only TCP ports are supported on this platform",
+            socket
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unknown_method_and_parse_error() {
+        let (sender, _receiver) = unbounded_channel();
+        let response = handle_request(r#"{"jsonrpc":"2.0","id":1,"method":"nope"}"#, &sender)
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(response["id"], 1);
+
+        let response = handle_request("{not json", &sender).await.unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+
+        // notifications get no response
+        assert!(handle_request(r#"{"jsonrpc":"2.0","method":"nope"}"#, &sender).await.is_none());
+    }
+
+    #[test]
+    fn test_progress_only_carries_the_install() {
+        use log4rs::append::Append;
+        let append = |target: &str| {
+            RpcProgressAppender
+                .append(
+                    &log::Record::builder()
+                        .args(format_args!("message"))
+                        .target(target)
+                        .level(log::Level::Info)
+                        .build(),
+                )
+                .unwrap()
+        };
+        let (sender, mut receiver) = unbounded_channel();
+        *PROGRESS_SINK.lock().unwrap() = Some((json!(7), sender));
+        append("idf_im_lib::idf_tools");
+        without_progress(|| append("idf_im_lib::version_manager"));
+        append(module_path!());
+        *PROGRESS_SINK.lock().unwrap() = None;
+
+        let notification: Value = serde_json::from_str(&receiver.try_recv().unwrap()).unwrap();
+        assert_eq!(notification["params"]["request_id"], 7);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let (response, accepted) =
+            authenticate(r#"{"jsonrpc":"2.0","id":1,"method":"authenticate","params":{"token":"secret"}}"#, "secret");
+        assert!(accepted);
+        assert_eq!(response["result"]["authenticated"], true);
+
+        let (response, accepted) =
+            authenticate(r#"{"jsonrpc":"2.0","id":2,"method":"authenticate","params":{"token":"secreT"}}"#, "secret");
+        assert!(!accepted);
+        assert_eq!(response["error"]["code"], UNAUTHORIZED);
+        assert_eq!(response["id"], 2);
+
+        // other methods are refused before authenticating
+        let (_, accepted) = authenticate(r#"{"jsonrpc":"2.0","id":3,"method":"list"}"#, "secret");
+        assert!(!accepted);
+        assert!(!token_matches("secret", "secret2"));
+    }
+
+    #[tokio::test]
+    async fn test_connections_are_served_concurrently() {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(serve_connection(stream, Some(Arc::from("secret"))));
+            }
+        });
+
+        // an idle first client does not keep the second one waiting
+        let _idle = tokio::net::TcpStream::connect(address).await.unwrap();
+        let (reader, mut writer) = tokio::net::TcpStream::connect(address).await.unwrap().into_split();
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"authenticate","params":{"token":"secret"}}"#;
+        writer.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+        let mut lines = BufReader::new(reader).lines();
+        let response: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(response["result"]["authenticated"], true);
+    }
+
+    #[test]
+    fn test_json_params_become_config_values() {
+        let value = json_to_config_value(&json!({"idf_versions": ["v5.4"], "recurse_submodules": true})).unwrap();
+        let table = value.into_table().unwrap();
+        assert_eq!(table["recurse_submodules"].clone().into_bool().unwrap(), true);
+        assert_eq!(
+            table["idf_versions"].clone().into_array().unwrap()[0].clone().into_string().unwrap(),
+            "v5.4"
+        );
+    }
+
+    #[test]
+    fn test_clients_cannot_set_hooks_or_config() {
+        let settings = settings_from_params(&json!({"idf_versions": ["v5.4"], "target": ["esp32"]})).unwrap();
+        assert_eq!(settings.idf_versions, Some(vec!["v5.4".to_string()]));
+        assert_eq!(settings.non_interactive, Some(true));
+
+        for params in [
+            json!({"hooks": {"pre_install": ["touch /tmp/pwned"]}}),
+            json!({"config": "/tmp/eim_config.toml"}),
+            json!({"idf_versions": ["v5.4"], "tools_manifest": "/tmp/tools.json"}),
+        ] {
+            let error = settings_from_params(&params).unwrap_err();
+            assert_eq!(error.code, INVALID_PARAMS);
+        }
+    }
+}
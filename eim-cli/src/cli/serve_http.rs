@@ -0,0 +1,395 @@
+//! REST mode of `eim serve` for provisioning dashboards.
+//!
+//! Read endpoints report the installations and the state of the last install started
+//! over HTTP, `POST /install` starts a new one in the background and `GET /metrics` returns
+//! Prometheus metrics of the installs. Every request must carry `Authorization: Bearer <token>`.
+//!
+//! The server speaks plain HTTP, so the token and the install parameters can be read on the
+//! network. It only binds loopback addresses unless remote plaintext access is allowed explicitly,
+//! e.g. behind a reverse proxy terminating TLS.
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use idf_im_lib::metrics;
+use log::{debug, info, warn};
+use rust_i18n::t;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::unbounded_channel;
+
+use crate::cli::serve::{self, RpcError};
+
+/// Log lines of the running install kept for `GET /install`.
+const LOG_TAIL_LINES: usize = 500;
+/// Upper limit for request bodies, install parameters are tiny.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+/// Upper limit for the request line and headers.
+const MAX_HEADER_SIZE: u64 = 64 * 1024;
+/// Time a client gets to send its request and to receive the response, so a stalled client
+/// does not hold a connection forever.
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum InstallState {
+    Idle,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct InstallStatus {
+    state: InstallState,
+    params: Value,
+    started: Option<String>,
+    finished: Option<String>,
+    error: Option<String>,
+    log: VecDeque<Value>,
+}
+
+impl Default for InstallStatus {
+    fn default() -> Self {
+        Self {
+            state: InstallState::Idle,
+            params: Value::Null,
+            started: None,
+            finished: None,
+            error: None,
+            log: VecDeque::new(),
+        }
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    token: Option<String>,
+    body: Vec<u8>,
+}
+
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<HttpRequest> {
+    let mut reader = BufReader::new(AsyncReadExt::take(&mut *stream, MAX_HEADER_SIZE + MAX_BODY_SIZE as u64));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0usize;
+    let mut token = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            match name.trim().to_lowercase().as_str() {
+                "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                "authorization" => {
+                    token = value.trim().strip_prefix("Bearer ").map(|t| t.trim().to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Err(std::io::Error::other("request body too large"));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+    Ok(HttpRequest {
+        method,
+        path,
+        token,
+        body,
+    })
+}
+
+async fn write_response(stream: &mut TcpStream, status: u16, body: &Value) -> std::io::Result<()> {
+    write_body(stream, status, "application/json", &body.to_string()).await
+}
+
+async fn write_body(stream: &mut TcpStream, status: u16, content_type: &str, body: &str) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.flush().await
+}
+
+fn error_body(error: &RpcError) -> Value {
+    json!({ "error": error.message, "code": error.code })
+}
+
+/// Starts an installation on its own thread, as the installer blocks while it runs.
+fn start_install(status: Arc<Mutex<InstallStatus>>, params: Value) -> Result<(), RpcError> {
+    let settings = serve::settings_from_params(&params)?;
+    {
+        let mut current = status.lock().unwrap();
+        if current.state == InstallState::Running {
+            return Err(RpcError::new(serve::LOCKED, "An installation is already running"));
+        }
+        *current = InstallStatus {
+            state: InstallState::Running,
+            params,
+            started: Some(Utc::now().to_rfc3339()),
+            ..Default::default()
+        };
+    }
+
+    let (sender, mut receiver) = unbounded_channel::<String>();
+    let log_status = status.clone();
+    tokio::spawn(async move {
+        while let Some(notification) = receiver.recv().await {
+            if let Ok(notification) = serde_json::from_str::<Value>(&notification) {
+                let mut current = log_status.lock().unwrap();
+                current.log.push_back(notification["params"].clone());
+                if current.log.len() > LOG_TAIL_LINES {
+                    current.log.pop_front();
+                }
+            }
+        }
+    });
+
+    std::thread::spawn(move || {
+        let result = match tokio::runtime::Runtime::new() {
+            Ok(runtime) => runtime.block_on(serve::run_install(settings, json!("http"), sender)),
+            Err(e) => Err(RpcError::new(serve::SERVER_ERROR, e.to_string())),
+        };
+        let mut current = status.lock().unwrap();
+        current.finished = Some(Utc::now().to_rfc3339());
+        match result {
+            Ok(_) => current.state = InstallState::Succeeded,
+            Err(error) => {
+                current.state = InstallState::Failed;
+                current.error = Some(error.message);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn route(request: &HttpRequest, status: &Arc<Mutex<InstallStatus>>) -> (u16, Value) {
+    let path = request.path.split('?').next().unwrap_or_default();
+    match (request.method.as_str(), path) {
+        ("GET", "/status") => {
+            let install = status.lock().unwrap().clone();
+            match serve::method_list() {
+                Ok(versions) => (
+                    200,
+                    json!({
+                        "hostname": std::env::var("COMPUTERNAME").or_else(|_| std::env::var("HOSTNAME")).unwrap_or_default(),
+                        "versions": versions["versions"],
+                        "install": { "state": install.state, "started": install.started, "finished": install.finished, "error": install.error },
+                    }),
+                ),
+                Err(error) => (500, error_body(&error)),
+            }
+        }
+        ("GET", "/versions") => match serve::method_list() {
+            Ok(result) => (200, result),
+            Err(error) => (500, error_body(&error)),
+        },
+        ("GET", "/verify") => match serve::method_verify(&Value::Null) {
+            Ok(result) => (200, result),
+            Err(error) => (500, error_body(&error)),
+        },
+        ("GET", "/install") => (200, json!(status.lock().unwrap().clone())),
+        ("POST", "/install") => {
+            let params = if request.body.is_empty() {
+                Value::Null
+            } else {
+                match serde_json::from_slice(&request.body) {
+                    Ok(params) => params,
+                    Err(e) => return (400, json!({ "error": e.to_string() })),
+                }
+            };
+            match start_install(status.clone(), params) {
+                Ok(()) => (202, json!({ "state": InstallState::Running })),
+                Err(error) if error.code == serve::LOCKED => (409, error_body(&error)),
+                Err(error) => (400, error_body(&error)),
+            }
+        }
+        _ => (404, json!({ "error": "not found" })),
+    }
+}
+
+/// Answers the single request of a client, which has to carry `token`.
+async fn serve_client(mut stream: TcpStream, peer: SocketAddr, token: &str, status: Arc<Mutex<InstallStatus>>) {
+    let request = match tokio::time::timeout(IO_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(request)) => request,
+        Ok(Err(e)) => {
+            warn!("Invalid HTTP request from {}: {}", peer, e);
+            let _ = tokio::time::timeout(
+                IO_TIMEOUT,
+                write_response(&mut stream, 400, &json!({ "error": e.to_string() })),
+            )
+            .await;
+            return;
+        }
+        Err(_) => {
+            warn!("{} did not send its request in time", peer);
+            let _ = tokio::time::timeout(
+                IO_TIMEOUT,
+                write_response(&mut stream, 408, &json!({ "error": "request timeout" })),
+            )
+            .await;
+            return;
+        }
+    };
+    let authorized = request.token.as_deref().is_some_and(|given| serve::token_matches(token, given));
+    let (code, content_type, body) = if !authorized {
+        warn!("Rejected unauthenticated request from {}", peer);
+        (401, "application/json", json!({ "error": "missing or invalid bearer token" }).to_string())
+    } else if request.method == "GET" && request.path.split('?').next() == Some("/metrics") {
+        debug!("{} {} from {}", request.method, request.path, peer);
+        (200, metrics::CONTENT_TYPE, metrics::render())
+    } else {
+        info!("{} {} from {}", request.method, request.path, peer);
+        // the routes read eim_idf.json and the tools.json files of the installations
+        let (code, body) = tokio::task::spawn_blocking(move || serve::without_progress(|| route(&request, &status)))
+            .await
+            .unwrap_or_else(|e| (500, json!({ "error": e.to_string() })));
+        (code, "application/json", body.to_string())
+    };
+    match tokio::time::timeout(IO_TIMEOUT, write_body(&mut stream, code, content_type, &body)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!("Failed to answer {}: {}", peer, e),
+        Err(_) => warn!("{} did not read the response in time", peer),
+    }
+}
+
+/// Whether the server may listen on `address` without TLS.
+fn plaintext_allowed(address: &SocketAddr, allow_remote_plaintext: bool) -> bool {
+    address.ip().is_loopback() || allow_remote_plaintext
+}
+
+/// Runs the REST server on `address` (e.g. `127.0.0.1:9900`), accepting only requests with `token`.
+/// Other addresses than loopback ones are refused unless `allow_remote_plaintext` is set.
+pub async fn serve_http(address: &str, token: &str, allow_remote_plaintext: bool) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    let local_address = listener.local_addr()?;
+    if !plaintext_allowed(&local_address, allow_remote_plaintext) {
+        anyhow::bail!(t!("serve.remote_plaintext", address = local_address));
+    }
+    if !local_address.ip().is_loopback() {
+        warn!("{}", t!("serve.remote_plaintext_allowed", address = local_address));
+    }
+    info!("REST server listening on {}", local_address);
+    let status = Arc::new(Mutex::new(InstallStatus::default()));
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let status = status.clone();
+        let token = token.to_string();
+        tokio::spawn(async move { serve_client(stream, peer, &token, status).await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends `request` to a server answering a single client and returns the status code.
+    async fn send(request: String, status: Arc<Mutex<InstallStatus>>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            serve_client(stream, peer, "secret", status).await;
+        });
+        let mut client = TcpStream::connect(address).await.unwrap();
+        client.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        server.await.unwrap();
+        response.split_whitespace().nth(1).unwrap().parse().unwrap()
+    }
+
+    fn request(method: &str, path: &str, token: Option<&str>, body: &str) -> String {
+        let authorization = token.map(|token| format!("Authorization: Bearer {}\r\n", token)).unwrap_or_default();
+        format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            authorization,
+            body.len(),
+            body
+        )
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized() {
+        let status = Arc::new(Mutex::new(InstallStatus::default()));
+        assert_eq!(send(request("GET", "/install", None, ""), status.clone()).await, 401);
+        assert_eq!(send(request("GET", "/install", Some("wrong"), ""), status.clone()).await, 401);
+        assert_eq!(send(request("GET", "/install", Some("secret"), ""), status).await, 200);
+    }
+
+    #[tokio::test]
+    async fn test_body_too_large() {
+        let request = format!(
+            "POST /install HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: {}\r\n\r\n{{}}",
+            MAX_BODY_SIZE + 1
+        );
+        assert_eq!(send(request, Arc::new(Mutex::new(InstallStatus::default()))).await, 400);
+    }
+
+    #[tokio::test]
+    async fn test_second_install_conflicts() {
+        let status = Arc::new(Mutex::new(InstallStatus {
+            state: InstallState::Running,
+            ..Default::default()
+        }));
+        let body = r#"{"idf_versions": ["v5.4"]}"#;
+        assert_eq!(send(request("POST", "/install", Some("secret"), body), status.clone()).await, 409);
+        // the running install is left alone
+        assert_eq!(status.lock().unwrap().state, InstallState::Running);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_path() {
+        let status = Arc::new(Mutex::new(InstallStatus::default()));
+        assert_eq!(send(request("GET", "/nope", Some("secret"), ""), status.clone()).await, 404);
+        assert_eq!(send(request("DELETE", "/install", Some("secret"), ""), status).await, 404);
+    }
+
+    #[tokio::test]
+    async fn test_install_refuses_hooks() {
+        let status = Arc::new(Mutex::new(InstallStatus::default()));
+        let body = r#"{"idf_versions": ["v5.4"], "hooks": {"pre_install": ["touch /tmp/pwned"]}}"#;
+        assert_eq!(send(request("POST", "/install", Some("secret"), body), status.clone()).await, 400);
+        assert_eq!(status.lock().unwrap().state, InstallState::Idle);
+    }
+
+    #[test]
+    fn test_remote_plaintext_needs_opt_in() {
+        let loopback: SocketAddr = "127.0.0.1:9900".parse().unwrap();
+        let remote: SocketAddr = "0.0.0.0:9900".parse().unwrap();
+        assert!(plaintext_allowed(&loopback, false));
+        assert!(plaintext_allowed(&"[::1]:9900".parse().unwrap(), false));
+        assert!(!plaintext_allowed(&remote, false));
+        assert!(plaintext_allowed(&remote, true));
+    }
+}
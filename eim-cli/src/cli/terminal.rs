@@ -0,0 +1,289 @@
+use idf_im_lib::announcements::{AnnounceDecision, Announcer, Milestone};
+use idf_im_lib::transfer_rate::TransferRate;
+use indicatif::{ProgressBar, ProgressStyle};
+use once_cell::sync::Lazy;
+use std::io::IsTerminal;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::cli::tui::TuiBar;
+
+/// What the terminal the CLI writes to can display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerminalCapabilities {
+    /// ANSI colors and styles
+    pub color: bool,
+    /// Redrawn progress bars and spinners, otherwise progress is printed as plain percentage lines
+    pub animate: bool,
+    /// Plain output without percentage lines, the stages of an installation are announced instead
+    pub screen_reader: bool,
+}
+
+impl TerminalCapabilities {
+    /// Plain output is used with `--plain` or `--screen-reader`, when `NO_COLOR` is set (see
+    /// no-color.org), when stdout is not a terminal (CI logs, pipes) and for `TERM=dumb`.
+    pub fn detect(plain: bool, screen_reader: bool) -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let dumb = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+        let tty = std::io::stdout().is_terminal();
+        let animate = !plain && !screen_reader && tty && !dumb;
+        Self {
+            color: animate && !no_color,
+            animate,
+            screen_reader,
+        }
+    }
+}
+
+static CAPABILITIES: OnceLock<TerminalCapabilities> = OnceLock::new();
+
+/// Detects the terminal capabilities once at startup and configures the console styling accordingly.
+pub fn init(plain: bool, screen_reader: bool) -> TerminalCapabilities {
+    let capabilities = *CAPABILITIES.get_or_init(|| TerminalCapabilities::detect(plain, screen_reader));
+    console::set_colors_enabled(capabilities.color);
+    console::set_colors_enabled_stderr(capabilities.color);
+    capabilities
+}
+
+pub fn capabilities() -> TerminalCapabilities {
+    *CAPABILITIES.get_or_init(|| TerminalCapabilities::detect(false, false))
+}
+
+static ANNOUNCER: Lazy<Mutex<Announcer>> = Lazy::new(|| Mutex::new(Announcer::default()));
+
+/// Prints the milestone of an installation on its own line with `--screen-reader`, rate limited
+/// like the announcements of the GUI; a deferred one is printed by a thread after its delay.
+pub fn announce(milestone: Milestone, version: Option<&str>) {
+    if !capabilities().screen_reader {
+        return;
+    }
+    match ANNOUNCER.lock().unwrap().submit(milestone, version, std::time::Instant::now()) {
+        AnnounceDecision::Announce(announcement) => crate::cli::tui::print_line(announcement.message),
+        AnnounceDecision::Skip | AnnounceDecision::Defer => {}
+        AnnounceDecision::DeferAndFlushIn(delay) => {
+            std::thread::spawn(move || {
+                std::thread::sleep(delay);
+                if let Some(announcement) = ANNOUNCER.lock().unwrap().take_pending(std::time::Instant::now()) {
+                    crate::cli::tui::print_line(announcement.message);
+                }
+            });
+        }
+    }
+}
+
+/// Percentage steps at which plain progress is printed
+const PLAIN_PROGRESS_STEP: u64 = 10;
+
+#[derive(Default)]
+struct PlainProgress {
+    length: u64,
+    position: u64,
+    message: String,
+    last_reported: Option<u64>,
+}
+
+impl PlainProgress {
+    /// Returns the line to print when the progress crossed the next step.
+    fn update(&mut self) -> Option<String> {
+        if self.length == 0 {
+            return None;
+        }
+        let percent = (self.position.min(self.length) * 100 / self.length) / PLAIN_PROGRESS_STEP * PLAIN_PROGRESS_STEP;
+        if self.last_reported.is_some_and(|last| percent <= last) {
+            return None;
+        }
+        self.last_reported = Some(percent);
+        Some(if self.message.is_empty() {
+            format!("{}%", percent)
+        } else {
+            format!("{}: {}%", self.message, percent)
+        })
+    }
+}
+
+/// Speed and time left appended to the message of a bar counting bytes
+#[derive(Default)]
+struct TransferState {
+    rate: TransferRate,
+    length: u64,
+    message: String,
+}
+
+impl TransferState {
+    fn render(&self, position: u64) -> String {
+        match self.rate.stats(position, self.length) {
+            Some(stats) if self.message.is_empty() => stats.to_string(),
+            Some(stats) => format!("{} ({})", self.message, stats),
+            None => self.message.clone(),
+        }
+    }
+}
+
+/// A progress bar on interactive terminals, percentage lines in steps of 10% everywhere else,
+/// a row of the progress screen while `eim install --tui` runs.
+#[derive(Clone)]
+pub struct CliProgress {
+    bar: Option<ProgressBar>,
+    plain: Arc<Mutex<PlainProgress>>,
+    tui: Option<TuiBar>,
+    transfer: Option<Arc<Mutex<TransferState>>>,
+}
+
+impl CliProgress {
+    pub fn new(length: u64, style: impl FnOnce() -> ProgressStyle) -> Self {
+        let tui = TuiBar::new(length);
+        let bar = (capabilities().animate && tui.is_none()).then(|| {
+            let bar = ProgressBar::new(length);
+            bar.set_style(style());
+            bar
+        });
+        Self {
+            bar,
+            plain: Arc::new(Mutex::new(PlainProgress {
+                length,
+                ..Default::default()
+            })),
+            tui,
+            transfer: None,
+        }
+    }
+
+    /// Shows the rolling average speed and the time left after the message, for bars counting bytes.
+    pub fn with_transfer_rate(mut self) -> Self {
+        self.transfer = Some(Arc::new(Mutex::new(TransferState::default())));
+        self
+    }
+
+    fn update_plain(&self, update: impl FnOnce(&mut PlainProgress)) {
+        if self.bar.is_some() || self.tui.is_some() || capabilities().screen_reader {
+            return;
+        }
+        let mut plain = self.plain.lock().unwrap();
+        update(&mut plain);
+        if let Some(line) = plain.update() {
+            println!("{}", line);
+        }
+    }
+
+    pub fn set_length(&self, length: u64) {
+        if let Some(transfer) = &self.transfer {
+            transfer.lock().unwrap().length = length;
+        }
+        if let Some(tui) = &self.tui {
+            return tui.set_length(length);
+        }
+        match &self.bar {
+            Some(bar) => bar.set_length(length),
+            None => self.update_plain(|p| {
+                if p.length != length {
+                    p.length = length;
+                    p.last_reported = None;
+                }
+            }),
+        }
+    }
+
+    pub fn set_position(&self, position: u64) {
+        if let Some(transfer) = &self.transfer {
+            let message = {
+                let mut transfer = transfer.lock().unwrap();
+                transfer.rate.record_at(position, std::time::Instant::now());
+                transfer.render(position)
+            };
+            self.show_message(message);
+        }
+        if let Some(tui) = &self.tui {
+            return tui.set_position(position);
+        }
+        match &self.bar {
+            Some(bar) => bar.set_position(position),
+            None => self.update_plain(|p| {
+                if position < p.position {
+                    // a new item started
+                    p.last_reported = None;
+                }
+                p.position = position;
+            }),
+        }
+    }
+
+    pub fn set_message(&self, message: impl Into<String>) {
+        let message = message.into();
+        let message = match &self.transfer {
+            Some(transfer) => {
+                let mut transfer = transfer.lock().unwrap();
+                transfer.message = message;
+                // a new item starts, the speed of the previous one doesn't apply
+                transfer.rate.reset();
+                transfer.render(0)
+            }
+            None => message,
+        };
+        self.show_message(message)
+    }
+
+    fn show_message(&self, message: String) {
+        if let Some(tui) = &self.tui {
+            return tui.set_message(message);
+        }
+        match &self.bar {
+            Some(bar) => bar.set_message(message),
+            None => self.plain.lock().unwrap().message = message,
+        }
+    }
+
+    pub fn finish(&self) {
+        if let Some(tui) = &self.tui {
+            return tui.finish();
+        }
+        match &self.bar {
+            Some(bar) => bar.finish(),
+            None => self.update_plain(|p| p.position = p.length),
+        }
+    }
+
+    pub fn abandon_with_message(&self, message: String) {
+        if let Some(tui) = &self.tui {
+            return tui.abandon_with_message(message);
+        }
+        match &self.bar {
+            Some(bar) => bar.abandon_with_message(message),
+            None => println!("{}", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_message() {
+        let mut transfer = TransferState {
+            message: "xtensa-esp-elf.tar.xz".to_string(),
+            length: 10_000,
+            ..Default::default()
+        };
+        assert_eq!(transfer.render(0), "xtensa-esp-elf.tar.xz");
+        let start = std::time::Instant::now();
+        transfer.rate.record_at(0, start);
+        transfer.rate.record_at(2_000, start + std::time::Duration::from_secs(1));
+        assert_eq!(transfer.render(2_000), "xtensa-esp-elf.tar.xz (2.0 kB/s, 4s left)");
+    }
+
+    #[test]
+    fn test_plain_progress_prints_steps_once() {
+        let mut progress = PlainProgress {
+            length: 200,
+            ..Default::default()
+        };
+        let mut lines = Vec::new();
+        for position in (0..=200).step_by(5) {
+            progress.position = position;
+            lines.extend(progress.update());
+        }
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "0%");
+        assert_eq!(lines[10], "100%");
+        assert!(lines.iter().all(|line| line.is_ascii()));
+    }
+}
@@ -0,0 +1,487 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use idf_im_lib::errors::{EimError, ErrorKind};
+use idf_im_lib::settings::Settings;
+use once_cell::sync::Lazy;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use rust_i18n::t;
+
+use crate::cli::{prompts, wizard};
+
+/// Log lines kept for the log pane of the progress screen
+const LOG_LINES: usize = 200;
+/// How often the progress screen is redrawn without new events
+const TICK: Duration = Duration::from_millis(100);
+
+/// Progress of the installation, sent to the screen while `eim install --tui` runs.
+enum TuiEvent {
+    Progress(usize, BarUpdate),
+    Log(String),
+    Done(Result<(), String>),
+}
+
+enum BarUpdate {
+    Length(u64),
+    Position(u64),
+    Message(String),
+    Finish,
+    Abandon(String),
+}
+
+/// Receives log records and progress while the TUI is on screen, `None` otherwise
+static SINK: Lazy<Mutex<Option<Sender<TuiEvent>>>> = Lazy::new(|| Mutex::new(None));
+static NEXT_BAR: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the TUI owns the terminal, so nothing else may draw on it.
+pub fn is_active() -> bool {
+    SINK.lock().map(|sink| sink.is_some()).unwrap_or(false)
+}
+
+fn send(event: TuiEvent) {
+    if let Ok(sink) = SINK.lock() {
+        if let Some(sender) = sink.as_ref() {
+            let _ = sender.send(event);
+        }
+    }
+}
+
+/// Prints `line`, or shows it in the log pane while the TUI owns the terminal, where printing
+/// would garble the screen.
+pub fn print_line(line: String) {
+    if is_active() {
+        send(TuiEvent::Log(line));
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// Shows the log records in the log pane of the TUI instead of on the console.
+#[derive(Debug)]
+pub struct TuiLogAppender;
+
+impl log4rs::append::Append for TuiLogAppender {
+    fn append(&self, record: &log::Record) -> anyhow::Result<()> {
+        send(TuiEvent::Log(format!("{:<5} {}", record.level(), record.args())));
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
+
+/// A progress bar of the TUI, used by [`crate::cli::terminal::CliProgress`] while the TUI runs.
+#[derive(Clone)]
+pub struct TuiBar(usize);
+
+impl TuiBar {
+    /// A new bar when the TUI is on screen, `None` otherwise.
+    pub fn new(length: u64) -> Option<Self> {
+        if !is_active() {
+            return None;
+        }
+        let bar = Self(NEXT_BAR.fetch_add(1, Ordering::Relaxed));
+        bar.set_length(length);
+        Some(bar)
+    }
+
+    pub fn set_length(&self, length: u64) {
+        send(TuiEvent::Progress(self.0, BarUpdate::Length(length)));
+    }
+
+    pub fn set_position(&self, position: u64) {
+        send(TuiEvent::Progress(self.0, BarUpdate::Position(position)));
+    }
+
+    pub fn set_message(&self, message: String) {
+        send(TuiEvent::Progress(self.0, BarUpdate::Message(message)));
+    }
+
+    pub fn finish(&self) {
+        send(TuiEvent::Progress(self.0, BarUpdate::Finish));
+    }
+
+    pub fn abandon_with_message(&self, message: String) {
+        send(TuiEvent::Progress(self.0, BarUpdate::Abandon(message)));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RowState {
+    Running,
+    Done,
+    Failed,
+}
+
+/// A line of the progress screen, one per tool download or clone step.
+struct ProgressRow {
+    label: String,
+    position: u64,
+    length: u64,
+    state: RowState,
+}
+
+impl ProgressRow {
+    fn ratio(&self) -> f64 {
+        match self.state {
+            RowState::Done => 1.0,
+            _ if self.length == 0 => 0.0,
+            _ => (self.position.min(self.length) as f64) / self.length as f64,
+        }
+    }
+}
+
+/// State of the progress screen, built from the events of the installation.
+#[derive(Default)]
+struct ProgressView {
+    title: String,
+    rows: Vec<ProgressRow>,
+    /// Row each bar currently draws into
+    current: HashMap<usize, usize>,
+    log: VecDeque<String>,
+    done: Option<Result<(), String>>,
+}
+
+impl ProgressView {
+    /// Row of `bar`, starting a new one when its last row finished: the download bar is
+    /// reused for every tool, each of them gets a row of its own.
+    fn row(&mut self, bar: usize) -> &mut ProgressRow {
+        let index = match self.current.get(&bar) {
+            Some(&index) if self.rows[index].state == RowState::Running => index,
+            _ => {
+                let length = self.current.get(&bar).map(|&i| self.rows[i].length).unwrap_or(100);
+                self.rows.push(ProgressRow {
+                    label: String::new(),
+                    position: 0,
+                    length,
+                    state: RowState::Running,
+                });
+                self.current.insert(bar, self.rows.len() - 1);
+                self.rows.len() - 1
+            }
+        };
+        &mut self.rows[index]
+    }
+
+    fn apply(&mut self, event: TuiEvent) {
+        match event {
+            TuiEvent::Progress(bar, update) => match update {
+                BarUpdate::Length(length) => self.row(bar).length = length,
+                BarUpdate::Position(position) => self.row(bar).position = position,
+                BarUpdate::Message(message) => self.row(bar).label = message,
+                BarUpdate::Finish => {
+                    let row = self.row(bar);
+                    row.position = row.length;
+                    row.state = RowState::Done;
+                }
+                BarUpdate::Abandon(message) => {
+                    self.row(bar).state = RowState::Failed;
+                    self.push_log(message);
+                }
+            },
+            TuiEvent::Log(line) => self.push_log(line),
+            TuiEvent::Done(result) => self.done = Some(result),
+        }
+    }
+
+    fn push_log(&mut self, line: String) {
+        if self.log.len() == LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log.push_back(line);
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [title_area, rows_area, log_area, help_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(5),
+            Constraint::Percentage(35),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let status = match &self.done {
+            None => Line::from(self.title.clone()).bold(),
+            Some(Ok(())) => Line::from(t!("tui.install.success").to_string()).bold().green(),
+            Some(Err(err)) => Line::from(t!("tui.install.failed", error = err).to_string()).bold().red(),
+        };
+        frame.render_widget(Paragraph::new(status).block(Block::bordered().title(" eim ")), title_area);
+
+        let block = Block::bordered().title(t!("tui.install.progress").to_string());
+        let inner = block.inner(rows_area);
+        frame.render_widget(block, rows_area);
+        // the latest rows, the finished ones scroll out at the top
+        let visible = inner.height as usize;
+        let skip = self.rows.len().saturating_sub(visible);
+        for (i, row) in self.rows.iter().skip(skip).enumerate() {
+            let area = Rect { y: inner.y + i as u16, height: 1, ..inner };
+            let (color, mark) = match row.state {
+                RowState::Running => (Color::Cyan, " "),
+                RowState::Done => (Color::Green, "✓"),
+                RowState::Failed => (Color::Red, "✗"),
+            };
+            let label = if row.label.is_empty() { "…" } else { row.label.as_str() };
+            let gauge = Gauge::default()
+                .gauge_style(Style::new().fg(color).bg(Color::Black))
+                .ratio(row.ratio())
+                .label(format!("{} {} {:.0}%", mark, label, row.ratio() * 100.0));
+            frame.render_widget(gauge, area);
+        }
+
+        let log_block = Block::bordered().title(t!("tui.install.log").to_string());
+        let log_height = log_block.inner(log_area).height as usize;
+        let lines: Vec<Line> = self
+            .log
+            .iter()
+            .skip(self.log.len().saturating_sub(log_height))
+            .map(|line| Line::from(line.as_str()))
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(log_block).wrap(Wrap { trim: false }), log_area);
+
+        let help = if self.done.is_some() { t!("tui.help.done") } else { t!("tui.help.running") };
+        frame.render_widget(Paragraph::new(help.to_string()).dim(), help_area);
+    }
+}
+
+/// A list to pick one or more entries from, the first screens of the TUI.
+struct Picker {
+    title: String,
+    items: Vec<String>,
+    checked: Vec<bool>,
+    state: ListState,
+}
+
+impl Picker {
+    fn new(title: String, items: Vec<String>, preselected: &[String]) -> Self {
+        let mut checked: Vec<bool> = items.iter().map(|item| preselected.contains(item)).collect();
+        if !checked.contains(&true) {
+            if let Some(first) = checked.first_mut() {
+                *first = true;
+            }
+        }
+        Self {
+            title,
+            items,
+            checked,
+            state: ListState::default().with_selected(Some(0)),
+        }
+    }
+
+    fn selection(&self) -> Vec<String> {
+        self.items
+            .iter()
+            .zip(&self.checked)
+            .filter(|(_, checked)| **checked)
+            .map(|(item, _)| item.clone())
+            .collect()
+    }
+
+    /// Handles a key, returns the picked entries once confirmed.
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Vec<String>> {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+            KeyCode::Char(' ') => {
+                if let Some(index) = self.state.selected() {
+                    if let Some(checked) = self.checked.get_mut(index) {
+                        *checked = !*checked;
+                    }
+                }
+            }
+            KeyCode::Enter if self.checked.contains(&true) => return Some(self.selection()),
+            _ => {}
+        }
+        None
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let [list_area, help_area] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let items: Vec<ListItem> = self
+            .items
+            .iter()
+            .zip(&self.checked)
+            .map(|(item, checked)| ListItem::new(format!("[{}] {}", if *checked { "x" } else { " " }, item)))
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(" {} ", self.title)))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, list_area, &mut self.state);
+        frame.render_widget(Paragraph::new(t!("tui.help.picker").to_string()).dim(), help_area);
+    }
+}
+
+fn is_interrupt(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+/// Lets the user pick entries of `items`, `None` when they quit.
+fn pick(terminal: &mut DefaultTerminal, title: String, items: Vec<String>, preselected: &[String]) -> std::io::Result<Option<Vec<String>>> {
+    let mut picker = Picker::new(title, items, preselected);
+    loop {
+        terminal.draw(|frame| picker.render(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if key.code == KeyCode::Esc || key.code == KeyCode::Char('q') || is_interrupt(&key) {
+                return Ok(None);
+            }
+            if let Some(selection) = picker.handle_key(key) {
+                return Ok(Some(selection));
+            }
+        }
+    }
+}
+
+fn show_message(terminal: &mut DefaultTerminal, message: String) -> std::io::Result<()> {
+    terminal.draw(|frame| {
+        frame.render_widget(Paragraph::new(message).block(Block::bordered().title(" eim ")), frame.area());
+    })?;
+    Ok(())
+}
+
+/// Draws the progress screen until the installation is done and the user leaves it.
+fn run_progress(mut terminal: DefaultTerminal, mut view: ProgressView, events: Receiver<TuiEvent>) -> std::io::Result<()> {
+    loop {
+        while let Ok(event) = events.try_recv() {
+            if matches!(event, TuiEvent::Done(_)) {
+                // the installation report is printed with println, draw the whole screen again
+                terminal.clear()?;
+            }
+            view.apply(event);
+        }
+        terminal.draw(|frame| view.render(frame))?;
+        if !event::poll(TICK)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if is_interrupt(&key) {
+                ratatui::restore();
+                eprintln!("{}", t!("tui.interrupted"));
+                std::process::exit(130);
+            }
+            if view.done.is_some() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn io_error(err: std::io::Error) -> EimError {
+    EimError::new(ErrorKind::General, format!("Terminal error: {}", err))
+}
+
+/// Picks the targets and versions, then installs them with live progress of each tool.
+///
+/// All the other settings are taken as they are, as in the non-interactive `eim install`.
+pub async fn run_install(mut config: Settings) -> Result<(), EimError> {
+    if !std::io::stdout().is_terminal() {
+        return Err(EimError::new(ErrorKind::Usage, t!("tui.no_terminal").to_string()));
+    }
+    let mut targets = idf_im_lib::idf_versions::get_avalible_targets()
+        .await
+        .map_err(|e| EimError::new(ErrorKind::Network, e))?;
+    targets.insert(0, "all".to_string());
+
+    // installing prerequisites may ask for a password, which has to be visible and read from the
+    // terminal before the TUI takes it over
+    if !config.skip_prerequisites_check.unwrap_or(false) {
+        let install = config.install_all_prerequisites.unwrap_or(false);
+        prompts::check_and_install_prerequisites(false, install)
+            .and_then(|_| prompts::check_and_install_python(false, install, config.python_version_override.clone()))
+            .map_err(|err| EimError::new(ErrorKind::Prerequisites, err))?;
+        config.skip_prerequisites_check = Some(true);
+    }
+
+    let mut terminal = ratatui::init();
+    let preselected_targets = config.target.clone().unwrap_or_default();
+    let target = match pick(&mut terminal, t!("wizard.select_target.prompt").to_string(), targets, &preselected_targets) {
+        Ok(Some(target)) => target,
+        Ok(None) => {
+            ratatui::restore();
+            return Err(EimError::new(ErrorKind::Cancelled, t!("tui.cancelled").to_string()));
+        }
+        Err(err) => {
+            ratatui::restore();
+            return Err(io_error(err));
+        }
+    };
+
+    let _ = show_message(&mut terminal, t!("tui.loading_versions").to_string());
+    let mut versions = if target.iter().any(|t| t == "all") || target.len() > 1 {
+        idf_im_lib::idf_versions::get_idf_names(true).await
+    } else {
+        idf_im_lib::idf_versions::get_idf_name_by_target(&target[0].to_lowercase(), true).await
+    };
+    versions.push("master".to_string());
+    let preselected_versions = config.idf_versions.clone().unwrap_or_default();
+    let idf_versions = match pick(&mut terminal, t!("wizard.select_idf_version.prompt").to_string(), versions, &preselected_versions) {
+        Ok(Some(versions)) => versions,
+        Ok(None) => {
+            ratatui::restore();
+            return Err(EimError::new(ErrorKind::Cancelled, t!("tui.cancelled").to_string()));
+        }
+        Err(err) => {
+            ratatui::restore();
+            return Err(io_error(err));
+        }
+    };
+
+    let view = ProgressView {
+        title: t!("tui.install.title", versions = idf_versions.join(", "), targets = target.join(", ")).to_string(),
+        ..Default::default()
+    };
+    config.target = Some(target);
+    config.idf_versions = Some(idf_versions);
+    config.non_interactive = Some(true);
+
+    let (sender, events) = mpsc::channel();
+    *SINK.lock().unwrap() = Some(sender);
+    let screen = std::thread::spawn(move || run_progress(terminal, view, events));
+
+    let result = wizard::run_wizzard_run(config).await;
+    send(TuiEvent::Done(result.clone().map_err(|err| err.message.clone())));
+    let screen_result = screen.join();
+    *SINK.lock().unwrap() = None;
+    ratatui::restore();
+    if let Ok(Err(err)) = screen_result {
+        log::warn!("Terminal error: {}", err);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_view_starts_row_per_tool() {
+        let mut view = ProgressView::default();
+        view.apply(TuiEvent::Progress(0, BarUpdate::Message("cmake.tar.xz".to_string())));
+        view.apply(TuiEvent::Progress(0, BarUpdate::Length(200)));
+        view.apply(TuiEvent::Progress(0, BarUpdate::Position(50)));
+        view.apply(TuiEvent::Progress(0, BarUpdate::Message("cmake.tar.xz: 25%".to_string())));
+        assert_eq!(view.rows.len(), 1);
+        assert_eq!(view.rows[0].ratio(), 0.25);
+
+        view.apply(TuiEvent::Progress(0, BarUpdate::Finish));
+        view.apply(TuiEvent::Progress(0, BarUpdate::Message("ninja.zip".to_string())));
+        assert_eq!(view.rows.len(), 2);
+        assert!(view.rows[0].state == RowState::Done);
+        assert_eq!(view.rows[1].label, "ninja.zip");
+        assert_eq!(view.rows[1].length, 200);
+    }
+}
@@ -0,0 +1,1094 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use dialoguer::FolderSelect;
+use idf_im_lib::announcements::Milestone;
+use idf_im_lib::display_format::format_size;
+use idf_im_lib::errors::{classify_io_error, EimError, ErrorKind};
+use idf_im_lib::idf_features::get_requirements_json_url;
+use idf_im_lib::idf_features::RequirementsMetadata;
+use idf_im_lib::idf_tools::ToolsFile;
+use idf_im_lib::hooks::HookStage;
+use idf_im_lib::install_report::InstallReport;
+use idf_im_lib::offline_installer::copy_idf_from_offline_archive;
+use idf_im_lib::offline_installer::install_prerequisites_offline;
+use idf_im_lib::offline_installer::use_offline_archive;
+use idf_im_lib::settings::Settings;
+use idf_im_lib::transaction::InstallTransaction;
+use idf_im_lib::utils::copy_dir_contents;
+use idf_im_lib::utils::extract_zst_archive;
+use idf_im_lib::{ensure_path, DownloadProgress};
+use idf_im_lib::git_tools::ProgressMessage;
+use indicatif::{ProgressState, ProgressStyle};
+use log::{debug, error, info, warn};
+use rust_i18n::t;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Instant;
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
+
+// maybe move the default values to the config too?
+const DEFAULT_TOOLS_DOWNLOAD_FOLDER: &str = "dist";
+const DEFAULT_TOOLS_INSTALL_FOLDER: &str = "tools";
+const DEFAULT_TOOLS_JSON_LOCATION: &str = "tools/tools.json";
+const DEFAULT_IDF_TOOLS_PY_LOCATION: &str = "./tools/idf_tools.py";
+
+use crate::cli::helpers::{
+    create_progress_bar, create_theme, generic_confirm, generic_input, update_progress_bar_number,
+};
+
+use crate::cli::prompts::*;
+use crate::cli::terminal::{self, CliProgress};
+
+/// `println!`, shown in the log pane instead while `eim install --tui` owns the terminal
+macro_rules! say {
+    ($($arg:tt)*) => {
+        crate::cli::tui::print_line(format!($($arg)*))
+    };
+}
+
+fn add_to_shell_rc(content: &str) -> Result<(), String> {
+    let shell = env::var("SHELL").unwrap_or_else(|_| String::from(""));
+    let home = dirs::home_dir().unwrap();
+
+    let rc_file = match shell.as_str() {
+        "/bin/bash" => home.join(".bashrc"),
+        "/bin/zsh" => home.join(".zshrc"),
+        "/bin/fish" => home.join(".config/fish/config.fish"),
+        _ => return Err(t!("wizard.shell.unsupported").to_string()),
+    };
+
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&rc_file)
+        .unwrap();
+
+    match std::io::Write::write_all(&mut file, content.as_bytes()) {
+        Ok(_) => {
+            for line in content.lines().filter(|line| !line.trim().is_empty()) {
+                idf_im_lib::install_manifest::record_system_change(idf_im_lib::install_manifest::ManifestEntry::RcFileLine {
+                    path: rc_file.to_string_lossy().into_owned(),
+                    line: line.to_string(),
+                });
+            }
+            info!("{}", t!("wizard.shellrc.update.success"))
+        }
+        Err(err) => {
+            error!("{}", t!("wizard.shellrc.update.error"));
+            error!("Error: {:?}", err);
+        }
+    };
+
+    Ok(())
+}
+
+async fn select_targets_and_versions(mut config: Settings) -> Result<Settings, String> {
+    if (config.wizard_all_questions.unwrap_or_default()
+        || config.target.is_none()
+        || config.is_default("target"))
+        && config.non_interactive == Some(false)
+    {
+        config.target = Some(select_target().await?);
+    }
+    let target = config.target.clone().unwrap_or_default();
+    debug!(
+        "{}",
+        t!("wizard.debug.target.selected", target = target.join(", "))
+    );
+
+    // here the non-interactive flag is passed to the inner function
+    if config.wizard_all_questions.unwrap_or_default()
+        || config.idf_versions.is_none()
+        || config.is_default("idf_versions")
+    {
+        config.idf_versions =
+            Some(select_idf_version(&target[0], config.non_interactive.unwrap_or_default()).await?);
+        // TODO: handle multiple targets
+    }
+    if let Some(versions) = config.idf_versions.clone() {
+        config.idf_versions = Some(idf_im_lib::idf_versions::resolve_version_aliases(versions).await?);
+    }
+    let idf_versions = config.idf_versions.clone().unwrap_or_default();
+    debug!(
+        "{}",
+        t!(
+            "wizard.debug.idf_version.selected",
+            version = idf_versions.join(", ")
+        )
+    );
+
+    Ok(config)
+}
+
+pub struct DownloadConfig {
+    pub idf_path: String,
+    pub repo_stub: Option<String>,
+    pub idf_version: String,
+    pub idf_mirror: Option<String>,
+    pub recurse_submodules: Option<bool>,
+    pub non_interactive: Option<bool>,
+}
+
+pub enum DownloadError {
+    PathCreationFailed(String),
+    DownloadFailed(String),
+    UserCancelled,
+}
+
+fn handle_download_error(err: String) -> Result<(), DownloadError> {
+    let err_string = err;
+    if err_string.contains("exists") || err_string.contains("not empty") {
+        match generic_confirm("wizard.idf_path_exists.prompt") {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(DownloadError::UserCancelled),
+            Err(e) => Err(DownloadError::DownloadFailed(e.to_string())),
+        }
+    } else {
+        Err(DownloadError::DownloadFailed(err_string))
+    }
+}
+
+pub fn download_idf(config: DownloadConfig) -> Result<(), DownloadError> {
+    idf_im_lib::ensure_path(&config.idf_path)
+        .map_err(|err| DownloadError::PathCreationFailed(err.to_string()))?;
+
+    let (tx, rx) = mpsc::channel();
+
+    // Spawn a thread to handle progress bar updates
+    let handle = thread::spawn(move || {
+        let mut progress_bar = create_progress_bar();
+
+        loop {
+            match rx.recv() {
+                Ok(ProgressMessage::Finish) => {
+                    update_progress_bar_number(&progress_bar, 100);
+                    progress_bar.finish();
+                    progress_bar = create_progress_bar();
+                }
+                Ok(ProgressMessage::Update(value)) => {
+                    update_progress_bar_number(&progress_bar, value);
+                }
+                Ok(ProgressMessage::SubmoduleUpdate((name, value))) => {
+                    let message = t!(
+                        "wizard.debug.submodule.progress",
+                        name = name,
+                        progress = value
+                    );
+                    progress_bar.set_message(message);
+                    progress_bar.set_position(value);
+                }
+                Ok(ProgressMessage::SubmoduleFinish(name)) => {
+                    let message = format!("{}: {}", name, 100);
+                    progress_bar.set_message(message);
+                    progress_bar.finish();
+                    info!("{}: {}", t!("wizard.idf.submodule_finish"), name);
+                    progress_bar = create_progress_bar();
+                }
+                Err(_) => {
+                    break;
+                }
+            }
+        }
+    });
+
+    info!("{}", t!("wizard.idf.cloning"));
+
+    match idf_im_lib::git_tools::get_esp_idf(
+        &config.idf_path,
+        config.repo_stub.as_deref(),
+        &config.idf_version,
+        config.idf_mirror.as_deref(),
+        config.recurse_submodules.unwrap_or_default(),
+        tx,
+    ) {
+        Ok(_) => {
+            debug!("{}", t!("wizard.idf.success"));
+            match handle.join() {
+                Ok(_) => {
+                    debug!("{}", t!("wizard.idf.progress_bar.join"));
+                }
+                Err(err) => {
+                    error!("{}", t!("wizard.idf.progress_bar.error"));
+                }
+            }
+            Ok(())
+        }
+        Err(err) => {
+            if config.non_interactive == Some(true) {
+                Ok(())
+            } else {
+                handle_download_error(err)
+            }
+        }
+    }
+}
+
+fn setup_directory(
+    wizard_all_questions: Option<bool>,
+    base_path: &PathBuf,
+    config_field: &mut Option<String>,
+    prompt_key: &str,
+    default_name: &str,
+) -> Result<PathBuf, String> {
+    let mut directory = base_path.clone();
+
+    if let Some(name) = config_field.clone() {
+        directory.push(name);
+    } else if wizard_all_questions.unwrap_or(false) {
+        let name = generic_input(prompt_key, &format!("{}.failure", prompt_key), default_name)?;
+        directory.push(&name);
+        *config_field = Some(name);
+    } else {
+        directory.push(default_name);
+        *config_field = Some(default_name.to_string());
+    }
+
+    idf_im_lib::ensure_path(&directory.display().to_string()).map_err(|err| err.to_string())?;
+    Ok(directory)
+}
+
+fn get_tools_json_path(config: &mut Settings, idf_path: &Path) -> PathBuf {
+    let mut tools_json_file = idf_path.to_path_buf();
+
+    if let Some(file) = &config.tools_json_file {
+        tools_json_file.push(file);
+    } else if config.wizard_all_questions.unwrap_or(false) {
+        let name = generic_input(
+            "wizard.tools_json.prompt",
+            "wizard.tools_json.prompt.failure",
+            DEFAULT_TOOLS_JSON_LOCATION,
+        )
+        .unwrap();
+        tools_json_file.push(&name);
+        config.tools_json_file = Some(name);
+    } else {
+        tools_json_file.push(DEFAULT_TOOLS_JSON_LOCATION);
+        config.tools_json_file = Some(DEFAULT_TOOLS_JSON_LOCATION.to_string());
+    }
+
+    tools_json_file
+}
+
+fn validate_tools_json_file(tools_json_file: &Path, config: &mut Settings) -> String {
+    if fs::metadata(tools_json_file).is_err() {
+        warn!("{}", t!("wizard.tools_json.not_found"));
+        let selected_file = FolderSelect::with_theme(&create_theme())
+            .with_prompt(t!("wizard.tools_json.select.prompt"))
+            .folder(tools_json_file.to_str().unwrap())
+            .file(true)
+            .interact()
+            .unwrap();
+        if fs::metadata(&selected_file).is_ok() {
+            config.tools_json_file = Some(selected_file.to_string());
+            selected_file
+        } else {
+            // TODO: implement the retry logic -> in interactive mode the user should not be able to proceed until the files is found
+            panic!("{}", t!("wizard.tools_json.unreachable"));
+        }
+    } else {
+        tools_json_file.to_str().unwrap().to_string()
+    }
+}
+
+/// Progress bar of the tool downloads and extractions.
+fn tools_progress_callback() -> impl Fn(DownloadProgress) + Clone + Send + 'static {
+    let progress_bar = CliProgress::new(0, || {
+        ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {size}/{total_size} {msg}").unwrap()
+            .with_key("size", |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+                write!(w, "{}", format_size(state.pos())).unwrap()
+            })
+            .with_key("total_size", |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+                write!(w, "{}", format_size(state.len().unwrap_or(0))).unwrap()
+            })
+            .progress_chars("#>-")
+    })
+    .with_transfer_rate();
+    // archives are extracted while the next one downloads, the bar shows an extraction only
+    // when no download is running
+    let downloading = Arc::new(AtomicBool::new(false));
+
+    let progress_callback = move |progress: DownloadProgress| match progress {
+        DownloadProgress::Progress(current, total) => {
+            progress_bar.set_length(total);
+            progress_bar.set_position(current);
+        }
+        DownloadProgress::Extracting(read, size) => {
+            if !downloading.load(Ordering::Relaxed) {
+                progress_bar.set_length(size);
+                progress_bar.set_position(read);
+            }
+        }
+        DownloadProgress::Hashing(hashed, total) => {
+            // the bar shows the checksum verification after the download
+            progress_bar.set_length(total);
+            progress_bar.set_position(hashed);
+        }
+        DownloadProgress::Complete(_) => {
+            progress_bar.finish();
+        }
+        DownloadProgress::Error(err) => {
+            progress_bar.abandon_with_message(format!("Error: {}", err));
+        }
+        DownloadProgress::Start(_, url) => {
+            downloading.store(true, Ordering::Relaxed);
+            if let Some(filename) = Path::new(&url).file_name().and_then(|f| f.to_str()) {
+                progress_bar.set_message(filename);
+            }
+            progress_bar.set_position(0);
+        }
+        DownloadProgress::Downloaded(url) => {
+            if let Some(filename) = Path::new(&url).file_name().and_then(|f| f.to_str()) {
+                info!("{}", t!("wizard.tool.download.success", filename = filename));
+            }
+        }
+        DownloadProgress::Verified(url) => {
+            downloading.store(false, Ordering::Relaxed);
+            if let Some(filename) = Path::new(&url).file_name().and_then(|f| f.to_str()) {
+                info!("{}", t!("wizard.tool.verified", filename = filename));
+            }
+        }
+        DownloadProgress::Extracted(_, url, dest) => {
+            if let Some(filename) = Path::new(&url).file_name().and_then(|f| f.to_str()) {
+                info!(
+                    "{}",
+                    t!("wizard.tool.extract.success", filename = filename, dest = dest)
+                );
+            }
+        }
+    };
+    progress_callback
+}
+
+async fn download_and_extract_tools(
+    config: &Settings,
+    tools: &ToolsFile,
+    download_dir: &PathBuf,
+    install_dir: &PathBuf,
+) -> anyhow::Result<HashMap<String, (String, idf_im_lib::idf_tools::Download)>> {
+    info!(
+        "{}: {:?}",
+        t!("wizard.tools_download.progress"),
+        download_dir.display()
+    );
+    idf_im_lib::idf_tools::setup_tools(
+        tools,
+        config.target.clone().unwrap(),
+        download_dir,
+        install_dir,
+        config.mirror.as_deref(),
+        config.tools_url_rewrite.as_deref(),
+        tools_progress_callback(),
+    )
+    .await
+}
+
+/// Fetches what the installation of a version downloads, for `--download-only`: the tool
+/// archives and the Python packages, and records them for the installation finishing it.
+async fn fetch_version_downloads(
+    config: &Settings,
+    paths: &idf_im_lib::settings::VersionPaths,
+    idf_version: &str,
+    tools: &ToolsFile,
+    download_dir: &PathBuf,
+    features: &[String],
+) -> Result<(), EimError> {
+    info!("{}: {:?}", t!("wizard.tools_download.progress"), download_dir.display());
+    let targets = config.target.clone().unwrap_or_default();
+    let archives = idf_im_lib::idf_tools::download_tools(
+        tools,
+        targets.clone(),
+        download_dir,
+        config.mirror.as_deref(),
+        config.tools_url_rewrite.as_deref(),
+        tools_progress_callback(),
+    )
+    .await
+    .map_err(|err| idf_im_lib::antivirus::diagnose(&err, config).unwrap_or_else(|| err.into()))?;
+
+    let packages_dir = idf_im_lib::download_only::packages_dir(download_dir, idf_version);
+    info!("{}", t!("wizard.download_only.python", path = packages_dir.display()));
+    let python_version = idf_im_lib::python_utils::download_python_packages(
+        paths,
+        &paths.actual_version,
+        features,
+        &packages_dir,
+        &config.pypi_mirror,
+    )
+    .await
+    .map_err(|err| EimError::new(ErrorKind::Python, err))?;
+
+    let downloaded = idf_im_lib::download_only::DownloadedVersion {
+        version: idf_version.to_string(),
+        targets,
+        features: features.to_vec(),
+        python_version,
+        tool_archives: archives
+            .values()
+            .filter_map(|(_, download)| download.url.rsplit('/').next().map(str::to_string))
+            .collect(),
+    };
+    idf_im_lib::download_only::record(download_dir, &downloaded)?;
+    say!(
+        "{}",
+        t!("wizard.download_only.version_done", version = idf_version, tools = downloaded.tool_archives.len(), path = download_dir.display())
+    );
+    Ok(())
+}
+
+/// Checks that every endpoint the installation downloads from is reachable,
+/// so a blocked host is reported up front instead of failing mid-install.
+async fn run_network_preflight(config: &Settings) -> Result<(), String> {
+    info!("{}", t!("wizard.network_preflight.running"));
+    let reports = idf_im_lib::network_preflight::run_network_preflight(config).await;
+    let failed: Vec<String> = reports
+        .iter()
+        .filter(|r| !r.is_ok())
+        .map(|r| format!("  - {}", r))
+        .collect();
+    if failed.is_empty() {
+        info!("{}", t!("wizard.network_preflight.ok"));
+        Ok(())
+    } else {
+        Err(format!(
+            "{}\n{}",
+            t!("wizard.network_preflight.failed"),
+            failed.join("\n")
+        ))
+    }
+}
+
+/// Installs `Initialize-Idf` and the "ESP-IDF (eim)" Windows Terminal profile, only warning on failure.
+fn install_powershell_integration(config: &Settings) {
+    let eim_idf_json = PathBuf::from(config.esp_idf_json_path.clone().unwrap_or_default())
+        .join(idf_im_lib::idf_config::IDF_CONFIG_FILE_NAME);
+    match idf_im_lib::install_powershell_integration(eim_idf_json.to_str().unwrap()) {
+        Ok(profiles) => {
+            for profile in profiles {
+                info!("{}", t!("wizard.powershell.installed", path = profile.display()));
+            }
+        }
+        Err(err) => warn!("{}", t!("wizard.powershell.failed", error = err.to_string())),
+    }
+    let icon_path = dirs::home_dir().map(|home| home.join("Icons").join("eim.ico"));
+    let icon_path = icon_path.filter(|p| p.exists());
+    match idf_im_lib::add_windows_terminal_eim_profile(icon_path.as_ref().and_then(|p| p.to_str())) {
+        Ok(message) => info!("{}", message),
+        Err(err) => warn!("{}", t!("wizard.powershell.terminal_failed", error = err.to_string())),
+    }
+    // the profiles are changed once for all versions, the last one installed records it
+    let last_installed = config
+        .idf_versions
+        .as_ref()
+        .and_then(|versions| versions.last())
+        .and_then(|version| config.get_version_paths(version).ok());
+    if let Some(paths) = last_installed {
+        if let Err(err) = idf_im_lib::install_manifest::record_pending_changes(&paths.idf_path.to_string_lossy()) {
+            warn!("{}", t!("wizard.manifest.record_failed", error = err.to_string()));
+        }
+    }
+}
+
+/// Boots each installed QEMU build once, only warning when one does not work.
+fn smoke_test_qemu(tool_install_directory: &Path) {
+    for tool_name in idf_im_lib::qemu::QEMU_TOOLS {
+        let Some(binary) = idf_im_lib::qemu::find_qemu_binary(tool_install_directory, tool_name) else {
+            continue;
+        };
+        match idf_im_lib::qemu::smoke_test(&binary, tool_name, idf_im_lib::qemu::SMOKE_TEST_TIMEOUT) {
+            Ok(()) => info!("{}", t!("wizard.qemu.verified", tool = tool_name)),
+            Err(err) => warn!("{}", t!("wizard.qemu.smoke_test_failed", tool = tool_name, error = err.to_string())),
+        }
+    }
+}
+
+/// Warns when installing inside WSL onto a mounted Windows drive, where builds are slow.
+fn warn_about_wsl_installation_path(config: &Settings) {
+    info!(
+        "{}",
+        t!(
+            "wsl.detected",
+            distro = idf_im_lib::wsl::current_distro().unwrap_or_default()
+        )
+    );
+    if let Some(path) = &config.path {
+        if idf_im_lib::wsl::is_windows_mount(path) {
+            warn!("{}", t!("wsl.windows_mount", path = path.display()));
+        }
+    }
+}
+
+/// Runs the installation and writes the installation report, also when it fails.
+pub async fn run_wizzard_run(config: Settings) -> Result<(), EimError> {
+    let mut report = InstallReport::new(&config);
+    let signing_key = config.report_signing_key.clone();
+    let result = run_wizzard_steps(config, &mut report).await;
+    terminal::announce(
+        match &result {
+            Ok(()) => Milestone::Complete,
+            Err(err) if err.kind == ErrorKind::Cancelled => Milestone::Cancelled,
+            Err(_) => Milestone::Failed,
+        },
+        None,
+    );
+    report.finish(&result.clone().map_err(|err| err.message));
+    match report.write(signing_key.as_deref()) {
+        Ok(path) => say!("{}", t!("wizard.report.written", path = path.display())),
+        Err(err) => warn!("{}", t!("wizard.report.failed", error = err.to_string())),
+    }
+    result
+}
+
+async fn run_wizzard_steps(mut config: Settings, report: &mut InstallReport) -> Result<(), EimError> {
+    debug!(
+        "{}",
+        t!(
+            "wizard.debug.config_entering",
+            config = format!("{:?}", config)
+        )
+    );
+
+    if let Some(project_dir) = config.project_dir.clone() {
+        if idf_im_lib::system_install::is_enabled(&config) {
+            return Err(EimError::new(ErrorKind::Usage, t!("wizard.project.system_conflict").to_string()));
+        }
+        idf_im_lib::project_install::apply_to_settings(&mut config, &project_dir)?;
+    }
+    if idf_im_lib::system_install::is_enabled(&config) {
+        idf_im_lib::system_install::prepare(&mut config)?;
+    }
+
+    let offline_mode = config.use_local_archive.is_some();
+    let download_only = config.download_only.unwrap_or(false);
+    if download_only && offline_mode {
+        return Err(EimError::new(ErrorKind::Usage, t!("wizard.download_only.offline_conflict").to_string()));
+    }
+    let offline_archive_dir = if offline_mode {
+        Some(TempDir::new().expect(&t!("wizard.error.create_temp_dir")))
+    } else {
+        None
+    };
+
+    if offline_mode {
+        // only setting up temp directory if offline mode is enabled
+        let archive_dir = offline_archive_dir.as_ref().unwrap();
+        config = match use_offline_archive(config, archive_dir) {
+            Ok(updated_config) => updated_config,
+            Err(err) => {
+                error!("Failed to use offline archive: {}", err);
+                return Err(err.into());
+            }
+        };
+        // install prerequisites offline
+        if std::env::consts::OS == "windows" {
+            match install_prerequisites_offline(&archive_dir) {
+                Ok(_) => {
+                    info!("{}", t!("wizard.prerequisites.offline_install.success"));
+                }
+                Err(err) => {
+                    return Err(EimError::new(
+                        ErrorKind::Prerequisites,
+                        t!("wizard.error.prerequisites_offline_install", error = err.to_string()),
+                    ));
+                }
+            }
+        }
+    }
+
+    let stage_start = Instant::now();
+    idf_im_lib::metrics::set_stage("prerequisites", None);
+    terminal::announce(Milestone::Prerequisites, None);
+    if config.skip_prerequisites_check.unwrap_or(false) {
+        info!("{}", t!("wizard.prerequisites.skip_check"));
+    } else {
+        // Check prerequisites, a download-only run needs git but installs nothing
+        check_and_install_prerequisites(
+            config.non_interactive.unwrap_or_default(),
+            config.install_all_prerequisites.unwrap_or_default() && !download_only,
+        )
+        .map_err(|err| EimError::new(ErrorKind::Prerequisites, err))?;
+    }
+
+    // Python sanity check
+    check_and_install_python(
+        config.non_interactive.unwrap_or_default(),
+        config.install_all_prerequisites.unwrap_or_default() && !download_only,
+        config.python_version_override.clone(),
+    )
+    .map_err(|err| EimError::new(ErrorKind::Prerequisites, err))?;
+    report.record_stage("prerequisites", None, stage_start.elapsed());
+
+    if !download_only {
+        if let Err(err) = select_and_install_drivers(
+            config.non_interactive.unwrap_or_default(),
+            config.install_drivers,
+        )
+        .await
+        {
+            warn!("{}", t!("wizard.drivers.failed", error = err));
+        }
+    }
+
+    if offline_mode {
+        let archive_dir = offline_archive_dir.as_ref().unwrap();
+        // copy IDFs
+        copy_idf_from_offline_archive(archive_dir, &config)?;
+    }
+
+    // select target & idf version
+    config = select_targets_and_versions(config).await?;
+
+    // mirrors select
+    config = select_mirrors(config).await?;
+    report.set_mirrors(&config);
+
+    // versions fetched with --download-only before are installed without network
+    let finishing_download = !download_only && idf_im_lib::download_only::all_downloaded(&config);
+    if finishing_download {
+        info!("{}", t!("wizard.download_only.finishing"));
+    }
+    if !offline_mode && !finishing_download && !config.skip_network_preflight.unwrap_or(false) {
+        run_network_preflight(&config)
+            .await
+            .map_err(|err| EimError::new(ErrorKind::Network, err))?;
+    }
+
+    config = select_installation_path(config)?;
+    idf_im_lib::version_manager::check_version_name(&config)?;
+    config = select_components(config)?;
+    config = select_system_tools(config)?;
+    if !offline_mode && !confirm_versions(&config).await? {
+        return Err(EimError::new(ErrorKind::Cancelled, t!("wizard.confirm_versions.cancelled").to_string()));
+    }
+    let omitted_components = idf_im_lib::components::omitted_components(&config)?;
+    if idf_im_lib::wsl::is_running_in_wsl() {
+        warn_about_wsl_installation_path(&config);
+    }
+
+    // initialize the per-version map if not already set
+    if config.idf_features_per_version.is_none() {
+        config.idf_features_per_version = Some(HashMap::new());
+    }
+    // Multiple version starts here
+    let mut using_existing_idf = false;
+    for idf_version in config.idf_versions.clone().unwrap() {
+        let paths = config.get_version_paths(&idf_version).map_err(|err| {
+            error!("Failed to get version paths: {}", err);
+            err.to_string()
+        })?;
+        using_existing_idf = paths.using_existing_idf;
+        if !download_only {
+            idf_im_lib::hooks::run_hooks(HookStage::PreInstall, &config, &paths)?;
+        }
+        let downloaded = if download_only {
+            None
+        } else {
+            idf_im_lib::download_only::load(&idf_im_lib::download_only::download_dir(&config, &paths), &idf_version)
+        };
+
+        let snapshot = idf_im_lib::install_manifest::PreInstallSnapshot::take(&paths);
+        // A version which fails half way through is removed again, so it does not show up as broken install
+        let mut transaction = InstallTransaction::new(&format!("installation of {}", idf_version));
+        if !paths.using_existing_idf && !paths.version_installation_path.exists() {
+            transaction.register_created_path(&paths.version_installation_path);
+        }
+
+        config.idf_path = Some(paths.idf_path.clone());
+        idf_im_lib::add_path_to_path(paths.idf_path.to_str().unwrap());
+
+
+        let features = if let Some(downloaded) = &downloaded {
+          config.get_features_for_version_if_set(&idf_version).unwrap_or_else(|| downloaded.features.clone())
+        } else if !offline_mode {
+          let req_url = get_requirements_json_url(config.repo_stub.clone().as_deref(), &idf_version.to_string(), config.idf_mirror.clone().as_deref());
+
+          let requirements_files = match RequirementsMetadata::from_url(&req_url) {
+              Ok(files) => files,
+              Err(err) => {
+                  warn!("{}: {}. {}", t!("wizard.requirements.read_failure"), err, t!("wizard.features.selection_unavailable"));
+                  return Err(EimError::new(ErrorKind::Network, err.to_string()));
+              }
+          };
+
+          // Check if we already have features for this version (from CLI arg or config file)
+          let f = if let Some(existing) = config.get_features_for_version_if_set(&idf_version) {
+              // Convert feature names back to FeatureInfo
+              requirements_files.features
+                  .iter()
+                  .filter(|f| existing.contains(&f.name))
+                  .cloned()
+                  .collect()
+          } else {
+              // Interactive selection for this version
+              select_features(
+                  &requirements_files,
+                  config.non_interactive.unwrap_or_default(),
+                  true,
+              )?
+          };
+          // Save to per-version map
+          if let Some(ref mut per_version) = config.idf_features_per_version {
+            per_version.insert(
+              idf_version.clone(),
+              f.iter().map(|f| f.name.clone()).collect(),
+            );
+          }
+          f.iter().map(|f| f.name.clone()).collect::<Vec<String>>()
+        } else {
+          config.get_features_for_version_if_set(&idf_version).unwrap_or(vec![])
+        };
+
+        let features = idf_im_lib::components::filter_features(features, &omitted_components);
+        debug!(
+            "{}: {}",
+            t!("wizard.features.selected"),
+            features
+                .join(", ")
+        );
+
+
+        let stage_start = Instant::now();
+        idf_im_lib::metrics::set_stage("download_idf", Some(&idf_version));
+        terminal::announce(Milestone::Download, Some(&idf_version));
+        if !using_existing_idf {
+            // download idf
+            let download_config = DownloadConfig {
+                idf_path: paths.idf_path.to_str().unwrap().to_string(),
+                repo_stub: config.repo_stub.clone(),
+                idf_version: idf_version.to_string(),
+                idf_mirror: config.idf_mirror.clone(),
+                recurse_submodules: config.recurse_submodules,
+                non_interactive: config.non_interactive,
+            };
+
+            match download_idf(download_config) {
+                Ok(_) => {
+                    debug!("{}", t!("wizard.idf.sucess"));
+                    idf_im_lib::components::apply_to_repository_or_warn(&paths.idf_path, &config);
+                }
+                Err(DownloadError::PathCreationFailed(err)) => {
+                    error!("{} {:?}", t!("wizard.idf.path_creation_failure"), err);
+                    return Err(EimError::new(ErrorKind::Disk, err));
+                }
+                Err(DownloadError::DownloadFailed(err)) => {
+                    error!("{} {:?}", t!("wizard.idf.failure"), err);
+                    return Err(EimError::new(ErrorKind::Network, err));
+                }
+                Err(DownloadError::UserCancelled) => {
+                    error!("{}", t!("wizard.idf.user_cancelled"));
+                    return Err(EimError::new(ErrorKind::Cancelled, "User cancelled the operation"));
+                }
+            }
+        }
+        report.record_stage("download_idf", Some(&idf_version), stage_start.elapsed());
+        // setup tool directories
+
+        let tool_download_directory = setup_directory(
+            config.wizard_all_questions,
+            &paths.version_installation_path,
+            &mut config.tool_download_folder_name,
+            "wizard.tools.download.prompt",
+            DEFAULT_TOOLS_DOWNLOAD_FOLDER,
+        )?;
+
+        if offline_mode {
+            // copy content dist from offline_archive_dir
+            copy_dir_contents(
+                &offline_archive_dir.as_ref().unwrap().path().join("dist"),
+                &tool_download_directory,
+            )
+            .map_err(|err| t!("wizard.error.copy_dist_directory", error = err.to_string()).to_string())?;
+        }
+
+        // Setup install directory
+        let tool_install_directory = setup_directory(
+            config.wizard_all_questions,
+            &paths.version_installation_path,
+            &mut config.tool_install_folder_name,
+            "wizard.tools.install.prompt",
+            DEFAULT_TOOLS_INSTALL_FOLDER,
+        )?;
+
+        idf_im_lib::add_path_to_path(tool_install_directory.to_str().unwrap());
+
+        // tools_json_file
+
+        let (tools_json_file, validated_file) = match config.tools_manifest.clone() {
+            Some(manifest) => {
+                let file = idf_im_lib::idf_tools::resolve_tools_manifest(&manifest, &tool_download_directory)
+                    .await
+                    .map_err(|err| t!("wizard.tools_manifest.failed", error = err.to_string()).to_string())?;
+                let validated = file.to_str().unwrap().to_string();
+                (file, validated)
+            }
+            None => {
+                let file = get_tools_json_path(&mut config, &paths.idf_path);
+                let validated = validate_tools_json_file(&file, &mut config);
+                (file, validated)
+            }
+        };
+
+        debug!(
+            "{}",
+            t!(
+                "wizard.debug.tools_json_file",
+                path = tools_json_file.display()
+            )
+        );
+
+        let tools = idf_im_lib::idf_tools::read_and_parse_tools_file(&validated_file)
+            .map_err(|err| format!("{}: {}", t!("wizard.tools_json.unparsable"), err))?;
+        let tool_version_overrides = config.tool_version_overrides.clone().unwrap_or_default();
+        let tools = idf_im_lib::idf_tools::apply_tool_version_overrides(tools, &tool_version_overrides)
+            .map_err(|err| t!("wizard.tool_overrides.failed", error = err.to_string()).to_string())?;
+        let tools = if config.install_qemu.unwrap_or(false) {
+            tools
+        } else {
+            idf_im_lib::qemu::without_qemu(tools)
+        };
+        let tools = idf_im_lib::system_tools::without_system_tools(tools, &idf_im_lib::system_tools::selected(&config));
+
+        if download_only {
+            fetch_version_downloads(&config, &paths, &idf_version, &tools, &tool_download_directory, &features).await?;
+            // the ESP-IDF repository stays for the installation finishing the download
+            transaction.commit();
+            continue;
+        }
+
+        let stage_start = Instant::now();
+        idf_im_lib::metrics::set_stage("tools", Some(&idf_version));
+        terminal::announce(Milestone::Tools, Some(&idf_version));
+        let installed_tools_list = match download_and_extract_tools(
+            &config,
+            &tools,
+            &tool_download_directory,
+            &tool_install_directory,
+        )
+        .await
+        {
+            Ok(list) => {
+                info!(
+                    "{}: {}",
+                    t!("wizard.tools.downloaded"),
+                    tools_json_file.display()
+                );
+                list
+            }
+            Err(err) => {
+                error!("Failed to download and extract tools: {}", err);
+                return Err(idf_im_lib::antivirus::diagnose(&err, &config).unwrap_or_else(|| err.into()));
+            }
+        };
+
+        report.record_stage("tools", Some(&idf_version), stage_start.elapsed());
+        if config.install_qemu.unwrap_or(false) {
+            smoke_test_qemu(&tool_install_directory);
+        }
+        report.add_version(&paths, &installed_tools_list);
+
+        let stage_start = Instant::now();
+        idf_im_lib::metrics::set_stage("python_environment", Some(&idf_version));
+        terminal::announce(Milestone::Python, Some(&idf_version));
+        let downloaded_packages = downloaded
+            .as_ref()
+            .map(|_| idf_im_lib::download_only::packages_dir(&tool_download_directory, &idf_version));
+        match idf_im_lib::python_utils::install_python_env(
+            &paths,
+            &paths.actual_version,
+            &tool_install_directory,
+            true, //TODO: actually read from config
+            &features,
+            if offline_mode {
+                Some(offline_archive_dir.as_ref().unwrap().path())
+            } else {
+                downloaded_packages.as_deref()
+            },
+            &config.pypi_mirror,
+        )
+        .await
+        {
+            Ok(_) => {
+                info!("{}", t!("wizard.python.env_installed"));
+            }
+            Err(err) => {
+                error!("Failed to install Python environment: {}", err);
+                return Err(idf_im_lib::antivirus::diagnose(&anyhow!(err.to_string()), &config)
+                    .unwrap_or_else(|| EimError::new(ErrorKind::Python, err.to_string())));
+            }
+        };
+
+        ensure_path(paths.python_venv_path.to_str().unwrap())
+            .map_err(|err| t!("wizard.error.create_python_env", error = err.to_string()).to_string())?;
+        report.record_stage("python_environment", Some(&idf_version), stage_start.elapsed());
+        let stage_start = Instant::now();
+        idf_im_lib::metrics::set_stage("post_install", Some(&idf_version));
+        terminal::announce(Milestone::Configure, Some(&idf_version));
+
+        let mut export_paths: Vec<String> = idf_im_lib::idf_tools::get_tools_export_paths_from_list(
+            tools,
+            installed_tools_list,
+            tool_install_directory.to_str().unwrap(),
+        )
+        .into_iter()
+        .map(|p| {
+            if std::env::consts::OS == "windows" {
+                idf_im_lib::replace_unescaped_spaces_win(&p)
+            } else {
+                p
+            }
+        })
+        .collect();
+        let mut env_vars = idf_im_lib::default_environment_variables(
+            tool_install_directory.to_str().unwrap(),
+            paths.idf_path.to_str().unwrap(),
+            paths.python_venv_path.to_str(),
+        );
+        if config.enable_ccache.unwrap_or(false) {
+            match idf_im_lib::idf_tools::setup_ccache(
+                Path::new(&validated_file),
+                &tool_download_directory,
+                &tool_install_directory,
+                config.mirror.as_deref(),
+                config.tools_url_rewrite.as_deref(),
+            )
+            .await
+            {
+                Ok((vars, ccache_paths)) => {
+                    env_vars.extend(vars);
+                    export_paths.extend(ccache_paths);
+                }
+                Err(err) => warn!("{}", t!("wizard.ccache.failed", error = err.to_string())),
+            }
+        }
+        if config.rust_toolchain.unwrap_or(false) {
+            match idf_im_lib::esp_rs::install_rust_toolchain(
+                &tool_install_directory,
+                &config.target.clone().unwrap_or_default(),
+            )
+            .await
+            {
+                Ok((vars, rust_paths)) => {
+                    env_vars.extend(vars);
+                    export_paths.extend(rust_paths);
+                    info!("{}", t!("wizard.rust.installed"));
+                }
+                Err(err) => warn!("{}", t!("wizard.rust.failed", error = err.to_string())),
+            }
+        }
+        if idf_im_lib::component_manager::is_enabled(&config) || offline_mode {
+            let archive_dir = offline_archive_dir.as_ref().map(|dir| dir.path());
+            match idf_im_lib::component_manager::setup(&config, &paths, archive_dir) {
+                Ok(vars) => env_vars.extend(vars),
+                Err(err) => warn!("{}", t!("wizard.component_manager.failed", error = err.to_string())),
+            }
+        }
+        export_paths.splice(0..0, idf_im_lib::custom_toolchains::export_paths());
+        idf_im_lib::single_version_post_install(
+            &paths.activation_script_path.to_str().unwrap(),
+            paths.idf_path.to_str().unwrap(),
+            &paths.actual_version,
+            tool_install_directory.to_str().unwrap(),
+            export_paths,
+            paths.python_venv_path.to_str(),
+            Some(env_vars),
+        );
+        transaction.commit();
+        if config.dedupe_tools.unwrap_or(true) {
+            if let Err(err) = idf_im_lib::dedupe::dedupe_new_installation(&tool_install_directory) {
+                warn!("{}", t!("dedupe.failed", error = err.to_string()));
+            }
+        }
+        let mut warnings = idf_im_lib::idf_tools::tool_version_override_warnings(&tool_version_overrides);
+        if idf_im_lib::system_install::is_enabled(&config) {
+            if let Err(err) = idf_im_lib::system_install::finish_version(&config, &paths) {
+                warn!("{}", t!("wizard.system_install.failed", error = err.to_string()));
+                warnings.push(err.to_string());
+            }
+        }
+        if let Err(err) = idf_im_lib::install_manifest::record_version_manifest(&paths, &snapshot, &config, &warnings, &omitted_components) {
+            warn!("{}", t!("wizard.manifest.record_failed", error = err.to_string()));
+        }
+        report.add_warnings(&warnings);
+        if let Err(err) = idf_im_lib::hooks::run_hooks(HookStage::PostInstall, &config, &paths) {
+            warn!("{}", t!("wizard.hooks.post_install_failed", error = err.to_string()));
+            report.add_warnings(&[err.to_string()]);
+        }
+        report.record_stage("post_install", Some(&idf_version), stage_start.elapsed());
+    }
+    if download_only {
+        say!("{}", t!("wizard.download_only.done"));
+        return Ok(());
+    }
+    save_config_if_desired(&config)?;
+    let ide_conf_path_tmp = PathBuf::from(&config.esp_idf_json_path.clone().unwrap_or_default());
+    debug!(
+        "{}",
+        t!(
+            "wizard.debug.ide_config_path",
+            path = ide_conf_path_tmp.display()
+        )
+    );
+    match ensure_path(ide_conf_path_tmp.to_str().unwrap()) {
+        Ok(_) => (),
+        Err(err) => {
+            error!("Failed to create IDE configuration directory: {}", err);
+            let kind = classify_io_error(&err).unwrap_or(ErrorKind::General);
+            return Err(EimError::new(kind, err.to_string()));
+        }
+    }
+    match config.save_esp_ide_json() {
+        Ok(_) => debug!("{}", t!("wizard.debug.ide_config_saved")),
+        Err(err) => {
+            error!("Failed to save IDE configuration: {}", err);
+            return Err(err.into());
+        }
+    };
+    if idf_im_lib::system_install::is_enabled(&config) {
+        if let Err(err) = idf_im_lib::system_install::finish_installation(&config) {
+            warn!("{}", t!("wizard.system_install.failed", error = err.to_string()));
+        }
+    }
+
+    if std::env::consts::OS == "windows" && config.powershell_integration.unwrap_or(false) {
+        install_powershell_integration(&config);
+    }
+
+    match std::env::consts::OS {
+        "windows" => {
+            say!("{}", t!("wizard.windows.finish_steps.line_1"));
+            say!("{}", t!("wizard.windows.finish_steps.line_2"));
+        }
+        _ => {
+            say!("{}", t!("wizard.posix.finish_steps.line_1"));
+            say!("{}", t!("wizard.posix.finish_steps.line_2"));
+            say!("{}", t!("wizard.posix.finish_steps.line_3"));
+            say!("============================================");
+            say!("{}:", t!("wizard.posix.finish_steps.line_4"));
+            for idf_version in config.idf_versions.clone().unwrap() {
+                let paths = config.get_version_paths(&idf_version).map_err(|err| {
+                    error!("Failed to get version paths: {}", err);
+                    err.to_string()
+                })?;
+                say!(
+                    "       {} \"{}\"",
+                    t!("wizard.posix.finish_steps.line_5"),
+                    paths.activation_script.display()
+                );
+            }
+            say!("============================================");
+            if idf_im_lib::wsl::is_running_in_wsl() {
+                say!("{}", t!("wsl.usb.line_1"));
+                say!("       usbipd list");
+                say!("       usbipd attach --wsl --busid <BUSID>");
+                say!("{}", t!("wsl.usb.line_2"));
+            }
+        }
+    }
+    Ok(())
+}
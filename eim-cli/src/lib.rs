@@ -0,0 +1,20 @@
+//! Command line interface of the ESP-IDF Installation Manager.
+//!
+//! It is run by the `eim` binary of this crate, which does not link the Tauri GUI, and by the
+//! `eim-gui` binary, which additionally starts the GUI when run without a command.
+
+use log::{debug, info};
+
+pub mod cli;
+
+rust_i18n::i18n!("../eim-core/locales", fallback = "en");
+
+pub fn set_locale(locale: &Option<String>) {
+    match locale {
+        Some(l) => {
+            rust_i18n::set_locale(l);
+            info!("Set locale to: {}", l);
+        }
+        None => debug!("No locale specified, defaulting to en"),
+    }
+}
@@ -0,0 +1,23 @@
+//! `eim`, the command line interface. It does not link the webview libraries, `eim gui` runs
+//! the `eim-gui` binary installed next to it.
+
+use clap::Parser;
+use eim_cli::cli;
+use idf_im_lib::settings::Settings;
+
+#[tokio::main]
+async fn main() {
+    idf_im_lib::crash_report::run_monitor_if_requested();
+    match Settings::default().initialize_esp_ide_json() {
+        Ok(_) => log::debug!("ESP-IDF JSON initialized successfully."),
+        Err(e) => log::warn!("Failed to initialize ESP-IDF JSON: {}. It is possible you will face issues with persistance and IDE integration.", e),
+    };
+    let cli = cli::cli_args::Cli::parse();
+    eim_cli::set_locale(&cli.locale);
+    let json_output = cli.json;
+
+    match cli::run_cli(cli, None).await {
+        Ok(_) => std::process::exit(0),
+        Err(e) => std::process::exit(cli::report_error(&e, json_output)),
+    }
+}
@@ -369,6 +369,10 @@ struct Args {
     #[arg(long)]
     build_all_versions: bool,
 
+    /// Add a mirror of the managed components used by the examples, so they install offline
+    #[arg(long)]
+    component_mirror: bool,
+
     /// List all supported IDF versions in machine-readable format and exit
     /// Output format: one version per line
     #[arg(long)]
@@ -670,6 +674,32 @@ async fn main() {
                 continue;
             }
 
+            // Mirror of the managed components of the examples, picked up by the wizard
+            let component_mirror = args.component_mirror || settings.component_mirror.unwrap_or(false);
+            if let Some(wheel_version) = wheel_versions.first().filter(|_| component_mirror) {
+                let python_env = archive_dir
+                    .path()
+                    .join(format!("python_env_{}", wheel_version.replace('.', "_")));
+                let python = match std::env::consts::OS {
+                    "windows" => python_env.join("Scripts/python.exe"),
+                    _ => python_env.join("bin/python"),
+                };
+                let mirror = archive_dir.path().join(idf_im_lib::component_manager::MIRROR_DIR_NAME);
+                let synced = idf_im_lib::component_manager::ensure_installed(&python, settings.pypi_mirror.as_deref())
+                    .and_then(|_| {
+                        idf_im_lib::component_manager::sync_example_components(
+                            &python,
+                            &idf_path,
+                            &mirror,
+                            settings.component_registry_url.as_deref(),
+                        )
+                    });
+                match synced {
+                    Ok(_) => info!("Component mirror created in {}", mirror.display()),
+                    Err(e) => warn!("Failed to create the component mirror for {}: {}", idf_version, e),
+                }
+            }
+
             // Save settings for this version
             let mut version_settings = settings.clone();
             version_settings.idf_versions = Some(vec![idf_version.clone()]);
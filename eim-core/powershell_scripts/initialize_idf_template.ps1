@@ -0,0 +1,24 @@
+# >>> eim Initialize-Idf >>>
+# Auto-generated - Installed via ESP-IDF Manager
+# Activates an installed ESP-IDF version, the selected one (see 'eim select') if none is given.
+function Initialize-Idf {
+    param([string]$Version)
+
+    $configPath = "{{eim_idf_json}}"
+    if (-not (Test-Path $configPath)) {
+        Write-Error "ESP-IDF configuration not found at: $configPath"
+        return
+    }
+    $config = Get-Content -Raw $configPath | ConvertFrom-Json
+    if ($Version) {
+        $installation = $config.idfInstalled | Where-Object { $_.name -eq $Version -or $_.id -eq $Version } | Select-Object -First 1
+    } else {
+        $installation = $config.idfInstalled | Where-Object { $_.id -eq $config.idfSelectedId } | Select-Object -First 1
+    }
+    if (-not $installation) {
+        Write-Error "ESP-IDF version not found: $Version"
+        return
+    }
+    . $installation.activationScript
+}
+# <<< eim Initialize-Idf <<<
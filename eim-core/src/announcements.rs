@@ -0,0 +1,216 @@
+use rust_i18n::t;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two announcements, so a screen reader finishes reading one before the next
+pub const MIN_ANNOUNCEMENT_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Stages of an installation announced to assistive technology, one announcement each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Milestone {
+    Checking,
+    Prerequisites,
+    Download,
+    Tools,
+    Python,
+    Configure,
+    Complete,
+    Failed,
+    Cancelled,
+}
+
+impl Milestone {
+    /// The end of an installation, announced at once regardless of the rate limit.
+    pub fn is_final(self) -> bool {
+        matches!(self, Milestone::Complete | Milestone::Failed | Milestone::Cancelled)
+    }
+
+    /// Human-readable text of the milestone, prefixed with `version` when it concerns a single one.
+    pub fn text(self, version: Option<&str>) -> String {
+        let message = match self {
+            Milestone::Checking => t!("announcements.checking"),
+            Milestone::Prerequisites => t!("announcements.prerequisites"),
+            Milestone::Download => t!("announcements.download"),
+            Milestone::Tools => t!("announcements.tools"),
+            Milestone::Python => t!("announcements.python"),
+            Milestone::Configure => t!("announcements.configure"),
+            Milestone::Complete => t!("announcements.complete"),
+            Milestone::Failed => t!("announcements.failed"),
+            Milestone::Cancelled => t!("announcements.cancelled"),
+        };
+        match version {
+            Some(version) => t!("announcements.with_version", version = version, message = message).to_string(),
+            None => message.to_string(),
+        }
+    }
+}
+
+/// A milestone worded for a screen reader. `assertive` ones interrupt what is being read, the
+/// `aria-live` politeness the GUI gives them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Announcement {
+    pub milestone: Milestone,
+    pub version: Option<String>,
+    pub message: String,
+    pub assertive: bool,
+}
+
+impl Announcement {
+    pub fn new(milestone: Milestone, version: Option<&str>) -> Self {
+        Self {
+            milestone,
+            version: version.map(str::to_string),
+            message: milestone.text(version),
+            assertive: matches!(milestone, Milestone::Failed | Milestone::Cancelled),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AnnounceDecision {
+    Announce(Announcement),
+    /// Announced before, or superseded by a later milestone
+    Skip,
+    Defer,
+    /// Deferred, and the pending announcement has to be taken after the delay
+    DeferAndFlushIn(Duration),
+}
+
+/// Turns the stream of progress updates into discrete announcements.
+///
+/// Every milestone of a version is announced once. Announcements come at most once per
+/// `interval`; one arriving sooner waits and is replaced by any later one, so stages which
+/// pass quickly are not read out after the installation moved on. Final milestones are
+/// announced at once.
+#[derive(Debug)]
+pub struct Announcer {
+    interval: Duration,
+    announced: HashSet<(Milestone, Option<String>)>,
+    last_announced: Option<Instant>,
+    pending: Option<Announcement>,
+    flush_scheduled: bool,
+}
+
+impl Default for Announcer {
+    fn default() -> Self {
+        Self::new(MIN_ANNOUNCEMENT_INTERVAL)
+    }
+}
+
+impl Announcer {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            announced: HashSet::new(),
+            last_announced: None,
+            pending: None,
+            flush_scheduled: false,
+        }
+    }
+
+    pub fn submit(&mut self, milestone: Milestone, version: Option<&str>, now: Instant) -> AnnounceDecision {
+        let key = (milestone, version.map(str::to_string));
+        let is_pending = self
+            .pending
+            .as_ref()
+            .is_some_and(|p| p.milestone == milestone && p.version == key.1);
+        if is_pending || self.announced.contains(&key) {
+            return AnnounceDecision::Skip;
+        }
+        if let Some(superseded) = self.pending.take() {
+            self.announced.insert((superseded.milestone, superseded.version));
+        }
+
+        let announcement = Announcement::new(milestone, version);
+        let elapsed = self.last_announced.map(|last| now.saturating_duration_since(last));
+        match elapsed {
+            Some(elapsed) if elapsed < self.interval && !milestone.is_final() => {
+                self.pending = Some(announcement);
+                if self.flush_scheduled {
+                    AnnounceDecision::Defer
+                } else {
+                    self.flush_scheduled = true;
+                    AnnounceDecision::DeferAndFlushIn(self.interval - elapsed)
+                }
+            }
+            _ => AnnounceDecision::Announce(self.mark_announced(announcement, now)),
+        }
+    }
+
+    /// The deferred announcement, once its delay passed.
+    pub fn take_pending(&mut self, now: Instant) -> Option<Announcement> {
+        self.flush_scheduled = false;
+        let pending = self.pending.take()?;
+        Some(self.mark_announced(pending, now))
+    }
+
+    /// Forgets what was announced, for the next installation.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.interval);
+    }
+
+    fn mark_announced(&mut self, announcement: Announcement, now: Instant) -> Announcement {
+        self.announced
+            .insert((announcement.milestone, announcement.version.clone()));
+        self.last_announced = Some(now);
+        announcement
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announced(decision: AnnounceDecision) -> Option<Milestone> {
+        match decision {
+            AnnounceDecision::Announce(announcement) => Some(announcement.milestone),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_each_milestone_is_announced_once() {
+        let mut announcer = Announcer::new(Duration::from_secs(3));
+        let start = Instant::now();
+        assert_eq!(
+            announced(announcer.submit(Milestone::Download, Some("v5.4"), start)),
+            Some(Milestone::Download)
+        );
+        let later = start + Duration::from_secs(10);
+        assert_eq!(announcer.submit(Milestone::Download, Some("v5.4"), later), AnnounceDecision::Skip);
+        assert_eq!(
+            announced(announcer.submit(Milestone::Download, Some("v5.3"), later)),
+            Some(Milestone::Download)
+        );
+    }
+
+    #[test]
+    fn test_announcements_are_rate_limited() {
+        let mut announcer = Announcer::new(Duration::from_secs(3));
+        let start = Instant::now();
+        announcer.submit(Milestone::Prerequisites, None, start);
+
+        let soon = start + Duration::from_secs(1);
+        assert_eq!(
+            announcer.submit(Milestone::Download, Some("v5.4"), soon),
+            AnnounceDecision::DeferAndFlushIn(Duration::from_secs(2))
+        );
+        // the tools replace the download, which is then never announced
+        assert_eq!(announcer.submit(Milestone::Tools, Some("v5.4"), soon), AnnounceDecision::Defer);
+        let pending = announcer.take_pending(start + Duration::from_secs(3)).unwrap();
+        assert_eq!(pending.milestone, Milestone::Tools);
+        assert_eq!(
+            announcer.submit(Milestone::Download, Some("v5.4"), start + Duration::from_secs(20)),
+            AnnounceDecision::Skip
+        );
+
+        // the end of the installation is not held back
+        let failed = announcer.submit(Milestone::Failed, None, start + Duration::from_secs(4));
+        match failed {
+            AnnounceDecision::Announce(announcement) => assert!(announcement.assertive),
+            other => panic!("expected an announcement, got {:?}", other),
+        }
+    }
+}
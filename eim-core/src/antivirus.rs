@@ -0,0 +1,157 @@
+use log::{debug, warn};
+use rust_i18n::t;
+use std::io;
+use std::path::PathBuf;
+
+use crate::errors::{EimError, ErrorKind};
+use crate::settings::Settings;
+
+pub const EXCLUSIONS_SCRIPT_FILE_NAME: &str = "eim_av_exclusions.ps1";
+
+/// ERROR_ACCESS_DENIED, ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION, ERROR_VIRUS_INFECTED and ERROR_VIRUS_DELETED
+const INTERFERENCE_OS_ERRORS: &[i32] = &[5, 32, 33, 225, 226];
+
+/// Messages of tools (tar, 7z, python, git) failing on a file held or removed by a scanner
+const INTERFERENCE_MESSAGES: &[&str] = &[
+    "access is denied",
+    "being used by another process",
+    "contains a virus",
+    "potentially unwanted software",
+    "permission denied",
+];
+
+/// Returns true if `err` looks like an access denied or file in use failure, the way real-time
+/// scanning breaks extraction and freshly extracted tools.
+pub fn is_interference_pattern(err: &anyhow::Error) -> bool {
+    for cause in err.chain() {
+        if let Some(io_err) = cause.downcast_ref::<io::Error>() {
+            if io_err.kind() == io::ErrorKind::PermissionDenied
+                || io_err
+                    .raw_os_error()
+                    .is_some_and(|code| INTERFERENCE_OS_ERRORS.contains(&code))
+            {
+                return true;
+            }
+        }
+    }
+    let message = format!("{:#}", err).to_lowercase();
+    INTERFERENCE_MESSAGES.iter().any(|pattern| message.contains(pattern))
+}
+
+/// Returns the names of the antivirus products scanning files in real time.
+///
+/// Only Windows is checked: Microsoft Defender through `Get-MpComputerStatus`, other products
+/// through the Security Center. Elsewhere the list is empty.
+pub fn detect_realtime_scanners() -> Vec<String> {
+    if std::env::consts::OS != "windows" {
+        return Vec::new();
+    }
+    let script = r#"
+$names = @()
+try { if ((Get-MpComputerStatus -ErrorAction Stop).RealTimeProtectionEnabled) { $names += 'Microsoft Defender' } } catch {}
+try {
+    Get-CimInstance -Namespace root/SecurityCenter2 -ClassName AntivirusProduct -ErrorAction Stop | ForEach-Object {
+        # bits 12-15 of productState are 0x1 when real-time protection is on
+        if ((($_.productState -shr 12) -band 0xF) -eq 1 -and $_.displayName -notlike '*Defender*') { $names += $_.displayName }
+    }
+} catch {}
+$names -join "`n"
+"#;
+    match crate::run_powershell_script(script) {
+        Ok(output) => output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(e) => {
+            debug!("Failed to query antivirus status: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Directories an administrator should exclude from real-time scanning for the installation.
+pub fn exclusion_paths(settings: &Settings) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = [
+        settings.path.clone(),
+        settings.tool_install_folder_name.clone().map(PathBuf::from),
+        settings.tool_download_folder_name.clone().map(PathBuf::from),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    paths.dedup();
+    paths
+}
+
+/// PowerShell commands adding the Microsoft Defender exclusions, to be run elevated.
+pub fn exclusion_script(paths: &[PathBuf]) -> String {
+    let mut script = String::from("# Run in an elevated PowerShell to exclude the ESP-IDF directories from real-time scanning\n");
+    for path in paths {
+        script.push_str(&format!(
+            "Add-MpPreference -ExclusionPath '{}'\n",
+            path.display().to_string().replace('\'', "''")
+        ));
+    }
+    script
+}
+
+fn write_exclusion_script(paths: &[PathBuf]) -> Option<PathBuf> {
+    let path = crate::get_log_directory()?.join(EXCLUSIONS_SCRIPT_FILE_NAME);
+    match std::fs::write(&path, exclusion_script(paths)) {
+        Ok(()) => Some(path),
+        Err(e) => {
+            warn!("Failed to write {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Turns a failed extraction or tool run into an `ErrorKind::Antivirus` error when it matches
+/// the access denied / file in use patterns and a real-time scanner is active.
+///
+/// The error names the scanners and the directories to exclude, which are also written to a
+/// PowerShell script next to the log files.
+pub fn diagnose(err: &anyhow::Error, settings: &Settings) -> Option<EimError> {
+    if !is_interference_pattern(err) {
+        return None;
+    }
+    let scanners = detect_realtime_scanners();
+    if scanners.is_empty() {
+        return None;
+    }
+    let paths = exclusion_paths(settings);
+    let script = write_exclusion_script(&paths)
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+    let path_list: Vec<String> = paths.iter().map(|p| format!("  {}", p.display())).collect();
+    Some(EimError::new(
+        ErrorKind::Antivirus,
+        t!(
+            "antivirus.interference",
+            error = format!("{:#}", err),
+            scanners = scanners.join(", "),
+            paths = path_list.join("\n"),
+            script = script
+        )
+        .to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interference_patterns() {
+        let err = anyhow::Error::new(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(is_interference_pattern(&err));
+        let err = anyhow::anyhow!("The process cannot access the file because it is being used by another process");
+        assert!(is_interference_pattern(&err));
+        assert!(!is_interference_pattern(&anyhow::anyhow!("Checksum mismatch")));
+
+        let script = exclusion_script(&[PathBuf::from("C:\\Espressif's tools")]);
+        assert!(script.contains("Add-MpPreference -ExclusionPath 'C:\\Espressif''s tools'"));
+    }
+}
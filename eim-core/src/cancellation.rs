@@ -0,0 +1,176 @@
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::errors::{EimError, ErrorKind};
+
+/// Time a subprocess has to exit after being asked to terminate, before it is killed
+pub const TERMINATE_GRACE: Duration = Duration::from_secs(3);
+
+/// Set when the running operation is cancelled. Also passed to gix as its interrupt flag, so
+/// clones and fetches stop at the next object.
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Process ids of the subprocesses (git, pip, the installer process of the GUI on Windows)
+/// started by the running operation
+static CHILDREN: Lazy<Mutex<HashSet<u32>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Starts a new operation, or ends the cancelled one: forgets the cancellation, so later
+/// commands run again.
+pub fn reset() {
+    CANCELLED.store(false, Ordering::SeqCst);
+}
+
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// The flag gix polls to interrupt clones and fetches.
+pub fn interrupt_flag() -> &'static AtomicBool {
+    &CANCELLED
+}
+
+/// Fails with [`ErrorKind::Cancelled`] once the operation was cancelled, called between steps.
+pub fn check() -> Result<(), EimError> {
+    if is_cancelled() {
+        return Err(EimError::new(ErrorKind::Cancelled, "The operation was cancelled"));
+    }
+    Ok(())
+}
+
+/// Waits until the operation is cancelled, to race long awaits with.
+pub async fn cancelled() {
+    while !is_cancelled() {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+/// Keeps a subprocess registered for termination on cancel while it runs.
+pub struct ChildGuard(u32);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Ok(mut children) = CHILDREN.lock() {
+            children.remove(&self.0);
+        }
+    }
+}
+
+/// Registers the subprocess `pid`, terminated by [`cancel`] until the guard is dropped. A
+/// process started after the cancellation is terminated right away.
+pub fn register(pid: u32) -> ChildGuard {
+    if let Ok(mut children) = CHILDREN.lock() {
+        children.insert(pid);
+    }
+    if is_cancelled() {
+        terminate(pid);
+    }
+    ChildGuard(pid)
+}
+
+/// Starts `command` in a process group of its own, so [`cancel`] reaches the processes it starts
+/// too. A no-op on Windows, where taskkill follows the process tree.
+pub fn own_process_group(command: &mut Command) -> &mut Command {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    command
+}
+
+/// Runs `command` to completion like [`Command::output`], registered for termination on cancel.
+pub fn output(command: &mut Command) -> std::io::Result<Output> {
+    if is_cancelled() {
+        return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "The operation was cancelled"));
+    }
+    let child = own_process_group(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let _guard = register(child.id());
+    child.wait_with_output()
+}
+
+/// Sends `signal` to the process group led by `pid`, or to `pid` alone when it was not started
+/// with [`own_process_group`].
+fn signal_group(signal: &str, pid: u32) {
+    let group = format!("-{}", pid);
+    let delivered = Command::new("kill")
+        .args([signal, "--", &group])
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    if !delivered {
+        let _ = Command::new("kill").args([signal, &pid.to_string()]).output();
+    }
+}
+
+/// Asks the process `pid` and the processes it started to exit, and kills them after
+/// [`TERMINATE_GRACE`]. git removes its lock files when terminated this way.
+fn terminate(pid: u32) {
+    match std::env::consts::OS {
+        "windows" => {
+            // taskkill can't ask console processes to exit, /T includes the children
+            let _ = Command::new("taskkill").args(["/PID", &pid.to_string(), "/T", "/F"]).output();
+        }
+        _ => {
+            signal_group("-TERM", pid);
+            std::thread::spawn(move || {
+                std::thread::sleep(TERMINATE_GRACE);
+                // still registered means still running, the id was not reused
+                let running = CHILDREN.lock().map(|children| children.contains(&pid)).unwrap_or(false);
+                if running {
+                    signal_group("-KILL", pid);
+                }
+            });
+        }
+    }
+}
+
+/// Cancels the running operation: sets the flag polled between steps and by gix, and terminates
+/// the registered subprocesses. Returns how many were terminated.
+pub fn cancel() -> usize {
+    CANCELLED.store(true, Ordering::SeqCst);
+    let children: Vec<u32> = CHILDREN
+        .lock()
+        .map(|children| children.iter().copied().collect())
+        .unwrap_or_default();
+    if !children.is_empty() {
+        info!("Terminating {} subprocesses of the cancelled operation", children.len());
+    }
+    for pid in &children {
+        terminate(*pid);
+    }
+    children.len()
+}
+
+/// Cancels like [`cancel`] and waits until the terminated subprocesses exited, at most
+/// [`TERMINATE_GRACE`] and a second, e.g. before the application exits.
+pub fn cancel_and_wait() {
+    cancel();
+    let deadline = std::time::Instant::now() + TERMINATE_GRACE + Duration::from_secs(1);
+    while std::time::Instant::now() < deadline {
+        if CHILDREN.lock().map(|children| children.is_empty()).unwrap_or(true) {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    warn!("Subprocesses of the cancelled operation are still running");
+}
+
+/// Removes the half extracted tools and the other staging folders a cancelled operation left
+/// behind. The caller has to hold the installer lock, see [`crate::recovery::clear_staging`].
+pub fn clean_up_after_cancel() {
+    let (count, _) = crate::recovery::clear_staging();
+    if count > 0 {
+        warn!("Removed {} staging folders left by the cancelled operation", count);
+    }
+}
+
@@ -0,0 +1,299 @@
+use log::{debug, warn};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::cell::Cell;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::sync_channel;
+use std::time::UNIX_EPOCH;
+
+use crate::settings::Settings;
+
+/// Size of the blocks handed from the reader thread to the hasher.
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+/// Number of blocks the reader may be ahead of the hasher.
+const CHUNKS_IN_FLIGHT: usize = 4;
+/// File next to the cached archives recording which of them were verified
+const VERIFIED_FILE: &str = ".eim-verified.json";
+
+static PARANOID: AtomicBool = AtomicBool::new(false);
+
+/// Makes every cached archive be hashed again, even if it was verified before and is unchanged.
+pub fn set_paranoid(paranoid: bool) {
+    PARANOID.store(paranoid, Ordering::Relaxed);
+}
+
+/// Applies the `paranoid` setting.
+pub fn configure_from_settings(settings: &Settings) {
+    set_paranoid(settings.paranoid.unwrap_or(false));
+}
+
+/// Computes the SHA256 of a file, reporting `(hashed, total)` bytes to `progress`.
+///
+/// SHA256 itself is sequential, so a single file cannot be split between cores. Instead
+/// reading runs on its own thread and hands large blocks to the hasher, so disk (or
+/// antivirus scanning) latency overlaps with the hashing instead of adding up.
+pub fn sha256_file_with_progress<F>(path: &Path, progress: F) -> io::Result<String>
+where
+    F: Fn(u64, u64),
+{
+    let mut file = File::open(path)?;
+    let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let (sender, receiver) = sync_channel::<io::Result<Vec<u8>>>(CHUNKS_IN_FLIGHT);
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || loop {
+            let mut buffer = vec![0; CHUNK_SIZE];
+            let mut filled = 0;
+            // fill the whole block, short reads are common on network drives
+            while filled < CHUNK_SIZE {
+                match file.read(&mut buffer[filled..]) {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        let _ = sender.send(Err(e));
+                        return;
+                    }
+                }
+            }
+            if filled == 0 {
+                return;
+            }
+            buffer.truncate(filled);
+            if sender.send(Ok(buffer)).is_err() {
+                return;
+            }
+        });
+
+        let mut hasher = Sha256::new();
+        let mut hashed = 0u64;
+        for chunk in receiver {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            hashed += chunk.len() as u64;
+            progress(hashed, total);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+}
+
+/// Computes the SHA256 of a file.
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    sha256_file_with_progress(path, |_, _| {})
+}
+
+/// Hashes many files at once, spreading them over all available cores.
+///
+/// Used for extracted trees, which consist of many files. The results are returned in
+/// the order of `paths`.
+pub fn sha256_files_parallel(paths: &[PathBuf]) -> Vec<io::Result<String>> {
+    paths.par_iter().map(|path| sha256_file(path)).collect()
+}
+
+/// Verifies many files against their expected SHA256 at once, each hashed in blocks by
+/// [`sha256_file_with_progress`] and the files spread over all available cores.
+///
+/// Reports the `(hashed, total)` bytes of all files together. The files that match are
+/// recorded with [`record_verified`]; the results are returned in the order of `files`.
+pub fn verify_files_parallel<F>(files: &[(PathBuf, String)], progress: F) -> Vec<bool>
+where
+    F: Fn(u64, u64) + Sync,
+{
+    let total = files
+        .iter()
+        .filter_map(|(path, _)| std::fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    let hashed = AtomicU64::new(0);
+    let results: Vec<bool> = files
+        .par_iter()
+        .map(|(path, sha256)| {
+            let reported = Cell::new(0u64);
+            let report = |done: u64, _| {
+                let delta = done - reported.replace(done);
+                progress(hashed.fetch_add(delta, Ordering::Relaxed) + delta, total);
+            };
+            sha256_file_with_progress(path, report)
+                .is_ok_and(|actual| actual.eq_ignore_ascii_case(sha256))
+        })
+        .collect();
+    // recorded one after the other, the record of a folder is rewritten as a whole
+    for ((path, sha256), _) in files.iter().zip(&results).filter(|(_, verified)| **verified) {
+        record_verified(path, sha256);
+    }
+    results
+}
+
+/// A successful verification of a cached file, valid while the file keeps its size and
+/// modification time.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct VerifiedFile {
+    sha256: String,
+    size: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    modified: u128,
+    /// When the file was hashed, seconds since the Unix epoch
+    verified_at: u64,
+}
+
+fn size_and_modified(path: &Path) -> Option<(u64, u128)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    Some((metadata.len(), modified))
+}
+
+fn verified_files_path(path: &Path) -> Option<PathBuf> {
+    Some(path.parent()?.join(VERIFIED_FILE))
+}
+
+fn load_verified_files(record: &Path) -> HashMap<String, VerifiedFile> {
+    std::fs::read_to_string(record)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn file_key(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// Whether `path` was verified against `sha256` before and has not changed since, so hashing it
+/// again can be skipped. Always `false` in paranoid mode.
+pub fn is_verified(path: &Path, sha256: &str) -> bool {
+    if PARANOID.load(Ordering::Relaxed) {
+        return false;
+    }
+    let (Some(record), Some((size, modified))) = (verified_files_path(path), size_and_modified(path)) else {
+        return false;
+    };
+    let verified = load_verified_files(&record)
+        .get(&file_key(path))
+        .is_some_and(|entry| {
+            entry.sha256.eq_ignore_ascii_case(sha256) && entry.size == size && entry.modified == modified
+        });
+    if verified {
+        debug!("Skipping the checksum of {}, it is unchanged since its verification", path.display());
+    }
+    verified
+}
+
+/// Records that `path` matches `sha256`, for [`is_verified`] to skip hashing it next time.
+pub fn record_verified(path: &Path, sha256: &str) {
+    let (Some(record), Some((size, modified))) = (verified_files_path(path), size_and_modified(path)) else {
+        return;
+    };
+    let mut verified = load_verified_files(&record);
+    verified.insert(
+        file_key(path),
+        VerifiedFile {
+            sha256: sha256.to_lowercase(),
+            size,
+            modified,
+            verified_at: std::time::SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        },
+    );
+    // written aside and renamed, a reader never sees a partly written file
+    let temp = record.with_extension("json.tmp");
+    let result = serde_json::to_string_pretty(&verified)
+        .map_err(io::Error::other)
+        .and_then(|content| std::fs::write(&temp, content))
+        .and_then(|_| std::fs::rename(&temp, &record));
+    if let Err(e) = result {
+        warn!("Failed to record the verification of {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_chunked_hash_matches_plain_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("archive.tar.gz");
+        let content: Vec<u8> = (0..(CHUNK_SIZE * 2 + 123)).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        let reported = Cell::new(0);
+        let hash = sha256_file_with_progress(&path, |done, total| {
+            assert_eq!(total, content.len() as u64);
+            reported.set(done);
+        })
+        .unwrap();
+        assert_eq!(hash, format!("{:x}", Sha256::digest(&content)));
+        assert_eq!(reported.get(), content.len() as u64);
+    }
+
+    #[test]
+    fn test_parallel_hashes_keep_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let paths: Vec<PathBuf> = (0..10)
+            .map(|i| {
+                let path = temp_dir.path().join(format!("file{}", i));
+                std::fs::write(&path, format!("content {}", i)).unwrap();
+                path
+            })
+            .collect();
+        let mut with_missing = paths.clone();
+        with_missing.push(temp_dir.path().join("missing"));
+
+        let results = sha256_files_parallel(&with_missing);
+        for (i, result) in results.iter().take(10).enumerate() {
+            let expected = format!("{:x}", Sha256::digest(format!("content {}", i).as_bytes()));
+            assert_eq!(result.as_ref().unwrap(), &expected);
+        }
+        assert!(results[10].is_err());
+    }
+
+    #[test]
+    fn test_verify_files_parallel() {
+        let temp_dir = TempDir::new().unwrap();
+        let good = temp_dir.path().join("good.tar.gz");
+        let bad = temp_dir.path().join("bad.tar.gz");
+        std::fs::write(&good, "good archive").unwrap();
+        std::fs::write(&bad, "bad archive").unwrap();
+        let good_sha256 = sha256_file(&good).unwrap();
+        let files = vec![
+            (good.clone(), good_sha256.clone()),
+            (bad.clone(), good_sha256.clone()),
+            (temp_dir.path().join("missing.tar.gz"), good_sha256.clone()),
+        ];
+
+        // the reports of different files may arrive out of order
+        let most = Mutex::new((0, 0));
+        let results = verify_files_parallel(&files, |hashed, total| {
+            let mut most = most.lock().unwrap();
+            *most = (most.0.max(hashed), total);
+        });
+        assert_eq!(results, vec![true, false, false]);
+        assert_eq!(most.into_inner().unwrap(), (23, 23));
+        assert!(is_verified(&good, &good_sha256));
+        assert!(!is_verified(&bad, &good_sha256));
+    }
+
+    #[test]
+    fn test_verified_files_are_invalidated_by_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("tool.tar.gz");
+        std::fs::write(&path, "archive").unwrap();
+        let sha256 = sha256_file(&path).unwrap();
+
+        assert!(!is_verified(&path, &sha256));
+        record_verified(&path, &sha256);
+        assert!(is_verified(&path, &sha256.to_uppercase()));
+        assert!(!is_verified(&path, "0000"));
+
+        std::fs::write(&path, "other archive").unwrap();
+        assert!(!is_verified(&path, &sha256));
+    }
+}
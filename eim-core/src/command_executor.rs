@@ -33,7 +33,7 @@ struct DefaultExecutor;
 
 impl CommandExecutor for DefaultExecutor {
     fn execute(&self, command: &str, args: &[&str]) -> std::io::Result<Output> {
-        Command::new(command).args(args).output()
+        crate::cancellation::output(Command::new(command).args(args))
     }
     fn execute_with_env(
         &self,
@@ -46,7 +46,7 @@ impl CommandExecutor for DefaultExecutor {
         for (key, value) in env {
             command = command.env(key, value);
         }
-        command.output()
+        crate::cancellation::output(command)
     }
     fn execute_with_dir(
         &self,
@@ -54,10 +54,7 @@ impl CommandExecutor for DefaultExecutor {
         args: &[&str],
         dir: &str,
     ) -> std::io::Result<Output> {
-        Command::new(command)
-            .args(args)
-            .current_dir(dir)
-            .output()
+        crate::cancellation::output(Command::new(command).args(args).current_dir(dir))
     }
     fn spawn_with_dir(
         &self,
@@ -65,9 +62,7 @@ impl CommandExecutor for DefaultExecutor {
         args: &[&str],
         dir: &str,
     ) -> std::io::Result<Child> {
-        Command::new(command)
-            .args(args)
-            .current_dir(dir)
+        crate::cancellation::own_process_group(Command::new(command).args(args).current_dir(dir))
             .spawn()
     }
     fn run_script_from_string(&self, script: &str) -> std::io::Result<Output> {
@@ -117,10 +112,7 @@ impl CommandExecutor for WindowsExecutor {
     fn execute(&self, command: &str, args: &[&str]) -> std::io::Result<Output> {
         use std::os::windows::process::CommandExt;
         const CREATE_NO_WINDOW: u32 = 0x08000000;
-        Command::new(command)
-            .args(args)
-            .creation_flags(CREATE_NO_WINDOW)
-            .output()
+        crate::cancellation::output(Command::new(command).args(args).creation_flags(CREATE_NO_WINDOW))
     }
     fn execute_with_env(
         &self,
@@ -135,7 +127,7 @@ impl CommandExecutor for WindowsExecutor {
         for (key, value) in env {
             command = command.env(key, value);
         }
-        command.output()
+        crate::cancellation::output(command)
     }
 
     fn execute_with_dir(
@@ -146,11 +138,12 @@ impl CommandExecutor for WindowsExecutor {
     ) -> std::io::Result<Output> {
         use std::os::windows::process::CommandExt;
         const CREATE_NO_WINDOW: u32 = 0x08000000;
-        Command::new(command)
-            .args(args)
-            .current_dir(dir)
-            .creation_flags(CREATE_NO_WINDOW)
-            .output()
+        crate::cancellation::output(
+            Command::new(command)
+                .args(args)
+                .current_dir(dir)
+                .creation_flags(CREATE_NO_WINDOW),
+        )
     }
 
     fn spawn_with_dir(
@@ -209,6 +202,7 @@ impl CommandExecutor for WindowsExecutor {
                 )
                 .spawn()?;
 
+            let _guard = crate::cancellation::register(child.id());
             let output = child.wait_with_output()?;
             Ok(output)
         } else {
@@ -232,13 +226,96 @@ impl CommandExecutor for WindowsExecutor {
                 stdin.write_all(script.as_bytes())?;
             }
 
+            let _guard = crate::cancellation::register(child.id());
             let output = child.wait_with_output()?;
             Ok(output)
         }
     }
 }
 
+#[cfg(test)]
+thread_local! {
+    static MOCK_EXECUTOR: std::cell::RefCell<Option<MockExecutor>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Test double answering every command with a fixed exit code and stderr, recording the calls.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct MockExecutor {
+    pub exit_code: i32,
+    pub stderr: String,
+    pub calls: std::sync::Arc<std::sync::Mutex<Vec<Vec<String>>>>,
+}
+
+#[cfg(test)]
+impl MockExecutor {
+    /// Runs `f` with `get_executor` returning this mock on the current thread.
+    pub fn install<T>(&self, f: impl FnOnce() -> T) -> T {
+        MOCK_EXECUTOR.with(|mock| *mock.borrow_mut() = Some(self.clone()));
+        let result = f();
+        MOCK_EXECUTOR.with(|mock| *mock.borrow_mut() = None);
+        result
+    }
+
+    fn output(&self, command: &str, args: &[&str]) -> std::io::Result<Output> {
+        #[cfg(unix)]
+        use std::os::unix::process::ExitStatusExt;
+        #[cfg(windows)]
+        use std::os::windows::process::ExitStatusExt;
+        let mut call = vec![command.to_string()];
+        call.extend(args.iter().map(|a| a.to_string()));
+        self.calls.lock().unwrap().push(call);
+        #[cfg(unix)]
+        let status = std::process::ExitStatus::from_raw(self.exit_code << 8);
+        #[cfg(windows)]
+        let status = std::process::ExitStatus::from_raw(self.exit_code as u32);
+        Ok(Output {
+            status,
+            stdout: Vec::new(),
+            stderr: self.stderr.clone().into_bytes(),
+        })
+    }
+}
+
+#[cfg(test)]
+impl CommandExecutor for MockExecutor {
+    fn execute(&self, command: &str, args: &[&str]) -> std::io::Result<Output> {
+        self.output(command, args)
+    }
+    fn execute_with_env(
+        &self,
+        command: &str,
+        args: &[&str],
+        _env: Vec<(&str, &str)>,
+    ) -> std::io::Result<Output> {
+        self.output(command, args)
+    }
+    fn execute_with_dir(
+        &self,
+        command: &str,
+        args: &[&str],
+        _dir: &str,
+    ) -> std::io::Result<Output> {
+        self.output(command, args)
+    }
+    fn spawn_with_dir(
+        &self,
+        _command: &str,
+        _args: &[&str],
+        _dir: &str,
+    ) -> std::io::Result<Child> {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "MockExecutor cannot spawn"))
+    }
+    fn run_script_from_string(&self, script: &str) -> std::io::Result<Output> {
+        self.output("powershell", &[script])
+    }
+}
+
 pub fn get_executor() -> Box<dyn CommandExecutor> {
+    #[cfg(test)]
+    if let Some(mock) = MOCK_EXECUTOR.with(|mock| mock.borrow().clone()) {
+        return Box::new(mock);
+    }
     #[cfg(target_os = "windows")]
     {
         Box::new(WindowsExecutor)
@@ -0,0 +1,175 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use std::path::Path;
+
+use crate::command_executor;
+use crate::settings::{Settings, VersionPaths};
+
+/// Local registry mirror with the components used by the examples, in the version folder and in
+/// offline bundles
+pub const MIRROR_DIR_NAME: &str = "component_mirror";
+/// Storage of the components of the default registry, used when the local mirror lacks one
+pub const DEFAULT_STORAGE_URL: &str = "https://components-file.espressif.com";
+
+/// Whether any component manager setting asks for the setup step.
+pub fn is_enabled(settings: &Settings) -> bool {
+    settings.component_mirror.unwrap_or(false)
+        || settings.component_registry_url.is_some()
+        || settings.component_storage_url.is_some()
+}
+
+/// Runs the component manager of `python` with `args`, failing with its stderr.
+fn run_component_manager(python: &Path, args: &[&str], env: Vec<(&str, &str)>) -> Result<()> {
+    let mut full_args = vec!["-m", "idf_component_manager"];
+    full_args.extend_from_slice(args);
+    let output = command_executor::execute_command_with_env(&python.to_string_lossy(), &full_args, env)?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "idf_component_manager {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Installs the component manager into the Python environment unless it is there already.
+///
+/// The requirements of current ESP-IDF versions include it; older ones and trimmed
+/// environments don't.
+pub fn ensure_installed(python: &Path, pypi_mirror: Option<&str>) -> Result<()> {
+    let installed = command_executor::execute_command(&python.to_string_lossy(), &["-m", "pip", "show", "idf-component-manager"])
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+    if installed {
+        debug!("The component manager is already installed in {}", python.display());
+        return Ok(());
+    }
+    info!("Installing the IDF Component Manager");
+    let mut args = vec!["-m", "pip", "install", "idf-component-manager"];
+    let index = crate::url_rewrite::pypi_index(pypi_mirror);
+    if let Some(index) = &index {
+        args.push("--index-url");
+        args.push(index);
+    }
+    let output = command_executor::execute_command(&python.to_string_lossy(), &args)?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to install the component manager: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Downloads the managed components the examples of `idf_path` depend on, with their
+/// dependencies, into a registry mirror at `mirror`.
+pub fn sync_example_components(python: &Path, idf_path: &Path, mirror: &Path, registry_url: Option<&str>) -> Result<()> {
+    std::fs::create_dir_all(mirror)?;
+    let examples = idf_path.join("examples");
+    let examples = examples.to_string_lossy();
+    let mirror_path = mirror.to_string_lossy();
+    let idf_path_value = idf_path.to_string_lossy();
+    let mut env = vec![("IDF_PATH", idf_path_value.as_ref())];
+    if let Some(url) = registry_url {
+        env.push(("IDF_COMPONENT_REGISTRY_URL", url));
+    }
+    info!("Downloading the components used by the examples to {}", mirror.display());
+    run_component_manager(
+        python,
+        &["registry", "sync", "--recursive", "--project-dir", &examples, &mirror_path],
+        env,
+    )
+}
+
+/// `file://` URL of a local mirror
+fn mirror_url(mirror: &Path) -> String {
+    let path = mirror.to_string_lossy().replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        format!("file:///{}", path)
+    }
+}
+
+/// Environment of the activation script pointing the component manager to the configured
+/// registry, the local mirror first when there is one.
+pub fn environment_variables(settings: &Settings, mirror: Option<&Path>) -> Vec<(String, String)> {
+    let mut env = Vec::new();
+    if let Some(url) = &settings.component_registry_url {
+        env.push(("IDF_COMPONENT_REGISTRY_URL".to_string(), url.clone()));
+    }
+    let mut storage: Vec<String> = mirror.map(mirror_url).into_iter().collect();
+    match &settings.component_storage_url {
+        Some(url) => storage.push(url.clone()),
+        // without a registry of its own, missing components still come from the default one
+        None if !storage.is_empty() && settings.component_registry_url.is_none() => {
+            storage.push(DEFAULT_STORAGE_URL.to_string())
+        }
+        None => {}
+    }
+    if !storage.is_empty() {
+        env.push(("IDF_COMPONENT_STORAGE_URL".to_string(), storage.join(";")));
+    }
+    env
+}
+
+/// Sets up the component manager for a new installation and returns the environment entries
+/// for its activation script.
+///
+/// An offline bundle brings its mirror along in `offline_archive_dir`, otherwise the mirror is
+/// synced from the registry when `component_mirror` is set.
+pub fn setup(settings: &Settings, paths: &VersionPaths, offline_archive_dir: Option<&Path>) -> Result<Vec<(String, String)>> {
+    let mirror = paths.version_installation_path.join(MIRROR_DIR_NAME);
+    let bundled = offline_archive_dir.map(|dir| dir.join(MIRROR_DIR_NAME)).filter(|dir| dir.is_dir());
+    let mirror = if let Some(bundled) = bundled {
+        info!("Using the component mirror of the offline archive");
+        crate::utils::copy_dir_contents(&bundled, &mirror)?;
+        Some(mirror)
+    } else if settings.component_mirror.unwrap_or(false) && offline_archive_dir.is_none() {
+        ensure_installed(&paths.python_path, settings.pypi_mirror.as_deref())?;
+        sync_example_components(
+            &paths.python_path,
+            &paths.idf_path,
+            &mirror,
+            settings.component_registry_url.as_deref(),
+        )?;
+        Some(mirror)
+    } else {
+        None
+    };
+    Ok(environment_variables(settings, mirror.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment_variables() {
+        let mut settings = Settings::default();
+        assert!(environment_variables(&settings, None).is_empty());
+
+        let mirror = Path::new("/opt/esp/v5.3.2/component_mirror");
+        assert_eq!(
+            environment_variables(&settings, Some(mirror)),
+            vec![(
+                "IDF_COMPONENT_STORAGE_URL".to_string(),
+                format!("file:///opt/esp/v5.3.2/component_mirror;{}", DEFAULT_STORAGE_URL)
+            )]
+        );
+
+        settings.component_registry_url = Some("https://registry.example.com".to_string());
+        settings.component_storage_url = Some("https://files.example.com".to_string());
+        assert_eq!(
+            environment_variables(&settings, Some(mirror)),
+            vec![
+                ("IDF_COMPONENT_REGISTRY_URL".to_string(), "https://registry.example.com".to_string()),
+                (
+                    "IDF_COMPONENT_STORAGE_URL".to_string(),
+                    "file:///opt/esp/v5.3.2/component_mirror;https://files.example.com".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,432 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::components::{selected_components, Component, OPTIONAL_COMPONENTS};
+use crate::idf_features::{get_requirements_json_url, FeatureInfo, RequirementsMetadata};
+use crate::idf_tools::{Download, Tool, ToolsFile};
+use crate::settings::{Settings, VersionPaths};
+use crate::size_estimate::{
+    tool_item, SizeItem, SizeSource, DOCS_DISK_BYTES, EXAMPLES_DISK_BYTES, IDF_DISK_BYTES, IDF_DOWNLOAD_BYTES,
+    PYTHON_DISK_BYTES, PYTHON_DOWNLOAD_BYTES,
+};
+
+const MIB: u64 = 1024 * 1024;
+
+/// Typical download of the packages of an optional Python feature
+pub const FEATURE_DOWNLOAD_BYTES: u64 = 15 * MIB;
+/// Typical size of the packages of an optional Python feature in the virtual environment
+pub const FEATURE_DISK_BYTES: u64 = 60 * MIB;
+
+/// One entry of the component tree: a group, a target, a tool, a Python feature or an extra.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ComponentNode {
+    /// Stable identifier, e.g. `target:esp32s3`, `tool:openocd-esp32` or `feature:docs`
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// Version of a tool, empty for everything else
+    pub version: String,
+    /// Always installed when its parent is, shown without a checkbox
+    pub required: bool,
+    pub selected: bool,
+    /// Targets a tool is installed for, empty when it is installed for all of them
+    pub targets: Vec<String>,
+    /// Of a leaf, or of all distinct leaves below a group
+    pub download_bytes: u64,
+    pub disk_bytes: u64,
+    pub source: SizeSource,
+    pub children: Vec<ComponentNode>,
+}
+
+impl ComponentNode {
+    fn group(id: &str, children: Vec<ComponentNode>) -> Self {
+        let mut seen = HashSet::new();
+        let (download_bytes, disk_bytes) = children
+            .iter()
+            .map(|child| child.leaf_sum(&mut seen, &|_| true))
+            .fold((0, 0), |(download, disk), (d, s)| (download + d, disk + s));
+        let source = children
+            .iter()
+            .map(|child| child.source)
+            .find(|source| *source != SizeSource::Measured)
+            .unwrap_or(SizeSource::Measured);
+        Self {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: None,
+            version: String::new(),
+            required: true,
+            selected: true,
+            targets: Vec::new(),
+            download_bytes,
+            disk_bytes,
+            source,
+            children,
+        }
+    }
+
+    fn leaf(id: String, item: SizeItem) -> Self {
+        Self {
+            id,
+            name: item.name,
+            description: None,
+            version: item.version,
+            required: true,
+            selected: true,
+            targets: Vec::new(),
+            download_bytes: item.download_bytes,
+            disk_bytes: item.disk_bytes,
+            source: item.source,
+            children: Vec::new(),
+        }
+    }
+
+    fn optional(mut self, selected: bool) -> Self {
+        self.required = false;
+        self.selected = selected;
+        self
+    }
+
+    /// Sums the leaves accepted by `include`, skipping the ids already in `seen` so tools listed
+    /// under several targets are counted once.
+    fn leaf_sum<'a>(&'a self, seen: &mut HashSet<&'a str>, include: &dyn Fn(&ComponentNode) -> bool) -> (u64, u64) {
+        if !include(self) {
+            return (0, 0);
+        }
+        if self.children.is_empty() {
+            return if seen.insert(&self.id) {
+                (self.download_bytes, self.disk_bytes)
+            } else {
+                (0, 0)
+            };
+        }
+        self.children
+            .iter()
+            .map(|child| child.leaf_sum(seen, include))
+            .fold((0, 0), |(download, disk), (d, s)| (download + d, disk + s))
+    }
+}
+
+/// Everything installing a version can include, grouped into the core, the toolchains of each
+/// target, the debug tools, the Python features and the optional extras, with sizes.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ComponentTree {
+    pub version: String,
+    /// Selected targets, `all` is expanded to the targets of the version
+    pub targets: Vec<String>,
+    pub groups: Vec<ComponentNode>,
+    /// Of the current selection
+    pub download_bytes: u64,
+    pub disk_bytes: u64,
+}
+
+impl ComponentTree {
+    /// Download and disk size of the ticked nodes, counting shared tools once.
+    pub fn selection_totals(&self) -> (u64, u64) {
+        let include = |node: &ComponentNode| {
+            (node.required || node.selected)
+                && (node.targets.is_empty() || node.targets.iter().any(|t| self.targets.contains(t)))
+        };
+        let mut seen = HashSet::new();
+        self.groups
+            .iter()
+            .map(|group| group.leaf_sum(&mut seen, &include))
+            .fold((0, 0), |(download, disk), (d, s)| (download + d, disk + s))
+    }
+}
+
+/// Ticked parts of the tree.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    pub targets: Vec<String>,
+    pub features: Vec<String>,
+    pub components: Vec<Component>,
+}
+
+enum ToolKind {
+    Core,
+    Toolchain,
+    Debug,
+    Qemu,
+}
+
+fn tool_kind(tool: &Tool) -> ToolKind {
+    if crate::qemu::QEMU_TOOLS.contains(&tool.name.as_str()) {
+        ToolKind::Qemu
+    } else if tool.name.contains("gdb") || tool.name.contains("openocd") {
+        ToolKind::Debug
+    } else if tool_targets(tool).is_empty() {
+        ToolKind::Core
+    } else {
+        ToolKind::Toolchain
+    }
+}
+
+/// Targets a tool is limited to, empty when it is needed for all of them.
+fn tool_targets(tool: &Tool) -> Vec<String> {
+    match &tool.supported_targets {
+        Some(targets) if !targets.iter().any(|t| t == "all") => targets.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// Part of ESP-IDF itself, nothing if the checkout is already there.
+fn idf_part(id: &str, exists: bool, download_bytes: u64, disk_bytes: u64) -> ComponentNode {
+    let (download_bytes, disk_bytes, source) = if exists {
+        (0, 0, SizeSource::Measured)
+    } else {
+        (download_bytes, disk_bytes, SizeSource::Estimated)
+    };
+    ComponentNode::leaf(
+        id.to_string(),
+        SizeItem {
+            name: id.to_string(),
+            version: String::new(),
+            download_bytes,
+            disk_bytes,
+            source,
+        },
+    )
+}
+
+/// Builds the tree from the tools of the version, the download each has on this platform and the
+/// Python features from `requirements.json`.
+pub fn build_component_tree(
+    version: &str,
+    tools: &ToolsFile,
+    downloads: &HashMap<String, (String, Download)>,
+    features: &[FeatureInfo],
+    selection: &Selection,
+    paths: &VersionPaths,
+) -> ComponentTree {
+    let tool_node = |tool: &Tool| {
+        let (tool_version, download) = downloads.get(&tool.name)?;
+        let item = tool_item(
+            &tool.name,
+            tool_version,
+            download,
+            &paths.tool_install_directory,
+            &paths.tool_download_directory,
+        );
+        let mut node = ComponentNode::leaf(format!("tool:{}", tool.name), item);
+        node.description = Some(tool.description.clone());
+        node.targets = tool_targets(tool);
+        Some(node)
+    };
+    let of_kind = |kind: fn(&ToolKind) -> bool| -> Vec<ComponentNode> {
+        tools
+            .tools
+            .iter()
+            .filter(|tool| kind(&tool_kind(tool)))
+            .filter_map(tool_node)
+            .collect()
+    };
+
+    let idf_exists = paths.idf_path.exists();
+    let mut core = vec![idf_part(
+        "esp-idf",
+        idf_exists,
+        IDF_DOWNLOAD_BYTES,
+        IDF_DISK_BYTES - EXAMPLES_DISK_BYTES - DOCS_DISK_BYTES,
+    )];
+    core.extend(of_kind(|kind| matches!(kind, ToolKind::Core)));
+
+    let all_targets: BTreeSet<String> = tools.tools.iter().flat_map(tool_targets).collect();
+    let selected_targets: Vec<String> = if selection.targets.iter().any(|t| t == "all") {
+        all_targets.iter().cloned().collect()
+    } else {
+        selection.targets.clone()
+    };
+    let toolchains = of_kind(|kind| matches!(kind, ToolKind::Toolchain));
+    let targets = all_targets
+        .iter()
+        .map(|target| {
+            let children = toolchains
+                .iter()
+                .filter(|tool| tool.targets.contains(target))
+                .cloned()
+                .collect();
+            let mut node = ComponentNode::group(&format!("target:{}", target), children);
+            node.name = target.clone();
+            node.targets = vec![target.clone()];
+            node.optional(selected_targets.contains(target))
+        })
+        .collect();
+
+    let venv_exists = paths.python_venv_path.exists();
+    let mut python = vec![idf_part("python-env", venv_exists, PYTHON_DOWNLOAD_BYTES, PYTHON_DISK_BYTES)];
+    python.extend(features.iter().map(|feature| {
+        let (download_bytes, disk_bytes, source) = if feature.optional {
+            (FEATURE_DOWNLOAD_BYTES, FEATURE_DISK_BYTES, SizeSource::Estimated)
+        } else {
+            // required features are what the environment itself consists of
+            (0, 0, SizeSource::Estimated)
+        };
+        let mut node = ComponentNode::leaf(
+            format!("feature:{}", feature.name),
+            SizeItem {
+                name: feature.name.clone(),
+                version: String::new(),
+                download_bytes,
+                disk_bytes,
+                source,
+            },
+        );
+        node.description = feature.description.clone();
+        if feature.optional {
+            node.optional(selection.features.contains(&feature.name))
+        } else {
+            node
+        }
+    }));
+
+    let qemu = of_kind(|kind| matches!(kind, ToolKind::Qemu));
+    let extras = OPTIONAL_COMPONENTS
+        .iter()
+        .map(|component| {
+            let id = format!("component:{}", component.name());
+            let mut node = match component {
+                Component::Examples => idf_part(&id, idf_exists, 0, EXAMPLES_DISK_BYTES),
+                Component::Docs => idf_part(&id, idf_exists, 0, DOCS_DISK_BYTES),
+                Component::Qemu => ComponentNode::group(&id, qemu.clone()),
+            };
+            node.name = component.name().to_string();
+            node.optional(selection.components.contains(component))
+        })
+        .collect();
+
+    let mut tree = ComponentTree {
+        version: version.to_string(),
+        targets: selected_targets,
+        groups: vec![
+            ComponentNode::group("core", core),
+            ComponentNode::group("toolchains", targets),
+            ComponentNode::group("debug", of_kind(|kind| matches!(kind, ToolKind::Debug))),
+            ComponentNode::group("python", python),
+            ComponentNode::group("extras", extras),
+        ],
+        download_bytes: 0,
+        disk_bytes: 0,
+    };
+    (tree.download_bytes, tree.disk_bytes) = tree.selection_totals();
+    tree
+}
+
+/// Fetches the `tools.json` and `requirements.json` of `version` and builds its component tree,
+/// ticking `targets` and the features and components selected in `settings`.
+pub async fn component_tree(settings: &Settings, version: &str, targets: Vec<String>) -> Result<ComponentTree> {
+    let paths = settings.get_version_paths(version)?;
+    let tools_json = crate::support_matrix::fetch_text(version, settings.idf_mirror.as_deref(), "tools/tools.json").await?;
+    let tools: ToolsFile = serde_json::from_str(&tools_json)?;
+    let tools = crate::system_tools::without_system_tools(tools, &crate::system_tools::selected(settings));
+    let platform = crate::idf_tools::get_platform_identification().map_err(|err| anyhow!(err))?;
+    let downloads = crate::idf_tools::get_download_link_by_platform(tools.tools.clone(), &platform);
+    let requirements_url = get_requirements_json_url(settings.repo_stub.as_deref(), version, settings.idf_mirror.as_deref());
+    let features = RequirementsMetadata::from_url_async(&requirements_url).await?.features;
+    let selection = Selection {
+        targets,
+        features: settings.get_features_for_version(version),
+        components: selected_components(settings)?,
+    };
+    Ok(build_component_tree(version, &tools, &downloads, &features, &selection, &paths))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn tool(name: &str, targets: &str) -> String {
+        format!(
+            r#"{{"description": "{name}", "export_paths": [], "export_vars": {{}}, "info_url": "", "install": "always",
+                "name": "{name}", "supported_targets": {targets}, "version_cmd": [], "version_regex": "",
+                "versions": [{{"name": "1.0", "status": "recommended"}}]}}"#
+        )
+    }
+
+    fn download(name: &str, size: u64) -> (String, (String, Download)) {
+        let download = Download {
+            sha256: String::new(),
+            size,
+            url: format!("https://dl.espressif.com/{}-1.0.zip", name),
+            rename_dist: None,
+        };
+        (name.to_string(), ("1.0".to_string(), download))
+    }
+
+    #[test]
+    fn test_component_tree() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let tools: ToolsFile = serde_json::from_str(&format!(
+            r#"{{"version": 2, "tools": [{}, {}, {}, {}, {}]}}"#,
+            tool("cmake", r#"["all"]"#),
+            tool("xtensa-esp-elf", r#"["esp32", "esp32s3"]"#),
+            tool("riscv32-esp-elf", r#"["esp32c3"]"#),
+            tool("openocd-esp32", r#"["all"]"#),
+            tool("qemu-xtensa", r#"["esp32"]"#),
+        ))
+        .unwrap();
+        let downloads: HashMap<_, _> = [
+            download("cmake", 10),
+            download("xtensa-esp-elf", 100),
+            download("riscv32-esp-elf", 200),
+            download("openocd-esp32", 5),
+            download("qemu-xtensa", 50),
+        ]
+        .into_iter()
+        .collect();
+        let features = vec![
+            FeatureInfo {
+                name: "core".to_string(),
+                description: None,
+                optional: false,
+                requirement_path: String::new(),
+            },
+            FeatureInfo {
+                name: "ci".to_string(),
+                description: None,
+                optional: true,
+                requirement_path: String::new(),
+            },
+        ];
+        let paths = VersionPaths {
+            idf_path: dir.path().join("esp-idf"),
+            version_installation_path: dir.path().to_path_buf(),
+            tool_download_directory: dir.path().join("dist"),
+            tool_install_directory: dir.path().join("tools"),
+            python_venv_path: dir.path().join("venv"),
+            python_path: PathBuf::new(),
+            activation_script: PathBuf::new(),
+            activation_script_path: PathBuf::new(),
+            actual_version: "v5.4".to_string(),
+            idf_version: "v5.4".to_string(),
+            using_existing_idf: false,
+        };
+        let selection = Selection {
+            targets: vec!["esp32".to_string(), "esp32s3".to_string()],
+            features: Vec::new(),
+            components: vec![Component::Examples],
+        };
+
+        let mut tree = build_component_tree("v5.4", &tools, &downloads, &features, &selection, &paths);
+        let ids: Vec<&str> = tree.groups.iter().map(|g| g.id.as_str()).collect();
+        assert_eq!(ids, vec!["core", "toolchains", "debug", "python", "extras"]);
+        let toolchains = &tree.groups[1];
+        let target_ids: Vec<&str> = toolchains.children.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(target_ids, vec!["target:esp32", "target:esp32c3", "target:esp32s3"]);
+        // the xtensa toolchain is shared by two targets but counted once in the group
+        assert_eq!(toolchains.download_bytes, 300);
+        assert!(!toolchains.children[1].selected);
+
+        let base = IDF_DOWNLOAD_BYTES + 10 + 100 + 5 + PYTHON_DOWNLOAD_BYTES;
+        assert_eq!(tree.download_bytes, base);
+
+        tree.targets.push("esp32c3".to_string());
+        tree.groups[1].children[1].selected = true;
+        tree.groups[3].children[2].selected = true;
+        tree.groups[4].children[2].selected = true;
+        let (download_bytes, _) = tree.selection_totals();
+        assert_eq!(download_bytes, base + 200 + FEATURE_DOWNLOAD_BYTES + 50);
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::path::Path;
+
+use crate::command_executor::execute_command_with_dir;
+use crate::errors::{EimError, ErrorKind};
+use crate::settings::Settings;
+
+/// Optional parts of an installation which can be left out, e.g. for minimal CI images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    /// The `examples` directory of the ESP-IDF repository
+    Examples,
+    /// The `docs` directory and the `docs` Python feature used to build the documentation
+    Docs,
+    /// Espressif's QEMU builds
+    Qemu,
+}
+
+pub const OPTIONAL_COMPONENTS: &[Component] = &[Component::Examples, Component::Docs, Component::Qemu];
+
+impl Component {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Component::Examples => "examples",
+            Component::Docs => "docs",
+            Component::Qemu => "qemu",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        OPTIONAL_COMPONENTS
+            .iter()
+            .copied()
+            .find(|c| c.name() == name.trim().to_lowercase())
+    }
+
+    /// Installed unless deselected; QEMU has to be asked for
+    pub fn default_selected(&self) -> bool {
+        !matches!(self, Component::Qemu)
+    }
+
+    /// Paths of the ESP-IDF repository left out of the checkout without this component
+    fn sparse_checkout_exclusions(&self) -> &'static [&'static str] {
+        match self {
+            Component::Examples => &["!/examples/"],
+            Component::Docs => &["!/docs/"],
+            Component::Qemu => &[],
+        }
+    }
+}
+
+/// Returns the components installed with `settings`.
+///
+/// Without the `components` setting these are the default ones, plus QEMU if `install_qemu` is set.
+pub fn selected_components(settings: &Settings) -> Result<Vec<Component>> {
+    match &settings.components {
+        Some(names) => names
+            .iter()
+            .filter(|name| !name.trim().is_empty())
+            .map(|name| {
+                Component::from_name(name).ok_or_else(|| {
+                    let known: Vec<&str> = OPTIONAL_COMPONENTS.iter().map(|c| c.name()).collect();
+                    EimError::new(
+                        ErrorKind::Usage,
+                        format!("Unknown component '{}', expected one of: {}", name, known.join(", ")),
+                    )
+                    .into()
+                })
+            })
+            .collect(),
+        None => Ok(OPTIONAL_COMPONENTS
+            .iter()
+            .copied()
+            .filter(|c| match c {
+                Component::Qemu => settings.install_qemu.unwrap_or(false),
+                _ => c.default_selected(),
+            })
+            .collect()),
+    }
+}
+
+/// Returns the optional components intentionally left out with `settings`.
+pub fn omitted_components(settings: &Settings) -> Result<Vec<Component>> {
+    let selected = selected_components(settings)?;
+    Ok(OPTIONAL_COMPONENTS
+        .iter()
+        .copied()
+        .filter(|c| !selected.contains(c))
+        .collect())
+}
+
+/// Checks the `components` setting and makes `install_qemu` follow it.
+pub fn apply_to_settings(settings: &mut Settings) -> Result<()> {
+    if settings.components.is_some() {
+        settings.install_qemu = Some(selected_components(settings)?.contains(&Component::Qemu));
+    }
+    Ok(())
+}
+
+/// Removes the Python features belonging to omitted components.
+pub fn filter_features(features: Vec<String>, omitted: &[Component]) -> Vec<String> {
+    features
+        .into_iter()
+        .filter(|feature| !(omitted.contains(&Component::Docs) && feature == "docs"))
+        .collect()
+}
+
+/// Patterns of a non-cone sparse checkout leaving out the omitted components.
+pub fn sparse_checkout_patterns(omitted: &[Component]) -> Vec<String> {
+    let exclusions: Vec<String> = omitted
+        .iter()
+        .flat_map(|c| c.sparse_checkout_exclusions())
+        .map(|p| p.to_string())
+        .collect();
+    if exclusions.is_empty() {
+        return Vec::new();
+    }
+    std::iter::once("/*".to_string()).chain(exclusions).collect()
+}
+
+/// Leaves the directories of omitted components out of the ESP-IDF checkout with a sparse checkout,
+/// so they are not on disk but the repository stays clean and can be updated.
+pub fn apply_to_repository(idf_path: &Path, omitted: &[Component]) -> Result<()> {
+    let patterns = sparse_checkout_patterns(omitted);
+    if patterns.is_empty() {
+        return Ok(());
+    }
+    let dir = idf_path.to_str().unwrap_or_default();
+    let mut args = vec!["sparse-checkout", "set", "--no-cone"];
+    args.extend(patterns.iter().map(String::as_str));
+    let output = execute_command_with_dir("git", &args, dir)?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git sparse-checkout failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let names: Vec<&str> = omitted.iter().map(|c| c.name()).collect();
+    info!("Left out of {}: {}", idf_path.display(), names.join(", "));
+    Ok(())
+}
+
+/// Like [`apply_to_repository`], keeping the full checkout if git does not support sparse checkouts.
+pub fn apply_to_repository_or_warn(idf_path: &Path, settings: &Settings) {
+    let result = omitted_components(settings).and_then(|omitted| apply_to_repository(idf_path, &omitted));
+    if let Err(e) = result {
+        warn!("Failed to leave out the omitted components, keeping all files: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_component_selection() {
+        let mut settings = Settings::default();
+        assert_eq!(omitted_components(&settings).unwrap(), vec![Component::Qemu]);
+
+        settings.components = Some(vec!["qemu".to_string()]);
+        apply_to_settings(&mut settings).unwrap();
+        assert_eq!(settings.install_qemu, Some(true));
+        let omitted = omitted_components(&settings).unwrap();
+        assert_eq!(omitted, vec![Component::Examples, Component::Docs]);
+        assert_eq!(sparse_checkout_patterns(&omitted), vec!["/*", "!/examples/", "!/docs/"]);
+        assert_eq!(
+            filter_features(vec!["core".to_string(), "docs".to_string()], &omitted),
+            vec!["core".to_string()]
+        );
+
+        settings.components = Some(vec!["simulator".to_string()]);
+        assert!(selected_components(&settings).is_err());
+    }
+}
@@ -0,0 +1,386 @@
+use log::info;
+use rust_i18n::t;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use struct_iterable::Iterable;
+use toml::{Table, Value};
+
+use crate::errors::{EimError, ErrorKind};
+use crate::settings::Settings;
+
+/// Version of the configuration file format written by this eim, stored as `config_version`
+pub const CONFIG_SCHEMA_VERSION: u32 = 2;
+
+/// Upgrades a table of the version before `from` to version `from`.
+type Migration = fn(&mut Table);
+
+/// Migrations applied in order to files older than `CONFIG_SCHEMA_VERSION`
+const MIGRATIONS: &[(u32, Migration)] = &[(2, migrate_list_values)];
+
+/// Version 1 files, written before `config_version` existed, may give lists as comma-separated
+/// strings (`target = "esp32,esp32s3"`), which the wizard accepted.
+fn migrate_list_values(table: &mut Table) {
+    for key in ["target", "idf_versions", "idf_features", "ca_certificates"] {
+        if let Some(Value::String(list)) = table.get(key) {
+            let items = list
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| Value::String(item.to_string()))
+                .collect();
+            table.insert(key.to_string(), Value::Array(items));
+        }
+    }
+}
+
+/// A problem found in a configuration file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigIssue {
+    /// The file is not valid TOML
+    Syntax { message: String },
+    UnknownKey {
+        key: String,
+        suggestion: Option<String>,
+    },
+    InvalidType { key: String, expected: &'static str },
+    /// The file was written by a newer eim
+    NewerVersion { version: u32 },
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ConfigIssue::Syntax { message } => t!("config.schema.syntax", error = message),
+            ConfigIssue::UnknownKey {
+                key,
+                suggestion: Some(suggestion),
+            } => t!(
+                "config.schema.unknown_key_suggestion",
+                key = key,
+                suggestion = suggestion
+            ),
+            ConfigIssue::UnknownKey { key, suggestion: None } => {
+                t!("config.schema.unknown_key", key = key)
+            }
+            ConfigIssue::InvalidType { key, expected } => t!(
+                "config.schema.invalid_type",
+                key = key,
+                expected = type_name(expected)
+            ),
+            ConfigIssue::NewerVersion { version } => t!(
+                "config.schema.newer_version",
+                version = version,
+                supported = CONFIG_SCHEMA_VERSION
+            ),
+        };
+        write!(f, "{}", message)
+    }
+}
+
+fn type_name(expected: &str) -> String {
+    match expected {
+        "boolean" => t!("config.schema.type.boolean"),
+        "integer" => t!("config.schema.type.integer"),
+        "list" => t!("config.schema.type.list"),
+        "string" => t!("config.schema.type.string"),
+        _ => t!("config.schema.type.table"),
+    }
+    .to_string()
+}
+
+/// Describes the type of a settings field, used in the error messages.
+fn expected_type(value: &dyn std::any::Any) -> &'static str {
+    if value.is::<Option<bool>>() {
+        "boolean"
+    } else if value.is::<Option<u32>>() {
+        "integer"
+    } else if value.is::<Option<Vec<String>>>() {
+        "list"
+    } else if value.is::<Option<Vec<crate::url_rewrite::UrlRewriteRule>>>() {
+        "list of tables"
+    } else if value.is::<Option<String>>() || value.is::<Option<PathBuf>>() {
+        "string"
+    } else {
+        "table"
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Returns the known key closest to `key`, if it is close enough to be a typo.
+fn suggest_key(key: &str, known_keys: &[&'static str]) -> Option<String> {
+    known_keys
+        .iter()
+        .map(|known| (edit_distance(key, known), *known))
+        .filter(|(distance, known)| *distance <= (known.len() / 3).max(1))
+        .min()
+        .map(|(_, known)| known.to_string())
+}
+
+/// Upgrades a configuration table to `CONFIG_SCHEMA_VERSION`. Files without `config_version` are version 1.
+pub fn migrate(table: &mut Table) -> Result<(), ConfigIssue> {
+    let version = match table.get("config_version") {
+        None => 1,
+        Some(Value::Integer(version)) => u32::try_from(*version).unwrap_or(u32::MAX),
+        Some(_) => {
+            return Err(ConfigIssue::InvalidType {
+                key: "config_version".to_string(),
+                expected: "integer",
+            })
+        }
+    };
+    if version > CONFIG_SCHEMA_VERSION {
+        return Err(ConfigIssue::NewerVersion { version });
+    }
+    for (target_version, migration) in MIGRATIONS {
+        if version < *target_version {
+            migration(table);
+        }
+    }
+    if version < CONFIG_SCHEMA_VERSION {
+        info!(
+            "Migrated configuration from version {} to {}",
+            version, CONFIG_SCHEMA_VERSION
+        );
+    }
+    table.insert(
+        "config_version".to_string(),
+        Value::Integer(CONFIG_SCHEMA_VERSION.into()),
+    );
+    Ok(())
+}
+
+/// Checks every key of a configuration table against the fields of `Settings`.
+pub fn validate(table: &Table) -> Vec<ConfigIssue> {
+    let defaults = Settings::default();
+    let fields: HashMap<&'static str, &'static str> = defaults
+        .iter()
+        .map(|(name, value)| (name, expected_type(value)))
+        .collect();
+    let mut known_keys: Vec<&'static str> = fields.keys().copied().collect();
+    known_keys.sort();
+
+    let mut issues = Vec::new();
+    let mut entries: Vec<(&String, &Value)> = table.iter().collect();
+    entries.sort_by_key(|(key, _)| *key);
+    for (key, value) in entries {
+        let Some(expected) = fields.get(key.as_str()) else {
+            issues.push(ConfigIssue::UnknownKey {
+                key: key.clone(),
+                suggestion: suggest_key(key, &known_keys),
+            });
+            continue;
+        };
+        // each key on its own, so the error names the key instead of a position in the file
+        let mut single = Table::new();
+        single.insert(key.clone(), value.clone());
+        if Value::Table(single).try_into::<Settings>().is_err() {
+            issues.push(ConfigIssue::InvalidType {
+                key: key.clone(),
+                expected: *expected,
+            });
+        }
+    }
+    issues
+}
+
+/// Format of a configuration file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+/// Detects the format from the extension of `path`, or from the content if it has none of
+/// `.toml`, `.yaml`, `.yml` and `.json` (e.g. a config piped through a CI variable).
+pub fn detect_format(path: &str, content: &str) -> ConfigFormat {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("toml") => ConfigFormat::Toml,
+        Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+        Some("json") => ConfigFormat::Json,
+        _ if content.trim_start().starts_with('{') => ConfigFormat::Json,
+        _ if toml::from_str::<Table>(content).is_ok() => ConfigFormat::Toml,
+        _ => ConfigFormat::Yaml,
+    }
+}
+
+/// Removes `null` values, which mean "not set" in YAML and JSON but do not exist in TOML.
+fn strip_nulls(value: serde_json::Value) -> Option<serde_json::Value> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::Object(map) => Some(serde_json::Value::Object(
+            map.into_iter()
+                .filter_map(|(key, value)| strip_nulls(value).map(|value| (key, value)))
+                .collect(),
+        )),
+        serde_json::Value::Array(items) => Some(serde_json::Value::Array(
+            items.into_iter().filter_map(strip_nulls).collect(),
+        )),
+        other => Some(other),
+    }
+}
+
+/// Parses a configuration file of any supported format into a TOML table.
+pub(crate) fn parse_table(content: &str, format: ConfigFormat) -> Result<Table, ConfigIssue> {
+    let value = match format {
+        ConfigFormat::Toml => {
+            return toml::from_str(content).map_err(|e| {
+                ConfigIssue::Syntax {
+                    message: e.message().to_string(),
+                }
+                .with_position(content, e.span())
+            })
+        }
+        ConfigFormat::Json => serde_json::from_str::<serde_json::Value>(content)
+            .map_err(|e| t!("config.schema.invalid_json", error = e).to_string()),
+        ConfigFormat::Yaml => serde_yaml::from_str::<serde_json::Value>(content)
+            .map_err(|e| t!("config.schema.invalid_yaml", error = e).to_string()),
+    }
+    .map_err(|message| ConfigIssue::Syntax { message })?;
+    match strip_nulls(value) {
+        // an empty YAML file is null
+        None => Ok(Table::new()),
+        Some(value @ serde_json::Value::Object(_)) => serde_json::from_value(value)
+            .map_err(|e| ConfigIssue::Syntax {
+                message: e.to_string(),
+            }),
+        Some(_) => Err(ConfigIssue::Syntax {
+            message: t!("config.schema.not_a_mapping").to_string(),
+        }),
+    }
+}
+
+/// Parses, migrates and validates the content of a TOML, YAML or JSON configuration file into `Settings`.
+///
+/// All problems are reported at once in an `ErrorKind::Usage` error naming the file.
+pub fn parse_config(content: &str, path: &str) -> Result<Settings, EimError> {
+    let to_error = |issues: Vec<ConfigIssue>| {
+        let lines: Vec<String> = issues.iter().map(|issue| format!("  - {}", issue)).collect();
+        EimError::new(
+            ErrorKind::Usage,
+            format!(
+                "{}\n{}",
+                t!("config.schema.invalid", path = path),
+                lines.join("\n")
+            ),
+        )
+    };
+    let mut table = parse_table(content, detect_format(path, content))
+        .map_err(|issue| to_error(vec![issue]))?;
+    migrate(&mut table).map_err(|issue| to_error(vec![issue]))?;
+    let issues = validate(&table);
+    if !issues.is_empty() {
+        return Err(to_error(issues));
+    }
+    Value::Table(table).try_into::<Settings>().map_err(|e| {
+        to_error(vec![ConfigIssue::Syntax {
+            message: e.message().to_string(),
+        }])
+    })
+}
+
+impl ConfigIssue {
+    /// Adds the line and column of a syntax error to its message.
+    fn with_position(self, content: &str, span: Option<std::ops::Range<usize>>) -> Self {
+        match (self, span) {
+            (ConfigIssue::Syntax { message }, Some(span)) => {
+                let before = &content[..span.start.min(content.len())];
+                let line = before.matches('\n').count() + 1;
+                let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+                ConfigIssue::Syntax {
+                    message: format!("{} (line {}, column {})", message, line, column),
+                }
+            }
+            (issue, _) => issue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(content: &str) -> Vec<ConfigIssue> {
+        let mut table: Table = toml::from_str(content).unwrap();
+        migrate(&mut table).unwrap();
+        validate(&table)
+    }
+
+    #[test]
+    fn test_unknown_key_gets_a_suggestion() {
+        assert_eq!(
+            issues("mirorr = \"https://github.com\"\nunrelated_option = 1\n"),
+            vec![
+                ConfigIssue::UnknownKey {
+                    key: "mirorr".to_string(),
+                    suggestion: Some("mirror".to_string())
+                },
+                ConfigIssue::UnknownKey {
+                    key: "unrelated_option".to_string(),
+                    suggestion: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_type_names_the_key() {
+        assert_eq!(
+            issues("config_version = 2\nnon_interactive = \"yes\"\nidf_versions = [\"v5.5\"]\n"),
+            vec![ConfigIssue::InvalidType {
+                key: "non_interactive".to_string(),
+                expected: "boolean"
+            }]
+        );
+    }
+
+    #[test]
+    fn test_yaml_and_json_configs() {
+        let yaml = "idf_versions:\n  - v5.5\ntarget: [esp32s3]\nnon_interactive: true\nmirror: ~\n";
+        assert_eq!(detect_format("ci/eim.yml", yaml), ConfigFormat::Yaml);
+        let settings = parse_config(yaml, "ci/eim.yml").unwrap();
+        assert_eq!(settings.idf_versions, Some(vec!["v5.5".to_string()]));
+        assert_eq!(settings.non_interactive, Some(true));
+
+        let json = r#"{"idf_versions": ["v5.4"], "non_interactive": false}"#;
+        assert_eq!(detect_format("/dev/stdin", json), ConfigFormat::Json);
+        let settings = parse_config(json, "/dev/stdin").unwrap();
+        assert_eq!(settings.idf_versions, Some(vec!["v5.4".to_string()]));
+
+        let error = parse_config("target: esp32\nmirorr: x\n", "eim.yaml").unwrap_err();
+        assert!(error.message.contains("mirorr"));
+    }
+
+    #[test]
+    fn test_version_1_lists_are_migrated() {
+        let settings = parse_config("target = \"esp32, esp32s3\"\n", "eim_config.toml").unwrap();
+        assert_eq!(
+            settings.target,
+            Some(vec!["esp32".to_string(), "esp32s3".to_string()])
+        );
+        assert_eq!(settings.config_version, Some(CONFIG_SCHEMA_VERSION));
+
+        let mut table: Table = toml::from_str("config_version = 99").unwrap();
+        assert_eq!(
+            migrate(&mut table),
+            Err(ConfigIssue::NewerVersion { version: 99 })
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use log::{error, info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::settings::Settings;
+
+/// Folder in the log directory the crash reports and minidumps are written to
+pub const CRASHES_FOLDER_NAME: &str = "crashes";
+/// Folder in the crashes folder the reports are moved to once offered to the user
+pub const SEEN_FOLDER_NAME: &str = "seen";
+/// Lines of the log file kept in a crash report
+pub const LOG_TAIL_LINES: usize = 200;
+/// Number of crash reports kept, older ones are deleted when a new one is written
+pub const CRASH_REPORTS_KEPT: usize = 10;
+
+/// Key-value pairs describing what eim was doing, e.g. the running command or the version being
+/// installed, copied into the crash report.
+static STATE: Lazy<Mutex<BTreeMap<String, String>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Binary and log file of this process, set by [`install`]
+static PROCESS: OnceCell<(String, PathBuf)> = OnceCell::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrashKind {
+    /// A Rust panic, with its message and backtrace
+    Panic,
+    /// A signal or exception of the operating system, with a minidump
+    Native,
+}
+
+/// What is known about a crash, written as JSON next to the log files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub time: String,
+    pub eim_version: String,
+    pub os: String,
+    pub arch: String,
+    /// `eim` or `eim-gui`
+    pub binary: String,
+    pub kind: CrashKind,
+    pub message: String,
+    /// Source location of a panic
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: Option<String>,
+    pub state: BTreeMap<String, String>,
+    /// Last lines of the log file before the crash
+    pub log_tail: Vec<String>,
+    pub minidump: Option<PathBuf>,
+}
+
+impl CrashReport {
+    fn new(kind: CrashKind, message: String) -> Self {
+        let (binary, log_file) = PROCESS.get().cloned().unwrap_or_default();
+        Self {
+            time: Utc::now().to_rfc3339(),
+            eim_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            binary,
+            kind,
+            message,
+            location: None,
+            thread: std::thread::current().name().map(str::to_string),
+            backtrace: None,
+            // a panic while the state is locked must not deadlock the hook
+            state: STATE.try_lock().map(|state| state.clone()).unwrap_or_default(),
+            log_tail: tail_lines(&log_file, LOG_TAIL_LINES),
+            minidump: None,
+        }
+    }
+}
+
+/// Records what eim is doing, to be included in a crash report.
+pub fn set_state(key: &str, value: impl std::fmt::Display) {
+    if let Ok(mut state) = STATE.lock() {
+        state.insert(key.to_string(), value.to_string());
+    }
+}
+
+pub fn clear_state(key: &str) {
+    if let Ok(mut state) = STATE.lock() {
+        state.remove(key);
+    }
+}
+
+pub fn get_crashes_directory() -> Option<PathBuf> {
+    crate::get_log_directory().map(|dir| dir.join(CRASHES_FOLDER_NAME))
+}
+
+/// Last `count` lines of the file at `path`, empty when it can't be read.
+fn tail_lines(path: &Path, count: usize) -> Vec<String> {
+    let Ok(content) = fs::read(path) else {
+        return Vec::new();
+    };
+    let content = String::from_utf8_lossy(&content);
+    let lines: Vec<&str> = content.lines().collect();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+fn crash_file_name(extension: &str) -> String {
+    format!("crash_{}.{}", Utc::now().format("%Y%m%dT%H%M%S%.3fZ"), extension)
+}
+
+/// Writes `report` into `dir` and deletes the oldest reports beyond [`CRASH_REPORTS_KEPT`].
+fn write_report(dir: &Path, report: &CrashReport) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(crash_file_name("json"));
+    fs::write(&path, serde_json::to_string_pretty(report)?)?;
+    let reports = list_reports(dir);
+    for old in reports.iter().take(reports.len().saturating_sub(CRASH_REPORTS_KEPT)) {
+        let _ = fs::remove_file(old.with_extension("dmp"));
+        let _ = fs::remove_file(old);
+    }
+    Ok(path)
+}
+
+/// Crash reports in `dir`, oldest first.
+fn list_reports(dir: &Path) -> Vec<PathBuf> {
+    let mut reports: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension().is_some_and(|ext| ext == "json")
+                        && path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("crash_"))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    reports.sort();
+    reports
+}
+
+fn write(report: CrashReport) {
+    let Some(dir) = get_crashes_directory() else {
+        return;
+    };
+    match write_report(&dir, &report) {
+        Ok(path) => error!("eim crashed, crash report written to {}", path.display()),
+        Err(err) => eprintln!("Failed to write the crash report: {}", err),
+    }
+}
+
+/// Set for the monitor process started by [`install`], to the socket its parent connects to
+const MONITOR_ENV: &str = "EIM_CRASH_MONITOR";
+
+/// Writes a crash report on panics and on crashes of the native code (segmentation faults,
+/// access violations...), tailing `log_file`. Call once, right after the logging is initialized.
+///
+/// Reports of native crashes are completed here on the next launch: the crashed process only
+/// has a monitor process write the minidump.
+pub fn install(binary: &str, log_file: PathBuf) {
+    if PROCESS.set((binary.to_string(), log_file)).is_err() {
+        return;
+    }
+    if let Some(dir) = get_crashes_directory() {
+        report_native_crashes(&dir);
+    }
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        let message = panic_info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic_info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<dyn Any>".to_string());
+        let mut report = CrashReport::new(CrashKind::Panic, message);
+        report.location = panic_info.location().map(|location| location.to_string());
+        report.backtrace = Some(std::backtrace::Backtrace::force_capture().to_string());
+        write(report);
+        default_hook(panic_info);
+    }));
+    install_native_handler();
+}
+
+/// Minidumps in `dir` without a report, written by the monitor of a process which crashed.
+fn unreported_minidumps(dir: &Path) -> Vec<PathBuf> {
+    let mut minidumps: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension().is_some_and(|ext| ext == "dmp")
+                        && path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("crash_"))
+                        && !path.with_extension("json").exists()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    minidumps.sort();
+    minidumps
+}
+
+/// Writes the reports of the native crashes since the last launch, next to their minidumps.
+fn report_native_crashes(dir: &Path) {
+    for minidump in unreported_minidumps(dir) {
+        let mut report = CrashReport::new(CrashKind::Native, "Native crash".to_string());
+        if let Ok(modified) = fs::metadata(&minidump).and_then(|metadata| metadata.modified()) {
+            report.time = chrono::DateTime::<Utc>::from(modified).to_rfc3339();
+        }
+        // what this run did, not the crashed one
+        report.thread = None;
+        report.state.clear();
+        report.minidump = Some(minidump.clone());
+        let path = minidump.with_extension("json");
+        match serde_json::to_string_pretty(&report).map_err(anyhow::Error::from).and_then(|json| Ok(fs::write(&path, json)?)) {
+            Ok(()) => error!("eim crashed during the last run, crash report written to {}", path.display()),
+            Err(err) => warn!("Failed to write the report of {}: {}", minidump.display(), err),
+        }
+    }
+}
+
+/// Runs this process as the crash monitor of its parent if it was started as one, and exits
+/// once the parent is gone. Call first thing in `main`.
+pub fn run_monitor_if_requested() {
+    let Some(socket) = std::env::var_os(MONITOR_ENV) else {
+        return;
+    };
+    #[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+    if let Err(err) = run_monitor(Path::new(&socket)) {
+        eprintln!("The crash monitor failed: {}", err);
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    let _ = socket;
+    std::process::exit(0);
+}
+
+/// Writes the minidump of the parent when it crashes, from outside the crashed process.
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+struct MinidumpServer {
+    dir: PathBuf,
+}
+
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+impl minidumper::ServerHandler for MinidumpServer {
+    fn create_minidump_file(&self) -> std::io::Result<(fs::File, PathBuf)> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(crash_file_name("dmp"));
+        Ok((fs::File::create(&path)?, path))
+    }
+
+    fn on_minidump_created(&self, result: Result<minidumper::MinidumpBinary, minidumper::Error>) -> minidumper::LoopAction {
+        if let Err(err) = result {
+            eprintln!("Failed to write the minidump: {}", err);
+        }
+        minidumper::LoopAction::Exit
+    }
+
+    fn on_message(&self, _kind: u32, _buffer: Vec<u8>) {}
+
+    fn on_client_disconnected(&self, clients: usize) -> minidumper::LoopAction {
+        if clients == 0 {
+            minidumper::LoopAction::Exit
+        } else {
+            minidumper::LoopAction::Continue
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+fn run_monitor(socket: &Path) -> Result<()> {
+    let dir = get_crashes_directory().ok_or_else(|| anyhow!("No log directory"))?;
+    let mut server = minidumper::Server::with_name(socket)?;
+    let shutdown = std::sync::atomic::AtomicBool::new(false);
+    server.run(Box::new(MinidumpServer { dir }), &shutdown, None)?;
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+fn connect_to_monitor(socket: &Path) -> Result<minidumper::Client> {
+    // the monitor needs a moment to listen
+    for _ in 0..50 {
+        if let Ok(client) = minidumper::Client::with_name(socket) {
+            return Ok(client);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    Ok(minidumper::Client::with_name(socket)?)
+}
+
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+fn install_native_handler() {
+    let socket = std::env::temp_dir().join(format!("eim-crash-{}.sock", std::process::id()));
+    let monitor = std::env::current_exe().and_then(|exe| {
+        std::process::Command::new(exe)
+            .env(MONITOR_ENV, &socket)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+    });
+    let monitor = match monitor {
+        Ok(monitor) => monitor,
+        Err(err) => {
+            warn!("Failed to start the crash monitor: {}", err);
+            return;
+        }
+    };
+    let client = match connect_to_monitor(&socket) {
+        Ok(client) => client,
+        Err(err) => {
+            warn!("Failed to connect to the crash monitor: {}", err);
+            return;
+        }
+    };
+    // The handler runs in the crashed process, where nothing but async-signal-safe calls are
+    // allowed: no allocation, locks or logging. The monitor writes the minidump.
+    let handler = crash_handler::CrashHandler::attach(unsafe {
+        crash_handler::make_crash_event(move |context: &crash_handler::CrashContext| {
+            let _ = client.request_dump(context);
+            // the operating system still handles the crash
+            crash_handler::CrashEventResult::Handled(false)
+        })
+    });
+    match handler {
+        Ok(handler) => {
+            // the monitor reads the memory of this process, which Yama only allows to the parent
+            #[cfg(target_os = "linux")]
+            handler.set_ptracer(Some(monitor.id()));
+            // kept for the lifetime of the process
+            std::mem::forget(handler);
+        }
+        Err(err) => warn!("Failed to install the native crash handler: {}", err),
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = monitor;
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn install_native_handler() {}
+
+/// A crash report not offered to the user yet
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingCrash {
+    pub path: PathBuf,
+    pub report: CrashReport,
+}
+
+/// Crash reports written since they were last offered to the user, oldest first.
+pub fn pending_reports() -> Vec<PendingCrash> {
+    get_crashes_directory()
+        .map(|dir| list_reports(&dir))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|path| {
+            let report = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            Some(PendingCrash { path, report })
+        })
+        .collect()
+}
+
+/// Moves the reports to the seen folder, so they are not offered again. They are still in the
+/// log directory, for a support bundle made later.
+pub fn mark_seen(reports: &[PendingCrash]) {
+    for pending in reports {
+        let Some(dir) = pending.path.parent() else {
+            continue;
+        };
+        let seen = dir.join(SEEN_FOLDER_NAME);
+        if let Err(err) = fs::create_dir_all(&seen) {
+            warn!("Failed to create {}: {}", seen.display(), err);
+            return;
+        }
+        for path in [Some(pending.path.clone()), pending.report.minidump.clone()].into_iter().flatten() {
+            if let Some(name) = path.file_name() {
+                if let Err(err) = fs::rename(&path, seen.join(name)) {
+                    warn!("Failed to move {}: {}", path.display(), err);
+                }
+            }
+        }
+    }
+}
+
+/// Writes a zip with the crash reports, their minidumps, the logs of the CLI and the GUI and the
+/// status of eim, to attach to an issue.
+pub fn write_crash_bundle(path: &Path, reports: &[PendingCrash], settings: &Settings) -> Result<()> {
+    use crate::unattended::{add_bytes, add_file};
+
+    let mut zip = zip::ZipWriter::new(fs::File::create(path)?);
+    for pending in reports {
+        let name = pending
+            .path
+            .file_name()
+            .ok_or_else(|| anyhow!("Invalid crash report path {}", pending.path.display()))?
+            .to_string_lossy();
+        add_file(&mut zip, &format!("crashes/{}", name), &pending.path)?;
+        if let Some(minidump) = &pending.report.minidump {
+            if let Some(name) = minidump.file_name() {
+                add_file(&mut zip, &format!("crashes/{}", name.to_string_lossy()), minidump)?;
+            }
+        }
+    }
+    add_bytes(
+        &mut zip,
+        "status.json",
+        &serde_json::to_vec_pretty(&crate::status::collect(settings))?,
+    )?;
+    if let Some(log_dir) = crate::get_log_directory() {
+        for log in [crate::logging::CLI_LOG_FILE_NAME, crate::logging::GUI_LOG_FILE_NAME] {
+            add_file(&mut zip, log, &log_dir.join(log))?;
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Writes the support bundle of the pending crash reports into `dir` and marks them as seen,
+/// returning the bundle path.
+pub fn bundle_pending_reports(dir: &Path, reports: &[PendingCrash], settings: &Settings) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let bundle = dir.join(format!("eim_crash_{}.zip", Utc::now().format("%Y%m%dT%H%M%SZ")));
+    write_crash_bundle(&bundle, reports, settings)?;
+    info!("Crash support bundle written to {}", bundle.display());
+    mark_seen(reports);
+    Ok(bundle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("eim.log");
+        fs::write(&log, "1\n2\n3\n4\n").unwrap();
+        assert_eq!(tail_lines(&log, 2), vec!["3", "4"]);
+        assert_eq!(tail_lines(&log, 10).len(), 4);
+        assert!(tail_lines(&dir.path().join("missing.log"), 2).is_empty());
+    }
+
+    #[test]
+    fn test_report_native_crashes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("crash_20240101T000000.000Z.dmp"), "MDMP").unwrap();
+        fs::write(dir.path().join("crash_20240102T000000.000Z.dmp"), "MDMP").unwrap();
+        fs::write(dir.path().join("crash_20240102T000000.000Z.json"), "{}").unwrap();
+        assert_eq!(unreported_minidumps(dir.path()).len(), 1);
+
+        report_native_crashes(dir.path());
+        assert!(unreported_minidumps(dir.path()).is_empty());
+        let report: CrashReport =
+            serde_json::from_str(&fs::read_to_string(dir.path().join("crash_20240101T000000.000Z.json")).unwrap()).unwrap();
+        assert_eq!(report.kind, CrashKind::Native);
+        assert_eq!(report.minidump, Some(dir.path().join("crash_20240101T000000.000Z.dmp")));
+    }
+
+    #[test]
+    fn test_write_report_keeps_the_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..CRASH_REPORTS_KEPT + 2 {
+            fs::write(dir.path().join(format!("crash_2000010{:02}.json", i)), "{}").unwrap();
+        }
+        let report = CrashReport::new(CrashKind::Panic, "boom".to_string());
+        let path = write_report(dir.path(), &report).unwrap();
+        let reports = list_reports(dir.path());
+        assert_eq!(reports.len(), CRASH_REPORTS_KEPT);
+        assert_eq!(reports.last(), Some(&path));
+        let written: CrashReport = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(written.message, "boom");
+        assert_eq!(written.kind, CrashKind::Panic);
+    }
+}
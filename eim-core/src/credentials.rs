@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::RwLock;
+
+use crate::errors::{EimError, ErrorKind};
+
+/// Service name of the eim entries in the keyring
+pub const KEYRING_SERVICE: &str = "eim";
+
+/// A secret used by the installer, never written to configuration files.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Credential {
+    /// Password of the user given in the `proxy` URL
+    ProxyPassword,
+    /// Token sent as `Authorization: Bearer` to a private mirror
+    MirrorToken { host: String },
+}
+
+impl Credential {
+    /// Name of the keyring entry
+    pub fn account(&self) -> String {
+        match self {
+            Credential::ProxyPassword => "proxy".to_string(),
+            Credential::MirrorToken { host } => format!("mirror:{}", host),
+        }
+    }
+}
+
+/// Secrets read from stdin for this run only, and keyring lookups already done
+static SESSION_SECRETS: Lazy<RwLock<HashMap<Credential, Option<String>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Uses `secret` for this run without storing it, e.g. a password passed with `--stdin-password`.
+pub fn set_session_secret(credential: Credential, secret: String) {
+    if let Ok(mut secrets) = SESSION_SECRETS.write() {
+        secrets.insert(credential, Some(secret));
+    }
+}
+
+/// Reads a secret from the first line of stdin, so it does not show up in the process list or the shell history.
+pub fn read_secret_from_stdin() -> Result<String> {
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let secret = line.trim_end_matches(['\r', '\n']).to_string();
+    if secret.is_empty() {
+        return Err(EimError::new(ErrorKind::Usage, "No secret was given on stdin").into());
+    }
+    Ok(secret)
+}
+
+/// Returns the secret for `credential`, from this run or else from the keyring.
+///
+/// A keyring which is not available counts as no secret, lookups are cached for the run.
+pub fn get_secret(credential: &Credential) -> Option<String> {
+    if let Some(cached) = SESSION_SECRETS
+        .read()
+        .ok()
+        .and_then(|secrets| secrets.get(credential).cloned())
+    {
+        return cached;
+    }
+    let secret = match keyring_read(&credential.account()) {
+        Ok(secret) => secret,
+        Err(e) => {
+            debug!("Keyring lookup of {} failed: {}", credential.account(), e);
+            None
+        }
+    };
+    if let Ok(mut secrets) = SESSION_SECRETS.write() {
+        secrets.insert(credential.clone(), secret.clone());
+    }
+    secret
+}
+
+/// Forgets the secret cached for this run, so the next lookup reads the keyring again.
+pub fn forget_session_secret(credential: &Credential) {
+    if let Ok(mut secrets) = SESSION_SECRETS.write() {
+        secrets.remove(credential);
+    }
+}
+
+/// Stores `secret` in the keyring of the operating system.
+pub fn store_secret(credential: &Credential, secret: &str) -> Result<()> {
+    keyring_write(&credential.account(), secret)?;
+    set_session_secret(credential.clone(), secret.to_string());
+    Ok(())
+}
+
+/// Removes the secret from the keyring.
+pub fn delete_secret(credential: &Credential) -> Result<()> {
+    keyring_delete(&credential.account())?;
+    if let Ok(mut secrets) = SESSION_SECRETS.write() {
+        secrets.insert(credential.clone(), None);
+    }
+    Ok(())
+}
+
+fn keyring_unavailable(e: keyring::Error) -> anyhow::Error {
+    EimError::new(
+        ErrorKind::Prerequisites,
+        format!(
+            "The keyring is not available ({}), pass secrets with --stdin-password instead",
+            e
+        ),
+    )
+    .into()
+}
+
+/// Converts an error of the keyring, telling a missing keyring apart from a failed `action`.
+fn keyring_error(e: keyring::Error, action: &str) -> anyhow::Error {
+    match e {
+        keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_) => keyring_unavailable(e),
+        e => anyhow!("Failed to {} the keyring entry: {}", action, e),
+    }
+}
+
+/// Entry of `account` in the Secret Service on Linux, the Keychain on macOS and the
+/// Credential Manager on Windows.
+fn keyring_entry(account: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, account).map_err(keyring_unavailable)
+}
+
+fn keyring_write(account: &str, secret: &str) -> Result<()> {
+    keyring_entry(account)?
+        .set_password(secret)
+        .map_err(|e| keyring_error(e, "store"))
+}
+
+fn keyring_read(account: &str) -> Result<Option<String>> {
+    match keyring_entry(account)?.get_password() {
+        Ok(secret) => Ok(Some(secret).filter(|s| !s.is_empty())),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(keyring_error(e, "read")),
+    }
+}
+
+fn keyring_delete(account: &str) -> Result<()> {
+    match keyring_entry(account)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(keyring_error(e, "remove")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_secrets_take_precedence() {
+        let credential = Credential::MirrorToken {
+            host: "mirror.test.invalid".to_string(),
+        };
+        assert_eq!(credential.account(), "mirror:mirror.test.invalid");
+        set_session_secret(credential.clone(), "token".to_string());
+        assert_eq!(get_secret(&credential), Some("token".to_string()));
+    }
+}
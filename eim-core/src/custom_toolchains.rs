@@ -0,0 +1,179 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::command_executor;
+use crate::errors::{EimError, ErrorKind};
+use crate::ide_export::toolchain_prefix;
+use crate::settings::Settings;
+
+/// Registry of the self-built toolchains, next to `eim_idf.json`
+pub const TOOLCHAINS_FILE_NAME: &str = "eim_toolchains.json";
+
+/// A toolchain built outside of ESP-IDF, e.g. with crosstool-NG, used instead of the official one
+/// for its targets.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct CustomToolchain {
+    pub name: String,
+    /// Directory containing the compiler executables
+    pub bin_path: String,
+    pub targets: Vec<String>,
+    /// First line of `gcc --version`, recorded when registering
+    pub version: String,
+}
+
+pub fn get_registry_path() -> PathBuf {
+    PathBuf::from(Settings::default().esp_idf_json_path.unwrap_or_default()).join(TOOLCHAINS_FILE_NAME)
+}
+
+/// Returns the registered toolchains, empty if there are none.
+pub fn list_toolchains() -> Result<Vec<CustomToolchain>> {
+    let path = get_registry_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+}
+
+fn save_toolchains(toolchains: &[CustomToolchain]) -> Result<()> {
+    let path = get_registry_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(toolchains)?)?;
+    Ok(())
+}
+
+/// The `bin` folder of a toolchain, or `path` itself when the compilers are directly in it.
+pub fn find_bin_dir(path: &Path) -> PathBuf {
+    let bin = path.join("bin");
+    if bin.is_dir() {
+        bin
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Compiler executable building for `target`, e.g. `xtensa-esp32-elf-gcc`
+pub fn compiler_name(target: &str) -> String {
+    let name = format!("{}-gcc", toolchain_prefix(target));
+    if std::env::consts::OS == "windows" {
+        format!("{}.exe", name)
+    } else {
+        name
+    }
+}
+
+/// Checks that `bin_dir` has a compiler for each target which runs on this machine and returns
+/// the version it reports.
+pub fn validate(bin_dir: &Path, targets: &[String]) -> Result<String> {
+    if targets.is_empty() {
+        return Err(EimError::new(ErrorKind::Usage, "At least one target is required").into());
+    }
+    let mut version = String::new();
+    for target in targets {
+        let compiler = bin_dir.join(compiler_name(target));
+        if !compiler.is_file() {
+            return Err(EimError::new(
+                ErrorKind::NotFound,
+                format!("{} not found, the toolchain does not build for {}", compiler.display(), target),
+            )
+            .into());
+        }
+        let output = command_executor::execute_command(&compiler.to_string_lossy(), &["--version"])
+            .map_err(|e| anyhow!("{} does not run on this machine: {}", compiler.display(), e))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{} --version failed: {}",
+                compiler.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        version = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        debug!("{} reports {}", compiler.display(), version);
+    }
+    Ok(version)
+}
+
+/// Validates and registers a toolchain, replacing one registered under the same name.
+pub fn register(name: &str, path: &Path, targets: &[String]) -> Result<CustomToolchain> {
+    let path = path
+        .canonicalize()
+        .map_err(|e| EimError::new(ErrorKind::NotFound, format!("{}: {}", path.display(), e)))?;
+    let bin_dir = find_bin_dir(&path);
+    let version = validate(&bin_dir, targets)?;
+    let toolchain = CustomToolchain {
+        name: name.to_string(),
+        bin_path: bin_dir.to_string_lossy().into_owned(),
+        targets: targets.to_vec(),
+        version,
+    };
+    let mut toolchains = list_toolchains()?;
+    toolchains.retain(|t| t.name != name);
+    toolchains.push(toolchain.clone());
+    save_toolchains(&toolchains)?;
+    Ok(toolchain)
+}
+
+pub fn unregister(name: &str) -> Result<()> {
+    let mut toolchains = list_toolchains()?;
+    let count = toolchains.len();
+    toolchains.retain(|t| t.name != name);
+    if toolchains.len() == count {
+        return Err(EimError::new(ErrorKind::NotFound, format!("No toolchain named {} is registered", name)).into());
+    }
+    save_toolchains(&toolchains)
+}
+
+/// Directories of the registered toolchains to put in front of `PATH`, so their compilers win
+/// over the official ones. A broken registry is logged and ignored.
+pub fn export_paths() -> Vec<String> {
+    match list_toolchains() {
+        Ok(toolchains) => toolchains.into_iter().map(|t| t.bin_path).collect(),
+        Err(err) => {
+            log::warn!("Ignoring the registered toolchains: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_requires_compilers() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let bin = dir.path().join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        assert_eq!(find_bin_dir(dir.path()), bin);
+
+        let err = validate(&bin, &["esp32c3".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("riscv32-esp-elf-gcc"));
+        assert!(validate(&bin, &[]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_validate_runs_compiler() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let compiler = dir.path().join("xtensa-esp32-elf-gcc");
+        fs::write(&compiler, "#!/bin/sh\necho 'xtensa-esp32-elf-gcc (crosstool-NG custom) 14.2.0'\n").unwrap();
+        fs::set_permissions(&compiler, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(
+            validate(dir.path(), &["esp32".to_string()]).unwrap(),
+            "xtensa-esp32-elf-gcc (crosstool-NG custom) 14.2.0"
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::Result;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::checksum::sha256_files_parallel;
+
+/// Summary of a deduplication run.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DedupeReport {
+    /// Number of files replaced by a hard link
+    pub files_linked: u64,
+    /// Disk space freed by the replaced files
+    pub bytes_saved: u64,
+}
+
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &fs::Metadata, _b: &fs::Metadata) -> bool {
+    // file indexes are not exposed on stable Rust on Windows, the hash comparison decides
+    false
+}
+
+#[cfg(unix)]
+fn same_permissions(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    a.permissions().mode() == b.permissions().mode()
+}
+
+#[cfg(not(unix))]
+fn same_permissions(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    a.permissions().readonly() == b.permissions().readonly()
+}
+
+/// Collects all regular files below `dir` as paths relative to `root`.
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(root, &entry.path(), files)?;
+        } else if file_type.is_file() {
+            if let Ok(relative) = entry.path().strip_prefix(root) {
+                files.push(relative.to_path_buf());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces `duplicate` by a hard link to `canonical`.
+///
+/// The link is created next to the duplicate first and renamed over it, so the
+/// file never disappears if linking fails half way.
+fn replace_with_hard_link(canonical: &Path, duplicate: &Path) -> io::Result<()> {
+    let temporary = duplicate.with_file_name(format!(
+        ".{}.eim-dedupe",
+        duplicate.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    if temporary.exists() {
+        fs::remove_file(&temporary)?;
+    }
+    fs::hard_link(canonical, &temporary)?;
+    if let Err(err) = fs::rename(&temporary, duplicate) {
+        let _ = fs::remove_file(&temporary);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Hard-links identical tool files shared by several tools directories.
+///
+/// Files are matched by their path relative to the tools directory, which includes the
+/// tool name and version (e.g. `xtensa-esp-elf/esp-14.2.0_20241119/bin/...`), and are only
+/// linked when size, permissions and SHA256 are equal. The first directory in `tool_dirs`
+/// keeps its files, duplicates in the following ones are replaced. With `dry_run`, nothing
+/// is changed and the report tells how much space would be saved.
+pub fn dedupe_tool_dirs(tool_dirs: &[PathBuf], dry_run: bool) -> Result<DedupeReport> {
+    let mut report = DedupeReport::default();
+    // relative path -> first file found with it
+    let mut canonicals: HashMap<PathBuf, (PathBuf, fs::Metadata)> = HashMap::new();
+    // (canonical, duplicate, size) pairs which still need their content compared
+    let mut candidates: Vec<(PathBuf, PathBuf, u64)> = Vec::new();
+
+    for tools_dir in tool_dirs {
+        if !tools_dir.is_dir() {
+            debug!("Skipping missing tools directory {}", tools_dir.display());
+            continue;
+        }
+        let mut files = Vec::new();
+        collect_files(tools_dir, tools_dir, &mut files)?;
+
+        for relative in files {
+            let path = tools_dir.join(&relative);
+            let metadata = fs::symlink_metadata(&path)?;
+            match canonicals.get(&relative) {
+                None => {
+                    canonicals.insert(relative, (path, metadata));
+                }
+                Some((canonical, canonical_metadata)) => {
+                    if canonical_metadata.len() == metadata.len()
+                        && metadata.len() > 0
+                        && !same_file(canonical_metadata, &metadata)
+                        && same_permissions(canonical_metadata, &metadata)
+                    {
+                        candidates.push((canonical.clone(), path, metadata.len()));
+                    }
+                }
+            }
+        }
+    }
+
+    // Hashing dominates the run time, so all candidates are hashed in parallel
+    let mut to_hash: Vec<PathBuf> = candidates
+        .iter()
+        .flat_map(|(canonical, duplicate, _)| [canonical.clone(), duplicate.clone()])
+        .collect();
+    to_hash.sort();
+    to_hash.dedup();
+    let hashes: HashMap<PathBuf, String> = to_hash
+        .iter()
+        .cloned()
+        .zip(sha256_files_parallel(&to_hash))
+        .filter_map(|(path, hash)| match hash {
+            Ok(hash) => Some((path, hash)),
+            Err(err) => {
+                warn!("Failed to hash {}: {}", path.display(), err);
+                None
+            }
+        })
+        .collect();
+
+    for (canonical, duplicate, size) in candidates {
+        match (hashes.get(&canonical), hashes.get(&duplicate)) {
+            (Some(a), Some(b)) if a == b => {}
+            _ => continue,
+        }
+        if !dry_run {
+            if let Err(err) = replace_with_hard_link(&canonical, &duplicate) {
+                // e.g. the installations are on different filesystems
+                warn!("Failed to link {} to {}: {}", duplicate.display(), canonical.display(), err);
+                continue;
+            }
+        }
+        report.files_linked += 1;
+        report.bytes_saved += size;
+    }
+
+    info!(
+        "Deduplication {} {} files, {} bytes",
+        if dry_run { "would link" } else { "linked" },
+        report.files_linked,
+        report.bytes_saved
+    );
+    Ok(report)
+}
+
+/// Returns the tools directories of all installed versions, without duplicates.
+pub fn installed_tool_dirs() -> Result<Vec<PathBuf>> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for version in crate::version_manager::list_installed_versions()? {
+        let dir = PathBuf::from(&version.idf_tools_path);
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    Ok(dirs)
+}
+
+/// Deduplicates a freshly installed tools directory against all installed versions.
+pub fn dedupe_new_installation(tools_dir: &Path) -> Result<DedupeReport> {
+    let mut dirs = installed_tool_dirs().unwrap_or_default();
+    dirs.retain(|d| d != tools_dir);
+    dirs.push(tools_dir.to_path_buf());
+    dedupe_tool_dirs(&dirs, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_identical_files_are_linked() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("v5.3/tools");
+        let second = temp_dir.path().join("v5.4/tools");
+        for dir in [&first, &second] {
+            fs::create_dir_all(dir.join("cmake/3.30.2/bin")).unwrap();
+            fs::write(dir.join("cmake/3.30.2/bin/cmake"), "cmake binary").unwrap();
+        }
+        fs::write(first.join("cmake/3.30.2/bin/ctest"), "ctest 1").unwrap();
+        fs::write(second.join("cmake/3.30.2/bin/ctest"), "ctest 2").unwrap();
+
+        let dirs = vec![first.clone(), second.clone()];
+        let report = dedupe_tool_dirs(&dirs, true).unwrap();
+        assert_eq!(report.files_linked, 1);
+
+        let report = dedupe_tool_dirs(&dirs, false).unwrap();
+        assert_eq!(report.bytes_saved, "cmake binary".len() as u64);
+        assert_eq!(
+            fs::read_to_string(second.join("cmake/3.30.2/bin/cmake")).unwrap(),
+            "cmake binary"
+        );
+        assert_eq!(
+            fs::read_to_string(second.join("cmake/3.30.2/bin/ctest")).unwrap(),
+            "ctest 2"
+        );
+        #[cfg(unix)]
+        assert_eq!(dedupe_tool_dirs(&dirs, false).unwrap().files_linked, 0);
+    }
+}
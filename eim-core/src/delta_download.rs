@@ -0,0 +1,204 @@
+use log::{debug, info};
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::settings::Settings;
+
+/// Suffix of the index next to an archive listing the patches which produce it
+pub const DELTA_INDEX_SUFFIX: &str = ".deltas.json";
+/// Largest zstd window, a patch references the whole previous archive
+const MAX_WINDOW_LOG: u32 = 31;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Applies the `differential_downloads` setting.
+pub fn configure_from_settings(settings: &Settings) {
+    ENABLED.store(settings.differential_downloads.unwrap_or(false), Ordering::Relaxed);
+}
+
+/// `<archive url>.deltas.json`, published by a mirror next to an archive for which it generated
+/// patches from previous versions.
+#[derive(Debug, Deserialize)]
+struct DeltaIndex {
+    deltas: Vec<Delta>,
+}
+
+/// A zstd `--patch-from` patch turning the archive `from` into the one the index is for.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+struct Delta {
+    /// File name of the previous archive
+    from: String,
+    from_sha256: String,
+    /// URL of the patch, relative to the index or absolute
+    patch: String,
+}
+
+fn patch_url(index_url: &str, patch: &str) -> String {
+    if patch.contains("://") {
+        return patch.to_string();
+    }
+    match index_url.rfind('/') {
+        Some(slash) => format!("{}/{}", &index_url[..slash], patch),
+        None => patch.to_string(),
+    }
+}
+
+/// The first delta whose previous archive is in `download_dir` and intact.
+fn find_base<'a>(index: &'a DeltaIndex, download_dir: &Path) -> Option<(&'a Delta, PathBuf)> {
+    index.deltas.iter().find_map(|delta| {
+        // a file name only, not a path leading out of the download folder
+        if Path::new(&delta.from).file_name()? != std::ffi::OsStr::new(&delta.from) {
+            return None;
+        }
+        let base = download_dir.join(&delta.from);
+        if !base.is_file() {
+            return None;
+        }
+        let intact = crate::checksum::is_verified(&base, &delta.from_sha256)
+            || crate::checksum::sha256_file(&base).is_ok_and(|sha256| sha256.eq_ignore_ascii_case(&delta.from_sha256));
+        intact.then_some((delta, base))
+    })
+}
+
+/// Writes `output` from `base` and the zstd `patch`, as `zstd -d --patch-from=base patch` does.
+///
+/// The base archive is memory-mapped rather than read, toolchain archives are hundreds of MB.
+pub fn apply_patch(base: &Path, patch: &Path, output: &Path) -> io::Result<()> {
+    // SAFETY: the base is a cached archive in the download folder, which nothing else writes
+    // while the installer runs; a concurrent change would only corrupt the output, which the
+    // caller verifies against its checksum
+    let reference = unsafe { memmap2::Mmap::map(&File::open(base)?)? };
+    let mut decoder = zstd::stream::read::Decoder::with_ref_prefix(BufReader::new(File::open(patch)?), &reference[..])?;
+    decoder.window_log_max(MAX_WINDOW_LOG)?;
+    let mut writer = BufWriter::new(File::create(output)?);
+    io::copy(&mut decoder, &mut writer)?;
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+async fn fetch_index(url: &str) -> Option<DeltaIndex> {
+    let client = crate::http_client::client_builder().build().ok()?;
+    let response = crate::http_client::authorize(client.get(url), url).send().await.ok()?;
+    if !response.status().is_success() {
+        debug!("No patches for the archive, {} returned {}", url, response.status());
+        return None;
+    }
+    response.json().await.ok()
+}
+
+/// Creates `target`, the archive of `url`, by patching an archive of a previous version of the
+/// tool cached in `download_dir`, if the server publishes a patch from one of them.
+///
+/// Returns whether `target` was written; the caller verifies its checksum and downloads the
+/// whole archive otherwise. Every failure just means no patch, the full download follows, so
+/// no progress is reported: a failed patch download would show as a failed installation.
+pub async fn try_patch(url: &str, download_dir: &Path, target: &Path) -> bool {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return false;
+    }
+    let index_url = format!("{}{}", url, DELTA_INDEX_SUFFIX);
+    let Some(index) = fetch_index(&index_url).await else {
+        return false;
+    };
+    let Some((delta, base)) = find_base(&index, download_dir) else {
+        debug!("None of the archives {} has patches from is cached", url);
+        return false;
+    };
+
+    let patch_url = patch_url(&index_url, &delta.patch);
+    let patch_name = format!(
+        "{}.eim-patch",
+        target.file_name().unwrap_or_default().to_string_lossy()
+    );
+    let patch = download_dir.join(&patch_name);
+    if let Err(e) = crate::download_file_and_rename(&patch_url, &download_dir.to_string_lossy(), None, Some(&patch_name)).await {
+        debug!("Failed to download the patch {}: {}", patch_url, e);
+        let _ = fs::remove_file(&patch);
+        return false;
+    }
+    let patch_size = fs::metadata(&patch).map(|metadata| metadata.len()).unwrap_or_default();
+
+    let (base_path, patch_path, target_path) = (base.clone(), patch.clone(), target.to_path_buf());
+    let applied = tokio::task::spawn_blocking(move || apply_patch(&base_path, &patch_path, &target_path)).await;
+    let _ = fs::remove_file(&patch);
+    match applied {
+        Ok(Ok(())) => {
+            info!(
+                "Created {} from {} with a patch of {} bytes",
+                target.display(),
+                base.display(),
+                patch_size
+            );
+            true
+        }
+        Ok(Err(e)) => {
+            debug!("Failed to apply {} to {}: {}", patch_url, base.display(), e);
+            let _ = fs::remove_file(target);
+            false
+        }
+        Err(e) => {
+            debug!("Applying {} panicked: {}", patch_url, e);
+            let _ = fs::remove_file(target);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_patch_url() {
+        let index = "https://mirror.example/tools/cmake-3.30.2-linux.tar.gz.deltas.json";
+        assert_eq!(patch_url(index, "cmake-3.30.2-from-3.24.0.zst"), "https://mirror.example/tools/cmake-3.30.2-from-3.24.0.zst");
+        assert_eq!(patch_url(index, "https://cdn.example/p.zst"), "https://cdn.example/p.zst");
+    }
+
+    #[test]
+    fn test_find_base() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("cmake-3.24.0.tar.gz"), b"old archive").unwrap();
+        let sha256 = crate::checksum::sha256_file(&dir.path().join("cmake-3.24.0.tar.gz")).unwrap();
+        let delta = |from: &str, from_sha256: &str| Delta {
+            from: from.to_string(),
+            from_sha256: from_sha256.to_string(),
+            patch: format!("{}.zst", from),
+        };
+        let index = DeltaIndex {
+            deltas: vec![
+                delta("cmake-3.16.0.tar.gz", &sha256),
+                delta("../cmake-3.24.0.tar.gz", &sha256),
+                delta("cmake-3.24.0.tar.gz", "0000"),
+                delta("cmake-3.24.0.tar.gz", &sha256),
+            ],
+        };
+        let (found, base) = find_base(&index, dir.path()).unwrap();
+        assert_eq!(found, &index.deltas[3]);
+        assert_eq!(base, dir.path().join("cmake-3.24.0.tar.gz"));
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let dir = TempDir::new().unwrap();
+        let old: Vec<u8> = (0..200_000u32).flat_map(|i| (i % 251).to_le_bytes()).collect();
+        let mut new = old.clone();
+        new[1000..1010].copy_from_slice(b"new tools!");
+        new.extend_from_slice(b"appended");
+
+        let mut patch = Vec::new();
+        let mut encoder = zstd::stream::write::Encoder::with_ref_prefix(&mut patch, 19, &old).unwrap();
+        encoder.write_all(&new).unwrap();
+        encoder.finish().unwrap();
+
+        fs::write(dir.path().join("old.tar"), &old).unwrap();
+        fs::write(dir.path().join("patch.zst"), &patch).unwrap();
+        let output = dir.path().join("new.tar");
+        apply_patch(&dir.path().join("old.tar"), &dir.path().join("patch.zst"), &output).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), new);
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, Local};
+use rust_i18n::t;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::settings::Settings;
+
+/// Values of the `size_units` setting
+pub const SIZE_UNITS: &[&str] = &["decimal", "binary"];
+
+/// Languages writing a decimal comma, e.g. `1,5 MB`; all others write a period
+const DECIMAL_COMMA_LANGUAGES: &[&str] = &[
+    "cs", "da", "de", "es", "fi", "fr", "id", "it", "nb", "nl", "pl", "pt", "ro", "ru", "sv", "tr", "uk",
+];
+
+/// Sizes in KiB, MiB and GiB (1024) instead of kB, MB and GB (1000)
+static BINARY_UNITS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_binary_units(binary: bool) {
+    BINARY_UNITS.store(binary, Ordering::Relaxed);
+}
+
+/// Applies the `size_units` setting.
+pub fn configure_from_settings(settings: &Settings) {
+    let units = settings.size_units.as_deref().unwrap_or("decimal");
+    if !SIZE_UNITS.contains(&units) {
+        log::warn!("Unknown size_units '{}', expected one of {}", units, SIZE_UNITS.join(", "));
+    }
+    set_binary_units(units == "binary");
+}
+
+/// Decimal separator of `locale`, e.g. `de-DE` or `pt_BR`.
+pub fn decimal_separator(locale: &str) -> char {
+    let language = locale.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+    if DECIMAL_COMMA_LANGUAGES.contains(&language.as_str()) {
+        ','
+    } else {
+        '.'
+    }
+}
+
+/// `value` with one decimal and the separator of the selected locale.
+pub fn format_decimal(value: f64) -> String {
+    let formatted = format!("{:.1}", value);
+    match decimal_separator(&rust_i18n::locale()) {
+        '.' => formatted,
+        separator => formatted.replace('.', &separator.to_string()),
+    }
+}
+
+fn format_with_units(value: u64, binary: bool, suffix: &str) -> String {
+    let (base, units): (f64, [&str; 5]) = if binary {
+        (1024.0, ["B", "KiB", "MiB", "GiB", "TiB"])
+    } else {
+        (1000.0, ["B", "kB", "MB", "GB", "TB"])
+    };
+    let mut scaled = value as f64;
+    let mut unit = 0;
+    while scaled >= base && unit < units.len() - 1 {
+        scaled /= base;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}{}", value, units[0], suffix)
+    } else {
+        format!("{} {}{}", format_decimal(scaled), units[unit], suffix)
+    }
+}
+
+/// Size for display, e.g. `3.2 MB`, or `3.1 MiB` with `size_units = "binary"`.
+pub fn format_size(bytes: u64) -> String {
+    format_with_units(bytes, BINARY_UNITS.load(Ordering::Relaxed), "")
+}
+
+/// Speed for display, e.g. `3.2 MB/s`.
+pub fn format_speed(bytes_per_second: u64) -> String {
+    format_with_units(bytes_per_second, BINARY_UNITS.load(Ordering::Relaxed), "/s")
+}
+
+/// Time left for display, e.g. `4m 05s`.
+pub fn format_eta(seconds: u64) -> String {
+    match seconds {
+        0..=59 => t!("format.eta.seconds", s = seconds).to_string(),
+        60..=3599 => t!("format.eta.minutes", m = seconds / 60, s = format!("{:02}", seconds % 60)).to_string(),
+        _ => t!("format.eta.hours", h = seconds / 3600, m = format!("{:02}", seconds % 3600 / 60)).to_string(),
+    }
+}
+
+/// An RFC 3339 timestamp, as stored by eim, in local time and the date format of the selected
+/// locale. Anything else is returned as it is.
+pub fn format_timestamp(timestamp: &str) -> String {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(time) => time
+            .with_timezone(&Local)
+            .format(&t!("format.datetime"))
+            .to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimal_separator() {
+        assert_eq!(decimal_separator("en"), '.');
+        assert_eq!(decimal_separator("cn"), '.');
+        assert_eq!(decimal_separator("de-DE"), ',');
+        assert_eq!(decimal_separator("pt_BR"), ',');
+    }
+
+    #[test]
+    fn test_units() {
+        assert_eq!(format_with_units(512, false, ""), "512 B");
+        assert_eq!(format_with_units(3_240_000, false, "/s"), "3.2 MB/s");
+        assert_eq!(format_with_units(3_240_000, true, ""), "3.1 MiB");
+        assert_eq!(format_with_units(5 * 1024 * 1024 * 1024, true, ""), "5.0 GiB");
+    }
+
+    #[test]
+    fn test_format_timestamp_keeps_unknown_values() {
+        assert_eq!(format_timestamp("unknown"), "unknown");
+    }
+}
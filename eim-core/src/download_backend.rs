@@ -0,0 +1,463 @@
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::future::Future;
+use std::io::Write;
+use std::path::Path;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::mpsc::Sender;
+use std::sync::RwLock;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::settings::Settings;
+use crate::{http_client, DownloadProgress};
+
+/// How often the size of a file downloaded by an external tool is reported as progress
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Which implementation downloads files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// Built-in HTTP client
+    #[default]
+    Reqwest,
+    /// The `curl` of the system, with its proxy configuration (`.curlrc`, environment)
+    Curl,
+    /// The `aria2c` download utility
+    Aria2c,
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reqwest" | "builtin" => Ok(BackendKind::Reqwest),
+            "curl" => Ok(BackendKind::Curl),
+            "aria2c" | "aria2" => Ok(BackendKind::Aria2c),
+            _ => Err(format!(
+                "Unknown download backend '{}', expected one of: reqwest, curl, aria2c",
+                s
+            )),
+        }
+    }
+}
+
+/// A failed download attempt, `retryable` unless the server refused the request for good.
+pub struct DownloadFailure {
+    pub error: std::io::Error,
+    pub retryable: bool,
+    /// The proxy answered 407, the attempt can be repeated once its credentials are refreshed
+    pub proxy_auth: bool,
+}
+
+impl From<std::io::Error> for DownloadFailure {
+    fn from(error: std::io::Error) -> Self {
+        Self {
+            error,
+            retryable: true,
+            proxy_auth: false,
+        }
+    }
+}
+
+impl DownloadFailure {
+    fn proxy_auth_required() -> Self {
+        Self {
+            error: other_io_error("HTTP error: 407 Proxy Authentication Required"),
+            retryable: false,
+            proxy_auth: true,
+        }
+    }
+}
+
+fn other_io_error(error: impl ToString) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, error.to_string())
+}
+
+/// Other client errors won't go away by asking again.
+fn is_retryable_status(status: u16) -> bool {
+    !(400..500).contains(&status) || status == 408 || status == 429
+}
+
+/// Whether the proxy refused to tunnel an HTTPS request for lack of valid credentials, which the
+/// client reports as an error instead of a 407 response.
+fn is_proxy_auth_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if error.to_string().to_lowercase().contains("proxy authentication required") {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+/// One download to run, retries are up to the caller.
+pub struct DownloadRequest<'a> {
+    pub url: &'a str,
+    /// The file to create, replaced if it exists
+    pub destination: &'a Path,
+    pub progress_sender: &'a Option<Sender<DownloadProgress>>,
+    /// Abort when nothing is received for this long, `None` waits forever
+    pub stall_timeout: Option<Duration>,
+}
+
+impl DownloadRequest<'_> {
+    fn report(&self, downloaded: u64, total: u64) -> Result<(), DownloadFailure> {
+        if let Some(sender) = self.progress_sender {
+            if let Err(e) = sender.send(DownloadProgress::Progress(downloaded, total)) {
+                return Err(DownloadFailure {
+                    error: other_io_error(format!("Failed to send progress: {}", e)),
+                    retryable: false,
+                    proxy_auth: false,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+pub type DownloadFuture<'a> = Pin<Box<dyn Future<Output = Result<(), DownloadFailure>> + Send + 'a>>;
+
+/// Something which can download a file over HTTP(S).
+pub trait DownloadBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Makes one attempt at downloading `request.url` to `request.destination`.
+    fn download<'a>(&'a self, request: &'a DownloadRequest<'a>) -> DownloadFuture<'a>;
+}
+
+/// Downloads with the built-in client, configured by [`http_client`].
+pub struct ReqwestBackend;
+
+impl DownloadBackend for ReqwestBackend {
+    fn name(&self) -> &'static str {
+        "reqwest"
+    }
+
+    fn download<'a>(&'a self, request: &'a DownloadRequest<'a>) -> DownloadFuture<'a> {
+        Box::pin(async move {
+            let client = http_client::client();
+            let mut response = http_client::authorize(client.get(request.url), request.url)
+                .send()
+                .await
+                .map_err(|e| {
+                    if is_proxy_auth_error(&e) {
+                        DownloadFailure::proxy_auth_required()
+                    } else {
+                        other_io_error(e).into()
+                    }
+                })?;
+
+            let status = response.status();
+            if status == reqwest::StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+                return Err(DownloadFailure::proxy_auth_required());
+            }
+            if !status.is_success() {
+                return Err(DownloadFailure {
+                    error: other_io_error(format!("HTTP error: {}", status)),
+                    retryable: is_retryable_status(status.as_u16()),
+                    proxy_auth: false,
+                });
+            }
+
+            let total_size = response.content_length().ok_or_else(|| DownloadFailure {
+                error: other_io_error("Failed to get content length"),
+                retryable: false,
+                proxy_auth: false,
+            })?;
+            // a retry starts the file over
+            let mut file = File::create(request.destination)?;
+            let mut downloaded: u64 = 0;
+
+            // a connection which stays silent for the stall timeout is dropped
+            loop {
+                let chunk = match request.stall_timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, response.chunk())
+                        .await
+                        .map_err(|_| other_io_error(format!("No data received for {}s", timeout.as_secs())))?,
+                    None => response.chunk().await,
+                };
+                let Some(chunk) = chunk.map_err(other_io_error)? else {
+                    break;
+                };
+                downloaded += chunk.len() as u64;
+                file.write_all(&chunk)?;
+                request.report(downloaded, total_size)?;
+            }
+            if downloaded < total_size {
+                return Err(other_io_error(format!("Download ended after {} of {} bytes", downloaded, total_size)).into());
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Quotes a value for a curl config file.
+fn curl_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Status of the request and of the CONNECT to the proxy from the `write-out` of curl, 0 when missing.
+fn parse_curl_status(write_out: &str) -> (u16, u16) {
+    let mut codes = write_out.split_whitespace().map(|code| code.parse().unwrap_or(0));
+    (codes.next().unwrap_or(0), codes.next().unwrap_or(0))
+}
+
+/// Downloads with the `curl` of the system. Without a `proxy` setting curl uses its own proxy
+/// configuration, which is what some corporate networks only work with.
+pub struct CurlBackend;
+
+impl CurlBackend {
+    /// Options passed on stdin, so the proxy password and the mirror token do not show up in
+    /// the process list.
+    fn config(request: &DownloadRequest) -> String {
+        let mut config = vec![
+            format!("url = {}", curl_quote(request.url)),
+            format!("output = {}", curl_quote(&request.destination.to_string_lossy())),
+            "location".to_string(),
+            "silent".to_string(),
+            "show-error".to_string(),
+            // the status of the CONNECT to the proxy is separate from the one of the request
+            "write-out = \"%{http_code} %{http_connect}\"".to_string(),
+            "user-agent = \"esp-idf-installer\"".to_string(),
+        ];
+        if let Some(timeout) = request.stall_timeout {
+            config.push("speed-limit = 1".to_string());
+            config.push(format!("speed-time = {}", timeout.as_secs().max(1)));
+        }
+        if let Some(proxy) = http_client::proxy_url_with_password() {
+            config.push(format!("proxy = {}", curl_quote(proxy.as_str())));
+        }
+        if let Some(bundle) = http_client::ca_bundle() {
+            config.push(format!("cacert = {}", curl_quote(&bundle.to_string_lossy())));
+        }
+        if let Some(token) = http_client::mirror_token(request.url) {
+            config.push(format!("header = {}", curl_quote(&format!("Authorization: Bearer {}", token))));
+        }
+        config.join("\n") + "\n"
+    }
+}
+
+impl DownloadBackend for CurlBackend {
+    fn name(&self) -> &'static str {
+        "curl"
+    }
+
+    fn download<'a>(&'a self, request: &'a DownloadRequest<'a>) -> DownloadFuture<'a> {
+        Box::pin(async move {
+            let output = run_external("curl", &["--config", "-"], &Self::config(request), request).await?;
+            let (status, connect_status) = parse_curl_status(&String::from_utf8_lossy(&output.stdout));
+            if output.status.success() && (200..300).contains(&status) {
+                return Ok(());
+            }
+            if status == 407 || connect_status == 407 {
+                return Err(DownloadFailure::proxy_auth_required());
+            }
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            Err(DownloadFailure {
+                error: other_io_error(if status >= 400 {
+                    format!("HTTP error: {}", status)
+                } else {
+                    format!("curl failed: {}", stderr)
+                }),
+                retryable: status < 400 || is_retryable_status(status),
+                proxy_auth: false,
+            })
+        })
+    }
+}
+
+/// Downloads with `aria2c`.
+pub struct Aria2cBackend;
+
+impl Aria2cBackend {
+    /// Input file read from stdin, keeping the secrets out of the process list like for curl.
+    fn input(request: &DownloadRequest) -> String {
+        let mut input = vec![request.url.to_string()];
+        let mut option = |name: &str, value: &str| input.push(format!("  {}={}", name, value));
+        option("dir", &request.destination.parent().unwrap_or(Path::new(".")).to_string_lossy());
+        option("out", &request.destination.file_name().unwrap_or_default().to_string_lossy());
+        if let Some(proxy) = http_client::proxy_url_with_password() {
+            option("all-proxy", proxy.as_str());
+        }
+        if let Some(bundle) = http_client::ca_bundle() {
+            option("ca-certificate", &bundle.to_string_lossy());
+        }
+        if let Some(token) = http_client::mirror_token(request.url) {
+            option("header", &format!("Authorization: Bearer {}", token));
+        }
+        input.join("\n") + "\n"
+    }
+}
+
+impl DownloadBackend for Aria2cBackend {
+    fn name(&self) -> &'static str {
+        "aria2c"
+    }
+
+    fn download<'a>(&'a self, request: &'a DownloadRequest<'a>) -> DownloadFuture<'a> {
+        Box::pin(async move {
+            let timeout = request
+                .stall_timeout
+                .map(|timeout| format!("--timeout={}", timeout.as_secs().max(1)));
+            let mut args = vec![
+                "--input-file=-",
+                // retries are done by the caller, with the configured policy
+                "--max-tries=1",
+                "--allow-overwrite=true",
+                "--auto-file-renaming=false",
+                // the file grows as it is downloaded, its size is the progress
+                "--file-allocation=none",
+                "--console-log-level=error",
+                "--summary-interval=0",
+                "--user-agent=esp-idf-installer",
+            ];
+            args.extend(timeout.as_deref());
+            let output = run_external("aria2c", &args, &Self::input(request), request).await?;
+            if output.status.success() {
+                return Ok(());
+            }
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            // 3: resource not found, 24: authorization failed
+            let code = output.status.code().unwrap_or(-1);
+            Err(DownloadFailure {
+                error: other_io_error(format!("aria2c failed with exit code {}: {}", code, stderr)),
+                retryable: !matches!(code, 3 | 24),
+                proxy_auth: false,
+            })
+        })
+    }
+}
+
+/// Runs a download tool with `stdin` as its input, reporting the size of the destination file
+/// as progress while it runs. The total is not known to the installer, so it is reported equal
+/// to the bytes received.
+async fn run_external(
+    program: &str,
+    args: &[&str],
+    stdin: &str,
+    request: &DownloadRequest<'_>,
+) -> Result<std::process::Output, DownloadFailure> {
+    debug!("Downloading {} with {}", request.url, program);
+    let mut command = tokio::process::Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(windows)]
+    command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    let mut child = command.spawn().map_err(|e| DownloadFailure {
+        error: other_io_error(format!(
+            "Failed to run {}: {}. Install it or set download_backend to \"reqwest\"",
+            program, e
+        )),
+        retryable: false,
+        proxy_auth: false,
+    })?;
+    if let Some(mut input) = child.stdin.take() {
+        input.write_all(stdin.as_bytes()).await?;
+    }
+
+    let output = child.wait_with_output();
+    tokio::pin!(output);
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            output = &mut output => {
+                let output = output?;
+                if output.status.success() {
+                    let size = std::fs::metadata(request.destination).map(|m| m.len()).unwrap_or(0);
+                    request.report(size, size)?;
+                }
+                return Ok(output);
+            }
+            _ = interval.tick() => {
+                if let Ok(metadata) = std::fs::metadata(request.destination) {
+                    request.report(metadata.len(), metadata.len())?;
+                }
+            }
+        }
+    }
+}
+
+static BACKEND: Lazy<RwLock<BackendKind>> = Lazy::new(|| RwLock::new(BackendKind::Reqwest));
+
+pub fn set_backend(kind: BackendKind) {
+    if let Ok(mut guard) = BACKEND.write() {
+        *guard = kind;
+    }
+}
+
+pub fn get_backend() -> BackendKind {
+    BACKEND.read().map(|kind| *kind).unwrap_or_default()
+}
+
+/// Updates the backend from the `download_backend` setting.
+pub fn configure_from_settings(settings: &Settings) {
+    let kind = match settings.download_backend.as_deref().map(BackendKind::from_str) {
+        Some(Ok(kind)) => kind,
+        Some(Err(e)) => {
+            warn!("{}, using reqwest", e);
+            BackendKind::Reqwest
+        }
+        None => BackendKind::Reqwest,
+    };
+    set_backend(kind);
+}
+
+/// The backend selected by the configuration.
+pub fn current() -> Box<dyn DownloadBackend> {
+    match get_backend() {
+        BackendKind::Reqwest => Box::new(ReqwestBackend),
+        BackendKind::Curl => Box::new(CurlBackend),
+        BackendKind::Aria2c => Box::new(Aria2cBackend),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_kind_from_str() {
+        assert_eq!("CURL".parse::<BackendKind>(), Ok(BackendKind::Curl));
+        assert_eq!("aria2".parse::<BackendKind>(), Ok(BackendKind::Aria2c));
+        assert_eq!("reqwest".parse::<BackendKind>(), Ok(BackendKind::Reqwest));
+        assert!("wget".parse::<BackendKind>().is_err());
+    }
+
+    #[test]
+    fn test_curl_quote() {
+        assert_eq!(curl_quote("C:\\dist\\a \"b\".zip"), "\"C:\\\\dist\\\\a \\\"b\\\".zip\"");
+    }
+
+    #[test]
+    fn test_parse_curl_status() {
+        assert_eq!(parse_curl_status("200 000"), (200, 0));
+        assert_eq!(parse_curl_status("000 407"), (0, 407));
+        assert_eq!(parse_curl_status("407"), (407, 0));
+        assert_eq!(parse_curl_status(""), (0, 0));
+    }
+
+    #[test]
+    fn test_proxy_auth_error() {
+        assert!(is_proxy_auth_error(&other_io_error("Proxy Authentication Required")));
+        assert!(!is_proxy_auth_error(&other_io_error("connection refused")));
+    }
+
+    #[test]
+    fn test_retryable_status() {
+        assert!(is_retryable_status(500));
+        assert!(is_retryable_status(429));
+        assert!(!is_retryable_status(404));
+    }
+}
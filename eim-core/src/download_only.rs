@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::settings::{Settings, VersionPaths};
+
+/// Folder in the tools download folder holding the Python packages fetched by `--download-only`
+pub const PACKAGES_FOLDER: &str = "python-packages";
+const MANIFEST_FILE: &str = "download-only.json";
+const DEFAULT_DOWNLOAD_FOLDER: &str = "dist";
+
+/// What `eim install --download-only` fetched for a version, read back by the installation
+/// finishing it without network.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DownloadedVersion {
+    pub version: String,
+    pub targets: Vec<String>,
+    pub features: Vec<String>,
+    /// Python version the packages are built for, e.g. "3.11"
+    pub python_version: String,
+    /// File names of the verified tool archives
+    pub tool_archives: Vec<String>,
+}
+
+/// The tools download folder of a version, as the installation resolves it.
+pub fn download_dir(settings: &Settings, paths: &VersionPaths) -> PathBuf {
+    paths.version_installation_path.join(
+        settings
+            .tool_download_folder_name
+            .as_deref()
+            .unwrap_or(DEFAULT_DOWNLOAD_FOLDER),
+    )
+}
+
+/// Folder of the Python packages and the constraints file of `idf_version`, laid out like an
+/// offline archive.
+pub fn packages_dir(download_dir: &Path, idf_version: &str) -> PathBuf {
+    download_dir.join(PACKAGES_FOLDER).join(idf_version)
+}
+
+pub fn record(download_dir: &Path, downloaded: &DownloadedVersion) -> Result<PathBuf> {
+    let path = packages_dir(download_dir, &downloaded.version).join(MANIFEST_FILE);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(&path, serde_json::to_string_pretty(downloaded)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// The download of `idf_version`, `None` if it was not fetched with `--download-only`.
+pub fn load(download_dir: &Path, idf_version: &str) -> Option<DownloadedVersion> {
+    let path = packages_dir(download_dir, idf_version).join(MANIFEST_FILE);
+    let content = std::fs::read_to_string(&path).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(downloaded) => Some(downloaded),
+        Err(e) => {
+            debug!("Ignoring unreadable {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Whether every version of `settings` was fetched with `--download-only`, so the installation
+/// needs no network.
+pub fn all_downloaded(settings: &Settings) -> bool {
+    let versions = settings.idf_versions.clone().unwrap_or_default();
+    !versions.is_empty()
+        && versions.iter().all(|version| {
+            settings
+                .get_version_paths(version)
+                .map(|paths| load(&download_dir(settings, &paths), version).is_some())
+                .unwrap_or(false)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_load() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let downloaded = DownloadedVersion {
+            version: "v5.4".to_string(),
+            targets: vec!["esp32".to_string()],
+            features: vec!["core".to_string()],
+            python_version: "3.11".to_string(),
+            tool_archives: vec!["openocd-esp32-linux-amd64-0.12.0.tar.gz".to_string()],
+        };
+        assert_eq!(load(dir.path(), "v5.4"), None);
+        record(dir.path(), &downloaded).unwrap();
+        assert_eq!(load(dir.path(), "v5.4"), Some(downloaded));
+        assert_eq!(load(dir.path(), "v5.3"), None);
+    }
+}
@@ -0,0 +1,199 @@
+use log::info;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// One attempt to download a file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DownloadAttempt {
+    pub seconds: f64,
+    /// `None` if the attempt succeeded
+    pub error: Option<String>,
+}
+
+/// All attempts to download one file, kept in the install report to tell which mirrors and CDNs
+/// fail for which users.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DownloadRecord {
+    pub url: String,
+    /// Host the file was downloaded from, the mirror or the CDN it redirected to is not known
+    pub host: String,
+    pub attempts: Vec<DownloadAttempt>,
+    pub success: bool,
+    /// Size of the downloaded file, 0 if it failed
+    pub bytes: u64,
+    /// Average speed of the successful attempt
+    pub bytes_per_second: u64,
+}
+
+impl DownloadRecord {
+    /// Retries it took, 0 if the first attempt succeeded.
+    pub fn retries(&self) -> usize {
+        self.attempts.len().saturating_sub(1)
+    }
+}
+
+/// Totals of the downloads from one host.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HostSummary {
+    pub host: String,
+    pub files: usize,
+    pub failed_files: usize,
+    pub retries: usize,
+    pub bytes: u64,
+    pub bytes_per_second: u64,
+}
+
+/// The downloads of a run and their totals per host.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct DownloadsReport {
+    pub files: Vec<DownloadRecord>,
+    pub hosts: Vec<HostSummary>,
+}
+
+static RECORDS: Lazy<Mutex<Vec<DownloadRecord>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Host of `url`, the url itself if it has none.
+pub fn host_of(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase))
+        .unwrap_or_else(|| url.to_string())
+}
+
+/// Records a finished download, successful or not, and logs its attempts.
+pub fn record(url: &str, attempts: Vec<DownloadAttempt>, bytes: Option<u64>) {
+    let success = bytes.is_some();
+    let bytes = bytes.unwrap_or(0);
+    let seconds = attempts.last().filter(|_| success).map_or(0.0, |a| a.seconds);
+    let record = DownloadRecord {
+        url: url.to_string(),
+        host: host_of(url),
+        success,
+        bytes,
+        bytes_per_second: if seconds > 0.0 { (bytes as f64 / seconds) as u64 } else { 0 },
+        attempts,
+    };
+    info!(
+        "Download of {} from {} {} after {} attempt(s), {} bytes at {} B/s",
+        record.url,
+        record.host,
+        if success { "succeeded" } else { "failed" },
+        record.attempts.len(),
+        record.bytes,
+        record.bytes_per_second
+    );
+    crate::metrics::record_download(success.then_some(bytes), record.retries());
+    RECORDS.lock().unwrap().push(record);
+}
+
+/// Forgets the downloads recorded so far, called when an installation run starts.
+pub fn reset() {
+    RECORDS.lock().unwrap().clear();
+}
+
+/// Totals of `records` per host, sorted by host.
+pub fn summarize(records: &[DownloadRecord]) -> Vec<HostSummary> {
+    let mut hosts: BTreeMap<&str, (HostSummary, f64)> = BTreeMap::new();
+    for record in records {
+        let (summary, seconds) = hosts.entry(&record.host).or_insert_with(|| {
+            (
+                HostSummary {
+                    host: record.host.clone(),
+                    files: 0,
+                    failed_files: 0,
+                    retries: 0,
+                    bytes: 0,
+                    bytes_per_second: 0,
+                },
+                0.0,
+            )
+        });
+        summary.files += 1;
+        summary.retries += record.retries();
+        if record.success {
+            summary.bytes += record.bytes;
+            *seconds += record.attempts.last().map_or(0.0, |a| a.seconds);
+        } else {
+            summary.failed_files += 1;
+        }
+    }
+    hosts
+        .into_values()
+        .map(|(mut summary, seconds)| {
+            if seconds > 0.0 {
+                summary.bytes_per_second = (summary.bytes as f64 / seconds) as u64;
+            }
+            summary
+        })
+        .collect()
+}
+
+/// Takes the downloads recorded since the last [`reset`] for the install report, and logs the
+/// totals per host.
+pub fn take_report() -> DownloadsReport {
+    let files = std::mem::take(&mut *RECORDS.lock().unwrap());
+    let hosts = summarize(&files);
+    for host in &hosts {
+        info!(
+            "Downloads from {}: {} file(s), {} failed, {} retries, {} bytes at {} B/s",
+            host.host, host.files, host.failed_files, host.retries, host.bytes, host.bytes_per_second
+        );
+    }
+    DownloadsReport { files, hosts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempt(seconds: f64, error: Option<&str>) -> DownloadAttempt {
+        DownloadAttempt {
+            seconds,
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let records = vec![
+            DownloadRecord {
+                url: "https://dl.espressif.com/a.tar.xz".to_string(),
+                host: "dl.espressif.com".to_string(),
+                attempts: vec![attempt(1.0, Some("stalled")), attempt(2.0, None)],
+                success: true,
+                bytes: 4000,
+                bytes_per_second: 2000,
+            },
+            DownloadRecord {
+                url: "https://dl.espressif.com/b.tar.xz".to_string(),
+                host: "dl.espressif.com".to_string(),
+                attempts: vec![attempt(1.0, Some("503")); 3],
+                success: false,
+                bytes: 0,
+                bytes_per_second: 0,
+            },
+            DownloadRecord {
+                url: "https://github.com/c.zip".to_string(),
+                host: "github.com".to_string(),
+                attempts: vec![attempt(1.0, None)],
+                success: true,
+                bytes: 500,
+                bytes_per_second: 500,
+            },
+        ];
+        let hosts = summarize(&records);
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].host, "dl.espressif.com");
+        assert_eq!((hosts[0].files, hosts[0].failed_files, hosts[0].retries), (2, 1, 3));
+        assert_eq!(hosts[0].bytes_per_second, 2000);
+        assert_eq!(hosts[1].bytes, 500);
+    }
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(host_of("https://DL.espressif.com/dl/x.zip"), "dl.espressif.com");
+        assert_eq!(host_of("not a url"), "not a url");
+    }
+}
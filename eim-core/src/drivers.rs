@@ -0,0 +1,338 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::run_powershell_script;
+
+/// USB vendor/product pairs used by Espressif development boards together with
+/// the name of the driver (as listed by `get_drivers_list`) they require.
+const KNOWN_USB_DEVICES: &[(u16, u16, &str)] = &[
+    (0x10C4, 0xEA60, "CP210x USB to UART Bridge VCP Drivers"),
+    (0x10C4, 0xEA70, "CP210x USB to UART Bridge VCP Drivers"),
+    (0x0403, 0x6001, "FTDI driver"),
+    (0x0403, 0x6010, "FTDI driver"),
+    (0x0403, 0x6014, "FTDI driver"),
+    (0x303A, 0x1001, "ESP32 USB JTAG Driver"),
+    (0x303A, 0x1002, "ESP32 USB JTAG Driver"),
+    (0x1A86, 0x7523, "CH341SER Driver"),
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DetectedUsbDevice {
+    pub instance_id: String,
+    pub friendly_name: String,
+    pub vid: u16,
+    pub pid: u16,
+    pub status: String,
+    pub driver: String,
+    pub has_driver: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PnpDevice {
+    instance_id: String,
+    friendly_name: Option<String>,
+    status: Option<String>,
+}
+
+/// Extracts the vendor and product id from a PnP instance id
+/// like `USB\VID_10C4&PID_EA60\0001`.
+pub fn parse_usb_ids(instance_id: &str) -> Option<(u16, u16)> {
+    let upper = instance_id.to_uppercase();
+    let vid_start = upper.find("VID_")? + 4;
+    let pid_start = upper.find("PID_")? + 4;
+    let vid = u16::from_str_radix(upper.get(vid_start..vid_start + 4)?, 16).ok()?;
+    let pid = u16::from_str_radix(upper.get(pid_start..pid_start + 4)?, 16).ok()?;
+    Some((vid, pid))
+}
+
+/// Returns the name of the driver needed by the given USB device, if it is a known Espressif device.
+pub fn get_driver_for_device(vid: u16, pid: u16) -> Option<&'static str> {
+    KNOWN_USB_DEVICES
+        .iter()
+        .find(|(v, p, _)| *v == vid && *p == pid)
+        .map(|(_, _, driver)| *driver)
+}
+
+fn parse_pnp_devices(json_output: &str) -> Result<Vec<DetectedUsbDevice>> {
+    let trimmed = json_output.trim();
+    if trimmed.is_empty() {
+        return Ok(vec![]);
+    }
+    // ConvertTo-Json emits a bare object instead of an array when there is only one device
+    let raw: Vec<PnpDevice> = if trimmed.starts_with('[') {
+        serde_json::from_str(trimmed)?
+    } else {
+        vec![serde_json::from_str(trimmed)?]
+    };
+
+    Ok(raw
+        .into_iter()
+        .filter_map(|device| {
+            let (vid, pid) = parse_usb_ids(&device.instance_id)?;
+            let driver = get_driver_for_device(vid, pid)?;
+            let status = device.status.unwrap_or_else(|| "Unknown".to_string());
+            Some(DetectedUsbDevice {
+                has_driver: status.eq_ignore_ascii_case("OK"),
+                instance_id: device.instance_id,
+                friendly_name: device.friendly_name.unwrap_or_default(),
+                vid,
+                pid,
+                status,
+                driver: driver.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Lists connected Espressif USB devices and whether Windows has a working driver bound to them.
+///
+/// On non-Windows platforms drivers are provided by the kernel, so an empty list is returned.
+pub fn detect_usb_devices() -> Result<Vec<DetectedUsbDevice>> {
+    if std::env::consts::OS != "windows" {
+        return Ok(vec![]);
+    }
+    let vendor_ids = KNOWN_USB_DEVICES
+        .iter()
+        .map(|(vid, _, _)| format!("{:04X}", vid))
+        .collect::<std::collections::HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>()
+        .join("|");
+    let script = format!(
+        "Get-PnpDevice -PresentOnly | Where-Object {{ $_.InstanceId -match '^USB\\\\VID_({})' }} | Select-Object InstanceId,FriendlyName,Status | ConvertTo-Json",
+        vendor_ids
+    );
+    let output = run_powershell_script(&script)
+        .map_err(|e| anyhow!("Failed to query connected USB devices: {}", e))?;
+    debug!("Get-PnpDevice output: {}", output);
+    parse_pnp_devices(&output)
+}
+
+/// Returns the connected Espressif devices which do not have a working driver.
+pub fn detect_devices_missing_drivers() -> Result<Vec<DetectedUsbDevice>> {
+    Ok(detect_usb_devices()?
+        .into_iter()
+        .filter(|device| !device.has_driver)
+        .collect())
+}
+
+/// Publishers whose signature on a driver catalog is accepted: the vendors of the USB bridges
+/// and Microsoft, which signs the catalogs of WHQL certified drivers.
+const TRUSTED_DRIVER_SIGNERS: &[&str] = &[
+    "Silicon Laboratories Inc.",
+    "Future Technology Devices International Ltd",
+    "Espressif Systems (Shanghai) Co., Ltd.",
+    "Jiangsu Qinheng Co., Ltd.",
+    "Microsoft Windows Hardware Compatibility Publisher",
+];
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CatalogSignature {
+    status: String,
+    subject: Option<String>,
+    thumbprint: Option<String>,
+}
+
+/// Returns the value of attribute `name` (e.g. `CN`) of a certificate subject like
+/// `CN="Espressif Systems (Shanghai) Co., Ltd.", O=..., C=CN`.
+fn subject_attribute(subject: &str, name: &str) -> Option<String> {
+    let mut rest = subject.trim();
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=')?;
+        let value = value.trim_start();
+        let (parsed, remaining) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+        if key.trim().eq_ignore_ascii_case(name) {
+            return Some(parsed.trim().to_string());
+        }
+        rest = remaining.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+    None
+}
+
+/// Accepts a catalog signature only if it is valid and made by one of the [`TRUSTED_DRIVER_SIGNERS`],
+/// as any code signing certificate would make a replaced driver package look valid.
+fn check_catalog_signature(catalog: &Path, signature: &CatalogSignature) -> Result<()> {
+    if signature.status != "Valid" {
+        warn!(
+            "Driver catalog {} has invalid signature status: {}",
+            catalog.display(),
+            signature.status
+        );
+        return Err(anyhow!(
+            "Signature of {} is not valid ({})",
+            catalog.display(),
+            signature.status
+        ));
+    }
+    let subject = signature.subject.as_deref().unwrap_or_default();
+    let trusted = ["CN", "O"]
+        .iter()
+        .filter_map(|name| subject_attribute(subject, name))
+        .any(|signer| TRUSTED_DRIVER_SIGNERS.contains(&signer.as_str()));
+    if !trusted {
+        warn!(
+            "Driver catalog {} is signed by an unexpected publisher: {} ({})",
+            catalog.display(),
+            subject,
+            signature.thumbprint.as_deref().unwrap_or("no thumbprint")
+        );
+        return Err(anyhow!(
+            "{} is not signed by a known driver vendor but by {}",
+            catalog.display(),
+            subject
+        ));
+    }
+    Ok(())
+}
+
+/// Verifies the Authenticode signature of every catalog file shipped with a driver package,
+/// and that it was made by one of the driver vendors.
+///
+/// Windows refuses unsigned catalogs during a silent install anyway, but checking up front
+/// lets us report a tampered or truncated download instead of a generic pnputil failure.
+pub fn verify_driver_signature(driver_dir: &Path) -> Result<()> {
+    if std::env::consts::OS != "windows" {
+        return Ok(());
+    }
+    let catalogs: Vec<_> = fs::read_dir(driver_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |ext| ext.eq_ignore_ascii_case("cat"))
+        })
+        .collect();
+    if catalogs.is_empty() {
+        return Err(anyhow!(
+            "No signed catalog file found in driver package {}",
+            driver_dir.display()
+        ));
+    }
+    for catalog in catalogs {
+        let script = format!(
+            "$signature = Get-AuthenticodeSignature -FilePath '{}'; [pscustomobject]@{{ Status = [string]$signature.Status; Subject = $signature.SignerCertificate.Subject; Thumbprint = $signature.SignerCertificate.Thumbprint }} | ConvertTo-Json",
+            catalog.display()
+        );
+        let output = run_powershell_script(&script)
+            .map_err(|e| anyhow!("Failed to verify signature of {}: {}", catalog.display(), e))?;
+        let signature: CatalogSignature = serde_json::from_str(output.trim())
+            .with_context(|| format!("Unexpected signature of {}: {}", catalog.display(), output.trim()))?;
+        check_catalog_signature(&catalog, &signature)?;
+        info!("Verified driver signature: {}", catalog.display());
+    }
+    Ok(())
+}
+
+/// Relaunches the current executable with administrator rights to run `eim drivers install`.
+///
+/// Blocks until the elevated process exits and returns an error if it failed or the UAC prompt was declined.
+pub fn run_elevated_driver_install(missing_only: bool) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    let mut arguments = vec!["drivers", "install"];
+    if missing_only {
+        arguments.push("--missing-only");
+    }
+    crate::elevation::run_elevated(
+        "install the USB drivers of the ESP development boards",
+        &exe.to_string_lossy(),
+        &arguments,
+    )
+    .context("Failed to run elevated driver installation")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_executor::MockExecutor;
+
+    #[test]
+    fn test_elevated_driver_install_fails_with_the_elevated_run() {
+        let failing = MockExecutor { exit_code: 1, ..Default::default() };
+        assert!(failing.install(|| run_elevated_driver_install(true)).is_err());
+        let calls = failing.calls.lock().unwrap();
+        assert!(calls.iter().flatten().any(|arg| arg.contains("--missing-only")));
+
+        let succeeding = MockExecutor::default();
+        assert!(succeeding.install(|| run_elevated_driver_install(false)).is_ok());
+    }
+
+    #[test]
+    fn test_parse_usb_ids() {
+        assert_eq!(
+            parse_usb_ids("USB\\VID_10C4&PID_EA60\\0001"),
+            Some((0x10C4, 0xEA60))
+        );
+        assert_eq!(
+            parse_usb_ids("usb\\vid_303a&pid_1001&mi_02\\6&1"),
+            Some((0x303A, 0x1001))
+        );
+        assert_eq!(parse_usb_ids("PCI\\VEN_8086"), None);
+    }
+
+    #[test]
+    fn test_parse_pnp_devices_single_object() {
+        let output = r#"{"InstanceId":"USB\\VID_10C4&PID_EA60\\0001","FriendlyName":"CP2102 USB to UART","Status":"Error"}"#;
+        let devices = parse_pnp_devices(output).unwrap();
+        assert_eq!(devices.len(), 1);
+        assert!(!devices[0].has_driver);
+        assert_eq!(devices[0].driver, "CP210x USB to UART Bridge VCP Drivers");
+    }
+
+    #[test]
+    fn test_parse_pnp_devices_filters_unknown() {
+        let output = r#"[
+            {"InstanceId":"USB\\VID_303A&PID_1001\\1","FriendlyName":"USB JTAG/serial debug unit","Status":"OK"},
+            {"InstanceId":"USB\\VID_0403&PID_FFFF\\2","FriendlyName":"Something else","Status":"OK"}
+        ]"#;
+        let devices = parse_pnp_devices(output).unwrap();
+        assert_eq!(devices.len(), 1);
+        assert!(devices[0].has_driver);
+    }
+
+    fn signature(status: &str, subject: &str) -> CatalogSignature {
+        CatalogSignature {
+            status: status.to_string(),
+            subject: Some(subject.to_string()),
+            thumbprint: Some("0123456789ABCDEF".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_catalog_signed_by_a_driver_vendor() {
+        let catalog = Path::new("silabser.cat");
+        let subject = "CN=Silicon Laboratories Inc., O=Silicon Laboratories Inc., L=Austin, S=Texas, C=US";
+        assert!(check_catalog_signature(catalog, &signature("Valid", subject)).is_ok());
+        let subject = r#"CN="Espressif Systems (Shanghai) Co., Ltd.", O="Espressif Systems (Shanghai) Co., Ltd.", C=CN"#;
+        assert!(check_catalog_signature(catalog, &signature("Valid", subject)).is_ok());
+        assert!(check_catalog_signature(catalog, &signature("HashMismatch", subject)).is_err());
+    }
+
+    #[test]
+    fn test_valid_catalog_of_another_signer_is_refused() {
+        let catalog = Path::new("silabser.cat");
+        let subject = "CN=Silicon Laboratories Inc. Fake, O=Evil Corp, C=US";
+        let error = check_catalog_signature(catalog, &signature("Valid", subject)).unwrap_err();
+        assert!(error.to_string().contains("Evil Corp"));
+        let unsigned = CatalogSignature { status: "Valid".to_string(), subject: None, thumbprint: None };
+        assert!(check_catalog_signature(catalog, &unsigned).is_err());
+    }
+
+    #[test]
+    fn test_subject_attribute() {
+        let subject = r#"CN="Espressif Systems (Shanghai) Co., Ltd.", O=Espressif, C=CN"#;
+        assert_eq!(subject_attribute(subject, "CN").as_deref(), Some("Espressif Systems (Shanghai) Co., Ltd."));
+        assert_eq!(subject_attribute(subject, "O").as_deref(), Some("Espressif"));
+        assert_eq!(subject_attribute(subject, "OU"), None);
+    }
+}
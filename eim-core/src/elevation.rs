@@ -0,0 +1,196 @@
+use anyhow::Result;
+use log::{info, warn};
+use std::io::IsTerminal;
+use std::path::Path;
+
+use crate::command_executor::execute_command;
+use crate::errors::{EimError, ErrorKind};
+
+/// Whether the process runs as root (Unix) or elevated administrator (Windows).
+pub fn is_root() -> bool {
+    if std::env::consts::OS == "windows" {
+        return crate::utils::is_elevated().unwrap_or(false);
+    }
+    execute_command("id", &["-u"])
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "0")
+        .unwrap_or(false)
+}
+
+/// Warns when eim was started as root although nothing but single steps needs it.
+pub fn warn_if_root() {
+    if is_root() {
+        warn!(
+            "eim is running as {}. Files it creates in your home folder will belong to it; run eim as your user, it asks for elevation only for the steps which need it",
+            if std::env::consts::OS == "windows" { "Administrator" } else { "root" }
+        );
+    }
+}
+
+/// Quotes an argument for a POSIX shell.
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Quotes an argument for `Start-Process -ArgumentList`, which joins the arguments with spaces.
+fn powershell_argument(arg: &str) -> String {
+    let arg = if arg.contains(' ') {
+        format!("\"{}\"", arg)
+    } else {
+        arg.to_string()
+    };
+    format!("'{}'", arg.replace('\'', "''"))
+}
+
+fn graphical_session() -> bool {
+    std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// The command which runs `program` elevated on this platform.
+fn elevated_command(description: &str, program: &str, args: &[&str]) -> (String, Vec<String>) {
+    let mut command: Vec<String> = Vec::new();
+    match std::env::consts::OS {
+        "windows" => {
+            let argument_list = if args.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    " -ArgumentList {}",
+                    args.iter().map(|a| powershell_argument(a)).collect::<Vec<_>>().join(",")
+                )
+            };
+            // without -ErrorAction Stop a declined UAC prompt leaves $p empty and exits with 0
+            let script = format!(
+                "$p = Start-Process -FilePath {}{} -Verb RunAs -Wait -PassThru -ErrorAction Stop; exit $p.ExitCode",
+                powershell_argument(program),
+                argument_list
+            );
+            return (
+                "powershell".to_string(),
+                vec!["-NoProfile".into(), "-NonInteractive".into(), "-Command".into(), script],
+            );
+        }
+        "macos" if !std::io::stdin().is_terminal() => {
+            let shell_command = std::iter::once(program)
+                .chain(args.iter().copied())
+                .map(shell_quote)
+                .collect::<Vec<_>>()
+                .join(" ");
+            let script = format!(
+                "do shell script \"{}\" with prompt \"eim needs administrator rights to {}\" with administrator privileges",
+                shell_command.replace('\\', "\\\\").replace('"', "\\\""),
+                description.replace('"', "'")
+            );
+            return ("osascript".to_string(), vec!["-e".into(), script]);
+        }
+        _ => {}
+    }
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let use_pkexec = !std::io::stdin().is_terminal()
+        && graphical_session()
+        && crate::which::find_in_path("pkexec", &path_var).is_some();
+    let use_doas = std::env::consts::OS == "freebsd"
+        && crate::which::find_in_path("sudo", &path_var).is_none()
+        && crate::which::find_in_path("doas", &path_var).is_some();
+    let launcher = if use_pkexec {
+        "pkexec"
+    } else if use_doas {
+        // FreeBSD ships no sudo, doas is the lighter choice there
+        if !std::io::stdin().is_terminal() {
+            command.push("-n".into());
+        }
+        "doas"
+    } else {
+        command.push("-p".into());
+        command.push(format!("[eim] password of %u to {}: ", description));
+        if !std::io::stdin().is_terminal() {
+            // no one to type the password, fail instead of hanging
+            command.insert(0, "-n".into());
+        }
+        "sudo"
+    };
+    command.push(program.to_string());
+    command.extend(args.iter().map(|a| a.to_string()));
+    (launcher.to_string(), command)
+}
+
+/// Runs `program` with administrator rights, asking the user with `description` of what it is for,
+/// e.g. "install the system packages git, cmake".
+///
+/// eim runs as the user and elevates single steps only: pkexec (GUI) or sudo (terminal) on Linux,
+/// sudo or doas on FreeBSD, the administrator prompt on macOS and UAC on Windows. Runs `program` directly when eim already is root.
+pub fn run_elevated(description: &str, program: &str, args: &[&str]) -> Result<()> {
+    let output = if is_root() {
+        execute_command(program, args)?
+    } else {
+        info!("Administrator rights are needed to {}", description);
+        let (launcher, launcher_args) = elevated_command(description, program, args);
+        let launcher_args: Vec<&str> = launcher_args.iter().map(String::as_str).collect();
+        execute_command(&launcher, &launcher_args)?
+    };
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    // 126 is pkexec's "not authorized", also returned when the dialog is dismissed
+    let kind = if output.status.code() == Some(126)
+        || stderr.contains("password is required")
+        || stderr.contains("canceled by the user")
+    {
+        ErrorKind::Permissions
+    } else {
+        ErrorKind::Prerequisites
+    };
+    Err(EimError::new(kind, format!("Failed to {}: {}", description, stderr)).into())
+}
+
+/// Copies `source` to `destination`, elevating only when the destination is not writable.
+pub fn copy_file(description: &str, source: &Path, destination: &Path) -> Result<()> {
+    match std::fs::copy(source, destination) {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+            let source = source.to_string_lossy();
+            let destination = destination.to_string_lossy();
+            match std::env::consts::OS {
+                "windows" => run_elevated(description, "cmd", &["/c", "copy", "/Y", &source, &destination]),
+                _ => run_elevated(description, "cp", &[&source, &destination]),
+            }
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_executor::MockExecutor;
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/opt/esp"), "'/opt/esp'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn test_run_elevated_checks_exit_status() {
+        let mock = MockExecutor::default();
+        assert!(mock.install(|| run_elevated("test", "true", &[])).is_ok());
+
+        let mock = MockExecutor {
+            exit_code: 1,
+            stderr: "The operation was canceled by the user.".to_string(),
+            ..Default::default()
+        };
+        let err = mock.install(|| run_elevated("test", "true", &[])).unwrap_err();
+        assert_eq!(err.downcast_ref::<EimError>().map(|e| e.kind), Some(ErrorKind::Permissions));
+
+        let mock = MockExecutor { exit_code: 2, ..Default::default() };
+        let err = mock.install(|| run_elevated("test", "true", &[])).unwrap_err();
+        assert_eq!(err.downcast_ref::<EimError>().map(|e| e.kind), Some(ErrorKind::Prerequisites));
+    }
+
+    #[test]
+    fn test_powershell_argument() {
+        assert_eq!(powershell_argument("drivers"), "'drivers'");
+        assert_eq!(powershell_argument("C:\\Program Files\\eim"), "'\"C:\\Program Files\\eim\"'");
+    }
+}
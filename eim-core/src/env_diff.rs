@@ -0,0 +1,271 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::idf_config::IdfInstallation;
+use crate::ide_export::InstallationEnvironment;
+
+/// Variables set in a shell which make Python ignore or replace the virtual environment
+const PYTHON_OVERRIDES: &[&str] = &["PYTHONHOME", "PYTHONPATH"];
+/// Variables of the activation which point to one installation
+const INSTALLATION_VARIABLES: &[&str] = &["IDF_PATH", "IDF_TOOLS_PATH", "IDF_PYTHON_ENV_PATH"];
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Changed,
+    Unchanged,
+}
+
+/// A variable the activation sets, compared with the current environment.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct VariableChange {
+    pub name: String,
+    pub current: Option<String>,
+    pub activated: String,
+    pub kind: ChangeKind,
+}
+
+/// Something in the current environment known to break builds with the activated version.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "conflict", rename_all = "snake_case")]
+pub enum Conflict {
+    /// `IDF_PATH` and friends point to another installation, the shell was activated for it
+    OtherInstallation { variable: String, value: String },
+    /// A `PATH` entry of another installation, e.g. its Python environment
+    OtherInstallationOnPath { entry: String, installation: String },
+    /// Another virtual or conda environment is active, its `python` shadows the one of ESP-IDF
+    /// in tools which run `python` without the full path
+    OtherPythonEnvironment { variable: String, value: String },
+    /// `PYTHONHOME` or `PYTHONPATH` make the Python of ESP-IDF load foreign packages
+    PythonOverride { variable: String, value: String },
+}
+
+/// What the activation of a version changes in the current environment.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EnvDiff {
+    pub version: String,
+    pub variables: Vec<VariableChange>,
+    /// Directories the activation prepends to `PATH`
+    pub path_added: Vec<String>,
+    /// Directories which are on `PATH` already; prepended anyway, so they come first
+    pub path_present: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl EnvDiff {
+    pub fn changes(&self) -> impl Iterator<Item = &VariableChange> {
+        self.variables.iter().filter(|change| change.kind != ChangeKind::Unchanged)
+    }
+}
+
+/// Variable names are case-insensitive on Windows.
+fn normalize_name(name: &str) -> String {
+    if std::env::consts::OS == "windows" {
+        name.to_uppercase()
+    } else {
+        name.to_string()
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    let path = path.trim_end_matches(['/', '\\']);
+    if std::env::consts::OS == "windows" {
+        path.replace('/', "\\").to_lowercase()
+    } else {
+        path.to_string()
+    }
+}
+
+fn is_within(path: &str, dir: &str) -> bool {
+    let (path, dir) = (normalize_path(path), normalize_path(dir));
+    !dir.is_empty() && Path::new(&path).starts_with(&dir)
+}
+
+/// Compares the environment the activation of `environment` sets up with `current`.
+///
+/// `others` are the other installations, whose paths in `current` are reported as conflicts.
+pub fn diff_environment(
+    environment: &InstallationEnvironment,
+    current: &HashMap<String, String>,
+    others: &[IdfInstallation],
+) -> EnvDiff {
+    let current: HashMap<String, String> = current
+        .iter()
+        .map(|(name, value)| (normalize_name(name), value.clone()))
+        .collect();
+
+    let variables = environment
+        .variables
+        .iter()
+        .map(|(name, activated)| {
+            let current = current.get(&normalize_name(name)).cloned();
+            let kind = match &current {
+                None => ChangeKind::Added,
+                Some(value) if normalize_path(value) == normalize_path(activated) => ChangeKind::Unchanged,
+                Some(_) => ChangeKind::Changed,
+            };
+            VariableChange {
+                name: name.clone(),
+                current,
+                activated: activated.clone(),
+                kind,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let current_path: Vec<String> = current
+        .get(&normalize_name("PATH"))
+        .map(|path| std::env::split_paths(path).map(|p| p.to_string_lossy().into_owned()).collect())
+        .unwrap_or_default();
+    let on_path = |entry: &String| current_path.iter().any(|p| normalize_path(p) == normalize_path(entry));
+    let (path_present, path_added): (Vec<String>, Vec<String>) =
+        environment.path_entries.iter().cloned().partition(on_path);
+
+    let mut conflicts = Vec::new();
+    for change in &variables {
+        if change.kind == ChangeKind::Changed
+            && INSTALLATION_VARIABLES.contains(&change.name.as_str())
+            && !change.current.as_deref().unwrap_or_default().is_empty()
+        {
+            conflicts.push(Conflict::OtherInstallation {
+                variable: change.name.clone(),
+                value: change.current.clone().unwrap_or_default(),
+            });
+        }
+    }
+    let own_venv = environment
+        .python
+        .parent()
+        .and_then(Path::parent)
+        .map(|venv| venv.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let own_dirs = [environment.idf_path.to_string_lossy().into_owned(), own_venv.clone()];
+    for entry in &current_path {
+        if own_dirs.iter().any(|dir| is_within(entry, dir)) {
+            continue;
+        }
+        // the tools folder is often shared, only the ESP-IDF and Python environment folders tell
+        // installations apart
+        let other = others.iter().find(|other| {
+            let venv = Path::new(&other.python)
+                .parent()
+                .and_then(Path::parent)
+                .map(|venv| venv.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            is_within(entry, &other.path) || is_within(entry, &venv)
+        });
+        if let Some(other) = other {
+            conflicts.push(Conflict::OtherInstallationOnPath {
+                entry: entry.clone(),
+                installation: other.name.clone(),
+            });
+        }
+    }
+    for variable in ["VIRTUAL_ENV", "CONDA_PREFIX"] {
+        if let Some(value) = current.get(&normalize_name(variable)) {
+            if !value.is_empty() && normalize_path(value) != normalize_path(&own_venv) {
+                conflicts.push(Conflict::OtherPythonEnvironment {
+                    variable: variable.to_string(),
+                    value: value.clone(),
+                });
+            }
+        }
+    }
+    for variable in PYTHON_OVERRIDES {
+        if let Some(value) = current.get(&normalize_name(variable)).filter(|value| !value.is_empty()) {
+            conflicts.push(Conflict::PythonOverride {
+                variable: variable.to_string(),
+                value: value.clone(),
+            });
+        }
+    }
+
+    EnvDiff {
+        version: environment.name.clone(),
+        variables,
+        path_added,
+        path_present,
+        conflicts,
+    }
+}
+
+/// Diff of the activation of `installation` against the environment of this process.
+pub fn diff_current_environment(installation: &IdfInstallation) -> anyhow::Result<EnvDiff> {
+    let environment = InstallationEnvironment::from_installation(installation)?;
+    let others: Vec<IdfInstallation> = crate::version_manager::get_esp_ide_config()
+        .map(|config| config.idf_installed)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|other| other.id != installation.id)
+        .collect();
+    let current: HashMap<String, String> = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .collect();
+    Ok(diff_environment(&environment, &current, &others))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::ide_export::test_environment;
+    use crate::idf_config::test_installation;
+
+    fn environment() -> InstallationEnvironment {
+        let mut environment = test_environment("v5.4");
+        environment.path_entries.push("/esp/tools/openocd-esp32/bin".to_string());
+        environment.variables.push(("IDF_TOOLS_PATH".to_string(), "/esp/tools".to_string()));
+        environment
+    }
+
+    fn other() -> IdfInstallation {
+        IdfInstallation {
+            idf_tools_path: "/esp/tools".to_string(),
+            path: "/esp/v5.1/esp-idf".to_string(),
+            python: "/esp/tools/python/v5.1/venv/bin/python".to_string(),
+            ..test_installation("v5.1")
+        }
+    }
+
+    #[test]
+    fn test_diff_fresh_shell() {
+        let current = HashMap::from([("PATH".to_string(), "/usr/bin:/esp/tools/openocd-esp32/bin".to_string())]);
+        let diff = diff_environment(&environment(), &current, &[other()]);
+        assert!(diff.variables.iter().all(|change| change.kind == ChangeKind::Added));
+        assert_eq!(diff.path_added, vec!["/esp/tools/python/v5.4/venv/bin".to_string()]);
+        assert_eq!(diff.path_present, vec!["/esp/tools/openocd-esp32/bin".to_string()]);
+        assert!(diff.conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_diff_shell_of_other_installation() {
+        let current = HashMap::from([
+            ("PATH".to_string(), "/esp/tools/python/v5.1/venv/bin:/usr/bin".to_string()),
+            ("IDF_PATH".to_string(), "/esp/v5.1/esp-idf".to_string()),
+            ("IDF_TOOLS_PATH".to_string(), "/esp/tools/".to_string()),
+            ("PYTHONPATH".to_string(), "/opt/lib".to_string()),
+        ]);
+        let diff = diff_environment(&environment(), &current, &[other()]);
+        assert_eq!(diff.variables[0].kind, ChangeKind::Changed);
+        assert_eq!(diff.variables[1].kind, ChangeKind::Unchanged);
+        assert_eq!(diff.changes().count(), 1);
+        assert_eq!(
+            diff.conflicts,
+            vec![
+                Conflict::OtherInstallation {
+                    variable: "IDF_PATH".to_string(),
+                    value: "/esp/v5.1/esp-idf".to_string()
+                },
+                Conflict::OtherInstallationOnPath {
+                    entry: "/esp/tools/python/v5.1/venv/bin".to_string(),
+                    installation: "v5.1".to_string()
+                },
+                Conflict::PythonOverride {
+                    variable: "PYTHONPATH".to_string(),
+                    value: "/opt/lib".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use thiserror::Error;
+
+use crate::install_lock::LockError;
+
+/// Class of a failure, mapped to a stable process exit code.
+///
+/// The codes are part of the CLI contract documented in `docs/src/cli_commands.md`:
+/// existing values must never change, new classes get new codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    General,
+    Usage,
+    Network,
+    Checksum,
+    Disk,
+    Permissions,
+    Prerequisites,
+    Python,
+    NotFound,
+    Locked,
+    /// Real-time antivirus scanning blocked or removed installed files
+    Antivirus,
+    /// An unattended installation exceeded its `--max-duration`
+    TimedOut,
+    Cancelled,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::General => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Network => 10,
+            ErrorKind::Checksum => 11,
+            ErrorKind::Disk => 12,
+            ErrorKind::Permissions => 13,
+            ErrorKind::Prerequisites => 14,
+            ErrorKind::Python => 15,
+            ErrorKind::NotFound => 16,
+            ErrorKind::Locked => 17,
+            ErrorKind::Antivirus => 18,
+            ErrorKind::TimedOut => 19,
+            ErrorKind::Cancelled => 130,
+        }
+    }
+}
+
+/// An error tagged with its class where it was raised.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{message}")]
+pub struct EimError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl EimError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.kind.exit_code()
+    }
+}
+
+impl From<String> for EimError {
+    fn from(message: String) -> Self {
+        EimError::new(ErrorKind::General, message)
+    }
+}
+
+impl From<anyhow::Error> for EimError {
+    fn from(err: anyhow::Error) -> Self {
+        EimError::new(classify(&err), format!("{:#}", err))
+    }
+}
+
+/// Classifies IO errors which tell the failure class on their own.
+pub fn classify_io_error(err: &io::Error) -> Option<ErrorKind> {
+    // ENOSPC on Unix, ERROR_HANDLE_DISK_FULL and ERROR_DISK_FULL on Windows
+    let disk_full_codes: &[i32] = if cfg!(windows) { &[39, 112] } else { &[28] };
+    if err.raw_os_error().is_some_and(|code| disk_full_codes.contains(&code)) {
+        return Some(ErrorKind::Disk);
+    }
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => Some(ErrorKind::Permissions),
+        io::ErrorKind::ConnectionRefused
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::NotConnected
+        | io::ErrorKind::AddrNotAvailable
+        | io::ErrorKind::TimedOut => Some(ErrorKind::Network),
+        _ => None,
+    }
+}
+
+/// Determines the class of an error from the first typed error in its chain.
+pub fn classify(err: &anyhow::Error) -> ErrorKind {
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<EimError>() {
+            return err.kind;
+        }
+        if let Some(err) = cause.downcast_ref::<LockError>() {
+            return match err {
+                LockError::Io(io_err) => classify_io_error(io_err).unwrap_or(ErrorKind::Locked),
+                _ => ErrorKind::Locked,
+            };
+        }
+        if cause.downcast_ref::<reqwest::Error>().is_some() {
+            return ErrorKind::Network;
+        }
+        if let Some(kind) = cause.downcast_ref::<io::Error>().and_then(classify_io_error) {
+            return kind;
+        }
+    }
+    ErrorKind::General
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_classify_walks_the_chain() {
+        let err = Err::<(), _>(EimError::new(ErrorKind::Checksum, "esp-rom-elfs.tar.gz is corrupted"))
+            .context("Failed to set up tools")
+            .unwrap_err();
+        assert_eq!(classify(&err), ErrorKind::Checksum);
+
+        let err = anyhow::Error::new(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert_eq!(EimError::from(err).exit_code(), 13);
+
+        assert_eq!(classify(&anyhow::anyhow!("something else")), ErrorKind::General);
+    }
+}
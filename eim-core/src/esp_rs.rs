@@ -0,0 +1,209 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::command_executor::execute_command;
+use crate::errors::{EimError, ErrorKind};
+
+pub const ESPUP_RELEASES_URL: &str = "https://github.com/esp-rs/espup/releases/latest/download";
+pub const RUST_TOOLCHAIN_FOLDER_NAME: &str = "esp-rs";
+/// Crates installed next to the toolchain, the linker proxy used by `esp-idf-sys` and the flasher
+pub const EXTRA_CRATES: &str = "ldproxy,espflash";
+/// Chips the Rust toolchain can target, see `espup install --help`
+const RUST_TARGETS: &[&str] = &[
+    "esp32", "esp32c2", "esp32c3", "esp32c6", "esp32h2", "esp32p4", "esp32s2", "esp32s3",
+];
+
+/// Returns the name of the espup release binary for the current platform.
+pub fn espup_asset_name() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Some("espup-x86_64-unknown-linux-gnu"),
+        ("linux", "aarch64") => Some("espup-aarch64-unknown-linux-gnu"),
+        ("macos", "x86_64") => Some("espup-x86_64-apple-darwin"),
+        ("macos", "aarch64") => Some("espup-aarch64-apple-darwin"),
+        ("windows", "x86_64") => Some("espup-x86_64-pc-windows-msvc.exe"),
+        _ => None,
+    }
+}
+
+/// Maps the IDF targets of the installation to the `--targets` value of espup.
+///
+/// Targets the Rust toolchain does not support are left out; if none remain, all are installed.
+pub fn espup_targets(idf_targets: &[String]) -> String {
+    let targets: Vec<&str> = idf_targets
+        .iter()
+        .filter_map(|target| {
+            RUST_TARGETS
+                .iter()
+                .find(|t| **t == target.as_str())
+                .copied()
+        })
+        .collect();
+    if targets.is_empty() || idf_targets.iter().any(|t| t == "all") {
+        "all".to_string()
+    } else {
+        targets.join(",")
+    }
+}
+
+/// Parses the export file written by espup into environment variables and PATH entries.
+///
+/// Handles both `export-esp.sh` (`export NAME="value"`) and `export-esp.ps1`
+/// (`$Env:NAME = "value"`); references to the inherited PATH are dropped.
+pub fn parse_export_file(content: &str) -> (Vec<(String, String)>, Vec<String>) {
+    let mut env_vars = Vec::new();
+    let mut paths = Vec::new();
+    for line in content.lines().map(str::trim) {
+        let assignment = line
+            .strip_prefix("export ")
+            .or_else(|| line.strip_prefix("$Env:"))
+            .or_else(|| line.strip_prefix("$env:"));
+        let Some((name, value)) = assignment.and_then(|a| a.split_once('=')) else {
+            continue;
+        };
+        let name = name.trim();
+        let value = value
+            .split('+')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .trim_matches('"');
+        if name.eq_ignore_ascii_case("PATH") {
+            let separator = if line.starts_with("export ") {
+                ':'
+            } else {
+                ';'
+            };
+            paths.extend(
+                value
+                    .split(separator)
+                    .filter(|entry| !entry.is_empty() && !entry.starts_with('$'))
+                    .map(str::to_string),
+            );
+        } else {
+            env_vars.push((name.to_string(), value.to_string()));
+        }
+    }
+    (env_vars, paths)
+}
+
+/// Installs the Rust toolchain for ESP chips (Xtensa rustc fork, ldproxy and espflash) with espup.
+///
+/// espup is downloaded to the `esp-rs` folder of the tools directory, the toolchain itself goes
+/// to rustup as usual. Returns the environment variables and PATH entries of the toolchain,
+/// which are added to the activation script of the IDF installation.
+pub async fn install_rust_toolchain(
+    tools_install_dir: &Path,
+    idf_targets: &[String],
+) -> Result<(Vec<(String, String)>, Vec<String>)> {
+    let rustup_found = execute_command("rustup", &["--version"])
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+    if !rustup_found {
+        return Err(EimError::new(
+            ErrorKind::Prerequisites,
+            "rustup is required for the Rust toolchain, install it from https://rustup.rs",
+        )
+        .into());
+    }
+    let asset = espup_asset_name().ok_or_else(|| {
+        anyhow!(
+            "The Rust toolchain is not available for {}-{}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )
+    })?;
+
+    let espup_dir = tools_install_dir.join(RUST_TOOLCHAIN_FOLDER_NAME);
+    fs::create_dir_all(&espup_dir)?;
+    let espup_name = if std::env::consts::OS == "windows" {
+        "espup.exe"
+    } else {
+        "espup"
+    };
+    let espup_path = espup_dir.join(espup_name);
+    if !espup_path.exists() {
+        let url = format!("{}/{}", ESPUP_RELEASES_URL, asset);
+        info!("Downloading espup from {}", url);
+        crate::download_file_and_rename(&url, espup_dir.to_str().unwrap(), None, Some(espup_name))
+            .await?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&espup_path, fs::Permissions::from_mode(0o755))?;
+        }
+    }
+
+    let export_file: PathBuf = espup_dir.join(if std::env::consts::OS == "windows" {
+        "export-esp.ps1"
+    } else {
+        "export-esp.sh"
+    });
+    let targets = espup_targets(idf_targets);
+    info!("Installing the Rust toolchain for {}", targets);
+    let output = execute_command(
+        espup_path.to_str().unwrap(),
+        &[
+            "install",
+            "--targets",
+            &targets,
+            "--extra-crates",
+            EXTRA_CRATES,
+            "--export-file",
+            export_file.to_str().unwrap(),
+        ],
+    )?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "espup failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let (env_vars, mut paths) = parse_export_file(&fs::read_to_string(&export_file)?);
+    // ldproxy and espflash are installed with cargo
+    if let Some(cargo_bin) = dirs::home_dir().map(|home| home.join(".cargo").join("bin")) {
+        paths.push(cargo_bin.to_string_lossy().into_owned());
+    }
+    debug!("Rust toolchain environment: {:?} {:?}", env_vars, paths);
+    Ok((env_vars, paths))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_espup_targets() {
+        let targets =
+            |list: &[&str]| espup_targets(&list.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+        assert_eq!(targets(&["esp32", "esp32s3"]), "esp32,esp32s3");
+        assert_eq!(targets(&["all"]), "all");
+        assert_eq!(targets(&["esp32c5"]), "all");
+    }
+
+    #[test]
+    fn test_parse_export_files() {
+        let sh = "export LIBCLANG_PATH=\"/home/u/.rustup/toolchains/esp/esp-clang/lib\"\n\
+                  export PATH=\"/home/u/.rustup/toolchains/esp/xtensa-esp-elf/bin:$PATH\"\n";
+        let (vars, paths) = parse_export_file(sh);
+        assert_eq!(
+            vars,
+            vec![(
+                "LIBCLANG_PATH".to_string(),
+                "/home/u/.rustup/toolchains/esp/esp-clang/lib".to_string()
+            )]
+        );
+        assert_eq!(
+            paths,
+            vec!["/home/u/.rustup/toolchains/esp/xtensa-esp-elf/bin"]
+        );
+
+        let ps1 = "$Env:LIBCLANG_PATH = \"C:\\esp\\libclang.dll\"\n\
+                   $Env:PATH = \"C:\\esp\\xtensa\\bin;C:\\esp\\clang\\bin;\" + $Env:PATH\n";
+        let (vars, paths) = parse_export_file(ps1);
+        assert_eq!(vars[0].1, "C:\\esp\\libclang.dll");
+        assert_eq!(paths, vec!["C:\\esp\\xtensa\\bin", "C:\\esp\\clang\\bin"]);
+    }
+}
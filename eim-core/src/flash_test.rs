@@ -0,0 +1,247 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use crate::errors::{EimError, ErrorKind};
+use crate::ide_export::InstallationEnvironment;
+use crate::idf_config::IdfInstallation;
+
+/// Example flashed by the test, part of every ESP-IDF release
+pub const FLASH_TEST_EXAMPLE: &str = "examples/get-started/hello_world";
+/// Line the example prints once the application runs
+pub const FLASH_TEST_MARKER: &str = "Hello world!";
+/// Seconds to wait for the marker after resetting the board
+pub const BOOT_LOG_TIMEOUT_SECS: u32 = 15;
+
+/// Resets the board through RTS like `idf.py monitor` does and prints the serial output until
+/// the marker shows up or the timeout passes. pyserial is installed with esptool in every
+/// IDF Python environment.
+const READ_BOOT_LOG_SCRIPT: &str = r#"
+import sys, time, serial
+port = serial.Serial(sys.argv[1], 115200, timeout=0.2)
+port.dtr = False
+port.rts = True
+time.sleep(0.1)
+port.rts = False
+marker = sys.argv[2].encode()
+deadline = time.time() + float(sys.argv[3])
+log = b""
+while time.time() < deadline and marker not in log:
+    log += port.read(1024)
+sys.stdout.write(log.decode("utf-8", "replace"))
+"#;
+
+/// Result of flashing the example to a board.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FlashTestReport {
+    pub port: String,
+    pub target: String,
+    pub success: bool,
+    pub boot_log: String,
+}
+
+/// Steps of the flash test, reported as they start.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FlashTestStep {
+    DetectChip,
+    Build,
+    Flash,
+    ReadBootLog,
+}
+
+/// Maps the chip name printed by esptool (e.g. `ESP32-S3 (QFN56) (revision v0.2)`) to an IDF target.
+pub fn target_from_chip_description(output: &str) -> Option<String> {
+    let line = output
+        .lines()
+        .find(|line| line.contains("Chip is ") || line.contains("Chip type:"))?;
+    let chip = line
+        .split_once("Chip is ")
+        .or_else(|| line.split_once("Chip type:"))?
+        .1
+        .split_whitespace()
+        .next()?;
+    let target = chip.to_lowercase().replace('-', "");
+    target.starts_with("esp32").then_some(target)
+}
+
+/// Returns the port to test, the first usable Espressif device if none was given.
+pub fn select_port(port: Option<&str>) -> Result<String> {
+    if let Some(port) = port {
+        return Ok(port.to_string());
+    }
+    let devices = crate::serial_devices::list_serial_devices()?;
+    if let Some(device) = devices.iter().find(|d| d.is_espressif && d.is_usable()) {
+        return Ok(device.port.clone().unwrap());
+    }
+    let message = match devices.iter().find(|d| d.is_espressif) {
+        Some(device) => format!(
+            "The connected board is not usable: {}",
+            device.hint.clone().unwrap_or_default()
+        ),
+        None => "No Espressif board found, connect one or pass --port".to_string(),
+    };
+    Err(EimError::new(ErrorKind::NotFound, message).into())
+}
+
+struct FlashTest {
+    env: InstallationEnvironment,
+}
+
+impl FlashTest {
+    fn command(&self, program: &Path, args: &[&str], dir: &Path) -> Result<Output> {
+        debug!(
+            "Running {} {:?} in {}",
+            program.display(),
+            args,
+            dir.display()
+        );
+        let output = Command::new(program)
+            .args(args)
+            .current_dir(dir)
+            .envs(
+                self.env
+                    .variables
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.as_str())),
+            )
+            .env(
+                "PATH",
+                self.env
+                    .path_value(&std::env::var("PATH").unwrap_or_default()),
+            )
+            .output()
+            .map_err(|e| anyhow!("Failed to run {}: {}", program.display(), e))?;
+        Ok(output)
+    }
+
+    fn idf_py(&self, args: &[&str], project: &Path) -> Result<()> {
+        let idf_py = self.env.idf_path.join("tools").join("idf.py");
+        let mut full_args = vec![idf_py.to_str().unwrap()];
+        full_args.extend_from_slice(args);
+        let output = self.command(&self.env.python, &full_args, project)?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let tail: Vec<&str> = stdout.lines().rev().take(30).collect();
+            Err(anyhow!(
+                "idf.py {} failed:\n{}\n{}",
+                args.join(" "),
+                tail.into_iter().rev().collect::<Vec<_>>().join("\n"),
+                String::from_utf8_lossy(&output.stderr)
+            ))
+        }
+    }
+
+    fn detect_target(&self, port: &str, dir: &Path) -> Result<String> {
+        let output = self.command(
+            &self.env.python,
+            &["-m", "esptool", "--port", port, "chip_id"],
+            dir,
+        )?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        target_from_chip_description(&stdout).ok_or_else(|| {
+            anyhow!(
+                "Failed to detect the chip on {}: {}{}",
+                port,
+                stdout,
+                String::from_utf8_lossy(&output.stderr)
+            )
+        })
+    }
+
+    fn read_boot_log(&self, port: &str, dir: &Path) -> Result<String> {
+        let timeout = BOOT_LOG_TIMEOUT_SECS.to_string();
+        let output = self.command(
+            &self.env.python,
+            &[
+                "-c",
+                READ_BOOT_LOG_SCRIPT,
+                port,
+                FLASH_TEST_MARKER,
+                &timeout,
+            ],
+            dir,
+        )?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Failed to read from {}: {}",
+                port,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Builds the hello_world example, flashes it to the board on `port` and checks its boot log.
+///
+/// This proves the toolchain, the Python environment, the drivers and the port permissions all
+/// work together. The chip is detected with esptool unless `target` is given. The project is
+/// built in a temporary directory, so nothing is left behind.
+pub fn run_flash_test(
+    installation: &IdfInstallation,
+    port: Option<&str>,
+    target: Option<&str>,
+    on_step: impl Fn(FlashTestStep),
+) -> Result<FlashTestReport> {
+    let test = FlashTest {
+        env: InstallationEnvironment::from_installation(installation)?,
+    };
+    let port = select_port(port)?;
+    let work_dir = tempfile::TempDir::new()?;
+    let project: PathBuf = work_dir.path().join("hello_world");
+    crate::utils::copy_dir_contents(&test.env.idf_path.join(FLASH_TEST_EXAMPLE), &project)?;
+
+    let target = match target {
+        Some(target) => target.to_string(),
+        None => {
+            on_step(FlashTestStep::DetectChip);
+            test.detect_target(&port, work_dir.path())?
+        }
+    };
+    info!(
+        "Flash test of ESP-IDF {} on {} ({})",
+        installation.name, port, target
+    );
+
+    on_step(FlashTestStep::Build);
+    test.idf_py(&["set-target", &target], &project)?;
+    test.idf_py(&["build"], &project)?;
+
+    on_step(FlashTestStep::Flash);
+    test.idf_py(&["-p", &port, "flash"], &project)?;
+
+    on_step(FlashTestStep::ReadBootLog);
+    let boot_log = test.read_boot_log(&port, work_dir.path())?;
+    Ok(FlashTestReport {
+        success: boot_log.contains(FLASH_TEST_MARKER),
+        port,
+        target,
+        boot_log,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_from_chip_description() {
+        let esptool_4 =
+            "Detecting chip type... ESP32-S3\nChip is ESP32-S3 (QFN56) (revision v0.2)\n";
+        assert_eq!(
+            target_from_chip_description(esptool_4),
+            Some("esp32s3".to_string())
+        );
+        let esptool_5 = "Connected to ESP32-C6 on /dev/ttyACM0:\nChip type:          ESP32-C6 (QFN40) (revision v0.1)\n";
+        assert_eq!(
+            target_from_chip_description(esptool_5),
+            Some("esp32c6".to_string())
+        );
+        assert_eq!(target_from_chip_description("A fatal error occurred"), None);
+    }
+}
@@ -0,0 +1,41 @@
+use log::{debug, warn};
+
+use crate::command_executor::execute_command;
+
+/// Packages of the ESP-IDF prerequisites on FreeBSD, installed with `pkg`
+pub const PKG_PREREQUISITES: &[&str] = &["git", "wget", "flex", "bison", "gperf", "ccache", "dfu-util", "cmake", "ninja"];
+
+/// Whether the Linux binary compatibility (linuxulator) is loaded, which runs the Linux builds
+/// of the tools: Espressif publishes none for FreeBSD.
+pub fn linuxulator_loaded() -> bool {
+    execute_command("sysctl", &["-n", "compat.linux.osrelease"])
+        .map(|output| output.status.success() && !output.stdout.is_empty())
+        .unwrap_or(false)
+}
+
+/// Warns that the tools will not run when the linuxulator is not loaded.
+pub fn check_linuxulator() {
+    if std::env::consts::OS != "freebsd" {
+        return;
+    }
+    if linuxulator_loaded() {
+        debug!("Linux binary compatibility is loaded, the Linux builds of the tools are used");
+    } else {
+        warn!(
+            "The tools are Linux builds, which FreeBSD runs with its Linux binary compatibility. Enable it with `sysrc linux_enable=YES && service linux start`, otherwise the toolchains will fail to start"
+        );
+    }
+}
+
+/// Packages of `prerequisites` `pkg` does not know as installed.
+pub fn missing_packages(prerequisites: &[&'static str]) -> Vec<&'static str> {
+    prerequisites
+        .iter()
+        .copied()
+        .filter(|package| {
+            !execute_command("pkg", &["info", "-e", package])
+                .map(|output| output.status.success())
+                .unwrap_or(false)
+        })
+        .collect()
+}
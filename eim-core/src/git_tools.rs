@@ -2,7 +2,7 @@ use std::fs::{self, OpenOptions, read_to_string};
 use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
 use std::io::{BufRead, BufReader};
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::Sender;
 use log::{debug, error, info, trace, warn};
 use gix::bstr::{BString, ByteSlice};
@@ -13,6 +13,46 @@ use anyhow::{anyhow, Result};
 use std::io::Write;
 use crate::command_executor::{ execute_command_with_dir, spawn_with_dir};
 use crate::ensure_path;
+use crate::settings::Settings;
+use crate::task_graph::TaskGraph;
+
+/// Default number of submodules fetched at the same time
+pub const DEFAULT_SUBMODULE_JOBS: usize = 8;
+
+static SUBMODULE_JOBS: AtomicUsize = AtomicUsize::new(DEFAULT_SUBMODULE_JOBS);
+
+/// Sets how many submodules are fetched at the same time, at least one.
+pub fn set_submodule_jobs(jobs: usize) {
+    SUBMODULE_JOBS.store(jobs.max(1), Ordering::Relaxed);
+}
+
+/// Applies the `submodule_jobs` setting.
+pub fn configure_from_settings(settings: &Settings) {
+    set_submodule_jobs(settings.submodule_jobs.map(|n| n as usize).unwrap_or(DEFAULT_SUBMODULE_JOBS));
+}
+
+/// Opens the repository at `dir` with the CA bundle, the proxy and the mirror token for `url`
+/// as in-memory configuration, so fetches into an existing repository use them like clones.
+fn open_for_fetch(dir: &Path, url: &str) -> Result<gix::Repository, gix::open::Error> {
+    let overrides = crate::http_client::git_config_overrides()
+        .into_iter()
+        .chain(crate::http_client::git_secret_overrides(url));
+    gix::open_opts(dir, gix::open::Options::default().config_overrides(overrides))
+}
+
+/// Writes the extra CA certificates configured for the installer into the local git config
+/// of `dest_path`, so fetches done with the `git` command-line tool trust them too.
+fn apply_git_tls_config(dest_path: &Path) {
+    for entry in crate::http_client::git_config_overrides() {
+        if let Some((key, value)) = entry.split_once('=') {
+            match execute_command_with_dir("git", &["config", key, value], dest_path.to_str().unwrap()) {
+                Ok(output) if output.status.success() => debug!("Set git {} in {}", key, dest_path.display()),
+                Ok(output) => warn!("Failed to set git {}: {}", key, String::from_utf8_lossy(&output.stderr)),
+                Err(e) => warn!("Failed to set git {}: {}", key, e),
+            }
+        }
+    }
+}
 
 /// Checks out a specific commit in a repository using the `git` command-line tool.
 ///
@@ -113,6 +153,7 @@ pub fn fetch_single_commit_git_cli(
                 String::from_utf8_lossy(&remote_result.stderr)
             ).into());
         }
+        apply_git_tls_config(dest_path);
     }
 
     // 10% - Initialized
@@ -120,6 +161,7 @@ pub fn fetch_single_commit_git_cli(
 
     // Fetch with progress parsing from stderr
     let mut child = spawn_with_dir("git", &["fetch", "--depth", "1", "--progress", "origin", commit_sha], dest_path.to_str().unwrap())?;
+    let _registered = crate::cancellation::register(child.id());
 
     // Parse progress from git's stderr
     if let Some(stderr) = child.stderr.take() {
@@ -270,6 +312,7 @@ pub fn clone_or_update_submodule(
                 String::from_utf8_lossy(&remote_result.stderr)
             ).into());
         }
+        apply_git_tls_config(dest_path);
     }
 
     // 10% - Initialized
@@ -277,6 +320,7 @@ pub fn clone_or_update_submodule(
 
     // Fetch with progress parsing from stderr
     let mut child = spawn_with_dir("git", &["fetch", "--depth", "1", "--progress", "origin", commit_sha], dest_path.to_str().unwrap())?;
+    let _registered = crate::cancellation::register(child.id());
 
     // Parse progress from git's stderr
     if let Some(stderr) = child.stderr.take() {
@@ -364,7 +408,33 @@ pub fn clone_repository(
     tx: Sender<ProgressMessage>,
 ) -> Result<PathBuf, Box<dyn std::error::Error>> {
     let dest_path = PathBuf::from(&options.path);
+    let recurse_submodules = options.recurse_submodules;
+
+    // the submodules are fetched once the checkout is complete, see `update_submodules_shallow`
+    let mut graph = TaskGraph::new("clone", 1);
+    graph.add_blocking_task("clone", &[], {
+        let (dest_path, tx) = (dest_path.clone(), tx.clone());
+        move |_| fetch_and_checkout(&options, &dest_path, &tx)
+    });
+    if recurse_submodules {
+        let dest_path = dest_path.clone();
+        graph.add_blocking_task("submodules", &["clone"], move |_| {
+            info!("Starting submodule update...");
+            let repo = gix::open(&dest_path)?;
+            match update_submodules_shallow(&repo, tx) {
+                Ok(_) => info!("Submodules updated successfully"),
+                Err(e) => error!("Submodule update failed: {}", e),
+            }
+            Ok(())
+        });
+    }
+    graph.run_blocking()?;
 
+    Ok(dest_path)
+}
+
+/// Fetches the repository of `options` and checks out its reference into `dest_path`.
+fn fetch_and_checkout(options: &CloneOptions, dest_path: &Path, tx: &Sender<ProgressMessage>) -> Result<()> {
     // Configure shallow clone
     let shallow = if options.shallow {
         match &options.reference {
@@ -377,15 +447,20 @@ pub fn clone_repository(
         gix::remote::fetch::Shallow::NoChange
     };
 
-    let should_interrupt = &AtomicBool::new(false);
+    let should_interrupt = crate::cancellation::interrupt_flag();
     let progress = gix::progress::Discard;
 
     // Prepare clone
     let url = gix::url::parse(options.url.as_str().into())?;
 
-    let mut prepare = gix::prepare_clone(url, &dest_path)?
+    let mut prepare = gix::prepare_clone(url, dest_path)?
         .with_remote_name("origin")?
-        .with_shallow(shallow);
+        .with_shallow(shallow)
+        .with_in_memory_config_overrides(
+            crate::http_client::git_config_overrides()
+                .into_iter()
+                .chain(crate::http_client::git_secret_overrides(&options.url)),
+        );
 
     // Configure which ref to fetch based on the reference type
     match &options.reference {
@@ -424,7 +499,7 @@ pub fn clone_repository(
             Err(e) => {
                 let _ = tx.send(ProgressMessage::Finish);
                 error!("Failed to fetch repository: {}", e);
-                return Err(Box::new(e));
+                return Err(e.into());
             }
         };
 
@@ -437,7 +512,7 @@ pub fn clone_repository(
             Err(e) => {
                 let _ = tx.send(ProgressMessage::Finish);
                 error!("Failed to checkout repository: {}", e);
-                return Err(Box::new(e));
+                return Err(e.into());
             }
         };
 
@@ -452,19 +527,17 @@ pub fn clone_repository(
         }
     }
 
-    info!("Cloned repository to {} proceeding to submodules...", dest_path.display());
-
-    // Handle submodules
-    info!("Starting submodule update...");
-    if options.recurse_submodules {
-        info!("Recurse submodules is TRUE");
-        match update_submodules_shallow(&repo, tx.clone()) {
-            Ok(_) => info!("Submodules updated successfully"),
-            Err(e) => error!("Submodule update failed: {}", e),
-        }
-    }
+    info!("Cloned repository to {}", dest_path.display());
+    Ok(())
+}
 
-    Ok(dest_path)
+/// A submodule registered in the parent repository, waiting to be fetched.
+struct SubmoduleJob {
+    name: String,
+    url: String,
+    expected_sha: String,
+    submodule_dir: PathBuf,
+    modules_dir: PathBuf,
 }
 
 /// Updates all submodules in a repository to their specified commits using a shallow fetch.
@@ -474,6 +547,11 @@ pub fn clone_repository(
 /// and then fetches only that specific commit for the submodule. This is more efficient than
 /// cloning the entire history of each submodule. It handles nested submodules recursively.
 ///
+/// Registering the submodules writes the shared `.git/config` and happens one after the other;
+/// the fetches then run as tasks of a [`TaskGraph`], up to `submodule_jobs` at a time, as each
+/// of them mostly waits on the network. A failing fetch is retried before the submodule counts
+/// as failed, which stops the fetches not started yet.
+///
 /// # Arguments
 ///
 /// * `repo` - The parent `gix::Repository` containing the submodules.
@@ -523,6 +601,7 @@ pub fn update_submodules_shallow(
 
     debug!("Found {} submodule commit entries in tree", submodule_commits.len());
 
+    let mut jobs = Vec::new();
     for submodule in submodules {
         let name = submodule.name().to_string();
         let path = submodule.path()?.to_string();
@@ -531,7 +610,7 @@ pub fn update_submodules_shallow(
         let url_raw = submodule.url()?.to_bstring().to_string();
 
         // Resolve relative URLs
-        let url = resolve_submodule_url(&url_raw, &parent_url)?;
+        let url = crate::url_rewrite::rewrite(&resolve_submodule_url(&url_raw, &parent_url)?);
 
         debug!("Processing submodule: {} at path: {}", name, path);
         if url != url_raw {
@@ -565,30 +644,91 @@ pub fn update_submodules_shallow(
         std::fs::create_dir_all(&submodule_dir)?;
         create_gitlink(&submodule_dir, &git_dir, &path)?;
 
-        // Step 4: Fetch commit into modules dir
-        fetch_single_commit_to_modules(
-            &modules_dir,
-            &url,
-            &expected_sha,
-            Some(tx.clone()),
-            Some(&name),
-        )?;
+        jobs.push(SubmoduleJob {
+            name,
+            url,
+            expected_sha,
+            submodule_dir,
+            modules_dir,
+        });
+    }
 
-        // Step 5: Checkout files to workdir
-        checkout_submodule_worktree(&modules_dir, &submodule_dir, &expected_sha)?;
+    let parallel = SUBMODULE_JOBS.load(Ordering::Relaxed);
+    debug!("Fetching {} submodules, {} at a time", jobs.len(), parallel);
+    let mut graph = TaskGraph::new("submodules", parallel);
+    for job in jobs {
+        let tx = tx.clone();
+        graph.add_blocking_task(&job.name.clone(), &[], move |stop| {
+            if stop.load(Ordering::SeqCst) || crate::cancellation::is_cancelled() {
+                return Ok(());
+            }
+            update_submodule(&job, &tx).map_err(|e| {
+                error!("Submodule {} failed: {}", job.name, e);
+                let _ = tx.send(ProgressMessage::SubmoduleFinish(job.name.clone()));
+                anyhow!("Failed to update submodule {}: {}", job.name, e)
+            })
+        });
+    }
+    let result = graph.run_blocking();
+    crate::cancellation::check()?;
+    result?;
+    Ok(())
+}
+
+/// Fetches and checks out a registered submodule, then its own submodules.
+fn update_submodule(
+    job: &SubmoduleJob,
+    tx: &Sender<ProgressMessage>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Step 4: Fetch commit into modules dir
+    fetch_submodule_with_retry(job, tx)?;
+
+    // Step 5: Checkout files to workdir
+    checkout_submodule_worktree(&job.modules_dir, &job.submodule_dir, &job.expected_sha)?;
 
-        info!("✓ Submodule complete: {}", name);
-        let _ = tx.send(ProgressMessage::SubmoduleFinish(name.clone()));
+    info!("✓ Submodule complete: {}", job.name);
+    let _ = tx.send(ProgressMessage::SubmoduleFinish(job.name.clone()));
 
-        // Recursively handle nested submodules
-        if let Ok(sub_repo) = gix::open(&submodule_dir) {
-            let _ = update_submodules_shallow(&sub_repo, tx.clone());
+    // Recursively handle nested submodules
+    if let Ok(sub_repo) = gix::open(&job.submodule_dir) {
+        if let Err(e) = update_submodules_shallow(&sub_repo, tx.clone()) {
+            warn!("Nested submodules of {} failed: {}", job.name, e);
         }
     }
-
     Ok(())
 }
 
+/// Fetches the commit of a submodule, retrying with a growing delay as set by the retry policy
+/// of its host, as a single dropped connection shouldn't fail a clone of dozens of submodules.
+fn fetch_submodule_with_retry(
+    job: &SubmoduleJob,
+    tx: &Sender<ProgressMessage>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let policy = crate::retry_policy::policy_for(&job.url);
+    let mut attempt = 1;
+    loop {
+        match fetch_single_commit_to_modules(
+            &job.modules_dir,
+            &job.url,
+            &job.expected_sha,
+            Some(tx.clone()),
+            Some(&job.name),
+        ) {
+            Ok(()) => return Ok(()),
+            Err(e) if policy.should_retry(attempt) && !crate::cancellation::is_cancelled() => {
+                warn!(
+                    "Fetching submodule {} failed (attempt {}/{}): {}",
+                    job.name, attempt, policy.max_attempts, e
+                );
+                let _ = tx.send(ProgressMessage::SubmoduleUpdate((job.name.clone(), 0)));
+                std::thread::sleep(policy.delay(attempt));
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 /// Retrieves the fetch URL of a remote for a `gix` repository.
 ///
 /// It first attempts to find the remote named "origin". If that fails, it iterates
@@ -1036,8 +1176,10 @@ fn fetch_single_commit_to_modules(
     send_progress(&tx, submodule_name, 20);
 
     // Open the repository at modules dir
-    let repo = gix::open(modules_dir)
-        .or_else(|_| gix::init(modules_dir))?;
+    if gix::open(modules_dir).is_err() {
+        gix::init(modules_dir)?;
+    }
+    let repo = open_for_fetch(modules_dir, url)?;
 
     let expected_oid = gix::ObjectId::from_hex(commit_sha.as_bytes())
         .map_err(|e| format!("Invalid SHA '{}': {}", commit_sha, e))?;
@@ -1080,7 +1222,7 @@ fn fetch_single_commit_to_modules(
         .prepare_fetch(gix::progress::Discard, gix::remote::ref_map::Options::default())
         .map_err(|e| format!("Failed to prepare fetch: {}", e))?
         .with_shallow(shallow)
-        .receive(gix::progress::Discard, &AtomicBool::new(false))
+        .receive(gix::progress::Discard, crate::cancellation::interrupt_flag())
         .map_err(|e| format!("Failed to receive: {}", e))?;
 
     send_progress(&tx, submodule_name, 70);
@@ -1310,7 +1452,7 @@ fn fetch_single_commit_gix(
     submodule_name: Option<&str>,
 ) -> Result<(), Box<dyn std::error::Error>> {
 
-    let should_interrupt = &AtomicBool::new(false);
+    let should_interrupt = crate::cancellation::interrupt_flag();
 
     // Parse the expected commit SHA upfront
     let expected_oid = gix::ObjectId::from_hex(commit_sha.as_bytes())
@@ -1351,7 +1493,7 @@ fn fetch_single_commit_gix(
     }
 
     // Initialize repository if needed
-    let repo = if !actual_git_dir.exists() {
+    if !actual_git_dir.exists() {
         fs::create_dir_all(&actual_git_dir)?;
 
         if is_submodule {
@@ -1360,15 +1502,11 @@ fn fetch_single_commit_gix(
 
             // Create HEAD file
             fs::write(actual_git_dir.join("HEAD"), "ref: refs/heads/master\n")?;
-
-            // Open the repository
-            gix::open(dest_path)?
         } else {
-            gix::init(dest_path)?
+            gix::init(dest_path)?;
         }
-    } else {
-        gix::open(dest_path)?
-    };
+    }
+    let repo = open_for_fetch(dest_path, url)?;
 
     send_progress(tx, submodule_name, 10);
 
@@ -1794,7 +1932,7 @@ pub fn get_esp_idf(
     // Ensure the path exists
     let _ = ensure_path(path);
 
-    let url = get_repo_url(repository, mirror);
+    let url = crate::url_rewrite::rewrite(&get_repo_url(repository, mirror));
 
     let shallow = true;
     // Parse version into a GitReference
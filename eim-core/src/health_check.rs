@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::path::Path;
+
+use crate::command_executor;
+use crate::idf_config::IdfInstallation;
+
+/// Modules every ESP-IDF Python environment has, a failing import means the venv is broken,
+/// typically because an OS upgrade replaced the Python it was created from.
+pub const PYTHON_IMPORT_CHECK: &str = "import esptool, idf_component_manager";
+
+/// Result of the quick startup check of the default installation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+pub struct HealthReport {
+    pub id: String,
+    pub name: String,
+    /// Empty when the installation is healthy
+    pub problems: Vec<String>,
+}
+
+/// Checks that the Python environment of the installation can still import the IDF packages.
+fn python_problems(installation: &IdfInstallation) -> Vec<String> {
+    match command_executor::execute_command(&installation.python, &["-c", PYTHON_IMPORT_CHECK]) {
+        Ok(output) if output.status.success() => vec![],
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.lines().last().unwrap_or_default().trim().to_string();
+            vec![format!("Python environment {} is broken: {}", installation.python, reason)]
+        }
+        Err(err) => vec![format!("Python {} cannot be run: {}", installation.python, err)],
+    }
+}
+
+/// Checks that the `PATH` still contains what eim put there: git, and the scoop shims
+/// on Windows when the prerequisites were installed with scoop.
+fn path_problems(path_var: &OsStr) -> Vec<String> {
+    let mut problems = Vec::new();
+    if crate::which::find_in_path("git", path_var).is_none() {
+        problems.push("git is no longer on PATH".to_string());
+    }
+    if let Some(shims) = crate::system_dependencies::get_scoop_path() {
+        let on_path = std::env::split_paths(path_var).any(|entry| entry == Path::new(&shims));
+        if Path::new(&shims).is_dir() && !on_path {
+            problems.push(format!("{} is no longer on PATH", shims));
+        }
+    }
+    problems
+}
+
+/// Fast health check of an installation: its folders, its Python environment and the `PATH`.
+pub fn check_installation(installation: &IdfInstallation) -> HealthReport {
+    let mut problems = crate::status::installation_problems(installation);
+    if Path::new(&installation.python).is_file() {
+        problems.extend(python_problems(installation));
+    }
+    problems.extend(path_problems(&std::env::var_os("PATH").unwrap_or_default()));
+    HealthReport {
+        id: installation.id.clone(),
+        name: installation.name.clone(),
+        problems,
+    }
+}
+
+/// Checks the selected installation, `None` if there is none.
+pub fn check_selected() -> Option<HealthReport> {
+    crate::version_manager::get_selected_version().map(|installation| check_installation(&installation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idf_config::test_installation;
+
+    #[test]
+    fn test_missing_installation_is_reported() {
+        let installation = IdfInstallation {
+            activation_script: "/nonexistent/activate_idf_v5.3.sh".to_string(),
+            id: "esp-idf-test".to_string(),
+            idf_tools_path: "/nonexistent/tools".to_string(),
+            path: "/nonexistent/esp-idf".to_string(),
+            python: "/nonexistent/python".to_string(),
+            ..test_installation("v5.3")
+        };
+        let report = check_installation(&installation);
+        assert_eq!(report.id, "esp-idf-test");
+        assert!(report.problems.iter().any(|problem| problem.contains("Python /nonexistent/python missing")));
+    }
+
+    #[test]
+    fn test_git_missing_from_path() {
+        assert!(path_problems(OsStr::new("")).iter().any(|problem| problem.starts_with("git")));
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::errors::{EimError, ErrorKind};
+use crate::settings::{Settings, VersionPaths};
+
+/// Time a hook script may run before it is killed, unless `timeout_secs` is set
+pub const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 600;
+
+/// Scripts from the `[hooks]` table of the configuration, run before and after each version is installed.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct InstallHooks {
+    pub pre_install: Vec<String>,
+    pub post_install: Vec<String>,
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookStage {
+    PreInstall,
+    PostInstall,
+}
+
+impl HookStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookStage::PreInstall => "pre_install",
+            HookStage::PostInstall => "post_install",
+        }
+    }
+}
+
+/// Environment variables describing the installation, exported to the hook scripts.
+pub fn hook_environment(stage: HookStage, settings: &Settings, paths: &VersionPaths) -> Vec<(String, String)> {
+    let path = |p: &std::path::Path| p.to_string_lossy().into_owned();
+    vec![
+        ("EIM_HOOK".to_string(), stage.name().to_string()),
+        ("EIM_VERSION".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+        ("EIM_IDF_VERSION".to_string(), paths.actual_version.clone()),
+        ("EIM_IDF_PATH".to_string(), path(&paths.idf_path)),
+        ("EIM_INSTALL_PATH".to_string(), path(&paths.version_installation_path)),
+        ("EIM_TOOLS_PATH".to_string(), path(&paths.tool_install_directory)),
+        ("EIM_PYTHON_ENV_PATH".to_string(), path(&paths.python_venv_path)),
+        ("EIM_ACTIVATION_SCRIPT".to_string(), path(&paths.activation_script)),
+        (
+            "EIM_TARGETS".to_string(),
+            settings.target.clone().unwrap_or_default().join(","),
+        ),
+    ]
+}
+
+/// Runs the hook scripts configured for `stage` one after another, stopping at the first failure.
+///
+/// Each entry is a command line run by the shell (`sh -c`, `cmd /C` on Windows) with the install
+/// context in `EIM_*` environment variables. Its output is written to the log line by line.
+pub fn run_hooks(stage: HookStage, settings: &Settings, paths: &VersionPaths) -> Result<()> {
+    let Some(hooks) = &settings.hooks else {
+        return Ok(());
+    };
+    let scripts = match stage {
+        HookStage::PreInstall => &hooks.pre_install,
+        HookStage::PostInstall => &hooks.post_install,
+    };
+    let timeout = Duration::from_secs(hooks.timeout_secs.unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS));
+    let env = hook_environment(stage, settings, paths);
+    for script in scripts.iter().filter(|s| !s.trim().is_empty()) {
+        run_hook(stage, script, &env, timeout)?;
+    }
+    Ok(())
+}
+
+fn log_output(stage: HookStage, stream: impl Read + Send + 'static) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            info!("[{} hook] {}", stage.name(), line);
+        }
+    })
+}
+
+fn run_hook(stage: HookStage, script: &str, env: &[(String, String)], timeout: Duration) -> Result<()> {
+    info!("Running {} hook: {}", stage.name(), script);
+    let mut command = match std::env::consts::OS {
+        "windows" => {
+            let mut command = Command::new("cmd");
+            command.args(["/C", script]);
+            command
+        }
+        _ => {
+            let mut command = Command::new("sh");
+            command.args(["-c", script]);
+            command
+        }
+    };
+    let mut child = command
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            EimError::new(
+                ErrorKind::General,
+                format!("Failed to start {} hook '{}': {}", stage.name(), script, e),
+            )
+        })?;
+    let readers = [
+        log_output(stage, child.stdout.take().unwrap()),
+        log_output(stage, child.stderr.take().unwrap()),
+    ];
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if started.elapsed() >= timeout {
+            warn!("{} hook '{}' timed out, killing it", stage.name(), script);
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    // after a kill, processes started by the script may still hold the pipes open
+    if status.is_some() {
+        for reader in readers {
+            let _ = reader.join();
+        }
+    }
+    match status {
+        Some(status) if status.success() => Ok(()),
+        Some(status) => Err(EimError::new(
+            ErrorKind::General,
+            format!("{} hook '{}' failed with {}", stage.name(), script, status),
+        )
+        .into()),
+        None => Err(EimError::new(
+            ErrorKind::General,
+            format!(
+                "{} hook '{}' did not finish within {} seconds",
+                stage.name(),
+                script,
+                timeout.as_secs()
+            ),
+        )
+        .into()),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_get_context_and_time_out() {
+        let env = vec![("EIM_IDF_VERSION".to_string(), "v5.3".to_string())];
+        let timeout = Duration::from_secs(5);
+        assert!(run_hook(HookStage::PostInstall, "test \"$EIM_IDF_VERSION\" = v5.3", &env, timeout).is_ok());
+        assert!(run_hook(HookStage::PostInstall, "exit 3", &env, timeout).is_err());
+
+        let started = Instant::now();
+        assert!(run_hook(HookStage::PreInstall, "sleep 10", &env, Duration::from_millis(300)).is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
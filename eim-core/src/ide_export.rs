@@ -0,0 +1,614 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use serde_json::{json, Map, Value};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tera::{Context, Tera};
+
+use crate::idf_config::IdfInstallation;
+use crate::idf_tools::{get_tools_export_paths, read_and_parse_tools_file};
+
+pub const CMAKE_USER_PRESETS_FILE_NAME: &str = "CMakeUserPresets.json";
+pub const DOTENV_FILE_NAME: &str = ".env";
+pub const ENVRC_FILE_NAME: &str = ".envrc";
+const ENVRC_BLOCK_START: &str = "# >>> eim >>>";
+const ENVRC_BLOCK_END: &str = "# <<< eim <<<";
+/// Preference node holding the CDT build environment, which Espressif-IDE reads the IDF setup from.
+const ECLIPSE_CDT_NODE: &str = "org.eclipse.cdt.core";
+/// Presets schema version 3 (CMake 3.21) is the first one supporting `toolchainFile`.
+const CMAKE_PRESETS_VERSION: u64 = 3;
+
+/// Environment an installed ESP-IDF version needs, as the activation script would set it up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstallationEnvironment {
+    pub name: String,
+    /// ESP-IDF version, differs from the name for installations named with `--version-name`
+    pub idf_version: String,
+    pub idf_path: PathBuf,
+    pub tools_path: PathBuf,
+    pub python: PathBuf,
+    /// Directories which have to be prepended to `PATH`
+    pub path_entries: Vec<String>,
+    /// Variables like `IDF_PATH` or `OPENOCD_SCRIPTS`, without `PATH`
+    pub variables: Vec<(String, String)>,
+}
+
+/// The environment of `name` installed below `/esp`, with its Python environment on the `PATH`.
+#[cfg(test)]
+pub(crate) fn test_environment(name: &str) -> InstallationEnvironment {
+    InstallationEnvironment {
+        name: name.to_string(),
+        idf_version: name.to_string(),
+        idf_path: PathBuf::from(format!("/esp/{}/esp-idf", name)),
+        tools_path: PathBuf::from("/esp/tools"),
+        python: PathBuf::from(format!("/esp/tools/python/{}/venv/bin/python", name)),
+        path_entries: vec![format!("/esp/tools/python/{}/venv/bin", name)],
+        variables: vec![("IDF_PATH".to_string(), format!("/esp/{}/esp-idf", name))],
+    }
+}
+
+impl InstallationEnvironment {
+    pub fn from_installation(installation: &IdfInstallation) -> Result<Self> {
+        let idf_path = PathBuf::from(&installation.path);
+        let tools_path = PathBuf::from(&installation.idf_tools_path);
+        let python = PathBuf::from(&installation.python);
+        let tools_json = idf_path.join("tools").join("tools.json");
+        let tools_file = read_and_parse_tools_file(tools_json.to_str().unwrap())
+            .map_err(|e| anyhow!("Failed to read {}: {}", tools_json.display(), e))?;
+
+        let mut path_entries = get_tools_export_paths(
+            tools_file,
+            vec!["all".to_string()],
+            tools_path.to_str().unwrap(),
+        );
+        // registered toolchains take precedence over the official ones
+        path_entries.splice(0..0, crate::custom_toolchains::export_paths());
+        if let Some(python_bin) = python.parent() {
+            path_entries.insert(0, python_bin.to_string_lossy().into_owned());
+        }
+        // idf.py
+        path_entries.push(idf_path.join("tools").to_string_lossy().into_owned());
+
+        // the python executable lives in <venv>/bin or <venv>/Scripts
+        let venv = python.parent().and_then(Path::parent).map(|p| p.to_string_lossy().into_owned());
+        let variables = crate::default_environment_variables(
+            tools_path.to_str().unwrap(),
+            idf_path.to_str().unwrap(),
+            venv.as_deref(),
+        );
+        debug!("Environment of {}: {:?} {:?}", installation.name, path_entries, variables);
+
+        Ok(Self {
+            name: installation.name.clone(),
+            idf_version: installation.idf_version.clone().unwrap_or_else(|| installation.name.clone()),
+            idf_path,
+            tools_path,
+            python,
+            path_entries,
+            variables,
+        })
+    }
+
+    /// Returns the `PATH` value with the ESP-IDF directories in front of `inherited`.
+    pub fn path_value(&self, inherited: &str) -> String {
+        let separator = if std::env::consts::OS == "windows" { ";" } else { ":" };
+        let mut entries = self.path_entries.clone();
+        entries.push(inherited.to_string());
+        entries.join(separator)
+    }
+
+    /// Looks up an executable in the ESP-IDF `PATH` entries.
+    pub fn find_executable(&self, name: &str) -> Option<PathBuf> {
+        let file_name = if std::env::consts::OS == "windows" {
+            format!("{}.exe", name)
+        } else {
+            name.to_string()
+        };
+        self.path_entries
+            .iter()
+            .map(|dir| Path::new(dir).join(&file_name))
+            .find(|path| path.is_file())
+    }
+}
+
+/// Returns the GCC prefix of the toolchain building for `target`.
+pub fn toolchain_prefix(target: &str) -> String {
+    match target {
+        "esp32" | "esp32s2" | "esp32s3" => format!("xtensa-{}-elf", target),
+        _ => "riscv32-esp-elf".to_string(),
+    }
+}
+
+fn preset_name(environment: &InstallationEnvironment, target: &str) -> String {
+    format!("esp-idf-{}-{}", environment.name, target)
+}
+
+/// Builds the configure preset for building projects for `target` with an installation.
+pub fn cmake_configure_preset(environment: &InstallationEnvironment, target: &str) -> Value {
+    let mut cache_variables = Map::new();
+    cache_variables.insert("IDF_TARGET".to_string(), json!(target));
+    cache_variables.insert("PYTHON".to_string(), json!(environment.python));
+    let prefix = toolchain_prefix(target);
+    for (variable, compiler) in [("CMAKE_C_COMPILER", "gcc"), ("CMAKE_CXX_COMPILER", "g++")] {
+        if let Some(path) = environment.find_executable(&format!("{}-{}", prefix, compiler)) {
+            cache_variables.insert(variable.to_string(), json!(path));
+        }
+    }
+
+    let mut variables = Map::new();
+    for (key, value) in &environment.variables {
+        variables.insert(key.clone(), json!(value));
+    }
+    variables.insert("PATH".to_string(), json!(environment.path_value("$penv{PATH}")));
+
+    json!({
+        "name": preset_name(environment, target),
+        "displayName": format!("ESP-IDF {} ({})", environment.name, target),
+        "generator": "Ninja",
+        "binaryDir": "${sourceDir}/build",
+        "toolchainFile": environment.idf_path.join("tools").join("cmake").join(format!("toolchain-{}.cmake", target)),
+        "cacheVariables": cache_variables,
+        "environment": variables,
+    })
+}
+
+/// Adds `preset` to the `key` array of `presets`, replacing a preset with the same name.
+fn upsert_preset(presets: &mut Map<String, Value>, key: &str, preset: Value) {
+    let list = presets.entry(key.to_string()).or_insert_with(|| json!([]));
+    if !list.is_array() {
+        *list = json!([]);
+    }
+    let list = list.as_array_mut().unwrap();
+    list.retain(|existing| existing["name"] != preset["name"]);
+    list.push(preset);
+}
+
+/// Merges the presets of an installation into the content of an existing `CMakeUserPresets.json`.
+///
+/// Presets of other installations or written by hand are kept.
+pub fn merge_cmake_presets(existing: Option<Value>, environment: &InstallationEnvironment, target: &str) -> Value {
+    let mut presets = match existing {
+        Some(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+    let version = presets.get("version").and_then(Value::as_u64).unwrap_or(0);
+    presets.insert("version".to_string(), json!(version.max(CMAKE_PRESETS_VERSION)));
+
+    let name = preset_name(environment, target);
+    upsert_preset(&mut presets, "configurePresets", cmake_configure_preset(environment, target));
+    upsert_preset(
+        &mut presets,
+        "buildPresets",
+        json!({ "name": name, "configurePreset": name }),
+    );
+    Value::Object(presets)
+}
+
+/// Writes the environment as `KEY=VALUE` lines, the format IDE env file plugins load.
+pub fn env_file_content(environment: &InstallationEnvironment) -> String {
+    let mut content = String::new();
+    for (key, value) in &environment.variables {
+        content.push_str(&format!("{}={}\n", key, value));
+    }
+    content.push_str(&format!("PATH={}\n", environment.path_value("${PATH}")));
+    content
+}
+
+/// Writes `CMakeUserPresets.json` and an environment file for an installation to `out_dir`.
+///
+/// Returns the paths of the written files.
+pub fn export_cmake(environment: &InstallationEnvironment, target: &str, out_dir: &Path) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(out_dir)?;
+    let presets_path = out_dir.join(CMAKE_USER_PRESETS_FILE_NAME);
+    let existing = match fs::read_to_string(&presets_path) {
+        Ok(content) => Some(
+            serde_json::from_str(&content)
+                .map_err(|e| anyhow!("Failed to parse {}: {}", presets_path.display(), e))?,
+        ),
+        Err(_) => None,
+    };
+    let presets = merge_cmake_presets(existing, environment, target);
+    fs::write(&presets_path, serde_json::to_string_pretty(&presets)?)?;
+
+    let env_path = out_dir.join(format!("esp-idf-{}.env", environment.name));
+    fs::write(&env_path, env_file_content(environment))?;
+    info!("Exported CMake presets of {} to {}", environment.name, out_dir.display());
+    Ok(vec![presets_path, env_path])
+}
+
+/// Escapes a key or value the way `java.util.Properties` stores it.
+fn escape_eclipse_property(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | ':' | '=' | '#' | '!' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns the CDT workspace environment preferences of an installation.
+///
+/// `PATH` is prepended to the environment Eclipse was started with, all other variables replace it.
+pub fn eclipse_preferences(environment: &InstallationEnvironment) -> Vec<(String, String)> {
+    let delimiter = if std::env::consts::OS == "windows" { ";" } else { ":" };
+    let mut variables: Vec<(String, String, &str)> = environment
+        .variables
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone(), "replace"))
+        .collect();
+    variables.push(("PATH".to_string(), environment.path_entries.join(delimiter), "prepend"));
+
+    let mut preferences = Vec::new();
+    for (key, value, operation) in variables {
+        let prefix = format!("environment/workspace/{}", key);
+        preferences.push((format!("{}/delimiter", prefix), delimiter.to_string()));
+        preferences.push((format!("{}/operation", prefix), operation.to_string()));
+        preferences.push((format!("{}/value", prefix), value));
+    }
+    preferences.push(("environment/workspace/append".to_string(), "true".to_string()));
+    preferences.push(("environment/workspace/appendContributed".to_string(), "true".to_string()));
+    preferences
+}
+
+/// Content of an `.epf` file, importable with File > Import > Preferences.
+pub fn eclipse_epf_content(environment: &InstallationEnvironment) -> String {
+    let mut content = String::from("file_export_version=3.0\n");
+    for (key, value) in eclipse_preferences(environment) {
+        content.push_str(&format!(
+            "/instance/{}/{}={}\n",
+            ECLIPSE_CDT_NODE,
+            escape_eclipse_property(&key),
+            escape_eclipse_property(&value)
+        ));
+    }
+    content
+}
+
+/// Merges the preferences into the content of an existing `.prefs` file, keeping unrelated lines.
+pub fn merge_eclipse_prefs(existing: &str, environment: &InstallationEnvironment) -> String {
+    let preferences: Vec<(String, String)> = eclipse_preferences(environment)
+        .into_iter()
+        .map(|(key, value)| (escape_eclipse_property(&key), escape_eclipse_property(&value)))
+        .collect();
+    let mut lines: Vec<String> = existing
+        .lines()
+        .filter(|line| {
+            let key = line.split('=').next().unwrap_or_default();
+            !preferences.iter().any(|(k, _)| k == key)
+        })
+        .map(str::to_string)
+        .collect();
+    if !lines.iter().any(|line| line.starts_with("eclipse.preferences.version=")) {
+        lines.insert(0, "eclipse.preferences.version=1".to_string());
+    }
+    for (key, value) in preferences {
+        lines.push(format!("{}={}", key, value));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Writes an Espressif-IDE/Eclipse preferences file for an installation to `out_dir`.
+///
+/// With `workspace`, the preferences are also merged into that Eclipse workspace directly,
+/// which takes effect the next time the IDE opens it. Returns the paths of the written files.
+pub fn export_eclipse(
+    environment: &InstallationEnvironment,
+    out_dir: &Path,
+    workspace: Option<&Path>,
+) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(out_dir)?;
+    let epf_path = out_dir.join(format!("esp-idf-{}.epf", environment.name));
+    fs::write(&epf_path, eclipse_epf_content(environment))?;
+    let mut written = vec![epf_path];
+
+    if let Some(workspace) = workspace {
+        let settings_dir = workspace
+            .join(".metadata")
+            .join(".plugins")
+            .join("org.eclipse.core.runtime")
+            .join(".settings");
+        fs::create_dir_all(&settings_dir)?;
+        let prefs_path = settings_dir.join(format!("{}.prefs", ECLIPSE_CDT_NODE));
+        let existing = fs::read_to_string(&prefs_path).unwrap_or_default();
+        fs::write(&prefs_path, merge_eclipse_prefs(&existing, environment))?;
+        written.push(prefs_path);
+    }
+    info!("Exported Eclipse preferences of {} to {}", environment.name, out_dir.display());
+    Ok(written)
+}
+
+/// Builds the devcontainer.json, bind-mounting the tools of `host` if given.
+pub fn devcontainer_json(idf_version: &str, host: Option<&InstallationEnvironment>) -> Value {
+    let mut devcontainer = json!({
+        "name": format!("ESP-IDF {}", idf_version),
+        "build": { "dockerfile": "Dockerfile" },
+        "customizations": { "vscode": { "extensions": ["espressif.esp-idf-extension"] } },
+    });
+    if let Some(environment) = host {
+        // mounted at the same paths, so the absolute paths in the venv and scripts stay valid
+        let mount = |path: &Path| {
+            format!(
+                "source={0},target={0},type=bind,readonly",
+                path.to_string_lossy()
+            )
+        };
+        let idf_root = environment.idf_path.parent().unwrap_or(&environment.idf_path);
+        devcontainer["mounts"] = json!([mount(idf_root), mount(&environment.tools_path)]);
+        let mut remote_env = Map::new();
+        for (key, value) in &environment.variables {
+            remote_env.insert(key.clone(), json!(value));
+        }
+        remote_env.insert(
+            "PATH".to_string(),
+            json!(environment.path_value("${containerEnv:PATH}")),
+        );
+        devcontainer["remoteEnv"] = Value::Object(remote_env);
+    }
+    devcontainer
+}
+
+/// Renders the Dockerfile, which installs `idf_version` with eim unless the host installation is mounted.
+pub fn devcontainer_dockerfile(idf_version: &str, mount: bool) -> Result<String> {
+    let template = include_str!("../devcontainer_templates/Dockerfile");
+    let mut tera = Tera::default();
+    tera.add_raw_template("Dockerfile", template)?;
+    let mut context = Context::new();
+    context.insert("idf_version", idf_version);
+    context.insert("mount", &mount);
+    Ok(tera.render("Dockerfile", &context)?)
+}
+
+/// Writes `devcontainer.json` and a `Dockerfile` to `out_dir`, usually `.devcontainer` in the project.
+///
+/// Without `host`, the container installs `idf_version` with eim when it is built. With `host`,
+/// the host installation is bind-mounted instead, which only works if the host runs Linux on
+/// the same architecture as the container. Returns the paths of the written files.
+pub fn export_devcontainer(
+    idf_version: &str,
+    host: Option<&InstallationEnvironment>,
+    out_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    if host.is_some() && std::env::consts::OS != "linux" {
+        return Err(anyhow!(
+            "Mounting the host installation into a container requires a Linux host"
+        ));
+    }
+    fs::create_dir_all(out_dir)?;
+    let json_path = out_dir.join("devcontainer.json");
+    fs::write(
+        &json_path,
+        serde_json::to_string_pretty(&devcontainer_json(idf_version, host))?,
+    )?;
+    let dockerfile_path = out_dir.join("Dockerfile");
+    fs::write(&dockerfile_path, devcontainer_dockerfile(idf_version, host.is_some())?)?;
+    info!("Exported devcontainer for {} to {}", idf_version, out_dir.display());
+    Ok(vec![json_path, dockerfile_path])
+}
+
+/// Appends the environment to the files GitHub Actions reads between steps.
+///
+/// `github_env` and `github_path` are the files named by `$GITHUB_ENV` and `$GITHUB_PATH`.
+/// The runner puts the last line of `$GITHUB_PATH` first, so the entries are written in
+/// reverse to keep their order.
+pub fn export_github_env(
+    environment: &InstallationEnvironment,
+    github_env: &Path,
+    github_path: &Path,
+) -> Result<()> {
+    let mut env_file = OpenOptions::new().create(true).append(true).open(github_env)?;
+    for (key, value) in &environment.variables {
+        writeln!(env_file, "{}={}", key, value)?;
+    }
+    writeln!(env_file, "ESP_IDF_VERSION={}", environment.idf_version)?;
+
+    let mut path_file = OpenOptions::new().create(true).append(true).open(github_path)?;
+    for entry in environment.path_entries.iter().rev() {
+        writeln!(path_file, "{}", entry)?;
+    }
+    info!("Exported environment of {} to GitHub Actions", environment.name);
+    Ok(())
+}
+
+/// Replaces the variables of the environment in the content of an existing `.env` file,
+/// keeping the project's own variables.
+pub fn merge_dotenv(existing: &str, environment: &InstallationEnvironment) -> String {
+    let content = env_file_content(environment);
+    let keys: Vec<&str> = content.lines().filter_map(|line| line.split('=').next()).collect();
+    let mut merged: String = existing
+        .lines()
+        .filter(|line| {
+            let key = line.trim_start().trim_start_matches("export ").split('=').next().unwrap_or_default();
+            !keys.contains(&key.trim())
+        })
+        .map(|line| format!("{}\n", line))
+        .collect();
+    merged.push_str(&content);
+    merged
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Returns the block eim maintains in a `.envrc`.
+pub fn envrc_block(environment: &InstallationEnvironment) -> String {
+    let mut block = format!("{}\n# ESP-IDF {}\n", ENVRC_BLOCK_START, environment.name);
+    for (key, value) in &environment.variables {
+        block.push_str(&format!("export {}={}\n", key, shell_quote(value)));
+    }
+    // PATH_add prepends, so the first entry has to be added last
+    for entry in environment.path_entries.iter().rev() {
+        block.push_str(&format!("PATH_add {}\n", shell_quote(entry)));
+    }
+    block.push_str(ENVRC_BLOCK_END);
+    block.push('\n');
+    block
+}
+
+/// Replaces the eim block of an existing `.envrc`, or appends it if there is none yet.
+pub fn merge_envrc(existing: &str, environment: &InstallationEnvironment) -> String {
+    let block = envrc_block(environment);
+    match (existing.find(ENVRC_BLOCK_START), existing.find(ENVRC_BLOCK_END)) {
+        (Some(start), Some(end)) if start < end => {
+            let end = existing[end..]
+                .find('\n')
+                .map(|i| end + i + 1)
+                .unwrap_or(existing.len());
+            format!("{}{}{}", &existing[..start], block, &existing[end..])
+        }
+        _ if existing.is_empty() => block,
+        _ if existing.ends_with('\n') => format!("{}{}", existing, block),
+        _ => format!("{}\n{}", existing, block),
+    }
+}
+
+/// Writes the environment into the `.env` file in `out_dir`, returning its path.
+pub fn export_dotenv(environment: &InstallationEnvironment, out_dir: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(out_dir)?;
+    let path = out_dir.join(DOTENV_FILE_NAME);
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    fs::write(&path, merge_dotenv(&existing, environment))?;
+    info!("Exported environment of {} to {}", environment.name, path.display());
+    Ok(path)
+}
+
+/// Writes the environment into the `.envrc` file in `out_dir`, returning its path.
+pub fn export_direnv(environment: &InstallationEnvironment, out_dir: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(out_dir)?;
+    let path = out_dir.join(ENVRC_FILE_NAME);
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    fs::write(&path, merge_envrc(&existing, environment))?;
+    info!("Exported environment of {} to {}", environment.name, path.display());
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn environment() -> InstallationEnvironment {
+        InstallationEnvironment {
+            path_entries: vec!["/esp/tools/cmake/3.30.2/bin".to_string()],
+            ..test_environment("v5.3.2")
+        }
+    }
+
+    #[test]
+    fn test_export_keeps_foreign_presets() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join(CMAKE_USER_PRESETS_FILE_NAME),
+            r#"{"version": 2, "configurePresets": [{"name": "host-tests"}]}"#,
+        )
+        .unwrap();
+
+        export_cmake(&environment(), "esp32s3", temp_dir.path()).unwrap();
+        // exporting again replaces the preset instead of adding a second one
+        let files = export_cmake(&environment(), "esp32s3", temp_dir.path()).unwrap();
+
+        let presets: Value = serde_json::from_str(&fs::read_to_string(&files[0]).unwrap()).unwrap();
+        assert_eq!(presets["version"], 3);
+        let names: Vec<&str> = presets["configurePresets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["host-tests", "esp-idf-v5.3.2-esp32s3"]);
+        assert_eq!(presets["configurePresets"][1]["cacheVariables"]["IDF_TARGET"], "esp32s3");
+
+        let env = fs::read_to_string(&files[1]).unwrap();
+        assert!(env.contains("IDF_PATH=/esp/v5.3.2/esp-idf\n"));
+    }
+
+    #[test]
+    fn test_eclipse_prefs_merge() {
+        let existing = "eclipse.preferences.version=1\nindexer/mode=fast\nenvironment/workspace/IDF_PATH/value=\\/old\n";
+        let merged = merge_eclipse_prefs(existing, &environment());
+        assert!(merged.contains("indexer/mode=fast\n"));
+        assert!(!merged.contains("/old"));
+        assert!(merged.contains("environment/workspace/IDF_PATH/value=/esp/v5.3.2/esp-idf\n"));
+        assert!(merged.contains("environment/workspace/PATH/operation=prepend\n"));
+        assert_eq!(merged.matches("eclipse.preferences.version").count(), 1);
+    }
+
+    #[test]
+    fn test_eclipse_property_escaping() {
+        assert_eq!(escape_eclipse_property(r"C:\Espressif"), r"C\:\\Espressif");
+    }
+
+    #[test]
+    fn test_devcontainer_modes() {
+        let dockerfile = devcontainer_dockerfile("v5.2", false).unwrap();
+        assert!(dockerfile.contains("ARG IDF_VERSION=v5.2"));
+        assert!(devcontainer_json("v5.2", None).get("mounts").is_none());
+
+        let dockerfile = devcontainer_dockerfile("v5.3.2", true).unwrap();
+        assert!(!dockerfile.contains("eim install"));
+        let devcontainer = devcontainer_json("v5.3.2", Some(&environment()));
+        assert_eq!(
+            devcontainer["mounts"][1],
+            "source=/esp/tools,target=/esp/tools,type=bind,readonly"
+        );
+        assert_eq!(devcontainer["remoteEnv"]["IDF_PATH"], "/esp/v5.3.2/esp-idf");
+    }
+
+    #[test]
+    fn test_github_env_appends() {
+        let temp_dir = TempDir::new().unwrap();
+        let github_env = temp_dir.path().join("env");
+        let github_path = temp_dir.path().join("path");
+        fs::write(&github_env, "FOO=bar\n").unwrap();
+        let mut environment = environment();
+        // a copy named with --version-name still reports the version it was installed from
+        environment.name = "customer-a".to_string();
+        environment.path_entries.insert(0, "/esp/tools/python/v5.3.2/venv/bin".to_string());
+
+        export_github_env(&environment, &github_env, &github_path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&github_env).unwrap(),
+            "FOO=bar\nIDF_PATH=/esp/v5.3.2/esp-idf\nESP_IDF_VERSION=v5.3.2\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&github_path).unwrap(),
+            "/esp/tools/cmake/3.30.2/bin\n/esp/tools/python/v5.3.2/venv/bin\n"
+        );
+    }
+
+    #[test]
+    fn test_dotenv_keeps_project_variables() {
+        let existing = "WIFI_SSID=lab\nIDF_PATH=/esp/v5.1/esp-idf\n";
+        let merged = merge_dotenv(existing, &environment());
+        assert!(merged.starts_with("WIFI_SSID=lab\n"));
+        assert_eq!(merged.matches("IDF_PATH=").count(), 1);
+        assert!(merged.contains("IDF_PATH=/esp/v5.3.2/esp-idf\n"));
+    }
+
+    #[test]
+    fn test_envrc_block_is_replaced() {
+        let first = merge_envrc("dotenv_if_exists\n", &environment());
+        assert!(first.starts_with("dotenv_if_exists\n# >>> eim >>>\n"));
+        assert!(first.contains("export IDF_PATH='/esp/v5.3.2/esp-idf'\n"));
+        assert!(first.contains("PATH_add '/esp/tools/cmake/3.30.2/bin'\n"));
+
+        let mut other = environment();
+        other.name = "v5.4".to_string();
+        let second = merge_envrc(&format!("{}layout python\n", first), &other);
+        assert_eq!(second.matches(ENVRC_BLOCK_START).count(), 1);
+        assert!(second.contains("# ESP-IDF v5.4\n"));
+        assert!(second.ends_with("# <<< eim <<<\nlayout python\n"));
+    }
+
+    #[test]
+    fn test_toolchain_prefix() {
+        assert_eq!(toolchain_prefix("esp32s3"), "xtensa-esp32s3-elf");
+        assert_eq!(toolchain_prefix("esp32c6"), "riscv32-esp-elf");
+    }
+}
@@ -9,6 +9,7 @@ use std::path::Path;
 use crate::ensure_path;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
 pub struct IdfInstallation {
     #[serde(rename = "activationScript")]
     pub activation_script: String,
@@ -18,6 +19,28 @@ pub struct IdfInstallation {
     pub name: String,
     pub path: String,
     pub python: String,
+    /// ESP-IDF version of an installation named with `--version-name`, e.g. a second copy of
+    /// v5.2 named `customer-a`; not set when the name is the version
+    #[serde(default, rename = "idfVersion", skip_serializing_if = "Option::is_none")]
+    pub idf_version: Option<String>,
+    /// Free-form note set with `eim label`, e.g. the product the installation is kept for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// An installation of `name` with the id eim gives it and no paths, to fill in where they matter.
+#[cfg(test)]
+pub(crate) fn test_installation(name: &str) -> IdfInstallation {
+    IdfInstallation {
+        activation_script: String::new(),
+        id: format!("esp-idf-{}", name),
+        idf_tools_path: String::new(),
+        name: name.to_string(),
+        path: String::new(),
+        python: String::new(),
+        idf_version: None,
+        label: None,
+    }
 }
 
 pub const IDF_CONFIG_FILE_NAME: &str = "eim_idf.json";
@@ -95,7 +118,15 @@ impl IdfConfig {
               .collect::<Vec<_>>();
             for install in self.idf_installed.iter() {
               if !merged_version.iter().any(|i| i.id == install.id) {
-                merged_version.push(install.clone());
+                let mut install = install.clone();
+                // a reinstallation into the same place keeps the label of the replaced one
+                if install.label.is_none() {
+                  install.label = existing_version
+                    .iter()
+                    .find(|i| i.path == install.path && i.idf_tools_path == install.idf_tools_path)
+                    .and_then(|i| i.label.clone());
+                }
+                merged_version.push(install);
               }
             }
             self.idf_installed = merged_version;
@@ -199,6 +230,26 @@ impl IdfConfig {
         }
     }
 
+    /// Sets or, with `None`, removes the label of an IDF installation.
+    ///
+    /// The installation is matched by ID or name, like in `update_installation_name`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if a matching installation was found.
+    pub fn update_installation_label(&mut self, identifier: &str, label: Option<String>) -> bool {
+        if let Some(installation) = self
+            .idf_installed
+            .iter_mut()
+            .find(|install| install.id == identifier || install.name == identifier)
+        {
+            installation.label = label.filter(|label| !label.trim().is_empty());
+            true
+        } else {
+            false
+        }
+    }
+
     /// Selects an IDF installation in the configuration.
     ///
     /// This function searches for an installation matching the given identifier
@@ -289,7 +340,8 @@ mod tests {
                     name: String::from("ESP-IDF v5.4"),
                     path: String::from("/tmp/esp-new/v5.4/esp-idf"),
                     python: String::from("/tmp/esp-new/v5.4/tools/python/bin/python3"),
-
+                    idf_version: None,
+                    label: None,
                 },
                 IdfInstallation {
                     activation_script: String::from("/tmp/esp-new/activate_idf_v5.1.5.sh"),
@@ -298,6 +350,8 @@ mod tests {
                     name: String::from("v5.1.5"),
                     path: String::from("/tmp/esp-new/v5.1.5/esp-idf"),
                     python: String::from("/tmp/esp-new/v5.1.5/tools/python/bin/python3"),
+                    idf_version: None,
+                    label: None,
                 },
             ],
             idf_selected_id: String::from("esp-idf-5705c12db93b4d1a8b084c6986173c1b"),
@@ -333,6 +387,20 @@ mod tests {
         assert!(!config.update_installation_name("non-existent", String::from("Invalid")));
     }
 
+    #[test]
+    fn test_update_installation_label() {
+        let mut config = create_test_config();
+
+        assert!(config.update_installation_label("v5.1.5", Some(String::from("customer-X firmware"))));
+        assert_eq!(config.idf_installed[1].label.as_deref(), Some("customer-X firmware"));
+
+        // An empty label removes it
+        assert!(config.update_installation_label("v5.1.5", Some(String::from("  "))));
+        assert_eq!(config.idf_installed[1].label, None);
+
+        assert!(!config.update_installation_label("non-existent", None));
+    }
+
     #[test]
     fn test_select_installation() {
         let mut config = create_test_config();
@@ -396,6 +464,8 @@ mod tests {
             name: String::from("ESP-IDF v5.1"),
             path: String::from("/esp/idf/v5.1.0"),
             python: String::from("/usr/bin/python3"),
+            idf_version: None,
+            label: None,
         };
 
         config.idf_installed = vec![new_installation.clone()];
@@ -486,6 +556,8 @@ fn test_append_with_same_path_replacement() -> Result<()> {
                 name: String::from("ESP-IDF v5.0 (Updated)"),
                 path: String::from("/tmp/esp-new/v5.1.5/esp-idf"), // Same path as the first installation in initial_config
                 python: String::from("/tmp/esp/v5.0/updated-tools/python/bin/python3"),
+                idf_version: None,
+                label: None,
             },
         ],
         idf_selected_id: String::from("esp-idf-new-id"),
@@ -65,7 +65,9 @@ impl RequirementsMetadata {
 
     /// Parse from a URL
     pub fn from_url(url: &str) -> Result<Self, ParseError> {
-        let response = reqwest::blocking::get(url)
+        let response = crate::http_client::blocking_client()
+            .get(url)
+            .send()
             .map_err(|e| ParseError::HttpError(e.to_string()))?;
 
         let text = response
@@ -77,7 +79,9 @@ impl RequirementsMetadata {
 
     /// Parse from a URL (async version)
     pub async fn from_url_async(url: &str) -> Result<Self, ParseError> {
-        let response = reqwest::get(url)
+        let response = crate::http_client::client()
+            .get(url)
+            .send()
             .await
             .map_err(|e| ParseError::HttpError(e.to_string()))?;
 
@@ -5,10 +5,14 @@ use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use anyhow::{anyhow, Result};
 
+use crate::errors::{EimError, ErrorKind};
 use crate::command_executor::{execute_command, execute_command_with_env};
-use crate::{decompress_archive, download_file, verify_file_checksum, DownloadProgress};
+use crate::{decompress_archive_with_progress, download_file, verify_file_checksum_with_progress, DownloadProgress};
+use crate::task_graph::TaskGraph;
+use crate::transaction::InstallTransaction;
 use crate::utils::{find_by_name_and_extension, find_directories_by_name, versions_match};
 
 #[derive(Deserialize, Debug, Clone)]
@@ -146,6 +150,135 @@ pub fn apply_platform_overrides(mut tools_file: ToolsFile, platform: &str) -> To
     tools_file
 }
 
+/// Finds the tool an override is meant for, by its name in tools.json or by a prefix only one
+/// tool starts with, e.g. `openocd` for `openocd-esp32`.
+fn overridden_tool_index(tools: &[Tool], name: &str) -> Result<usize> {
+    if let Some(index) = tools.iter().position(|t| t.name == name) {
+        return Ok(index);
+    }
+    let matching: Vec<usize> = (0..tools.len()).filter(|&index| tools[index].name.starts_with(name)).collect();
+    let names = |indexes: &[usize]| indexes.iter().map(|&index| tools[index].name.as_str()).collect::<Vec<_>>().join(", ");
+    match matching[..] {
+        [index] => Ok(index),
+        [] => Err(anyhow!(
+            "Cannot override version of unknown tool '{}', the tools in tools.json are: {}",
+            name,
+            names(&(0..tools.len()).collect::<Vec<_>>())
+        )),
+        _ => Err(anyhow!("'{}' matches several tools, override one of: {}", name, names(&matching))),
+    }
+}
+
+/// Pins individual tools to a version different from the one recommended by tools.json.
+///
+/// Each override maps a tool name to a version name, optionally followed by `@<sha256>`. The
+/// tool name may be shortened to a prefix matching a single tool, e.g. `openocd`.
+/// When the version is listed in tools.json it is simply marked as recommended. Otherwise
+/// the download URL is derived from the pinned version's URL by substituting the version
+/// string, and the sha256 is mandatory, as there is nothing else to verify the download against.
+///
+/// # Arguments
+///
+/// * `tools_file` - The parsed tools.json, with platform overrides already applied.
+/// * `overrides` - Map of tool name to `version` or `version@sha256`.
+///
+/// # Returns
+///
+/// * `Ok(ToolsFile)` - The tools file with the overridden versions marked as recommended.
+/// * `Err(anyhow::Error)` - If a tool is unknown or ambiguous, or a version not listed in tools.json lacks a checksum.
+pub fn apply_tool_version_overrides(
+    mut tools_file: ToolsFile,
+    overrides: &HashMap<String, String>,
+) -> Result<ToolsFile> {
+    if overrides.is_empty() {
+        return Ok(tools_file);
+    }
+    let platform = get_platform_identification().map_err(|e| anyhow!(e))?;
+
+    for (tool_name, value) in overrides {
+        let (version_name, sha256) = match value.split_once('@') {
+            Some((version, sha)) => (version.trim(), Some(sha.trim().to_lowercase())),
+            None => (value.trim(), None),
+        };
+        let index = overridden_tool_index(&tools_file.tools, tool_name)?;
+        let tool = &mut tools_file.tools[index];
+        let tool_name = tool.name.clone();
+        let pinned = tool
+            .versions
+            .iter()
+            .find(|v| v.status == "recommended")
+            .or_else(|| tool.versions.first())
+            .cloned()
+            .ok_or_else(|| anyhow!("Tool '{}' has no versions to override", tool_name))?;
+
+        if !tool.versions.iter().any(|v| v.name == version_name) {
+            let sha256 = sha256.clone().ok_or_else(|| {
+                anyhow!(
+                    "Version '{}' of tool '{}' is not listed in tools.json, provide its checksum as {}={}@<sha256>",
+                    version_name, tool_name, tool_name, version_name
+                )
+            })?;
+            let pinned_download = download_for_platform(&pinned, &platform)
+                .ok_or_else(|| anyhow!("Tool '{}' has no download for platform {}", tool_name, platform))?;
+            let url = pinned_download
+                .url
+                .replace(&pinned.name, version_name)
+                .replace(pinned.name.trim_start_matches('v'), version_name.trim_start_matches('v'));
+            let mut downloads = HashMap::new();
+            downloads.insert(
+                platform.clone(),
+                Download {
+                    sha256,
+                    size: 0,
+                    url,
+                    rename_dist: None,
+                },
+            );
+            tool.versions.push(Version {
+                name: version_name.to_string(),
+                status: "recommended".to_string(),
+                downloads,
+            });
+        } else if let Some(sha256) = sha256 {
+            // an explicit checksum always wins over the one in tools.json
+            for version in tool.versions.iter_mut().filter(|v| v.name == version_name) {
+                for download in version.downloads.values_mut() {
+                    download.sha256 = sha256.clone();
+                }
+            }
+        }
+
+        for version in tool.versions.iter_mut() {
+            if version.name == version_name {
+                version.status = "recommended".to_string();
+            } else if version.status == "recommended" {
+                version.status = "supported".to_string();
+            }
+        }
+        log::warn!(
+            "Tool '{}' is overridden to version '{}' instead of '{}' pinned by tools.json",
+            tool_name, version_name, pinned.name
+        );
+    }
+    Ok(tools_file)
+}
+
+/// Returns the warnings recorded in the install manifest for the given tool version overrides.
+pub fn tool_version_override_warnings(overrides: &HashMap<String, String>) -> Vec<String> {
+    let mut warnings: Vec<String> = overrides
+        .iter()
+        .map(|(tool_name, value)| {
+            let version_name = value.split('@').next().unwrap_or_default().trim();
+            format!(
+                "Tool '{}' is overridden to version '{}' instead of the version pinned by tools.json",
+                tool_name, version_name
+            )
+        })
+        .collect();
+    warnings.sort();
+    warnings
+}
+
 /// Filters a list of tools based on the given target platform.
 ///
 /// # Arguments
@@ -225,11 +358,17 @@ pub fn get_platform_identification() -> Result<String, String> {
     platform_from_name.insert("linux-x86", "linux-i686");
     platform_from_name.insert("linux-x86_64", "linux-amd64");
     platform_from_name.insert("linux-aarch64", "linux-arm64");
-    platform_from_name.insert("linux-arm", "linux-armel");
-
-    // Rust native FreeBSD identifiers
+    // Rust calls every 32-bit ARM "arm", the float ABI tells armhf (Raspberry Pi OS) from armel
+    platform_from_name.insert(
+        "linux-arm",
+        if cfg!(target_abi = "eabihf") { "linux-armhf" } else { "linux-armel" },
+    );
+
+    // Rust native FreeBSD identifiers, there are no FreeBSD builds and the Linux ones run
+    // under the Linux binary compatibility
     platform_from_name.insert("freebsd-x86_64", "linux-amd64");
     platform_from_name.insert("freebsd-x86", "linux-i686");
+    platform_from_name.insert("freebsd-aarch64", "linux-arm64");
 
     let platform_string = get_rust_platform_definition();
 
@@ -240,6 +379,33 @@ pub fn get_platform_identification() -> Result<String, String> {
     Ok(platform.to_string())
 }
 
+/// The tools.json platforms whose builds run on `platform`, best first.
+///
+/// Espressif publishes some tools for armel only, which armhf hosts run as well.
+pub fn compatible_platforms(platform: &str) -> Vec<&str> {
+    match platform {
+        "linux-armhf" => vec!["linux-armhf", "linux-armel"],
+        other => vec![other],
+    }
+}
+
+/// Tools which distributions package, installed from the system package manager on hosts
+/// Espressif publishes no build of them for: (tool, command it provides).
+pub const SYSTEM_PACKAGE_FALLBACKS: &[(&str, &str)] = &[
+    ("cmake", "cmake"),
+    ("ninja", "ninja"),
+    ("ccache", "ccache"),
+    ("dfu-util", "dfu-util"),
+];
+
+/// The download of `version` for `platform` or a platform compatible with it, or one for any platform.
+fn download_for_platform<'a>(version: &'a Version, platform: &str) -> Option<&'a Download> {
+    compatible_platforms(platform)
+        .into_iter()
+        .find_map(|p| version.downloads.get(p))
+        .or_else(|| version.downloads.get("any"))
+}
+
 /// Returns a string representing the current Rust platform in the format "os-arch".
 ///
 /// This function retrieves the operating system and architecture information
@@ -305,8 +471,13 @@ pub fn get_download_link_by_platform(
     } else {
       preferred_version = tool.versions.first().unwrap();
     }
-    if let Some(download) = preferred_version.downloads.get(platform).or_else(|| preferred_version.downloads.get("any")) {
+    if let Some(download) = download_for_platform(preferred_version, platform) {
       tool_links.insert(tool.name.clone(), (preferred_version.name.clone(), download.clone()));
+    } else if let Some((_, command)) = SYSTEM_PACKAGE_FALLBACKS.iter().find(|(name, _)| *name == tool.name) {
+      log::warn!(
+        "Tool {} has no build for platform {}, the {} of the system is used; install it with the package manager if it is missing",
+        tool.name, platform, command
+      );
     } else {
       log::warn!("Tool {} does not have a download link for platform {}", tool.name, platform);
     }
@@ -353,6 +524,68 @@ pub fn change_links_donwanload_mirror(
     new_tools
 }
 
+/// Rewrites the download links of tools according to a `<original prefix>=<new prefix>` rule.
+///
+/// This allows pointing eim at a private mirror (e.g. an internal Artifactory) which does not
+/// follow the GitHub URL layout expected by `change_links_donwanload_mirror`.
+///
+/// # Arguments
+///
+/// * `tools` - A HashMap of tool names to their version and Download instance.
+/// * `rewrite` - An optional rewrite rule. If `None`, the original URLs are used.
+///
+/// # Returns
+///
+/// * `Ok(HashMap)` with the rewritten URLs, or an error if the rule is malformed.
+pub fn apply_tools_url_rewrite(
+    tools: HashMap<String, (String, Download)>,
+    rewrite: Option<&str>,
+) -> Result<HashMap<String, (String, Download)>> {
+    let Some(rewrite) = rewrite.filter(|r| !r.is_empty()) else {
+        return Ok(tools);
+    };
+    let (from, to) = rewrite
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid tools URL rewrite '{}', expected <original prefix>=<new prefix>", rewrite))?;
+    Ok(tools
+        .into_iter()
+        .map(|(name, (version, mut link))| {
+            if let Some(rest) = link.url.strip_prefix(from) {
+                link.url = format!("{}{}", to, rest);
+            }
+            (name, (version, link))
+        })
+        .collect())
+}
+
+/// Resolves a custom tools manifest to a local file.
+///
+/// `manifest` can be either a local path or an http(s) URL. URLs are downloaded into `download_dir`.
+///
+/// # Returns
+///
+/// * `Ok(PathBuf)` - The path of the local tools.json file.
+/// * `Err(anyhow::Error)` - If the file does not exist, can not be downloaded or is not a valid tools.json.
+pub async fn resolve_tools_manifest(manifest: &str, download_dir: &Path) -> Result<PathBuf> {
+    let path = if manifest.starts_with("http://") || manifest.starts_with("https://") {
+        crate::ensure_path(download_dir.to_str().unwrap())?;
+        let file_name = "custom_tools.json";
+        crate::download_file_and_rename(manifest, download_dir.to_str().unwrap(), None, Some(file_name))
+            .await
+            .map_err(|e| anyhow!("Failed to download tools manifest {}: {}", manifest, e))?;
+        download_dir.join(file_name)
+    } else {
+        crate::expand_tilde(Path::new(manifest))
+    };
+    if !path.is_file() {
+        return Err(anyhow!("Tools manifest {} does not exist", path.display()));
+    }
+    read_and_parse_tools_file(path.to_str().unwrap())
+        .map_err(|e| anyhow!("Tools manifest {} is not valid: {}", path.display(), e))?;
+    log::info!("Using custom tools manifest {}", path.display());
+    Ok(path)
+}
+
 /// Retrieves a HashMap of tool names and their corresponding Download instances based on the given platform.
 ///
 /// # Parameters
@@ -539,7 +772,9 @@ pub fn find_bin_directories(path: &Path) -> Vec<String> {
 /// Sets up (downloads and installs) a list of selected tools based on their definitions.
 ///
 /// This asynchronous function orchestrates the entire setup process for a given set of tools.
-/// It first determines which tools need to be downloaded, then iteratively processes each tool:
+/// It first determines which tools need to be downloaded, then runs the steps below for each tool
+/// as tasks of a [`TaskGraph`]: the archives are downloaded one after another while the ones
+/// already downloaded are extracted in parallel, and the first failure stops all the others.
 ///
 /// 1. **Verifies Installation Status**: Checks if the tool is already installed correctly,
 ///    if a different version is present, or if it's missing. If already correct, it skips
@@ -563,6 +798,8 @@ pub fn find_bin_directories(path: &Path) -> Vec<String> {
 /// * `install_dir` - A `PathBuf` indicating the base directory where tools should be installed.
 /// * `mirror` - An `Option<&str>` specifying an optional mirror URL to use for downloads.
 ///              If `Some`, download URLs will be adjusted to use this mirror.
+/// * `url_rewrite` - An optional `<original prefix>=<new prefix>` rule applied to the download URLs
+///                   after the mirror, used for private mirrors with a custom layout.
 /// * `progress_callback` - A closure that implements `Fn(DownloadProgress) + Clone + Send + 'static`.
 ///                         This callback is invoked to report the progress and status of downloads
 ///                         and installations.
@@ -582,11 +819,26 @@ pub async fn setup_tools(
     download_dir: &PathBuf,
     install_dir: &PathBuf,
     mirror: Option<&str>,
+    url_rewrite: Option<&str>,
     progress_callback: impl Fn(DownloadProgress) + Clone + Send + 'static,
 ) -> anyhow::Result<HashMap<String, (String, Download)>> {
 
-    let download_links = get_list_of_tools_to_download(tools.clone(), selected_targets, mirror);
-    // Download each tool
+    crate::freebsd::check_linuxulator();
+    crate::shared_tools::check_writable(install_dir)?;
+    let download_links = apply_tools_url_rewrite(
+        get_list_of_tools_to_download(tools.clone(), selected_targets, mirror),
+        url_rewrite,
+    )?;
+    // Tools are extracted to staging folders and moved into place only when complete;
+    // if any tool fails, the already replaced ones are restored
+    let transaction = Arc::new(Mutex::new(InstallTransaction::new("tools setup")));
+    let tools_file = Arc::new(tools.clone());
+    let max_parallel = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
+    let mut graph = TaskGraph::new("tools setup", max_parallel);
+    // The progress events of a download carry no tool name, so the downloads run one after the
+    // other while the archives already downloaded are extracted
+    let mut previous_download: Option<String> = None;
+    let mut cached_archives = Vec::new();
     for (tool_name, (version, download_link)) in download_links.iter() {
       let file_path = Path::new(&download_link.url);
       let filename = file_path.file_name()
@@ -600,7 +852,7 @@ pub async fn setup_tools(
       match verify_tool_installation(tool_name, tools, install_dir, version) {
         Ok(ToolStatus::Correct { version }) => {
           progress_callback(DownloadProgress::Verified(download_link.url.clone()));
-          progress_callback(DownloadProgress::Complete);
+          progress_callback(DownloadProgress::Complete(tool_name.clone()));
           log::info!("Tool '{}' is already installed with the correct version: {}", tool_name, version);
           continue; // Skip if already installed correctly
         }
@@ -617,89 +869,422 @@ pub async fn setup_tools(
         }
       }
 
+      cached_archives.push((full_file_path.clone(), download_link.sha256.clone()));
+      let download_id = format!("download {}", tool_name);
+      let dependencies: Vec<&str> = previous_download.iter().map(String::as_str).collect();
+      graph.add_task(&download_id, &dependencies, {
+        let tool_name = tool_name.clone();
+        let url = download_link.url.clone();
+        let sha256 = download_link.sha256.clone();
+        let download_dir = download_dir.clone();
+        let full_file_path = full_file_path.clone();
+        let progress_callback = progress_callback.clone();
+        move || download_tool_archive(tool_name, url, sha256, download_dir, full_file_path, progress_callback)
+      });
+      graph.add_blocking_task(&format!("extract {}", tool_name), &[&download_id], {
+        let url = download_link.url.clone();
+        let tool_name = tool_name.clone();
+        let version = version.clone();
+        let install_dir = install_dir.clone();
+        let transaction = transaction.clone();
+        let tools_file = tools_file.clone();
+        let progress_callback = progress_callback.clone();
+        move |stop| {
+          // another user sharing the tools folder may install the same version at the same time
+          let _lock = crate::shared_tools::ToolLock::acquire(&this_install_dir)?;
+          if let Ok(ToolStatus::Correct { .. }) = verify_tool_installation(&tool_name, &tools_file, &install_dir, &version) {
+            log::info!("Tool '{}' {} was installed by another process meanwhile", tool_name, version);
+          } else {
+            extract_tool_staged(&transaction, &tools_file, &full_file_path, &tool_name, &this_install_dir, &progress_callback, stop)?;
+            if crate::shared_tools::is_shared(&install_dir) {
+              crate::shared_tools::normalize_permissions(&this_install_dir)?;
+            }
+          }
+          progress_callback(DownloadProgress::Extracted(tool_name.clone(), url, this_install_dir.to_str().unwrap().to_string()));
+          progress_callback(DownloadProgress::Complete(tool_name));
+          Ok(())
+        }
+      });
+      previous_download = Some(download_id);
+    }
 
+    verify_cached_archives(cached_archives, progress_callback.clone()).await?;
+    graph.run().await?;
+    let transaction = Arc::try_unwrap(transaction)
+        .map_err(|_| anyhow!("Tools setup finished with running tasks"))?
+        .into_inner()
+        .map_err(|_| anyhow!("A tools setup task panicked"))?;
+    transaction.commit();
+    Ok(download_links)
+}
 
+/// Downloads the archives of the tools for `selected_targets` into `download_dir` and verifies
+/// them, without extracting anything, so `setup_tools` can install them later without network.
+pub async fn download_tools(
+    tools: &ToolsFile,
+    selected_targets: Vec<String>,
+    download_dir: &PathBuf,
+    mirror: Option<&str>,
+    url_rewrite: Option<&str>,
+    progress_callback: impl Fn(DownloadProgress) + Clone + Send + 'static,
+) -> anyhow::Result<HashMap<String, (String, Download)>> {
+    let download_links = apply_tools_url_rewrite(
+        get_list_of_tools_to_download(tools.clone(), selected_targets, mirror),
+        url_rewrite,
+    )?;
+    let mut archives = Vec::new();
+    for (tool_name, (_, download_link)) in download_links.iter() {
+      let filename = Path::new(&download_link.url)
+          .file_name()
+          .and_then(|name| name.to_str())
+          .ok_or_else(|| anyhow::anyhow!("Invalid filename in URL {}", download_link.url))?;
+      archives.push((tool_name, download_link, download_dir.join(filename)));
+    }
+    verify_cached_archives(
+      archives.iter().map(|(_, link, path)| (path.clone(), link.sha256.clone())).collect(),
+      progress_callback.clone(),
+    )
+    .await?;
+    for (tool_name, download_link, full_file_path) in archives {
+      download_tool_archive(
+        tool_name.clone(),
+        download_link.url.clone(),
+        download_link.sha256.clone(),
+        download_dir.clone(),
+        full_file_path,
+        progress_callback.clone(),
+      )
+      .await?;
+      progress_callback(DownloadProgress::Complete(tool_name.clone()));
+    }
+    Ok(download_links)
+}
 
-      // Notify start of processing this tool
-      progress_callback(DownloadProgress::Start(download_link.url.clone()));
+/// Downloads a tool archive to `full_file_path` unless a file with the right checksum is
+/// already there, and verifies the checksum of the download.
+async fn download_tool_archive(
+    tool_name: String,
+    url: String,
+    sha256: String,
+    download_dir: PathBuf,
+    full_file_path: PathBuf,
+    progress_callback: impl Fn(DownloadProgress) + Clone + Send + 'static,
+) -> Result<()> {
+    // Notify start of processing this tool
+    progress_callback(DownloadProgress::Start(tool_name, url.clone()));
+
+    // Check if file already exists and has correct checksum
+    if let Ok(true) = verify_archive_checksum(&sha256, &full_file_path, progress_callback.clone()).await {
+      progress_callback(DownloadProgress::Verified(url));
+      return Ok(());
+    }
 
-      // Check if file already exists and has correct checksum
-      if let Ok(true) = verify_file_checksum(&download_link.sha256, full_file_path.to_str().unwrap()) {
-        progress_callback(DownloadProgress::Verified(download_link.url.clone()));
-        decompress_archive(full_file_path.to_str().unwrap(), this_install_dir.to_str().unwrap())?;
-        // this is fix for ninja not having `x` permission in zip archive
-        if tool_name.contains("ninja") {
-          match add_x_permission_to_tool(&this_install_dir, "ninja") {
-            Ok(_) => {
-              log::info!("Set executable permissions for ninja in {}", this_install_dir.display());
-            }
-            Err(e) => {
-              log::error!("Failed to set executable permissions for ninja: {}. Please set the `+x` permission manually.", e);
-            }
+    // a patch of the archive of a previous version of the tool, if the mirror has one
+    if crate::delta_download::try_patch(&url, &download_dir, &full_file_path).await {
+      if verify_archive_checksum(&sha256, &full_file_path, progress_callback.clone()).await? {
+        progress_callback(DownloadProgress::Downloaded(url.clone()));
+        progress_callback(DownloadProgress::Verified(url));
+        return Ok(());
+      }
+      log::warn!("The patched {} is corrupted, downloading the whole archive", full_file_path.display());
+      std::fs::remove_file(&full_file_path)?;
+    }
+
+    // Create a channel for progress updates
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    // Spawn a thread to forward progress updates to the callback
+    let callback = progress_callback.clone();
+    std::thread::spawn(move || {
+      while let Ok(progress) = rx.recv() {
+        match progress {
+          DownloadProgress::Progress(current, total) => {
+            callback(DownloadProgress::Progress(current, total));
+          }
+          DownloadProgress::Downloaded(url) => {
+            callback(DownloadProgress::Downloaded(url));
+          }
+          DownloadProgress::Error(e) => {
+            callback(DownloadProgress::Error(e));
           }
+          _ => {}
         }
-        progress_callback(DownloadProgress::Extracted(download_link.url.clone(), this_install_dir.to_str().unwrap().to_string()));
-        progress_callback(DownloadProgress::Complete);
-        continue;
       }
+    });
 
-      // Create a channel for progress updates
-      let (tx, rx) = std::sync::mpsc::channel();
-
-      // Spawn a thread to forward progress updates to the callback
-      let callback = progress_callback.clone();
-      let url = download_link.url.clone();
-      std::thread::spawn(move || {
-        while let Ok(progress) = rx.recv() {
-          match progress {
-            DownloadProgress::Progress(current, total) => {
-              callback(DownloadProgress::Progress(current, total));
-            }
-            DownloadProgress::Complete => {
-              callback(DownloadProgress::Downloaded(url.clone()));
-            }
-            DownloadProgress::Error(e) => {
-              callback(DownloadProgress::Error(e));
-            }
-            _ => {}
-          }
+    // Download the file
+    if let Err(e) = download_file(&url, download_dir.to_str().unwrap(), Some(tx)).await {
+      progress_callback(DownloadProgress::Error(e.to_string()));
+      return Err(EimError::new(ErrorKind::Network, format!("Download failed: {}", e)).into());
+    }
+    // Verify downloaded file
+    if verify_archive_checksum(&sha256, &full_file_path, progress_callback.clone()).await? {
+      progress_callback(DownloadProgress::Verified(url));
+      Ok(())
+    } else {
+      // Remove corrupted file
+      std::fs::remove_file(&full_file_path)?;
+      Err(EimError::new(
+        ErrorKind::Checksum,
+        format!("Downloaded file {} is corrupted", full_file_path.display()),
+      )
+      .into())
+    }
+}
+
+/// Hashes the archives left in the download folder by an earlier run all at once, before the
+/// downloads start, so each download finds its archive verified instead of hashing it alone.
+/// The archives that do not match are removed to be downloaded again.
+async fn verify_cached_archives(
+    archives: Vec<(PathBuf, String)>,
+    progress_callback: impl Fn(DownloadProgress) + Send + 'static,
+) -> Result<()> {
+    let archives: Vec<(PathBuf, String)> = archives
+        .into_iter()
+        .filter(|(path, sha256)| path.exists() && !crate::checksum::is_verified(path, sha256))
+        .collect();
+    if archives.is_empty() {
+      return Ok(());
+    }
+    tokio::task::spawn_blocking(move || {
+      let progress_callback = Mutex::new(progress_callback);
+      let results = crate::checksum::verify_files_parallel(&archives, |hashed, total| {
+        if let Ok(callback) = progress_callback.lock() {
+          callback(DownloadProgress::Hashing(hashed, total));
         }
       });
+      for ((path, _), verified) in archives.iter().zip(results) {
+        if !verified {
+          log::warn!("The cached {} is corrupted, downloading it again", path.display());
+          std::fs::remove_file(path)?;
+        }
+      }
+      Ok::<(), std::io::Error>(())
+    })
+    .await
+    .map_err(|e| anyhow!("Checksum verification panicked: {}", e))?
+    .map_err(Into::into)
+}
+
+/// Hashes an archive on the blocking thread pool, reporting the progress as `Hashing` events.
+///
+/// An archive verified before and unchanged since is not hashed again, unless paranoid mode is on.
+async fn verify_archive_checksum(
+    sha256: &str,
+    path: &Path,
+    progress_callback: impl Fn(DownloadProgress) + Send + 'static,
+) -> Result<bool> {
+    if crate::checksum::is_verified(path, sha256) {
+      return Ok(true);
+    }
+    let sha256 = sha256.to_string();
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+      let report_hashing = move |hashed, total| progress_callback(DownloadProgress::Hashing(hashed, total));
+      let verified = verify_file_checksum_with_progress(&sha256, path.to_str().unwrap(), &report_hashing)?;
+      if verified {
+        crate::checksum::record_verified(&path, &sha256);
+      }
+      Ok::<bool, std::io::Error>(verified)
+    })
+    .await
+    .map_err(|e| anyhow!("Checksum verification panicked: {}", e))?
+    .map_err(Into::into)
+}
 
-      // Download the file
-      match download_file(&download_link.url, download_dir.to_str().unwrap(), Some(tx)).await {
+/// Extracts a tool archive into a staging folder and moves it to `this_install_dir`
+/// as part of `transaction`.
+fn extract_tool_staged(
+    transaction: &Mutex<InstallTransaction>,
+    tools: &ToolsFile,
+    archive: &Path,
+    tool_name: &str,
+    this_install_dir: &PathBuf,
+    progress_callback: &impl Fn(DownloadProgress),
+    stop: &std::sync::atomic::AtomicBool,
+) -> Result<()> {
+    // the transaction is locked only to register the folders, tools are extracted in parallel
+    let staging = transaction.lock().unwrap().stage_dir(this_install_dir)?;
+    // reported once per percent, the reader is called for every few kilobytes
+    let last_percent = std::cell::Cell::new(u64::MAX);
+    let report_extraction = |read: u64, size: u64| {
+      let percent = if size == 0 { 100 } else { read * 100 / size };
+      if percent != last_percent.replace(percent) {
+        progress_callback(DownloadProgress::Extracting(read, size));
+      }
+    };
+    decompress_archive_with_progress(archive.to_str().unwrap(), staging.to_str().unwrap(), &report_extraction, stop)?;
+    // this is fix for ninja not having `x` permission in zip archive
+    if tool_name.contains("ninja") {
+      match add_x_permission_to_tool(&staging, "ninja") {
         Ok(_) => {
-          // Verify downloaded file
-          if verify_file_checksum(&download_link.sha256, full_file_path.to_str().unwrap())? {
-            progress_callback(DownloadProgress::Verified(download_link.url.clone()));
-            // Extract the archive
-
-            decompress_archive(full_file_path.to_str().unwrap(), this_install_dir.to_str().unwrap())?;
-            // this is fix for ninja not having `x` permission in zip archive
-            match add_x_permission_to_tool(&this_install_dir, "ninja") {
-              Ok(_) => {
-                log::info!("Set executable permissions for ninja in {}", this_install_dir.display());
-              }
-              Err(e) => {
-                log::error!("Failed to set executable permissions for ninja: {}. Please set the `+x` permission manually.", e);
-              }
-            }
-            progress_callback(DownloadProgress::Extracted(download_link.url.clone(), this_install_dir.to_str().unwrap().to_string()));
-            progress_callback(DownloadProgress::Complete);
-          } else {
-            // Remove corrupted file
-            std::fs::remove_file(&full_file_path)?;
-            return Err(anyhow::anyhow!("Downloaded file is corrupted"));
-          }
+          log::info!("Set executable permissions for ninja in {}", this_install_dir.display());
         }
         Err(e) => {
-          progress_callback(DownloadProgress::Error(e.to_string()));
-          return Err(anyhow::anyhow!("Download failed: {}", e));
+          log::error!("Failed to set executable permissions for ninja: {}. Please set the `+x` permission manually.", e);
         }
       }
     }
+    transaction.lock().unwrap().commit_dir(&staging, this_install_dir)?;
+    if let Some(tool) = tools.tools.iter().find(|t| t.name == tool_name) {
+        smoke_test_tool(tool, this_install_dir)?;
+    }
+    Ok(())
+}
 
-    Ok(download_links)
+/// Runs the version command of a freshly installed tool to make sure it can execute on this machine.
+///
+/// A tool which fails to start (missing shared libraries, wrong architecture, macOS quarantine,
+/// missing `x` permission) makes the whole installation fail with a message naming the cause,
+/// instead of surfacing later as an obscure build error.
+///
+/// # Arguments
+///
+/// * `tool` - The tool definition from tools.json.
+/// * `tool_dir` - The folder the tool version was extracted to, e.g. `<tools>/openocd-esp32/v0.12.0`.
+///
+/// # Returns
+///
+/// * `Ok(())` if the tool has no version command or the command executed successfully.
+/// * `Err(anyhow::Error)` describing why the tool can not execute.
+pub fn smoke_test_tool(tool: &Tool, tool_dir: &Path) -> Result<()> {
+    let Some(executable_name) = tool.version_cmd.first().filter(|c| !c.is_empty()) else {
+        return Ok(());
+    };
+    let args: Vec<&str> = tool.version_cmd.iter().skip(1).map(|a| a.as_str()).collect();
+
+    let mut candidates = vec![tool_dir.to_path_buf()];
+    for export_path in &tool.export_paths {
+        let mut dir = tool_dir.to_path_buf();
+        for level in export_path {
+            dir.push(level);
+        }
+        candidates.push(dir);
+    }
+    let executable = candidates.iter().find_map(|dir| {
+        let exe = dir.join(format!("{}.exe", executable_name));
+        let plain = dir.join(executable_name);
+        if std::env::consts::OS == "windows" && exe.is_file() {
+            Some(exe)
+        } else if plain.is_file() {
+            Some(plain)
+        } else {
+            None
+        }
+    });
+    let Some(executable) = executable else {
+        return Err(anyhow!(
+            "Smoke test of '{}' failed: executable '{}' not found in {}",
+            tool.name,
+            executable_name,
+            tool_dir.display()
+        ));
+    };
+
+    debug!("Smoke testing {} {}", executable.display(), args.join(" "));
+    match execute_command(&executable.to_string_lossy(), &args) {
+        Ok(output) => {
+            let combined_output = format!(
+                "{}\n{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            // some tools return non-zero exit code for the version command, accept them if the version is printed
+            let version_printed = Regex::new(&tool.version_regex)
+                .map(|re| re.is_match(&combined_output))
+                .unwrap_or(false);
+            if output.status.success() || version_printed {
+                log::info!("Smoke test of '{}' passed", tool.name);
+                Ok(())
+            } else {
+                Err(anyhow!(
+                    "Smoke test of '{}' failed: `{} {}` exited with {}: {}",
+                    tool.name,
+                    executable.display(),
+                    args.join(" "),
+                    output.status,
+                    diagnose_execution_failure(&executable, Some(&combined_output), None)
+                ))
+            }
+        }
+        Err(err) => Err(anyhow!(
+            "Smoke test of '{}' failed: `{}` could not be executed: {}",
+            tool.name,
+            executable.display(),
+            diagnose_execution_failure(&executable, None, Some(&err))
+        )),
+    }
+}
+
+/// Maps the `e_machine` field of an ELF header to the matching `std::env::consts::ARCH` value.
+fn elf_machine_to_arch(machine: u16) -> Option<&'static str> {
+    match machine {
+        0x03 => Some("x86"),
+        0x28 => Some("arm"),
+        0x3E => Some("x86_64"),
+        0xB7 => Some("aarch64"),
+        0xF3 => Some("riscv64"),
+        _ => None,
+    }
+}
+
+/// Reads the target architecture of an ELF binary, if `path` is one.
+fn read_elf_arch(path: &Path) -> Option<&'static str> {
+    let mut header = [0u8; 20];
+    let mut file = File::open(path).ok()?;
+    file.read_exact(&mut header).ok()?;
+    if &header[0..4] != b"\x7fELF" {
+        return None;
+    }
+    let machine = if header[5] == 2 {
+        u16::from_be_bytes([header[18], header[19]])
+    } else {
+        u16::from_le_bytes([header[18], header[19]])
+    };
+    elf_machine_to_arch(machine)
+}
+
+/// Tries to find out why a tool failed to execute, returning a human readable cause.
+fn diagnose_execution_failure(executable: &Path, output: Option<&str>, error: Option<&std::io::Error>) -> String {
+    if std::env::consts::OS == "macos" {
+        let quarantined = execute_command("xattr", &["-p", "com.apple.quarantine", &executable.to_string_lossy()])
+            .map(|o| o.status.success())
+            .unwrap_or(false);
+        if quarantined {
+            return format!(
+                "the binary is quarantined by macOS Gatekeeper, remove the attribute with `xattr -dr com.apple.quarantine {}`",
+                executable.parent().unwrap_or(executable).display()
+            );
+        }
+    }
+    if let Some(arch) = read_elf_arch(executable) {
+        if arch != std::env::consts::ARCH {
+            return format!(
+                "the binary is built for {} but this machine is {}",
+                arch,
+                std::env::consts::ARCH
+            );
+        }
+    }
+    if let Some(output) = output {
+        if let Some(line) = output
+            .lines()
+            .find(|l| l.contains("error while loading shared libraries") || l.contains("GLIBC_") || l.contains("Library not loaded"))
+        {
+            return format!("a required system library is missing or too old: {}", line.trim());
+        }
+        return output.trim().to_string();
+    }
+    match error {
+        Some(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+            "the binary is not executable (missing `x` permission)".to_string()
+        }
+        Some(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            "the binary exists but its interpreter or dynamic loader is missing (e.g. incompatible libc)".to_string()
+        }
+        Some(err) => err.to_string(),
+        None => "unknown reason".to_string(),
+    }
 }
 
 /// Adds execute (x) permission to the specified tool within the installation directory.
@@ -893,6 +1478,60 @@ pub fn verify_tool_installation(tool_name: &str, tools_file: &ToolsFile, install
 
 }
 
+/// Name of the ccache entry in `tools.json`
+pub const CCACHE_TOOL: &str = "ccache";
+
+/// Keeps only the ccache entry of the tools file.
+pub fn only_ccache(mut tools_file: ToolsFile) -> ToolsFile {
+    tools_file.tools.retain(|tool| tool.name == CCACHE_TOOL);
+    tools_file
+}
+
+/// Makes ccache available to IDF builds and returns the environment entries enabling it
+/// together with the paths to add to `PATH`.
+///
+/// ccache is installed from the `ccache` entry of `tools_json` into `tools_install_dir`, like
+/// the other tools. When `tools.json` has no ccache build for this platform, the one from the
+/// system package manager is used and installed if missing.
+pub async fn setup_ccache(
+    tools_json: &Path,
+    download_dir: &Path,
+    tools_install_dir: &Path,
+    mirror: Option<&str>,
+    url_rewrite: Option<&str>,
+) -> Result<(Vec<(String, String)>, Vec<String>)> {
+    let tools_file = read_and_parse_tools_file(tools_json.to_str().unwrap())
+        .map_err(|e| anyhow!("Failed to read {}: {}", tools_json.display(), e))?;
+    let ccache = only_ccache(tools_file);
+    let enable = vec![("IDF_CCACHE_ENABLE".to_string(), "1".to_string())];
+    if get_list_of_tools_to_download(ccache.clone(), vec!["all".to_string()], None).is_empty() {
+        let found = execute_command("ccache", &["--version"])
+            .map(|output| output.status.success())
+            .unwrap_or(false);
+        if !found {
+            log::info!("tools.json has no ccache for this platform, installing it with the system package manager");
+            crate::system_dependencies::install_prerequisites(vec!["ccache".to_string()])
+                .map_err(|e| anyhow!("Failed to install ccache: {}", e))?;
+        }
+        return Ok((enable, vec![]));
+    }
+
+    std::fs::create_dir_all(download_dir)?;
+    let installed = setup_tools(
+        &ccache,
+        vec!["all".to_string()],
+        &download_dir.to_path_buf(),
+        &tools_install_dir.to_path_buf(),
+        mirror,
+        url_rewrite,
+        |_| {},
+    )
+    .await?;
+    debug!("Using ccache from {}", tools_install_dir.join(CCACHE_TOOL).display());
+    let export_paths = get_tools_export_paths_from_list(ccache, installed, tools_install_dir.to_str().unwrap());
+    Ok((enable, export_paths))
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -1065,6 +1704,30 @@ mod tests {
         println!("Detected platform: {:?}", result);
     }
 
+    #[test]
+    fn test_armhf_falls_back_to_armel() {
+        let mut downloads = HashMap::new();
+        downloads.insert(
+            "linux-armel".to_string(),
+            Download {
+                sha256: "abc".to_string(),
+                size: 1,
+                url: "https://example.com/tool-armel.tar.gz".to_string(),
+                rename_dist: None,
+            },
+        );
+        let version = Version {
+            name: "1.0".to_string(),
+            status: "recommended".to_string(),
+            downloads,
+        };
+        assert_eq!(
+            download_for_platform(&version, "linux-armhf").map(|d| d.url.as_str()),
+            Some("https://example.com/tool-armel.tar.gz")
+        );
+        assert!(download_for_platform(&version, "linux-arm64").is_none());
+    }
+
     #[test]
     fn test_rust_platform_definition() {
         let platform_def = get_rust_platform_definition();
@@ -1133,4 +1796,158 @@ mod tests {
         assert_eq!(result_first.tools[0].install, result_second.tools[0].install);
         assert_eq!(result_first.tools[0].export_paths, result_second.tools[0].export_paths);
     }
+
+    #[test]
+    fn test_read_elf_arch() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let binary = temp_dir.path().join("gcc");
+        let mut header = vec![0x7f, b'E', b'L', b'F', 2, 1];
+        header.resize(18, 0);
+        header.extend_from_slice(&0xB7u16.to_le_bytes());
+        std::fs::write(&binary, &header).unwrap();
+        assert_eq!(read_elf_arch(&binary), Some("aarch64"));
+
+        let script = temp_dir.path().join("script");
+        std::fs::write(&script, "#!/bin/sh\necho 1.0.0\n").unwrap();
+        assert_eq!(read_elf_arch(&script), None);
+    }
+
+    #[test]
+    fn test_smoke_test_tool_missing_executable() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut tool = Tool {
+            description: "Test tool".to_string(),
+            export_paths: vec![vec!["bin".to_string()]],
+            export_vars: HashMap::new(),
+            info_url: "https://example.com".to_string(),
+            install: "always".to_string(),
+            license: None,
+            name: "test-tool".to_string(),
+            platform_overrides: None,
+            supported_targets: None,
+            strip_container_dirs: None,
+            version_cmd: vec!["test-tool".to_string(), "--version".to_string()],
+            version_regex: "version ([0-9.]+)".to_string(),
+            version_regex_replace: None,
+            versions: vec![],
+        };
+
+        let err = smoke_test_tool(&tool, temp_dir.path()).unwrap_err();
+        assert!(err.to_string().contains("not found"));
+
+        // tools without a version command are not tested
+        tool.version_cmd = vec![];
+        assert!(smoke_test_tool(&tool, temp_dir.path()).is_ok());
+    }
+
+    #[test]
+    fn test_apply_tools_url_rewrite() {
+        let mut tools = HashMap::new();
+        tools.insert(
+            "openocd".to_string(),
+            (
+                "v0.12.0".to_string(),
+                Download {
+                    sha256: "abc".to_string(),
+                    size: 1,
+                    url: "https://github.com/espressif/openocd-esp32/releases/download/v0.12.0/openocd.tar.gz".to_string(),
+                    rename_dist: None,
+                },
+            ),
+        );
+
+        let result = apply_tools_url_rewrite(
+            tools.clone(),
+            Some("https://github.com/espressif=https://artifactory.example.com/esp"),
+        )
+        .unwrap();
+        assert_eq!(
+            result["openocd"].1.url,
+            "https://artifactory.example.com/esp/openocd-esp32/releases/download/v0.12.0/openocd.tar.gz"
+        );
+
+        let unchanged = apply_tools_url_rewrite(tools.clone(), None).unwrap();
+        assert_eq!(unchanged["openocd"].1.url, tools["openocd"].1.url);
+
+        assert!(apply_tools_url_rewrite(tools, Some("no-separator")).is_err());
+    }
+
+    #[test]
+    fn test_apply_tool_version_overrides() {
+        let platform = get_platform_identification().unwrap();
+        let mut downloads = HashMap::new();
+        downloads.insert(
+            platform.clone(),
+            Download {
+                sha256: "pinned".to_string(),
+                size: 1,
+                url: "https://github.com/espressif/openocd-esp32/releases/download/v0.12.0-esp32-20240318/openocd-esp32-0.12.0-esp32-20240318.tar.gz".to_string(),
+                rename_dist: None,
+            },
+        );
+        let tools_file = ToolsFile {
+            tools: vec![Tool {
+                description: "OpenOCD".to_string(),
+                export_paths: vec![vec!["openocd-esp32".to_string(), "bin".to_string()]],
+                export_vars: HashMap::new(),
+                info_url: "https://example.com".to_string(),
+                install: "always".to_string(),
+                license: None,
+                name: "openocd-esp32".to_string(),
+                platform_overrides: None,
+                supported_targets: None,
+                strip_container_dirs: None,
+                version_cmd: vec![],
+                version_regex: "".to_string(),
+                version_regex_replace: None,
+                versions: vec![Version {
+                    name: "v0.12.0-esp32-20240318".to_string(),
+                    status: "recommended".to_string(),
+                    downloads,
+                }],
+            }],
+            version: 3,
+        };
+
+        let mut overrides = HashMap::new();
+        overrides.insert("openocd-esp32".to_string(), "v0.12.0-esp32-20240821".to_string());
+        assert!(apply_tool_version_overrides(tools_file.clone(), &overrides).is_err());
+
+        overrides.insert("openocd-esp32".to_string(), "v0.12.0-esp32-20240821@ABC".to_string());
+        let result = apply_tool_version_overrides(tools_file, &overrides).unwrap();
+        let links = get_download_link_by_platform(result.tools, &platform);
+        let (version, download) = &links["openocd-esp32"];
+        assert_eq!(version, "v0.12.0-esp32-20240821");
+        assert_eq!(download.sha256, "abc");
+        assert!(download.url.ends_with("v0.12.0-esp32-20240821/openocd-esp32-0.12.0-esp32-20240821.tar.gz"));
+        assert_eq!(tool_version_override_warnings(&overrides).len(), 1);
+    }
+
+    #[test]
+    fn test_overridden_tool_by_prefix() {
+        let tool = |name: &str| Tool {
+            description: String::new(),
+            export_paths: vec![],
+            export_vars: HashMap::new(),
+            info_url: String::new(),
+            install: "always".to_string(),
+            license: None,
+            name: name.to_string(),
+            platform_overrides: None,
+            supported_targets: None,
+            strip_container_dirs: None,
+            version_cmd: vec![],
+            version_regex: String::new(),
+            version_regex_replace: None,
+            versions: vec![],
+        };
+        let tools = vec![tool("xtensa-esp-elf"), tool("xtensa-esp-elf-gdb"), tool("openocd-esp32")];
+        assert_eq!(overridden_tool_index(&tools, "openocd").unwrap(), 2);
+        // an exact name wins over the longer names it is a prefix of
+        assert_eq!(overridden_tool_index(&tools, "xtensa-esp-elf").unwrap(), 0);
+        let ambiguous = overridden_tool_index(&tools, "xtensa").unwrap_err().to_string();
+        assert!(ambiguous.contains("xtensa-esp-elf, xtensa-esp-elf-gdb"));
+        let unknown = overridden_tool_index(&tools, "cmake").unwrap_err().to_string();
+        assert!(unknown.contains("openocd-esp32"));
+    }
 }
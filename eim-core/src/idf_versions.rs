@@ -1,4 +1,4 @@
-use log::{error, warn};
+use log::{error, info, warn};
 use serde_derive::Deserialize;
 use std::collections::HashMap;
 
@@ -109,6 +109,122 @@ pub async fn get_latest_idf_version(include_prerelease: bool) -> Result<Option<V
     Ok(latest_version)
 }
 
+/// Symbolic versions resolved against the release metadata at install time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionAlias {
+    /// The newest release, including pre-releases
+    Latest,
+    /// The newest release which is not a pre-release
+    Stable,
+    /// The newest release of the supported release series with the longest support period
+    Lts,
+}
+
+pub const VERSION_ALIASES: &[VersionAlias] = &[VersionAlias::Latest, VersionAlias::Stable, VersionAlias::Lts];
+
+impl VersionAlias {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VersionAlias::Latest => "latest",
+            VersionAlias::Stable => "stable",
+            VersionAlias::Lts => "lts",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        VERSION_ALIASES
+            .iter()
+            .copied()
+            .find(|alias| alias.name() == name.trim().to_lowercase())
+    }
+}
+
+/// Parses ESP-IDF version names, which may leave out the patch version (`v6.0-beta1`).
+pub(crate) fn parse_version_name(name: &str) -> Option<semver::Version> {
+    let name = name.strip_prefix('v').unwrap_or(name);
+    let (core, pre) = match name.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (name, None),
+    };
+    let mut core = core.to_string();
+    for _ in core.matches('.').count()..2 {
+        core.push_str(".0");
+    }
+    let full = match pre {
+        Some(pre) => format!("{}-{}", core, pre),
+        None => core,
+    };
+    semver::Version::parse(&full).ok()
+}
+
+/// Release series of a version as used as key of `RELEASES`, e.g. `v5.3` for `v5.3.2`
+pub(crate) fn release_series(version: &semver::Version) -> String {
+    format!("v{}.{}", version.major, version.minor)
+}
+
+pub(crate) fn parse_release_date(date: &str) -> Option<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Resolves `alias` to a version name of `releases`, with `today` deciding which release series are still supported.
+pub fn resolve_alias_in(releases: &Releases, alias: VersionAlias, today: chrono::NaiveDate) -> Option<String> {
+    let candidates = releases
+        .VERSIONS
+        .iter()
+        .filter(|v| !v.end_of_life && !v.old && v.name != "latest")
+        .filter(|v| alias == VersionAlias::Latest || !v.pre_release)
+        .filter_map(|v| parse_version_name(&v.name).map(|parsed| (parsed, v.name.clone())));
+    let candidates: Vec<(semver::Version, String)> = match alias {
+        VersionAlias::Latest | VersionAlias::Stable => candidates.collect(),
+        VersionAlias::Lts => {
+            // support period of each release series still supported today
+            let support_period = |series: &str| {
+                let release = releases.RELEASES.get(series)?;
+                let start = parse_release_date(&release.start_date)?;
+                let end = parse_release_date(&release.end_date)?;
+                (start <= today && today <= end).then(|| end - start)
+            };
+            let candidates: Vec<_> = candidates
+                .filter_map(|(parsed, name)| {
+                    support_period(&release_series(&parsed)).map(|period| (period, parsed, name))
+                })
+                .collect();
+            let longest = candidates.iter().map(|(period, _, _)| *period).max()?;
+            candidates
+                .into_iter()
+                .filter(|(period, _, _)| *period == longest)
+                .map(|(_, parsed, name)| (parsed, name))
+                .collect()
+        }
+    };
+    candidates.into_iter().max_by(|a, b| a.0.cmp(&b.0)).map(|(_, name)| name)
+}
+
+/// Resolves a version alias to the version it currently stands for.
+pub async fn resolve_alias(alias: VersionAlias) -> Result<String, String> {
+    let releases = get_idf_versions().await?;
+    resolve_alias_in(&releases, alias, chrono::Utc::now().date_naive())
+        .ok_or_else(|| format!("No ESP-IDF release matches the version alias '{}'", alias.name()))
+}
+
+/// Replaces the aliases (`latest`, `stable`, `lts`) among `versions` with the versions they stand for.
+pub async fn resolve_version_aliases(versions: Vec<String>) -> Result<Vec<String>, String> {
+    let mut resolved = Vec::with_capacity(versions.len());
+    for version in versions {
+        match VersionAlias::from_name(&version) {
+            Some(alias) => {
+                let name = resolve_alias(alias).await?;
+                info!("Version alias '{}' resolved to {}", alias.name(), name);
+                if !resolved.contains(&name) {
+                    resolved.push(name);
+                }
+            }
+            None => resolved.push(version),
+        }
+    }
+    Ok(resolved)
+}
+
 /// Retrieves the available IDF targets from the official website.
 ///
 /// This function fetches the IDF versions from the official website, extracts the available targets,
@@ -140,6 +256,9 @@ pub async fn get_avalible_targets() -> Result<Vec<String>, String> {
 
 /// This function downloads the IDF versions from the official website.
 ///
+/// The list goes through the metadata cache, a copy younger than `metadata_cache_ttl` is used
+/// without downloading it again.
+///
 /// # Returns
 ///
 /// * A Result containing a `Releases` struct if the download and parsing are successful.
@@ -147,16 +266,14 @@ pub async fn get_avalible_targets() -> Result<Vec<String>, String> {
 ///
 /// # Errors
 ///
-/// * If there is an error during the HTTP request, the error is returned as a `reqwest::Error`.
+/// * If the HTTP request fails and there is no recent cached copy, the error is returned as an `anyhow::Error`.
 /// * If there is an error during the JSON deserialization, the error is returned as a `serde_json::Error`.
 ///
 pub async fn download_idf_versions() -> Result<Releases, Box<dyn std::error::Error>> {
     let url = IDF_VERSIONS_URL.to_string();
-    let client = reqwest::Client::builder()
-        .user_agent("esp-idf-installer")
-        .build()?;
-    let response = client.get(&url).send().await?;
-    let json_versions_file = response.text().await?;
+    let client = crate::http_client::client_builder().build()?;
+    let request = crate::http_client::authorize(client.get(&url), &url);
+    let json_versions_file = crate::metadata_cache::fetch_text(&url, request).await?;
     let versions: Releases = serde_json::from_str(&json_versions_file)?;
 
     Ok(versions)
@@ -330,4 +447,40 @@ mod tests {
         assert_eq!(versions_by_target.get("esp32").unwrap().len(), 2);
         assert_eq!(versions_by_target.get("esp32s2").unwrap().len(), 1);
     }
+
+    #[test]
+    fn test_resolve_version_aliases() {
+        let version = |name: &str, pre_release: bool| Version {
+            name: name.to_string(),
+            pre_release,
+            old: false,
+            end_of_life: false,
+            has_targets: false,
+            supported_targets: vec![],
+        };
+        let release = |start: &str, end: &str| Release {
+            start_date: start.to_string(),
+            end_date: end.to_string(),
+        };
+        let releases = Releases {
+            VERSIONS: vec![
+                version("latest", false),
+                version("v6.0-beta1", true),
+                version("v5.5.1", false),
+                version("v5.4.2", false),
+                version("v5.4.1", false),
+            ],
+            IDF_TARGETS: vec![],
+            RELEASES: HashMap::from([
+                ("v5.5".to_string(), release("2025-07-01", "2027-07-01")),
+                ("v5.4".to_string(), release("2024-12-01", "2028-06-01")),
+            ]),
+        };
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 10, 1).unwrap();
+        let resolve = |alias| resolve_alias_in(&releases, alias, today);
+        assert_eq!(resolve(VersionAlias::Latest).as_deref(), Some("v6.0-beta1"));
+        assert_eq!(resolve(VersionAlias::Stable).as_deref(), Some("v5.5.1"));
+        assert_eq!(resolve(VersionAlias::Lts).as_deref(), Some("v5.4.2"));
+        assert_eq!(VersionAlias::from_name("LTS"), Some(VersionAlias::Lts));
+    }
 }
@@ -0,0 +1,303 @@
+use chrono::Utc;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::settings::Settings;
+
+pub const LOCK_FILE_NAME: &str = "eim.lock";
+pub const LOCK_OWNER_FILE_NAME: &str = "eim.lock.owner";
+/// Set by the GUI for the `eim install` it spawns, which then works under the lock of the GUI
+/// instead of waiting for it forever
+pub const LOCK_TOKEN_ENV: &str = "EIM_INSTALL_LOCK_TOKEN";
+
+/// Information about the process currently holding the installer lock.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LockHolder {
+    pub pid: u32,
+    pub command: String,
+    pub started: String,
+    /// Secret of this acquisition, handed to child processes through [`LOCK_TOKEN_ENV`]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub token: String,
+}
+
+impl std::fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PID {} ({}), started at {}",
+            self.pid,
+            self.command,
+            crate::display_format::format_timestamp(&self.started)
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LockError {
+    #[error("Another eim process is modifying the installations: {0}")]
+    Held(LockHolder),
+    #[error("Timed out waiting for the installer lock held by {0}")]
+    Timeout(LockHolder),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Advisory lock guarding every operation that mutates the shared tools directory
+/// or `eim_idf.json`. The lock is released when the guard is dropped.
+///
+/// The OS file lock is released automatically if the process dies. Because file locks
+/// are unreliable on some network filesystems, the owner file is additionally checked
+/// for a live PID before the lock is considered free.
+#[derive(Debug)]
+pub struct InstallLock {
+    /// `None` for a lock inherited from the parent process, which releases it
+    file: Option<File>,
+    owner_path: PathBuf,
+    token: String,
+}
+
+fn get_lock_directory() -> PathBuf {
+    PathBuf::from(Settings::default().esp_idf_json_path.unwrap_or_default())
+}
+
+fn read_lock_holder_in(directory: &Path) -> Option<LockHolder> {
+    let content = fs::read_to_string(directory.join(LOCK_OWNER_FILE_NAME)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Reads the owner of the installer lock, if any.
+pub fn read_lock_holder() -> Option<LockHolder> {
+    read_lock_holder_in(&get_lock_directory())
+}
+
+/// Checks whether a process with the given PID is still running.
+pub fn is_process_alive(pid: u32) -> bool {
+    match std::env::consts::OS {
+        "linux" => PathBuf::from(format!("/proc/{}", pid)).exists(),
+        "windows" => crate::command_executor::execute_command(
+            "tasklist",
+            &["/FI", &format!("PID eq {}", pid), "/NH"],
+        )
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+        .unwrap_or(true),
+        _ => crate::command_executor::execute_command("kill", &["-0", &pid.to_string()])
+            .map(|output| output.status.success())
+            .unwrap_or(true),
+    }
+}
+
+/// Removes the owner file left by a process which died holding the lock, e.g. after a crash on a
+/// network filesystem where the OS lock is not released. Returns the holder it removed.
+///
+/// A lock held by a running process, this one included, is left alone and reported as
+/// [`LockError::Held`].
+pub fn release_stale() -> Result<Option<LockHolder>, LockError> {
+    let Some(holder) = read_lock_holder() else {
+        return Ok(None);
+    };
+    if is_process_alive(holder.pid) {
+        return Err(LockError::Held(holder));
+    }
+    fs::remove_file(get_lock_directory().join(LOCK_OWNER_FILE_NAME))?;
+    warn!("Released the stale installer lock of {}", holder);
+    Ok(Some(holder))
+}
+
+impl InstallLock {
+    /// Tries to acquire the lock without waiting.
+    ///
+    /// A process started by the holder with its token in [`LOCK_TOKEN_ENV`] works under the
+    /// lock of the holder, which stays responsible for releasing it.
+    pub fn try_acquire(command: &str) -> Result<Self, LockError> {
+        Self::try_acquire_in(&get_lock_directory(), command)
+    }
+
+    fn try_acquire_in(directory: &Path, command: &str) -> Result<Self, LockError> {
+        fs::create_dir_all(directory)?;
+        let owner_path = directory.join(LOCK_OWNER_FILE_NAME);
+        if let Ok(token) = std::env::var(LOCK_TOKEN_ENV) {
+            match read_lock_holder_in(directory) {
+                Some(holder) if !token.is_empty() && holder.token == token && is_process_alive(holder.pid) => {
+                    debug!("Working under the installer lock of {}", holder);
+                    return Ok(Self {
+                        file: None,
+                        owner_path,
+                        token,
+                    });
+                }
+                _ => debug!("The inherited installer lock is no longer held, acquiring it"),
+            }
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(directory.join(LOCK_FILE_NAME))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => match read_lock_holder_in(directory) {
+                // the OS lock outlived its owner, e.g. on a network filesystem
+                Some(holder) if holder.pid != 0 && !is_process_alive(holder.pid) => {
+                    warn!("Taking over the installer lock of {}, the process is gone", holder);
+                }
+                holder => {
+                    return Err(LockError::Held(holder.unwrap_or(LockHolder {
+                        pid: 0,
+                        command: "unknown".to_string(),
+                        started: "unknown".to_string(),
+                        token: String::new(),
+                    })));
+                }
+            },
+            Err(TryLockError::Error(err)) if err.kind() == std::io::ErrorKind::Unsupported => {
+                debug!("File locking is not supported here, relying on PID check only");
+            }
+            Err(TryLockError::Error(err)) => return Err(LockError::Io(err)),
+        }
+
+        if let Some(holder) = read_lock_holder_in(directory) {
+            if holder.pid != std::process::id() && is_process_alive(holder.pid) {
+                let _ = file.unlock();
+                return Err(LockError::Held(holder));
+            }
+            if holder.pid != std::process::id() {
+                warn!("Removing stale installer lock left by {}", holder);
+            }
+        }
+
+        let holder = LockHolder {
+            pid: std::process::id(),
+            command: command.to_string(),
+            started: Utc::now().to_rfc3339(),
+            token: uuid::Uuid::new_v4().simple().to_string(),
+        };
+        let mut owner_file = File::create(&owner_path)?;
+        owner_file.write_all(serde_json::to_string(&holder).unwrap_or_default().as_bytes())?;
+        debug!("Acquired installer lock for '{}'", command);
+
+        Ok(Self {
+            file: Some(file),
+            owner_path,
+            token: holder.token,
+        })
+    }
+
+    /// The token to pass in [`LOCK_TOKEN_ENV`] to a child process which works under this lock.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Acquires the lock, polling until it becomes free or `timeout` elapses.
+    ///
+    /// `on_wait` is called once with the current holder when the lock is busy,
+    /// so callers can tell the user who they are waiting for.
+    pub async fn acquire<F>(command: &str, timeout: Option<Duration>, mut on_wait: F) -> Result<Self, LockError>
+    where
+        F: FnMut(&LockHolder),
+    {
+        let start = Instant::now();
+        let mut notified = false;
+        loop {
+            match Self::try_acquire(command) {
+                Ok(lock) => return Ok(lock),
+                Err(LockError::Held(holder)) => {
+                    if !notified {
+                        on_wait(&holder);
+                        notified = true;
+                    }
+                    if timeout.map_or(false, |t| start.elapsed() >= t) {
+                        return Err(LockError::Timeout(holder));
+                    }
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Drop for InstallLock {
+    fn drop(&mut self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let owned = fs::read_to_string(&self.owner_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<LockHolder>(&content).ok())
+            .is_some_and(|holder| holder.pid == std::process::id() && holder.token == self.token);
+        if owned {
+            let _ = fs::remove_file(&self.owner_path);
+        }
+        let _ = file.unlock();
+        debug!("Released installer lock");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// PID of a process which already exited
+    fn dead_pid() -> u32 {
+        let mut child = std::process::Command::new(std::env::current_exe().unwrap())
+            .arg("--list")
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        pid
+    }
+
+    fn write_holder(directory: &Path, pid: u32) {
+        let holder = LockHolder {
+            pid,
+            command: "eim install".to_string(),
+            started: Utc::now().to_rfc3339(),
+            token: "stale".to_string(),
+        };
+        fs::write(directory.join(LOCK_OWNER_FILE_NAME), serde_json::to_string(&holder).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_contention() {
+        let dir = TempDir::new().unwrap();
+        let lock = InstallLock::try_acquire_in(dir.path(), "eim install").unwrap();
+        match InstallLock::try_acquire_in(dir.path(), "eim fix") {
+            Err(LockError::Held(holder)) => {
+                assert_eq!(holder.pid, std::process::id());
+                assert_eq!(holder.command, "eim install");
+            }
+            other => panic!("expected the lock to be held, got {:?}", other),
+        }
+        drop(lock);
+        assert!(read_lock_holder_in(dir.path()).is_none());
+        assert!(InstallLock::try_acquire_in(dir.path(), "eim fix").is_ok());
+    }
+
+    #[test]
+    fn test_stale_owner_is_taken_over() {
+        let dir = TempDir::new().unwrap();
+        let pid = dead_pid();
+        write_holder(dir.path(), pid);
+        let lock = InstallLock::try_acquire_in(dir.path(), "eim install").unwrap();
+        assert_eq!(read_lock_holder_in(dir.path()).unwrap().pid, std::process::id());
+
+        // the OS lock is still held, but the recorded owner is gone
+        write_holder(dir.path(), pid);
+        let taken_over = InstallLock::try_acquire_in(dir.path(), "eim fix").unwrap();
+        assert_eq!(read_lock_holder_in(dir.path()).unwrap().command, "eim fix");
+        drop(taken_over);
+        drop(lock);
+    }
+}
@@ -0,0 +1,707 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::components::Component;
+use crate::settings::{Settings, VersionPaths};
+use crate::system_tools::SystemTool;
+use crate::{ensure_path, run_powershell_script};
+
+pub const MANIFEST_FORMAT_VERSION: &str = "1.0";
+pub const MANIFESTS_FOLDER_NAME: &str = "manifests";
+
+/// Registry key of the environment variables of the user
+pub const USER_ENVIRONMENT_KEY: &str = "HKCU:\\Environment";
+
+/// A single modification made to the system by an installation.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ManifestEntry {
+    File { path: String },
+    Directory { path: String },
+    Symlink { path: String, target: String },
+    /// A value under a Windows registry key, e.g. `HKCU:\Environment` / `IDF_TOOLS_PATH`. With
+    /// `entry`, only that entry was added to a `;` separated list such as `Path`
+    RegistryValue {
+        key: String,
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        entry: Option<String>,
+    },
+    /// A line appended to a shell rc file or a similar user configuration file, or a block of
+    /// lines such as the `Initialize-Idf` block of the PowerShell profiles
+    RcFileLine { path: String, line: String },
+    /// A profile added to the Windows Terminal settings.json
+    WindowsTerminalProfile { idf_version: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InstallManifest {
+    pub version: String,
+    pub idf_version: String,
+    pub idf_path: String,
+    pub created: String,
+    pub entries: Vec<ManifestEntry>,
+    /// Deviations from a standard installation, e.g. tools overridden to a version not pinned by tools.json
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Optional components intentionally left out, so a repair does not add them back
+    #[serde(default)]
+    pub omitted_components: Vec<String>,
+    /// Settings the installation was made with, to install the next version the same way
+    #[serde(default)]
+    pub settings: Option<RecordedSettings>,
+    /// Tools of the system used instead of the ones eim installs, re-checked by `eim status`
+    #[serde(default)]
+    pub system_tools: Vec<SystemTool>,
+    /// `entries` as a set, recording a whole ESP-IDF checkout checks hundreds of thousands
+    #[serde(skip)]
+    index: HashSet<ManifestEntry>,
+}
+
+/// Changes to shell rc files and the registry made while installing, before the manifest they
+/// belong to is written.
+static PENDING_CHANGES: Lazy<Mutex<Vec<ManifestEntry>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Records a change to a shell rc file or the registry made by the running installation, for the
+/// manifest of the version written next.
+pub fn record_system_change(entry: ManifestEntry) {
+    if let Ok(mut pending) = PENDING_CHANGES.lock() {
+        pending.push(entry);
+    }
+}
+
+fn take_pending_changes() -> Vec<ManifestEntry> {
+    PENDING_CHANGES.lock().map(|mut pending| std::mem::take(&mut *pending)).unwrap_or_default()
+}
+
+/// Adds the changes recorded since the last manifest was written to the manifest of the
+/// installation at `idf_path`, for changes made once all versions are installed.
+pub fn record_pending_changes(idf_path: &str) -> Result<()> {
+    let pending = take_pending_changes();
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let mut manifest = load_manifest_for(idf_path)
+        .ok_or_else(|| anyhow!("No install manifest recorded for {}", idf_path))?;
+    for entry in pending {
+        manifest.push(entry);
+    }
+    manifest.to_file(get_manifest_path(idf_path))
+}
+
+/// The paths present before an installation started, so its manifest claims only what the
+/// installation created and never a file of the user or of another installation.
+#[derive(Debug, Default)]
+pub struct PreInstallSnapshot {
+    existing: HashSet<PathBuf>,
+}
+
+impl PreInstallSnapshot {
+    /// Lists everything below the folders the manifest of `paths` is recorded from.
+    pub fn take(paths: &VersionPaths) -> Self {
+        let mut snapshot = Self::default();
+        for root in recorded_roots(paths) {
+            snapshot.add_tree(root);
+        }
+        snapshot
+    }
+
+    fn add_tree(&mut self, path: &Path) {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return;
+        };
+        self.existing.insert(path.to_path_buf());
+        if metadata.is_dir() {
+            for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+                self.add_tree(&entry.path());
+            }
+        }
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.existing.contains(path)
+    }
+}
+
+/// The folders whose content is recorded in the manifest of a version.
+///
+/// An existing ESP-IDF checkout belongs to the user, only the tool folders eim populated are
+/// recorded for it. For a new installation it is the version folder.
+fn recorded_roots(paths: &VersionPaths) -> Vec<&Path> {
+    if paths.using_existing_idf {
+        vec![paths.tool_install_directory.as_path(), paths.tool_download_directory.as_path()]
+    } else {
+        vec![paths.version_installation_path.as_path()]
+    }
+}
+
+/// Whether reverting `entry` affects all installations, not only the one which made it.
+fn is_shared(entry: &ManifestEntry) -> bool {
+    matches!(entry, ManifestEntry::RcFileLine { .. } | ManifestEntry::RegistryValue { .. })
+}
+
+/// The part of the settings worth carrying over to the installation of another version.
+///
+/// Versions, names and anything tied to a single installation are left out.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct RecordedSettings {
+    pub path: Option<PathBuf>,
+    pub target: Option<Vec<String>>,
+    pub idf_mirror: Option<String>,
+    pub mirror: Option<String>,
+    pub pypi_mirror: Option<String>,
+    pub tools_manifest: Option<String>,
+    pub tools_url_rewrite: Option<String>,
+    pub idf_features: Option<Vec<String>>,
+    pub components: Option<Vec<String>>,
+    pub recurse_submodules: Option<bool>,
+    pub tool_download_folder_name: Option<String>,
+    pub tool_install_folder_name: Option<String>,
+    pub python_env_folder_name: Option<String>,
+    pub enable_ccache: Option<bool>,
+    pub rust_toolchain: Option<bool>,
+    pub install_qemu: Option<bool>,
+    pub component_registry_url: Option<String>,
+    pub component_storage_url: Option<String>,
+    pub use_system_tools: Option<Vec<String>>,
+    pub url_rewrites: Option<Vec<crate::url_rewrite::UrlRewriteRule>>,
+}
+
+macro_rules! recorded_fields {
+    ($macro:ident) => {
+        $macro!(
+            path,
+            target,
+            idf_mirror,
+            mirror,
+            pypi_mirror,
+            tools_manifest,
+            tools_url_rewrite,
+            idf_features,
+            components,
+            recurse_submodules,
+            tool_download_folder_name,
+            tool_install_folder_name,
+            python_env_folder_name,
+            enable_ccache,
+            rust_toolchain,
+            install_qemu,
+            component_registry_url,
+            component_storage_url,
+            use_system_tools,
+            url_rewrites
+        )
+    };
+}
+
+impl RecordedSettings {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let mut recorded = Self::default();
+        macro_rules! copy {
+            ($($field:ident),*) => {
+                $(recorded.$field = settings.$field.clone();)*
+            };
+        }
+        recorded_fields!(copy);
+        recorded
+    }
+
+    /// Copies the recorded values into `settings`, values which were not recorded are kept.
+    pub fn apply_to(&self, settings: &mut Settings) {
+        macro_rules! apply {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field.is_some() {
+                        settings.$field = self.$field.clone();
+                    }
+                )*
+            };
+        }
+        recorded_fields!(apply);
+    }
+}
+
+/// Returns the directory where the install manifests are stored.
+pub fn get_manifests_directory() -> PathBuf {
+    PathBuf::from(Settings::default().esp_idf_json_path.unwrap_or_default()).join(MANIFESTS_FOLDER_NAME)
+}
+
+/// Returns the manifest path for an installation.
+///
+/// Manifests are keyed by the IDF path rather than the version name, so renaming
+/// an installation does not orphan its manifest.
+pub fn get_manifest_path(idf_path: &str) -> PathBuf {
+    let normalized = match std::env::consts::OS {
+        "windows" => idf_path.to_lowercase(),
+        _ => idf_path.to_string(),
+    };
+    let hash = format!("{:x}", Sha256::digest(normalized.as_bytes()));
+    get_manifests_directory().join(format!("{}.json", &hash[..16]))
+}
+
+impl InstallManifest {
+    pub fn new(idf_version: &str, idf_path: &str) -> Self {
+        Self {
+            version: MANIFEST_FORMAT_VERSION.to_string(),
+            idf_version: idf_version.to_string(),
+            idf_path: idf_path.to_string(),
+            created: Utc::now().to_rfc3339(),
+            entries: Vec::new(),
+            warnings: Vec::new(),
+            omitted_components: Vec::new(),
+            settings: None,
+            system_tools: Vec::new(),
+            index: HashSet::new(),
+        }
+    }
+
+    fn push(&mut self, entry: ManifestEntry) {
+        // a manifest read from a file comes without its index
+        if self.index.len() != self.entries.len() {
+            self.index = self.entries.iter().cloned().collect();
+        }
+        if self.index.insert(entry.clone()) {
+            self.entries.push(entry);
+        }
+    }
+
+    pub fn record_file<P: AsRef<Path>>(&mut self, path: P) {
+        self.push(ManifestEntry::File {
+            path: path.as_ref().to_string_lossy().into_owned(),
+        });
+    }
+
+    pub fn record_directory<P: AsRef<Path>>(&mut self, path: P) {
+        self.push(ManifestEntry::Directory {
+            path: path.as_ref().to_string_lossy().into_owned(),
+        });
+    }
+
+    pub fn record_rc_line<P: AsRef<Path>>(&mut self, path: P, line: &str) {
+        self.push(ManifestEntry::RcFileLine {
+            path: path.as_ref().to_string_lossy().into_owned(),
+            line: line.to_string(),
+        });
+    }
+
+    pub fn record_registry_value(&mut self, key: &str, name: &str) {
+        self.push(ManifestEntry::RegistryValue {
+            key: key.to_string(),
+            name: name.to_string(),
+            entry: None,
+        });
+    }
+
+    /// Recursively records every file, directory and symlink below (and including) `root`.
+    ///
+    /// Symlinks are recorded but not followed.
+    pub fn record_tree<P: AsRef<Path>>(&mut self, root: P) -> Result<()> {
+        let root = root.as_ref();
+        let metadata = fs::symlink_metadata(root)?;
+        if metadata.file_type().is_symlink() {
+            let target = fs::read_link(root)?;
+            self.push(ManifestEntry::Symlink {
+                path: root.to_string_lossy().into_owned(),
+                target: target.to_string_lossy().into_owned(),
+            });
+        } else if metadata.is_dir() {
+            self.record_directory(root);
+            for entry in fs::read_dir(root)? {
+                self.record_tree(entry?.path())?;
+            }
+        } else {
+            self.record_file(root);
+        }
+        Ok(())
+    }
+
+    /// Records what was created below (and including) `root` since `snapshot` was taken.
+    ///
+    /// Folders which existed before are walked but not recorded themselves.
+    pub fn record_created<P: AsRef<Path>>(&mut self, root: P, snapshot: &PreInstallSnapshot) -> Result<()> {
+        let root = root.as_ref();
+        if !snapshot.contains(root) {
+            return self.record_tree(root);
+        }
+        let metadata = fs::symlink_metadata(root)?;
+        if metadata.is_dir() && !metadata.file_type().is_symlink() {
+            for entry in fs::read_dir(root)? {
+                self.record_created(entry?.path(), snapshot)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            ensure_path(parent.to_str().unwrap_or_default())?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Reverts every recorded modification.
+    ///
+    /// Files and symlinks are removed, directories only when they are empty afterwards,
+    /// so anything the user put inside an installation folder is left untouched.
+    /// Returns the list of paths which were kept because they were not created by eim.
+    pub fn uninstall(&self) -> Result<Vec<String>> {
+        let mut kept = Vec::new();
+        let mut directories = Vec::new();
+
+        for entry in &self.entries {
+            match entry {
+                ManifestEntry::File { path } | ManifestEntry::Symlink { path, .. } => {
+                    let path = Path::new(path);
+                    if fs::symlink_metadata(path).is_ok() {
+                        #[cfg(windows)]
+                        if let Ok(metadata) = fs::metadata(path) {
+                            let mut permissions = metadata.permissions();
+                            permissions.set_readonly(false);
+                            let _ = fs::set_permissions(path, permissions);
+                        }
+                        if let Err(err) = fs::remove_file(path).or_else(|_| fs::remove_dir(path)) {
+                            warn!("Failed to remove {}: {}", path.display(), err);
+                        }
+                    }
+                }
+                ManifestEntry::Directory { path } => directories.push(PathBuf::from(path)),
+                ManifestEntry::RcFileLine { path, line } => {
+                    if let Err(err) = remove_line_from_file(Path::new(path), line) {
+                        warn!("Failed to revert modification of {}: {}", path, err);
+                    }
+                }
+                ManifestEntry::RegistryValue { key, name, entry } => {
+                    if std::env::consts::OS == "windows" {
+                        let quote = |value: &str| value.replace('\'', "''");
+                        let script = match entry {
+                            Some(entry) => format!(
+                                "$value = (Get-ItemProperty -Path '{key}' -Name '{name}' -ErrorAction SilentlyContinue).'{name}'; \
+                                 if ($value) {{ Set-ItemProperty -Path '{key}' -Name '{name}' -Value \
+                                 (($value -split ';' | Where-Object {{ $_ -and $_ -ne '{entry}' }}) -join ';') }}",
+                                key = quote(key),
+                                name = quote(name),
+                                entry = quote(entry)
+                            ),
+                            None => format!(
+                                "Remove-ItemProperty -Path '{}' -Name '{}' -ErrorAction SilentlyContinue",
+                                quote(key),
+                                quote(name)
+                            ),
+                        };
+                        if let Err(err) = run_powershell_script(&script) {
+                            warn!("Failed to remove registry value {}\\{}: {}", key, name, err);
+                        }
+                    }
+                }
+                ManifestEntry::WindowsTerminalProfile { idf_version } => {
+                    if std::env::consts::OS == "windows" {
+                        match crate::remove_windows_terminal_profile(idf_version) {
+                            Ok(msg) => debug!("{}", msg),
+                            Err(err) => warn!("Failed to remove Windows Terminal profile: {}", err),
+                        }
+                    }
+                }
+            }
+        }
+
+        // Deepest directories first, so parents are empty by the time we reach them
+        directories.sort_by_key(|d| std::cmp::Reverse(d.components().count()));
+        for directory in directories {
+            if !directory.exists() {
+                continue;
+            }
+            if let Err(err) = fs::remove_dir(&directory) {
+                debug!("Keeping directory {}: {}", directory.display(), err);
+                kept.push(directory.to_string_lossy().into_owned());
+            }
+        }
+        Ok(kept)
+    }
+}
+
+fn remove_line_from_file(path: &Path, line: &str) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let content = fs::read_to_string(path)?;
+    if line.contains('\n') {
+        // a block, removed as a whole
+        let block = line.trim_end();
+        if let Some(start) = content.find(block) {
+            let rest = content[start + block.len()..].trim_start_matches(['\r', '\n']);
+            fs::write(path, format!("{}{}", &content[..start], rest))?;
+        }
+        return Ok(());
+    }
+    let filtered: Vec<&str> = content.lines().filter(|l| l.trim_end() != line.trim_end()).collect();
+    let mut new_content = filtered.join("\n");
+    if content.ends_with('\n') {
+        new_content.push('\n');
+    }
+    fs::write(path, new_content)?;
+    Ok(())
+}
+
+/// Builds and saves the manifest of a freshly installed version.
+///
+/// For a new installation the version folder is recorded. When installing into an existing
+/// ESP-IDF checkout only the tool folders eim populated are recorded, as the repository itself
+/// belongs to the user. Either way only what did not exist when `snapshot` was taken is
+/// recorded. `warnings` are stored in the manifest as they are.
+pub fn record_version_manifest(
+    paths: &VersionPaths,
+    snapshot: &PreInstallSnapshot,
+    settings: &Settings,
+    warnings: &[String],
+    omitted_components: &[Component],
+) -> Result<PathBuf> {
+    let idf_path = paths.idf_path.to_string_lossy().into_owned();
+    let idf_version = if paths.using_existing_idf { &paths.actual_version } else { &paths.idf_version };
+    let mut manifest = InstallManifest::new(idf_version, &idf_path);
+    manifest.warnings = warnings.to_vec();
+    manifest.omitted_components = omitted_components.iter().map(|c| c.name().to_string()).collect();
+    manifest.settings = Some(RecordedSettings::from_settings(settings));
+    manifest.system_tools = crate::system_tools::selected(settings);
+    for entry in take_pending_changes() {
+        manifest.push(entry);
+    }
+
+    for root in recorded_roots(paths) {
+        if root.exists() {
+            manifest.record_created(root, snapshot)?;
+        }
+    }
+
+    if paths.activation_script.exists() {
+        manifest.record_file(&paths.activation_script);
+    }
+    // only the snippet of a system-wide install pointing at this very installation
+    let profile_script = crate::system_install::profile_script_path(&paths.actual_version);
+    if std::fs::read_to_string(&profile_script)
+        .is_ok_and(|content| content.contains(&*paths.activation_script.to_string_lossy()))
+    {
+        manifest.record_file(profile_script);
+    }
+
+    if std::env::consts::OS == "windows" {
+        let activation_script = paths.activation_script.to_string_lossy();
+        match crate::version_manager::find_shortcut_by_profile(&activation_script) {
+            Ok(Some(shortcut)) => {
+                if let Some(desktop) = dirs::desktop_dir() {
+                    manifest.record_file(desktop.join(shortcut));
+                }
+            }
+            Ok(None) => {}
+            Err(err) => warn!("Failed to look up desktop shortcut: {}", err),
+        }
+        if crate::is_windows_terminal_profile_installed(&paths.actual_version).unwrap_or(false) {
+            manifest.push(ManifestEntry::WindowsTerminalProfile {
+                idf_version: paths.actual_version.clone(),
+            });
+        }
+    }
+
+    let manifest_path = get_manifest_path(&idf_path);
+    manifest.to_file(&manifest_path)?;
+    info!(
+        "Install manifest with {} entries written to {}",
+        manifest.entries.len(),
+        manifest_path.display()
+    );
+    Ok(manifest_path)
+}
+
+/// Loads the manifest for the installation at `idf_path`, if one was recorded.
+pub fn load_manifest_for(idf_path: &str) -> Option<InstallManifest> {
+    let manifest_path = get_manifest_path(idf_path);
+    if !manifest_path.exists() {
+        return None;
+    }
+    match InstallManifest::from_file(&manifest_path) {
+        Ok(manifest) => Some(manifest),
+        Err(err) => {
+            warn!("Failed to read install manifest {}: {}", manifest_path.display(), err);
+            None
+        }
+    }
+}
+
+/// Returns the settings recorded by an installation, looked up by its name or id.
+pub fn recorded_settings_for(identifier: &str) -> Result<RecordedSettings> {
+    let installation = crate::version_manager::find_installation(Some(identifier))?;
+    load_manifest_for(&installation.path)
+        .and_then(|manifest| manifest.settings)
+        .ok_or_else(|| anyhow!("Installation {} has no recorded settings, it was installed by an older eim", identifier))
+}
+
+/// Uninstalls an installation using its manifest and deletes the manifest afterwards.
+///
+/// Changes to shell rc files and the registry serve every installation, e.g. a prerequisite
+/// added to the PATH. While other installations with a manifest remain, they are handed over
+/// to one of them instead of being reverted, so the last installation removed reverts them.
+pub fn uninstall_using_manifest(idf_path: &str) -> Result<Vec<String>> {
+    let mut manifest = load_manifest_for(idf_path)
+        .ok_or_else(|| anyhow!("No install manifest recorded for {}", idf_path))?;
+    let heir = crate::version_manager::get_esp_ide_config()
+        .map(|config| config.idf_installed)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|installation| installation.path)
+        .find(|path| path != idf_path && get_manifest_path(path).is_file());
+    if let Some(heir_path) = heir {
+        let (shared, own): (Vec<_>, Vec<_>) = manifest.entries.drain(..).partition(is_shared);
+        manifest.entries = own;
+        if !shared.is_empty() {
+            if let Some(mut heir_manifest) = load_manifest_for(&heir_path) {
+                for entry in shared {
+                    heir_manifest.push(entry);
+                }
+                heir_manifest.to_file(get_manifest_path(&heir_path))?;
+                debug!("Handed the shell and registry changes of {} over to {}", idf_path, heir_path);
+            }
+        }
+    }
+    let kept = manifest.uninstall()?;
+    fs::remove_file(get_manifest_path(idf_path))?;
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_uninstall_keeps_user_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("v5.3");
+        fs::create_dir_all(root.join("tools").join("bin")).unwrap();
+        fs::write(root.join("tools").join("bin").join("gcc"), "binary").unwrap();
+
+        let mut manifest = InstallManifest::new("v5.3", root.to_str().unwrap());
+        manifest.record_tree(&root).unwrap();
+
+        // created by the user after the installation
+        fs::write(root.join("tools").join("notes.txt"), "mine").unwrap();
+
+        let kept = manifest.uninstall().unwrap();
+        assert!(!root.join("tools").join("bin").exists());
+        assert!(root.join("tools").join("notes.txt").exists());
+        assert_eq!(kept.len(), 2);
+    }
+
+    #[test]
+    fn test_uninstall_keeps_files_present_before_install() {
+        let temp_dir = TempDir::new().unwrap();
+        let tools = temp_dir.path().join("tools");
+        let other_version = tools.join("xtensa-esp-elf").join("esp-13.2.0");
+        fs::create_dir_all(&other_version).unwrap();
+        fs::write(other_version.join("gcc"), "binary").unwrap();
+        fs::write(tools.join("notes.txt"), "mine").unwrap();
+        let paths = VersionPaths {
+            idf_path: temp_dir.path().join("esp-idf"),
+            version_installation_path: temp_dir.path().join("esp-idf"),
+            tool_download_directory: temp_dir.path().join("dist"),
+            tool_install_directory: tools.clone(),
+            python_venv_path: tools.join("python").join("v5.3").join("venv"),
+            python_path: tools.join("python").join("v5.3").join("venv").join("bin").join("python"),
+            activation_script: temp_dir.path().join("activate_idf_v5.3.sh"),
+            activation_script_path: temp_dir.path().to_path_buf(),
+            actual_version: "v5.3".to_string(),
+            idf_version: "v5.3".to_string(),
+            using_existing_idf: true,
+        };
+        let snapshot = PreInstallSnapshot::take(&paths);
+
+        let new_tool = tools.join("xtensa-esp-elf").join("esp-14.2.0");
+        fs::create_dir_all(&new_tool).unwrap();
+        fs::write(new_tool.join("gcc"), "binary").unwrap();
+        fs::create_dir_all(&paths.python_venv_path).unwrap();
+        let mut manifest = InstallManifest::new("v5.3", paths.idf_path.to_str().unwrap());
+        for root in recorded_roots(&paths) {
+            if root.exists() {
+                manifest.record_created(root, &snapshot).unwrap();
+            }
+        }
+
+        manifest.uninstall().unwrap();
+        assert!(!new_tool.exists());
+        assert!(!tools.join("python").exists());
+        assert!(other_version.join("gcc").exists());
+        assert!(tools.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_rc_line_removal() {
+        let temp_dir = TempDir::new().unwrap();
+        let rc = temp_dir.path().join(".bashrc");
+        fs::write(&rc, "export A=1\nsource /opt/activate_idf_v5.3.sh\nexport B=2\n").unwrap();
+
+        let mut manifest = InstallManifest::new("v5.3", "/opt/esp-idf");
+        manifest.record_rc_line(&rc, "source /opt/activate_idf_v5.3.sh");
+        manifest.uninstall().unwrap();
+
+        assert_eq!(fs::read_to_string(&rc).unwrap(), "export A=1\nexport B=2\n");
+    }
+
+    #[test]
+    fn test_entries_are_deduplicated() {
+        let mut manifest = InstallManifest::new("v5.3", "/opt/esp-idf");
+        manifest.record_file("/opt/a");
+        manifest.record_file("/opt/a");
+        assert_eq!(manifest.entries.len(), 1);
+
+        // also when read back, without the index
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("manifest.json");
+        manifest.to_file(&path).unwrap();
+        let mut manifest = InstallManifest::from_file(&path).unwrap();
+        manifest.record_file("/opt/a");
+        manifest.record_file("/opt/b");
+        assert_eq!(manifest.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_rc_block_removal() {
+        let temp_dir = TempDir::new().unwrap();
+        let profile = temp_dir.path().join("Microsoft.PowerShell_profile.ps1");
+        let block = "# >>> eim Initialize-Idf >>>\r\nfunction Initialize-Idf {}\r\n# <<< eim Initialize-Idf <<<";
+        fs::write(&profile, format!("Import-Module posh-git\r\n{}\r\nSet-Alias g git\r\n", block)).unwrap();
+
+        let mut manifest = InstallManifest::new("v5.3", "C:\\esp\\v5.3\\esp-idf");
+        manifest.record_rc_line(&profile, block);
+        manifest.uninstall().unwrap();
+
+        assert_eq!(fs::read_to_string(&profile).unwrap(), "Import-Module posh-git\r\nSet-Alias g git\r\n");
+    }
+
+    #[test]
+    fn test_recorded_settings_keep_unrecorded_values() {
+        let mut original = Settings::default();
+        original.target = Some(vec!["esp32s3".to_string()]);
+        original.idf_mirror = Some("https://gitee.com/EspressifSystems/esp-idf".to_string());
+        original.idf_versions = Some(vec!["v5.3".to_string()]);
+        let recorded = RecordedSettings::from_settings(&original);
+
+        let mut settings = Settings::default();
+        settings.idf_versions = Some(vec!["v5.4".to_string()]);
+        recorded.apply_to(&mut settings);
+        assert_eq!(settings.target, original.target);
+        assert_eq!(settings.idf_mirror, original.idf_mirror);
+        assert_eq!(settings.idf_versions, Some(vec!["v5.4".to_string()]));
+    }
+}
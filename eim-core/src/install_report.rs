@@ -0,0 +1,345 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use log::info;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::download_telemetry::{self, DownloadsReport};
+use crate::idf_tools::Download;
+use crate::settings::{Settings, VersionPaths};
+
+pub const REPORT_FORMAT_VERSION: &str = "1.0";
+pub const REPORTS_FOLDER_NAME: &str = "reports";
+pub const SIGNATURE_ALGORITHM: &str = "HMAC-SHA256";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolReport {
+    pub name: String,
+    pub version: String,
+    pub url: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VersionReport {
+    pub version: String,
+    pub idf_path: String,
+    pub tools_path: String,
+    pub tools: Vec<ToolReport>,
+}
+
+/// A Python package installed into the environment of a version and where pip got it from.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PythonPackageReport {
+    pub idf_version: String,
+    pub name: String,
+    pub version: String,
+    /// Index URL or wheelhouse folder which satisfied the package
+    pub source: String,
+    /// Whether `source` is a fallback, the configured index having been unreachable
+    pub fallback: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StageReport {
+    pub stage: String,
+    /// ESP-IDF version the stage belongs to, `None` for global stages like prerequisites
+    pub version: Option<String>,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct MirrorsReport {
+    pub idf: Option<String>,
+    pub tools: Option<String>,
+    pub pypi: Option<String>,
+    pub tools_url_rewrite: Option<String>,
+    pub tools_manifest: Option<String>,
+}
+
+/// Everything an installation put on the machine, for compliance archives and CI.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct InstallReport {
+    pub format_version: String,
+    pub eim_version: String,
+    pub os: String,
+    pub arch: String,
+    pub started: String,
+    pub finished: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub mirrors: MirrorsReport,
+    pub versions: Vec<VersionReport>,
+    pub stages: Vec<StageReport>,
+    pub warnings: Vec<String>,
+    /// Attempts, retries and speed of every download, older reports have none
+    #[serde(default)]
+    pub downloads: DownloadsReport,
+    /// Python packages installed and their sources, left out when empty so older reports still verify
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub python_packages: Vec<PythonPackageReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReportSignature {
+    pub algorithm: String,
+    /// First bytes of the SHA256 of the key, to tell which key signed the report
+    pub key_id: String,
+    pub value: String,
+}
+
+/// The report as written to disk. `report` is kept as the exact JSON text the signature was
+/// computed over, so a report is verified byte for byte, whatever version of eim reads it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StoredInstallReport {
+    pub report: Box<RawValue>,
+    /// Only reports of installations with a `report_signing_key` are signed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ReportSignature>,
+}
+
+fn hmac_sha256(key: &[u8]) -> Hmac<Sha256> {
+    // HMAC takes keys of any length
+    Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length")
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn key_id(key: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(key))[..16].to_string()
+}
+
+/// Returns the directory where the install reports are stored.
+pub fn get_reports_directory() -> PathBuf {
+    PathBuf::from(Settings::default().esp_idf_json_path.unwrap_or_default()).join(REPORTS_FOLDER_NAME)
+}
+
+/// Loads the signing key from `key_path`.
+///
+/// A key generated on the machine itself would let anyone able to change the report sign it
+/// again, so reports are signed only with a key the organization distributes and points the
+/// `report_signing_key` setting at.
+pub fn load_signing_key(key_path: &str) -> Result<Vec<u8>> {
+    let key = fs::read(crate::expand_tilde(Path::new(key_path)))
+        .map_err(|e| anyhow!("Failed to read report signing key {}: {}", key_path, e))?;
+    let key = key.trim_ascii().to_vec();
+    if key.is_empty() {
+        return Err(anyhow!("The report signing key {} is empty", key_path));
+    }
+    Ok(key)
+}
+
+/// Python packages installed during the run, see [`record_python_packages`]
+static PYTHON_PACKAGES: Lazy<Mutex<Vec<PythonPackageReport>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Records Python packages installed by the running installation for its report.
+pub fn record_python_packages(packages: Vec<PythonPackageReport>) {
+    PYTHON_PACKAGES.lock().unwrap().extend(packages);
+}
+
+impl InstallReport {
+    /// Starts the report of a run, the downloads are recorded from here on.
+    pub fn new(settings: &Settings) -> Self {
+        download_telemetry::reset();
+        PYTHON_PACKAGES.lock().unwrap().clear();
+        crate::metrics::install_started();
+        Self {
+            format_version: REPORT_FORMAT_VERSION.to_string(),
+            eim_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            started: Utc::now().to_rfc3339(),
+            finished: None,
+            success: false,
+            error: None,
+            mirrors: MirrorsReport::default(),
+            versions: Vec::new(),
+            stages: Vec::new(),
+            warnings: Vec::new(),
+            downloads: DownloadsReport::default(),
+            python_packages: Vec::new(),
+        }
+        .with_mirrors(settings)
+    }
+
+    /// Records the mirrors from `settings`, called again once the mirrors were selected.
+    pub fn with_mirrors(mut self, settings: &Settings) -> Self {
+        self.set_mirrors(settings);
+        self
+    }
+
+    pub fn set_mirrors(&mut self, settings: &Settings) {
+        self.mirrors = MirrorsReport {
+            idf: settings.idf_mirror.clone(),
+            tools: settings.mirror.clone(),
+            pypi: settings.pypi_mirror.clone(),
+            tools_url_rewrite: settings.tools_url_rewrite.clone(),
+            tools_manifest: settings.tools_manifest.clone(),
+        };
+    }
+
+    pub fn record_stage(&mut self, stage: &str, version: Option<&str>, duration: Duration) {
+        self.stages.push(StageReport {
+            stage: stage.to_string(),
+            version: version.map(str::to_string),
+            seconds: duration.as_secs_f64(),
+        });
+    }
+
+    /// Records an installed version with the tools `setup_tools` returned for it.
+    pub fn add_version(&mut self, paths: &VersionPaths, tools: &HashMap<String, (String, Download)>) {
+        let mut tools: Vec<ToolReport> = tools
+            .iter()
+            .map(|(name, (version, download))| ToolReport {
+                name: name.clone(),
+                version: version.clone(),
+                url: download.url.clone(),
+                sha256: download.sha256.clone(),
+            })
+            .collect();
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        self.versions.push(VersionReport {
+            version: paths.actual_version.clone(),
+            idf_path: paths.idf_path.to_string_lossy().into_owned(),
+            tools_path: paths.tool_install_directory.to_string_lossy().into_owned(),
+            tools,
+        });
+    }
+
+    pub fn add_warnings(&mut self, warnings: &[String]) {
+        self.warnings.extend_from_slice(warnings);
+    }
+
+    /// Completes the report with the result of the run and the downloads made during it.
+    pub fn finish(&mut self, result: &std::result::Result<(), String>) {
+        self.downloads = download_telemetry::take_report();
+        self.python_packages = std::mem::take(&mut *PYTHON_PACKAGES.lock().unwrap());
+        self.finished = Some(Utc::now().to_rfc3339());
+        self.success = result.is_ok();
+        crate::metrics::install_finished(self.success);
+        self.error = result.as_ref().err().cloned();
+    }
+
+    /// Serializes the report, signed with `key` if there is one.
+    pub fn sign(self, key: Option<&[u8]>) -> Result<StoredInstallReport> {
+        let payload = serde_json::to_string(&self)?;
+        let signature = key.map(|key| {
+            let mut mac = hmac_sha256(key);
+            mac.update(payload.as_bytes());
+            ReportSignature {
+                algorithm: SIGNATURE_ALGORITHM.to_string(),
+                key_id: key_id(key),
+                value: format!("{:x}", mac.finalize().into_bytes()),
+            }
+        });
+        Ok(StoredInstallReport {
+            report: RawValue::from_string(payload)?,
+            signature,
+        })
+    }
+
+    /// Writes the report to the reports directory, signed with the key at `key_path` if one is
+    /// configured, returning its path.
+    pub fn write(self, key_path: Option<&str>) -> Result<PathBuf> {
+        let key = key_path.map(load_signing_key).transpose()?;
+        let signed = self.sign(key.as_deref())?;
+        let directory = get_reports_directory();
+        fs::create_dir_all(&directory)?;
+        let path = directory.join(format!(
+            "install_report_{}.json",
+            Utc::now().format("%Y%m%dT%H%M%SZ")
+        ));
+        fs::write(&path, serde_json::to_string_pretty(&signed)?)?;
+        // stable path for CI jobs which archive the report of the last run
+        fs::copy(&path, directory.join("latest.json"))?;
+        info!("Installation report written to {}", path.display());
+        Ok(path)
+    }
+}
+
+impl StoredInstallReport {
+    /// Parses the stored report.
+    pub fn report(&self) -> Result<InstallReport> {
+        Ok(serde_json::from_str(self.report.get())?)
+    }
+
+    /// Checks that the report was signed with `key` and not modified since, comparing in
+    /// constant time.
+    pub fn verify(&self, key: &[u8]) -> Result<bool> {
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or_else(|| anyhow!("The report is not signed"))?;
+        if signature.algorithm != SIGNATURE_ALGORITHM {
+            return Err(anyhow!("Unsupported signature algorithm {}", signature.algorithm));
+        }
+        let Some(expected) = decode_hex(&signature.value) else {
+            return Ok(false);
+        };
+        let mut mac = hmac_sha256(key);
+        mac.update(self.report.get().as_bytes());
+        Ok(mac.verify_slice(&expected).is_ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_matches_rfc4231() {
+        // RFC 4231 test case 2
+        let mut mac = hmac_sha256(b"Jefe");
+        mac.update(b"what do ya want for nothing?");
+        assert_eq!(
+            format!("{:x}", mac.finalize().into_bytes()),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_tampered_report_fails_verification() {
+        let mut report = InstallReport::new(&Settings::default());
+        report.add_warnings(&["openocd-esp32 overridden".to_string()]);
+        report.finish(&Ok(()));
+        let signed = report.sign(Some(b"secret")).unwrap();
+
+        // verified from the file as written
+        let stored: StoredInstallReport =
+            serde_json::from_str(&serde_json::to_string_pretty(&signed).unwrap()).unwrap();
+        assert!(stored.verify(b"secret").unwrap());
+        assert!(!stored.verify(b"other").unwrap());
+        assert_eq!(stored.report().unwrap().warnings.len(), 1);
+
+        let tampered = stored.report.get().replace("openocd-esp32", "openocd-esp33");
+        let tampered = StoredInstallReport {
+            report: RawValue::from_string(tampered).unwrap(),
+            signature: stored.signature.clone(),
+        };
+        assert!(!tampered.verify(b"secret").unwrap());
+    }
+
+    #[test]
+    fn test_report_without_key_is_unsigned() {
+        let report = InstallReport::new(&Settings::default());
+        let stored = report.sign(None).unwrap();
+        assert!(stored.signature.is_none());
+        assert!(stored.verify(b"secret").is_err());
+    }
+}
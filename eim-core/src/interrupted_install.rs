@@ -0,0 +1,202 @@
+use anyhow::Result;
+use chrono::Utc;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::install_lock::is_process_alive;
+use crate::settings::Settings;
+
+/// Written when the GUI starts an installation and removed when it ends, next to `eim_idf.json`
+pub const MARKER_FILE_NAME: &str = "eim_install_in_progress.json";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueItemState {
+    Pending,
+    Installing,
+    Installed,
+}
+
+/// One version of the install queue and how far it got.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QueueItem {
+    pub version: String,
+    pub state: QueueItemState,
+}
+
+/// An installation started by the GUI, kept until it ends so one cut short by closing the app,
+/// a crash or a reboot can be resumed with the same settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallInProgress {
+    pub pid: u32,
+    pub started: String,
+    pub versions: Vec<String>,
+    pub settings: Settings,
+    /// State of each version, markers of older eim versions have none
+    #[serde(default)]
+    pub queue: Vec<QueueItem>,
+}
+
+impl InstallInProgress {
+    /// Versions not installed yet, the ones a resumed installation has to install.
+    pub fn remaining_versions(&self) -> Vec<String> {
+        if self.queue.is_empty() {
+            return self.versions.clone();
+        }
+        self.queue
+            .iter()
+            .filter(|item| item.state != QueueItemState::Installed)
+            .map(|item| item.version.clone())
+            .collect()
+    }
+
+    /// Versions the installation completed before it was cut short.
+    pub fn installed_versions(&self) -> Vec<String> {
+        self.queue
+            .iter()
+            .filter(|item| item.state == QueueItemState::Installed)
+            .map(|item| item.version.clone())
+            .collect()
+    }
+}
+
+pub fn get_marker_path() -> PathBuf {
+    PathBuf::from(Settings::default().esp_idf_json_path.unwrap_or_default()).join(MARKER_FILE_NAME)
+}
+
+/// Records that an installation with `settings` started in this process.
+pub fn mark_started(settings: &Settings) -> Result<()> {
+    let versions = settings.idf_versions.clone().unwrap_or_default();
+    let marker = InstallInProgress {
+        pid: std::process::id(),
+        started: Utc::now().to_rfc3339(),
+        queue: versions
+            .iter()
+            .map(|version| QueueItem {
+                version: version.clone(),
+                state: QueueItemState::Pending,
+            })
+            .collect(),
+        versions,
+        settings: settings.clone(),
+    };
+    write(&marker)?;
+    debug!("Recorded the installation of {:?} in {}", marker.versions, get_marker_path().display());
+    Ok(())
+}
+
+fn write(marker: &InstallInProgress) -> Result<()> {
+    let path = get_marker_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(marker)?)?;
+    Ok(())
+}
+
+/// Records how far the installation of `version` in this process got, so a resumed installation
+/// skips the versions already installed.
+pub fn set_version_state(version: &str, state: QueueItemState) {
+    let Some(mut marker) = load().filter(|marker| marker.pid == std::process::id()) else {
+        return;
+    };
+    for item in marker.queue.iter_mut().filter(|item| item.version == version) {
+        item.state = state;
+    }
+    if let Err(err) = write(&marker) {
+        warn!("Failed to record the state of {} in {}: {}", version, MARKER_FILE_NAME, err);
+    }
+}
+
+/// Removes the record of the running installation, it finished or failed.
+pub fn mark_finished() {
+    let path = get_marker_path();
+    if path.exists() {
+        if let Err(err) = fs::remove_file(&path) {
+            warn!("Failed to remove {}: {}", path.display(), err);
+        }
+    }
+}
+
+pub fn load() -> Option<InstallInProgress> {
+    let content = fs::read_to_string(get_marker_path()).ok()?;
+    serde_json::from_str(&content)
+        .map_err(|err| warn!("Ignoring the unreadable {}: {}", MARKER_FILE_NAME, err))
+        .ok()
+}
+
+/// Whether the process which ran the installation is gone without finishing it.
+pub fn is_interrupted(marker: &InstallInProgress) -> bool {
+    marker.pid != std::process::id() && !is_process_alive(marker.pid)
+}
+
+/// The installation which was cut short, `None` when there is none, it is still running or it
+/// only had the final steps left, which a resumed installation would not redo.
+pub fn find_interrupted() -> Option<InstallInProgress> {
+    load().filter(|marker| is_interrupted(marker) && !marker.remaining_versions().is_empty())
+}
+
+/// Removes what the interrupted installation left half done, so installing with its settings
+/// again continues it: the versions already installed are left out, downloaded tool archives
+/// are reused and partial checkouts are removed as they can't be cloned into.
+pub fn prepare_resume(marker: &InstallInProgress) -> Result<Settings> {
+    let remaining = marker.remaining_versions();
+    for version in &remaining {
+        let paths = marker.settings.get_version_paths(version)?;
+        if !paths.using_existing_idf && paths.idf_path.exists() {
+            info!("Removing the partial ESP-IDF checkout {}", paths.idf_path.display());
+            crate::utils::remove_directory_all(&paths.idf_path)?;
+        }
+    }
+    let mut settings = marker.settings.clone();
+    settings.idf_versions = Some(remaining);
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running_installation_is_not_interrupted() {
+        let mut settings = Settings::default();
+        settings.idf_versions = Some(vec!["v5.3.2".to_string()]);
+        let marker = InstallInProgress {
+            pid: std::process::id(),
+            started: Utc::now().to_rfc3339(),
+            versions: vec!["v5.3.2".to_string()],
+            settings,
+            queue: Vec::new(),
+        };
+        assert!(!is_interrupted(&marker));
+
+        let restored: InstallInProgress = serde_json::from_str(&serde_json::to_string(&marker).unwrap()).unwrap();
+        assert_eq!(restored.settings.idf_versions, Some(vec!["v5.3.2".to_string()]));
+    }
+
+    #[test]
+    fn test_remaining_versions() {
+        let item = |version: &str, state| QueueItem {
+            version: version.to_string(),
+            state,
+        };
+        let mut marker = InstallInProgress {
+            pid: 0,
+            started: Utc::now().to_rfc3339(),
+            versions: vec!["v5.3.2".to_string(), "v5.4".to_string(), "v5.5".to_string()],
+            settings: Settings::default(),
+            queue: Vec::new(),
+        };
+        assert_eq!(marker.remaining_versions(), marker.versions);
+
+        marker.queue = vec![
+            item("v5.3.2", QueueItemState::Installed),
+            item("v5.4", QueueItemState::Installing),
+            item("v5.5", QueueItemState::Pending),
+        ];
+        assert_eq!(marker.remaining_versions(), vec!["v5.4", "v5.5"]);
+        assert_eq!(marker.installed_versions(), vec!["v5.3.2"]);
+    }
+}
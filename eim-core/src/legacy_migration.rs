@@ -0,0 +1,581 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::idf_config::{IdfConfig, IdfInstallation};
+use crate::idf_tools::read_and_parse_tools_file;
+use crate::settings::Settings;
+use crate::utils::{is_valid_idf_directory, parse_cmake_version};
+use crate::version_manager::get_default_config_path;
+
+/// Files listing the installations of `idf_tools.py` (`idf-env.json`) and of the legacy
+/// Windows ESP-IDF Tools Installer (`esp_idf.json`) in the tools folder
+const LEGACY_REGISTRIES: &[&str] = &["idf-env.json", "esp_idf.json"];
+
+/// Suffix of the rc file lines disabled by the migration, so they can be found and restored
+pub const DISABLED_MARKER: &str = "# disabled by eim migrate";
+
+/// How a legacy installation was made.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LegacySource {
+    /// The ESP-IDF Tools Installer for Windows, which puts ESP-IDF into `<tools>\frameworks`
+    WindowsInstaller,
+    /// A clone of ESP-IDF set up with install.sh / install.bat / install.ps1
+    InstallScript,
+}
+
+/// An ESP-IDF installation made without eim, which eim can register and activate.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LegacyInstallation {
+    pub source: LegacySource,
+    /// Name the installation gets in eim, e.g. `v5.1`
+    pub name: String,
+    pub idf_path: PathBuf,
+    /// `IDF_TOOLS_PATH` of the installation, holding `tools` and `python_env`
+    pub tools_path: PathBuf,
+    pub python_env: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyRegistry {
+    #[serde(rename = "idfInstalled", default)]
+    installed: HashMap<String, LegacyRegistryEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyRegistryEntry {
+    version: Option<String>,
+    path: String,
+    /// python.exe of the virtual environment, only written by the Windows installer
+    python: Option<String>,
+}
+
+/// An entry of the Windows list of installed programs belonging to the legacy installer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LegacyUninstaller {
+    #[serde(rename = "DisplayName")]
+    pub name: String,
+    #[serde(rename = "DisplayVersion", default)]
+    pub version: Option<String>,
+    #[serde(rename = "UninstallString")]
+    pub command: String,
+}
+
+/// Result of removing the `PATH` entries of legacy installations.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PathCleanup {
+    /// Entries removed from the user `PATH` on Windows
+    pub removed_entries: Vec<String>,
+    /// Shell rc files and the lines disabled in them
+    pub disabled_lines: Vec<(PathBuf, String)>,
+}
+
+/// Tools folders the legacy tools use: `IDF_TOOLS_PATH`, `~/.espressif` and `C:\Espressif`.
+fn candidate_tools_paths() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = std::env::var_os("IDF_TOOLS_PATH").map(PathBuf::from).into_iter().collect();
+    if let Some(home) = dirs::home_dir() {
+        paths.push(home.join(".espressif"));
+    }
+    if cfg!(windows) {
+        paths.push(PathBuf::from(r"C:\Espressif"));
+    }
+    paths.dedup();
+    paths
+}
+
+/// ESP-IDF clones in `~/esp`, where the getting started guide puts them.
+fn candidate_checkouts() -> Vec<PathBuf> {
+    let Some(esp) = dirs::home_dir().map(|home| home.join("esp")) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(&esp) else {
+        return Vec::new();
+    };
+    let mut checkouts: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("esp-idf"))
+                && is_valid_idf_directory(&path.to_string_lossy())
+        })
+        .collect();
+    checkouts.sort();
+    checkouts
+}
+
+/// Name of a legacy installation in eim: the recorded version or the one of `version.cmake`.
+fn installation_name(idf_path: &Path, recorded: Option<&str>) -> Option<String> {
+    let version = match recorded.filter(|version| !version.is_empty()) {
+        Some(version) => version.to_string(),
+        None => {
+            let (major, minor) = parse_cmake_version(&idf_path.to_string_lossy()).ok()?;
+            format!("{}.{}", major, minor)
+        }
+    };
+    Some(if version.starts_with('v') { version } else { format!("v{}", version) })
+}
+
+/// The virtual environment `idf_tools.py` made for the version of `idf_path`, e.g.
+/// `python_env/idf5.1_py3.11_env`.
+fn find_python_env(tools_path: &Path, idf_path: &Path) -> Option<PathBuf> {
+    let (major, minor) = parse_cmake_version(&idf_path.to_string_lossy()).ok()?;
+    let prefix = format!("idf{}.{}_py", major, minor);
+    let mut envs: Vec<PathBuf> = fs::read_dir(tools_path.join("python_env"))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with(&prefix)))
+        .collect();
+    // the newest Python when install.sh ran with several
+    envs.sort();
+    envs.pop()
+}
+
+/// Guesses how an installation was made from where its ESP-IDF is.
+fn source_of(idf_path: &Path, tools_path: &Path) -> LegacySource {
+    if cfg!(windows) && idf_path.starts_with(tools_path.join("frameworks")) {
+        LegacySource::WindowsInstaller
+    } else {
+        LegacySource::InstallScript
+    }
+}
+
+fn read_registry(path: &Path) -> Vec<LegacyRegistryEntry> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    match serde_json::from_str::<LegacyRegistry>(&content) {
+        Ok(registry) => registry.installed.into_values().collect(),
+        Err(err) => {
+            warn!("Failed to parse {}: {}", path.display(), err);
+            Vec::new()
+        }
+    }
+}
+
+fn normalized(path: &Path) -> String {
+    let path = path.to_string_lossy().trim_end_matches(['/', '\\']).to_string();
+    if cfg!(windows) {
+        path.to_lowercase()
+    } else {
+        path
+    }
+}
+
+/// Finds the installations of the legacy installer and of install.sh which eim does not know
+/// yet, from the `idf-env.json` / `esp_idf.json` of their tools folder and the clones in `~/esp`.
+pub fn detect() -> Vec<LegacyInstallation> {
+    let registered: Vec<String> = crate::version_manager::list_installed_versions()
+        .unwrap_or_default()
+        .iter()
+        .map(|installation| normalized(Path::new(&installation.path)))
+        .collect();
+    let tools_paths: Vec<PathBuf> = candidate_tools_paths()
+        .into_iter()
+        .filter(|path| path.join("tools").is_dir())
+        .collect();
+
+    let mut found: Vec<LegacyInstallation> = Vec::new();
+    let mut add = |idf_path: PathBuf, tools_path: &Path, version: Option<&str>, python: Option<&str>| {
+        let key = normalized(&idf_path);
+        if registered.contains(&key) || found.iter().any(|known| normalized(&known.idf_path) == key) {
+            return;
+        }
+        if !is_valid_idf_directory(&idf_path.to_string_lossy()) {
+            debug!("Skipping {}, it is no ESP-IDF directory", idf_path.display());
+            return;
+        }
+        let Some(name) = installation_name(&idf_path, version) else {
+            debug!("Skipping {}, its version is unknown", idf_path.display());
+            return;
+        };
+        // python.exe is in <env>\Scripts
+        let python_env = python
+            .and_then(|python| Path::new(python).parent()?.parent().map(Path::to_path_buf))
+            .filter(|env| env.is_dir())
+            .or_else(|| find_python_env(tools_path, &idf_path));
+        found.push(LegacyInstallation {
+            source: source_of(&idf_path, tools_path),
+            name,
+            idf_path,
+            tools_path: tools_path.to_path_buf(),
+            python_env,
+        });
+    };
+
+    for tools_path in &tools_paths {
+        for registry in LEGACY_REGISTRIES {
+            for entry in read_registry(&tools_path.join(registry)) {
+                add(PathBuf::from(&entry.path), tools_path, entry.version.as_deref(), entry.python.as_deref());
+            }
+        }
+    }
+    if let Some(tools_path) = tools_paths.first() {
+        for checkout in candidate_checkouts() {
+            add(checkout, tools_path, None, None);
+        }
+    }
+    found
+}
+
+/// Folders of the tools of `tools.json` which `idf_tools.py` installed, in the layout
+/// `<tools_path>/tools/<tool>/<version>/<export path>`.
+fn export_paths(installation: &LegacyInstallation) -> Result<Vec<String>> {
+    let tools_json = installation.idf_path.join("tools").join("tools.json");
+    let tools_file = read_and_parse_tools_file(&tools_json.to_string_lossy())
+        .map_err(|err| anyhow!("Failed to read {}: {}", tools_json.display(), err))?;
+    let mut paths = Vec::new();
+    for tool in &tools_file.tools {
+        for version in tool.versions.iter().filter(|version| version.status == "recommended") {
+            let version_dir = installation.tools_path.join("tools").join(&tool.name).join(&version.name);
+            for export_path in &tool.export_paths {
+                let path = export_path.iter().fold(version_dir.clone(), |path, level| path.join(level));
+                if path.is_dir() {
+                    paths.push(path.to_string_lossy().into_owned());
+                }
+            }
+        }
+    }
+    if paths.is_empty() {
+        return Err(anyhow!(
+            "No tools of {} found in {}, run its install script first",
+            installation.name,
+            installation.tools_path.join("tools").display()
+        ));
+    }
+    Ok(paths)
+}
+
+/// A name not used by an installation of `config` yet.
+fn unique_name(config: Option<&IdfConfig>, name: &str) -> String {
+    let taken = |candidate: &str| {
+        config.is_some_and(|config| config.idf_installed.iter().any(|installation| installation.name == candidate))
+    };
+    if !taken(name) {
+        return name.to_string();
+    }
+    let legacy = format!("{}-legacy", name);
+    (1..)
+        .map(|index| if index == 1 { legacy.clone() } else { format!("{}-{}", legacy, index) })
+        .find(|candidate| !taken(candidate))
+        .unwrap_or(legacy)
+}
+
+/// Registers a legacy installation in `eim_idf.json` with an activation script using its tools
+/// where they are, so `eim select`, `eim activate` and the IDEs can use it.
+pub fn migrate(installation: &LegacyInstallation) -> Result<IdfInstallation> {
+    let export_paths = export_paths(installation)?;
+    let config_path = get_default_config_path();
+    let existing = IdfConfig::from_file(&config_path).ok();
+    let name = unique_name(existing.as_ref(), &installation.name);
+
+    let mut settings = Settings::default();
+    settings.version_name = Some(name.clone());
+    let paths = settings.get_version_paths(&name)?;
+    let idf_path = installation.idf_path.to_string_lossy().into_owned();
+    let python_env = installation.python_env.as_ref().map(|env| env.to_string_lossy().into_owned());
+
+    // the tools of idf_tools.py are below <IDF_TOOLS_PATH>/tools, which is also where the ROM
+    // ELFs and the OpenOCD scripts are looked up
+    let mut env_vars = crate::setup_environment_variables(&installation.tools_path.join("tools"), &installation.idf_path)
+        .map_err(|err| anyhow!(err))?;
+    for (key, value) in env_vars.iter_mut() {
+        if key == "IDF_TOOLS_PATH" {
+            *value = installation.tools_path.to_string_lossy().into_owned();
+        }
+    }
+    env_vars.push(("IDF_PYTHON_ENV_PATH".to_string(), python_env.clone().unwrap_or_default()));
+
+    crate::single_version_post_install(
+        &paths.activation_script_path.to_string_lossy(),
+        &idf_path,
+        &name,
+        &installation.tools_path.to_string_lossy(),
+        export_paths,
+        python_env.as_deref(),
+        Some(env_vars),
+    );
+
+    let python = installation
+        .python_env
+        .as_ref()
+        .map(|env| match std::env::consts::OS {
+            "windows" => env.join("Scripts").join("python.exe"),
+            _ => env.join("bin").join("python"),
+        })
+        .unwrap_or_default();
+    let registered = IdfInstallation {
+        id: format!("esp-idf-{}", Uuid::new_v4().to_string().replace("-", "")),
+        activation_script: paths.activation_script.to_string_lossy().into_owned(),
+        path: idf_path,
+        name,
+        python: python.to_string_lossy().into_owned(),
+        idf_tools_path: installation.tools_path.to_string_lossy().into_owned(),
+        idf_version: None,
+        label: Some(match installation.source {
+            LegacySource::WindowsInstaller => t!("migrate.label_windows_installer").to_string(),
+            LegacySource::InstallScript => t!("migrate.label_install_script").to_string(),
+        }),
+    };
+
+    let mut config = match existing {
+        Some(config) => config,
+        None => {
+            settings.idf_versions = Some(vec![]);
+            settings.save_esp_ide_json()?;
+            IdfConfig::from_file(&config_path)?
+        }
+    };
+    config.idf_installed.push(registered.clone());
+    config.to_file(&config_path, true, true)?;
+    info!("Registered {} at {} in {}", registered.name, registered.path, config_path.display());
+    Ok(registered)
+}
+
+/// Whether a `PATH` entry belongs to one of the legacy installations, e.g. a tool folder the
+/// legacy installer or the user added permanently.
+fn is_legacy_entry(entry: &str, installations: &[LegacyInstallation]) -> bool {
+    let entry = Path::new(entry.trim());
+    installations.iter().any(|installation| {
+        entry.starts_with(installation.tools_path.join("tools"))
+            || entry.starts_with(installation.tools_path.join("python_env"))
+            || entry.starts_with(&installation.idf_path)
+    })
+}
+
+/// Splits `PATH` entries into the ones to keep and the ones to remove: entries of the legacy
+/// installations, which their eim activation scripts add again, and repeated entries.
+pub fn split_path_entries(entries: &[String], installations: &[LegacyInstallation]) -> (Vec<String>, Vec<String>) {
+    let mut kept: Vec<String> = Vec::new();
+    let mut removed = Vec::new();
+    for entry in entries {
+        let seen = kept.iter().any(|known| normalized(Path::new(known)) == normalized(Path::new(entry)));
+        if entry.trim().is_empty() {
+            continue;
+        } else if seen || is_legacy_entry(entry, installations) {
+            removed.push(entry.clone());
+        } else {
+            kept.push(entry.clone());
+        }
+    }
+    (kept, removed)
+}
+
+/// Lines of a shell rc file sourcing the `export.sh` of a legacy installation or adding its
+/// tools to `PATH`, both of which conflict with the activation scripts of eim.
+pub fn legacy_rc_lines(content: &str, installations: &[LegacyInstallation]) -> Vec<String> {
+    content
+        .lines()
+        .filter(|line| {
+            let line = line.trim();
+            !line.starts_with('#')
+                && installations.iter().any(|installation| {
+                    let export_script = installation.idf_path.join("export.sh");
+                    let tools = installation.tools_path.join("tools");
+                    line.contains(&*export_script.to_string_lossy())
+                        || (line.contains("PATH") && line.contains(&*tools.to_string_lossy()))
+                })
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Comments out `lines` in `content`, marking them so they are easy to find and restore.
+pub fn disable_lines(content: &str, lines: &[String]) -> String {
+    let mut disabled: String = content
+        .lines()
+        .map(|line| {
+            if lines.iter().any(|disabled| disabled == line) {
+                format!("# {}  {}\n", line, DISABLED_MARKER)
+            } else {
+                format!("{}\n", line)
+            }
+        })
+        .collect();
+    if !content.ends_with('\n') {
+        disabled.pop();
+    }
+    disabled
+}
+
+#[cfg(windows)]
+fn clean_user_path(installations: &[LegacyInstallation], cleanup: &mut PathCleanup) -> Result<()> {
+    let current = crate::run_powershell_script("[Environment]::GetEnvironmentVariable('PATH', 'User')")
+        .map_err(|err| anyhow!("Failed to read the user PATH: {}", err))?;
+    let entries: Vec<String> = current.trim().split(';').map(str::to_string).collect();
+    let (kept, removed) = split_path_entries(&entries, installations);
+    if removed.is_empty() {
+        return Ok(());
+    }
+    let script = format!(
+        "[Environment]::SetEnvironmentVariable('PATH', '{}', 'User')",
+        kept.join(";").replace('\'', "''")
+    );
+    crate::run_powershell_script(&script).map_err(|err| anyhow!("Failed to write the user PATH: {}", err))?;
+    cleanup.removed_entries = removed;
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn clean_user_path(installations: &[LegacyInstallation], cleanup: &mut PathCleanup) -> Result<()> {
+    let Some(home) = dirs::home_dir() else {
+        return Ok(());
+    };
+    for rc_file in [".bashrc", ".bash_profile", ".zshrc", ".profile"] {
+        let path = home.join(rc_file);
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let lines = legacy_rc_lines(&content, installations);
+        if lines.is_empty() {
+            continue;
+        }
+        fs::write(&path, disable_lines(&content, &lines))?;
+        cleanup
+            .disabled_lines
+            .extend(lines.into_iter().map(|line| (path.clone(), line)));
+    }
+    Ok(())
+}
+
+/// Removes what makes the shell pick up the tools of the legacy installations without an eim
+/// activation: their entries of the user `PATH` on Windows, and the rc file lines sourcing their
+/// export.sh elsewhere, which are commented out rather than deleted.
+pub fn clean_path(installations: &[LegacyInstallation]) -> Result<PathCleanup> {
+    let mut cleanup = PathCleanup::default();
+    clean_user_path(installations, &mut cleanup)?;
+    Ok(cleanup)
+}
+
+/// Entries of the legacy ESP-IDF Tools Installer in the list of installed programs of Windows.
+pub fn find_legacy_uninstallers() -> Vec<LegacyUninstaller> {
+    if std::env::consts::OS != "windows" {
+        return Vec::new();
+    }
+    let script = "@(Get-ItemProperty -ErrorAction SilentlyContinue \
+        'HKCU:\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\*', \
+        'HKLM:\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\*', \
+        'HKLM:\\Software\\WOW6432Node\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\*' \
+        | Where-Object { $_.DisplayName -like 'ESP-IDF Tools*' -and $_.UninstallString } \
+        | Select-Object DisplayName,DisplayVersion,UninstallString) | ConvertTo-Json -Compress";
+    match crate::run_powershell_script(script) {
+        Ok(output) => parse_uninstallers(&output),
+        Err(err) => {
+            warn!("Failed to list the installed programs: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+/// Parses the JSON of `ConvertTo-Json`, which is an object instead of an array for one entry.
+fn parse_uninstallers(output: &str) -> Vec<LegacyUninstaller> {
+    let output = output.trim();
+    if output.is_empty() {
+        return Vec::new();
+    }
+    serde_json::from_str::<Vec<LegacyUninstaller>>(output)
+        .or_else(|_| serde_json::from_str::<LegacyUninstaller>(output).map(|entry| vec![entry]))
+        .unwrap_or_else(|err| {
+            warn!("Failed to parse the installed programs: {}", err);
+            Vec::new()
+        })
+}
+
+/// Runs the Inno Setup uninstaller of the legacy installer silently.
+pub fn run_uninstaller(uninstaller: &LegacyUninstaller) -> Result<()> {
+    let command = format!("{} /VERYSILENT /SUPPRESSMSGBOXES /NORESTART", uninstaller.command);
+    info!("Running the uninstaller of {}", uninstaller.name);
+    let output = crate::command_executor::execute_command("cmd", &["/C", &command])?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "The uninstaller of {} failed: {}",
+            uninstaller.name,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn installation(idf_path: &str, tools_path: &str) -> LegacyInstallation {
+        LegacyInstallation {
+            source: LegacySource::InstallScript,
+            name: "v5.1".to_string(),
+            idf_path: PathBuf::from(idf_path),
+            tools_path: PathBuf::from(tools_path),
+            python_env: None,
+        }
+    }
+
+    #[test]
+    fn test_split_path_entries() {
+        let installations = [installation("/home/u/esp/esp-idf", "/home/u/.espressif")];
+        let entries = [
+            "/usr/bin",
+            "/home/u/.espressif/tools/xtensa-esp-elf/esp-13.2.0_20230928/xtensa-esp-elf/bin",
+            "/home/u/esp/esp-idf/tools",
+            "/usr/bin",
+            "/home/u/.local/bin",
+        ]
+        .map(str::to_string);
+        let (kept, removed) = split_path_entries(&entries, &installations);
+        assert_eq!(kept, vec!["/usr/bin", "/home/u/.local/bin"]);
+        assert_eq!(removed.len(), 3);
+    }
+
+    #[test]
+    fn test_legacy_rc_lines() {
+        let installations = [installation("/home/u/esp/esp-idf", "/home/u/.espressif")];
+        let content = "export EDITOR=vim\n\
+                       . /home/u/esp/esp-idf/export.sh\n\
+                       # . /home/u/esp/esp-idf/export.sh\n\
+                       alias get_idf='. /home/u/esp/esp-idf/export.sh'\n\
+                       export PATH=\"/home/u/.espressif/tools/ninja/1.11.1:$PATH\"\n";
+        let lines = legacy_rc_lines(content, &installations);
+        assert_eq!(lines.len(), 3);
+
+        let disabled = disable_lines(content, &lines);
+        assert!(disabled.starts_with("export EDITOR=vim\n# . /home/u/esp/esp-idf/export.sh  # disabled by eim migrate\n"));
+        assert!(disabled.ends_with('\n'));
+        assert!(legacy_rc_lines(&disabled, &installations).is_empty());
+    }
+
+    #[test]
+    fn test_parse_uninstallers() {
+        let one = r#"{"DisplayName":"ESP-IDF Tools Offline 5.0","DisplayVersion":"2.18","UninstallString":"\"C:\\Espressif\\unins000.exe\""}"#;
+        assert_eq!(parse_uninstallers(one).len(), 1);
+        assert_eq!(parse_uninstallers(&format!("[{},{}]", one, one)).len(), 2);
+        assert!(parse_uninstallers("").is_empty());
+    }
+
+    #[test]
+    fn test_unique_name() {
+        assert_eq!(unique_name(None, "v5.1"), "v5.1");
+    }
+
+    #[test]
+    fn test_installation_name_and_python_env() {
+        let tools = tempfile::TempDir::new().unwrap();
+        let idf = tools.path().join("esp-idf");
+        fs::create_dir_all(idf.join("tools").join("cmake")).unwrap();
+        fs::write(
+            idf.join("tools").join("cmake").join("version.cmake"),
+            "set(IDF_VERSION_MAJOR 5)\nset(IDF_VERSION_MINOR 1)\n",
+        )
+        .unwrap();
+        let env = tools.path().join("python_env").join("idf5.1_py3.11_env");
+        fs::create_dir_all(&env).unwrap();
+        assert_eq!(installation_name(&idf, None).as_deref(), Some("v5.1"));
+        assert_eq!(installation_name(&idf, Some("5.1.2")).as_deref(), Some("v5.1.2"));
+        assert_eq!(find_python_env(tools.path(), &idf), Some(env));
+    }
+}
@@ -3,37 +3,109 @@ use std::hash::{DefaultHasher, Hash,Hasher};
 use anyhow::{anyhow, Result};
 use idf_env::driver;
 use log::{error, info, trace, warn};
-use reqwest::Client;
 #[cfg(feature = "userustpython")]
 use rustpython_vm::literal::char;
 use serde_json::{json, Value};
-use sha2::{Digest, Sha256};
 use system_dependencies::copy_openocd_rules;
 use tempfile::TempDir;
 use tar::Archive;
 use tera::{Context, Tera};
 use thiserror::Error;
 use utils::{find_directories_by_name};
+use download_backend::DownloadFailure;
 use zip::ZipArchive;
 
+rust_i18n::i18n!("locales", fallback = "en");
+
+pub mod announcements;
+pub mod antivirus;
+pub mod cancellation;
+pub mod checksum;
 pub mod command_executor;
+pub mod component_manager;
+pub mod components;
+pub mod component_tree;
+pub mod config_schema;
+pub mod crash_report;
+pub mod credentials;
+pub mod custom_toolchains;
+pub mod dedupe;
+pub mod delta_download;
+pub mod display_format;
+pub mod download_backend;
+pub mod download_only;
+pub mod download_telemetry;
+pub mod drivers;
+pub mod elevation;
+pub mod env_diff;
+pub mod errors;
+pub mod esp_rs;
+pub mod flash_test;
+pub mod freebsd;
 pub mod git_tools;
+pub mod health_check;
+pub mod hooks;
+pub mod http_client;
 pub mod idf_config;
 pub mod idf_tools;
 pub mod idf_versions;
+pub mod network_preflight;
+pub mod install_lock;
+pub mod legacy_migration;
+pub mod interrupted_install;
+pub mod links;
+pub mod logging;
+pub mod metadata_cache;
+pub mod metrics;
+pub mod maintenance;
+pub mod mirror_health;
+pub mod install_manifest;
+pub mod install_report;
 pub mod idf_features;
+pub mod ide_export;
+pub mod path_conflicts;
+pub mod plugins;
+pub mod proxy_auth;
+pub mod project_install;
+pub mod prune;
 pub mod python_utils;
+pub mod qemu;
+pub mod read_only;
+pub mod recovery;
+pub mod release_notes;
+pub mod remnants;
+pub mod retry_policy;
+pub mod run_monitor;
+pub mod serial_devices;
 pub mod settings;
+pub mod shell;
+pub mod shared_tools;
+pub mod size_estimate;
+pub mod status;
+pub mod snapshot;
+pub mod support_matrix;
 pub mod system_dependencies;
+pub mod system_install;
+pub mod system_tools;
+pub mod task_graph;
+pub mod transfer_rate;
+pub mod transaction;
+pub mod unattended;
+pub mod update_notifications;
+pub mod url_rewrite;
 pub mod utils;
 pub mod version_manager;
+pub mod version_tracking;
+pub mod which;
 pub mod offline_installer;
+pub mod path_validation;
 pub mod telemetry;
+pub mod wsl;
 use std::fs::{set_permissions, File};
 use std::{
     env,
     fs,
-    io::{self, Read, Write, BufReader},
+    io::{self, Write, BufReader, Read, Seek, SeekFrom},
     path::{Path, PathBuf},
     sync::mpsc::Sender,
 };
@@ -111,6 +183,44 @@ fn format_powershell_env_pairs(pairs: &[(String, String)]) -> String {
     format!("$env_var_pairs = @{{\n{}\n}}", formatted_pairs.join("\n"))
 }
 
+/// Adds `entries` in front of the PATH set by an existing activation script.
+///
+/// Used when a component is installed into an existing installation, so the rest of
+/// the script (including custom environment variables) is kept as it is. Entries
+/// already present in the script are skipped. Works for both the bash and the
+/// PowerShell activation scripts.
+pub fn prepend_paths_to_activation_script(script: &Path, entries: &[String]) -> Result<()> {
+    let content = fs::read_to_string(script)?;
+    let new_entries: Vec<&String> = entries.iter().filter(|e| !content.contains(e.as_str())).collect();
+    if new_entries.is_empty() {
+        return Ok(());
+    }
+    let is_powershell = script.extension().is_some_and(|ext| ext == "ps1");
+    let separator = if is_powershell { ";" } else { ":" };
+    let joined = new_entries.iter().map(|e| e.as_str()).collect::<Vec<_>>().join(separator);
+    let export_line = if is_powershell { "$env:PATH = \"" } else { "export PATH=\"" };
+    if !content.contains(export_line) {
+        return Err(anyhow!("No PATH export found in {}", script.display()));
+    }
+    let updated = content
+        .replace(export_line, &format!("{}{}{}", export_line, joined, separator))
+        .replace("\"PATH=", &format!("\"PATH={}{}", joined, separator));
+    fs::write(script, updated)?;
+    Ok(())
+}
+
+/// Removes entries added with [`prepend_paths_to_activation_script`] from the PATH exports of `script`.
+pub fn remove_paths_from_activation_script(script: &Path, entries: &[String]) -> Result<()> {
+    let mut content = fs::read_to_string(script)?;
+    let is_powershell = script.extension().is_some_and(|ext| ext == "ps1");
+    let separator = if is_powershell { ";" } else { ":" };
+    for entry in entries {
+        content = content.replace(&format!("{}{}", entry, separator), "");
+    }
+    fs::write(script, content)?;
+    Ok(())
+}
+
 /// Creates an activation shell script for the ESP-IDF toolchain.
 ///
 /// # Parameters
@@ -136,7 +246,7 @@ pub fn create_activation_shell_script(
     ensure_path(file_path).map_err(|e| e.to_string())?;
     let mut filename = PathBuf::from(file_path);
     filename.push(format!("activate_idf_{}.sh", idf_version));
-    let template = include_str!("../../bash_scripts/activate_idf_template.sh");
+    let template = include_str!("../bash_scripts/activate_idf_template.sh");
     let mut tera = Tera::default();
     if let Err(e) = tera.add_raw_template("activate_idf_template", template) {
         error!("Failed to add template: {}", e);
@@ -182,9 +292,39 @@ pub fn create_activation_shell_script(
     };
 
     create_executable_shell_script(filename.to_str().unwrap(), &rendered)?;
+    if std::env::consts::OS == "freebsd" {
+        create_activation_csh_script(file_path, idf_path, idf_version, &context, &env_var_pairs)?;
+    }
     Ok(())
 }
 
+/// Writes `activate_idf_<version>.csh` next to the shell script, for csh and tcsh users.
+fn create_activation_csh_script(
+    file_path: &str,
+    idf_path: &str,
+    idf_version: &str,
+    shell_context: &Context,
+    env_var_pairs: &[(String, String)],
+) -> Result<(), String> {
+    let filename = PathBuf::from(file_path).join(format!("activate_idf_{}.csh", idf_version));
+    let mut tera = Tera::default();
+    tera.add_raw_template("activate_idf_csh", include_str!("../bash_scripts/activate_idf_template.csh"))
+        .map_err(|e| e.to_string())?;
+    let mut context = shell_context.clone();
+    let setenvs: Vec<String> = env_var_pairs
+        .iter()
+        .map(|(key, value)| format!("setenv {} \"{}\"", key, value))
+        .collect();
+    context.insert("env_var_setenvs", &setenvs.join("\n"));
+    context.insert("script_path", &filename.to_string_lossy());
+    let major_minor = utils::parse_cmake_version(idf_path)
+        .map(|(major, minor)| format!("{}.{}", major, minor))
+        .unwrap_or_else(|_| idf_version.to_string());
+    context.insert("idf_major_minor", &major_minor);
+    let rendered = tera.render("activate_idf_csh", &context).map_err(|e| e.to_string())?;
+    fs::write(&filename, rendered).map_err(|e| e.to_string())
+}
+
 // TODO: unify the replace_unescaped_spaces functions
 pub fn replace_unescaped_spaces_posix(input: &str) -> String {
     let mut result = String::with_capacity(input.len());
@@ -345,22 +485,8 @@ fn create_terminal_profile(
     profile
 }
 
-/// Adds or updates an ESP-IDF profile in Windows Terminal settings.
-///
-/// # Parameters
-///
-/// * `profile_script_path` - Path to the PowerShell profile script
-/// * `idf_version` - ESP-IDF version string
-/// * `icon_path` - Optional path to icon file
-///
-/// # Returns
-///
-/// * `Result<String, std::io::Error>` - Success message
-pub fn add_windows_terminal_profile(
-    profile_script_path: &str,
-    idf_version: &str,
-    icon_path: Option<&str>,
-) -> Result<String, std::io::Error> {
+/// Adds `new_profile` to the Windows Terminal settings, replacing a profile with the same GUID.
+fn upsert_windows_terminal_profile(new_profile: Value) -> Result<(), std::io::Error> {
     let settings_path = get_windows_terminal_settings_path()?;
 
     if !settings_path.exists() {
@@ -390,8 +516,6 @@ pub fn add_windows_terminal_profile(
             "profiles.list is not an array"
         ))?;
 
-    // Create new profile
-    let new_profile = create_terminal_profile(profile_script_path, idf_version, icon_path);
     let profile_guid = new_profile["guid"].as_str().unwrap();
 
     // Check if profile already exists and remove it
@@ -414,6 +538,29 @@ pub fn add_windows_terminal_profile(
 
     fs::write(&settings_path, formatted)?;
 
+    Ok(())
+}
+
+/// Adds or updates an ESP-IDF profile in Windows Terminal settings.
+///
+/// # Parameters
+///
+/// * `profile_script_path` - Path to the PowerShell profile script
+/// * `idf_version` - ESP-IDF version string
+/// * `icon_path` - Optional path to icon file
+///
+/// # Returns
+///
+/// * `Result<String, std::io::Error>` - Success message
+pub fn add_windows_terminal_profile(
+    profile_script_path: &str,
+    idf_version: &str,
+    icon_path: Option<&str>,
+) -> Result<String, std::io::Error> {
+    // Create new profile
+    let new_profile = create_terminal_profile(profile_script_path, idf_version, icon_path);
+    upsert_windows_terminal_profile(new_profile)?;
+
     Ok(format!(
         "Windows Terminal profile 'ESP-IDF {}' added successfully.\nRestart Windows Terminal to see the new profile in the dropdown.",
         idf_version
@@ -599,7 +746,7 @@ fn create_powershell_profile(
     export_paths: Vec<String>,
     env_var_pairs: Vec<(String, String)>,
 ) -> Result<String, std::io::Error> {
-    let profile_template = include_str!("../../powershell_scripts/idf_tools_profile_template.ps1");
+    let profile_template = include_str!("../powershell_scripts/idf_tools_profile_template.ps1");
 
     let mut tera = Tera::default();
     if let Err(e) = tera.add_raw_template("powershell_profile", profile_template) {
@@ -611,7 +758,7 @@ fn create_powershell_profile(
     }
     ensure_path(profile_path).expect("Unable to create directory");
     let mut context = Context::new();
-    println!("idf_path: {}", replace_unescaped_spaces_win(idf_path));
+    log::debug!("idf_path: {}", replace_unescaped_spaces_win(idf_path));
     context.insert("idf_path", &replace_unescaped_spaces_win(idf_path));
     context.insert("idf_version", &idf_version);
     context.insert(
@@ -693,14 +840,14 @@ pub fn create_desktop_shortcut(
                     return Err(err);
                 }
             };
-            let icon = include_bytes!("../../icons/eim.ico");
+            let icon = include_bytes!("../icons/eim.ico");
             let mut home = dirs::home_dir().unwrap();
             home.push("Icons");
             let _ = ensure_path(home.to_str().unwrap());
             home.push("eim.ico");
             fs::write(&home, icon).expect("Unable to write file");
             let powershell_script_template =
-                include_str!("../../powershell_scripts/create_desktop_shortcut_template.ps1");
+                include_str!("../powershell_scripts/create_desktop_shortcut_template.ps1");
             // Create a new Tera instance
             let mut tera = Tera::default();
             if let Err(e) = tera.add_raw_template("powershell_script", powershell_script_template) {
@@ -784,6 +931,91 @@ pub fn create_desktop_shortcut_and_terminal_profile(
     Ok(format!("{}\n\n{}", shortcut_result, terminal_result))
 }
 
+const POWERSHELL_BLOCK_START: &str = "# >>> eim Initialize-Idf >>>";
+const POWERSHELL_BLOCK_END: &str = "# <<< eim Initialize-Idf <<<";
+
+/// Returns the `$PROFILE` paths of Windows PowerShell 5 and PowerShell 7.
+fn get_powershell_user_profiles() -> Vec<PathBuf> {
+    match dirs::document_dir() {
+        Some(documents) => vec![
+            documents.join("WindowsPowerShell").join("Microsoft.PowerShell_profile.ps1"),
+            documents.join("PowerShell").join("Microsoft.PowerShell_profile.ps1"),
+        ],
+        None => vec![],
+    }
+}
+
+/// Replaces the `Initialize-Idf` block in the content of a PowerShell profile, or appends it.
+fn merge_powershell_profile(existing: &str, block: &str) -> String {
+    match (existing.find(POWERSHELL_BLOCK_START), existing.find(POWERSHELL_BLOCK_END)) {
+        (Some(start), Some(end)) if start < end => {
+            let end = end + POWERSHELL_BLOCK_END.len();
+            format!("{}{}{}", &existing[..start], block.trim_end(), &existing[end..])
+        }
+        _ if existing.trim().is_empty() => block.to_string(),
+        _ => format!("{}\r\n{}", existing.trim_end(), block),
+    }
+}
+
+/// Installs the `Initialize-Idf` function into the PowerShell profiles of the user.
+///
+/// The function reads `eim_idf.json` when it is called, so it always activates the currently
+/// selected version and does not need to be reinstalled when versions are added or removed.
+///
+/// # Returns
+///
+/// * `Result<Vec<PathBuf>, std::io::Error>` - The profile files which were written
+pub fn install_powershell_integration(eim_idf_json_path: &str) -> Result<Vec<PathBuf>, std::io::Error> {
+    let template = include_str!("../powershell_scripts/initialize_idf_template.ps1");
+    let mut tera = Tera::default();
+    tera.add_raw_template("initialize_idf", template)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+    let mut context = Context::new();
+    context.insert("eim_idf_json", eim_idf_json_path);
+    let block = tera
+        .render("initialize_idf", &context)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?
+        .replace("\r\n", "\n")
+        .replace('\n', "\r\n");
+
+    let mut written = vec![];
+    for profile in get_powershell_user_profiles() {
+        if let Some(parent) = profile.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let existing = fs::read_to_string(&profile).unwrap_or_default();
+        fs::write(&profile, merge_powershell_profile(&existing, &block))?;
+        crate::install_manifest::record_system_change(crate::install_manifest::ManifestEntry::RcFileLine {
+            path: profile.to_string_lossy().into_owned(),
+            line: block.trim_end().to_string(),
+        });
+        info!("Installed Initialize-Idf into {}", profile.display());
+        written.push(profile);
+    }
+    Ok(written)
+}
+
+/// Adds the "ESP-IDF (eim)" Windows Terminal profile, which opens PowerShell with the
+/// selected ESP-IDF version activated by `Initialize-Idf`.
+///
+/// # Returns
+///
+/// * `Result<String, std::io::Error>` - Success message
+pub fn add_windows_terminal_eim_profile(icon_path: Option<&str>) -> Result<String, std::io::Error> {
+    let mut profile = json!({
+        "guid": format!("{{{}}}", generate_guid_from_string("esp-idf-eim")),
+        "name": "ESP-IDF (eim)",
+        "commandline": "powershell.exe -NoExit -ExecutionPolicy Bypass -Command \"Initialize-Idf\"",
+        "hidden": false,
+        "startingDirectory": "%USERPROFILE%"
+    });
+    if let Some(icon) = icon_path {
+        profile["icon"] = json!(icon);
+    }
+    upsert_windows_terminal_profile(profile)?;
+    Ok("Windows Terminal profile 'ESP-IDF (eim)' added successfully.".to_string())
+}
+
 /// Retrieves the path to the local data directory for storing logs.
 ///
 /// This function uses the `dirs` crate to find the appropriate directory for storing logs.
@@ -797,15 +1029,18 @@ pub fn create_desktop_shortcut_and_terminal_profile(
 ///
 pub fn get_log_directory() -> Option<PathBuf> {
     // Use the dirs crate to find the local data directory
-    dirs::data_local_dir().map(|data_dir| {
+    dirs::data_local_dir().and_then(|data_dir| {
         // Create a subdirectory named "logs" within the local data directory
         let log_dir = data_dir.join("eim").join("logs");
 
-        // Attempt to create the log directory
-        std::fs::create_dir_all(&log_dir).expect("Failed to create log directory");
-
-        // Return the path to the log directory
-        log_dir
+        // Attempt to create the log directory, a read-only home only loses the log file
+        match std::fs::create_dir_all(&log_dir) {
+            Ok(()) => Some(log_dir),
+            Err(e) => {
+                eprintln!("Failed to create the log directory {}: {}", log_dir.display(), e);
+                None
+            }
+        }
     })
 }
 /// Verifies the SHA256 checksum of a file against an expected checksum.
@@ -821,28 +1056,23 @@ pub fn get_log_directory() -> Option<PathBuf> {
 /// * `Ok(false)` if the file does not exist or its checksum does not match the expected checksum.
 /// * `Err(io::Error)` if an error occurs while opening or reading the file.
 pub fn verify_file_checksum(expected_checksum: &str, file_path: &str) -> Result<bool, io::Error> {
+    verify_file_checksum_with_progress(expected_checksum, file_path, |_, _| {})
+}
+
+/// Same as [`verify_file_checksum`], reporting `(hashed, total)` bytes while hashing.
+pub fn verify_file_checksum_with_progress<F>(
+    expected_checksum: &str,
+    file_path: &str,
+    progress: F,
+) -> Result<bool, io::Error>
+where
+    F: Fn(u64, u64),
+{
     if !Path::new(file_path).exists() {
         return Ok(false);
     }
 
-    let mut file = File::open(file_path)?;
-
-    let mut hasher = Sha256::new();
-
-    let mut buffer = [0; 1024];
-    loop {
-        let bytes_read = file.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
-        }
-        hasher.update(&buffer[..bytes_read]);
-    }
-
-    // Get the final hash
-    let result = hasher.finalize();
-
-    // Convert the hash to a hexadecimal string
-    let computed_checksum = format!("{:x}", result);
+    let computed_checksum = checksum::sha256_file_with_progress(Path::new(file_path), progress)?;
 
     // Compare the computed checksum with the expected checksum
     Ok(computed_checksum == expected_checksum)
@@ -954,13 +1184,17 @@ fn get_openocd_scripts_folder(idf_tools_path: &PathBuf) -> Result<String, std::i
     Ok(result[0].clone())
 }
 
+/// Progress of a download, and of the tool setup around it. The tools are extracted in
+/// parallel, so the events starting and ending the setup of a tool carry its name.
 pub enum DownloadProgress {
-    Start(String),
+    Start(String, String), // (tool, url)
     Progress(u64, u64), // (downloaded, total)
     Downloaded(String),
+    Hashing(u64, u64), // (hashed, total)
     Verified(String),
-    Extracted(String, String), // (url, destination_path)
-    Complete,
+    Extracting(u64, u64), // (archive bytes read, archive size)
+    Extracted(String, String, String), // (tool, url, destination_path)
+    Complete(String), // tool
     Error(String),
 }
 pub async fn download_file(
@@ -976,94 +1210,105 @@ pub async fn download_file(
     ).await
 }
 
+/// Downloads `url` into `destination_path`, retrying failed and stalled downloads according to
+/// the retry policy of its host, see [`retry_policy`].
 pub async fn download_file_and_rename(
     url: &str,
     destination_path: &str,
     progress_sender: Option<Sender<DownloadProgress>>,
     new_name: Option<&str>,
 ) -> Result<(), std::io::Error> {
-    // Create a new HTTP client
-    let client = Client::new();
-
-    // Send a GET request to the specified URL
-    let mut response = client
-        .get(url)
-        .send()
-        .await
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-    if !response.status().is_success() {
-      if let Some(sender) = &progress_sender {
-        let _ = sender.send(DownloadProgress::Error(format!(
-          "HTTP error: {}",
-          response.status()
-        )));
-      }
-      return Err(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        format!("HTTP error: {}", response.status()),
-      ));
-    }
-
-    // Get the total size of the file being downloaded
-    let total_size = response.content_length().ok_or_else(|| {
-      if let Some(sender) = &progress_sender {
-        let _ = sender.send(DownloadProgress::Error(
-          "Failed to get content length".into(),
-        ));
-      }
-      std::io::Error::new(std::io::ErrorKind::Other, "Failed to get content length")
-    })?;
-    log::debug!("Downloading {} to {}", url, destination_path);
+    let url = &url_rewrite::rewrite(url);
+    let policy = retry_policy::policy_for(url);
+    let mut attempt = 1;
+    let mut proxy_refreshes = 0;
+    let mut attempts = Vec::new();
+    loop {
+        cancellation::check().map_err(|e| std::io::Error::new(std::io::ErrorKind::Interrupted, e))?;
+        let started = std::time::Instant::now();
+        let proxy_generation = proxy_auth::generation();
+        // a cancel drops the download, which also kills a curl or aria2c backend
+        let result = tokio::select! {
+            result = download_attempt(url, destination_path, &progress_sender, new_name, policy.stall_timeout) => result,
+            _ = cancellation::cancelled() => {
+                return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "The download was cancelled"));
+            }
+        };
+        attempts.push(download_telemetry::DownloadAttempt {
+            seconds: started.elapsed().as_secs_f64(),
+            error: result.as_ref().err().map(|failure| failure.error.to_string()),
+        });
+        match result {
+            Ok(()) => {
+                let filename = new_name.or_else(|| Path::new(url).file_name().and_then(|n| n.to_str())).unwrap_or_default();
+                let bytes = fs::metadata(Path::new(destination_path).join(filename)).map_or(0, |m| m.len());
+                download_telemetry::record(url, attempts, Some(bytes));
+                return Ok(());
+            }
+            // paused until the proxy credentials are refreshed, which doesn't count as an attempt
+            Err(failure)
+                if failure.proxy_auth
+                    && proxy_refreshes < proxy_auth::MAX_REFRESHES
+                    && proxy_auth::refresh(proxy_generation).await =>
+            {
+                info!("Resuming the download of {} with the new proxy credentials", url);
+                proxy_refreshes += 1;
+            }
+            Err(failure) if failure.retryable && policy.should_retry(attempt) => {
+                let delay = policy.delay(attempt);
+                warn!(
+                    "Download of {} failed (attempt {}/{}), retrying in {}s: {}",
+                    url,
+                    attempt,
+                    policy.max_attempts,
+                    delay.as_secs(),
+                    failure.error
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(failure) => {
+                download_telemetry::record(url, attempts, None);
+                if let Some(sender) = &progress_sender {
+                    let _ = sender.send(DownloadProgress::Error(failure.error.to_string()));
+                }
+                return Err(failure.error);
+            }
+        }
+    }
+}
 
+/// One download attempt with the configured backend, see [`download_backend`].
+async fn download_attempt(
+    url: &str,
+    destination_path: &str,
+    progress_sender: &Option<Sender<DownloadProgress>>,
+    new_name: Option<&str>,
+    stall_timeout: Option<std::time::Duration>,
+) -> Result<(), DownloadFailure> {
     let filename = if let Some(new_name) = new_name {
       new_name.to_string()
     } else {
       // Use the last part of the URL as the filename
       Path::new(&url).file_name().unwrap().to_str().unwrap().to_string()
     };
-    log::debug!(
-        "Filename: {} and destination: {}",
-        &filename,
-        destination_path
-    );
-    // Create a new file at the specified destination path
-    let mut file = File::create(Path::new(&destination_path).join(Path::new(&filename)))?;
-    log::debug!("Created file at {}", destination_path);
-
-    // Initialize the amount downloaded
-    let mut downloaded: u64 = 0;
-
-    // Download the file in chunks
-    while let Some(chunk) = response
-        .chunk()
-        .await
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
-    {
-        // Update the amount downloaded
-        downloaded += chunk.len() as u64;
-
-        // Write the chunk to the file
-        file.write_all(&chunk)?;
-
-        // Call the progress callback function
-        if let Some(sender) = &progress_sender {
-            if let Err(e) = sender.send(DownloadProgress::Progress(downloaded, total_size)) {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Failed to send progress: {}", e),
-                ));
-            }
-        }
-    }
-    if let Some(sender) = &progress_sender {
+    let destination = Path::new(&destination_path).join(Path::new(&filename));
+    let backend = download_backend::current();
+    log::debug!("Downloading {} to {} with {}", url, destination.display(), backend.name());
+    let request = download_backend::DownloadRequest {
+        url,
+        destination: &destination,
+        progress_sender,
+        stall_timeout,
+    };
+    backend.download(&request).await?;
+
+    if let Some(sender) = progress_sender {
         // Send a completion message
-        if let Err(e) = sender.send(DownloadProgress::Complete) {
+        if let Err(e) = sender.send(DownloadProgress::Downloaded(url.to_string())) {
             warn!("Failed to send completion: {}", e);
         }
     }
-
-    // Return Ok(()) if the download was successful
     Ok(())
 }
 
@@ -1077,6 +1322,47 @@ pub enum DecompressionError {
     UnsupportedFormat,
 }
 
+/// Reader of an archive file which reports the position in it, the decompressors read the
+/// archive once from start to end, apart from the zip central directory at the end.
+struct ProgressReader<'a> {
+    file: File,
+    position: u64,
+    size: u64,
+    on_progress: &'a dyn Fn(u64, u64),
+    stop: &'a std::sync::atomic::AtomicBool,
+}
+
+impl<'a> ProgressReader<'a> {
+    fn open(
+        path: &Path,
+        on_progress: &'a dyn Fn(u64, u64),
+        stop: &'a std::sync::atomic::AtomicBool,
+    ) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { file, position: 0, size, on_progress, stop })
+    }
+}
+
+impl Read for ProgressReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.stop.load(std::sync::atomic::Ordering::SeqCst) || cancellation::is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "The extraction was stopped"));
+        }
+        let read = self.file.read(buf)?;
+        self.position += read as u64;
+        (self.on_progress)(self.position, self.size);
+        Ok(read)
+    }
+}
+
+impl Seek for ProgressReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.file.seek(pos)?;
+        Ok(self.position)
+    }
+}
+
 /// Decompresses an archive file into the specified destination directory.
 ///
 /// # Parameters
@@ -1099,6 +1385,20 @@ pub enum DecompressionError {
 pub fn decompress_archive(
     archive_path: &str,
     destination_path: &str,
+) -> Result<(), DecompressionError> {
+    let stop = std::sync::atomic::AtomicBool::new(false);
+    decompress_archive_with_progress(archive_path, destination_path, &|_, _| {}, &stop)
+}
+
+/// Like [`decompress_archive`], reporting how much of the archive is read as `(read, size)`.
+///
+/// The extraction fails with [`io::ErrorKind::Interrupted`] once `stop` is set or the
+/// operation is cancelled.
+pub fn decompress_archive_with_progress(
+    archive_path: &str,
+    destination_path: &str,
+    on_progress: &dyn Fn(u64, u64),
+    stop: &std::sync::atomic::AtomicBool,
 ) -> Result<(), DecompressionError> {
     let archive_path = Path::new(&archive_path);
     let destination_path = Path::new(&destination_path);
@@ -1108,20 +1408,20 @@ pub fn decompress_archive(
     }
 
     let result = match archive_path.extension().and_then(|ext| ext.to_str()) {
-        Some("zip") | Some("ZIP") => decompress_zip(archive_path, destination_path),
-        Some("tar") | Some("TAR") => decompress_tar(archive_path, destination_path),
+        Some("zip") | Some("ZIP") => decompress_zip(archive_path, destination_path, on_progress, stop),
+        Some("tar") | Some("TAR") => decompress_tar(archive_path, destination_path, on_progress, stop),
         Some("gz") | Some("tgz") | Some("GZ") | Some("TGZ") => {
             if archive_path.to_str().unwrap_or("").ends_with(".tar.gz")
                 || archive_path.extension().unwrap() == "tgz"
             {
-                decompress_tar_gz(archive_path, destination_path)
+                decompress_tar_gz(archive_path, destination_path, on_progress, stop)
             } else {
                 Err(DecompressionError::UnsupportedFormat)
             }
         }
         Some("xz") => {
             if archive_path.to_str().unwrap_or("").ends_with(".tar.xz") {
-                decompress_tar_xz(archive_path, destination_path)
+                decompress_tar_xz(archive_path, destination_path, on_progress, stop)
             } else {
                 Err(DecompressionError::UnsupportedFormat)
             }
@@ -1156,7 +1456,12 @@ pub fn decompress_archive(
     }
 }
 
-fn decompress_zip(archive_path: &Path, destination_path: &Path) -> Result<(), DecompressionError> {
+fn decompress_zip(
+    archive_path: &Path,
+    destination_path: &Path,
+    on_progress: &dyn Fn(u64, u64),
+    stop: &std::sync::atomic::AtomicBool,
+) -> Result<(), DecompressionError> {
     log::info!(
         "Decompressing {} to {}",
         archive_path.display(),
@@ -1173,7 +1478,7 @@ fn decompress_zip(archive_path: &Path, destination_path: &Path) -> Result<(), De
 
     // First, try using ZipArchive for all platforms
     let zip_result = (|| {
-        let file = File::open(archive_path)?;
+        let file = ProgressReader::open(archive_path, on_progress, stop)?;
         let mut archive = ZipArchive::new(file)?;
 
         for i in 0..archive.len() {
@@ -1200,7 +1505,7 @@ fn decompress_zip(archive_path: &Path, destination_path: &Path) -> Result<(), De
 
     // If ZipArchive failed and we're on Windows, fall back to PowerShell
     if let Err(err) = zip_result {
-        if std::env::consts::OS == "windows" {
+        if std::env::consts::OS == "windows" && !stop.load(std::sync::atomic::Ordering::SeqCst) {
             log::warn!(
                 "ZipArchive decompression failed: {}. Falling back to PowerShell approach.",
                 err
@@ -1267,10 +1572,15 @@ fn decompress_zip(archive_path: &Path, destination_path: &Path) -> Result<(), De
 /// # Return Value
 ///
 /// * `Result<(), DecompressionError>`: On success, returns `Ok(())`. On error, returns a `DecompressionError` indicating the cause of the error.
-fn decompress_tar(archive_path: &Path, destination_path: &Path) -> Result<(), DecompressionError> {
-    let file = File::open(archive_path)?;
+fn decompress_tar(
+    archive_path: &Path,
+    destination_path: &Path,
+    on_progress: &dyn Fn(u64, u64),
+    stop: &std::sync::atomic::AtomicBool,
+) -> Result<(), DecompressionError> {
+    let file = ProgressReader::open(archive_path, on_progress, stop)?;
     let mut archive = Archive::new(file);
-    archive.unpack(destination_path)?;
+    crate::links::unpack_tar(&mut archive, destination_path)?;
     Ok(())
 }
 
@@ -1287,11 +1597,13 @@ fn decompress_tar(archive_path: &Path, destination_path: &Path) -> Result<(), De
 fn decompress_tar_gz(
     archive_path: &Path,
     destination_path: &Path,
+    on_progress: &dyn Fn(u64, u64),
+    stop: &std::sync::atomic::AtomicBool,
 ) -> Result<(), DecompressionError> {
-    let file = File::open(archive_path)?;
+    let file = ProgressReader::open(archive_path, on_progress, stop)?;
     let gz = GzDecoder::new(file);
     let mut archive = Archive::new(gz);
-    archive.unpack(destination_path)?;
+    crate::links::unpack_tar(&mut archive, destination_path)?;
     Ok(())
 }
 
@@ -1308,19 +1620,24 @@ fn decompress_tar_gz(
 fn decompress_tar_xz(
     archive_path: &Path,
     destination_path: &Path,
+    on_progress: &dyn Fn(u64, u64),
+    stop: &std::sync::atomic::AtomicBool,
 ) -> Result<(), DecompressionError> {
-    let file = File::open(archive_path)?;
+    let file = ProgressReader::open(archive_path, on_progress, stop)?;
     let mut reader = BufReader::new(file);
     let mut decompressed_data = Vec::new();
 
     // First decompress the XZ data
     lzma_rs::xz_decompress(&mut reader, &mut decompressed_data)
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    if stop.load(std::sync::atomic::Ordering::SeqCst) {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "The extraction was stopped").into());
+    }
 
     // Then process the tar archive from the decompressed data
     let cursor = std::io::Cursor::new(decompressed_data);
     let mut archive = Archive::new(cursor);
-    archive.unpack(destination_path)?;
+    crate::links::unpack_tar(&mut archive, destination_path)?;
     Ok(())
 }
 
@@ -1545,6 +1862,26 @@ pub fn to_absolute_path(path: &str) -> Result<String, Box<dyn std::error::Error>
     Ok(resolved_path.to_string_lossy().to_string())
 }
 
+/// Returns the environment variables written to the activation script of an installation
+/// when `single_version_post_install` is called without explicit variables.
+pub fn default_environment_variables(
+    tool_install_directory: &str,
+    idf_path: &str,
+    idf_python_env_path: Option<&str>,
+) -> Vec<(String, String)> {
+    let mut env_vars = setup_environment_variables(
+        &PathBuf::from(tool_install_directory),
+        &PathBuf::from(idf_path),
+    )
+    .unwrap_or_default();
+    env_vars.push((
+        // todo: move to setup_environment_variables
+        "IDF_PYTHON_ENV_PATH".to_string(),
+        idf_python_env_path.unwrap_or_default().to_string(),
+    ));
+    env_vars
+}
+
 /// Performs post-installation tasks for a single version of ESP-IDF.
 ///
 /// This function creates a desktop shortcut on Windows systems and generates an activation shell script
@@ -1569,19 +1906,7 @@ pub fn single_version_post_install(
 ) {
     let mut env_vars = match env_vars {
         Some(vars) => vars,
-        None => {
-          let mut env_vars = setup_environment_variables(
-            &PathBuf::from(tool_install_directory),
-            &PathBuf::from(idf_path),
-        )
-        .unwrap_or_default();
-        env_vars.push((
-          // todo: move to setup_environment_variables
-          "IDF_PYTHON_ENV_PATH".to_string(),
-          idf_python_env_path.unwrap_or_default().to_string(),
-        ));
-        env_vars
-      }
+        None => default_environment_variables(tool_install_directory, idf_path, idf_python_env_path),
     };
 
 
@@ -1733,13 +2058,23 @@ pub fn get_drivers_list() -> Vec<WindowsDriver> {
 }
 
 pub async fn install_drivers() -> Result<()> {
+    install_selected_drivers(None).await
+}
+
+/// Downloads, verifies and installs the Windows USB drivers.
+///
+/// If `only` is provided, just the drivers with matching names are installed
+/// (see `drivers::detect_devices_missing_drivers`).
+pub async fn install_selected_drivers(only: Option<&[String]>) -> Result<()> {
     if std::env::consts::OS != "windows" {
         return Ok(());
     }
 
     use idf_env::driver::install_driver_res;
 
-    let drivers = get_drivers_list();
+    let drivers = get_drivers_list()
+        .into_iter()
+        .filter(|driver| only.map_or(true, |names| names.contains(&driver.name)));
     let mut all_success = true;
     for driver in drivers {
         let temp_directory = TempDir::new()?;
@@ -1763,6 +2098,7 @@ pub async fn install_drivers() -> Result<()> {
         let zip_path = temp_dir.join(filename);
         if !zip_path.exists() {
             error!("Driver file not found: {}", zip_path.display());
+            all_success = false;
             continue;
         }
 
@@ -1775,6 +2111,7 @@ pub async fn install_drivers() -> Result<()> {
             }
             Err(err) => {
                 error!("Failed to extract driver {}: {}", driver.name, err);
+                all_success = false;
                 continue;
             }
         }
@@ -1782,6 +2119,12 @@ pub async fn install_drivers() -> Result<()> {
         let driver_path = temp_dir.join(&driver.inf_path);
         if !driver_path.exists() {
             error!("Driver file not found: {}", driver_path.display());
+            all_success = false;
+            continue;
+        }
+        if let Err(err) = drivers::verify_driver_signature(driver_path.parent().unwrap_or(&temp_dir)) {
+            error!("Refusing to install driver {}: {}", driver.name, err);
+            all_success = false;
             continue;
         }
         match install_driver_res(driver_path.to_str().unwrap().to_string()) {
@@ -2051,6 +2394,19 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_merge_powershell_profile_replaces_block() {
+        let block = format!("{}\r\nfunction Initialize-Idf {{}}\r\n{}\r\n", POWERSHELL_BLOCK_START, POWERSHELL_BLOCK_END);
+        let first = merge_powershell_profile("Set-PSReadLineOption -EditMode Emacs\r\n", &block);
+        assert!(first.starts_with("Set-PSReadLineOption -EditMode Emacs\r\n# >>> eim"));
+
+        let updated = block.replace("{}", "{ 'v2' }");
+        let second = merge_powershell_profile(&format!("{}\r\nImport-Module posh-git", first.trim_end()), &updated);
+        assert_eq!(second.matches(POWERSHELL_BLOCK_START).count(), 1);
+        assert!(second.contains("{ 'v2' }"));
+        assert!(second.ends_with("Import-Module posh-git"));
+    }
+
     #[test]
     fn test_get_elf_rom_dir_with_nonexistent_directory() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = TempDir::new()?;
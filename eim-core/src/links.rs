@@ -0,0 +1,331 @@
+use log::{debug, info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use crate::settings::Settings;
+
+/// How the installer creates links, e.g. the symlinks contained in tool archives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStrategy {
+    /// Symlink if allowed, then junction (directories on Windows), then copy
+    #[default]
+    Auto,
+    Symlink,
+    /// NTFS junction for directories, falls back to copy for files
+    Junction,
+    Copy,
+}
+
+impl FromStr for LinkStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(LinkStrategy::Auto),
+            "symlink" => Ok(LinkStrategy::Symlink),
+            "junction" => Ok(LinkStrategy::Junction),
+            "copy" => Ok(LinkStrategy::Copy),
+            _ => Err(format!(
+                "Unknown link strategy '{}', expected one of: auto, symlink, junction, copy",
+                s
+            )),
+        }
+    }
+}
+
+/// The kind of link which was actually created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreatedLink {
+    Symlink,
+    Junction,
+    Copy,
+}
+
+static LINK_STRATEGY: Lazy<RwLock<LinkStrategy>> = Lazy::new(|| RwLock::new(LinkStrategy::Auto));
+static SYMLINKS_ALLOWED: OnceCell<bool> = OnceCell::new();
+
+pub fn set_link_strategy(strategy: LinkStrategy) {
+    if let Ok(mut guard) = LINK_STRATEGY.write() {
+        *guard = strategy;
+    }
+}
+
+pub fn get_link_strategy() -> LinkStrategy {
+    LINK_STRATEGY.read().map(|s| *s).unwrap_or_default()
+}
+
+/// Updates the link strategy from the `link_strategy` setting.
+pub fn configure_from_settings(settings: &Settings) {
+    let strategy = match settings.link_strategy.as_deref().map(LinkStrategy::from_str) {
+        Some(Ok(strategy)) => strategy,
+        Some(Err(e)) => {
+            warn!("{}, using auto", e);
+            LinkStrategy::Auto
+        }
+        None => LinkStrategy::Auto,
+    };
+    set_link_strategy(strategy);
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path, _target_is_dir: bool) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &Path, link: &Path, target_is_dir: bool) -> io::Result<()> {
+    if target_is_dir {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+/// Checks whether this process may create symlinks.
+///
+/// On Windows this requires either an elevated process or Developer Mode. Rather than
+/// checking both, a symlink is created in the temp directory once and the result is cached.
+pub fn symlinks_allowed() -> bool {
+    *SYMLINKS_ALLOWED.get_or_init(|| {
+        if std::env::consts::OS != "windows" {
+            return true;
+        }
+        let probe_dir = std::env::temp_dir().join(format!("eim_link_probe_{}", std::process::id()));
+        let _ = fs::create_dir_all(&probe_dir);
+        let target = probe_dir.join("target");
+        let link = probe_dir.join("link");
+        let allowed = fs::write(&target, b"probe").is_ok() && create_symlink(&target, &link, false).is_ok();
+        let _ = fs::remove_dir_all(&probe_dir);
+        info!(
+            "Symlink creation is {} (elevated: {})",
+            if allowed { "allowed" } else { "not allowed" },
+            crate::utils::is_elevated().unwrap_or(false)
+        );
+        allowed
+    })
+}
+
+fn create_junction(target: &Path, link: &Path) -> io::Result<()> {
+    let output = crate::command_executor::execute_command(
+        "cmd",
+        &["/C", "mklink", "/J", &link.to_string_lossy(), &target.to_string_lossy()],
+    )?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+    }
+}
+
+fn copy_target(target: &Path, link: &Path) -> io::Result<()> {
+    if target.is_dir() {
+        fs::create_dir_all(link)?;
+        crate::utils::copy_dir_contents(target, link)
+    } else {
+        fs::copy(target, link).map(|_| ())
+    }
+}
+
+/// Creates `link` pointing to `target` using the configured strategy.
+///
+/// `target` may be relative to the directory containing `link`, as stored in archives.
+/// With the `auto` strategy, a symlink is tried first, then a junction for directories
+/// on Windows, and finally the target is copied, so a missing privilege never fails the
+/// installation.
+pub fn create_link(target: &Path, link: &Path) -> io::Result<CreatedLink> {
+    create_link_with_strategy(target, link, get_link_strategy())
+}
+
+pub fn create_link_with_strategy(target: &Path, link: &Path, strategy: LinkStrategy) -> io::Result<CreatedLink> {
+    let resolved_target = if target.is_absolute() {
+        target.to_path_buf()
+    } else {
+        link.parent().unwrap_or(Path::new("")).join(target)
+    };
+    let target_is_dir = resolved_target.is_dir();
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let try_symlink = matches!(strategy, LinkStrategy::Auto | LinkStrategy::Symlink);
+    if try_symlink && (strategy == LinkStrategy::Symlink || symlinks_allowed()) {
+        match create_symlink(target, link, target_is_dir) {
+            Ok(()) => return Ok(CreatedLink::Symlink),
+            Err(e) if strategy == LinkStrategy::Symlink => return Err(e),
+            Err(e) => debug!("Symlink {} failed: {}, trying fallback", link.display(), e),
+        }
+    }
+
+    if std::env::consts::OS == "windows"
+        && target_is_dir
+        && matches!(strategy, LinkStrategy::Auto | LinkStrategy::Junction)
+    {
+        match create_junction(&resolved_target, link) {
+            Ok(()) => return Ok(CreatedLink::Junction),
+            Err(e) => debug!("Junction {} failed: {}, copying instead", link.display(), e),
+        }
+    }
+
+    if !resolved_target.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Link target {} does not exist", resolved_target.display()),
+        ));
+    }
+    copy_target(&resolved_target, link)?;
+    Ok(CreatedLink::Copy)
+}
+
+/// Rejects absolute paths and `..` components in archive entries.
+fn is_safe_relative(path: &Path) -> bool {
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Whether the symlink at `link` (relative to the archive root) pointing at `target` stays inside it.
+///
+/// Absolute targets are refused, and `..` may not climb above the root.
+fn target_stays_inside(link: &Path, target: &Path) -> bool {
+    let mut depth: usize = 0;
+    let parent = link.parent().unwrap_or(Path::new(""));
+    for component in parent.components().chain(target.components()) {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(d) => depth = d,
+                None => return false,
+            },
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+/// Unpacks a tar archive, creating the symlinks it contains with the configured link strategy.
+///
+/// Links are created after all regular entries were unpacked, so their targets exist
+/// when a junction or a copy has to be made instead of a symlink.
+pub fn unpack_tar<R: Read>(archive: &mut tar::Archive<R>, destination: &Path) -> io::Result<()> {
+    fs::create_dir_all(destination)?;
+    let mut links: Vec<(PathBuf, PathBuf)> = Vec::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_symlink() {
+            let path = entry.path()?.into_owned();
+            let target = entry
+                .link_name()?
+                .ok_or_else(|| io::Error::other(format!("Symlink {} has no target", path.display())))?
+                .into_owned();
+            if !is_safe_relative(&path) {
+                warn!("Skipping symlink with unsafe path {}", path.display());
+                continue;
+            }
+            if !target_stays_inside(&path, &target) {
+                warn!("Skipping symlink {} pointing outside the archive: {}", path.display(), target.display());
+                continue;
+            }
+            links.push((destination.join(path), target));
+        } else {
+            entry.unpack_in(destination)?;
+        }
+    }
+
+    // Links pointing to other links need those to exist first, retry until no progress is made
+    let mut pending = links;
+    loop {
+        let mut failed = Vec::new();
+        let before = pending.len();
+        for (link, target) in pending {
+            if fs::symlink_metadata(&link).is_ok() {
+                continue;
+            }
+            match create_link(&target, &link) {
+                Ok(kind) => debug!("Created {:?} {} -> {}", kind, link.display(), target.display()),
+                Err(e) => failed.push((link, target, e)),
+            }
+        }
+        if failed.is_empty() {
+            return Ok(());
+        }
+        if failed.len() == before {
+            let (link, target, error) = failed.remove(0);
+            return Err(io::Error::new(
+                error.kind(),
+                format!("Failed to create link {} -> {}: {}", link.display(), target.display(), error),
+            ));
+        }
+        pending = failed.into_iter().map(|(link, target, _)| (link, target)).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_link_strategy() {
+        assert_eq!(LinkStrategy::from_str("Junction").unwrap(), LinkStrategy::Junction);
+        assert!(LinkStrategy::from_str("hardlink").is_err());
+    }
+
+    #[test]
+    fn test_copy_strategy() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("bin");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("tool"), "binary").unwrap();
+        let link = temp_dir.path().join("bin_link");
+
+        let created = create_link_with_strategy(Path::new("bin"), &link, LinkStrategy::Copy).unwrap();
+        assert_eq!(created, CreatedLink::Copy);
+        assert!(link.join("tool").exists());
+        assert!(!fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    }
+
+    #[test]
+    fn test_unsafe_paths_are_rejected() {
+        assert!(is_safe_relative(Path::new("tools/bin/gcc")));
+        assert!(!is_safe_relative(Path::new("../outside")));
+        assert!(!is_safe_relative(Path::new("/etc/passwd")));
+    }
+
+    #[test]
+    fn test_link_targets_outside_are_rejected() {
+        assert!(target_stays_inside(Path::new("tools/bin/cc"), Path::new("gcc")));
+        assert!(target_stays_inside(Path::new("tools/bin/cc"), Path::new("../lib/../bin/gcc")));
+        assert!(!target_stays_inside(Path::new("tools/bin/cc"), Path::new("../../../etc")));
+        assert!(!target_stays_inside(Path::new("cc"), Path::new("/usr/bin/gcc")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unpack_tar_skips_escaping_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, target) in [("inside", "data"), ("escape", "../../etc"), ("absolute", "/etc")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, name, target).unwrap();
+        }
+        let mut header = tar::Header::new_gnu();
+        header.set_size(1);
+        builder.append_data(&mut header, "data", &b"x"[..]).unwrap();
+        let bytes = builder.into_inner().unwrap();
+
+        let destination = temp_dir.path().join("out");
+        unpack_tar(&mut tar::Archive::new(&bytes[..]), &destination).unwrap();
+        assert!(destination.join("inside").exists());
+        assert!(fs::symlink_metadata(destination.join("escape")).is_err());
+        assert!(fs::symlink_metadata(destination.join("absolute")).is_err());
+    }
+}
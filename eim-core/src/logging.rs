@@ -0,0 +1,341 @@
+use anyhow::{anyhow, Result};
+use log::LevelFilter;
+use log4rs::append::console::ConsoleAppender;
+use log4rs::append::file::FileAppender;
+use log4rs::append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRoller;
+use log4rs::append::rolling_file::policy::compound::trigger::size::SizeTrigger;
+use log4rs::append::rolling_file::policy::compound::CompoundPolicy;
+use log4rs::append::rolling_file::RollingFileAppender;
+use log4rs::append::Append;
+use log4rs::config::{Appender, Logger, Root};
+use log4rs::encode::json::JsonEncoder;
+use log4rs::encode::pattern::PatternEncoder;
+use log4rs::filter::threshold::ThresholdFilter;
+use once_cell::sync::Lazy;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Log file of the CLI in the log directory
+pub const CLI_LOG_FILE_NAME: &str = "eim.log";
+/// Log file of the GUI in the log directory
+pub const GUI_LOG_FILE_NAME: &str = "eim_gui.log";
+/// Size at which the log file is rotated
+pub const MAX_LOG_FILE_SIZE: u64 = 10 * 1024 * 1024;
+/// Number of rotated log files kept next to the current one (`eim.log.1` is the newest)
+pub const ROTATED_LOG_FILES: u32 = 5;
+/// Folder in the log directory with one log file per install, upgrade or repair run
+pub const RUN_LOGS_FOLDER_NAME: &str = "runs";
+/// Number of run logs kept, older ones are deleted when a new run starts
+pub const RUN_LOGS_KEPT: usize = 20;
+/// Modules silenced unless a level is configured for them
+const QUIET_MODULES: &[&str] = &["lnk"];
+
+/// Log levels parsed from a spec like `info,idf_im_lib::idf_tools=trace,gix=warn`.
+///
+/// A bare level sets the default, `module=level` the level of a module and its submodules.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogLevels {
+    pub default: Option<LevelFilter>,
+    pub modules: Vec<(String, LevelFilter)>,
+}
+
+impl FromStr for LogLevels {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let mut levels = LogLevels::default();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let parse_level = |level: &str| {
+                LevelFilter::from_str(level.trim())
+                    .map_err(|_| anyhow!("Invalid log level '{}' in '{}'", level, spec))
+            };
+            match directive.split_once('=') {
+                Some((module, level)) => levels
+                    .modules
+                    .push((module.trim().to_string(), parse_level(level)?)),
+                None => levels.default = Some(parse_level(directive)?),
+            }
+        }
+        Ok(levels)
+    }
+}
+
+/// Creates an appender added next to the console and the file, e.g. the RPC stream of `eim serve`.
+pub type AppenderFactory = fn() -> Box<dyn Append>;
+
+/// How the CLI or the GUI logs; kept after initialization so the levels can be changed later.
+#[derive(Clone)]
+pub struct LoggingOptions {
+    pub log_file: PathBuf,
+    /// Threshold of the console output, `None` to not log to the console
+    pub console_level: Option<LevelFilter>,
+    pub levels: LogLevels,
+    pub extra_appenders: Vec<(String, LevelFilter, AppenderFactory)>,
+    /// Log file of the current install, upgrade or repair run, see [`start_run_log`]
+    pub run_log: Option<PathBuf>,
+}
+
+impl LoggingOptions {
+    /// Logs to `file_name` in the log directory unless `log_file` is given.
+    pub fn new(file_name: &str, log_file: Option<PathBuf>) -> Self {
+        let log_file = log_file.unwrap_or_else(|| {
+            crate::get_log_directory()
+                .map(|dir| dir.join(file_name))
+                .unwrap_or_else(|| {
+                    eprintln!("Failed to get log directory, using default {}", file_name);
+                    PathBuf::from(file_name)
+                })
+        });
+        Self {
+            log_file,
+            console_level: Some(LevelFilter::Info),
+            levels: LogLevels::default(),
+            extra_appenders: Vec::new(),
+            run_log: None,
+        }
+    }
+}
+
+static LOGGING: Lazy<Mutex<Option<(log4rs::Handle, LoggingOptions)>>> =
+    Lazy::new(|| Mutex::new(None));
+
+fn rolling_file_appender(log_file: &Path) -> Result<RollingFileAppender> {
+    let roller = FixedWindowRoller::builder()
+        .base(1)
+        .build(&format!("{}.{{}}", log_file.to_string_lossy()), ROTATED_LOG_FILES)
+        .map_err(|e| anyhow!("Failed to build log roller: {}", e))?;
+    let policy = CompoundPolicy::new(
+        Box::new(SizeTrigger::new(MAX_LOG_FILE_SIZE)),
+        Box::new(roller),
+    );
+    // JSON lines, so the log viewer and support bundles can filter by level and module
+    RollingFileAppender::builder()
+        .encoder(Box::new(JsonEncoder::new()))
+        .build(log_file, Box::new(policy))
+        .map_err(|e| anyhow!("Failed to open log file {}: {}", log_file.display(), e))
+}
+
+/// Set once the missing log file was reported, the configuration is built again on changes
+static LOG_FILE_WARNED: AtomicBool = AtomicBool::new(false);
+
+fn build_config(options: &LoggingOptions) -> Result<log4rs::Config> {
+    let mut config = log4rs::Config::builder();
+    let mut root = Root::builder();
+    // without write access, e.g. to the home folder on a lab machine, eim still works and
+    // only logs to the console
+    match rolling_file_appender(&options.log_file) {
+        Ok(file) => {
+            config = config.appender(Appender::builder().build("file", Box::new(file)));
+            root = root.appender("file");
+        }
+        Err(e) => {
+            if !LOG_FILE_WARNED.swap(true, Ordering::Relaxed) {
+                eprintln!("{}, logging to the console only", e);
+            }
+        }
+    }
+    if let Some(console_level) = options.console_level {
+        let stdout = ConsoleAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{d} - {l} - {m}\n")))
+            .build();
+        config = config.appender(
+            Appender::builder()
+                .filter(Box::new(ThresholdFilter::new(console_level)))
+                .build("stdout", Box::new(stdout)),
+        );
+        root = root.appender("stdout");
+    }
+    if let Some(run_log) = &options.run_log {
+        let file = FileAppender::builder()
+            .encoder(Box::new(JsonEncoder::new()))
+            .build(run_log)
+            .map_err(|e| anyhow!("Failed to open log file {}: {}", run_log.display(), e))?;
+        config = config.appender(Appender::builder().build("run", Box::new(file)));
+        root = root.appender("run");
+    }
+    for (name, level, factory) in &options.extra_appenders {
+        config = config.appender(
+            Appender::builder()
+                .filter(Box::new(ThresholdFilter::new(*level)))
+                .build(name, factory()),
+        );
+        root = root.appender(name);
+    }
+    // a module configured twice gets the level given last
+    let mut modules: Vec<(&str, LevelFilter)> = Vec::new();
+    for (module, level) in options.levels.modules.iter().rev() {
+        if !modules.iter().any(|(m, _)| *m == module.as_str()) {
+            modules.push((module.as_str(), *level));
+        }
+    }
+    for module in QUIET_MODULES {
+        if !modules.iter().any(|(m, _)| m == module) {
+            modules.push((*module, LevelFilter::Off));
+        }
+    }
+    for (module, level) in modules {
+        config = config.logger(Logger::builder().build(module, level));
+    }
+    config
+        .build(root.build(options.levels.default.unwrap_or(LevelFilter::Trace)))
+        .map_err(|e| anyhow!("Failed to build the logging configuration: {}", e))
+}
+
+/// Sets up logging to the console and to a rotating JSON log file, shared by the CLI and the GUI.
+pub fn init_logging(options: LoggingOptions) -> Result<()> {
+    let handle = log4rs::init_config(build_config(&options)?)
+        .map_err(|e| anyhow!("Failed to initialize logger: {}", e))?;
+    *LOGGING.lock().unwrap() = Some((handle, options));
+    Ok(())
+}
+
+/// Changes the log levels after initialization, e.g. once the `log_level` setting was loaded.
+///
+/// Does nothing if logging was not initialized with `init_logging`.
+pub fn set_log_levels(levels: LogLevels) -> Result<()> {
+    let mut logging = LOGGING.lock().unwrap();
+    let Some((handle, options)) = logging.as_mut() else {
+        return Ok(());
+    };
+    options.levels = levels;
+    handle.set_config(build_config(options)?);
+    Ok(())
+}
+
+/// Returns the folder with the logs of the single runs.
+pub fn get_run_logs_directory() -> Option<PathBuf> {
+    crate::get_log_directory().map(|dir| dir.join(RUN_LOGS_FOLDER_NAME))
+}
+
+/// Name of the log of a run, e.g. `install-20250314-093012.log`, sorting by time within a kind.
+fn run_log_name(kind: &str, started: chrono::DateTime<chrono::Local>) -> String {
+    format!("{}-{}.log", kind, started.format("%Y%m%d-%H%M%S"))
+}
+
+/// Deletes all but the newest `keep` run logs in `dir`.
+fn prune_run_logs(dir: &Path, keep: usize) {
+    let mut logs: Vec<(std::time::SystemTime, PathBuf)> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "log"))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+    logs.sort_by(|a, b| b.0.cmp(&a.0));
+    for (_, path) in logs.into_iter().skip(keep) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Starts logging everything of an install, upgrade or repair run (`kind`) into a file of its own
+/// next to the shared log, and returns its path.
+///
+/// Returns `None` if logging was not initialized with `init_logging` or the file can't be created.
+pub fn start_run_log(kind: &str) -> Option<PathBuf> {
+    let dir = get_run_logs_directory()?;
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        log::warn!("Failed to create the run log folder {}: {}", dir.display(), e);
+        return None;
+    }
+    prune_run_logs(&dir, RUN_LOGS_KEPT.saturating_sub(1));
+    let path = dir.join(run_log_name(kind, chrono::Local::now()));
+    let mut logging = LOGGING.lock().unwrap();
+    let (handle, options) = logging.as_mut()?;
+    options.run_log = Some(path.clone());
+    match build_config(options) {
+        Ok(config) => handle.set_config(config),
+        Err(e) => {
+            options.run_log = None;
+            log::warn!("Failed to start the run log: {}", e);
+            return None;
+        }
+    }
+    Some(path)
+}
+
+/// The log of the run in progress in this process, if any.
+pub fn current_run_log() -> Option<PathBuf> {
+    LOGGING.lock().unwrap().as_ref()?.1.run_log.clone()
+}
+
+/// Stops writing to the run log started with [`start_run_log`].
+pub fn finish_run_log() {
+    let mut logging = LOGGING.lock().unwrap();
+    let Some((handle, options)) = logging.as_mut() else {
+        return;
+    };
+    if options.run_log.take().is_some() {
+        if let Ok(config) = build_config(options) {
+            handle.set_config(config);
+        }
+    }
+}
+
+/// The log of the newest install, upgrade or repair run, also of a run made by another eim process.
+pub fn last_run_log() -> Option<PathBuf> {
+    std::fs::read_dir(get_run_logs_directory()?)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "log"))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// Applies the `log_level` setting, keeping the current levels if it is not set or invalid.
+pub fn apply_log_level_setting(spec: Option<&str>) {
+    let Some(spec) = spec.filter(|s| !s.trim().is_empty()) else {
+        return;
+    };
+    match spec.parse().and_then(set_log_levels) {
+        Ok(()) => log::debug!("Log levels set to {}", spec),
+        Err(e) => log::warn!("Ignoring the log_level setting: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_levels() {
+        let levels: LogLevels = "info, idf_im_lib::idf_tools=trace,gix=warn"
+            .parse()
+            .unwrap();
+        assert_eq!(levels.default, Some(LevelFilter::Info));
+        assert_eq!(
+            levels.modules,
+            vec![
+                ("idf_im_lib::idf_tools".to_string(), LevelFilter::Trace),
+                ("gix".to_string(), LevelFilter::Warn)
+            ]
+        );
+        assert_eq!("".parse::<LogLevels>().unwrap(), LogLevels::default());
+        assert!("idf_im_lib=loud".parse::<LogLevels>().is_err());
+    }
+
+    #[test]
+    fn test_run_log_name() {
+        use chrono::TimeZone;
+        let started = chrono::Local.with_ymd_and_hms(2025, 3, 14, 9, 30, 12).unwrap();
+        assert_eq!(run_log_name("install", started), "install-20250314-093012.log");
+    }
+
+    #[test]
+    fn test_prune_run_logs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in ["install-1.log", "repair-2.log", "upgrade-3.log", "notes.txt"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        prune_run_logs(dir.path(), 2);
+        let mut left: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, vec!["notes.txt", "repair-2.log", "upgrade-3.log"]);
+    }
+}
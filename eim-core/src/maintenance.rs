@@ -0,0 +1,383 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::command_executor::execute_command;
+use crate::health_check::HealthReport;
+use crate::settings::Settings;
+use crate::update_notifications::PatchUpdate;
+
+/// Schedule and the result of the last run, next to `eim_idf.json`
+pub const MAINTENANCE_STATE_FILE_NAME: &str = "eim_maintenance.json";
+/// Name of the systemd units, the launchd agent and the scheduled task
+pub const TASK_NAME: &str = "eim-maintenance";
+const LAUNCHD_LABEL: &str = "com.espressif.eim.maintenance";
+const WINDOWS_TASK_NAME: &str = "ESP-IDF Installation Manager Maintenance";
+
+/// How often the maintenance runs. Runs missed while the machine was off are caught up with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    Daily,
+    Weekly,
+}
+
+/// What a maintenance run did and found.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaintenanceReport {
+    /// Bytes reclaimed from leftovers of failed installations and expired cached metadata
+    pub reclaimed: u64,
+    /// `None` when the installation lock was held, e.g. by a running installation
+    pub cleaned: Option<usize>,
+    /// Version folders eim installed but never registered, only removed by `eim cleanup`
+    #[serde(default)]
+    pub unregistered: Vec<PathBuf>,
+    pub updates: Vec<PatchUpdate>,
+    /// Installations with problems, healthy ones are left out
+    pub unhealthy: Vec<HealthReport>,
+    /// Checks which could not run, e.g. the update check without network
+    pub errors: Vec<String>,
+}
+
+impl MaintenanceReport {
+    /// Whether the user has to do something: install an update, repair an installation or
+    /// review unregistered version folders.
+    pub fn needs_action(&self) -> bool {
+        !self.updates.is_empty() || !self.unhealthy.is_empty() || !self.unregistered.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaintenanceState {
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    #[serde(default)]
+    pub last_run: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_report: Option<MaintenanceReport>,
+}
+
+pub fn get_state_path() -> PathBuf {
+    PathBuf::from(Settings::default().esp_idf_json_path.unwrap_or_default()).join(MAINTENANCE_STATE_FILE_NAME)
+}
+
+/// Returns the stored state, the default one if there is none yet.
+pub fn load_state() -> Result<MaintenanceState> {
+    let path = get_state_path();
+    if !path.exists() {
+        return Ok(MaintenanceState::default());
+    }
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+}
+
+fn save_state(state: &MaintenanceState) -> Result<()> {
+    let path = get_state_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(state)?)?;
+    Ok(())
+}
+
+fn systemd_user_dir() -> Result<PathBuf> {
+    dirs::config_dir()
+        .map(|dir| dir.join("systemd").join("user"))
+        .ok_or_else(|| anyhow!("Failed to find the configuration folder"))
+}
+
+fn launch_agent_path() -> Result<PathBuf> {
+    dirs::home_dir()
+        .map(|home| home.join("Library").join("LaunchAgents").join(format!("{}.plist", LAUNCHD_LABEL)))
+        .ok_or_else(|| anyhow!("Failed to find the home folder"))
+}
+
+/// The service running `eim maintenance run` and the timer starting it.
+fn systemd_units(exe: &Path, schedule: Schedule) -> (String, String) {
+    let service = format!(
+        "[Unit]\nDescription=ESP-IDF Installation Manager maintenance\n\n[Service]\nType=oneshot\nExecStart=\"{}\" maintenance run\nNice=10\nIOSchedulingClass=idle\n",
+        exe.display()
+    );
+    let calendar = match schedule {
+        Schedule::Daily => "daily",
+        Schedule::Weekly => "weekly",
+    };
+    let timer = format!(
+        "[Unit]\nDescription=Run the ESP-IDF Installation Manager maintenance {}\n\n[Timer]\nOnCalendar={}\nPersistent=true\nRandomizedDelaySec=1h\n\n[Install]\nWantedBy=timers.target\n",
+        calendar, calendar
+    );
+    (service, timer)
+}
+
+/// A launch agent running `eim maintenance run` at 10:00, on Mondays when weekly. launchd runs
+/// it when the Mac wakes up if it was asleep then.
+fn launchd_plist(exe: &Path, schedule: Schedule) -> String {
+    let weekday = match schedule {
+        Schedule::Daily => String::new(),
+        Schedule::Weekly => "\n        <key>Weekday</key>\n        <integer>1</integer>".to_string(),
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>maintenance</string>
+        <string>run</string>
+    </array>
+    <key>StartCalendarInterval</key>
+    <dict>
+        <key>Hour</key>
+        <integer>10</integer>
+        <key>Minute</key>
+        <integer>0</integer>{}
+    </dict>
+    <key>LowPriorityIO</key>
+    <true/>
+    <key>ProcessType</key>
+    <string>Background</string>
+</dict>
+</plist>
+"#,
+        LAUNCHD_LABEL,
+        exe.display(),
+        weekday
+    )
+}
+
+/// Arguments of `schtasks` creating the task, replacing an existing one.
+fn schtasks_create_args(exe: &Path, schedule: Schedule) -> Vec<String> {
+    let mut args = vec![
+        "/Create".to_string(),
+        "/TN".to_string(),
+        WINDOWS_TASK_NAME.to_string(),
+        "/TR".to_string(),
+        format!("\"{}\" maintenance run", exe.display()),
+        "/ST".to_string(),
+        "10:00".to_string(),
+        "/F".to_string(),
+    ];
+    match schedule {
+        Schedule::Daily => args.extend(["/SC".to_string(), "DAILY".to_string()]),
+        Schedule::Weekly => args.extend(["/SC", "WEEKLY", "/D", "MON"].map(str::to_string)),
+    }
+    args
+}
+
+fn run_checked(program: &str, args: &[&str]) -> Result<()> {
+    let output = execute_command(program, args)?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+fn register(exe: &Path, schedule: Schedule) -> Result<()> {
+    match std::env::consts::OS {
+        "linux" => {
+            let dir = systemd_user_dir()?;
+            fs::create_dir_all(&dir)?;
+            let (service, timer) = systemd_units(exe, schedule);
+            fs::write(dir.join(format!("{}.service", TASK_NAME)), service)?;
+            fs::write(dir.join(format!("{}.timer", TASK_NAME)), timer)?;
+            run_checked("systemctl", &["--user", "daemon-reload"])?;
+            run_checked("systemctl", &["--user", "enable", "--now", &format!("{}.timer", TASK_NAME)])
+        }
+        "macos" => {
+            let path = launch_agent_path()?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // a loaded agent keeps its old schedule until it is unloaded
+            let _ = execute_command("launchctl", &["unload", &path.to_string_lossy()]);
+            fs::write(&path, launchd_plist(exe, schedule))?;
+            run_checked("launchctl", &["load", "-w", &path.to_string_lossy()])
+        }
+        "windows" => {
+            let args = schtasks_create_args(exe, schedule);
+            run_checked("schtasks", &args.iter().map(String::as_str).collect::<Vec<_>>())
+        }
+        os => Err(anyhow!("Scheduled maintenance is not supported on {}, run eim maintenance run from cron instead", os)),
+    }
+}
+
+fn unregister() -> Result<()> {
+    match std::env::consts::OS {
+        "linux" => {
+            let dir = systemd_user_dir()?;
+            let timer = dir.join(format!("{}.timer", TASK_NAME));
+            if timer.exists() {
+                let _ = execute_command("systemctl", &["--user", "disable", "--now", &format!("{}.timer", TASK_NAME)]);
+            }
+            for unit in [timer, dir.join(format!("{}.service", TASK_NAME))] {
+                if unit.exists() {
+                    fs::remove_file(unit)?;
+                }
+            }
+            let _ = execute_command("systemctl", &["--user", "daemon-reload"]);
+            Ok(())
+        }
+        "macos" => {
+            let path = launch_agent_path()?;
+            if path.exists() {
+                let _ = execute_command("launchctl", &["unload", "-w", &path.to_string_lossy()]);
+                fs::remove_file(path)?;
+            }
+            Ok(())
+        }
+        "windows" => {
+            // fails when there is no such task, which is what we want
+            let _ = execute_command("schtasks", &["/Delete", "/TN", WINDOWS_TASK_NAME, "/F"]);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Registers the maintenance with the scheduler of the OS for the current user: a systemd user
+/// timer on Linux, a launch agent on macOS, a scheduled task on Windows. Enabling it again
+/// changes the schedule.
+pub fn enable(schedule: Schedule) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    register(&exe, schedule)?;
+    info!("Scheduled the maintenance {:?} running {}", schedule, exe.display());
+    let mut state = load_state()?;
+    state.schedule = Some(schedule);
+    save_state(&state)
+}
+
+pub fn disable() -> Result<()> {
+    unregister()?;
+    let mut state = load_state()?;
+    state.schedule = None;
+    save_state(&state)
+}
+
+/// Runs the maintenance: removes the staging and backup folders of failed installations and
+/// expired cached metadata, checks for point releases of the installed versions and checks the
+/// health of every installation. The report is stored for `eim maintenance status`.
+///
+/// Nobody confirms what an unattended run removes, so unregistered version folders are only
+/// reported. The cleanup is skipped while another eim process holds the installation lock, the
+/// checks only read.
+pub async fn run() -> Result<MaintenanceReport> {
+    let mut report = MaintenanceReport::default();
+
+    match crate::install_lock::InstallLock::try_acquire("eim maintenance run") {
+        Ok(_lock) => {
+            let (installations, remnants): (Vec<_>, Vec<_>) =
+                crate::remnants::find_remnants(crate::remnants::DEFAULT_MIN_AGE)
+                    .into_iter()
+                    .partition(|remnant| remnant.kind == crate::remnants::RemnantKind::Installation);
+            report.unregistered = installations.into_iter().map(|remnant| remnant.path).collect();
+            report.cleaned = Some(remnants.len());
+            report.reclaimed = crate::remnants::remove_remnants(&remnants) + crate::metadata_cache::remove_expired();
+        }
+        Err(e) => debug!("Skipping the cleanup: {}", e),
+    }
+
+    match crate::update_notifications::pending_updates().await {
+        Ok(updates) => report.updates = updates,
+        Err(e) => {
+            warn!("Failed to check for updates: {}", e);
+            report.errors.push(e.to_string());
+        }
+    }
+
+    let installations = crate::version_manager::get_esp_ide_config()
+        .map(|config| config.idf_installed)
+        .unwrap_or_default();
+    report.unhealthy = installations
+        .iter()
+        .map(crate::health_check::check_installation)
+        .filter(|health| !health.problems.is_empty())
+        .collect();
+
+    let mut state = load_state()?;
+    state.last_run = Some(Utc::now());
+    state.last_report = Some(report.clone());
+    save_state(&state)?;
+    Ok(report)
+}
+
+/// Shows a desktop notification; best effort, a headless machine has nowhere to show it.
+pub fn notify(title: &str, body: &str) {
+    let result = match std::env::consts::OS {
+        "linux" | "freebsd" => execute_command("notify-send", &["--app-name=eim", title, body]),
+        "macos" => {
+            let script = format!(
+                "display notification \"{}\" with title \"{}\"",
+                body.replace('"', "\\\""),
+                title.replace('"', "\\\"")
+            );
+            execute_command("osascript", &["-e", &script])
+        }
+        "windows" => {
+            let script = format!(
+                "Add-Type -AssemblyName System.Windows.Forms; $n = New-Object System.Windows.Forms.NotifyIcon; \
+                 $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+                 $n.ShowBalloonTip(10000, '{}', '{}', 'Info'); Start-Sleep -Seconds 10; $n.Dispose()",
+                title.replace('\'', "''"),
+                body.replace('\'', "''")
+            );
+            execute_command("powershell", &["-NoProfile", "-NonInteractive", "-Command", &script])
+        }
+        _ => return,
+    };
+    if let Err(e) = result {
+        debug!("Failed to show a notification: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scheduler_definitions() {
+        let exe = Path::new("/opt/eim/eim");
+        let (service, timer) = systemd_units(exe, Schedule::Weekly);
+        assert!(service.contains("ExecStart=\"/opt/eim/eim\" maintenance run"));
+        assert!(timer.contains("OnCalendar=weekly"));
+        assert!(timer.contains("Persistent=true"));
+
+        assert!(launchd_plist(exe, Schedule::Weekly).contains("<key>Weekday</key>"));
+        assert!(!launchd_plist(exe, Schedule::Daily).contains("<key>Weekday</key>"));
+
+        let args = schtasks_create_args(Path::new("C:\\eim\\eim.exe"), Schedule::Daily);
+        assert!(args.contains(&"\"C:\\eim\\eim.exe\" maintenance run".to_string()));
+        assert!(args.windows(2).any(|pair| pair == ["/SC", "DAILY"]));
+    }
+
+    #[test]
+    fn test_needs_action() {
+        let mut report = MaintenanceReport {
+            reclaimed: 1024,
+            cleaned: Some(2),
+            ..Default::default()
+        };
+        assert!(!report.needs_action());
+        // only reported, never removed unattended
+        report.unregistered.push(PathBuf::from("/home/user/.espressif/v5.3"));
+        assert!(report.needs_action());
+        report.unregistered.clear();
+        report.unhealthy.push(HealthReport {
+            id: "esp-idf-v5.4".to_string(),
+            name: "v5.4".to_string(),
+            problems: vec!["git is no longer on PATH".to_string()],
+        });
+        assert!(report.needs_action());
+    }
+}
@@ -0,0 +1,284 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::settings::Settings;
+
+pub const METADATA_CACHE_DIR_NAME: &str = "eim_metadata_cache";
+pub const DEFAULT_TTL_SECONDS: u64 = 3600;
+/// How long a cached response is still used when the server can't be reached
+pub const OFFLINE_GRACE_DAYS: i64 = 7;
+
+static TTL: Lazy<RwLock<Duration>> = Lazy::new(|| RwLock::new(Duration::seconds(DEFAULT_TTL_SECONDS as i64)));
+
+/// Applies the `metadata_cache_ttl` setting.
+pub fn configure_from_settings(settings: &Settings) {
+    let seconds = settings.metadata_cache_ttl.unwrap_or(DEFAULT_TTL_SECONDS);
+    debug!("Caching fetched metadata for {} seconds", seconds);
+    if let Ok(mut guard) = TTL.write() {
+        *guard = Duration::seconds(seconds.min(i64::MAX as u64) as i64);
+    }
+}
+
+fn ttl() -> Duration {
+    TTL.read()
+        .map(|ttl| *ttl)
+        .unwrap_or_else(|_| Duration::seconds(DEFAULT_TTL_SECONDS as i64))
+}
+
+/// A fetched response with the validators the server sent along.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    fetched_at: DateTime<Utc>,
+    body: String,
+}
+
+impl CacheEntry {
+    /// Whether the entry is used without asking the server.
+    fn is_fresh(&self, ttl: Duration, now: DateTime<Utc>) -> bool {
+        now - self.fetched_at < ttl
+    }
+
+    fn usable_offline(&self, now: DateTime<Utc>) -> bool {
+        now - self.fetched_at < Duration::days(OFFLINE_GRACE_DAYS)
+    }
+
+    /// Makes `request` conditional, the server answers 304 Not Modified if the entry is current.
+    fn with_validators(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
+
+/// Folder of the cached responses, next to `eim_idf.json`.
+pub fn cache_dir() -> PathBuf {
+    PathBuf::from(Settings::default().esp_idf_json_path.unwrap_or_default()).join(METADATA_CACHE_DIR_NAME)
+}
+
+fn entry_path(dir: &Path, url: &str) -> PathBuf {
+    dir.join(format!("{:x}.json", Sha256::digest(url.as_bytes())))
+}
+
+fn load(dir: &Path, url: &str) -> Option<CacheEntry> {
+    let content = fs::read_to_string(entry_path(dir, url)).ok()?;
+    serde_json::from_str::<CacheEntry>(&content)
+        .ok()
+        .filter(|entry| entry.url == url)
+}
+
+/// Stores `entry`; the cache is an optimization, failures are only logged.
+fn store(dir: &Path, entry: &CacheEntry) {
+    let path = entry_path(dir, &entry.url);
+    let result = fs::create_dir_all(dir)
+        .and_then(|_| serde_json::to_string(entry).map_err(std::io::Error::other))
+        .and_then(|content| {
+            // written aside and renamed so a concurrent reader never sees half an entry
+            let temporary = path.with_extension("json.tmp");
+            fs::write(&temporary, content)?;
+            fs::rename(&temporary, &path)
+        });
+    if let Err(e) = result {
+        warn!("Failed to cache {}: {}", entry.url, e);
+    }
+}
+
+/// Removes the entries too old to be used even offline and returns the bytes reclaimed.
+pub fn remove_expired() -> u64 {
+    let now = Utc::now();
+    let Ok(entries) = fs::read_dir(cache_dir()) else {
+        return 0;
+    };
+    let mut reclaimed = 0;
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let expired = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<CacheEntry>(&content).ok())
+            .is_none_or(|entry| !entry.usable_offline(now));
+        let size = fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or_default();
+        if expired && fs::remove_file(&path).is_ok() {
+            debug!("Removed the expired cache entry {}", path.display());
+            reclaimed += size;
+        }
+    }
+    reclaimed
+}
+
+/// Failures a cached copy stands in for: the server being unreachable, overloaded or rate
+/// limiting, not a missing file.
+fn is_transient(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::FORBIDDEN
+}
+
+fn offline_fallback(cached: Option<CacheEntry>, now: DateTime<Utc>, error: anyhow::Error) -> Result<String> {
+    match cached.filter(|entry| entry.usable_offline(now)) {
+        Some(entry) => {
+            warn!(
+                "{}, using the copy of {} fetched {}",
+                error,
+                entry.url,
+                entry.fetched_at.format("%Y-%m-%d %H:%M UTC")
+            );
+            Ok(entry.body)
+        }
+        None => Err(error),
+    }
+}
+
+/// Fetches the body of `url` with `request`, a GET of `url` carrying any headers and
+/// credentials it needs, through the metadata cache.
+///
+/// A copy younger than the `metadata_cache_ttl` is returned without contacting the server, an
+/// older one is revalidated with `If-None-Match`/`If-Modified-Since`, and when the server
+/// can't be reached a copy up to `OFFLINE_GRACE_DAYS` old is used instead of failing.
+pub async fn fetch_text(url: &str, request: RequestBuilder) -> Result<String> {
+    fetch_text_in(&cache_dir(), url, request, ttl(), Utc::now()).await
+}
+
+async fn fetch_text_in(
+    dir: &Path,
+    url: &str,
+    request: RequestBuilder,
+    ttl: Duration,
+    now: DateTime<Utc>,
+) -> Result<String> {
+    let cached = load(dir, url);
+    if let Some(entry) = cached.as_ref().filter(|entry| entry.is_fresh(ttl, now)) {
+        debug!("Using the cached copy of {}", url);
+        return Ok(entry.body.clone());
+    }
+
+    let request = match &cached {
+        Some(entry) => entry.with_validators(request),
+        None => request,
+    };
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => return offline_fallback(cached, now, anyhow!("Failed to fetch {}: {}", url, e)),
+    };
+    let status = response.status();
+    if status == StatusCode::NOT_MODIFIED {
+        if let Some(mut entry) = cached {
+            debug!("{} is unchanged", url);
+            entry.fetched_at = now;
+            store(dir, &entry);
+            return Ok(entry.body);
+        }
+        return Err(anyhow!("{} returned {} for an unconditional request", url, status));
+    }
+    if !status.is_success() {
+        let error = anyhow!("{} returned {}", url, status);
+        return if is_transient(status) {
+            offline_fallback(cached, now, error)
+        } else {
+            Err(error)
+        };
+    }
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+    let body = match response.text().await {
+        Ok(body) => body,
+        Err(e) => return offline_fallback(cached, now, anyhow!("Failed to read {}: {}", url, e)),
+    };
+    store(
+        dir,
+        &CacheEntry {
+            url: url.to_string(),
+            etag,
+            last_modified,
+            fetched_at: now,
+            body: body.clone(),
+        },
+    );
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // nothing listens on port 1, requests to it fail right away
+    const UNREACHABLE_URL: &str = "http://127.0.0.1:1/idf_versions.json";
+
+    fn entry(url: &str, fetched_at: DateTime<Utc>) -> CacheEntry {
+        CacheEntry {
+            url: url.to_string(),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            fetched_at,
+            body: "cached".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_freshness() {
+        let now = Utc::now();
+        let entry = entry(UNREACHABLE_URL, now - Duration::minutes(30));
+        assert!(entry.is_fresh(Duration::hours(1), now));
+        assert!(!entry.is_fresh(Duration::minutes(10), now));
+        assert!(!entry.is_fresh(Duration::zero(), now));
+        assert!(entry.usable_offline(now));
+        assert!(!entry.usable_offline(now + Duration::days(OFFLINE_GRACE_DAYS)));
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let dir = TempDir::new().unwrap();
+        let stored = entry(UNREACHABLE_URL, Utc::now());
+        store(dir.path(), &stored);
+        assert_eq!(load(dir.path(), UNREACHABLE_URL), Some(stored));
+        assert_eq!(load(dir.path(), "http://127.0.0.1:1/other.json"), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_text_uses_cache() {
+        let dir = TempDir::new().unwrap();
+        let now = Utc::now();
+        let client = reqwest::Client::new();
+
+        // fresh, the server is not asked
+        store(dir.path(), &entry(UNREACHABLE_URL, now - Duration::minutes(5)));
+        let body = fetch_text_in(dir.path(), UNREACHABLE_URL, client.get(UNREACHABLE_URL), Duration::hours(1), now)
+            .await
+            .unwrap();
+        assert_eq!(body, "cached");
+
+        // expired and the server can't be reached, the copy stands in
+        let body = fetch_text_in(dir.path(), UNREACHABLE_URL, client.get(UNREACHABLE_URL), Duration::zero(), now)
+            .await
+            .unwrap();
+        assert_eq!(body, "cached");
+
+        // too old to be used offline
+        store(dir.path(), &entry(UNREACHABLE_URL, now - Duration::days(OFFLINE_GRACE_DAYS + 1)));
+        assert!(
+            fetch_text_in(dir.path(), UNREACHABLE_URL, client.get(UNREACHABLE_URL), Duration::zero(), now)
+                .await
+                .is_err()
+        );
+    }
+}
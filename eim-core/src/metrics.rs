@@ -0,0 +1,197 @@
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Content type of the Prometheus text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+static INSTALLS_STARTED: AtomicU64 = AtomicU64::new(0);
+static INSTALLS_SUCCEEDED: AtomicU64 = AtomicU64::new(0);
+static INSTALLS_FAILED: AtomicU64 = AtomicU64::new(0);
+static DOWNLOADS: AtomicU64 = AtomicU64::new(0);
+static DOWNLOAD_FAILURES: AtomicU64 = AtomicU64::new(0);
+static DOWNLOAD_RETRIES: AtomicU64 = AtomicU64::new(0);
+static BYTES_DOWNLOADED: AtomicU64 = AtomicU64::new(0);
+
+/// Stage of the running installation and the ESP-IDF version it belongs to
+static STAGE: Lazy<Mutex<Option<(String, Option<String>)>>> = Lazy::new(|| Mutex::new(None));
+/// Like `STAGE`, but kept after the installation ended
+static LAST_STAGE: Lazy<Mutex<Option<(String, Option<String>)>>> = Lazy::new(|| Mutex::new(None));
+
+/// Counts a started installation, called with the install report.
+pub fn install_started() {
+    INSTALLS_STARTED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts a finished installation and clears its stage.
+pub fn install_finished(success: bool) {
+    if success {
+        INSTALLS_SUCCEEDED.fetch_add(1, Ordering::Relaxed);
+    } else {
+        INSTALLS_FAILED.fetch_add(1, Ordering::Relaxed);
+    }
+    *STAGE.lock().unwrap() = None;
+}
+
+/// Sets the stage the running installation is in, e.g. `tools` of `v5.4`.
+pub fn set_stage(stage: &str, version: Option<&str>) {
+    let stage = Some((stage.to_string(), version.map(str::to_string)));
+    *LAST_STAGE.lock().unwrap() = stage.clone();
+    *STAGE.lock().unwrap() = stage;
+}
+
+/// Stage the last installation of this process was in, also after it failed.
+pub fn last_stage() -> Option<(String, Option<String>)> {
+    LAST_STAGE.lock().unwrap().clone()
+}
+
+/// Counts a finished download with the retries it took, called with the download telemetry.
+pub fn record_download(bytes: Option<u64>, retries: usize) {
+    DOWNLOADS.fetch_add(1, Ordering::Relaxed);
+    DOWNLOAD_RETRIES.fetch_add(retries as u64, Ordering::Relaxed);
+    match bytes {
+        Some(bytes) => {
+            BYTES_DOWNLOADED.fetch_add(bytes, Ordering::Relaxed);
+        }
+        None => {
+            DOWNLOAD_FAILURES.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// All metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+    let counters = [
+        ("eim_installs_started_total", "Installations started", &INSTALLS_STARTED),
+        ("eim_installs_succeeded_total", "Installations which succeeded", &INSTALLS_SUCCEEDED),
+        ("eim_installs_failed_total", "Installations which failed", &INSTALLS_FAILED),
+        ("eim_downloads_total", "Files downloaded, successfully or not", &DOWNLOADS),
+        ("eim_download_failures_total", "Files which could not be downloaded after all retries", &DOWNLOAD_FAILURES),
+        ("eim_download_retries_total", "Download attempts repeated after a failure", &DOWNLOAD_RETRIES),
+        ("eim_downloaded_bytes_total", "Bytes of the files downloaded", &BYTES_DOWNLOADED),
+    ];
+    for (name, help, counter) in counters {
+        write_metric(&mut out, name, "counter", help, counter.load(Ordering::Relaxed));
+    }
+
+    write_stage(&mut out, STAGE.lock().unwrap().as_ref());
+    out
+}
+
+fn write_stage(out: &mut String, stage: Option<&(String, Option<String>)>) {
+    write_metric(out, "eim_install_running", "gauge", "1 while an installation is running", stage.is_some() as u64);
+    let _ = writeln!(out, "# HELP eim_install_stage Stage of the running installation, always 1");
+    let _ = writeln!(out, "# TYPE eim_install_stage gauge");
+    if let Some((stage, version)) = stage {
+        let _ = writeln!(
+            out,
+            "eim_install_stage{{stage=\"{}\",version=\"{}\"}} 1",
+            escape_label(stage),
+            escape_label(version.as_deref().unwrap_or_default())
+        );
+    }
+}
+
+fn answer(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone()?);
+    reader.read_line(&mut request_line)?;
+    // the headers are not needed, but have to be read before answering
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next().map(|p| p.split('?').next().unwrap_or_default())) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", CONTENT_TYPE, render()),
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Serves `GET /metrics` on `127.0.0.1:port` from a background thread until the process exits,
+/// returning the bound address (port 0 picks a free one).
+pub fn serve(port: u16) -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let address = listener.local_addr()?;
+    info!("Serving metrics on http://{}/metrics", address);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(err) = answer(stream) {
+                        debug!("Failed to answer a metrics request: {}", err);
+                    }
+                }
+                Err(err) => warn!("Failed to accept a metrics connection: {}", err),
+            }
+        }
+    });
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_render_and_serve() {
+        record_download(Some(1000), 2);
+        record_download(None, 3);
+        let metrics = render();
+        assert!(metrics.contains("# TYPE eim_downloaded_bytes_total counter"));
+        assert!(metrics.contains("# TYPE eim_install_stage gauge"));
+
+        let address = serve(0).unwrap();
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("eim_download_failures_total"));
+    }
+
+    #[test]
+    fn test_write_stage() {
+        let mut out = String::new();
+        write_stage(&mut out, Some(&("tools".to_string(), Some("v5.4".to_string()))));
+        assert!(out.contains("eim_install_running 1"));
+        assert!(out.contains("eim_install_stage{stage=\"tools\",version=\"v5.4\"} 1"));
+
+        let mut out = String::new();
+        write_stage(&mut out, None);
+        assert!(out.contains("eim_install_running 0"));
+        assert!(!out.contains("eim_install_stage{"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
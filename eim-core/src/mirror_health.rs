@@ -0,0 +1,275 @@
+use log::{debug, warn};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::idf_tools::ToolsFile;
+use crate::settings::Settings;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Small file of the ESP-IDF repository fetched from the ESP-IDF mirrors
+const IDF_PROBE_FILE: &str = "tools/cmake/version.cmake";
+/// Package looked up on the PyPI mirrors, released with every ESP-IDF minor version
+const PYPI_PROBE_PACKAGE: &str = "esp-idf-monitor";
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MirrorKind {
+    Idf,
+    Tools,
+    Pypi,
+}
+
+impl std::fmt::Display for MirrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MirrorKind::Idf => "ESP-IDF",
+            MirrorKind::Tools => "tools",
+            MirrorKind::Pypi => "PyPI",
+        })
+    }
+}
+
+/// Health of a single mirror.
+#[derive(Debug, Clone, Serialize)]
+pub struct MirrorReport {
+    pub kind: MirrorKind,
+    pub url: String,
+    /// Whether the mirror is the one configured in the settings
+    pub configured: bool,
+    pub available: bool,
+    pub latency_ms: Option<u32>,
+    /// What the mirror was checked to have, e.g. ESP-IDF v5.5.1
+    pub latest: Option<String>,
+    /// `Some(false)` when the mirror responds without having `latest`; `None` when not checked
+    pub up_to_date: Option<bool>,
+    pub error: Option<String>,
+}
+
+impl MirrorReport {
+    fn new(kind: MirrorKind, url: &str, configured: bool) -> Self {
+        Self {
+            kind,
+            url: url.to_string(),
+            configured,
+            available: false,
+            latency_ms: None,
+            latest: None,
+            up_to_date: None,
+            error: None,
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.available && self.up_to_date != Some(false)
+    }
+}
+
+/// What the reports of all mirrors say about the problem.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Diagnosis {
+    /// Every mirror responds with the latest release
+    Healthy,
+    /// Some mirrors are down or behind, the network works
+    MirrorProblem,
+    /// No mirror responds, the network, proxy or firewall is the likely cause
+    NetworkProblem,
+}
+
+pub fn diagnose(reports: &[MirrorReport]) -> Diagnosis {
+    if reports.iter().all(MirrorReport::is_healthy) {
+        Diagnosis::Healthy
+    } else if reports.iter().any(|report| report.available) {
+        Diagnosis::MirrorProblem
+    } else {
+        Diagnosis::NetworkProblem
+    }
+}
+
+enum ProbeResult {
+    Found,
+    Missing,
+}
+
+/// Fetches `url`, returning whether it exists and how long the response took.
+async fn probe(client: &reqwest::Client, url: &str, head: bool) -> Result<(ProbeResult, u32), String> {
+    let start = Instant::now();
+    let request = if head { client.head(url) } else { client.get(url) };
+    let response = crate::http_client::authorize(request, url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let latency = start.elapsed().as_millis().min(u32::MAX as u128) as u32;
+    debug!("Mirror probe {} -> {} in {}ms", url, response.status(), latency);
+    match response.status() {
+        status if status.is_success() => Ok((ProbeResult::Found, latency)),
+        reqwest::StatusCode::NOT_FOUND => Ok((ProbeResult::Missing, latency)),
+        status => Err(format!("HTTP {}", status)),
+    }
+}
+
+fn record(report: &mut MirrorReport, result: Result<(ProbeResult, u32), String>) {
+    match result {
+        Ok((found, latency)) => {
+            report.available = true;
+            report.latency_ms = Some(latency);
+            report.up_to_date = Some(matches!(found, ProbeResult::Found));
+        }
+        Err(error) => report.error = Some(error),
+    }
+}
+
+/// Lists the known mirrors of `kind` with the configured one first, marked as configured.
+fn mirrors_of(kind: MirrorKind, configured: Option<&String>) -> Vec<(String, bool)> {
+    let known = match kind {
+        MirrorKind::Idf => crate::get_idf_mirrors_list(),
+        MirrorKind::Tools => crate::get_idf_tools_mirrors_list(),
+        MirrorKind::Pypi => crate::get_pypi_mirrors_list(),
+    };
+    let configured = configured.map(|url| url.trim_end_matches('/').to_string());
+    let mut mirrors: Vec<(String, bool)> = configured.iter().map(|url| (url.clone(), true)).collect();
+    mirrors.extend(
+        known
+            .iter()
+            .filter(|url| configured.as_deref() != Some(**url))
+            .map(|url| (url.to_string(), false)),
+    );
+    mirrors
+}
+
+/// The smallest tool archive of `tools` for this platform, its URL on GitHub.
+fn smallest_tool_url(tools: ToolsFile) -> Option<String> {
+    let platform = crate::idf_tools::get_platform_identification().ok()?;
+    let list = crate::idf_tools::filter_tools_by_target(tools.tools, &["all".to_string()]);
+    crate::idf_tools::get_download_link_by_platform(list, &platform)
+        .into_values()
+        .map(|(_, download)| download)
+        .min_by_key(|download| download.size)
+        .map(|download| download.url)
+}
+
+/// Latest release of the probe package on pypi.org, the reference for the PyPI mirrors.
+async fn latest_pypi_release(client: &reqwest::Client) -> Option<String> {
+    let url = format!("https://pypi.org/pypi/{}/json", PYPI_PROBE_PACKAGE);
+    let json: serde_json::Value = client.get(&url).send().await.ok()?.json().await.ok()?;
+    json["info"]["version"].as_str().map(str::to_string)
+}
+
+/// Probes every known mirror and the configured ones: whether they respond and whether they
+/// have the latest ESP-IDF release, the tools of it and the latest Python packages.
+pub async fn check_mirrors(settings: &Settings) -> Vec<MirrorReport> {
+    let client = match crate::http_client::client_builder()
+        .timeout(PROBE_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::limited(5))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to create the HTTP client: {}", e);
+            return Vec::new();
+        }
+    };
+    let latest_idf = crate::idf_versions::get_latest_idf_version(false)
+        .await
+        .ok()
+        .flatten()
+        .map(|version| version.name);
+    let mut reports = Vec::new();
+
+    let mut tools_json: Option<ToolsFile> = None;
+    for (url, configured) in mirrors_of(MirrorKind::Idf, settings.idf_mirror.as_ref()) {
+        let mut report = MirrorReport::new(MirrorKind::Idf, &url, configured);
+        match &latest_idf {
+            Some(version) => {
+                report.latest = Some(format!("ESP-IDF {}", version));
+                let probe_url = crate::git_tools::get_raw_file_url(None, version, Some(&url), IDF_PROBE_FILE);
+                record(&mut report, probe(&client, &probe_url, false).await);
+                if tools_json.is_none() && report.is_healthy() {
+                    let tools_url = crate::git_tools::get_raw_file_url(None, version, Some(&url), "tools/tools.json");
+                    if let Ok(response) = client.get(&tools_url).send().await {
+                        tools_json = response.json().await.ok();
+                    }
+                }
+            }
+            // without the versions list only the availability is known
+            None => {
+                record(&mut report, probe(&client, &url, true).await);
+                report.up_to_date = None;
+            }
+        }
+        reports.push(report);
+    }
+
+    let tool_url = tools_json.and_then(smallest_tool_url);
+    for (url, configured) in mirrors_of(MirrorKind::Tools, settings.mirror.as_ref()) {
+        let mut report = MirrorReport::new(MirrorKind::Tools, &url, configured);
+        match &tool_url {
+            Some(tool_url) => {
+                report.latest = tool_url.rsplit('/').next().map(str::to_string);
+                let probe_url = tool_url.replace("https://github.com", &url);
+                record(&mut report, probe(&client, &probe_url, true).await);
+            }
+            None => {
+                record(&mut report, probe(&client, &url, true).await);
+                report.up_to_date = None;
+            }
+        }
+        reports.push(report);
+    }
+
+    let latest_package = latest_pypi_release(&client).await;
+    for (url, configured) in mirrors_of(MirrorKind::Pypi, settings.pypi_mirror.as_ref()) {
+        let mut report = MirrorReport::new(MirrorKind::Pypi, &url, configured);
+        let index_url = format!("{}/{}/", url.trim_end_matches('/'), PYPI_PROBE_PACKAGE);
+        let start = Instant::now();
+        match client.get(&index_url).send().await {
+            Ok(response) if response.status().is_success() => {
+                report.available = true;
+                report.latency_ms = Some(start.elapsed().as_millis().min(u32::MAX as u128) as u32);
+                if let (Some(version), Ok(page)) = (&latest_package, response.text().await) {
+                    report.latest = Some(format!("{} {}", PYPI_PROBE_PACKAGE, version));
+                    // distribution file names use underscores, e.g. esp_idf_monitor-1.5.0.tar.gz
+                    let file_prefix = format!("{}-{}", PYPI_PROBE_PACKAGE.replace('-', "_"), version);
+                    report.up_to_date = Some(page.contains(&file_prefix));
+                }
+            }
+            Ok(response) => report.error = Some(format!("HTTP {}", response.status())),
+            Err(e) => report.error = Some(e.to_string()),
+        }
+        reports.push(report);
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(available: bool, up_to_date: Option<bool>) -> MirrorReport {
+        MirrorReport {
+            available,
+            up_to_date,
+            ..MirrorReport::new(MirrorKind::Tools, "https://dl.espressif.com/github_assets", false)
+        }
+    }
+
+    #[test]
+    fn test_diagnose() {
+        assert_eq!(diagnose(&[report(true, Some(true)), report(true, None)]), Diagnosis::Healthy);
+        assert_eq!(diagnose(&[report(true, Some(true)), report(true, Some(false))]), Diagnosis::MirrorProblem);
+        assert_eq!(diagnose(&[report(true, Some(true)), report(false, None)]), Diagnosis::MirrorProblem);
+        assert_eq!(diagnose(&[report(false, None), report(false, None)]), Diagnosis::NetworkProblem);
+    }
+
+    #[test]
+    fn test_configured_mirror_comes_first() {
+        let configured = "https://mirror.example.com/".to_string();
+        let mirrors = mirrors_of(MirrorKind::Idf, Some(&configured));
+        assert_eq!(mirrors[0], ("https://mirror.example.com".to_string(), true));
+        assert_eq!(mirrors.len(), crate::get_idf_mirrors_list().len() + 1);
+
+        let github = "https://github.com".to_string();
+        assert_eq!(mirrors_of(MirrorKind::Idf, Some(&github)).len(), crate::get_idf_mirrors_list().len());
+    }
+}
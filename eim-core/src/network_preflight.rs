@@ -0,0 +1,305 @@
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::error::Error as StdError;
+use std::time::Duration;
+use url::Url;
+
+use crate::idf_versions::IDF_VERSIONS_URL;
+use crate::settings::Settings;
+
+const PREFLIGHT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Result of probing a single endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum EndpointStatus {
+    Ok,
+    /// The host name could not be resolved
+    DnsFailed { error: String },
+    /// The configured proxy could not be reached
+    ProxyFailed { proxy: String, error: String },
+    /// No TCP connection could be established
+    ConnectFailed { error: String },
+    /// The TLS handshake failed for a reason other than an untrusted certificate
+    TlsFailed { error: String },
+    /// The server certificate is not trusted, typically caused by a corporate TLS inspecting proxy
+    TlsIntercepted { error: String },
+    Timeout,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EndpointReport {
+    /// What the endpoint is used for, e.g. "ESP-IDF repository"
+    pub purpose: String,
+    pub url: String,
+    pub host: String,
+    #[serde(flatten)]
+    pub status: EndpointStatus,
+}
+
+impl EndpointReport {
+    pub fn is_ok(&self) -> bool {
+        self.status == EndpointStatus::Ok
+    }
+}
+
+impl std::fmt::Display for EndpointReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match &self.status {
+            EndpointStatus::Ok => "reachable".to_string(),
+            EndpointStatus::DnsFailed { error } => format!("DNS resolution of {} failed: {}", self.host, error),
+            EndpointStatus::ProxyFailed { proxy, error } => format!("proxy {} is not reachable: {}", proxy, error),
+            EndpointStatus::ConnectFailed { error } => format!("connection to {} failed: {}", self.host, error),
+            EndpointStatus::TlsFailed { error } => format!("TLS handshake with {} failed: {}", self.host, error),
+            EndpointStatus::TlsIntercepted { error } => format!(
+                "the certificate presented for {} is not trusted, the connection is probably intercepted by a TLS inspecting proxy: {}",
+                self.host, error
+            ),
+            EndpointStatus::Timeout => format!("{} did not respond within {}s", self.host, PREFLIGHT_TIMEOUT.as_secs()),
+        };
+        write!(f, "{} ({}): {}", self.purpose, self.url, description)
+    }
+}
+
+/// Returns the endpoints an online installation with the given settings needs.
+pub fn required_endpoints(settings: &Settings) -> Vec<(String, String)> {
+    let mut endpoints = vec![
+        (
+            "ESP-IDF repository".to_string(),
+            settings.idf_mirror.clone().unwrap_or_else(|| crate::get_idf_mirrors_list()[0].to_string()),
+        ),
+        (
+            "Tools download".to_string(),
+            settings.mirror.clone().unwrap_or_else(|| crate::get_idf_tools_mirrors_list()[0].to_string()),
+        ),
+        (
+            "Python packages".to_string(),
+            settings.pypi_mirror.clone().unwrap_or_else(|| crate::get_pypi_mirrors_list()[0].to_string()),
+        ),
+        ("ESP-IDF versions list".to_string(), IDF_VERSIONS_URL.to_string()),
+    ];
+    if let Some(manifest) = &settings.tools_manifest {
+        if manifest.starts_with("http://") || manifest.starts_with("https://") {
+            endpoints.push(("Custom tools manifest".to_string(), manifest.clone()));
+        }
+    }
+    if let Some(rewrite) = &settings.tools_url_rewrite {
+        if let Some((_, to)) = rewrite.split_once('=') {
+            endpoints.push(("Private tools mirror".to_string(), to.to_string()));
+        }
+    }
+    // checks the artifact proxy the downloads are sent to instead
+    endpoints
+        .into_iter()
+        .map(|(purpose, url)| (purpose, crate::url_rewrite::rewrite(&url)))
+        .collect()
+}
+
+/// Returns the proxy which applies to `url`: the configured one, or the one from the standard
+/// environment variables.
+fn proxy_for(url: &Url) -> Option<String> {
+    if let Some(proxy) = crate::http_client::proxy_setting() {
+        return Some(proxy);
+    }
+    let candidates: &[&str] = match url.scheme() {
+        "https" => &["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"],
+        _ => &["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"],
+    };
+    let host = url.host_str().unwrap_or_default();
+    let no_proxy = std::env::var("NO_PROXY").or_else(|_| std::env::var("no_proxy")).unwrap_or_default();
+    if no_proxy
+        .split(',')
+        .map(|e| e.trim().trim_start_matches('.'))
+        .any(|e| !e.is_empty() && (e == "*" || host == e || host.ends_with(&format!(".{}", e))))
+    {
+        return None;
+    }
+    candidates
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+}
+
+async fn resolve(host: &str, port: u16) -> Result<(), String> {
+    match tokio::net::lookup_host((host, port)).await {
+        Ok(mut addresses) => {
+            if addresses.next().is_some() {
+                Ok(())
+            } else {
+                Err("no addresses returned".to_string())
+            }
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+async fn connect(host: &str, port: u16) -> Result<(), String> {
+    match tokio::time::timeout(PREFLIGHT_TIMEOUT, tokio::net::TcpStream::connect((host, port))).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("timed out".to_string()),
+    }
+}
+
+/// Flattens an error and all its sources into one message.
+fn error_chain(err: &dyn StdError) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(inner) = source {
+        message.push_str(": ");
+        message.push_str(&inner.to_string());
+        source = inner.source();
+    }
+    message
+}
+
+/// Classifies a failed request by looking at the whole error chain.
+fn classify_request_error(err: &reqwest::Error) -> EndpointStatus {
+    let chain = error_chain(err);
+    let lower = chain.to_lowercase();
+    if err.is_timeout() {
+        EndpointStatus::Timeout
+    } else if lower.contains("unknownissuer")
+        || lower.contains("unknown issuer")
+        || lower.contains("self signed")
+        || lower.contains("self-signed")
+        || lower.contains("unable to get local issuer")
+        || lower.contains("certificate verify failed")
+        || lower.contains("invalid peer certificate")
+        || lower.contains("not trusted")
+    {
+        EndpointStatus::TlsIntercepted { error: chain }
+    } else if lower.contains("tls") || lower.contains("ssl") || lower.contains("handshake") || lower.contains("certificate") {
+        EndpointStatus::TlsFailed { error: chain }
+    } else {
+        EndpointStatus::ConnectFailed { error: chain }
+    }
+}
+
+/// Probes one endpoint: DNS, proxy, TCP/TLS and finally an HTTP request.
+pub async fn check_endpoint(purpose: &str, url: &str) -> EndpointReport {
+    let mut report = EndpointReport {
+        purpose: purpose.to_string(),
+        url: url.to_string(),
+        host: String::new(),
+        status: EndpointStatus::Ok,
+    };
+    let parsed = match Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            report.status = EndpointStatus::ConnectFailed { error: format!("invalid URL: {}", e) };
+            return report;
+        }
+    };
+    report.host = parsed.host_str().unwrap_or_default().to_string();
+    let port = parsed.port_or_known_default().unwrap_or(443);
+
+    match proxy_for(&parsed) {
+        Some(proxy) => {
+            // with a proxy, name resolution of the target happens on the proxy side, apart from
+            // socks5 (without h), which resolves locally
+            let proxy_url = Url::parse(&proxy).or_else(|_| Url::parse(&format!("http://{}", proxy))).ok();
+            let proxy_host_port = proxy_url.as_ref().and_then(|p| {
+                let default_port = if p.scheme().starts_with("socks") { 1080 } else { 8080 };
+                Some((p.host_str()?.to_string(), p.port_or_known_default().unwrap_or(default_port)))
+            });
+            match proxy_host_port {
+                Some((proxy_host, proxy_port)) => {
+                    if let Err(error) = connect(&proxy_host, proxy_port).await {
+                        report.status = EndpointStatus::ProxyFailed { proxy, error };
+                        return report;
+                    }
+                    if proxy_url.is_some_and(|p| p.scheme() == "socks5") {
+                        if let Err(error) = resolve(&report.host, port).await {
+                            report.status = EndpointStatus::DnsFailed { error };
+                            return report;
+                        }
+                    }
+                }
+                None => {
+                    report.status = EndpointStatus::ProxyFailed {
+                        proxy,
+                        error: "invalid proxy URL".to_string(),
+                    };
+                    return report;
+                }
+            }
+        }
+        None => {
+            if let Err(error) = resolve(&report.host, port).await {
+                report.status = EndpointStatus::DnsFailed { error };
+                return report;
+            }
+        }
+    }
+
+    let client = match crate::http_client::client_builder()
+        .timeout(PREFLIGHT_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::limited(5))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            report.status = EndpointStatus::ConnectFailed { error: e.to_string() };
+            return report;
+        }
+    };
+
+    // Some servers reject HEAD, in that case any answer still proves the endpoint is reachable
+    let response = match client.head(url).send().await {
+        Ok(response) => Ok(response),
+        Err(e) if e.is_request() || e.is_connect() || e.is_timeout() => Err(e),
+        Err(_) => client.get(url).send().await,
+    };
+    report.status = match response {
+        Ok(response) if response.status().as_u16() == 407 => EndpointStatus::ProxyFailed {
+            proxy: proxy_for(&parsed).unwrap_or_default(),
+            error: "proxy authentication required".to_string(),
+        },
+        Ok(response) => {
+            // Mirror base URLs often do not allow listing, any answer proves the host is reachable
+            debug!("{} answered {}", url, response.status());
+            EndpointStatus::Ok
+        }
+        Err(e) => classify_request_error(&e),
+    };
+    report
+}
+
+/// Probes all endpoints needed by an installation with the given settings.
+///
+/// Returns one report per endpoint, so the caller can tell the user exactly which
+/// endpoint is blocked and why.
+pub async fn run_network_preflight(settings: &Settings) -> Vec<EndpointReport> {
+    let mut reports = Vec::new();
+    for (purpose, url) in required_endpoints(settings) {
+        let report = check_endpoint(&purpose, &url).await;
+        if report.is_ok() {
+            info!("Network preflight: {}", report);
+        } else {
+            warn!("Network preflight: {}", report);
+        }
+        reports.push(report);
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_endpoints_include_custom_mirrors() {
+        let mut settings = Settings::default();
+        settings.tools_manifest = Some("https://artifactory.example.com/tools.json".to_string());
+        settings.tools_url_rewrite = Some("https://github.com=https://artifactory.example.com/gh".to_string());
+        let endpoints = required_endpoints(&settings);
+        assert!(endpoints.iter().any(|(_, url)| url == "https://artifactory.example.com/tools.json"));
+        assert!(endpoints.iter().any(|(_, url)| url == "https://artifactory.example.com/gh"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_url_is_reported() {
+        let report = check_endpoint("Test", "not a url").await;
+        assert!(!report.is_ok());
+    }
+}
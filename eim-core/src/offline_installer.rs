@@ -319,25 +319,25 @@ fn setup_scoop_packages(scoop_path: &Path, scoop_command: &Path) -> Result<(), S
     let packages = [
         ScoopPackage {
             name: "7zip",
-            template_content: include_str!("../../scoop_manifest_templates/7zip.json"),
+            template_content: include_str!("../scoop_manifest_templates/7zip.json"),
             manifest_filename: "7zip.json",
             test_command: "echo 0"
         },
         ScoopPackage {
             name: "git",
-            template_content: include_str!("../../scoop_manifest_templates/git.json"),
+            template_content: include_str!("../scoop_manifest_templates/git.json"),
             manifest_filename: "git.json",
             test_command: "git --version",
         },
         ScoopPackage {
             name: "dark",
-            template_content: include_str!("../../scoop_manifest_templates/dark.json"),
+            template_content: include_str!("../scoop_manifest_templates/dark.json"),
             manifest_filename: "dark.json",
             test_command: "echo 0"
         },
         ScoopPackage {
             name: "python",
-            template_content: include_str!("../../scoop_manifest_templates/python311.json"),
+            template_content: include_str!("../scoop_manifest_templates/python311.json"),
             manifest_filename: "python.json",
             test_command: "python3 --version",
         },
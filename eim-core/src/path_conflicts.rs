@@ -0,0 +1,248 @@
+use anyhow::Result;
+use log::debug;
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::idf_config::IdfInstallation;
+use crate::ide_export::InstallationEnvironment;
+
+/// Tools of an installation checked for copies earlier on `PATH`
+pub const CHECKED_TOOLS: &[&str] = &[
+    "idf.py",
+    "esptool.py",
+    "python",
+    "cmake",
+    "ninja",
+    "xtensa-esp32-elf-gcc",
+    "xtensa-esp32s2-elf-gcc",
+    "xtensa-esp32s3-elf-gcc",
+    "riscv32-esp-elf-gcc",
+    "openocd",
+];
+
+/// Tools found on many machines for other purposes, a copy of them only conflicts when it
+/// comes before the one of eim
+const GENERIC_TOOLS: &[&str] = &["python", "cmake", "ninja"];
+
+/// Where the copy shadowing a tool of eim comes from, to tell the user what to remove.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConflictSource {
+    /// The MSYS2 or MinGW toolchains, e.g. of the old ESP-IDF MSYS2 environment
+    Msys2,
+    PlatformIo,
+    /// Another installation registered with eim, e.g. activated in the shell profile
+    OtherInstallation { name: String },
+    /// The legacy ESP-IDF Tools Installer or `install.sh` outside of eim
+    LegacyInstaller,
+    Other,
+}
+
+/// A `PATH` entry whose copy of a tool is used instead of the one of the installation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+pub struct PathConflict {
+    pub tool: String,
+    /// The copy the shell runs
+    pub shadowing: PathBuf,
+    /// The `PATH` entry it is in
+    pub entry: String,
+    /// The copy of the installation, which is not run
+    pub installation: PathBuf,
+    /// Whether the directory of the installation copy is on `PATH`, behind `entry`
+    pub installation_on_path: bool,
+    pub source: ConflictSource,
+}
+
+/// Lowercase with forward slashes, to match paths the same way on every OS.
+fn normalized(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/").to_lowercase()
+}
+
+fn classify(entry: &Path, installations: &[IdfInstallation]) -> ConflictSource {
+    let other = installations.iter().find(|installation| {
+        [&installation.path, &installation.idf_tools_path]
+            .iter()
+            .any(|root| !root.is_empty() && entry.starts_with(root))
+    });
+    if let Some(installation) = other {
+        return ConflictSource::OtherInstallation {
+            name: installation.name.clone(),
+        };
+    }
+    let entry = normalized(entry);
+    if entry.contains("/.platformio/") {
+        ConflictSource::PlatformIo
+    } else if entry.contains("msys") || entry.contains("mingw") {
+        ConflictSource::Msys2
+    } else if entry.contains("espressif/") || entry.contains("/esp-idf") {
+        ConflictSource::LegacyInstaller
+    } else {
+        ConflictSource::Other
+    }
+}
+
+/// Position and path of the first copy of `tool` on `PATH`.
+fn first_on_path(tool: &str, path_var: &OsStr) -> Option<(usize, PathBuf)> {
+    std::env::split_paths(path_var)
+        .enumerate()
+        .find_map(|(index, dir)| {
+            let found = crate::which::find_in_path(tool, &std::env::join_paths([&dir]).ok()?)?;
+            Some((index, found))
+        })
+}
+
+/// Position of `dir` on `PATH`.
+fn position_on_path(dir: &Path, path_var: &OsStr) -> Option<usize> {
+    std::env::split_paths(path_var).position(|entry| crate::which::same_file(&entry, dir))
+}
+
+/// The copies of the tools of `environment` which `path_var` resolves to instead of the ones of
+/// the installation.
+///
+/// ESP-IDF tools like the compilers, OpenOCD or `idf.py` conflict wherever they come from, a
+/// shell without the activation runs them instead of the ones of eim. Python, CMake and Ninja
+/// only conflict when they come before the directory of eim on `PATH`.
+pub fn find_conflicts_in(
+    environment: &InstallationEnvironment,
+    path_var: &OsStr,
+    installations: &[IdfInstallation],
+) -> Vec<PathConflict> {
+    let others: Vec<IdfInstallation> = installations
+        .iter()
+        .filter(|installation| Path::new(&installation.path) != environment.idf_path)
+        .cloned()
+        .collect();
+    let mut conflicts = Vec::new();
+    for tool in CHECKED_TOOLS {
+        let Some(installation) = crate::which::installation_copy(environment, tool) else {
+            continue;
+        };
+        let Some((index, shadowing)) = first_on_path(tool, path_var) else {
+            continue;
+        };
+        if crate::which::same_file(&installation, &shadowing) {
+            continue;
+        }
+        let installation_position = installation
+            .parent()
+            .and_then(|dir| position_on_path(dir, path_var));
+        if GENERIC_TOOLS.contains(tool) && !installation_position.is_some_and(|position| position > index) {
+            continue;
+        }
+        let entry = shadowing.parent().map(Path::to_path_buf).unwrap_or_default();
+        debug!("{} of {} is shadowed by {}", tool, environment.name, shadowing.display());
+        conflicts.push(PathConflict {
+            tool: tool.to_string(),
+            source: classify(&entry, &others),
+            entry: entry.to_string_lossy().into_owned(),
+            installation_on_path: installation_position.is_some(),
+            shadowing,
+            installation,
+        });
+    }
+    conflicts
+}
+
+/// The conflicts of `installation` with the `PATH` of this process.
+pub fn find_conflicts(installation: &IdfInstallation) -> Result<Vec<PathConflict>> {
+    let environment = InstallationEnvironment::from_installation(installation)?;
+    let installations = crate::version_manager::get_esp_ide_config()
+        .map(|config| config.idf_installed)
+        .unwrap_or_default();
+    Ok(find_conflicts_in(
+        &environment,
+        &std::env::var_os("PATH").unwrap_or_default(),
+        &installations,
+    ))
+}
+
+/// Checks the selected installation, empty if there is none.
+pub fn check_selected() -> Result<Vec<PathConflict>> {
+    match crate::version_manager::get_selected_version() {
+        Some(installation) => find_conflicts(&installation),
+        None => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idf_config::test_installation;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn installation(name: &str, path: &Path) -> IdfInstallation {
+        IdfInstallation {
+            idf_tools_path: path.join("tools").to_string_lossy().into_owned(),
+            path: path.to_string_lossy().into_owned(),
+            ..test_installation(name)
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let others = [installation("v5.1", Path::new("/opt/esp/v5.1"))];
+        assert_eq!(
+            classify(Path::new("/opt/esp/v5.1/tools/cmake/bin"), &others),
+            ConflictSource::OtherInstallation { name: "v5.1".to_string() }
+        );
+        assert_eq!(
+            classify(Path::new("/home/user/.platformio/packages/toolchain-xtensa-esp32/bin"), &others),
+            ConflictSource::PlatformIo
+        );
+        assert_eq!(classify(Path::new("C:\\msys32\\opt\\xtensa-esp32-elf\\bin"), &others), ConflictSource::Msys2);
+        assert_eq!(
+            classify(Path::new("/home/user/.espressif/tools/openocd-esp32/bin"), &others),
+            ConflictSource::LegacyInstaller
+        );
+        assert_eq!(classify(Path::new("/usr/bin"), &others), ConflictSource::Other);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_conflicts_in() {
+        let dir = TempDir::new().unwrap();
+        let eim_bin = dir.path().join("eim/tools/bin");
+        let platformio = dir.path().join(".platformio/packages/toolchain/bin");
+        let system = dir.path().join("usr/bin");
+        for folder in [&eim_bin, &platformio, &system] {
+            fs::create_dir_all(folder).unwrap();
+        }
+        for (folder, tool) in [
+            (&eim_bin, "xtensa-esp32-elf-gcc"),
+            (&eim_bin, "cmake"),
+            (&eim_bin, "ninja"),
+            (&platformio, "xtensa-esp32-elf-gcc"),
+            (&system, "cmake"),
+            (&system, "ninja"),
+        ] {
+            fs::write(folder.join(tool), "").unwrap();
+        }
+        let environment = InstallationEnvironment {
+            name: "v5.4".to_string(),
+            idf_version: "v5.4".to_string(),
+            idf_path: dir.path().join("eim/esp-idf"),
+            tools_path: dir.path().join("eim/tools"),
+            python: dir.path().join("eim/python/bin/python"),
+            path_entries: vec![eim_bin.to_string_lossy().into_owned()],
+            variables: vec![],
+        };
+
+        // cmake and ninja of the system come before eim, the compiler of PlatformIO after it
+        let path_var = std::env::join_paths([&system, &eim_bin, &platformio]).unwrap();
+        let conflicts = find_conflicts_in(&environment, &path_var, &[]);
+        let tools: Vec<&str> = conflicts.iter().map(|c| c.tool.as_str()).collect();
+        assert_eq!(tools, vec!["cmake", "ninja"]);
+        assert!(conflicts.iter().all(|c| c.installation_on_path && c.source == ConflictSource::Other));
+
+        let path_var = std::env::join_paths([&platformio, &system]).unwrap();
+        let conflicts = find_conflicts_in(&environment, &path_var, &[]);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].tool, "xtensa-esp32-elf-gcc");
+        assert_eq!(conflicts[0].source, ConflictSource::PlatformIo);
+        assert!(!conflicts[0].installation_on_path);
+    }
+}
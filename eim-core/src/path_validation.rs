@@ -0,0 +1,184 @@
+use rust_i18n::t;
+use serde::Serialize;
+use std::path::Path;
+
+/// Longest installation path which leaves room for the deepest files of the toolchains and of
+/// project builds below the 260 character limit of Windows
+pub const RECOMMENDED_MAX_LENGTH: usize = 90;
+
+/// Filesystem types of network shares in `/proc/mounts`
+#[cfg(not(windows))]
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "afs", "ceph", "glusterfs", "9p", "fuse.sshfs", "fuse.rclone", "davfs",
+];
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+#[serde(rename_all = "snake_case")]
+pub enum PathWarningKind {
+    Spaces,
+    NonAscii,
+    NotWritable,
+    NetworkDrive,
+    PathLength,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The installation may work, but builds may fail or be slow
+    Warning,
+    /// The installation or the builds will fail
+    Error,
+}
+
+/// A problem of an installation path, shown next to the path before installing.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+pub struct PathWarning {
+    pub kind: PathWarningKind,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl PathWarning {
+    fn new(kind: PathWarningKind, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            severity,
+            message: message.into(),
+        }
+    }
+}
+
+/// Checks of the path text alone: spaces, non-ASCII characters and, on Windows, its length.
+fn text_warnings(path: &str, windows: bool) -> Vec<PathWarning> {
+    // CMake and the toolchains of ESP-IDF fail on such paths on Windows, elsewhere only some scripts do
+    let severity = if windows { Severity::Error } else { Severity::Warning };
+    let mut warnings = Vec::new();
+    if path.contains(' ') {
+        warnings.push(PathWarning::new(PathWarningKind::Spaces, severity, t!("path_validation.spaces")));
+    }
+    if !path.is_ascii() {
+        let characters: String = path.chars().filter(|c| !c.is_ascii()).collect();
+        warnings.push(PathWarning::new(
+            PathWarningKind::NonAscii,
+            severity,
+            t!("path_validation.non_ascii", characters = characters),
+        ));
+    }
+    let length = path.chars().count();
+    if windows && length > RECOMMENDED_MAX_LENGTH {
+        warnings.push(PathWarning::new(
+            PathWarningKind::PathLength,
+            Severity::Warning,
+            t!("path_validation.length", length = length, max = RECOMMENDED_MAX_LENGTH),
+        ));
+    }
+    warnings
+}
+
+/// Filesystem type of the mount holding `path`, from the longest matching mount point.
+#[cfg(not(windows))]
+fn mount_type(mounts: &str, path: &Path) -> Option<String> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (_, mount_point, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+            // spaces in mount points are written as \040
+            let mount_point = mount_point.replace("\\040", " ");
+            path.starts_with(&mount_point).then(|| (mount_point.len(), fs_type.to_string()))
+        })
+        .max_by_key(|(length, _)| *length)
+        .map(|(_, fs_type)| fs_type)
+}
+
+#[cfg(windows)]
+fn is_network_path(path: &Path) -> bool {
+    use std::path::{Component, Prefix};
+    match path.components().next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::UNC(..) | Prefix::VerbatimUNC(..) => true,
+            Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => {
+                let root: Vec<u16> = format!("{}:\\", letter as char).encode_utf16().chain(Some(0)).collect();
+                unsafe { winapi::um::fileapi::GetDriveTypeW(root.as_ptr()) == winapi::um::winbase::DRIVE_REMOTE }
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+#[cfg(not(windows))]
+fn is_network_path(path: &Path) -> bool {
+    let Ok(mounts) = std::fs::read_to_string("/proc/mounts") else {
+        return false;
+    };
+    mount_type(&mounts, path).is_some_and(|fs_type| NETWORK_FILESYSTEMS.contains(&fs_type.as_str()))
+}
+
+/// Checks an installation path before anything is installed there: characters which break
+/// ESP-IDF builds, write permissions, network drives and the path length on Windows.
+pub fn validate_install_path(path: &Path) -> Vec<PathWarning> {
+    let mut warnings = text_warnings(&path.to_string_lossy(), cfg!(windows));
+    // the folder usually doesn't exist yet, its nearest existing parent tells where it will be
+    let existing = path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .and_then(|ancestor| ancestor.canonicalize().ok());
+    if let Err(err) = crate::shared_tools::check_writable(path) {
+        warnings.push(PathWarning::new(PathWarningKind::NotWritable, Severity::Error, err.to_string()));
+    }
+    if existing.is_some_and(|existing| is_network_path(&existing)) {
+        warnings.push(PathWarning::new(
+            PathWarningKind::NetworkDrive,
+            Severity::Warning,
+            t!("path_validation.network_drive"),
+        ));
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_warnings() {
+        assert!(text_warnings("/home/user/.espressif", false).is_empty());
+
+        let kinds = |warnings: Vec<PathWarning>| warnings.into_iter().map(|w| (w.kind, w.severity)).collect::<Vec<_>>();
+        assert_eq!(
+            kinds(text_warnings("C:\\Users\\Jürgen Müller\\.espressif", true)),
+            vec![(PathWarningKind::Spaces, Severity::Error), (PathWarningKind::NonAscii, Severity::Error)]
+        );
+        assert_eq!(
+            kinds(text_warnings("/home/jdoe/my projects", false)),
+            vec![(PathWarningKind::Spaces, Severity::Warning)]
+        );
+        let long = format!("C:\\{}", "a".repeat(RECOMMENDED_MAX_LENGTH));
+        assert_eq!(kinds(text_warnings(&long, true)), vec![(PathWarningKind::PathLength, Severity::Warning)]);
+        assert!(text_warnings(&long, false).is_empty());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_mount_type() {
+        let mounts = "/dev/sda1 / ext4 rw 0 0\n\
+                      server:/export /home/shared nfs4 rw 0 0\n\
+                      //nas/esp\\040tools /mnt/esp\\040tools cifs rw 0 0\n";
+        assert_eq!(mount_type(mounts, Path::new("/home/user")).as_deref(), Some("ext4"));
+        assert_eq!(mount_type(mounts, Path::new("/home/shared/esp")).as_deref(), Some("nfs4"));
+        assert_eq!(mount_type(mounts, Path::new("/home/sharedother")).as_deref(), Some("ext4"));
+        assert_eq!(mount_type(mounts, Path::new("/mnt/esp tools/v5.4")).as_deref(), Some("cifs"));
+    }
+
+    #[test]
+    fn test_temp_dir_is_writable() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let warnings = validate_install_path(&dir.path().join("esp"));
+        assert!(!warnings.iter().any(|w| w.kind == PathWarningKind::NotWritable), "{:?}", warnings);
+    }
+}
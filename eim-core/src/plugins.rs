@@ -0,0 +1,438 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config_schema::{detect_format, parse_table};
+use crate::errors::{EimError, ErrorKind};
+use crate::idf_config::IdfInstallation;
+use crate::idf_tools::{self, Download, Tool, ToolsFile, Version};
+use crate::settings::Settings;
+
+pub const PLUGINS_REGISTRY_FILE_NAME: &str = "eim_plugins.json";
+/// Folder of the plugins inside the tools directory of an installation
+pub const PLUGINS_FOLDER_NAME: &str = "plugins";
+/// Placeholder in `env` values replaced by the directory the plugin was installed to
+pub const PLUGIN_PATH_PLACEHOLDER: &str = "{plugin_path}";
+
+/// A downloadable archive of a plugin for one or more platforms.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct PluginArtifact {
+    /// Platforms as named in tools.json (`linux-amd64`, `win64`, `macos-arm64`, ...) or `any`
+    pub platforms: Vec<String>,
+    pub url: String,
+    pub sha256: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// Manifest published by third parties to make a component installable with `eim plugin add`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct PluginManifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    pub artifacts: Vec<PluginArtifact>,
+    /// Directories (relative to the plugin directory) added to PATH, `bin` finds all bin directories
+    #[serde(default)]
+    pub export_paths: Vec<Vec<String>>,
+    /// Environment variables set by the activation script
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub strip_container_dirs: Option<u8>,
+}
+
+impl PluginManifest {
+    /// Parses a manifest in TOML, YAML or JSON format.
+    pub fn parse(content: &str, source: &str) -> Result<Self> {
+        let invalid = |message: String| -> anyhow::Error {
+            EimError::new(
+                ErrorKind::Usage,
+                format!("Invalid plugin manifest {}: {}", source, message),
+            )
+            .into()
+        };
+        let table = parse_table(content, detect_format(source, content))
+            .map_err(|issue| invalid(issue.to_string()))?;
+        let manifest: PluginManifest = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| invalid(e.message().to_string()))?;
+        manifest.validate().map_err(invalid)?;
+        Ok(manifest)
+    }
+
+    fn validate(&self) -> std::result::Result<(), String> {
+        let valid_name = |s: &str| {
+            !s.is_empty()
+                && s
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+                && !s.starts_with('.')
+        };
+        if !valid_name(&self.name) {
+            return Err(format!("'{}' is not a valid plugin name", self.name));
+        }
+        if !valid_name(&self.version) {
+            return Err(format!("'{}' is not a valid plugin version", self.version));
+        }
+        if self.artifacts.is_empty() {
+            return Err("no artifacts".to_string());
+        }
+        for artifact in &self.artifacts {
+            if artifact.sha256.len() != 64 || !artifact.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("invalid sha256 for {}", artifact.url));
+            }
+            if artifact.platforms.is_empty() {
+                return Err(format!("no platforms for {}", artifact.url));
+            }
+        }
+        for (key, value) in &self.env {
+            check_env_var(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Describes the plugin as a tools.json tool, so it is installed by the same engine as the ESP-IDF tools.
+    pub fn to_tool(&self) -> Tool {
+        let mut downloads = HashMap::new();
+        for artifact in &self.artifacts {
+            for platform in &artifact.platforms {
+                downloads.insert(
+                    platform.clone(),
+                    Download {
+                        sha256: artifact.sha256.to_lowercase(),
+                        size: artifact.size,
+                        url: artifact.url.clone(),
+                        rename_dist: None,
+                    },
+                );
+            }
+        }
+        Tool {
+            description: self.description.clone(),
+            export_paths: if self.export_paths.is_empty() {
+                vec![vec!["bin".to_string()]]
+            } else {
+                self.export_paths.clone()
+            },
+            export_vars: self.env.clone(),
+            info_url: String::new(),
+            install: "always".to_string(),
+            license: None,
+            name: self.name.clone(),
+            platform_overrides: None,
+            supported_targets: None,
+            strip_container_dirs: self.strip_container_dirs,
+            version_cmd: Vec::new(),
+            version_regex: String::new(),
+            version_regex_replace: None,
+            versions: vec![Version {
+                name: self.version.clone(),
+                status: "recommended".to_string(),
+                downloads,
+            }],
+        }
+    }
+}
+
+/// A plugin installed into an ESP-IDF installation, recorded in `eim_plugins.json`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct InstalledPlugin {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    /// Id of the installation in eim_idf.json
+    pub installation_id: String,
+    pub path: String,
+    /// Entries added to PATH in the activation script
+    pub export_paths: Vec<String>,
+    pub env: Vec<(String, String)>,
+}
+
+pub fn get_registry_path() -> PathBuf {
+    PathBuf::from(Settings::default().esp_idf_json_path.unwrap_or_default()).join(PLUGINS_REGISTRY_FILE_NAME)
+}
+
+/// Returns all installed plugins, empty if no plugin was installed yet.
+pub fn list_plugins() -> Result<Vec<InstalledPlugin>> {
+    let path = get_registry_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Returns the plugins installed into the installation with id `installation_id`.
+pub fn plugins_of(installation_id: &str) -> Vec<InstalledPlugin> {
+    list_plugins()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|plugin| plugin.installation_id == installation_id)
+        .collect()
+}
+
+fn save_plugins(plugins: &[InstalledPlugin]) -> Result<()> {
+    let path = get_registry_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(plugins)?)?;
+    Ok(())
+}
+
+/// Reads a manifest from a local path or downloads it from an http(s) URL.
+pub async fn load_manifest(source: &str) -> Result<PluginManifest> {
+    let content = if source.starts_with("http://") || source.starts_with("https://") {
+        let response = crate::http_client::authorize(crate::http_client::client().get(source), source)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(EimError::new(
+                ErrorKind::Network,
+                format!("Failed to download plugin manifest {}: HTTP {}", source, response.status()),
+            )
+            .into());
+        }
+        response.text().await?
+    } else {
+        fs::read_to_string(crate::expand_tilde(Path::new(source))).map_err(|e| {
+            EimError::new(ErrorKind::NotFound, format!("Failed to read plugin manifest {}: {}", source, e))
+        })?
+    };
+    PluginManifest::parse(&content, source)
+}
+
+/// Marks the lines a plugin adds to an activation script, so they can be removed again.
+fn env_line_marker(name: &str) -> String {
+    format!("# eim plugin {}", name)
+}
+
+/// Environment variables of a plugin end up in activation scripts: the names have to be plain
+/// identifiers and the values must not break out of their line.
+fn check_env_var(key: &str, value: &str) -> std::result::Result<(), String> {
+    let mut chars = key.chars();
+    let valid_key = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_key {
+        return Err(format!("'{}' is not a valid environment variable name", key));
+    }
+    if value.chars().any(char::is_control) {
+        return Err(format!("the value of {} contains control characters", key));
+    }
+    Ok(())
+}
+
+/// Quotes `value` literally for a PowerShell or POSIX shell script.
+fn quote_env_value(value: &str, is_powershell: bool) -> String {
+    if is_powershell {
+        // PowerShell also ends single quoted strings at the typographic single quotes
+        let escaped: String = value
+            .chars()
+            .flat_map(|c| {
+                let quote = matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}');
+                std::iter::repeat(c).take(if quote { 2 } else { 1 })
+            })
+            .collect();
+        format!("'{}'", escaped)
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+fn append_env_to_activation_script(script: &Path, name: &str, env: &[(String, String)]) -> Result<()> {
+    if env.is_empty() {
+        return Ok(());
+    }
+    for (key, value) in env {
+        check_env_var(key, value).map_err(|message| EimError::new(ErrorKind::Usage, message))?;
+    }
+    let is_powershell = script.extension().is_some_and(|ext| ext == "ps1");
+    let mut content = fs::read_to_string(script)?;
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    for (key, value) in env {
+        let value = quote_env_value(value, is_powershell);
+        let line = if is_powershell {
+            format!("$env:{} = {} {}\n", key, value, env_line_marker(name))
+        } else {
+            format!("export {}={} {}\n", key, value, env_line_marker(name))
+        };
+        content.push_str(&line);
+    }
+    fs::write(script, content)?;
+    Ok(())
+}
+
+fn remove_plugin_from_activation_script(script: &Path, plugin: &InstalledPlugin) -> Result<()> {
+    if !script.exists() {
+        return Ok(());
+    }
+    let marker = env_line_marker(&plugin.name);
+    let content: String = fs::read_to_string(script)?
+        .lines()
+        .filter(|line| !line.trim_end().ends_with(&marker))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    fs::write(script, content)?;
+    crate::remove_paths_from_activation_script(script, &plugin.export_paths)
+}
+
+/// Downloads, verifies and extracts the plugin into the tools directory of `installation`
+/// and adds its paths and environment variables to the activation script.
+///
+/// A plugin which is already installed into the installation is replaced.
+pub async fn install_plugin(installation: &IdfInstallation, manifest: &PluginManifest) -> Result<InstalledPlugin> {
+    if plugins_of(&installation.id).iter().any(|p| p.name == manifest.name) {
+        info!("Replacing the installed plugin {}", manifest.name);
+        uninstall_plugin(installation, &manifest.name)?;
+    }
+    let tools_file = ToolsFile {
+        tools: vec![manifest.to_tool()],
+        version: 2,
+    };
+    let tools_dir = PathBuf::from(&installation.idf_tools_path);
+    let plugins_dir = tools_dir.join(PLUGINS_FOLDER_NAME);
+    let download_dir = tools_dir.join("dist");
+    fs::create_dir_all(&download_dir)?;
+    let installed = idf_tools::setup_tools(
+        &tools_file,
+        vec!["all".to_string()],
+        &download_dir,
+        &plugins_dir,
+        None,
+        None,
+        |_| {},
+    )
+    .await?;
+    if installed.is_empty() {
+        return Err(EimError::new(
+            ErrorKind::NotFound,
+            format!(
+                "Plugin {} has no artifact for {}",
+                manifest.name,
+                idf_tools::get_platform_identification().unwrap_or_default()
+            ),
+        )
+        .into());
+    }
+
+    let plugin_path = plugins_dir.join(&manifest.name).join(&manifest.version);
+    let export_paths = idf_tools::get_tools_export_paths_from_list(
+        tools_file,
+        installed,
+        plugins_dir.to_str().unwrap(),
+    );
+    let mut env: Vec<(String, String)> = manifest
+        .env
+        .iter()
+        .map(|(key, value)| {
+            (
+                key.clone(),
+                value.replace(PLUGIN_PATH_PLACEHOLDER, &plugin_path.to_string_lossy()),
+            )
+        })
+        .collect();
+    env.sort();
+    let script = Path::new(&installation.activation_script);
+    crate::prepend_paths_to_activation_script(script, &export_paths)?;
+    append_env_to_activation_script(script, &manifest.name, &env)?;
+
+    let plugin = InstalledPlugin {
+        name: manifest.name.clone(),
+        version: manifest.version.clone(),
+        description: manifest.description.clone(),
+        installation_id: installation.id.clone(),
+        path: plugin_path.to_string_lossy().into_owned(),
+        export_paths,
+        env,
+    };
+    let mut plugins = list_plugins()?;
+    plugins.push(plugin.clone());
+    save_plugins(&plugins)?;
+    Ok(plugin)
+}
+
+/// Removes a plugin from `installation`, deleting its files unless another installation uses them.
+pub fn uninstall_plugin(installation: &IdfInstallation, name: &str) -> Result<InstalledPlugin> {
+    let mut plugins = list_plugins()?;
+    let index = plugins
+        .iter()
+        .position(|p| p.installation_id == installation.id && p.name == name)
+        .ok_or_else(|| {
+            EimError::new(
+                ErrorKind::NotFound,
+                format!("Plugin {} is not installed in {}", name, installation.name),
+            )
+        })?;
+    let plugin = plugins.remove(index);
+    remove_plugin_from_activation_script(Path::new(&installation.activation_script), &plugin)?;
+    // installations sharing a tools directory share the plugin files too
+    if !plugins.iter().any(|p| p.path == plugin.path) {
+        if let Err(e) = fs::remove_dir_all(&plugin.path) {
+            warn!("Failed to remove {}: {}", plugin.path, e);
+        }
+    }
+    save_plugins(&plugins)?;
+    Ok(plugin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = PluginManifest::parse(
+            r#"
+name = "acme-sdk"
+version = "1.2.0"
+export_paths = [["tools", "bin"]]
+
+[env]
+ACME_SDK_PATH = "{plugin_path}"
+
+[[artifacts]]
+platforms = ["linux-amd64", "linux-arm64"]
+url = "https://example.com/acme-sdk-1.2.0-linux.tar.gz"
+sha256 = "0000000000000000000000000000000000000000000000000000000000000000"
+"#,
+            "acme.toml",
+        )
+        .unwrap();
+        let tool = manifest.to_tool();
+        assert_eq!(tool.versions[0].downloads.len(), 2);
+        assert_eq!(tool.export_paths, vec![vec!["tools".to_string(), "bin".to_string()]]);
+
+        let json = r#"{"name": "../x", "version": "1", "artifacts": []}"#;
+        assert!(PluginManifest::parse(json, "x.json").is_err());
+    }
+
+    #[test]
+    fn test_env_is_checked_and_quoted() {
+        assert!(check_env_var("ACME_SDK_PATH", "/opt/acme").is_ok());
+        assert!(check_env_var("1ACME", "x").is_err());
+        assert!(check_env_var("PATH;rm", "x").is_err());
+        assert!(check_env_var("ACME", "x\nrm -rf ~").is_err());
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let script = temp_dir.path().join("activate.sh");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        let env = vec![("ACME".to_string(), "it's $(whoami)".to_string())];
+        append_env_to_activation_script(&script, "acme", &env).unwrap();
+        let content = fs::read_to_string(&script).unwrap();
+        assert!(content.contains("export ACME='it'\\''s $(whoami)' # eim plugin acme"));
+
+        let script = temp_dir.path().join("activate.ps1");
+        fs::write(&script, "").unwrap();
+        append_env_to_activation_script(&script, "acme", &env).unwrap();
+        let content = fs::read_to_string(&script).unwrap();
+        assert!(content.contains("$env:ACME = 'it''s $(whoami)' # eim plugin acme"));
+    }
+}
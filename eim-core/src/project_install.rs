@@ -0,0 +1,109 @@
+use anyhow::Result;
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+
+use crate::errors::{EimError, ErrorKind};
+use crate::idf_config::{IdfConfig, IdfInstallation, IDF_CONFIG_FILE_NAME};
+use crate::settings::Settings;
+
+/// Folder inside the project holding a project-local installation, the same layout as `~/.espressif`
+pub const PROJECT_INSTALL_DIR_NAME: &str = ".espressif";
+
+/// Folder of a project-local installation in `project_dir`.
+pub fn install_dir(project_dir: &Path) -> PathBuf {
+    project_dir.join(PROJECT_INSTALL_DIR_NAME)
+}
+
+/// Folder with the tools (`IDF_TOOLS_PATH`), the activation scripts and `eim_idf.json` of a project-local installation.
+pub fn tools_dir(project_dir: &Path) -> PathBuf {
+    install_dir(project_dir).join("tools")
+}
+
+/// Points all installation paths into `project_dir`, so the project vendors its exact ESP-IDF and toolchain.
+pub fn apply_to_settings(settings: &mut Settings, project_dir: &Path) -> Result<()> {
+    if !project_dir.is_dir() {
+        return Err(EimError::new(
+            ErrorKind::NotFound,
+            format!("Project directory {} does not exist", project_dir.display()),
+        )
+        .into());
+    }
+    let project_dir = project_dir.canonicalize()?;
+    let base = install_dir(&project_dir);
+    let tools = tools_dir(&project_dir).to_string_lossy().into_owned();
+    settings.path = Some(base.clone());
+    settings.tool_install_folder_name = Some(tools.clone());
+    settings.tool_download_folder_name = Some(base.join("dist").to_string_lossy().into_owned());
+    settings.esp_idf_json_path = Some(tools.clone());
+    settings.activation_script_path_override = Some(tools);
+    info!("Installing into the project {}", project_dir.display());
+    Ok(())
+}
+
+/// Looks for a project-local installation in `start` and its parent folders.
+///
+/// Returns the project folder. The `~/.espressif` folder of the per-user installation is not a project.
+pub fn find_project(start: &Path) -> Option<PathBuf> {
+    let home = dirs::home_dir();
+    start
+        .ancestors()
+        .filter(|dir| home.as_deref() != Some(*dir))
+        .find(|dir| tools_dir(dir).join(IDF_CONFIG_FILE_NAME).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Finds the installation to activate in the project: the one named by `identifier`,
+/// otherwise the selected one or the only one.
+pub fn find_installation(project_dir: &Path, identifier: Option<&str>) -> Result<IdfInstallation> {
+    let config_path = tools_dir(project_dir).join(IDF_CONFIG_FILE_NAME);
+    debug!("Reading project installation from {}", config_path.display());
+    let config = IdfConfig::from_file(&config_path)?;
+    let found = match identifier {
+        Some(identifier) => config
+            .idf_installed
+            .iter()
+            .find(|install| install.id == identifier || install.name == identifier)
+            .cloned(),
+        None => config.get_selected_installation().cloned().or_else(|| {
+            (config.idf_installed.len() == 1).then(|| config.idf_installed[0].clone())
+        }),
+    };
+    found.ok_or_else(|| {
+        EimError::new(
+            ErrorKind::NotFound,
+            format!(
+                "No {} installation found in the project {}",
+                identifier.unwrap_or("selected"),
+                project_dir.display()
+            ),
+        )
+        .into()
+    })
+}
+
+/// Shell command sourcing the activation script, for `eval "$(eim activate)"` or `eim activate | Invoke-Expression`.
+pub fn activation_command(installation: &IdfInstallation) -> String {
+    format!(". \"{}\"", installation.activation_script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_project_in_parent_folders() {
+        let project = tempfile::TempDir::new().unwrap();
+        let nested = project.path().join("components").join("app");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_project(&nested), None);
+
+        std::fs::create_dir_all(tools_dir(project.path())).unwrap();
+        std::fs::write(tools_dir(project.path()).join(IDF_CONFIG_FILE_NAME), "{}").unwrap();
+        assert_eq!(find_project(&nested), Some(project.path().to_path_buf()));
+
+        let mut settings = Settings::default();
+        apply_to_settings(&mut settings, project.path()).unwrap();
+        let tools = tools_dir(&project.path().canonicalize().unwrap());
+        assert_eq!(settings.esp_idf_json_path, Some(tools.to_string_lossy().into_owned()));
+    }
+}
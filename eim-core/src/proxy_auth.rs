@@ -0,0 +1,93 @@
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::credentials::{self, Credential};
+use crate::http_client;
+
+/// How often the proxy credentials are refreshed for one download before it fails
+pub const MAX_REFRESHES: u32 = 3;
+
+/// Asks the user for a new password of the proxy user, `None` when they cancel.
+pub type PasswordPrompt = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+static PROMPT: Lazy<RwLock<Option<PasswordPrompt>>> = Lazy::new(|| RwLock::new(None));
+/// Bumped whenever the proxy password changes, so downloads which failed with the old one retry
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+/// Held while the credentials are refreshed, downloads failing meanwhile wait for the outcome
+static REFRESH: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+
+/// Sets how the user is asked for a new proxy password, `None` when nobody can be asked.
+pub fn set_prompt(prompt: Option<PasswordPrompt>) {
+    if let Ok(mut guard) = PROMPT.write() {
+        *guard = prompt;
+    }
+}
+
+/// Identifies the proxy password in use, taken before a download starts.
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// User of the configured proxy, credentials can only be refreshed for a proxy with one.
+fn proxy_user() -> Option<String> {
+    let proxy = url::Url::parse(&http_client::proxy_setting()?).ok()?;
+    Some(proxy.username().to_string()).filter(|user| !user.is_empty())
+}
+
+/// Gets new proxy credentials after the proxy answered 407 to a download started at
+/// `seen_generation`, returning whether the download should be made again.
+///
+/// Only one refresh runs at a time: downloads failing while the user is asked wait and then retry
+/// with the new password. The keyring is read again first, the password may have been updated
+/// with `eim credentials set proxy` in the meantime, and only then the user is asked.
+pub async fn refresh(seen_generation: u64) -> bool {
+    let _refreshing = REFRESH.lock().await;
+    if generation() != seen_generation {
+        debug!("Proxy credentials were refreshed meanwhile, retrying");
+        return true;
+    }
+    let Some(user) = proxy_user() else {
+        warn!("The proxy requires authentication, set the proxy user in the proxy setting and run 'eim credentials set proxy'");
+        return false;
+    };
+
+    let used = credentials::get_secret(&Credential::ProxyPassword);
+    credentials::forget_session_secret(&Credential::ProxyPassword);
+    let stored = credentials::get_secret(&Credential::ProxyPassword);
+    if stored.is_some() && stored != used {
+        info!("Using the proxy password updated in the keyring");
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+        return true;
+    }
+    if let (None, Some(used)) = (stored, used) {
+        // a password given with --stdin-password is not in the keyring, keep it if nobody answers
+        credentials::set_session_secret(Credential::ProxyPassword, used);
+    }
+
+    let prompt = PROMPT.read().ok().and_then(|guard| guard.clone());
+    let Some(prompt) = prompt else {
+        warn!("The proxy rejected the password of {}, update it with 'eim credentials set proxy'", user);
+        return false;
+    };
+    info!("The proxy rejected the password of {}, asking for a new one", user);
+    let asked_user = user.clone();
+    let password = tokio::task::spawn_blocking(move || prompt(&asked_user))
+        .await
+        .ok()
+        .flatten()
+        .filter(|password| !password.is_empty());
+    let Some(password) = password else {
+        warn!("No new proxy password was given");
+        return false;
+    };
+    // kept for the next runs too, where the keyring works
+    if let Err(e) = credentials::store_secret(&Credential::ProxyPassword, &password) {
+        debug!("Keeping the new proxy password for this run only: {}", e);
+        credentials::set_session_secret(Credential::ProxyPassword, password);
+    }
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    info!("Proxy password of {} updated, resuming the downloads", user);
+    true
+}
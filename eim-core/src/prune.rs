@@ -0,0 +1,257 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::idf_config::IdfInstallation;
+
+/// A tool version in a tools folder which no registered installation uses anymore.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnusedTool {
+    pub tool: String,
+    pub version: String,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// What an installation uses from its tools folder.
+#[derive(Debug, Default)]
+struct ToolReferences {
+    /// Tool names and versions listed by the `tools.json` of the installation
+    listed: BTreeMap<String, BTreeSet<String>>,
+    /// Activation script and manifest entries, a tool version folder appearing in them is in
+    /// use, e.g. a version set with `tool_version_overrides`
+    mentions: String,
+}
+
+fn tool_references(installation: &IdfInstallation) -> Result<ToolReferences> {
+    let tools_json = Path::new(&installation.path).join("tools").join("tools.json");
+    let tools_file = crate::idf_tools::read_and_parse_tools_file(&tools_json.to_string_lossy())
+        .map_err(|e| anyhow!("Failed to read {}: {}", tools_json.display(), e))?;
+
+    let mut references = ToolReferences::default();
+    for tool in tools_file.tools {
+        references
+            .listed
+            .entry(tool.name)
+            .or_default()
+            .extend(tool.versions.into_iter().map(|version| version.name));
+    }
+    references.mentions = fs::read_to_string(&installation.activation_script).unwrap_or_default();
+    if let Some(manifest) = crate::install_manifest::load_manifest_for(&installation.path) {
+        for entry in manifest.entries {
+            if let crate::install_manifest::ManifestEntry::File { path }
+            | crate::install_manifest::ManifestEntry::Directory { path }
+            | crate::install_manifest::ManifestEntry::Symlink { path, .. } = entry
+            {
+                references.mentions.push('\n');
+                references.mentions.push_str(&path);
+            }
+        }
+    }
+    Ok(references)
+}
+
+fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn is_mentioned(dir: &Path, mentions: &str) -> bool {
+    let dir = dir.to_string_lossy();
+    mentions.match_indices(&*dir).any(|(start, _)| {
+        // the folder itself or a path inside it, not a version with the same prefix
+        matches!(mentions[start + dir.len()..].chars().next(), None | Some('/' | '\\' | '\n' | '"' | '\'' | ':' | ';'))
+    })
+}
+
+/// Tool versions in `tools_dir`, `<tools>/<tool>/<version>`, used by none of `references`.
+///
+/// Only folders of tools some `tools.json` knows are considered, the Python environments and
+/// anything else kept in the tools folder are left alone, and so are the hidden staging and
+/// backup folders, which `eim` cleans up as remnants of failed installations.
+fn find_unused_in(tools_dir: &Path, references: &[ToolReferences]) -> Vec<UnusedTool> {
+    let known: BTreeSet<&String> = references.iter().flat_map(|r| r.listed.keys()).collect();
+    let mut unused = Vec::new();
+    for tool_dir in subdirectories(tools_dir) {
+        let tool = tool_dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if !known.contains(&tool) {
+            continue;
+        }
+        for version_dir in subdirectories(&tool_dir) {
+            let version = version_dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            if version.starts_with('.') {
+                continue;
+            }
+            let used = references.iter().any(|r| {
+                r.listed.get(&tool).is_some_and(|versions| versions.contains(&version))
+                    || is_mentioned(&version_dir, &r.mentions)
+            });
+            if !used {
+                debug!("{} {} is not used by any installation", tool, version);
+                unused.push(UnusedTool {
+                    size: crate::status::dir_size(&version_dir),
+                    tool: tool.clone(),
+                    version,
+                    path: version_dir,
+                });
+            }
+        }
+    }
+    unused
+}
+
+/// Finds the tool versions no registered installation references anymore, typically left
+/// behind by upgrades, in the tools folders of all installations.
+///
+/// A tools folder is skipped with a warning when the `tools.json` of one of the installations
+/// using it can't be read, as what that installation needs is unknown.
+pub fn find_unused_tools() -> Result<Vec<UnusedTool>> {
+    let installations = crate::version_manager::get_esp_ide_config()
+        .map(|config| config.idf_installed)
+        .unwrap_or_default();
+
+    let mut by_tools_dir: BTreeMap<PathBuf, Vec<&IdfInstallation>> = BTreeMap::new();
+    for installation in &installations {
+        by_tools_dir
+            .entry(PathBuf::from(&installation.idf_tools_path))
+            .or_default()
+            .push(installation);
+    }
+
+    let mut unused = Vec::new();
+    for (tools_dir, installations) in by_tools_dir {
+        let references: Result<Vec<ToolReferences>> = installations.into_iter().map(tool_references).collect();
+        match references {
+            Ok(references) => unused.extend(find_unused_in(&tools_dir, &references)),
+            Err(e) => warn!("Not pruning {}: {}", tools_dir.display(), e),
+        }
+    }
+    Ok(unused)
+}
+
+/// Removes `unused` and returns the bytes reclaimed; failures are logged and skipped. Tool
+/// folders left empty are removed too.
+pub fn remove_unused_tools(unused: &[UnusedTool]) -> u64 {
+    let mut reclaimed = 0;
+    for tool in unused {
+        // waits for another user installing this very version into a shared tools folder
+        let _lock = match crate::shared_tools::ToolLock::acquire(&tool.path) {
+            Ok(lock) => lock,
+            Err(e) => {
+                warn!("Failed to lock {}: {}", tool.path.display(), e);
+                continue;
+            }
+        };
+        match crate::utils::remove_directory_all(&tool.path) {
+            Ok(()) => {
+                info!("Removed {} {}", tool.tool, tool.version);
+                reclaimed += tool.size;
+            }
+            Err(e) => warn!("Failed to remove {}: {}", tool.path.display(), e),
+        }
+    }
+    for tool_dir in unused.iter().filter_map(|tool| tool.path.parent()).collect::<BTreeSet<_>>() {
+        let only_locks = fs::read_dir(tool_dir).is_ok_and(|entries| {
+            entries
+                .flatten()
+                .all(|entry| entry.file_name().to_string_lossy().ends_with(crate::shared_tools::LOCK_SUFFIX))
+        });
+        if only_locks {
+            let _ = crate::utils::remove_directory_all(tool_dir);
+        }
+    }
+    reclaimed
+}
+
+pub fn total_size(unused: &[UnusedTool]) -> u64 {
+    unused.iter().map(|tool| tool.size).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn references(listed: &[(&str, &[&str])], mentions: &str) -> ToolReferences {
+        ToolReferences {
+            listed: listed
+                .iter()
+                .map(|(tool, versions)| (tool.to_string(), versions.iter().map(|v| v.to_string()).collect()))
+                .collect(),
+            mentions: mentions.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find_unused_in() {
+        let dir = TempDir::new().unwrap();
+        for path in [
+            "cmake/3.24.0/bin",
+            "cmake/3.30.2/bin",
+            "cmake/.3.16.0.eim-staging",
+            "ninja/1.10.2",
+            "ninja/1.12.1",
+            "openocd-esp32/v0.12.0-custom",
+            "python/v5.1/venv",
+        ] {
+            fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+        fs::write(dir.path().join("cmake/3.24.0/bin/cmake"), [0u8; 64]).unwrap();
+
+        let custom = dir.path().join("openocd-esp32/v0.12.0-custom");
+        let installations = [
+            references(&[("cmake", &["3.30.2"]), ("ninja", &["1.12.1"])], ""),
+            references(
+                &[("ninja", &["1.12.1"]), ("openocd-esp32", &["v0.12.0"])],
+                &format!("export PATH=\"{}/bin:$PATH\"", custom.display()),
+            ),
+        ];
+
+        let mut unused: Vec<(String, String, u64)> = find_unused_in(dir.path(), &installations)
+            .into_iter()
+            .map(|tool| (tool.tool, tool.version, tool.size))
+            .collect();
+        unused.sort();
+        assert_eq!(
+            unused,
+            vec![
+                ("cmake".to_string(), "3.24.0".to_string(), 64),
+                ("ninja".to_string(), "1.10.2".to_string(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_mentioned() {
+        let dir = Path::new("/tools/cmake/3.30.2");
+        assert!(is_mentioned(dir, "PATH=/tools/cmake/3.30.2/bin:$PATH"));
+        assert!(is_mentioned(dir, "/tools/cmake/3.30.2"));
+        assert!(!is_mentioned(dir, "PATH=/tools/cmake/3.30.20/bin"));
+    }
+
+    #[test]
+    fn test_remove_unused_tools() {
+        let dir = TempDir::new().unwrap();
+        let version_dir = dir.path().join("ninja/1.10.2");
+        fs::create_dir_all(&version_dir).unwrap();
+        fs::write(version_dir.join("ninja"), [0u8; 10]).unwrap();
+        let unused = vec![UnusedTool {
+            tool: "ninja".to_string(),
+            version: "1.10.2".to_string(),
+            size: crate::status::dir_size(&version_dir),
+            path: version_dir,
+        }];
+        assert_eq!(remove_unused_tools(&unused), 10);
+        assert!(!dir.path().join("ninja").exists());
+    }
+}
@@ -4,13 +4,15 @@ use log::{debug, error, info, trace, warn};
 use rustpython_vm as vm;
 #[cfg(feature = "userustpython")]
 use rustpython_vm::function::PosArgs;
+use once_cell::sync::Lazy;
 use semver::{Version, VersionReq};
 #[cfg(feature = "userustpython")]
 use std::process::ExitCode;
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
-    sync::mpsc,
+    sync::{mpsc, RwLock},
     time::{Duration, SystemTime},
     vec,
 };
@@ -18,7 +20,7 @@ use std::{
 use vm::{builtins::PyStrRef, Interpreter};
 
 use crate::{
-    command_executor, download_file, ensure_path, replace_unescaped_spaces_posix, replace_unescaped_spaces_win, settings::VersionPaths, system_dependencies::get_scoop_path, utils::{copy_dir_contents, parse_cmake_version, remove_after_second_dot, with_retry}
+    command_executor, download_file, ensure_path, replace_unescaped_spaces_posix, replace_unescaped_spaces_win, settings::{Settings, VersionPaths}, system_dependencies::get_scoop_path, utils::{copy_dir_contents, parse_cmake_version, remove_after_second_dot, with_retry}
 };
 
 /// Runs a Python script from a specified file with optional arguments and environment variables.
@@ -272,6 +274,7 @@ pub fn pip_install_requirements(
     wheel_dir: &Option<PathBuf>,
     pypi_mirror: &Option<String>,
 ) -> Result<(), std::io::Error> {
+    let pypi_mirror = &crate::url_rewrite::pypi_index(pypi_mirror.as_deref());
     let python_location = match std::env::consts::OS {
         "windows" => venv_path.join("Scripts").join("python.exe"),
         _ => venv_path.join("bin").join("python3"),
@@ -281,10 +284,16 @@ pub fn pip_install_requirements(
         debug!("Found PIP_USER=\"yes\" in the environment. Disabling PIP_USER in this shell to install packages into a virtual environment.");
         std::env::set_var("PIP_USER", "no".to_string());
     }
-    let constrain_path = match constraint_file {
-        Some(path) => path.to_str().unwrap(),
-        None => "",
+    // without a constraints file, e.g. when it couldn't be downloaded, pip resolves freely
+    let constraint_args: Vec<&str> = match constraint_file {
+        Some(path) => vec!["--constraint", path.to_str().unwrap()],
+        None => vec![],
     };
+    let quoted_constraint_args = constraint_args
+        .iter()
+        .map(|arg| shlex::quote(arg).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ");
 
     match std::env::consts::OS {
         "windows" => {
@@ -292,9 +301,10 @@ pub fn pip_install_requirements(
                 let mut args = vec![
                     "-m", "pip", "install", "-r",
                     requirements_file.to_str().unwrap(),
-                    "--upgrade", "--constraint", constrain_path,
+                    "--upgrade",
                     "--no-index", "--find-links", wheel_dir.to_str().unwrap()
                 ];
+                args.extend(&constraint_args);
                 command_executor::execute_command_with_env(
                     python_location.to_str().unwrap(),
                     &args,
@@ -304,8 +314,9 @@ pub fn pip_install_requirements(
                 let mut args = vec![
                     "-m", "pip", "install", "-r",
                     requirements_file.to_str().unwrap(),
-                    "--upgrade", "--constraint", constrain_path
+                    "--upgrade"
                 ];
+                args.extend(&constraint_args);
 
                 // Add PyPI mirror if specified
                 if let Some(mirror_url) = pypi_mirror {
@@ -339,10 +350,10 @@ pub fn pip_install_requirements(
                   &vec![
                       "-c",
                       &format!(
-                          "{} -m pip install -r {} --upgrade --constraint {} --no-index --find-links {}",
+                          "{} -m pip install -r {} --upgrade {} --no-index --find-links {}",
                           shlex::quote(python_location.to_str().unwrap()),
                           shlex::quote(requirements_file.to_str().unwrap()),
-                          shlex::quote(constrain_path),
+                          quoted_constraint_args,
                           shlex::quote(wheel_dir.to_str().unwrap())
                       ),
                   ],
@@ -350,10 +361,10 @@ pub fn pip_install_requirements(
                 )
             } else {
                 let mut cmd = format!(
-                    "{} -m pip install -r {} --upgrade --constraint {}",
+                    "{} -m pip install -r {} --upgrade {}",
                     shlex::quote(python_location.to_str().unwrap()),
                     shlex::quote(requirements_file.to_str().unwrap()),
-                    shlex::quote(constrain_path)
+                    quoted_constraint_args
                 );
 
                 // Add PyPI mirror if specified
@@ -469,6 +480,266 @@ fn find_wheel_directory(offline_archive_dir: &Path, python_version: &str) -> Opt
     None
 }
 
+/// The Python interpreter the virtual environments are created with.
+fn system_python_executable() -> String {
+    match std::env::consts::OS {
+        "windows" => {
+          if let Some(scoop_shims_path) = get_scoop_path() {
+            // Use the Scoop shims path for the Python executable
+            let python_executable_path = PathBuf::from(scoop_shims_path).join("python3.exe");
+            match python_executable_path.try_exists() {
+                Ok(true) => python_executable_path.to_string_lossy().into_owned(),
+                Ok(false) => "python3.exe".to_string(),
+                Err(e) => {
+                    warn!("Failed to check if Python executable exists: {}", e);
+                    "python3.exe".to_string()
+                }
+            }
+          } else {
+            "python3.exe".to_string()
+          }
+        },
+        _ => "python3".to_string(),
+    }
+}
+
+/// The requirements files of ESP-IDF for the core and the selected `features`.
+fn requirements_files(paths: &VersionPaths, features: &[String]) -> Vec<PathBuf> {
+    let base_requirements_path = paths.idf_path.join("tools").join("requirements");
+    let mut requirements_file_list = vec![base_requirements_path.join("requirements.core.txt")];
+    for feature in features {
+        let requirements_file =
+            base_requirements_path.join(format!("requirements.{}.txt", feature));
+        if requirements_file.exists() {
+            requirements_file_list.push(requirements_file);
+        } else {
+            warn!(
+                "requirements file not found: {}",
+                requirements_file.display()
+            );
+        }
+    }
+    requirements_file_list
+}
+
+/// The `vX.Y` version the constraints file is published for.
+fn constraints_version(paths: &VersionPaths, idf_version: &str) -> String {
+    match parse_cmake_version(paths.idf_path.to_str().unwrap()) {
+        Ok((maj,min)) => format!("v{}.{}", maj, min),
+        Err(e) => {
+            warn!("Failed to parse CMake version: {}", e);
+            idf_version.to_string()
+        }
+    }
+}
+
+/// Downloads the Python packages of an ESP-IDF version and its constraints file into `dest_dir`,
+/// laid out like in an offline archive, so `install_python_env` can install them without network.
+///
+/// The packages are downloaded for the Python interpreter of this system, no virtual environment
+/// is created.
+///
+/// # Returns
+///
+/// The Python version the packages are for, e.g. "3.11".
+pub async fn download_python_packages(
+    paths: &VersionPaths,
+    idf_version: &str,
+    features: &[String],
+    dest_dir: &Path,
+    pypi_mirror: &Option<String>,
+) -> Result<String, String> {
+    let pypi_mirror = &crate::url_rewrite::pypi_index(pypi_mirror.as_deref());
+    let python_executable = system_python_executable();
+    let python_version = detect_python_version(&python_executable)?;
+    let constraint_file = download_constraints_file(dest_dir, &constraints_version(paths, idf_version))
+        .await
+        .map_err(|e| format!("Failed to download constraints file: {}", e))?;
+    let wheel_dir = dest_dir.join(format!("wheels_py{}", python_version.replace('.', "")));
+    ensure_path(wheel_dir.to_str().unwrap()).map_err(|e| e.to_string())?;
+
+    for requirements_file in requirements_files(paths, features) {
+        let mut args = vec![
+            "-m", "pip", "download", "-r",
+            requirements_file.to_str().unwrap(),
+            "--constraint", constraint_file.to_str().unwrap(),
+            "--dest", wheel_dir.to_str().unwrap(),
+        ];
+        if let Some(mirror_url) = pypi_mirror {
+            args.push("--index-url");
+            args.push(mirror_url.as_str());
+        }
+        let output = command_executor::execute_command(&python_executable, &args)
+            .map_err(|e| format!("Failed to run pip: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "failed to download requirements from file {:?}: {}",
+                requirements_file,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        debug!("requirements downloaded: {}", requirements_file.display());
+    }
+    info!("Python packages for Python {} downloaded to {}", python_version, wheel_dir.display());
+    Ok(python_version)
+}
+
+/// Folder next to the `eim` executable with wheels bundled with the installer, laid out like
+/// an offline archive (`wheels_py311`, ...)
+pub const BUNDLED_WHEELHOUSE_FOLDER: &str = "wheelhouse";
+
+/// Indexes tried in order when the configured one can't be reached, see [`configure_from_settings`]
+static FALLBACK_INDEXES: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Applies the `pypi_fallback_mirrors` setting.
+pub fn configure_from_settings(settings: &Settings) {
+    if let Ok(mut guard) = FALLBACK_INDEXES.write() {
+        *guard = settings.pypi_fallback_mirrors.clone().unwrap_or_default();
+    }
+}
+
+/// Where pip installs the packages of the ESP-IDF requirements from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackageSource {
+    /// A package index as configured, `None` for the default index; URL rewrites are applied by pip_install_requirements
+    Index(Option<String>),
+    /// A folder of wheels, installed from with `--no-index`
+    Wheelhouse(PathBuf),
+}
+
+impl std::fmt::Display for PackageSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageSource::Index(index) => write!(
+                f,
+                "{}",
+                crate::url_rewrite::pypi_index(index.as_deref())
+                    .unwrap_or_else(|| crate::url_rewrite::DEFAULT_PYPI_INDEX.to_string())
+            ),
+            PackageSource::Wheelhouse(dir) => write!(f, "{}", dir.display()),
+        }
+    }
+}
+
+/// The configured index followed by the fallback indexes, without duplicates.
+fn index_sources(pypi_mirror: Option<&str>, fallbacks: &[String]) -> Vec<PackageSource> {
+    let mut sources = vec![PackageSource::Index(pypi_mirror.map(str::to_string))];
+    for fallback in fallbacks.iter().map(|f| f.trim()).filter(|f| !f.is_empty()) {
+        let source = PackageSource::Index(Some(fallback.to_string()));
+        if !sources.iter().any(|s| s.to_string() == source.to_string()) {
+            sources.push(source);
+        }
+    }
+    sources
+}
+
+/// Wheel folders for the last attempt: the packages fetched with `--download-only` for the
+/// version and the wheelhouse bundled with the installer, those present for `python_executable`.
+fn wheelhouse_sources(paths: &VersionPaths, idf_version: &str, python_executable: &str) -> Vec<PackageSource> {
+    let mut roots = vec![crate::download_only::packages_dir(&paths.tool_download_directory, idf_version)];
+    if let Some(exe_dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        roots.push(exe_dir.join(BUNDLED_WHEELHOUSE_FOLDER));
+    }
+    roots.retain(|root| root.is_dir());
+    if roots.is_empty() {
+        return Vec::new();
+    }
+    let python_version = match detect_python_version(python_executable) {
+        Ok(version) => version,
+        Err(e) => {
+            debug!("Not using a wheelhouse as fallback: {}", e);
+            return Vec::new();
+        }
+    };
+    roots
+        .iter()
+        .filter_map(|root| find_wheel_directory(root, &python_version))
+        .map(PackageSource::Wheelhouse)
+        .collect()
+}
+
+/// Whether pip failed to reach the index or to find packages on it, as opposed to a broken
+/// requirement, which another index would not fix.
+pub fn is_index_failure(pip_error: &str) -> bool {
+    const MARKERS: &[&str] = &[
+        "connectionerror",
+        "connecttimeouterror",
+        "max retries exceeded",
+        "read timed out",
+        "temporary failure in name resolution",
+        "name or service not known",
+        "nodename nor servname provided",
+        "getaddrinfo failed",
+        "could not fetch url",
+        "sslerror",
+        "proxyerror",
+        "no matching distribution found",
+        "http error 5",
+        "503 service unavailable",
+    ];
+    let pip_error = pip_error.to_lowercase();
+    MARKERS.iter().any(|marker| pip_error.contains(marker))
+}
+
+/// Name and version of the packages installed in the virtual environment.
+fn installed_packages(venv_path: &Path) -> BTreeMap<String, String> {
+    let python = match std::env::consts::OS {
+        "windows" => venv_path.join("Scripts").join("python.exe"),
+        _ => venv_path.join("bin").join("python3"),
+    };
+    let output = match command_executor::execute_command(
+        python.to_str().unwrap(),
+        &["-m", "pip", "list", "--format=json", "--disable-pip-version-check"],
+    ) {
+        Ok(output) if output.status.success() => output,
+        _ => return BTreeMap::new(),
+    };
+    serde_json::from_slice::<Vec<serde_json::Value>>(&output.stdout)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|package| Some((package["name"].as_str()?.to_string(), package["version"].as_str()?.to_string())))
+        .collect()
+}
+
+/// Installs `requirements_file` from the first of `sources` which works. The next source is
+/// only tried when pip could not reach the previous one, see [`is_index_failure`].
+///
+/// Returns the index of the source the requirements were installed from.
+fn pip_install_with_fallback(
+    venv_path: &Path,
+    requirements_file: &Path,
+    constraint_file: &Option<PathBuf>,
+    sources: &[PackageSource],
+) -> Result<usize, String> {
+    for (i, source) in sources.iter().enumerate() {
+        crate::cancellation::check().map_err(|e| e.to_string())?;
+        let (wheel_dir, index) = match source {
+            PackageSource::Index(index) => (None, index.clone()),
+            PackageSource::Wheelhouse(dir) => (Some(dir.clone()), None),
+        };
+        let error = match pip_install_requirements(venv_path, requirements_file, constraint_file, &wheel_dir, &index) {
+            Ok(()) => {
+                if i > 0 {
+                    warn!("Installed {} from the fallback {}", requirements_file.display(), source);
+                }
+                return Ok(i);
+            }
+            Err(e) => e.to_string(),
+        };
+        match sources.get(i + 1) {
+            Some(next) if is_index_failure(&error) => warn!(
+                "Failed to install {} from {}, trying {}: {}",
+                requirements_file.display(),
+                source,
+                next,
+                error.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or_default()
+            ),
+            _ => return Err(error),
+        }
+    }
+    Err("No package index configured".to_string())
+}
+
 /// Installs or updates the Python virtual environment for a specific ESP-IDF version.
 ///
 /// This asynchronous function orchestrates the creation of a Python virtual environment,
@@ -546,25 +817,7 @@ pub async fn install_python_env(
         debug!("No offline archive directory provided, skipping copying contents.");
     }
 
-    let python_executable = match std::env::consts::OS {
-            "windows" => {
-              if let Some(scoop_shims_path) = get_scoop_path() {
-                // Use the Scoop shims path for the Python executable
-                let python_executable_path = PathBuf::from(scoop_shims_path).join("python3.exe");
-                match python_executable_path.try_exists() {
-                    Ok(true) => python_executable_path.to_string_lossy().into_owned(),
-                    Ok(false) => "python3.exe".to_string(),
-                    Err(e) => {
-                        warn!("Failed to check if Python executable exists: {}", e);
-                        "python3.exe".to_string()
-                    }
-                }
-              } else {
-                "python3.exe".to_string()
-              }
-            },
-            _ => "python3".to_string(),
-        };
+    let python_executable = system_python_executable();
 
     // create the venv
     match create_python_venv(venv_path.to_str().unwrap(), &python_executable) {
@@ -578,30 +831,8 @@ pub async fn install_python_env(
     }
 
     // install the requirements
-    let mut requirements_file_list = vec![];
-
-    let base_requirements_path = paths.idf_path.join("tools").join("requirements");
-    requirements_file_list.push(base_requirements_path.join("requirements.core.txt"));
-    // prepare list of requirements files
-    for feature in features {
-        let requirements_file =
-            base_requirements_path.join(format!("requirements.{}.txt", feature));
-        if requirements_file.exists() {
-            requirements_file_list.push(requirements_file);
-        } else {
-            warn!(
-                "requirements file not found: {}",
-                requirements_file.display()
-            );
-        }
-    }
-    let constrains_idf_version = match parse_cmake_version(paths.idf_path.to_str().unwrap()) {
-        Ok((maj,min)) => format!("v{}.{}", maj, min),
-        Err(e) => {
-            warn!("Failed to parse CMake version: {}", e);
-            idf_version.to_string()
-        }
-    };
+    let requirements_file_list = requirements_files(paths, features);
+    let constrains_idf_version = constraints_version(paths, idf_version);
 
     let constraint_file = if offline_mode {
       let filename = format!("espidf.constraints.{}.txt", remove_after_second_dot(&constrains_idf_version));
@@ -652,11 +883,34 @@ pub async fn install_python_env(
         None
     };
 
+    let sources = match wheel_dir {
+        Some(wheel_dir) => vec![PackageSource::Wheelhouse(wheel_dir)],
+        None => {
+            let fallbacks = FALLBACK_INDEXES.read().map(|f| f.clone()).unwrap_or_default();
+            let mut sources = index_sources(pypi_mirror.as_deref(), &fallbacks);
+            sources.extend(wheelhouse_sources(paths, idf_version, &python_executable));
+            sources
+        }
+    };
+
     // install the requirements from files
     for requirements_file in requirements_file_list {
-        match pip_install_requirements(&venv_path, &requirements_file, &constraint_file, &wheel_dir, pypi_mirror) {
-            Ok(_) => {
+        let before = installed_packages(&venv_path);
+        match pip_install_with_fallback(&venv_path, &requirements_file, &constraint_file, &sources) {
+            Ok(source) => {
                 debug!("requirements installed: {}", requirements_file.display());
+                let packages = installed_packages(&venv_path)
+                    .into_iter()
+                    .filter(|(name, version)| before.get(name) != Some(version))
+                    .map(|(name, version)| crate::install_report::PythonPackageReport {
+                        idf_version: paths.actual_version.clone(),
+                        name,
+                        version,
+                        source: sources[source].to_string(),
+                        fallback: source > 0,
+                    })
+                    .collect();
+                crate::install_report::record_python_packages(packages);
             }
             Err(e) => {
                 error!(
@@ -671,9 +925,60 @@ pub async fn install_python_env(
         }
     }
     info!("Python environment installed successfully");
+    smoke_test_idf_py(paths, idf_tools_path)?;
     Ok(())
 }
 
+/// Runs `idf.py --version` with the python from the freshly created virtual environment.
+///
+/// This makes sure the environment is usable before the installation is reported as successful.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The version reported by idf.py.
+/// * `Err(String)` - A message describing why idf.py could not be executed.
+pub fn smoke_test_idf_py(paths: &VersionPaths, idf_tools_path: &Path) -> Result<String, String> {
+    let idf_py = paths.idf_path.join("tools").join("idf.py");
+    if !idf_py.exists() {
+        return Err(format!("Smoke test of idf.py failed: {} not found", idf_py.display()));
+    }
+    let idf_path = paths.idf_path.to_string_lossy().into_owned();
+    let venv_path = paths.python_venv_path.to_string_lossy().into_owned();
+    let tools_path = idf_tools_path.to_string_lossy().into_owned();
+    let env = vec![
+        ("IDF_PATH", idf_path.as_str()),
+        ("IDF_PYTHON_ENV_PATH", venv_path.as_str()),
+        ("IDF_TOOLS_PATH", tools_path.as_str()),
+    ];
+    let idf_py_str = idf_py.to_string_lossy().into_owned();
+    match command_executor::execute_command_with_env(
+        &paths.python_path.to_string_lossy(),
+        &vec![idf_py_str.as_str(), "--version"],
+        env,
+    ) {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if output.status.success() || stdout.contains("ESP-IDF") {
+                info!("Smoke test of idf.py passed: {}", stdout);
+                Ok(stdout)
+            } else {
+                Err(format!(
+                    "Smoke test of idf.py failed: `{} {} --version` exited with {}: {}",
+                    paths.python_path.display(),
+                    idf_py.display(),
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
+            }
+        }
+        Err(e) => Err(format!(
+            "Smoke test of idf.py failed: python {} could not be executed: {}",
+            paths.python_path.display(),
+            e
+        )),
+    }
+}
+
 /// Runs the IDF tools Python installation script.
 ///
 /// This function prepares the environment to run a Python installation script for
@@ -868,13 +1173,13 @@ pub fn python_sanity_check(python: Option<&str>) -> Vec<Result<String, String>>
         Err(e) => outputs.push(Err(e.to_string())),
     }
     // check standard library
-    let script = include_str!("../../python_scripts/sanity_check/import_standard_library.py");
+    let script = include_str!("../python_scripts/sanity_check/import_standard_library.py");
     outputs.push(run_python_script(script, python));
     // check ctypes
-    let script = include_str!("../../python_scripts/sanity_check/ctypes_check.py");
+    let script = include_str!("../python_scripts/sanity_check/ctypes_check.py");
     outputs.push(run_python_script(script, python));
     // check https
-    let script = include_str!("../../python_scripts/sanity_check/import_standard_library.py");
+    let script = include_str!("../python_scripts/sanity_check/import_standard_library.py");
     outputs.push(run_python_script(script, python));
     outputs
 }
@@ -950,3 +1255,42 @@ pub fn run_idf_tools() -> ExitCode {
     });
     ExitCode::from(interp.run(|_vm| result))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_index_failure() {
+        assert!(is_index_failure(
+            "WARNING: Retrying (Retry(total=4)) after connection broken by 'NewConnectionError(...)'\n\
+             ERROR: Could not find a version that satisfies the requirement esptool\n\
+             ERROR: No matching distribution found for esptool"
+        ));
+        assert!(is_index_failure("pip._vendor.urllib3.exceptions.ReadTimeoutError: Read timed out."));
+        assert!(!is_index_failure(
+            "ERROR: Cannot install esptool==4.8 because these package versions have conflicting dependencies.\n\
+             ERROR: ResolutionImpossible"
+        ));
+    }
+
+    #[test]
+    fn test_index_sources() {
+        let sources = index_sources(
+            Some("https://mirror.example.com/simple"),
+            &[
+                " https://mirror.example.com/simple".to_string(),
+                "https://pypi.org/simple".to_string(),
+                String::new(),
+            ],
+        );
+        assert_eq!(
+            sources,
+            vec![
+                PackageSource::Index(Some("https://mirror.example.com/simple".to_string())),
+                PackageSource::Index(Some("https://pypi.org/simple".to_string())),
+            ]
+        );
+        assert_eq!(index_sources(None, &[])[0].to_string(), crate::url_rewrite::DEFAULT_PYPI_INDEX);
+    }
+}
@@ -0,0 +1,208 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::idf_config::IdfInstallation;
+use crate::idf_tools::{self, ToolsFile};
+
+/// Espressif QEMU builds in tools.json, installed only on request
+pub const QEMU_TOOLS: &[&str] = &["qemu-xtensa", "qemu-riscv32"];
+/// How long the ROM bootloader may take to print its banner in the smoke test
+pub const SMOKE_TEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Returns the emulator binary and the machine booted in the smoke test for a QEMU tool.
+fn qemu_binary_and_machine(tool_name: &str) -> Option<(&'static str, &'static str)> {
+    match tool_name {
+        "qemu-xtensa" => Some(("qemu-system-xtensa", "esp32")),
+        "qemu-riscv32" => Some(("qemu-system-riscv32", "esp32c3")),
+        _ => None,
+    }
+}
+
+/// Removes the QEMU tools from the tools file, so they are only installed when asked for.
+pub fn without_qemu(mut tools_file: ToolsFile) -> ToolsFile {
+    tools_file
+        .tools
+        .retain(|tool| !QEMU_TOOLS.contains(&tool.name.as_str()));
+    tools_file
+}
+
+/// Keeps only the QEMU tools in the tools file.
+pub fn only_qemu(mut tools_file: ToolsFile) -> ToolsFile {
+    tools_file
+        .tools
+        .retain(|tool| QEMU_TOOLS.contains(&tool.name.as_str()));
+    tools_file
+}
+
+/// Finds the emulator binary of `tool_name` in the tools directory.
+pub fn find_qemu_binary(tools_dir: &Path, tool_name: &str) -> Option<PathBuf> {
+    let (binary, _) = qemu_binary_and_machine(tool_name)?;
+    let file_name = if std::env::consts::OS == "windows" {
+        format!("{}.exe", binary)
+    } else {
+        binary.to_string()
+    };
+    let mut pending = vec![tools_dir.join(tool_name)];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).ok()?.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path
+                .file_name()
+                .is_some_and(|name| name == file_name.as_str())
+            {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+/// Boots the emulated chip of `tool_name` without firmware and waits for the ROM banner.
+///
+/// This catches missing shared libraries and broken builds, which `--version` alone
+/// does not, as QEMU loads the chip ROM images only when booting a machine.
+pub fn smoke_test(binary: &Path, tool_name: &str, timeout: Duration) -> Result<()> {
+    let (_, machine) = qemu_binary_and_machine(tool_name)
+        .ok_or_else(|| anyhow!("{} is not a QEMU tool", tool_name))?;
+    let mut child = Command::new(binary)
+        .args(["-nographic", "-no-reboot", "-machine", machine])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("Failed to start {}: {}", binary.display(), e))?;
+
+    let (sender, receiver) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(|line| line.ok()) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let deadline = std::time::Instant::now() + timeout;
+    let mut booted = false;
+    while let Some(remaining) = deadline.checked_duration_since(std::time::Instant::now()) {
+        match receiver.recv_timeout(remaining) {
+            Ok(line) => {
+                debug!("{}: {}", tool_name, line);
+                // "ets Jun  8 2016" on ESP32, "ESP-ROM:esp32c3-..." on newer chips
+                if line.starts_with("ets ") || line.starts_with("ESP-ROM") {
+                    booted = true;
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    let _ = child.kill();
+    let output = child.wait_with_output()?;
+    if booted {
+        info!("{} booted the {} ROM", binary.display(), machine);
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} did not boot the {} ROM: {}",
+            binary.display(),
+            machine,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Installs QEMU into an existing installation, checks it boots and adds it to its activation script.
+///
+/// Returns the installed tools with their versions.
+pub async fn install_qemu(
+    installation: &IdfInstallation,
+    mirror: Option<&str>,
+    url_rewrite: Option<&str>,
+) -> Result<Vec<(String, String)>> {
+    let tools_json = Path::new(&installation.path)
+        .join("tools")
+        .join("tools.json");
+    let tools_file = idf_tools::read_and_parse_tools_file(tools_json.to_str().unwrap())
+        .map_err(|e| anyhow!("Failed to read {}: {}", tools_json.display(), e))?;
+    let qemu_tools = only_qemu(tools_file);
+    if qemu_tools.tools.is_empty() {
+        return Err(anyhow!(
+            "ESP-IDF {} does not provide QEMU builds, QEMU is available from ESP-IDF v5.3",
+            installation.name
+        ));
+    }
+
+    let tools_dir = PathBuf::from(&installation.idf_tools_path);
+    let download_dir = tools_dir.join("dist");
+    std::fs::create_dir_all(&download_dir)?;
+    let installed = idf_tools::setup_tools(
+        &qemu_tools,
+        vec!["all".to_string()],
+        &download_dir,
+        &tools_dir,
+        mirror,
+        url_rewrite,
+        |_| {},
+    )
+    .await?;
+
+    for tool_name in installed.keys() {
+        let binary = find_qemu_binary(&tools_dir, tool_name)
+            .ok_or_else(|| anyhow!("{} binary not found in {}", tool_name, tools_dir.display()))?;
+        smoke_test(&binary, tool_name, SMOKE_TEST_TIMEOUT)?;
+    }
+
+    let export_paths = idf_tools::get_tools_export_paths_from_list(
+        qemu_tools,
+        installed.clone(),
+        tools_dir.to_str().unwrap(),
+    );
+    crate::prepend_paths_to_activation_script(
+        Path::new(&installation.activation_script),
+        &export_paths,
+    )?;
+
+    let mut versions: Vec<(String, String)> = installed
+        .into_iter()
+        .map(|(name, (version, _))| (name, version))
+        .collect();
+    versions.sort();
+    Ok(versions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_qemu_binary() {
+        let tools_dir = tempfile::TempDir::new().unwrap();
+        let bin_dir = tools_dir
+            .path()
+            .join("qemu-riscv32")
+            .join("esp_develop_9.0.0_20240606")
+            .join("qemu")
+            .join("bin");
+        std::fs::create_dir_all(&bin_dir).unwrap();
+        let name = if std::env::consts::OS == "windows" {
+            "qemu-system-riscv32.exe"
+        } else {
+            "qemu-system-riscv32"
+        };
+        std::fs::write(bin_dir.join(name), "").unwrap();
+
+        assert_eq!(
+            find_qemu_binary(tools_dir.path(), "qemu-riscv32"),
+            Some(bin_dir.join(name))
+        );
+        assert_eq!(find_qemu_binary(tools_dir.path(), "qemu-xtensa"), None);
+    }
+}
@@ -0,0 +1,203 @@
+use log::debug;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use crate::idf_config::IdfInstallation;
+
+/// What eim needs to write a path for.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Purpose {
+    /// `eim_idf.json`, the list of installations
+    Registry,
+    /// The folder of `eim_idf.json`, holding the installation lock and the state files of eim
+    RegistryFolder,
+    /// The tools folder of installations, e.g. a system-wide one
+    Tools { installations: Vec<String> },
+}
+
+/// A file or folder a command has to write which the current user can't write.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MissingPermission {
+    pub path: PathBuf,
+    pub purpose: Purpose,
+}
+
+/// Whether the current user can write `path`, or create it when it doesn't exist yet.
+///
+/// Folders are probed by creating a file, which also catches read-only mounts and ACLs the
+/// permission bits don't show. Files are opened for writing without truncating them.
+pub fn is_writable(path: &Path) -> bool {
+    if path.is_dir() {
+        let probe = path.join(format!(".eim-write-check-{}", std::process::id()));
+        return match OpenOptions::new().write(true).create_new(true).open(&probe) {
+            Ok(_) => {
+                let _ = fs::remove_file(&probe);
+                true
+            }
+            Err(e) => {
+                debug!("{} is not writable: {}", path.display(), e);
+                false
+            }
+        };
+    }
+    if path.exists() {
+        return OpenOptions::new().write(true).open(path).is_ok();
+    }
+    // created by the command, in the nearest folder which exists
+    path.ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.is_dir())
+        .is_some_and(is_writable)
+}
+
+/// What a command writes, from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    /// Only the installation lock and state files next to `eim_idf.json`, e.g. `--download-only`
+    Lock,
+    /// The list of installations
+    Registry,
+    /// The list of installations and the tools folders of the installations
+    Tools,
+}
+
+fn registry_permissions_of(registry: &Path, access: Access) -> Vec<MissingPermission> {
+    let mut missing = Vec::new();
+    if let Some(folder) = registry.parent().filter(|folder| !is_writable(folder)) {
+        missing.push(MissingPermission {
+            path: folder.to_path_buf(),
+            purpose: Purpose::RegistryFolder,
+        });
+    }
+    if access >= Access::Registry && registry.exists() && !is_writable(registry) {
+        missing.push(MissingPermission {
+            path: registry.to_path_buf(),
+            purpose: Purpose::Registry,
+        });
+    }
+    missing
+}
+
+/// What is missing to change the list of installations, empty when it can be changed.
+pub fn registry_permissions() -> Vec<MissingPermission> {
+    registry_permissions_of(&crate::version_manager::get_default_config_path(), Access::Registry)
+}
+
+fn missing_permissions_of(registry: &Path, access: Access, installations: &[IdfInstallation]) -> Vec<MissingPermission> {
+    let mut missing = registry_permissions_of(registry, access);
+    if access >= Access::Tools {
+        missing.extend(tools_permissions(installations));
+    }
+    missing
+}
+
+/// What is missing for a command needing `access`, empty when it can run.
+pub fn missing_permissions(access: Access) -> Vec<MissingPermission> {
+    let installations = if access >= Access::Tools {
+        crate::version_manager::get_esp_ide_config()
+            .map(|config| config.idf_installed)
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    missing_permissions_of(&crate::version_manager::get_default_config_path(), access, &installations)
+}
+
+/// What is missing to change the tools of `installations`, one entry per tools folder.
+pub fn tools_permissions(installations: &[IdfInstallation]) -> Vec<MissingPermission> {
+    let folders: BTreeSet<&str> = installations
+        .iter()
+        .map(|installation| installation.idf_tools_path.as_str())
+        .filter(|folder| !folder.is_empty())
+        .collect();
+    folders
+        .into_iter()
+        .filter(|folder| !is_writable(Path::new(folder)))
+        .map(|folder| MissingPermission {
+            path: PathBuf::from(folder),
+            purpose: Purpose::Tools {
+                installations: installations
+                    .iter()
+                    .filter(|installation| installation.idf_tools_path == folder)
+                    .map(|installation| installation.name.clone())
+                    .collect(),
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idf_config::test_installation;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_is_writable() {
+        let dir = TempDir::new().unwrap();
+        assert!(is_writable(dir.path()));
+        assert!(is_writable(&dir.path().join("not/created/yet/eim_idf.json")));
+        fs::write(dir.path().join("eim_idf.json"), "{}").unwrap();
+        assert!(is_writable(&dir.path().join("eim_idf.json")));
+        // the probe is cleaned up
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_only_registry() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = TempDir::new().unwrap();
+        let registry = dir.path().join("eim_idf.json");
+        fs::write(&registry, "{}").unwrap();
+        fs::set_permissions(&registry, fs::Permissions::from_mode(0o444)).unwrap();
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o555)).unwrap();
+        // root writes anything, there is nothing to check then
+        let root = is_writable(dir.path());
+
+        let missing = registry_permissions_of(&registry, Access::Registry);
+        let lock_only = registry_permissions_of(&registry, Access::Lock);
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o755)).unwrap();
+        if root {
+            return;
+        }
+        let purposes: Vec<Purpose> = missing.into_iter().map(|m| m.purpose).collect();
+        assert_eq!(purposes, vec![Purpose::RegistryFolder, Purpose::Registry]);
+        let purposes: Vec<Purpose> = lock_only.into_iter().map(|m| m.purpose).collect();
+        assert_eq!(purposes, vec![Purpose::RegistryFolder]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_only_registry_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = TempDir::new().unwrap();
+        let registry = dir.path().join("eim_idf.json");
+        let tools = dir.path().join("tools");
+        fs::create_dir(&tools).unwrap();
+        fs::write(&registry, "{}").unwrap();
+        fs::set_permissions(&registry, fs::Permissions::from_mode(0o444)).unwrap();
+        fs::set_permissions(&tools, fs::Permissions::from_mode(0o555)).unwrap();
+        let root = is_writable(&registry);
+        let installations = vec![IdfInstallation {
+            idf_tools_path: tools.to_string_lossy().into_owned(),
+            ..test_installation("v5.3")
+        }];
+
+        let lock_only = missing_permissions_of(&registry, Access::Lock, &installations);
+        let registry_only = missing_permissions_of(&registry, Access::Registry, &installations);
+        let all = missing_permissions_of(&registry, Access::Tools, &installations);
+        fs::set_permissions(&tools, fs::Permissions::from_mode(0o755)).unwrap();
+        if root {
+            return;
+        }
+        // the lock lives in the writable folder, a download-only run needs nothing else
+        assert!(lock_only.is_empty());
+        assert_eq!(registry_only.len(), 1);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].purpose, Purpose::Tools { installations: vec!["v5.3".to_string()] });
+    }
+}
@@ -0,0 +1,62 @@
+use log::{info, warn};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::remnants::{find_remnants, remove_remnants, RemnantKind};
+use crate::settings::Settings;
+
+/// Removes the staging and backup folders of tools and the partly restored snapshots, whatever
+/// their age, and returns how many were removed and the bytes reclaimed.
+///
+/// Unlike the regular cleanup, which only removes leftovers untouched for a day, this may break
+/// an installation running at the same time: the caller has to hold the installer lock. Version
+/// folders which were never registered are left alone, they may hold work of the user.
+pub fn clear_staging() -> (usize, u64) {
+    let remnants: Vec<_> = find_remnants(Duration::ZERO)
+        .into_iter()
+        .filter(|remnant| remnant.kind != RemnantKind::Installation)
+        .collect();
+    let reclaimed = remove_remnants(&remnants);
+    info!("Cleared {} staging folders, {} bytes reclaimed", remnants.len(), reclaimed);
+    (remnants.len(), reclaimed)
+}
+
+/// Removes `dirs` and returns the bytes reclaimed; failures are logged and skipped.
+pub fn remove_dirs(dirs: &[(PathBuf, u64)]) -> u64 {
+    let mut reclaimed = 0;
+    for (dir, size) in dirs {
+        match crate::utils::remove_directory_all(dir) {
+            Ok(()) => {
+                info!("Removed {}", dir.display());
+                reclaimed += size;
+            }
+            Err(err) => warn!("Failed to remove {}: {}", dir.display(), err),
+        }
+    }
+    reclaimed
+}
+
+/// Deletes the downloaded tool archives of all installations, which are downloaded again when needed.
+pub fn purge_download_cache(settings: &Settings) -> u64 {
+    let caches = crate::status::collect(settings).caches;
+    let reclaimed = remove_dirs(&caches);
+    info!("Purged {} download caches, {} bytes reclaimed", caches.len(), reclaimed);
+    reclaimed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_dirs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dist = dir.path().join("dist");
+        std::fs::create_dir_all(&dist).unwrap();
+        std::fs::write(dist.join("cmake-3.30.2.tar.gz"), [0u8; 10]).unwrap();
+
+        let reclaimed = remove_dirs(&[(dist.clone(), 10)]);
+        assert_eq!(reclaimed, 10);
+        assert!(!dist.exists());
+    }
+}
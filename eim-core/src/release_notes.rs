@@ -0,0 +1,169 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::idf_versions::{get_idf_versions, parse_release_date, parse_version_name, release_series, Releases};
+
+/// GitHub releases of ESP-IDF, the release notes are the bodies of the releases
+pub const RELEASES_API_URL: &str = "https://api.github.com/repos/espressif/esp-idf/releases/tags";
+
+/// Support status of a release series according to `idf_versions.json`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SupportStatus {
+    /// Not released yet, e.g. `v6.0-beta1`
+    PreRelease,
+    Supported { until: String },
+    EndOfLife { since: String },
+    /// Branches, commits and series missing in the release metadata
+    Unknown,
+}
+
+/// What `eim info` and the wizard show about a version before it is installed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseInfo {
+    pub version: String,
+    pub support: SupportStatus,
+    /// Markdown release notes, `None` for branches and commits or when they could not be fetched
+    pub notes: Option<String>,
+    pub published: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubRelease {
+    body: Option<String>,
+    published_at: Option<String>,
+    html_url: Option<String>,
+}
+
+/// Support status of `version` on `today`.
+pub fn support_status(releases: &Releases, version: &str, today: NaiveDate) -> SupportStatus {
+    let Some(parsed) = parse_version_name(version) else {
+        return SupportStatus::Unknown;
+    };
+    if !parsed.pre.is_empty() {
+        return SupportStatus::PreRelease;
+    }
+    let Some(release) = releases.RELEASES.get(&release_series(&parsed)) else {
+        return SupportStatus::Unknown;
+    };
+    match parse_release_date(&release.end_date) {
+        Some(end) if end < today => SupportStatus::EndOfLife { since: end.to_string() },
+        Some(end) => SupportStatus::Supported { until: end.to_string() },
+        None => SupportStatus::Unknown,
+    }
+}
+
+/// Fetches the release notes of a tagged release from GitHub.
+async fn fetch_github_release(version: &str) -> Result<GithubRelease> {
+    let url = format!("{}/{}", RELEASES_API_URL, version);
+    let request = crate::http_client::client()
+        .get(&url)
+        .header("Accept", "application/vnd.github+json");
+    let body = crate::metadata_cache::fetch_text(&url, request).await?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Collects the support status and release notes of `version`.
+///
+/// Failing to fetch the notes is not an error, they are informational; the status is still returned.
+pub async fn get_release_info(version: &str) -> Result<ReleaseInfo> {
+    let releases = get_idf_versions().await.map_err(|e| anyhow!(e))?;
+    let support = support_status(&releases, version, chrono::Utc::now().date_naive());
+    let mut info = ReleaseInfo {
+        version: version.to_string(),
+        support,
+        notes: None,
+        published: None,
+        url: None,
+    };
+    if parse_version_name(version).is_none() {
+        return Ok(info);
+    }
+    match fetch_github_release(version).await {
+        Ok(release) => {
+            info.notes = release.body.filter(|body| !body.trim().is_empty());
+            info.published = release.published_at.and_then(|date| date.get(..10).map(str::to_string));
+            info.url = release.html_url;
+        }
+        Err(err) => log::warn!("Failed to fetch the release notes of {}: {}", version, err),
+    }
+    Ok(info)
+}
+
+/// Release notes as plain text for the terminal: markdown markup removed and at most `max_lines`
+/// lines, `None` for no limit. Returns the text and whether it was shortened.
+pub fn notes_for_terminal(notes: &str, max_lines: Option<usize>) -> (String, bool) {
+    let lines: Vec<String> = notes
+        .lines()
+        .map(|line| {
+            let line = line.trim_end();
+            let line = line.trim_start_matches('#').trim_start();
+            line.replace("**", "").replace('`', "")
+        })
+        .collect();
+    // drop runs of empty lines, the GitHub bodies have plenty
+    let mut text: Vec<String> = Vec::new();
+    for line in lines {
+        if line.is_empty() && text.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        text.push(line);
+    }
+    while text.last().is_some_and(|last| last.is_empty()) {
+        text.pop();
+    }
+    match max_lines {
+        Some(max) if text.len() > max => (text[..max].join("\n"), true),
+        _ => (text.join("\n"), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idf_versions::Release;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_support_status() {
+        let mut series = HashMap::new();
+        for (name, start, end) in [("v5.3", "2024-07-18", "2027-01-18"), ("v4.4", "2022-01-27", "2024-12-31")] {
+            series.insert(
+                name.to_string(),
+                Release {
+                    start_date: start.to_string(),
+                    end_date: end.to_string(),
+                },
+            );
+        }
+        let releases = Releases {
+            VERSIONS: Vec::new(),
+            IDF_TARGETS: Vec::new(),
+            RELEASES: series,
+        };
+        let today = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+
+        assert_eq!(
+            support_status(&releases, "v5.3.2", today),
+            SupportStatus::Supported { until: "2027-01-18".to_string() }
+        );
+        assert_eq!(
+            support_status(&releases, "v4.4.8", today),
+            SupportStatus::EndOfLife { since: "2024-12-31".to_string() }
+        );
+        assert_eq!(support_status(&releases, "v6.0-beta1", today), SupportStatus::PreRelease);
+        assert_eq!(support_status(&releases, "master", today), SupportStatus::Unknown);
+    }
+
+    #[test]
+    fn test_notes_for_terminal() {
+        let notes = "## Major Changes\n\n\n- **Breaking**: `esp_flash` API\n- Fix\n\n";
+        assert_eq!(
+            notes_for_terminal(notes, None),
+            ("Major Changes\n\n- Breaking: esp_flash API\n- Fix".to_string(), false)
+        );
+        assert_eq!(notes_for_terminal(notes, Some(2)), ("Major Changes\n".to_string(), true));
+    }
+}
@@ -0,0 +1,229 @@
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::settings::Settings;
+
+/// Leftovers younger than this may belong to an installation which is still running
+pub const DEFAULT_MIN_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+const STAGING_SUFFIX: &str = ".eim-staging";
+const BACKUP_SUFFIX: &str = ".eim-backup";
+const SNAPSHOT_PREFIX: &str = ".eim_snapshot";
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+#[serde(rename_all = "snake_case")]
+pub enum RemnantKind {
+    /// Half extracted tool, moved into place only when complete
+    Staging,
+    /// Previous version of a tool, kept until the installation replacing it commits
+    Backup,
+    /// Partly restored snapshot
+    Snapshot,
+    /// Version folder eim recorded an install manifest for, but which was never registered in
+    /// `eim_idf.json`
+    Installation,
+}
+
+/// Something a crashed or killed installation left behind.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+pub struct Remnant {
+    pub path: PathBuf,
+    pub kind: RemnantKind,
+    #[cfg_attr(feature = "ts-bindings", ts(type = "number"))]
+    pub size: u64,
+}
+
+fn kind_of(name: &str) -> Option<RemnantKind> {
+    if !name.starts_with('.') {
+        None
+    } else if name.ends_with(STAGING_SUFFIX) {
+        Some(RemnantKind::Staging)
+    } else if name.ends_with(BACKUP_SUFFIX) {
+        Some(RemnantKind::Backup)
+    } else if name.starts_with(SNAPSHOT_PREFIX) {
+        Some(RemnantKind::Snapshot)
+    } else {
+        None
+    }
+}
+
+fn is_older_than(path: &Path, min_age: Duration) -> bool {
+    fs::symlink_metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age >= min_age)
+}
+
+fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// Version folders an installation may still finish: the one of an interrupted installation,
+/// which can be resumed, and those fetched with `--download-only`.
+fn is_pending(version_dir: &Path, interrupted: &[PathBuf]) -> bool {
+    if interrupted.iter().any(|path| path == version_dir) {
+        return true;
+    }
+    let name = file_name(version_dir);
+    let download_dirs = [
+        Settings::default().tool_download_folder_name.map(PathBuf::from),
+        Some(version_dir.join("dist")),
+    ];
+    download_dirs
+        .iter()
+        .flatten()
+        .any(|dir| crate::download_only::load(dir, &name).is_some())
+}
+
+/// Whether `version_dir` is an installation eim made but never registered. Only the install
+/// manifest eim writes for `<version>/esp-idf` counts: a folder merely holding an ESP-IDF
+/// checkout may be a project of the user with ESP-IDF as a submodule.
+fn is_unregistered_installation(version_dir: &Path, registered: &[PathBuf]) -> bool {
+    let idf_path = version_dir.join("esp-idf");
+    !registered.iter().any(|path| path.starts_with(version_dir))
+        && crate::install_manifest::get_manifest_path(&idf_path.to_string_lossy()).is_file()
+}
+
+/// Finds what failed installations left behind, untouched for at least `min_age`: tool staging
+/// and backup folders in the tools folders, partly restored snapshots and version folders eim
+/// installed but never registered.
+pub fn find_remnants(min_age: Duration) -> Vec<Remnant> {
+    let defaults = Settings::default();
+    let installations = crate::version_manager::get_esp_ide_config()
+        .map(|config| config.idf_installed)
+        .unwrap_or_default();
+
+    let mut tools_dirs: BTreeSet<PathBuf> = defaults.tool_install_folder_name.iter().map(PathBuf::from).collect();
+    let mut base_dirs: BTreeSet<PathBuf> = defaults.path.iter().cloned().collect();
+    for installation in &installations {
+        tools_dirs.insert(PathBuf::from(&installation.idf_tools_path));
+        // <base>/<version>/esp-idf
+        if let Some(base) = Path::new(&installation.path).parent().and_then(Path::parent) {
+            base_dirs.insert(base.to_path_buf());
+        }
+    }
+    let registered: Vec<PathBuf> = installations.iter().map(|i| PathBuf::from(&i.path)).collect();
+    let interrupted: Vec<PathBuf> = crate::interrupted_install::load()
+        .map(|marker| {
+            marker
+                .versions
+                .iter()
+                .filter_map(|version| marker.settings.get_version_paths(version).ok())
+                .map(|paths| paths.version_installation_path)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut candidates = Vec::new();
+    // <tools>/<tool>/.<version>.eim-staging
+    for tool_dir in tools_dirs.iter().flat_map(|dir| subdirectories(dir)) {
+        for dir in subdirectories(&tool_dir) {
+            if let Some(kind) = kind_of(&file_name(&dir)) {
+                candidates.push((dir, kind));
+            }
+        }
+    }
+    for dir in base_dirs.iter().flat_map(|dir| subdirectories(dir)) {
+        if let Some(kind) = kind_of(&file_name(&dir)) {
+            candidates.push((dir, kind));
+        } else if is_unregistered_installation(&dir, &registered) && !is_pending(&dir, &interrupted) {
+            candidates.push((dir, RemnantKind::Installation));
+        }
+    }
+
+    candidates
+        .into_iter()
+        .filter(|(path, _)| is_older_than(path, min_age))
+        .map(|(path, kind)| {
+            debug!("Found the remnant {} of a failed installation", path.display());
+            Remnant {
+                size: crate::status::dir_size(&path),
+                path,
+                kind,
+            }
+        })
+        .collect()
+}
+
+/// Removes `remnants` and returns the bytes reclaimed; failures are logged and skipped.
+pub fn remove_remnants(remnants: &[Remnant]) -> u64 {
+    let mut reclaimed = 0;
+    for remnant in remnants {
+        match crate::utils::remove_directory_all(&remnant.path) {
+            Ok(()) => {
+                info!("Removed {}", remnant.path.display());
+                reclaimed += remnant.size;
+                if remnant.kind == RemnantKind::Installation {
+                    let idf_path = remnant.path.join("esp-idf");
+                    let _ = fs::remove_file(crate::install_manifest::get_manifest_path(&idf_path.to_string_lossy()));
+                }
+            }
+            Err(err) => warn!("Failed to remove {}: {}", remnant.path.display(), err),
+        }
+    }
+    reclaimed
+}
+
+pub fn total_size(remnants: &[Remnant]) -> u64 {
+    remnants.iter().map(|remnant| remnant.size).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_of() {
+        assert_eq!(kind_of(".v0.12.0.eim-staging"), Some(RemnantKind::Staging));
+        assert_eq!(kind_of(".esp-13.2.0_20240530.eim-backup"), Some(RemnantKind::Backup));
+        assert_eq!(kind_of(".eim_snapshotX1b2"), Some(RemnantKind::Snapshot));
+        assert_eq!(kind_of("v0.12.0"), None);
+        assert_eq!(kind_of("eim-staging"), None);
+    }
+
+    #[test]
+    fn test_checkout_without_manifest_is_kept() {
+        // a project of the user next to the version folders, with ESP-IDF as a submodule
+        let dir = tempfile::TempDir::new().unwrap();
+        let project = dir.path().join("my-project");
+        fs::create_dir_all(project.join("esp-idf")).unwrap();
+        assert!(!is_unregistered_installation(&project, &[]));
+    }
+
+    #[test]
+    fn test_remove_remnants() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let staging = dir.path().join(".v1.eim-staging");
+        fs::create_dir_all(&staging).unwrap();
+        fs::write(staging.join("bin"), [0u8; 100]).unwrap();
+        assert!(is_older_than(&staging, Duration::ZERO));
+        assert!(!is_older_than(&staging, DEFAULT_MIN_AGE));
+
+        let remnant = Remnant {
+            size: crate::status::dir_size(&staging),
+            path: staging.clone(),
+            kind: RemnantKind::Staging,
+        };
+        assert_eq!(remove_remnants(&[remnant]), 100);
+        assert!(!staging.exists());
+    }
+}
@@ -0,0 +1,156 @@
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::settings::Settings;
+
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+pub const DEFAULT_BACKOFF_BASE: Duration = Duration::from_secs(2);
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Keys of a per-host override in `retry_host_overrides`
+pub const OVERRIDE_KEYS: &[&str] = &["max_attempts", "backoff_base", "stall_timeout"];
+
+/// How often and how patiently a download or fetch is retried.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts before giving up, the first one included
+    pub max_attempts: u32,
+    /// Delay after the first failed attempt, doubled after every further one
+    pub backoff_base: Duration,
+    /// A download which receives nothing for this long is aborted and retried, `None` waits forever
+    pub stall_timeout: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            backoff_base: DEFAULT_BACKOFF_BASE,
+            stall_timeout: Some(DEFAULT_STALL_TIMEOUT),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the attempt following the failed `attempt` (counted from 1).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff_base.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+
+    /// Whether another attempt follows the failed `attempt`.
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+
+    /// Applies the keys of a `retry_host_overrides` entry, unknown keys are ignored with a warning.
+    fn with_overrides(mut self, host: &str, overrides: &HashMap<String, u64>) -> Self {
+        for (key, value) in overrides {
+            match key.as_str() {
+                "max_attempts" => self.max_attempts = (*value as u32).max(1),
+                "backoff_base" => self.backoff_base = Duration::from_secs(*value),
+                "stall_timeout" => self.stall_timeout = stall_timeout(*value),
+                _ => warn!(
+                    "Ignoring unknown retry setting '{}' for {}, expected one of {}",
+                    key,
+                    host,
+                    OVERRIDE_KEYS.join(", ")
+                ),
+            }
+        }
+        self
+    }
+}
+
+/// A stall timeout of 0 disables it.
+fn stall_timeout(seconds: u64) -> Option<Duration> {
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+#[derive(Debug, Clone, Default)]
+struct RetryConfig {
+    default: RetryPolicy,
+    hosts: HashMap<String, HashMap<String, u64>>,
+}
+
+static RETRY_CONFIG: Lazy<RwLock<RetryConfig>> = Lazy::new(|| RwLock::new(RetryConfig::default()));
+
+/// Applies the `retry_max_attempts`, `retry_backoff_base`, `stall_timeout` and
+/// `retry_host_overrides` settings.
+pub fn configure_from_settings(settings: &Settings) {
+    let default = RetryPolicy {
+        max_attempts: settings.retry_max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS).max(1),
+        backoff_base: settings
+            .retry_backoff_base
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_BACKOFF_BASE),
+        stall_timeout: settings
+            .stall_timeout
+            .map_or(Some(DEFAULT_STALL_TIMEOUT), stall_timeout),
+    };
+    let hosts = settings
+        .retry_host_overrides
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(host, overrides)| (host.to_lowercase(), overrides))
+        .collect();
+    debug!("Using retry policy {:?}", default);
+    if let Ok(mut config) = RETRY_CONFIG.write() {
+        *config = RetryConfig { default, hosts };
+    }
+}
+
+/// The policy for `url`: the configured one with the override of its host applied.
+pub fn policy_for(url: &str) -> RetryPolicy {
+    let config = RETRY_CONFIG.read().map(|c| c.clone()).unwrap_or_default();
+    let host = url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase));
+    match host.as_deref().and_then(|host| config.hosts.get(host).map(|o| (host, o))) {
+        Some((host, overrides)) => config.default.with_overrides(host, overrides),
+        None => config.default,
+    }
+}
+
+/// Parses a `--retry-host` value, `<host>:<key>=<value>[,<key>=<value>...]`.
+pub fn parse_host_override(value: &str) -> Option<(String, HashMap<String, u64>)> {
+    let (host, spec) = value.rsplit_once(':')?;
+    let overrides = spec
+        .split(',')
+        .map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            Some((key.trim().to_string(), value.trim().parse().ok()?))
+        })
+        .collect::<Option<HashMap<String, u64>>>()?;
+    Some((host.trim().to_lowercase(), overrides))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(8));
+        assert!(policy.should_retry(2));
+        assert!(!policy.should_retry(3));
+    }
+
+    #[test]
+    fn test_host_override() {
+        let (host, overrides) = parse_host_override("GitHub.com:max_attempts=6,stall_timeout=0").unwrap();
+        assert_eq!(host, "github.com");
+        let policy = RetryPolicy::default().with_overrides(&host, &overrides);
+        assert_eq!(policy.max_attempts, 6);
+        assert_eq!(policy.stall_timeout, None);
+        assert_eq!(policy.backoff_base, DEFAULT_BACKOFF_BASE);
+
+        assert_eq!(parse_host_override("github.com"), None);
+        assert_eq!(parse_host_override("github.com:max_attempts=many"), None);
+    }
+}
@@ -0,0 +1,116 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::errors::{EimError, ErrorKind};
+use crate::install_lock::{is_process_alive, read_lock_holder, LockHolder};
+
+/// Start of the last line of a run log, followed by `null` for success or the error class as JSON
+pub const RUN_RESULT_MARKER: &str = "[eim] run result: ";
+
+/// How often a followed run log is checked for new lines
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// An install, upgrade or repair run of another eim process, GUI or CLI.
+#[derive(Debug, Clone)]
+pub struct RunningInstall {
+    pub holder: LockHolder,
+    /// `None` until the run has started its log
+    pub run_log: Option<PathBuf>,
+}
+
+/// Appends the result of a run to its log once the run stopped writing to it, `failure` is
+/// `None` if it succeeded. `eim wait` and `eim attach` return it as their own result.
+pub fn record_result(run_log: &Path, failure: Option<ErrorKind>) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new().append(true).open(run_log)?;
+    let result = serde_json::to_string(&failure).unwrap_or_else(|_| "null".to_string());
+    writeln!(file, "{}{}", RUN_RESULT_MARKER, result)
+}
+
+/// Parses a line written by [`record_result`], `Some(None)` for a successful run.
+pub fn parse_result(line: &str) -> Option<Option<ErrorKind>> {
+    serde_json::from_str(line.trim_end().strip_prefix(RUN_RESULT_MARKER)?).ok()
+}
+
+fn started(holder: &LockHolder) -> Option<SystemTime> {
+    chrono::DateTime::parse_from_rfc3339(&holder.started).ok().map(SystemTime::from)
+}
+
+/// The log of the run holding the installer lock: the newest run log written since the lock was taken.
+fn run_log_of(holder: &LockHolder) -> Option<PathBuf> {
+    let log = crate::logging::last_run_log()?;
+    let modified = std::fs::metadata(&log).ok()?.modified().ok()?;
+    (modified >= started(holder)?).then_some(log)
+}
+
+/// The run of another eim process in progress, if any.
+pub fn find_running() -> Option<RunningInstall> {
+    let holder = read_lock_holder()
+        .filter(|holder| holder.pid != std::process::id() && is_process_alive(holder.pid))?;
+    let run_log = run_log_of(&holder);
+    Some(RunningInstall { holder, run_log })
+}
+
+/// Whether `holder` still runs: it holds the lock and its process is alive.
+fn still_running(holder: &LockHolder) -> bool {
+    read_lock_holder().is_some_and(|current| current == *holder) && is_process_alive(holder.pid)
+}
+
+/// Follows `run` until it ends, passing each line of its log to `on_line`, from the start of the log.
+///
+/// Returns the result the run recorded; a run which ends without one failed, e.g. it crashed or was killed.
+pub async fn follow<F: FnMut(&str)>(mut run: RunningInstall, mut on_line: F) -> anyhow::Result<()> {
+    let mut offset = 0;
+    let mut pending = String::new();
+    loop {
+        let running = still_running(&run.holder);
+        if run.run_log.is_none() {
+            run.run_log = run_log_of(&run.holder);
+        }
+        if let Some(log) = &run.run_log {
+            let mut file = std::fs::File::open(log)
+                .map_err(|err| EimError::new(ErrorKind::NotFound, format!("{}: {}", log.display(), err)))?;
+            file.seek(SeekFrom::Start(offset))?;
+            let mut bytes = Vec::new();
+            offset += file.read_to_end(&mut bytes)? as u64;
+            pending.push_str(&String::from_utf8_lossy(&bytes));
+            while let Some(end) = pending.find('\n') {
+                let line: String = pending.drain(..=end).collect();
+                match parse_result(&line) {
+                    Some(None) => return Ok(()),
+                    Some(Some(kind)) => {
+                        return Err(EimError::new(kind, format!("The run of {} failed", run.holder)).into())
+                    }
+                    None => on_line(line.trim_end()),
+                }
+            }
+        }
+        // the last read happened after the run had already ended
+        if !running {
+            return Err(EimError::new(
+                ErrorKind::General,
+                format!("The run of {} ended without recording its result", run.holder),
+            )
+            .into());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("install-20250314-093012.log");
+        std::fs::write(&log, "2025-03-14 INFO Installing v5.3\n").unwrap();
+        record_result(&log, Some(ErrorKind::Network)).unwrap();
+        let content = std::fs::read_to_string(&log).unwrap();
+        let last = content.lines().last().unwrap();
+        assert_eq!(parse_result(last), Some(Some(ErrorKind::Network)));
+        assert_eq!(parse_result(&format!("{}null\n", RUN_RESULT_MARKER)), Some(None));
+        assert_eq!(parse_result("2025-03-14 INFO Installing v5.3"), None);
+    }
+}
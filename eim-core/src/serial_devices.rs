@@ -0,0 +1,360 @@
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::drivers::{get_driver_for_device, parse_usb_ids};
+
+/// USB vendor id of Espressif, used by the native USB of the newer chips
+pub const ESPRESSIF_VID: u16 = 0x303A;
+
+/// A serial port of a connected board, with everything needed to use it from ESP-IDF.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+pub struct SerialDevice {
+    /// `/dev/ttyUSB0`, `/dev/cu.usbserial-0001` or `COM3`, `None` while Windows has no driver bound
+    pub port: Option<String>,
+    pub description: String,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    /// The native USB of an Espressif chip or a USB-UART bridge used on Espressif boards
+    pub is_espressif: bool,
+    /// Driver the device needs on Windows
+    pub driver: Option<String>,
+    pub driver_ok: bool,
+    /// Whether the current user may open the port
+    pub accessible: bool,
+    /// What to do to make the device usable, if it is not
+    pub hint: Option<String>,
+}
+
+impl SerialDevice {
+    pub fn is_usable(&self) -> bool {
+        self.port.is_some() && self.driver_ok && self.accessible
+    }
+}
+
+fn is_known_device(vid: Option<u16>, pid: Option<u16>) -> bool {
+    match (vid, pid) {
+        (Some(ESPRESSIF_VID), _) => true,
+        (Some(vid), Some(pid)) => get_driver_for_device(vid, pid).is_some(),
+        _ => false,
+    }
+}
+
+/// Lists the serial ports of connected devices, Espressif boards first.
+pub fn list_serial_devices() -> Result<Vec<SerialDevice>> {
+    let mut devices = match std::env::consts::OS {
+        "linux" => list_linux_devices(Path::new("/sys/class/tty"))?,
+        #[cfg(target_os = "macos")]
+        "macos" => list_macos_devices()?,
+        "windows" => list_windows_devices()?,
+        os => return Err(anyhow!("Serial port listing is not supported on {}", os)),
+    };
+    devices.sort_by(|a, b| {
+        b.is_espressif
+            .cmp(&a.is_espressif)
+            .then_with(|| a.port.cmp(&b.port))
+    });
+    debug!("Serial devices: {:?}", devices);
+    Ok(devices)
+}
+
+fn read_sysfs(dir: &Path, name: &str) -> Option<String> {
+    std::fs::read_to_string(dir.join(name))
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Reads the USB serial ports from sysfs, skipping the legacy on-board UARTs.
+fn list_linux_devices(sys_tty: &Path) -> Result<Vec<SerialDevice>> {
+    let mut devices = Vec::new();
+    let Ok(entries) = std::fs::read_dir(sys_tty) else {
+        return Ok(devices);
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with("ttyUSB") && !name.starts_with("ttyACM") {
+            continue;
+        }
+        // <tty>/device is the USB interface, its parent the USB device with the descriptors
+        let usb_device = entry
+            .path()
+            .join("device")
+            .canonicalize()
+            .ok()
+            .and_then(|interface| interface.parent().map(Path::to_path_buf));
+        let read = |file: &str| usb_device.as_deref().and_then(|dir| read_sysfs(dir, file));
+        let vid = read("idVendor").and_then(|v| u16::from_str_radix(&v, 16).ok());
+        let pid = read("idProduct").and_then(|p| u16::from_str_radix(&p, 16).ok());
+        let description = [read("manufacturer"), read("product")]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let port = format!("/dev/{}", name);
+        let (accessible, hint) = linux_port_access(Path::new(&port));
+        devices.push(SerialDevice {
+            port: Some(port),
+            description,
+            vid,
+            pid,
+            is_espressif: is_known_device(vid, pid),
+            driver: None,
+            driver_ok: true,
+            accessible,
+            hint,
+        });
+    }
+    Ok(devices)
+}
+
+/// Checks whether the current user may open the port, and which group to join if not.
+#[cfg(unix)]
+fn linux_port_access(port: &Path) -> (bool, Option<String>) {
+    use std::os::unix::fs::MetadataExt;
+    let Ok(metadata) = std::fs::metadata(port) else {
+        return (false, None);
+    };
+    if metadata.mode() & 0o006 == 0o006 {
+        return (true, None);
+    }
+    let user_groups: Vec<u32> = crate::command_executor::execute_command("id", &["-G"])
+        .ok()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .filter_map(|gid| gid.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    if user_groups.contains(&0) || user_groups.contains(&metadata.gid()) {
+        return (true, None);
+    }
+    let group = group_name(metadata.gid()).unwrap_or_else(|| "dialout".to_string());
+    (
+        false,
+        Some(t!("devices.hint.join_group", group = group).to_string()),
+    )
+}
+
+#[cfg(not(unix))]
+fn linux_port_access(_port: &Path) -> (bool, Option<String>) {
+    (true, None)
+}
+
+fn group_name(gid: u32) -> Option<String> {
+    std::fs::read_to_string("/etc/group")
+        .ok()?
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() > 2 && fields[2] == gid.to_string())
+        .map(|fields| fields[0].to_string())
+}
+
+/// Lists the `cu.*` call-out devices of USB serial ports, with the USB ids from IOKit;
+/// macOS needs no drivers or permissions for them.
+#[cfg(target_os = "macos")]
+fn list_macos_devices() -> Result<Vec<SerialDevice>> {
+    let ports = serialport::available_ports()
+        .map_err(|e| anyhow!("Failed to query serial ports: {}", e))?;
+    Ok(ports
+        .into_iter()
+        .filter_map(|port| match port.port_type {
+            serialport::SerialPortType::UsbPort(usb) => {
+                let description = [usb.manufacturer, usb.product]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                macos_usb_device(&port.port_name, usb.vid, usb.pid, description)
+            }
+            // Bluetooth and debug console ports
+            _ => None,
+        })
+        .collect())
+}
+
+/// Builds the device of a USB serial port, skipping the `tty.*` dial-in twin of each `cu.*` port.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn macos_usb_device(port: &str, vid: u16, pid: u16, description: String) -> Option<SerialDevice> {
+    if !port.starts_with("/dev/cu.") {
+        return None;
+    }
+    Some(SerialDevice {
+        port: Some(port.to_string()),
+        description,
+        vid: Some(vid),
+        pid: Some(pid),
+        is_espressif: is_known_device(Some(vid), Some(pid)),
+        driver: None,
+        driver_ok: true,
+        accessible: true,
+        hint: None,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PnpPort {
+    instance_id: String,
+    friendly_name: Option<String>,
+    status: Option<String>,
+}
+
+/// Extracts `COM3` from a friendly name like `Silicon Labs CP210x USB to UART Bridge (COM3)`.
+pub fn parse_com_port(friendly_name: &str) -> Option<String> {
+    let start = friendly_name.rfind("(COM")? + 1;
+    let end = start + friendly_name[start..].find(')')?;
+    Some(friendly_name[start..end].to_string())
+}
+
+fn parse_windows_ports(json_output: &str) -> Result<Vec<SerialDevice>> {
+    let trimmed = json_output.trim();
+    if trimmed.is_empty() {
+        return Ok(vec![]);
+    }
+    // ConvertTo-Json emits a bare object instead of an array when there is only one port
+    let raw: Vec<PnpPort> = if trimmed.starts_with('[') {
+        serde_json::from_str(trimmed)?
+    } else {
+        vec![serde_json::from_str(trimmed)?]
+    };
+    Ok(raw
+        .into_iter()
+        .map(|port| {
+            let ids = parse_usb_ids(&port.instance_id);
+            let (vid, pid) = (ids.map(|(v, _)| v), ids.map(|(_, p)| p));
+            let friendly_name = port.friendly_name.unwrap_or_default();
+            let driver_ok = port
+                .status
+                .as_deref()
+                .is_some_and(|status| status.eq_ignore_ascii_case("OK"));
+            let driver = ids
+                .and_then(|(vid, pid)| get_driver_for_device(vid, pid))
+                .map(str::to_string);
+            SerialDevice {
+                port: parse_com_port(&friendly_name),
+                description: friendly_name,
+                vid,
+                pid,
+                is_espressif: is_known_device(vid, pid),
+                hint: (!driver_ok).then(|| t!("devices.hint.install_drivers").to_string()),
+                driver,
+                driver_ok,
+                accessible: true,
+            }
+        })
+        .collect())
+}
+
+/// Lists the COM ports, plus Espressif devices which have no COM port yet as their driver is missing.
+fn list_windows_devices() -> Result<Vec<SerialDevice>> {
+    let script = "Get-PnpDevice -Class Ports -PresentOnly | Select-Object InstanceId,FriendlyName,Status | ConvertTo-Json";
+    let output = crate::run_powershell_script(script)
+        .map_err(|e| anyhow!("Failed to query serial ports: {}", e))?;
+    debug!("Get-PnpDevice output: {}", output);
+    let mut devices = parse_windows_ports(&output)?;
+    // The COM ports are still worth listing when the driver check fails
+    let missing_drivers = crate::drivers::detect_devices_missing_drivers().unwrap_or_else(|e| {
+        warn!("Failed to detect devices with missing drivers: {}", e);
+        vec![]
+    });
+    for usb_device in missing_drivers {
+        if devices
+            .iter()
+            .any(|d| d.vid == Some(usb_device.vid) && d.pid == Some(usb_device.pid))
+        {
+            continue;
+        }
+        devices.push(SerialDevice {
+            port: None,
+            description: usb_device.friendly_name,
+            vid: Some(usb_device.vid),
+            pid: Some(usb_device.pid),
+            is_espressif: true,
+            driver: Some(usb_device.driver),
+            driver_ok: false,
+            accessible: true,
+            hint: Some(t!("devices.hint.install_drivers").to_string()),
+        });
+    }
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_com_port() {
+        assert_eq!(
+            parse_com_port("Silicon Labs CP210x USB to UART Bridge (COM3)"),
+            Some("COM3".to_string())
+        );
+        assert_eq!(parse_com_port("USB Serial Device"), None);
+    }
+
+    #[test]
+    fn test_parse_windows_ports() {
+        let output = r#"{"InstanceId":"USB\\VID_303A&PID_1001&MI_00\\6&1","FriendlyName":"USB Serial Device (COM7)","Status":"OK"}"#;
+        let devices = parse_windows_ports(output).unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].port.as_deref(), Some("COM7"));
+        assert!(devices[0].is_espressif);
+        assert!(devices[0].is_usable());
+    }
+
+    #[test]
+    fn test_macos_usb_device() {
+        let device = macos_usb_device(
+            "/dev/cu.usbserial-0001",
+            0x10C4,
+            0xEA60,
+            "Silicon Labs CP2102N".to_string(),
+        )
+        .unwrap();
+        assert_eq!((device.vid, device.pid), (Some(0x10C4), Some(0xEA60)));
+        assert!(device.is_espressif);
+        assert!(device.is_usable());
+
+        let other = macos_usb_device("/dev/cu.usbmodem101", 0x2341, 0x0043, String::new()).unwrap();
+        assert!(!other.is_espressif);
+        assert!(
+            macos_usb_device("/dev/tty.usbserial-0001", 0x10C4, 0xEA60, String::new()).is_none()
+        );
+    }
+
+    #[test]
+    fn test_list_linux_devices_reads_usb_descriptors() {
+        let sys = tempfile::TempDir::new().unwrap();
+        let usb_device = sys.path().join("devices").join("1-1");
+        let interface = usb_device.join("1-1:1.0");
+        std::fs::create_dir_all(&interface).unwrap();
+        std::fs::write(usb_device.join("idVendor"), "10c4\n").unwrap();
+        std::fs::write(usb_device.join("idProduct"), "ea60\n").unwrap();
+        std::fs::write(
+            usb_device.join("product"),
+            "CP2102N USB to UART Bridge Controller\n",
+        )
+        .unwrap();
+        let tty = sys.path().join("class").join("ttyUSB0");
+        std::fs::create_dir_all(&tty).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&interface, tty.join("device")).unwrap();
+        std::fs::create_dir_all(sys.path().join("class").join("ttyS0")).unwrap();
+
+        let devices = list_linux_devices(&sys.path().join("class")).unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].port.as_deref(), Some("/dev/ttyUSB0"));
+        if cfg!(unix) {
+            assert_eq!(
+                (devices[0].vid, devices[0].pid),
+                (Some(0x10C4), Some(0xEA60))
+            );
+            assert!(devices[0].is_espressif);
+        }
+    }
+}
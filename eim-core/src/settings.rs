@@ -51,6 +51,46 @@ pub struct Settings {
     pub use_local_archive: Option<PathBuf>, // Path to a local archive for offline installation
     pub activation_script_path_override: Option<String>, // Optional override for activation script path
     pub python_version_override: Option<String>, // Optional override for Python version to install when installing prerequisites
+    pub install_drivers: Option<bool>, // Windows only, None means ask in the interactive wizard
+    pub tools_manifest: Option<String>, // URL or path of a tools.json used instead of the one shipped with ESP-IDF
+    pub tools_url_rewrite: Option<String>, // Rewrite of tool download URLs in the form <original prefix>=<new prefix>
+    pub tool_version_overrides: Option<HashMap<String, String>>, // Tool name to version (optionally version@sha256) installed instead of the version pinned by tools.json
+    pub skip_network_preflight: Option<bool>, // Skip checking that all download endpoints are reachable before installing
+    pub ca_certificates: Option<Vec<String>>, // Additional root CA certificates (PEM or DER files) trusted by all HTTPS connections
+    pub use_system_trust_store: Option<bool>, // Trust the operating system certificate store in addition to the bundled roots
+    pub link_strategy: Option<String>, // How links are created: auto, symlink, junction or copy
+    pub dedupe_tools: Option<bool>, // Hard-link tool files identical to those of other installed versions after installing
+    pub enable_ccache: Option<bool>, // Install ccache and enable it for IDF builds
+    pub report_signing_key: Option<String>, // Key file used to sign the installation report, the report is not signed if not set
+    pub powershell_integration: Option<bool>, // Install the Initialize-Idf PowerShell function and the ESP-IDF (eim) Windows Terminal profile (Windows only)
+    pub rust_toolchain: Option<bool>, // Install the Rust toolchain for ESP chips (espup) next to the C toolchain
+    pub install_qemu: Option<bool>, // Install Espressif's QEMU builds (not installed by default)
+    pub log_level: Option<String>, // per-module log levels, e.g. info,idf_im_lib::idf_tools=trace
+    pub config_version: Option<u32>, // version of the configuration file format
+    pub proxy: Option<String>, // Proxy URL like http://user@proxy:3128 used for downloads and clones, its password is moved to the keyring on save
+    pub hooks: Option<crate::hooks::InstallHooks>, // Scripts run before and after installing each version, see the [hooks] table
+    pub components: Option<Vec<String>>, // Optional components to install (examples, docs, qemu), the defaults if not set
+    pub system_install: Option<bool>, // Install into /opt/esp for all users
+    pub system_group: Option<String>, // Group owning a system-wide installation
+    pub project_dir: Option<PathBuf>, // Install into this project instead of the user folder
+    pub component_mirror: Option<bool>, // Mirror the components used by the examples for offline use
+    pub component_registry_url: Option<String>, // IDF Component Manager registry
+    pub component_storage_url: Option<String>, // IDF Component Manager storage, e.g. a mirror
+    pub submodule_jobs: Option<u32>, // number of submodules fetched at the same time
+    pub retry_max_attempts: Option<u32>, // attempts of a download or submodule fetch before giving up
+    pub retry_backoff_base: Option<u64>, // seconds to wait after the first failed attempt, doubled after every further one
+    pub stall_timeout: Option<u64>, // seconds without data before a download is retried, 0 waits forever
+    pub retry_host_overrides: Option<HashMap<String, HashMap<String, u64>>>, // per host max_attempts, backoff_base and stall_timeout replacing the values above
+    pub download_only: Option<bool>, // Only fetch and verify the ESP-IDF repository, the tool archives and the Python packages, install nothing
+    pub paranoid: Option<bool>, // Hash cached archives again even if they were verified before and are unchanged
+    pub download_backend: Option<String>, // What downloads files: reqwest (built in), curl or aria2c
+    pub from_installation: Option<String>, // Name or id of an installation whose recorded settings (targets, mirrors, paths) pre-fill this one
+    pub size_units: Option<String>, // Units of sizes and speeds shown, "decimal" (MB) or "binary" (MiB)
+    pub use_system_tools: Option<Vec<String>>, // Tools of the system used instead of downloading them (cmake, ninja or auto), none if not set
+    pub url_rewrites: Option<Vec<crate::url_rewrite::UrlRewriteRule>>, // Regex rules rewriting the URLs of all downloads, clones and the Python index, see the [[url_rewrites]] tables
+    pub pypi_fallback_mirrors: Option<Vec<String>>, // PyPI indexes tried in order when the pypi_mirror can't be reached, a bundled wheelhouse after them
+    pub metadata_cache_ttl: Option<u64>, // seconds the fetched version list, tools.json and release notes are used before asking the server again, 0 always revalidates
+    pub differential_downloads: Option<bool>, // patch cached archives of previous tool versions when the mirror publishes patches, instead of downloading the whole archive
 }
 
 #[derive(Debug, Clone)]
@@ -64,6 +104,7 @@ pub struct VersionPaths {
     pub activation_script: PathBuf,
     pub activation_script_path: PathBuf, // Path to the activation script
     pub actual_version: String, // This might be different from input if using existing IDF
+    pub idf_version: String, // The version checked out, differs from actual_version for a named installation
     pub using_existing_idf: bool, // Indicates if the IDF directory already exists
 }
 
@@ -125,6 +166,46 @@ impl Default for Settings {
             use_local_archive: None,
             activation_script_path_override: Some(default_activation_script_path_override),
             python_version_override: Some(PYTHON_NAME_TO_INSTALL.to_string()),
+            install_drivers: None,
+            tools_manifest: None,
+            tools_url_rewrite: None,
+            tool_version_overrides: None,
+            skip_network_preflight: None,
+            ca_certificates: None,
+            use_system_trust_store: None,
+            link_strategy: None,
+            dedupe_tools: None,
+            enable_ccache: None,
+            report_signing_key: None,
+            powershell_integration: None,
+            rust_toolchain: None,
+            install_qemu: None,
+            log_level: None,
+            config_version: Some(crate::config_schema::CONFIG_SCHEMA_VERSION),
+            proxy: None,
+            hooks: None,
+            components: None,
+            system_install: Some(false),
+            system_group: None,
+            project_dir: None,
+            component_mirror: Some(false),
+            component_registry_url: None,
+            component_storage_url: None,
+            submodule_jobs: Some(8),
+            retry_max_attempts: Some(3),
+            retry_backoff_base: Some(2),
+            stall_timeout: Some(60),
+            retry_host_overrides: None,
+            download_only: None,
+            paranoid: None,
+            download_backend: None,
+            from_installation: None,
+            size_units: None,
+            use_system_tools: None,
+            url_rewrites: None,
+            pypi_fallback_mirrors: None,
+            metadata_cache_ttl: Some(crate::metadata_cache::DEFAULT_TTL_SECONDS),
+            differential_downloads: Some(false),
         }
     }
 }
@@ -160,10 +241,11 @@ impl Settings {
         if let Some(config_path) = config_path.clone() {
           if config_path.exists() {
             log::info!("Loading config from file: {:?}", config_path);
-            match settings.load(config_path.to_str().unwrap_or_default()) {
-              Ok(_) => log::info!("Config loaded successfully"),
-              Err(e) => log::warn!("Failed to load config from file: {}", e),
-            }
+            // an invalid file stops here, installing with half of the intended settings is worse
+            settings
+              .load(config_path.to_str().unwrap_or_default())
+              .map_err(|e| ConfigError::Message(e.to_string()))?;
+            log::info!("Config loaded successfully");
           } else {
             log::warn!("Config file does not exist: {:?}", config_path);
           }
@@ -172,6 +254,19 @@ impl Settings {
 
         log::debug!("Settings after config load - idf_features: {:?}", settings.idf_features);
 
+        // recorded settings of an existing installation sit between the config file and the CLI arguments
+        let from_installation = cli_items
+            .iter()
+            .find(|(key, value)| key == "from_installation" && value.is_some())
+            .and_then(|(_, value)| value.as_ref().map(|v| v.to_string()))
+            .or_else(|| settings.from_installation.clone());
+        if let Some(identifier) = from_installation {
+            let recorded = crate::install_manifest::recorded_settings_for(&identifier)
+                .map_err(|e| ConfigError::Message(e.to_string()))?;
+            log::info!("Using the settings recorded by installation {}", identifier);
+            recorded.apply_to(&mut settings);
+        }
+
         let cli_config = cli_config.build()?;
         if let Ok(cli_settings_struct) = cli_config.try_deserialize::<Settings>() {
           macro_rules! apply_if_overridden {
@@ -209,7 +304,47 @@ impl Settings {
             python_env_folder_name,
             use_local_archive,
             activation_script_path_override,
-            python_version_override
+            python_version_override,
+            install_drivers,
+            tools_manifest,
+            tools_url_rewrite,
+            tool_version_overrides,
+            skip_network_preflight,
+            ca_certificates,
+            use_system_trust_store,
+            link_strategy,
+            dedupe_tools,
+            enable_ccache,
+            report_signing_key,
+            powershell_integration,
+            rust_toolchain,
+            install_qemu,
+            log_level,
+            config_version,
+            proxy,
+            hooks,
+            components,
+            system_install,
+            system_group,
+            project_dir,
+            component_mirror,
+            component_registry_url,
+            component_storage_url,
+            submodule_jobs,
+            retry_max_attempts,
+            retry_backoff_base,
+            stall_timeout,
+            retry_host_overrides,
+            download_only,
+            paranoid,
+            download_backend,
+            from_installation,
+            size_units,
+            use_system_tools,
+            url_rewrites,
+            pypi_fallback_mirrors,
+            metadata_cache_ttl,
+            differential_downloads
           );
         }
 
@@ -218,6 +353,19 @@ impl Settings {
             settings.config_file = config_path;
         }
         log::debug!("Final settings: {:?}", settings);
+        crate::url_rewrite::compile(settings.url_rewrites.as_deref().unwrap_or_default())
+            .map_err(|e| ConfigError::Message(e.to_string()))?;
+        crate::http_client::configure_from_settings(&settings);
+        crate::links::configure_from_settings(&settings);
+        crate::git_tools::configure_from_settings(&settings);
+        crate::retry_policy::configure_from_settings(&settings);
+        crate::checksum::configure_from_settings(&settings);
+        crate::download_backend::configure_from_settings(&settings);
+        crate::display_format::configure_from_settings(&settings);
+        crate::url_rewrite::configure_from_settings(&settings);
+        crate::metadata_cache::configure_from_settings(&settings);
+        crate::python_utils::configure_from_settings(&settings);
+        crate::delta_download::configure_from_settings(&settings);
 
 
         Ok(settings)
@@ -241,7 +389,18 @@ impl Settings {
             }
         }
 
-        let toml_value = toml::to_string(self).map_err(|e| ConfigError::Message(e.to_string()))?;
+        // a password in the proxy URL goes to the keyring, never into the file
+        let mut settings = self.clone();
+        if let Some(proxy) = &self.proxy {
+            let (proxy, password) = crate::http_client::split_proxy_password(proxy);
+            if let Some(password) = password {
+                crate::credentials::store_secret(&crate::credentials::Credential::ProxyPassword, &password)
+                    .map_err(|e| ConfigError::Message(format!("Failed to store the proxy password: {}", e)))?;
+                settings.proxy = Some(proxy);
+            }
+        }
+
+        let toml_value = toml::to_string(&settings).map_err(|e| ConfigError::Message(e.to_string()))?;
         let mut file = OpenOptions::new()
             .write(true)
             .truncate(true)
@@ -255,7 +414,7 @@ impl Settings {
 
     pub fn load(&mut self, config_path: &str) -> Result<()> {
         let config_string = std::fs::read_to_string(config_path)?;
-        let loaded_settings = toml::from_str::<Settings>(&config_string)?;
+        let loaded_settings = crate::config_schema::parse_config(&config_string, config_path)?;
         self.merge_from(loaded_settings);
 
         Ok(())
@@ -290,7 +449,47 @@ impl Settings {
             python_env_folder_name,
             use_local_archive,
             activation_script_path_override,
-            python_version_override
+            python_version_override,
+            install_drivers,
+            tools_manifest,
+            tools_url_rewrite,
+            tool_version_overrides,
+            skip_network_preflight,
+            ca_certificates,
+            use_system_trust_store,
+            link_strategy,
+            dedupe_tools,
+            enable_ccache,
+            report_signing_key,
+            powershell_integration,
+            rust_toolchain,
+            install_qemu,
+            log_level,
+            config_version,
+            proxy,
+            hooks,
+            components,
+            system_install,
+            system_group,
+            project_dir,
+            component_mirror,
+            component_registry_url,
+            component_storage_url,
+            submodule_jobs,
+            retry_max_attempts,
+            retry_backoff_base,
+            stall_timeout,
+            retry_host_overrides,
+            download_only,
+            paranoid,
+            download_backend,
+            from_installation,
+            size_units,
+            use_system_tools,
+            url_rewrites,
+            pypi_fallback_mirrors,
+            metadata_cache_ttl,
+            differential_downloads
         );
     }
 
@@ -341,13 +540,19 @@ impl Settings {
               let paths = self.get_version_paths(&version)?;
               let id = format!("esp-idf-{}", Uuid::new_v4().to_string().replace("-", ""));
 
+              // a named installation remembers the version it is a copy of
+              let idf_version = (!paths.using_existing_idf && paths.idf_version != paths.actual_version)
+                .then(|| paths.idf_version.clone());
+
               idf_installations.push(IdfInstallation {
                 id,
                 name: paths.actual_version,
+                idf_version,
                 path: paths.idf_path.to_string_lossy().into_owned(),
                 python: paths.python_path.to_string_lossy().into_owned(),
                 idf_tools_path: paths.tool_install_directory.to_string_lossy().into_owned(),
                 activation_script: paths.activation_script.to_string_lossy().into_owned(),
+                label: None,
               });
             }
         }
@@ -460,6 +665,7 @@ impl Settings {
         activation_script,
         activation_script_path,
         actual_version,
+        idf_version: version.to_string(),
         using_existing_idf,
       })
     }
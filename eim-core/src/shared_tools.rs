@@ -0,0 +1,219 @@
+use anyhow::Result;
+use log::{debug, info};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::errors::{EimError, ErrorKind};
+
+/// Suffix of the lock file next to a tool version folder, `<tools>/<tool>/.<version>.eim-lock`
+pub const LOCK_SUFFIX: &str = ".eim-lock";
+
+/// How often a tool locked by another process is checked again
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Lock on the folder of one tool version, held while it is extracted and moved into place.
+///
+/// The installer lock lives in the per-user configuration folder, so it does not stop two users
+/// sharing a tools folder from installing at the same time. This lock lives in the tools folder
+/// itself and is released when the guard is dropped.
+#[derive(Debug)]
+pub struct ToolLock {
+    file: File,
+    path: PathBuf,
+}
+
+/// Path of the lock file of the tool version folder `tool_dir`.
+pub fn lock_path(tool_dir: &Path) -> PathBuf {
+    let name = tool_dir.file_name().unwrap_or_default().to_string_lossy();
+    tool_dir.with_file_name(format!(".{}{}", name, LOCK_SUFFIX))
+}
+
+/// Name of the user running eim, for the diagnostics.
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn open_lock_file(path: &Path) -> std::io::Result<File> {
+    match OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path) {
+        Ok(file) => {
+            // other users have to open the same file
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o666));
+            }
+            Ok(file)
+        }
+        // created by another user without write permission for us, locking works read-only too
+        Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied && path.exists() => File::open(path),
+        Err(err) => Err(err),
+    }
+}
+
+impl ToolLock {
+    /// Locks `tool_dir` against other eim processes, also of other users, waiting while one of
+    /// them installs the same tool version.
+    pub fn acquire(tool_dir: &Path) -> Result<Self> {
+        let path = lock_path(tool_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|err| permission_error(parent, err))?;
+        }
+        let mut file = open_lock_file(&path).map_err(|err| permission_error(&path, err))?;
+        let mut notified = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => {
+                    if !notified {
+                        let holder = fs::read_to_string(&path).unwrap_or_default();
+                        info!(
+                            "Waiting for {} to be installed by {}",
+                            tool_dir.display(),
+                            if holder.trim().is_empty() { "another eim process" } else { holder.trim() }
+                        );
+                        notified = true;
+                    }
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                Err(TryLockError::Error(err)) if err.kind() == std::io::ErrorKind::Unsupported => {
+                    debug!("File locking is not supported in {}, installing without a tool lock", path.display());
+                    break;
+                }
+                Err(TryLockError::Error(err)) => return Err(err.into()),
+            }
+        }
+        // best effort, the file may be read-only for us
+        let _ = file.set_len(0);
+        let _ = write!(file, "{} (PID {})", current_user(), std::process::id());
+        debug!("Locked {}", tool_dir.display());
+        Ok(Self { file, path })
+    }
+}
+
+impl Drop for ToolLock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+        debug!("Released {}", self.path.display());
+    }
+}
+
+/// Whether `tools_dir` is shared between users: its group gets the files created in it,
+/// which `eim install --system` sets up.
+#[cfg(unix)]
+pub fn is_shared(tools_dir: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(tools_dir).is_ok_and(|metadata| metadata.permissions().mode() & 0o2000 != 0)
+}
+
+#[cfg(not(unix))]
+pub fn is_shared(_tools_dir: &Path) -> bool {
+    false
+}
+
+/// Makes a tool version installed into a shared tools folder usable and updatable by the other
+/// members of its group, whatever the umask of the installing user.
+pub fn normalize_permissions(tool_dir: &Path) -> Result<()> {
+    crate::system_install::make_group_writable(tool_dir, None)?;
+    // the folder of the tool may have been created by this installation, it is not ours otherwise
+    #[cfg(unix)]
+    if let Some(parent) = tool_dir.parent() {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(metadata) = fs::metadata(parent) {
+            let mode = metadata.permissions().mode();
+            if mode & 0o2070 != 0o2070 {
+                if let Err(err) = fs::set_permissions(parent, fs::Permissions::from_mode(mode | 0o2070)) {
+                    debug!("Failed to make {} group writable: {}", parent.display(), err);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Owner, group and mode of `path`, e.g. `root:esp 0755`.
+#[cfg(unix)]
+fn describe_ownership(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path).ok()?;
+    Some(format!("{}:{} {:04o}", metadata.uid(), metadata.gid(), metadata.mode() & 0o7777))
+}
+
+#[cfg(not(unix))]
+fn describe_ownership(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Turns a failure to write `path` into an error naming who owns it and how to get access.
+pub fn permission_error(path: &Path, err: std::io::Error) -> anyhow::Error {
+    if err.kind() != std::io::ErrorKind::PermissionDenied {
+        return err.into();
+    }
+    let ownership = describe_ownership(path)
+        .or_else(|| path.parent().and_then(describe_ownership))
+        .map(|ownership| format!(" (owner:group mode {})", ownership))
+        .unwrap_or_default();
+    EimError::new(
+        ErrorKind::Permissions,
+        format!(
+            "{} is not writable by {}{}. The tools folder is shared with other users: ask its owner to add you to its group, or reinstall it with `eim install --system --system-group <group>`",
+            path.display(),
+            current_user(),
+            ownership
+        ),
+    )
+    .into()
+}
+
+/// Fails with `ErrorKind::Permissions` and the ownership of the folder unless this user can
+/// create files in `dir`, or its nearest existing parent, before anything is downloaded.
+pub fn check_writable(dir: &Path) -> Result<()> {
+    let Some(existing) = dir.ancestors().find(|path| path.exists()) else {
+        return Ok(());
+    };
+    let probe = existing.join(format!(".eim-write-test-{}", std::process::id()));
+    match File::create(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            Ok(())
+        }
+        Err(err) => Err(permission_error(existing, err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_path() {
+        assert_eq!(
+            lock_path(Path::new("/opt/esp/tools/cmake/3.30.2")),
+            PathBuf::from("/opt/esp/tools/cmake/.3.30.2.eim-lock")
+        );
+    }
+
+    #[test]
+    fn test_tool_lock_is_exclusive() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let tool_dir = dir.path().join("cmake").join("3.30.2");
+        let lock = ToolLock::acquire(&tool_dir).unwrap();
+        let other = open_lock_file(&lock_path(&tool_dir)).unwrap();
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+    }
+
+    #[test]
+    fn test_permission_error() {
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let err = permission_error(Path::new("/opt/esp/tools"), denied);
+        assert_eq!(err.downcast_ref::<EimError>().map(|e| e.kind), Some(ErrorKind::Permissions));
+        let other = permission_error(Path::new("/opt/esp/tools"), std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(other.downcast_ref::<EimError>().is_none());
+    }
+}
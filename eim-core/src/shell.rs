@@ -0,0 +1,190 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+use crate::ide_export::InstallationEnvironment;
+
+/// Set in shells started by `eim shell` to the name of their installation
+pub const SHELL_MARKER_VARIABLE: &str = "EIM_SHELL";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShellKind {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+    Cmd,
+    /// Any other shell, its prompt is set with `PS1`
+    Other,
+}
+
+impl ShellKind {
+    pub fn from_program(program: &Path) -> Self {
+        let name = program
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match name.as_str() {
+            "bash" => ShellKind::Bash,
+            "zsh" => ShellKind::Zsh,
+            "fish" => ShellKind::Fish,
+            "pwsh" | "powershell" => ShellKind::PowerShell,
+            "cmd" => ShellKind::Cmd,
+            _ => ShellKind::Other,
+        }
+    }
+}
+
+/// The shell of the user: `$SHELL`, or PowerShell on Windows, preferring PowerShell 7.
+pub fn default_shell() -> PathBuf {
+    if cfg!(windows) {
+        let path_var = std::env::var_os("PATH").unwrap_or_default();
+        return crate::which::find_in_path("pwsh", &path_var)
+            .or_else(|| crate::which::find_in_path("powershell", &path_var))
+            .unwrap_or_else(|| PathBuf::from("powershell"));
+    }
+    std::env::var_os("SHELL")
+        .filter(|shell| !shell.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/bin/sh"))
+}
+
+/// Text put in front of the prompt, e.g. `(esp-idf v5.4) `.
+pub fn prompt_prefix(name: &str) -> String {
+    format!("(esp-idf {}) ", name)
+}
+
+/// Quotes `value` for POSIX shells and fish.
+fn single_quoted(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// A shell ready to start, the files it reads on start live as long as it does.
+pub struct ShellLaunch {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    _startup_files: Option<TempDir>,
+}
+
+impl ShellLaunch {
+    /// Runs the shell in the foreground until the user leaves it, returning its exit code.
+    pub fn run(self) -> Result<i32> {
+        debug!("Starting {} {:?}", self.program.display(), self.args);
+        let status = Command::new(&self.program)
+            .args(&self.args)
+            .envs(self.env.iter().map(|(key, value)| (key, value)))
+            .status()
+            .map_err(|err| anyhow!("Failed to start {}: {}", self.program.display(), err))?;
+        Ok(status.code().unwrap_or(1))
+    }
+}
+
+/// Prepares `program` to start with the environment of an installation and its name in front of
+/// the prompt. The rc files of the user are still read, the prompt is changed after them.
+pub fn prepare(environment: &InstallationEnvironment, program: &Path) -> Result<ShellLaunch> {
+    let prefix = prompt_prefix(&environment.name);
+    let inherited = std::env::var("PATH").unwrap_or_default();
+    let mut env = environment.variables.clone();
+    env.push(("PATH".to_string(), environment.path_value(&inherited)));
+    env.push(("ESP_IDF_VERSION".to_string(), environment.idf_version.clone()));
+    env.push((SHELL_MARKER_VARIABLE.to_string(), environment.name.clone()));
+
+    let mut args = Vec::new();
+    let mut startup_files = None;
+    match ShellKind::from_program(program) {
+        ShellKind::Bash => {
+            let dir = TempDir::new()?;
+            let rc_file = dir.path().join("bashrc");
+            fs::write(
+                &rc_file,
+                format!("[ -f ~/.bashrc ] && . ~/.bashrc\nPS1={}\"$PS1\"\n", single_quoted(&prefix)),
+            )?;
+            args = vec!["--rcfile".to_string(), rc_file.to_string_lossy().into_owned()];
+            startup_files = Some(dir);
+        }
+        ShellKind::Zsh => {
+            // zsh reads its rc files from ZDOTDIR, which points to ours until they hand over
+            let dir = TempDir::new()?;
+            let user_dir = std::env::var("ZDOTDIR")
+                .ok()
+                .or_else(|| dirs::home_dir().map(|home| home.to_string_lossy().into_owned()))
+                .unwrap_or_default();
+            fs::write(
+                dir.path().join(".zshenv"),
+                "[ -f \"$EIM_ZDOTDIR/.zshenv\" ] && . \"$EIM_ZDOTDIR/.zshenv\"\n",
+            )?;
+            fs::write(
+                dir.path().join(".zshrc"),
+                format!(
+                    "ZDOTDIR=\"$EIM_ZDOTDIR\"\nunset EIM_ZDOTDIR\n[ -f \"$ZDOTDIR/.zshrc\" ] && . \"$ZDOTDIR/.zshrc\"\nPROMPT={}\"$PROMPT\"\n",
+                    single_quoted(&prefix)
+                ),
+            )?;
+            env.push(("EIM_ZDOTDIR".to_string(), user_dir));
+            env.push(("ZDOTDIR".to_string(), dir.path().to_string_lossy().into_owned()));
+            startup_files = Some(dir);
+        }
+        ShellKind::Fish => {
+            args = vec![
+                "--init-command".to_string(),
+                format!(
+                    "functions -c fish_prompt __eim_fish_prompt; function fish_prompt; printf '%s' {}; __eim_fish_prompt; end",
+                    single_quoted(&prefix)
+                ),
+            ];
+        }
+        ShellKind::PowerShell => {
+            args = vec![
+                "-NoExit".to_string(),
+                "-Command".to_string(),
+                format!(
+                    "$global:__eimPrompt = $function:prompt; function global:prompt {{ '{}' + (& $global:__eimPrompt) }}",
+                    prefix.replace('\'', "''")
+                ),
+            ];
+        }
+        ShellKind::Cmd => env.push(("PROMPT".to_string(), format!("{}$P$G", prefix))),
+        ShellKind::Other => {
+            let inherited_prompt = std::env::var("PS1").unwrap_or_else(|_| "$ ".to_string());
+            env.push(("PS1".to_string(), format!("{}{}", prefix, inherited_prompt)));
+        }
+    }
+    Ok(ShellLaunch {
+        program: program.to_path_buf(),
+        args,
+        env,
+        _startup_files: startup_files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ide_export::test_environment;
+
+    #[test]
+    fn test_shell_kind() {
+        assert_eq!(ShellKind::from_program(Path::new("/usr/bin/zsh")), ShellKind::Zsh);
+        assert_eq!(ShellKind::from_program(Path::new("C:\\Program Files\\PowerShell\\7\\pwsh.exe")), ShellKind::PowerShell);
+        assert_eq!(ShellKind::from_program(Path::new("/bin/dash")), ShellKind::Other);
+    }
+
+    #[test]
+    fn test_prepare_bash() {
+        let launch = prepare(&test_environment("v5.4"), Path::new("/bin/bash")).unwrap();
+        assert_eq!(launch.args[0], "--rcfile");
+        let rc = fs::read_to_string(&launch.args[1]).unwrap();
+        assert!(rc.contains("PS1='(esp-idf v5.4) '\"$PS1\""));
+        assert!(launch.env.contains(&("IDF_PATH".to_string(), "/esp/v5.4/esp-idf".to_string())));
+        assert!(launch.env.contains(&(SHELL_MARKER_VARIABLE.to_string(), "v5.4".to_string())));
+    }
+
+    #[test]
+    fn test_single_quoted() {
+        assert_eq!(single_quoted("(esp-idf it's) "), "'(esp-idf it'\\''s) '");
+    }
+}
@@ -0,0 +1,247 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::Path;
+
+use crate::components::{selected_components, Component};
+use crate::idf_tools::{Download, ToolsFile};
+use crate::settings::Settings;
+
+const MIB: u64 = 1024 * 1024;
+
+/// Typical download of a shallow ESP-IDF checkout with its submodules; the repository publishes no size
+pub const IDF_DOWNLOAD_BYTES: u64 = 450 * MIB;
+/// Typical size of an ESP-IDF checkout on disk, git objects included
+pub const IDF_DISK_BYTES: u64 = 1400 * MIB;
+/// Part of [`IDF_DISK_BYTES`] taken by the `examples` directory
+pub const EXAMPLES_DISK_BYTES: u64 = 120 * MIB;
+/// Part of [`IDF_DISK_BYTES`] taken by the `docs` directory
+pub const DOCS_DISK_BYTES: u64 = 60 * MIB;
+/// Typical download of the Python packages of the virtual environment
+pub const PYTHON_DOWNLOAD_BYTES: u64 = 60 * MIB;
+/// Typical size of the Python virtual environment
+pub const PYTHON_DISK_BYTES: u64 = 350 * MIB;
+
+/// Where a size comes from, shown so users know how far to trust it.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeSource {
+    /// Read from the files already on this machine
+    Measured,
+    /// Archive size from `tools.json`, unpacked size from the typical compression ratio
+    Metadata,
+    /// Typical size of the part
+    Estimated,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SizeItem {
+    pub name: String,
+    /// Version of a tool, empty for ESP-IDF and the Python environment
+    pub version: String,
+    pub download_bytes: u64,
+    pub disk_bytes: u64,
+    pub source: SizeSource,
+}
+
+/// What installing a version will download and take on disk, shown before the user confirms.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SizeEstimate {
+    pub version: String,
+    pub targets: Vec<String>,
+    pub items: Vec<SizeItem>,
+    pub download_bytes: u64,
+    pub disk_bytes: u64,
+}
+
+/// Typical ratio of the unpacked size to the archive size, by archive format.
+pub fn unpack_ratio(file_name: &str) -> f64 {
+    let name = file_name.to_lowercase();
+    if name.ends_with(".tar.xz") {
+        4.0
+    } else if name.ends_with(".tar.bz2") {
+        3.5
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        3.0
+    } else if name.ends_with(".zip") {
+        2.5
+    } else {
+        1.0
+    }
+}
+
+/// Unpacked size of a zip archive, from its central directory.
+fn zip_unpacked_size(archive: &Path) -> Option<u64> {
+    let mut zip = zip::ZipArchive::new(File::open(archive).ok()?).ok()?;
+    (0..zip.len()).map(|i| zip.by_index_raw(i).ok().map(|f| f.size())).sum()
+}
+
+/// Size of one tool: nothing if the version is installed, no download if its archive is cached.
+pub fn tool_item(name: &str, version: &str, download: &Download, install_dir: &Path, download_dir: &Path) -> SizeItem {
+    let item = |download_bytes, disk_bytes, source| SizeItem {
+        name: name.to_string(),
+        version: version.to_string(),
+        download_bytes,
+        disk_bytes,
+        source,
+    };
+    if install_dir.join(name).join(version).is_dir() {
+        return item(0, 0, SizeSource::Measured);
+    }
+    let file_name = Path::new(&download.url)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let estimated_disk = (download.size as f64 * unpack_ratio(&file_name)) as u64;
+    let archive = download_dir.join(&file_name);
+    let cached = std::fs::metadata(&archive).is_ok_and(|m| m.len() == download.size);
+    if !cached {
+        return item(download.size, estimated_disk, SizeSource::Metadata);
+    }
+    match zip_unpacked_size(&archive) {
+        Some(unpacked) => item(0, unpacked, SizeSource::Measured),
+        None => item(0, estimated_disk, SizeSource::Metadata),
+    }
+}
+
+/// Sizes of ESP-IDF itself and its Python environment, without the omitted components.
+fn idf_items(components: &[Component], idf_path: &Path, venv_path: &Path) -> Vec<SizeItem> {
+    let mut idf_disk = IDF_DISK_BYTES;
+    if !components.contains(&Component::Examples) {
+        idf_disk -= EXAMPLES_DISK_BYTES;
+    }
+    if !components.contains(&Component::Docs) {
+        idf_disk -= DOCS_DISK_BYTES;
+    }
+    let part = |name: &str, path: &Path, download_bytes, disk_bytes| {
+        if path.exists() {
+            SizeItem {
+                name: name.to_string(),
+                version: String::new(),
+                download_bytes: 0,
+                disk_bytes: 0,
+                source: SizeSource::Measured,
+            }
+        } else {
+            SizeItem {
+                name: name.to_string(),
+                version: String::new(),
+                download_bytes,
+                disk_bytes,
+                source: SizeSource::Estimated,
+            }
+        }
+    };
+    vec![
+        part("esp-idf", idf_path, IDF_DOWNLOAD_BYTES, idf_disk),
+        part("python-env", venv_path, PYTHON_DOWNLOAD_BYTES, PYTHON_DISK_BYTES),
+    ]
+}
+
+/// Estimates the download and disk size of installing `version` with the targets, components
+/// and paths of `settings`, from the `tools.json` of the release.
+pub async fn estimate(settings: &Settings, version: &str) -> Result<SizeEstimate> {
+    let targets = settings.target.clone().unwrap_or_else(|| vec!["all".to_string()]);
+    let components = selected_components(settings)?;
+    let paths = settings.get_version_paths(version)?;
+    let tools_json = crate::support_matrix::fetch_text(version, settings.idf_mirror.as_deref(), "tools/tools.json").await?;
+    let mut tools: ToolsFile = serde_json::from_str(&tools_json)?;
+    if !components.contains(&Component::Qemu) {
+        tools = crate::qemu::without_qemu(tools);
+    }
+    let tools = crate::system_tools::without_system_tools(tools, &crate::system_tools::selected(settings));
+    let downloads = crate::idf_tools::get_list_of_tools_to_download(tools, targets.clone(), settings.mirror.as_deref());
+    let mut items: Vec<SizeItem> = downloads
+        .iter()
+        .map(|(name, (tool_version, download))| {
+            tool_item(name, tool_version, download, &paths.tool_install_directory, &paths.tool_download_directory)
+        })
+        .collect();
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    items.splice(0..0, idf_items(&components, &paths.idf_path, &paths.python_venv_path));
+    Ok(SizeEstimate {
+        version: version.to_string(),
+        targets,
+        download_bytes: items.iter().map(|i| i.download_bytes).sum(),
+        disk_bytes: items.iter().map(|i| i.disk_bytes).sum(),
+        items,
+    })
+}
+
+/// Download and disk size of installing all of `estimates`, counting tools shared by several
+/// versions once.
+pub fn totals(estimates: &[SizeEstimate]) -> (u64, u64) {
+    let mut seen = HashSet::new();
+    estimates
+        .iter()
+        .flat_map(|estimate| estimate.items.iter().map(move |item| (estimate, item)))
+        .filter(|(estimate, item)| {
+            let key = if item.version.is_empty() {
+                (estimate.version.clone(), item.name.clone())
+            } else {
+                (item.version.clone(), item.name.clone())
+            };
+            seen.insert(key)
+        })
+        .fold((0, 0), |(download, disk), (_, item)| (download + item.download_bytes, disk + item.disk_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn download(url: &str, size: u64) -> Download {
+        Download {
+            sha256: String::new(),
+            size,
+            url: url.to_string(),
+            rename_dist: None,
+        }
+    }
+
+    #[test]
+    fn test_tool_item() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let install_dir = dir.path().join("tools");
+        let download_dir = dir.path().join("dist");
+        std::fs::create_dir_all(install_dir.join("cmake").join("3.30.2")).unwrap();
+        std::fs::create_dir_all(&download_dir).unwrap();
+
+        let cmake = download("https://dl.espressif.com/cmake-3.30.2.tar.gz", 100);
+        let installed = tool_item("cmake", "3.30.2", &cmake, &install_dir, &download_dir);
+        assert_eq!((installed.download_bytes, installed.disk_bytes), (0, 0));
+
+        let ninja = download("https://dl.espressif.com/ninja-1.12.1.tar.xz", 100);
+        let missing = tool_item("ninja", "1.12.1", &ninja, &install_dir, &download_dir);
+        assert_eq!((missing.download_bytes, missing.disk_bytes), (100, 400));
+        assert_eq!(missing.source, SizeSource::Metadata);
+
+        std::fs::write(download_dir.join("ninja-1.12.1.tar.xz"), vec![0u8; 100]).unwrap();
+        let cached = tool_item("ninja", "1.12.1", &ninja, &install_dir, &download_dir);
+        assert_eq!((cached.download_bytes, cached.disk_bytes), (0, 400));
+    }
+
+    #[test]
+    fn test_totals_count_shared_tools_once() {
+        let item = |name: &str, version: &str, bytes| SizeItem {
+            name: name.to_string(),
+            version: version.to_string(),
+            download_bytes: bytes,
+            disk_bytes: bytes * 2,
+            source: SizeSource::Metadata,
+        };
+        let estimate = |version: &str, items| SizeEstimate {
+            version: version.to_string(),
+            targets: vec!["esp32".to_string()],
+            items,
+            download_bytes: 0,
+            disk_bytes: 0,
+        };
+        let estimates = [
+            estimate("v5.3", vec![item("esp-idf", "", 10), item("cmake", "3.30.2", 5)]),
+            estimate("v5.4", vec![item("esp-idf", "", 10), item("cmake", "3.30.2", 5)]),
+        ];
+        assert_eq!(totals(&estimates), (25, 50));
+    }
+}
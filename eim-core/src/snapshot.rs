@@ -0,0 +1,398 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use crate::errors::{EimError, ErrorKind};
+use crate::idf_config::{IdfConfig, IdfInstallation, IDF_CONFIG_FILE_NAME};
+use crate::idf_tools::{get_platform_identification, get_tools_export_paths, read_and_parse_tools_file};
+use crate::settings::Settings;
+
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+/// Manifest describing the snapshot, the first entry of the archive
+pub const SNAPSHOT_MANIFEST_NAME: &str = "eim_snapshot.json";
+
+const IDF_DIR: &str = "esp-idf";
+const TOOLS_DIR: &str = "tools";
+const PYTHON_ENV_DIR: &str = "python_env";
+/// Files larger than this are not searched for paths to fix up, they are binaries
+const MAX_FIXUP_FILE_SIZE: u64 = 4 * 1024 * 1024;
+/// Extension of the checksum file written next to an exported tarball
+pub const CHECKSUM_EXTENSION: &str = "sha256";
+
+/// Describes an installation captured by `eim snapshot create`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SnapshotManifest {
+    pub format_version: u32,
+    /// Name of the installation, e.g. `v5.3.2`
+    pub name: String,
+    /// ESP-IDF version of an installation named with `--version-name`, not set when the name is the version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idf_version: Option<String>,
+    /// Platform as named in tools.json; snapshots only restore on the same platform
+    pub platform: String,
+    pub eim_version: String,
+    pub created: String,
+    /// Paths on the machine the snapshot was created on, replaced by the new ones on restore
+    pub idf_path: String,
+    pub tools_path: String,
+    pub python_env_path: String,
+    /// Tool folders relative to the tools folder, e.g. `xtensa-esp-elf/esp-14.2.0_20241119`
+    pub tool_dirs: Vec<String>,
+}
+
+/// Folder of the Python virtual environment of an installation
+fn python_env_of(installation: &IdfInstallation) -> Result<PathBuf> {
+    // <venv>/bin/python or <venv>\Scripts\python.exe
+    Path::new(&installation.python)
+        .parent()
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .ok_or_else(|| anyhow!("Unexpected Python path {}", installation.python))
+}
+
+/// Installed tool folders of the tools listed in the tools.json of the installation.
+fn tool_dirs_of(installation: &IdfInstallation) -> Result<Vec<String>> {
+    let tools_json = Path::new(&installation.path).join("tools").join("tools.json");
+    let tools_file = read_and_parse_tools_file(&tools_json.to_string_lossy())
+        .map_err(|e| anyhow!("Failed to read {}: {}", tools_json.display(), e))?;
+    let tools_path = Path::new(&installation.idf_tools_path);
+    let mut dirs = Vec::new();
+    for tool in &tools_file.tools {
+        for version in &tool.versions {
+            if tools_path.join(&tool.name).join(&version.name).is_dir() {
+                dirs.push(format!("{}/{}", tool.name, version.name));
+            }
+        }
+    }
+    Ok(dirs)
+}
+
+/// Captures `installation` with its tools and Python environment into a `.tar.zst` archive at `out`.
+pub fn create_snapshot(installation: &IdfInstallation, out: &Path) -> Result<SnapshotManifest> {
+    let python_env = python_env_of(installation)?;
+    let manifest = SnapshotManifest {
+        format_version: SNAPSHOT_FORMAT_VERSION,
+        name: installation.name.clone(),
+        idf_version: installation.idf_version.clone(),
+        platform: get_platform_identification().map_err(|e| anyhow!(e))?,
+        eim_version: env!("CARGO_PKG_VERSION").to_string(),
+        created: Utc::now().to_rfc3339(),
+        idf_path: installation.path.clone(),
+        tools_path: installation.idf_tools_path.clone(),
+        python_env_path: python_env.to_string_lossy().into_owned(),
+        tool_dirs: tool_dirs_of(installation)?,
+    };
+
+    if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let encoder = zstd::stream::write::Encoder::new(File::create(out)?, 3)?;
+    let mut builder = tar::Builder::new(encoder);
+    // the venv links to the base interpreter, links are kept as they are
+    builder.follow_symlinks(false);
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, SNAPSHOT_MANIFEST_NAME, manifest_json.as_slice())?;
+
+    info!("Adding {} to the snapshot", installation.path);
+    builder.append_dir_all(IDF_DIR, &installation.path)?;
+    for dir in &manifest.tool_dirs {
+        debug!("Adding tool {} to the snapshot", dir);
+        builder.append_dir_all(
+            format!("{}/{}", TOOLS_DIR, dir),
+            Path::new(&installation.idf_tools_path).join(dir),
+        )?;
+    }
+    if python_env.is_dir() {
+        builder.append_dir_all(PYTHON_ENV_DIR, &python_env)?;
+    } else {
+        warn!("Python environment {} not found, the snapshot will not contain it", python_env.display());
+    }
+    builder.into_inner()?.finish()?;
+    info!("Snapshot of {} written to {}", installation.name, out.display());
+    Ok(manifest)
+}
+
+/// Moves `from` to `to`, copying when they are on different file systems.
+fn move_dir(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_err() {
+        crate::utils::copy_dir_contents(from, to)?;
+        fs::remove_dir_all(from)?;
+    }
+    Ok(())
+}
+
+/// Replaces the old absolute paths in the text files below `dir`, e.g. the shebangs of the
+/// scripts and the `activate` scripts of a virtual environment. Returns the number of fixed files.
+pub fn rewrite_paths(dir: &Path, replacements: &[(String, String)]) -> Result<usize> {
+    let mut fixed = 0;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let metadata = fs::symlink_metadata(&current)?;
+        if metadata.is_dir() {
+            for entry in fs::read_dir(&current)? {
+                pending.push(entry?.path());
+            }
+            continue;
+        }
+        if !metadata.is_file() || metadata.len() > MAX_FIXUP_FILE_SIZE {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&current) else {
+            continue; // binary
+        };
+        let mut rewritten = content.clone();
+        for (old, new) in replacements {
+            rewritten = rewritten.replace(old.as_str(), new);
+        }
+        if rewritten != content {
+            fs::write(&current, rewritten)?;
+            fixed += 1;
+        }
+    }
+    Ok(fixed)
+}
+
+/// Path replacements for `old` to `new`, also in the forward slash form used by Python on Windows.
+fn path_replacements(old: &str, new: &Path) -> Vec<(String, String)> {
+    let new = new.to_string_lossy().into_owned();
+    let mut replacements = vec![(old.to_string(), new.clone())];
+    if old.contains('\\') {
+        replacements.push((old.replace('\\', "/"), new.replace('\\', "/")));
+    }
+    replacements.retain(|(old, new)| old != new);
+    replacements
+}
+
+/// Restores a snapshot created by [`create_snapshot`] into the paths of `settings`, fixing up the
+/// absolute paths, writing a new activation script and registering the installation in `eim_idf.json`.
+pub fn restore_snapshot(archive: &Path, settings: &Settings) -> Result<IdfInstallation> {
+    let base = settings
+        .path
+        .clone()
+        .ok_or_else(|| anyhow!("Installation path not set"))?;
+    fs::create_dir_all(&base)?;
+    let staging = tempfile::Builder::new().prefix(".eim_snapshot").tempdir_in(&base)?;
+    crate::utils::extract_zst_archive(archive, staging.path())
+        .map_err(|e| anyhow!("Failed to extract {}: {}", archive.display(), e))?;
+
+    let manifest_path = staging.path().join(SNAPSHOT_MANIFEST_NAME);
+    let manifest: SnapshotManifest = serde_json::from_str(
+        &fs::read_to_string(&manifest_path).context("The archive is not an eim snapshot")?,
+    )?;
+    if manifest.format_version > SNAPSHOT_FORMAT_VERSION {
+        return Err(EimError::new(
+            ErrorKind::Usage,
+            format!("The snapshot was created by a newer eim ({}), update eim to restore it", manifest.eim_version),
+        )
+        .into());
+    }
+    let platform = get_platform_identification().map_err(|e| anyhow!(e))?;
+    if manifest.platform != platform {
+        return Err(EimError::new(
+            ErrorKind::Usage,
+            format!("The snapshot was created on {} and cannot be restored on {}", manifest.platform, platform),
+        )
+        .into());
+    }
+
+    // a named copy is restored under its name, with the paths of a `--version-name` installation
+    let mut settings = settings.clone();
+    let idf_version = match &manifest.idf_version {
+        Some(idf_version) => {
+            settings.version_name = Some(manifest.name.clone());
+            idf_version.clone()
+        }
+        None => manifest.name.clone(),
+    };
+    let paths = settings.get_version_paths(&idf_version)?;
+    if paths.idf_path.exists() {
+        return Err(EimError::new(
+            ErrorKind::Usage,
+            format!("{} already exists, remove the installation first", paths.idf_path.display()),
+        )
+        .into());
+    }
+    move_dir(&staging.path().join(IDF_DIR), &paths.idf_path)?;
+    for dir in &manifest.tool_dirs {
+        let destination = paths.tool_install_directory.join(dir);
+        if destination.exists() {
+            debug!("Tool {} is already installed", dir);
+            continue;
+        }
+        move_dir(&staging.path().join(TOOLS_DIR).join(dir), &destination)?;
+    }
+    let python_env = staging.path().join(PYTHON_ENV_DIR);
+    if python_env.is_dir() {
+        if paths.python_venv_path.exists() {
+            fs::remove_dir_all(&paths.python_venv_path)?;
+        }
+        move_dir(&python_env, &paths.python_venv_path)?;
+        let mut replacements = path_replacements(&manifest.python_env_path, &paths.python_venv_path);
+        replacements.extend(path_replacements(&manifest.idf_path, &paths.idf_path));
+        let fixed = rewrite_paths(&paths.python_venv_path, &replacements)?;
+        debug!("Fixed paths in {} files of the Python environment", fixed);
+        if !paths.python_path.exists() {
+            warn!(
+                "{} does not work, the Python it was created from is missing on this machine",
+                paths.python_path.display()
+            );
+        }
+    }
+
+    let tools_file = read_and_parse_tools_file(&paths.idf_path.join("tools").join("tools.json").to_string_lossy())
+        .map_err(|e| anyhow!("Failed to read the tools.json of the snapshot: {}", e))?;
+    let tools_path = paths.tool_install_directory.to_string_lossy().into_owned();
+    let export_paths = get_tools_export_paths(
+        tools_file,
+        settings.target.clone().unwrap_or_else(|| vec!["all".to_string()]),
+        &tools_path,
+    );
+    crate::single_version_post_install(
+        &paths.activation_script_path.to_string_lossy(),
+        &paths.idf_path.to_string_lossy(),
+        &paths.actual_version,
+        &tools_path,
+        export_paths,
+        paths.python_venv_path.to_str(),
+        None,
+    );
+
+    let mut registered = settings.clone();
+    registered.idf_versions = Some(vec![idf_version]);
+    registered.save_esp_ide_json()?;
+    info!("Snapshot of {} restored to {}", manifest.name, paths.idf_path.display());
+    let config_path = PathBuf::from(settings.esp_idf_json_path.clone().unwrap_or_default()).join(IDF_CONFIG_FILE_NAME);
+    let idf_path = paths.idf_path.to_string_lossy();
+    IdfConfig::from_file(&config_path)?
+        .idf_installed
+        .into_iter()
+        .find(|installation| installation.path == idf_path)
+        .ok_or_else(|| anyhow!("The restored installation is missing in {}", config_path.display()))
+}
+
+/// Default file name of an exported tarball, e.g. `idf-v5.2-linux-amd64.tar.zst`
+pub fn tarball_file_name(name: &str) -> Result<String> {
+    let platform = get_platform_identification().map_err(|e| anyhow!(e))?;
+    Ok(format!("idf-{}-{}.tar.zst", name, platform))
+}
+
+/// Checksum file of `archive`, e.g. `idf-v5.2-linux-amd64.tar.zst.sha256`
+pub fn checksum_path(archive: &Path) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(".");
+    path.push(CHECKSUM_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Writes the checksum of `archive` next to it, in the format of `sha256sum`, and returns it.
+pub fn write_checksum(archive: &Path) -> Result<String> {
+    let sha256 = crate::checksum::sha256_file(archive)?;
+    let file_name = archive.file_name().unwrap_or_default().to_string_lossy();
+    fs::write(checksum_path(archive), format!("{}  {}\n", sha256, file_name))?;
+    Ok(sha256)
+}
+
+/// Checks `archive` against the checksum file next to it. Returns `false` when there is none,
+/// fails when the archive got corrupted on the way, e.g. on a USB stick.
+pub fn verify_checksum(archive: &Path) -> Result<bool> {
+    let path = checksum_path(archive);
+    if !path.is_file() {
+        return Ok(false);
+    }
+    let content = fs::read_to_string(&path)?;
+    let expected = content.split_whitespace().next().unwrap_or_default();
+    let actual = crate::checksum::sha256_file(archive)?;
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(EimError::new(
+            ErrorKind::Usage,
+            format!("{} does not match {}, copy the archive again", archive.display(), path.display()),
+        )
+        .into());
+    }
+    Ok(true)
+}
+
+/// Exports `installation` as a relocatable tarball for machines without network, with a
+/// checksum file next to it. The tarball is a snapshot; [`import_tarball`] installs it.
+pub fn export_tarball(installation: &IdfInstallation, out: &Path) -> Result<(SnapshotManifest, String)> {
+    let manifest = create_snapshot(installation, out)?;
+    let sha256 = write_checksum(out)?;
+    Ok((manifest, sha256))
+}
+
+/// Installs a tarball exported by [`export_tarball`] into the paths of `settings` and registers
+/// it, after checking it against its checksum file when it came with one.
+pub fn import_tarball(archive: &Path, settings: &Settings) -> Result<IdfInstallation> {
+    if verify_checksum(archive)? {
+        info!("Checksum of {} verified", archive.display());
+    } else {
+        warn!("No checksum file next to {}, the archive is not verified", archive.display());
+    }
+    restore_snapshot(archive, settings)
+}
+
+/// Whether `path` looks like an archive of [`export_tarball`] rather than a configuration file.
+pub fn is_tarball(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(".tar.zst")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_paths() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let bin = dir.path().join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join("idf-component"), "#!/home/ci/.espressif/venv/bin/python\nimport sys\n").unwrap();
+        fs::write(bin.join("python.so"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let replacements = path_replacements("/home/ci/.espressif/venv", Path::new("/opt/runner/venv"));
+        assert_eq!(rewrite_paths(dir.path(), &replacements).unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(bin.join("idf-component")).unwrap(),
+            "#!/opt/runner/venv/bin/python\nimport sys\n"
+        );
+    }
+
+    #[test]
+    fn test_checksum_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let archive = dir.path().join("idf-v5.2-linux-amd64.tar.zst");
+        fs::write(&archive, b"archive").unwrap();
+        assert!(!verify_checksum(&archive).unwrap());
+
+        write_checksum(&archive).unwrap();
+        let checksum = fs::read_to_string(checksum_path(&archive)).unwrap();
+        assert!(checksum.ends_with("  idf-v5.2-linux-amd64.tar.zst\n"));
+        assert!(verify_checksum(&archive).unwrap());
+
+        fs::write(&archive, b"corrupted").unwrap();
+        assert!(verify_checksum(&archive).is_err());
+    }
+
+    #[test]
+    fn test_manifest_without_idf_version() {
+        // snapshots of earlier versions do not record the ESP-IDF version of named copies
+        let manifest: SnapshotManifest = serde_json::from_str(
+            r#"{"format_version": 1, "name": "v5.2", "platform": "linux-amd64", "eim_version": "0.3.0",
+                "created": "2025-01-01T00:00:00Z", "idf_path": "/esp/v5.2/esp-idf", "tools_path": "/esp/tools",
+                "python_env_path": "/esp/tools/python/v5.2/venv", "tool_dirs": []}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.idf_version, None);
+        assert!(!serde_json::to_string(&manifest).unwrap().contains("idf_version"));
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::idf_tools::ToolsFile;
+
+/// Lists of the chip targets of a release, `SUPPORTED_TARGETS` and `PREVIEW_TARGETS`
+pub const TARGETS_FILE: &str = "tools/idf_py_actions/constants.py";
+
+/// A chip target of a release with the silicon revisions it can be built for.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TargetSupport {
+    pub target: String,
+    /// Preview targets build, but are not production ready in the release
+    pub preview: bool,
+    /// Revisions offered as minimum supported revision, e.g. `v0.0`, `v1.0`; empty if unknown
+    pub revisions: Vec<String>,
+}
+
+/// The recommended version of a tool, with the targets it is installed for (empty: all).
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ToolSupport {
+    pub name: String,
+    pub version: String,
+    pub targets: Vec<String>,
+}
+
+/// What `eim info --targets` and the GUI show to pick the right release for a chip.
+#[derive(Debug, Clone, Serialize)]
+pub struct SupportMatrix {
+    pub version: String,
+    pub targets: Vec<TargetSupport>,
+    pub tools: Vec<ToolSupport>,
+}
+
+/// Parses a Python list of strings assigned to `name`, which may span several lines.
+pub fn parse_target_list(constants_py: &str, name: &str) -> Vec<String> {
+    let prefix = format!("{} = [", name);
+    let Some(start) = constants_py.find(&prefix) else {
+        return vec![];
+    };
+    let list = &constants_py[start + prefix.len()..];
+    let list = &list[..list.find(']').unwrap_or(list.len())];
+    list.split(',')
+        .map(|item| item.trim().trim_matches(|c| c == '\'' || c == '"').to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Parses the choices of the minimum supported revision from a `Kconfig.hw_support`,
+/// e.g. `bool "Rev v0.1"` gives `v0.1`.
+pub fn parse_revisions(kconfig: &str) -> Vec<String> {
+    let mut revisions = Vec::new();
+    let mut in_choice = false;
+    for line in kconfig.lines().map(str::trim) {
+        if line.starts_with("choice ") && line.ends_with("_REV_MIN") {
+            in_choice = true;
+        } else if line == "endchoice" {
+            in_choice = false;
+        } else if in_choice {
+            if let Some(label) = line.strip_prefix("bool \"Rev ") {
+                let revision = label.trim_end_matches('"').split_whitespace().next().unwrap_or_default();
+                revisions.push(revision.to_string());
+            }
+        }
+    }
+    revisions
+}
+
+/// The recommended version of every tool in `tools`.
+pub fn recommended_tools(tools: &ToolsFile) -> Vec<ToolSupport> {
+    tools
+        .tools
+        .iter()
+        .filter_map(|tool| {
+            let version = tool.versions.iter().find(|v| v.status == "recommended")?;
+            let mut targets = tool.supported_targets.clone().unwrap_or_default();
+            targets.retain(|t| t != "all");
+            Some(ToolSupport {
+                name: tool.name.clone(),
+                version: version.name.clone(),
+                targets,
+            })
+        })
+        .collect()
+}
+
+pub(crate) async fn fetch_text(version: &str, mirror: Option<&str>, path: &str) -> Result<String> {
+    let url = crate::git_tools::get_raw_file_url(None, version, mirror, path);
+    crate::metadata_cache::fetch_text(&url, crate::http_client::client().get(&url)).await
+}
+
+/// Collects the targets, their revisions and the tool versions of `version` from the files of the
+/// release, fetched from `mirror` (GitHub if `None`).
+///
+/// Falls back to the targets listed in `idf_versions.json` when the release has no targets file.
+pub async fn get_support_matrix(version: &str, mirror: Option<&str>) -> Result<SupportMatrix> {
+    let (supported, preview) = match fetch_text(version, mirror, TARGETS_FILE).await {
+        Ok(constants) => (
+            parse_target_list(&constants, "SUPPORTED_TARGETS"),
+            parse_target_list(&constants, "PREVIEW_TARGETS"),
+        ),
+        Err(err) => {
+            log::warn!("Failed to fetch the targets of {}: {}", version, err);
+            let releases = crate::idf_versions::get_idf_versions().await.map_err(|e| anyhow!(e))?;
+            let supported = releases
+                .VERSIONS
+                .iter()
+                .find(|v| v.name == version)
+                .map(|v| v.supported_targets.clone())
+                .ok_or_else(|| anyhow!("Version {} not found", version))?;
+            (supported, vec![])
+        }
+    };
+
+    let mut targets = Vec::new();
+    let candidates = supported
+        .into_iter()
+        .map(|t| (t, false))
+        .chain(preview.into_iter().map(|t| (t, true)))
+        .filter(|(target, _)| target != "linux");
+    for (target, preview) in candidates {
+        let kconfig_path = format!("components/esp_hw_support/port/{}/Kconfig.hw_support", target);
+        let revisions = match fetch_text(version, mirror, &kconfig_path).await {
+            Ok(kconfig) => parse_revisions(&kconfig),
+            Err(err) => {
+                log::debug!("No revisions of {} in {}: {}", target, version, err);
+                vec![]
+            }
+        };
+        targets.push(TargetSupport { target, preview, revisions });
+    }
+
+    let tools = match fetch_text(version, mirror, "tools/tools.json").await {
+        Ok(json) => recommended_tools(&serde_json::from_str(&json)?),
+        Err(err) => {
+            log::warn!("Failed to fetch the tools of {}: {}", version, err);
+            vec![]
+        }
+    };
+    Ok(SupportMatrix {
+        version: version.to_string(),
+        targets,
+        tools,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target_list() {
+        let constants = "SUPPORTED_TARGETS = ['esp32', 'esp32s2',\n    'esp32p4']\nPREVIEW_TARGETS = ['linux', 'esp32c5']\n";
+        assert_eq!(parse_target_list(constants, "SUPPORTED_TARGETS"), vec!["esp32", "esp32s2", "esp32p4"]);
+        assert_eq!(parse_target_list(constants, "PREVIEW_TARGETS"), vec!["linux", "esp32c5"]);
+        assert!(parse_target_list(constants, "OTHER").is_empty());
+    }
+
+    #[test]
+    fn test_parse_revisions() {
+        let kconfig = r#"
+choice ESP32P4_REV_MIN
+    prompt "Minimum Supported ESP32-P4 Revision"
+    default ESP32P4_REV_MIN_0
+    config ESP32P4_REV_MIN_0
+        bool "Rev v0.0"
+    config ESP32P4_REV_MIN_100
+        bool "Rev v1.0 (ECO1)"
+endchoice
+
+choice ESP32P4_REV_MAX
+    config ESP32P4_REV_MAX_199
+        bool "Rev v1.99"
+endchoice
+"#;
+        assert_eq!(parse_revisions(kconfig), vec!["v0.0", "v1.0"]);
+    }
+}
@@ -57,6 +57,7 @@ pub fn get_prequisites() -> Vec<&'static str> {
         ],
         "windows" => vec!["git"],
         "macos" => vec!["dfu-util","cmake"],
+        "freebsd" => crate::freebsd::PKG_PREREQUISITES.to_vec(),
         _ => vec![],
     }
 }
@@ -378,6 +379,9 @@ pub fn check_prerequisites() -> Result<Vec<&'static str>, String> {
                 }
             }
         }
+        "freebsd" => {
+            unsatisfied = crate::freebsd::missing_packages(&list_of_required_tools);
+        }
         _ => {
             return Err(format!("Unsupported OS - {}", std::env::consts::OS));
         }
@@ -430,7 +434,7 @@ fn install_scoop_package_manager() -> Result<(), String> {
                 }
             };
             add_to_path(&path_with_scoop).unwrap();
-            let scoop_install_cmd = include_str!("../../powershell_scripts/install_scoop.ps1");
+            let scoop_install_cmd = include_str!("../powershell_scripts/install_scoop.ps1");
             let output = crate::run_powershell_script(scoop_install_cmd);
 
             match output {
@@ -544,70 +548,39 @@ pub fn install_prerequisites(packages_list: Vec<String>) -> Result<(), String> {
     match std::env::consts::OS {
         "linux" => {
             let package_manager = determine_package_manager();
-            match package_manager {
-                Some("apt") => {
-                    for package in packages_list {
-                        let output = command_executor::execute_command(
-                            "sudo",
-                            &["apt", "install", "-y", &package],
-                        );
-                        match output {
-                            Ok(_) => {
-                                debug!("Successfully installed {}", package);
-                            }
-                            Err(e) => panic!("Failed to install {}: {}", package, e),
-                        }
-                    }
-                }
-                Some("dnf") => {
-                    for package in packages_list {
-                        let output = command_executor::execute_command(
-                            "sudo",
-                            &["dnf", "install", "-y", &package],
-                        );
-                        match output {
-                            Ok(_) => {
-                                debug!("Successfully installed {}", package);
-                            }
-                            Err(e) => panic!("Failed to install {}: {}", package, e),
-                        }
-                    }
-                }
-                Some("pacman") => {
-                    for package in packages_list {
-                        let output = command_executor::execute_command(
-                            "sudo",
-                            &["pacman", "-S", "--noconfirm", &package],
-                        );
-                        match output {
-                            Ok(_) => {
-                                debug!("Successfully installed {}", package);
-                            }
-                            Err(e) => panic!("Failed to install {}: {}", package, e),
-                        }
-                    }
-                }
-                Some("zypper") => {
-                    for package in packages_list {
-                        let output = command_executor::execute_command(
-                            "sudo",
-                            &["zypper", "install", "-y", &package],
-                        );
-                        match output {
-                            Ok(_) => {
-                                debug!("Successfully installed {}", package);
-                            }
-                            Err(e) => panic!("Failed to install {}: {}", package, e),
-                        }
-                    }
-                }
+            let install_command: &[&str] = match package_manager {
+                Some("apt") => &["apt", "install", "-y"],
+                Some("dnf") => &["dnf", "install", "-y"],
+                Some("pacman") => &["pacman", "-S", "--noconfirm"],
+                Some("zypper") => &["zypper", "install", "-y"],
                 _ => {
                     return Err(format!(
                         "Unsupported package manager - {}",
-                        package_manager.unwrap()
+                        package_manager.unwrap_or("none")
                     ));
                 }
-            }
+            };
+            // all packages at once, so the user is asked for the password only once
+            let mut args = install_command[1..].to_vec();
+            args.extend(packages_list.iter().map(String::as_str));
+            crate::elevation::run_elevated(
+                &format!("install the system packages {}", packages_list.join(", ")),
+                install_command[0],
+                &args,
+            )
+            .map_err(|e| e.to_string())?;
+            debug!("Successfully installed {}", packages_list.join(", "));
+        }
+        "freebsd" => {
+            let mut args = vec!["install", "-y"];
+            args.extend(packages_list.iter().map(String::as_str));
+            crate::elevation::run_elevated(
+                &format!("install the system packages {}", packages_list.join(", ")),
+                "pkg",
+                &args,
+            )
+            .map_err(|e| e.to_string())?;
+            debug!("Successfully installed {}", packages_list.join(", "));
         }
         "macos" => {
             for package in packages_list {
@@ -713,6 +686,7 @@ pub fn add_to_path(new_path: &str) -> Result<String, std::io::Error> {
                if (-not $oldPath.Contains('{}')) {{ \
                    $newPath = '{}' + ';' + $oldPath; \
                    [Environment]::SetEnvironmentVariable('PATH', $newPath, 'User'); \
+                   Write-Output 'added'; \
                }}",
             new_path.replace("'", "''"),
             new_path.replace("'", "''")
@@ -724,8 +698,16 @@ pub fn add_to_path(new_path: &str) -> Result<String, std::io::Error> {
         );
 
         match res {
-            Ok(_) => {
+            Ok(output) => {
                 debug!("Added {} to PATH", new_path);
+                // reverted when the installation is removed
+                if String::from_utf8_lossy(&output.stdout).contains("added") {
+                    crate::install_manifest::record_system_change(crate::install_manifest::ManifestEntry::RegistryValue {
+                        key: crate::install_manifest::USER_ENVIRONMENT_KEY.to_string(),
+                        name: "Path".to_string(),
+                        entry: Some(new_path.to_string()),
+                    });
+                }
             }
             Err(e) => {
                 warn!("Failed to add {} to PATH: {}", new_path, e);
@@ -743,7 +725,8 @@ pub fn add_to_path(new_path: &str) -> Result<String, std::io::Error> {
 /// Copies the 60-openocd.rules file to /etc/udev/rules.d/ on Linux.
 ///
 /// This function checks if the rules file already exists. If not, it attempts
-/// to find it within the provided `tools_path` and copy it.
+/// to find it within the provided `tools_path` and copy it, asking for elevation
+/// when /etc/udev/rules.d is not writable.
 ///
 /// # Arguments
 /// * `tools_path` - The path where tool-related files might be located,
@@ -773,9 +756,14 @@ pub fn copy_openocd_rules(tools_path: &str) -> Result<()> {
       tools_path.display()
     )
   })?;
-  fs::copy(openocd_rules_source, openocd_rules_path).with_context(|| {
+  crate::elevation::copy_file(
+    "install the udev rules which let OpenOCD access debug probes without root",
+    std::path::Path::new(openocd_rules_source),
+    openocd_rules_path,
+  )
+  .with_context(|| {
     format!(
-      "Failed to copy {} to {} . Make sure you have the necessary permissions. Now you can copy it manually.",
+      "Failed to copy {} to {} . Now you can copy it manually.",
       openocd_rules_source,
       openocd_rules_path.display()
     )
@@ -0,0 +1,201 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+
+use crate::command_executor::execute_command;
+use crate::errors::{EimError, ErrorKind};
+use crate::settings::{Settings, VersionPaths};
+
+/// Base directory of system-wide installations
+pub const SYSTEM_INSTALL_PATH: &str = "/opt/esp";
+/// Directory of the login shell snippets every user sources
+pub const PROFILE_D_DIR: &str = "/etc/profile.d";
+
+pub fn is_enabled(settings: &Settings) -> bool {
+    settings.system_install.unwrap_or(false)
+}
+
+/// Path of the `/etc/profile.d` snippet making the activation script of `idf_version` available to all users.
+pub fn profile_script_path(idf_version: &str) -> PathBuf {
+    PathBuf::from(PROFILE_D_DIR).join(format!("esp-idf-{}.sh", idf_version))
+}
+
+/// Points the installation to [`SYSTEM_INSTALL_PATH`] and checks it is run by root.
+///
+/// Only the paths still at their per-user defaults are moved, so an explicit `--path` is kept.
+pub fn prepare(settings: &mut Settings) -> Result<()> {
+    if std::env::consts::OS == "windows" {
+        return Err(EimError::new(
+            ErrorKind::Usage,
+            "System-wide installations are only supported on Linux and macOS",
+        )
+        .into());
+    }
+    check_privileges()?;
+
+    let defaults = Settings::default();
+    let base = PathBuf::from(SYSTEM_INSTALL_PATH);
+    let tools = base.join("tools").to_string_lossy().into_owned();
+    if settings.path == defaults.path {
+        settings.path = Some(base.clone());
+    }
+    if settings.tool_install_folder_name == defaults.tool_install_folder_name {
+        settings.tool_install_folder_name = Some(tools.clone());
+    }
+    if settings.tool_download_folder_name == defaults.tool_download_folder_name {
+        settings.tool_download_folder_name = Some(base.join("dist").to_string_lossy().into_owned());
+    }
+    if settings.esp_idf_json_path == defaults.esp_idf_json_path {
+        settings.esp_idf_json_path = Some(tools.clone());
+    }
+    if settings.activation_script_path_override == defaults.activation_script_path_override {
+        settings.activation_script_path_override = Some(tools);
+    }
+    info!(
+        "Installing system-wide into {}",
+        settings.path.clone().unwrap_or_default().display()
+    );
+    Ok(())
+}
+
+/// Fails with `ErrorKind::Permissions` unless running as root.
+pub fn check_privileges() -> Result<()> {
+    let output = execute_command("id", &["-u"])?;
+    let uid = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if uid != "0" {
+        return Err(EimError::new(
+            ErrorKind::Permissions,
+            "A system-wide installation has to be run as root, e.g. with sudo",
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Makes everything below `path` writable by its group, so all members can update the installation.
+///
+/// Directories get the setgid bit so files created later inherit the group. With `group` set,
+/// the tree is handed to that group first.
+#[cfg(unix)]
+pub fn make_group_writable(path: &Path, group: Option<&str>) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(group) = group {
+        let output = execute_command("chgrp", &["-R", group, &path.to_string_lossy()])?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Failed to change the group of {} to {}: {}",
+                path.display(),
+                group,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        // symlinks are skipped, chmod would change their targets
+        let metadata = std::fs::symlink_metadata(&current)?;
+        if metadata.file_type().is_symlink() {
+            continue;
+        }
+        let mode = metadata.permissions().mode();
+        let new_mode = if metadata.is_dir() {
+            for entry in std::fs::read_dir(&current)? {
+                pending.push(entry?.path());
+            }
+            mode | 0o2070
+        } else {
+            // executables stay executable for the group, everything becomes group writable
+            mode | 0o060 | ((mode & 0o100) >> 3)
+        };
+        if new_mode != mode {
+            std::fs::set_permissions(&current, std::fs::Permissions::from_mode(new_mode))?;
+        }
+    }
+    debug!("Made {} group writable", path.display());
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn make_group_writable(_path: &Path, _group: Option<&str>) -> Result<()> {
+    Ok(())
+}
+
+/// Contents of the `/etc/profile.d` snippet, an alias activating the version in the current shell.
+pub fn profile_script(idf_version: &str, activation_script: &Path) -> String {
+    format!(
+        "# ESP-IDF {version} installed system-wide by eim\nalias activate_idf_{version}='. \"{script}\"'\n",
+        version = idf_version,
+        script = activation_script.display().to_string().replace('\'', "'\\''")
+    )
+}
+
+/// Writes the `/etc/profile.d` snippet for the installed version.
+pub fn install_profile_script(paths: &VersionPaths) -> Result<PathBuf> {
+    let path = profile_script_path(&paths.actual_version);
+    std::fs::write(&path, profile_script(&paths.actual_version, &paths.activation_script))
+        .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    info!("Activation alias for all users written to {}", path.display());
+    Ok(path)
+}
+
+/// Shares a freshly installed version with all users: the profile snippet is written and the
+/// installation made group writable.
+pub fn finish_version(settings: &Settings, paths: &VersionPaths) -> Result<()> {
+    install_profile_script(paths)?;
+    finish_installation(settings)
+}
+
+/// Makes the whole system-wide installation, including `eim_idf.json`, group writable.
+pub fn finish_installation(settings: &Settings) -> Result<()> {
+    let group = settings.system_group.as_deref();
+    let mut roots: Vec<PathBuf> = [
+        settings.path.clone(),
+        settings.esp_idf_json_path.clone().map(PathBuf::from),
+        settings.activation_script_path_override.clone().map(PathBuf::from),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|p| p.exists())
+    .collect();
+    roots.sort();
+    roots.dedup();
+    // nested roots are covered by their parent
+    let roots: Vec<&PathBuf> = roots
+        .iter()
+        .filter(|root| !roots.iter().any(|other| other != *root && root.starts_with(other)))
+        .collect();
+    for root in roots {
+        make_group_writable(root, group)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_script() {
+        let script = profile_script("v5.3.2", Path::new("/opt/esp/tools/activate_idf_v5.3.2.sh"));
+        assert!(script.contains("alias activate_idf_v5.3.2='. \"/opt/esp/tools/activate_idf_v5.3.2.sh\"'"));
+        assert_eq!(profile_script_path("v5.3.2"), PathBuf::from("/etc/profile.d/esp-idf-v5.3.2.sh"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_make_group_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let tool = dir.path().join("bin").join("tool");
+        std::fs::create_dir_all(tool.parent().unwrap()).unwrap();
+        std::fs::write(&tool, "").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o700)).unwrap();
+
+        make_group_writable(dir.path(), None).unwrap();
+        let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode(&tool), 0o770);
+        assert_eq!(mode(&dir.path().join("bin")) & 0o2070, 0o2070);
+    }
+}
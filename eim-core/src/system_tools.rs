@@ -0,0 +1,184 @@
+use log::{debug, info};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+use crate::idf_tools::ToolsFile;
+use crate::settings::Settings;
+
+/// Value of `use_system_tools` selecting every acceptable tool found on the `PATH`
+pub const AUTO: &str = "auto";
+
+/// A tool of `tools.json` which ESP-IDF runs just as well from the `PATH`.
+pub struct ReusableTool {
+    pub name: &'static str,
+    /// Oldest version ESP-IDF builds work with
+    pub minimum_version: &'static str,
+    version_regex: &'static str,
+}
+
+/// Tools eim can leave out in favour of the copy of the system
+pub const REUSABLE_TOOLS: &[ReusableTool] = &[
+    ReusableTool {
+        name: "cmake",
+        minimum_version: "3.16",
+        version_regex: r"cmake version ([0-9][0-9.]*)",
+    },
+    ReusableTool {
+        name: "ninja",
+        minimum_version: "1.10",
+        version_regex: r"(?m)^([0-9][0-9.]*)",
+    },
+];
+
+/// A tool found on the `PATH`, recorded in the install manifest when it is used instead of
+/// the copy eim would install.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SystemTool {
+    pub name: String,
+    pub path: String,
+    pub version: String,
+    pub minimum_version: String,
+}
+
+impl SystemTool {
+    pub fn is_acceptable(&self) -> bool {
+        version_at_least(&self.version, &self.minimum_version)
+    }
+}
+
+fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .split('.')
+        .map(|part| {
+            let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().unwrap_or(0)
+        })
+        .collect()
+}
+
+/// Whether `version` is `minimum` or newer, e.g. `3.28.3` and `3.16`.
+pub fn version_at_least(version: &str, minimum: &str) -> bool {
+    let (mut version, mut minimum) = (version_parts(version), version_parts(minimum));
+    let length = version.len().max(minimum.len());
+    version.resize(length, 0);
+    minimum.resize(length, 0);
+    version >= minimum
+}
+
+/// Version the executable at `path` prints, `None` if it can't be run.
+fn installed_version(tool: &ReusableTool, path: &Path) -> Option<String> {
+    let output = crate::command_executor::execute_command(&path.to_string_lossy(), &["--version"]).ok()?;
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let captures = Regex::new(tool.version_regex).ok()?.captures(text.trim())?;
+    Some(captures.get(1)?.as_str().trim_end_matches('.').to_string())
+}
+
+/// `path_var` without the folders below `root`, where eim installs its own tools.
+fn path_without(path_var: &OsStr, root: Option<&Path>) -> OsString {
+    let dirs = std::env::split_paths(path_var).filter(|dir| root.is_none_or(|root| !dir.starts_with(root)));
+    std::env::join_paths(dirs).unwrap_or_else(|_| path_var.to_os_string())
+}
+
+/// Looks up the reusable tools on the `PATH`, ignoring the tools of eim installations below
+/// the installation path of `settings`.
+pub fn detect(settings: &Settings) -> Vec<SystemTool> {
+    let path_var = path_without(&std::env::var_os("PATH").unwrap_or_default(), settings.path.as_deref());
+    REUSABLE_TOOLS
+        .iter()
+        .filter_map(|tool| {
+            let path = crate::which::find_in_path(tool.name, &path_var)?;
+            let version = installed_version(tool, &path)?;
+            debug!("Found {} {} at {}", tool.name, version, path.display());
+            Some(SystemTool {
+                name: tool.name.to_string(),
+                path: path.to_string_lossy().into_owned(),
+                version,
+                minimum_version: tool.minimum_version.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The acceptable system tools selected by the `use_system_tools` setting.
+pub fn selected(settings: &Settings) -> Vec<SystemTool> {
+    let Some(names) = settings.use_system_tools.as_ref().filter(|names| !names.is_empty()) else {
+        return Vec::new();
+    };
+    let all = names.iter().any(|name| name == AUTO);
+    detect(settings)
+        .into_iter()
+        .filter(|tool| tool.is_acceptable() && (all || names.contains(&tool.name)))
+        .collect()
+}
+
+/// Removes the tools used from the system from the tools file, so they are not downloaded.
+pub fn without_system_tools(mut tools_file: ToolsFile, system_tools: &[SystemTool]) -> ToolsFile {
+    tools_file.tools.retain(|tool| !system_tools.iter().any(|system| system.name == tool.name));
+    for tool in system_tools {
+        info!("Using {} {} of the system at {} instead of downloading it", tool.name, tool.version, tool.path);
+    }
+    tools_file
+}
+
+/// Problems of the system tools an installation was made with, e.g. after an OS upgrade
+/// removed them or replaced them by an older version.
+pub fn recheck(recorded: &[SystemTool]) -> Vec<String> {
+    recorded
+        .iter()
+        .filter_map(|tool| {
+            let reusable = REUSABLE_TOOLS.iter().find(|reusable| reusable.name == tool.name)?;
+            let path = Path::new(&tool.path);
+            if !path.is_file() {
+                return Some(format!("{} of the system is missing at {}", tool.name, tool.path));
+            }
+            match installed_version(reusable, path) {
+                Some(version) if version_at_least(&version, &tool.minimum_version) => None,
+                Some(version) => Some(format!(
+                    "{} of the system at {} is version {}, ESP-IDF needs {} or newer",
+                    tool.name, tool.path, version, tool.minimum_version
+                )),
+                None => Some(format!("{} of the system at {} cannot be run", tool.name, tool.path)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_at_least() {
+        assert!(version_at_least("3.28.3", "3.16"));
+        assert!(version_at_least("3.16", "3.16.0"));
+        assert!(version_at_least("1.12.1", "1.10"));
+        assert!(!version_at_least("3.10.2", "3.16"));
+        assert!(!version_at_least("1.9.0", "1.10"));
+    }
+
+    #[test]
+    fn test_path_without() {
+        let dirs = [Path::new("/usr/bin"), Path::new("/home/user/.espressif/tools/cmake/3.30.2/bin")];
+        let path_var = std::env::join_paths(dirs).unwrap();
+        let filtered = path_without(&path_var, Some(Path::new("/home/user/.espressif")));
+        assert_eq!(std::env::split_paths(&filtered).collect::<Vec<_>>(), vec![Path::new("/usr/bin")]);
+        assert_eq!(path_without(&path_var, None), path_var);
+    }
+
+    #[test]
+    fn test_recheck_reports_missing_tools() {
+        let recorded = SystemTool {
+            name: "cmake".to_string(),
+            path: "/nonexistent/bin/cmake".to_string(),
+            version: "3.28.3".to_string(),
+            minimum_version: "3.16".to_string(),
+        };
+        assert_eq!(recheck(&[recorded]), vec!["cmake of the system is missing at /nonexistent/bin/cmake"]);
+    }
+}
@@ -0,0 +1,321 @@
+use anyhow::{anyhow, Result};
+use log::{debug, error};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+type TaskFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+type TaskFn = Box<dyn FnOnce() -> TaskFuture + Send>;
+
+struct Task {
+    id: String,
+    dependencies: Vec<String>,
+    run: TaskFn,
+}
+
+/// Installation steps (downloads, clones, extractions) run as tokio tasks with dependencies.
+///
+/// A task starts once all its dependencies completed, with at most `max_parallel` tasks
+/// running at a time. The first failing task stops the graph: no new task is started,
+/// the running async tasks are aborted and its error is returned unchanged, so callers can
+/// still classify it. Blocking tasks can't be aborted, they get the stop flag of the graph
+/// to poll instead, and are waited for before the error is returned, so the caller rolls
+/// back only once nothing writes anymore.
+pub struct TaskGraph {
+    name: String,
+    max_parallel: usize,
+    tasks: Vec<Task>,
+    stop: Arc<AtomicBool>,
+}
+
+impl TaskGraph {
+    pub fn new(name: &str, max_parallel: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            max_parallel: max_parallel.max(1),
+            tasks: Vec::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn push_task(&mut self, id: &str, dependencies: &[&str], run: TaskFn) {
+        self.tasks.push(Task {
+            id: id.to_string(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            run,
+        });
+    }
+
+    /// Adds an async task which starts after the tasks in `dependencies` completed.
+    pub fn add_task<F, Fut>(&mut self, id: &str, dependencies: &[&str], run: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let stop = self.stop.clone();
+        self.push_task(
+            id,
+            dependencies,
+            Box::new(move || {
+                Box::pin(async move {
+                    tokio::select! {
+                        result = run() => result,
+                        _ = stopped(&stop) => Err(anyhow!("Stopped after another task failed")),
+                    }
+                })
+            }),
+        );
+    }
+
+    /// Adds a task doing blocking work (extraction, hashing, git), run on the blocking thread pool.
+    ///
+    /// `run` gets the stop flag of the graph, set when another task failed, and should return
+    /// early once it is set.
+    pub fn add_blocking_task<F>(&mut self, id: &str, dependencies: &[&str], run: F)
+    where
+        F: FnOnce(&AtomicBool) -> Result<()> + Send + 'static,
+    {
+        let task_id = id.to_string();
+        let stop = self.stop.clone();
+        self.push_task(
+            id,
+            dependencies,
+            Box::new(move || {
+                Box::pin(async move {
+                    tokio::task::spawn_blocking(move || run(&stop))
+                        .await
+                        .map_err(|e| anyhow!("Task '{}' panicked: {}", task_id, e))?
+                })
+            }),
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Checks that task ids are unique, dependencies exist and there is no cycle.
+    fn validate(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        for task in &self.tasks {
+            if !ids.insert(task.id.as_str()) {
+                return Err(anyhow!("Duplicate task '{}' in {}", task.id, self.name));
+            }
+        }
+        for task in &self.tasks {
+            if let Some(missing) = task.dependencies.iter().find(|d| !ids.contains(d.as_str())) {
+                return Err(anyhow!(
+                    "Task '{}' in {} depends on unknown task '{}'",
+                    task.id,
+                    self.name,
+                    missing
+                ));
+            }
+        }
+        // Kahn's algorithm, every task is reached only if the graph is acyclic
+        let mut remaining: HashMap<&str, usize> = self
+            .tasks
+            .iter()
+            .map(|t| (t.id.as_str(), t.dependencies.len()))
+            .collect();
+        let mut ready: Vec<&str> = self
+            .tasks
+            .iter()
+            .filter(|t| t.dependencies.is_empty())
+            .map(|t| t.id.as_str())
+            .collect();
+        let mut reached = 0;
+        while let Some(id) = ready.pop() {
+            reached += 1;
+            for task in self
+                .tasks
+                .iter()
+                .filter(|t| t.dependencies.iter().any(|d| d == id))
+            {
+                let count = remaining.get_mut(task.id.as_str()).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(task.id.as_str());
+                }
+            }
+        }
+        if reached != self.tasks.len() {
+            return Err(anyhow!("Tasks of {} have a dependency cycle", self.name));
+        }
+        Ok(())
+    }
+
+    /// Runs all tasks and returns their ids in the order they completed.
+    pub async fn run(self) -> Result<Vec<String>> {
+        self.validate()?;
+        let name = self.name;
+        let stop = self.stop;
+        let mut remaining: HashMap<String, usize> = HashMap::new();
+        let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+        let mut pending: HashMap<String, TaskFn> = HashMap::new();
+        let mut ready: VecDeque<String> = VecDeque::new();
+        for task in self.tasks {
+            for dependency in &task.dependencies {
+                dependents
+                    .entry(dependency.clone())
+                    .or_default()
+                    .push(task.id.clone());
+            }
+            if task.dependencies.is_empty() {
+                ready.push_back(task.id.clone());
+            }
+            remaining.insert(task.id.clone(), task.dependencies.len());
+            pending.insert(task.id, task.run);
+        }
+
+        let mut running = JoinSet::new();
+        let mut completed = Vec::new();
+        loop {
+            while running.len() < self.max_parallel {
+                let Some(id) = ready.pop_front() else { break };
+                let run = pending.remove(&id).unwrap();
+                debug!("{}: starting {}", name, id);
+                running.spawn(async move { (id, run().await) });
+            }
+            let Some(joined) = running.join_next().await else {
+                break;
+            };
+            let (id, result) = match joined {
+                Ok((id, result)) => (id, result),
+                Err(e) => (String::new(), Err(anyhow!("A task of {} panicked: {}", name, e))),
+            };
+            if let Err(e) = result {
+                error!("{}: {} failed: {}", name, id, e);
+                stop.store(true, Ordering::SeqCst);
+                // the async tasks end at the stop flag, the blocking ones at their next check of it
+                while running.join_next().await.is_some() {}
+                return Err(e);
+            }
+            debug!("{}: {} completed", name, id);
+            for dependent in dependents.remove(&id).unwrap_or_default() {
+                let count = remaining.get_mut(&dependent).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push_back(dependent);
+                }
+            }
+            completed.push(id);
+        }
+        Ok(completed)
+    }
+
+    /// Runs the graph from synchronous code, on a runtime of its own, so it works the same
+    /// on threads of a tokio runtime and outside of one.
+    pub fn run_blocking(self) -> Result<Vec<String>> {
+        std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?
+                .block_on(self.run())
+        })
+        .join()
+        .map_err(|_| anyhow!("A task graph panicked"))?
+    }
+}
+
+/// Waits until `stop` is set, to race the async tasks with.
+async fn stopped(stop: &AtomicBool) {
+    while !stop.load(Ordering::SeqCst) {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_tasks_run_after_their_dependencies() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut graph = TaskGraph::new("test", 4);
+        for (id, deps) in [
+            ("extract", vec!["download"]),
+            ("download", vec![]),
+            ("export", vec!["extract", "clone"]),
+            ("clone", vec![]),
+        ] {
+            let log = log.clone();
+            graph.add_blocking_task(id, &deps, move |_| {
+                log.lock().unwrap().push(id);
+                Ok(())
+            });
+        }
+        let completed = graph.run().await.unwrap();
+        assert_eq!(completed.len(), 4);
+        let log = log.lock().unwrap();
+        let position = |id| log.iter().position(|l| *l == id).unwrap();
+        assert!(position("download") < position("extract"));
+        assert!(position("extract") < position("export"));
+        assert!(position("clone") < position("export"));
+    }
+
+    #[tokio::test]
+    async fn test_failure_skips_dependents() {
+        let ran = Arc::new(Mutex::new(false));
+        let mut graph = TaskGraph::new("test", 2);
+        graph.add_task("download", &[], || async { Err(anyhow!("network down")) });
+        let ran_clone = ran.clone();
+        graph.add_blocking_task("extract", &["download"], move |_| {
+            *ran_clone.lock().unwrap() = true;
+            Ok(())
+        });
+        let err = graph.run().await.unwrap_err();
+        assert_eq!(err.to_string(), "network down");
+        assert!(!*ran.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_failure_waits_for_blocking_tasks() {
+        let finished = Arc::new(Mutex::new(false));
+        let mut graph = TaskGraph::new("test", 2);
+        let finished_clone = finished.clone();
+        graph.add_blocking_task("extract", &[], move |stop| {
+            while !stop.load(Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            *finished_clone.lock().unwrap() = true;
+            Ok(())
+        });
+        graph.add_task("download", &[], || async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Err(anyhow!("network down"))
+        });
+        let err = graph.run().await.unwrap_err();
+        assert_eq!(err.to_string(), "network down");
+        assert!(*finished.lock().unwrap());
+    }
+
+    #[test]
+    fn test_run_blocking() {
+        let mut graph = TaskGraph::new("test", 1);
+        graph.add_blocking_task("clone", &[], |_| Ok(()));
+        graph.add_task("submodules", &["clone"], || async { Ok(()) });
+        assert_eq!(graph.run_blocking().unwrap(), vec!["clone", "submodules"]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_graphs_are_rejected() {
+        let mut graph = TaskGraph::new("test", 1);
+        graph.add_task("a", &["b"], || async { Ok(()) });
+        graph.add_task("b", &["a"], || async { Ok(()) });
+        assert!(graph.run().await.is_err());
+
+        let mut graph = TaskGraph::new("test", 1);
+        graph.add_task("a", &["missing"], || async { Ok(()) });
+        assert!(graph.run().await.is_err());
+    }
+}
@@ -9,7 +9,7 @@ static CLIENT: Lazy<Option<reqwest::Client>> = Lazy::new(|| {
     if CONNECTION_STRING.is_none() {
         return None;
     }
-    reqwest::Client::builder()
+    crate::http_client::client_builder()
         .timeout(Duration::from_secs(5))
         .build()
         .ok()
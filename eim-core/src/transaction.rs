@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Result};
+use log::{debug, error, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::utils::remove_directory_all;
+
+const STAGING_SUFFIX: &str = "eim-staging";
+const BACKUP_SUFFIX: &str = "eim-backup";
+
+enum UndoAction {
+    /// Remove a path created during the transaction
+    RemovePath(PathBuf),
+    /// Move a backed up path back to its original location
+    RestoreBackup { backup: PathBuf, original: PathBuf },
+    Custom(String, Box<dyn FnOnce() -> Result<()> + Send>),
+}
+
+/// Groups installation steps so that a failure leaves either the previous state or nothing.
+///
+/// Every step registers how to undo itself. Directories are populated in a staging
+/// location next to their final path and moved into place with an atomic rename, so
+/// a half-extracted toolchain is never visible under the final path. If the transaction
+/// is dropped without `commit`, all registered undo actions run in reverse order.
+pub struct InstallTransaction {
+    name: String,
+    undo_actions: Vec<UndoAction>,
+    committed: bool,
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Path {} has no file name", path.display()))?
+        .to_string_lossy();
+    let parent = path
+        .parent()
+        .ok_or_else(|| anyhow!("Path {} has no parent directory", path.display()))?;
+    Ok(parent.join(format!(".{}.{}", file_name, suffix)))
+}
+
+impl InstallTransaction {
+    pub fn new(name: &str) -> Self {
+        debug!("Starting install transaction '{}'", name);
+        Self {
+            name: name.to_string(),
+            undo_actions: Vec::new(),
+            committed: false,
+        }
+    }
+
+    /// Registers a path created by this transaction, to be removed on rollback.
+    pub fn register_created_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.undo_actions
+            .push(UndoAction::RemovePath(path.as_ref().to_path_buf()));
+    }
+
+    /// Registers an arbitrary undo action.
+    pub fn register_undo<F>(&mut self, description: &str, action: F)
+    where
+        F: FnOnce() -> Result<()> + Send + 'static,
+    {
+        self.undo_actions
+            .push(UndoAction::Custom(description.to_string(), Box::new(action)));
+    }
+
+    /// Creates an empty staging directory for content which will end up in `final_path`.
+    ///
+    /// The staging directory is a sibling of `final_path`, so the later rename stays
+    /// on the same filesystem and is atomic.
+    pub fn stage_dir<P: AsRef<Path>>(&mut self, final_path: P) -> Result<PathBuf> {
+        let final_path = final_path.as_ref();
+        let staging = sibling_path(final_path, STAGING_SUFFIX)?;
+        if staging.exists() {
+            // left over from an interrupted run
+            remove_directory_all(&staging)?;
+        }
+        fs::create_dir_all(&staging)?;
+        self.register_created_path(&staging);
+        Ok(staging)
+    }
+
+    /// Moves a fully populated staging directory to `final_path`.
+    ///
+    /// An existing `final_path` is kept as a backup until the transaction commits,
+    /// so it can be restored on rollback.
+    pub fn commit_dir<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, staging: P, final_path: Q) -> Result<()> {
+        let staging = staging.as_ref();
+        let final_path = final_path.as_ref();
+        if let Some(parent) = final_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if final_path.exists() {
+            let backup = sibling_path(final_path, BACKUP_SUFFIX)?;
+            if backup.exists() {
+                remove_directory_all(&backup)?;
+            }
+            fs::rename(final_path, &backup)?;
+            self.undo_actions.push(UndoAction::RestoreBackup {
+                backup,
+                original: final_path.to_path_buf(),
+            });
+        }
+        fs::rename(staging, final_path)?;
+        self.register_created_path(final_path);
+        debug!("Committed {} to {}", staging.display(), final_path.display());
+        Ok(())
+    }
+
+    /// Marks the transaction as successful and deletes the backups of replaced paths.
+    pub fn commit(mut self) {
+        for action in self.undo_actions.drain(..) {
+            if let UndoAction::RestoreBackup { backup, .. } = action {
+                if let Err(err) = remove_directory_all(&backup) {
+                    warn!("Failed to remove backup {}: {}", backup.display(), err);
+                }
+            }
+        }
+        self.committed = true;
+        debug!("Install transaction '{}' committed", self.name);
+    }
+
+    /// Runs all registered undo actions in reverse order.
+    pub fn rollback(&mut self) {
+        if self.undo_actions.is_empty() {
+            return;
+        }
+        info!("Rolling back '{}'", self.name);
+        while let Some(action) = self.undo_actions.pop() {
+            let result = match action {
+                UndoAction::RemovePath(path) => {
+                    if path.exists() {
+                        remove_directory_all(&path).map_err(|e| anyhow!(e))
+                    } else {
+                        Ok(())
+                    }
+                }
+                UndoAction::RestoreBackup { backup, original } => {
+                    if original.exists() {
+                        let _ = remove_directory_all(&original);
+                    }
+                    fs::rename(&backup, &original).map_err(|e| anyhow!(e))
+                }
+                UndoAction::Custom(description, action) => {
+                    debug!("Undoing: {}", description);
+                    action()
+                }
+            };
+            if let Err(err) = result {
+                error!("Rollback step of '{}' failed: {}", self.name, err);
+            }
+        }
+    }
+}
+
+impl Drop for InstallTransaction {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.rollback();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rollback_restores_previous_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let final_path = temp_dir.path().join("openocd");
+        fs::create_dir_all(&final_path).unwrap();
+        fs::write(final_path.join("old"), "old").unwrap();
+
+        {
+            let mut transaction = InstallTransaction::new("test");
+            let staging = transaction.stage_dir(&final_path).unwrap();
+            fs::write(staging.join("new"), "new").unwrap();
+            transaction.commit_dir(&staging, &final_path).unwrap();
+            assert!(final_path.join("new").exists());
+            // dropped without commit
+        }
+
+        assert!(final_path.join("old").exists());
+        assert!(!final_path.join("new").exists());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_commit_removes_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let final_path = temp_dir.path().join("cmake");
+        fs::create_dir_all(&final_path).unwrap();
+
+        let mut transaction = InstallTransaction::new("test");
+        let staging = transaction.stage_dir(&final_path).unwrap();
+        fs::write(staging.join("cmake"), "bin").unwrap();
+        transaction.commit_dir(&staging, &final_path).unwrap();
+        transaction.commit();
+
+        assert!(final_path.join("cmake").exists());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_rollback_removes_created_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let created = temp_dir.path().join("v5.4");
+        fs::create_dir_all(created.join("esp-idf")).unwrap();
+        let mut transaction = InstallTransaction::new("test");
+        transaction.register_created_path(&created);
+        drop(transaction);
+        assert!(!created.exists());
+    }
+}
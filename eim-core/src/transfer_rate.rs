@@ -0,0 +1,134 @@
+use rust_i18n::t;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::display_format::{format_eta, format_speed};
+
+/// Span the speed is averaged over, long enough to smooth out bursts of a slow connection
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(5);
+
+/// Speed and time left of a download or an extraction, sent with the progress events.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct TransferStats {
+    pub bytes_per_second: u64,
+    /// `None` while the total is unknown or nothing has moved yet
+    pub eta_seconds: Option<u64>,
+}
+
+/// Rolling average of the transfer speed over the last `window`.
+///
+/// Fed with the byte count reported by the progress callbacks, which is what both the
+/// downloads and the extractions report.
+#[derive(Debug, Clone)]
+pub struct TransferRate {
+    window: Duration,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl Default for TransferRate {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+impl TransferRate {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Forgets the samples, for the next file.
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Records that `done` bytes are transferred and returns the stats towards `total`.
+    pub fn update(&mut self, done: u64, total: u64) -> Option<TransferStats> {
+        self.record_at(done, Instant::now());
+        self.stats(done, total)
+    }
+
+    pub fn record_at(&mut self, done: u64, now: Instant) {
+        // a smaller count means a new file or a restarted download
+        if self.samples.back().is_some_and(|(_, last)| done < *last) {
+            self.samples.clear();
+        }
+        self.samples.push_back((now, done));
+        // one sample older than the window is kept, so the window is always covered
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Bytes per second over the window, `None` until two samples are apart in time.
+    pub fn bytes_per_second(&self) -> Option<f64> {
+        let (first_time, first_done) = self.samples.front()?;
+        let (last_time, last_done) = self.samples.back()?;
+        let elapsed = last_time.duration_since(*first_time).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        Some(last_done.saturating_sub(*first_done) as f64 / elapsed)
+    }
+
+    pub fn eta(&self, done: u64, total: u64) -> Option<Duration> {
+        let speed = self.bytes_per_second()?;
+        if total == 0 || speed <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(total.saturating_sub(done) as f64 / speed))
+    }
+
+    pub fn stats(&self, done: u64, total: u64) -> Option<TransferStats> {
+        let speed = self.bytes_per_second()?;
+        Some(TransferStats {
+            bytes_per_second: speed as u64,
+            eta_seconds: self.eta(done, total).map(|eta| eta.as_secs()),
+        })
+    }
+}
+
+impl std::fmt::Display for TransferStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let speed = format_speed(self.bytes_per_second);
+        match self.eta_seconds {
+            Some(eta) => write!(f, "{}", t!("format.transfer.speed_and_eta", speed = speed, eta = format_eta(eta))),
+            None => write!(f, "{}", speed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_average() {
+        let start = Instant::now();
+        let mut rate = TransferRate::new(Duration::from_secs(2));
+        rate.record_at(0, start);
+        assert_eq!(rate.bytes_per_second(), None);
+        rate.record_at(1_000, start + Duration::from_secs(1));
+        rate.record_at(2_000, start + Duration::from_secs(2));
+        // the first second was slow, it falls out of the window
+        rate.record_at(6_000, start + Duration::from_secs(3));
+        rate.record_at(10_000, start + Duration::from_secs(4));
+        assert_eq!(rate.bytes_per_second(), Some(4_000.0));
+        assert_eq!(rate.eta(10_000, 30_000), Some(Duration::from_secs(5)));
+
+        rate.record_at(500, start + Duration::from_secs(5));
+        assert_eq!(rate.bytes_per_second(), None);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_eta(42), "42s");
+        assert_eq!(format_eta(245), "4m 05s");
+        assert_eq!(format_eta(7_500), "2h 05m");
+        let stats = TransferStats { bytes_per_second: 2_000_000, eta_seconds: Some(90) };
+        assert_eq!(stats.to_string(), "2.0 MB/s, 1m 30s left");
+    }
+}
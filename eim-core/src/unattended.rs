@@ -0,0 +1,187 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use log::{info, warn};
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::errors::{EimError, ErrorKind};
+use crate::idf_config::IDF_CONFIG_FILE_NAME;
+use crate::settings::Settings;
+
+/// Name of the failure summary, stable so CI jobs can pick it up without globbing
+pub const FAILURE_SUMMARY_FILE_NAME: &str = "eim_failure.json";
+
+/// Parses a duration like `90s`, `40m`, `2h` or `1h30m`.
+pub fn parse_duration(value: &str) -> std::result::Result<Duration, String> {
+    let value = value.trim();
+    let mut seconds = 0u64;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            _ => return Err(format!("Unknown unit '{}' in duration {}, use s, m or h", c, value)),
+        };
+        let amount: u64 = number
+            .parse()
+            .map_err(|_| format!("Missing number before '{}' in duration {}", c, value))?;
+        seconds += amount * unit;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(format!("Missing unit after {} in duration {}, e.g. {}m", number, value, number));
+    }
+    if seconds == 0 {
+        return Err(format!("Duration {} must be longer than zero", value));
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Machine-readable outcome of a failed unattended installation.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailureSummary {
+    pub eim_version: String,
+    pub os: String,
+    pub arch: String,
+    pub finished: String,
+    pub kind: ErrorKind,
+    pub exit_code: i32,
+    pub message: String,
+    /// The installation was stopped because it exceeded `max_duration_seconds`
+    pub timed_out: bool,
+    pub max_duration_seconds: Option<u64>,
+    pub elapsed_seconds: u64,
+    /// Stage the installation was in when it failed, e.g. `tools`
+    pub stage: Option<String>,
+    pub version: Option<String>,
+    pub run_log: Option<PathBuf>,
+    pub support_bundle: Option<PathBuf>,
+}
+
+impl FailureSummary {
+    pub fn new(error: &EimError, timed_out: bool, max_duration: Option<Duration>, elapsed: Duration) -> Self {
+        let (stage, version) = match crate::metrics::last_stage() {
+            Some((stage, version)) => (Some(stage), version),
+            None => (None, None),
+        };
+        Self {
+            eim_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            finished: Utc::now().to_rfc3339(),
+            kind: error.kind,
+            exit_code: error.exit_code(),
+            message: error.message.clone(),
+            timed_out,
+            max_duration_seconds: max_duration.map(|duration| duration.as_secs()),
+            elapsed_seconds: elapsed.as_secs(),
+            stage,
+            version,
+            run_log: crate::logging::current_run_log(),
+            support_bundle: None,
+        }
+    }
+}
+
+pub(crate) fn add_file<W: Write + std::io::Seek>(zip: &mut zip::ZipWriter<W>, name: &str, path: &Path) -> Result<()> {
+    let Ok(content) = fs::read(path) else {
+        return Ok(());
+    };
+    add_bytes(zip, name, &content)
+}
+
+pub(crate) fn add_bytes<W: Write + std::io::Seek>(zip: &mut zip::ZipWriter<W>, name: &str, content: &[u8]) -> Result<()> {
+    let options = zip::write::FileOptions::<()>::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file(name, options)?;
+    zip.write_all(content)?;
+    Ok(())
+}
+
+/// Writes a zip with everything needed to report the failure: the summary, the logs, the last
+/// install report, `eim_idf.json` and the status of eim with the proxy password redacted.
+pub fn write_support_bundle(path: &Path, summary: &FailureSummary, settings: &Settings) -> Result<()> {
+    let mut zip = zip::ZipWriter::new(fs::File::create(path)?);
+    add_bytes(&mut zip, "failure.json", &serde_json::to_vec_pretty(summary)?)?;
+    add_bytes(
+        &mut zip,
+        "status.json",
+        &serde_json::to_vec_pretty(&crate::status::collect(settings))?,
+    )?;
+    if let Some(run_log) = &summary.run_log {
+        add_file(&mut zip, "run.log", run_log)?;
+    }
+    if let Some(log_dir) = crate::get_log_directory() {
+        add_file(&mut zip, crate::logging::CLI_LOG_FILE_NAME, &log_dir.join(crate::logging::CLI_LOG_FILE_NAME))?;
+    }
+    add_file(
+        &mut zip,
+        "install_report.json",
+        &crate::install_report::get_reports_directory().join("latest.json"),
+    )?;
+    let esp_idf_json = PathBuf::from(settings.esp_idf_json_path.clone().unwrap_or_default()).join(IDF_CONFIG_FILE_NAME);
+    add_file(&mut zip, IDF_CONFIG_FILE_NAME, &esp_idf_json)?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// Writes the support bundle and the failure summary into `dir`, returning the summary path.
+///
+/// The bundle is best effort: when it can't be written the summary is still written without it.
+pub fn write_failure_artifacts(dir: &Path, mut summary: FailureSummary, settings: &Settings) -> Result<PathBuf> {
+    fs::create_dir_all(dir).map_err(|err| anyhow!("Failed to create {}: {}", dir.display(), err))?;
+    let bundle = dir.join(format!("eim_support_{}.zip", Utc::now().format("%Y%m%dT%H%M%SZ")));
+    match write_support_bundle(&bundle, &summary, settings) {
+        Ok(()) => {
+            info!("Support bundle written to {}", bundle.display());
+            summary.support_bundle = Some(bundle);
+        }
+        Err(err) => warn!("Failed to write the support bundle {}: {}", bundle.display(), err),
+    }
+    let path = dir.join(FAILURE_SUMMARY_FILE_NAME);
+    fs::write(&path, serde_json::to_string_pretty(&summary)?)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("40m"), Ok(Duration::from_secs(2400)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert!(parse_duration("40").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("2d").is_err());
+        assert!(parse_duration("0m").is_err());
+    }
+
+    #[test]
+    fn test_write_failure_artifacts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let error = EimError::new(ErrorKind::TimedOut, "The installation did not finish within 40m");
+        let summary = FailureSummary::new(&error, true, Some(Duration::from_secs(2400)), Duration::from_secs(2400));
+        let settings = Settings {
+            esp_idf_json_path: Some(dir.path().to_string_lossy().into_owned()),
+            ..Settings::default()
+        };
+
+        let path = write_failure_artifacts(dir.path(), summary, &settings).unwrap();
+        let written: serde_json::Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(written["kind"], "timed_out");
+        assert_eq!(written["exit_code"], 19);
+        let bundle = PathBuf::from(written["support_bundle"].as_str().unwrap());
+        let archive = zip::ZipArchive::new(fs::File::open(bundle).unwrap()).unwrap();
+        assert!(archive.file_names().any(|name| name == "failure.json"));
+        assert!(archive.file_names().any(|name| name == "status.json"));
+    }
+}
@@ -0,0 +1,251 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::idf_config::IdfInstallation;
+use crate::idf_versions::{get_idf_versions, parse_version_name, Releases};
+use crate::settings::Settings;
+
+/// Muted versions and the result of the last check, next to `eim_idf.json`
+pub const UPDATE_NOTIFICATIONS_FILE_NAME: &str = "eim_update_notifications.json";
+/// The release feed is checked in the background at most this often
+const CHECK_INTERVAL_HOURS: i64 = 24;
+
+/// A point release of the same release series superseding an installed version.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+pub struct PatchUpdate {
+    /// Id of the installation in eim_idf.json
+    pub installation_id: String,
+    pub installation_name: String,
+    /// Installed version, e.g. `v5.3.1`
+    pub installed: String,
+    /// Newest point release of the series, e.g. `v5.3.3`
+    pub available: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NotificationState {
+    /// Set to false by `eim check-updates --disable` to stop the background check
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Available versions the user doesn't want to hear about anymore
+    #[serde(default)]
+    pub muted: Vec<String>,
+    #[serde(default)]
+    pub last_check: Option<DateTime<Utc>>,
+    /// Updates found by the last check, before muting
+    #[serde(default)]
+    pub updates: Vec<PatchUpdate>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Default for NotificationState {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            muted: Vec::new(),
+            last_check: None,
+            updates: Vec::new(),
+        }
+    }
+}
+
+impl NotificationState {
+    /// Updates of the last check which are not muted
+    pub fn pending(&self) -> Vec<PatchUpdate> {
+        self.updates
+            .iter()
+            .filter(|update| !self.muted.contains(&update.available))
+            .cloned()
+            .collect()
+    }
+
+    fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        self.last_check
+            .is_none_or(|checked| now - checked > Duration::hours(CHECK_INTERVAL_HOURS))
+    }
+}
+
+pub fn get_state_path() -> PathBuf {
+    PathBuf::from(Settings::default().esp_idf_json_path.unwrap_or_default()).join(UPDATE_NOTIFICATIONS_FILE_NAME)
+}
+
+/// Returns the stored state, the default one if there is none yet.
+pub fn load_state() -> Result<NotificationState> {
+    let path = get_state_path();
+    if !path.exists() {
+        return Ok(NotificationState::default());
+    }
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+}
+
+fn save_state(state: &NotificationState) -> Result<()> {
+    let path = get_state_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(state)?)?;
+    Ok(())
+}
+
+/// Version an installation was installed as: the one it is a named copy of, the one in its
+/// manifest, or its name.
+fn installed_version(installation: &IdfInstallation) -> String {
+    if let Some(idf_version) = &installation.idf_version {
+        return idf_version.clone();
+    }
+    crate::install_manifest::load_manifest_for(&installation.path)
+        .map(|manifest| manifest.idf_version)
+        .unwrap_or_else(|| installation.name.clone())
+}
+
+/// Finds the installations for which `releases` has a newer point release in the same release series.
+///
+/// Installations of branches or commits (`master`, `release/v5.3`) are skipped, they are updated with git.
+pub fn find_patch_updates(installations: &[(IdfInstallation, String)], releases: &Releases) -> Vec<PatchUpdate> {
+    let mut updates = Vec::new();
+    for (installation, installed) in installations {
+        let Some(current) = parse_version_name(installed) else {
+            debug!("Skipping {}, {} is not a release", installation.name, installed);
+            continue;
+        };
+        let newest = releases
+            .VERSIONS
+            .iter()
+            .filter(|v| !v.pre_release && v.name != "latest")
+            .filter_map(|v| parse_version_name(&v.name).map(|parsed| (parsed, &v.name)))
+            .filter(|(parsed, _)| parsed.major == current.major && parsed.minor == current.minor && *parsed > current)
+            .max_by(|a, b| a.0.cmp(&b.0));
+        if let Some((_, available)) = newest {
+            updates.push(PatchUpdate {
+                installation_id: installation.id.clone(),
+                installation_name: installation.name.clone(),
+                installed: installed.clone(),
+                available: available.clone(),
+            });
+        }
+    }
+    updates
+}
+
+/// Checks the release feed for point releases of the installed versions and stores the result.
+///
+/// Returns the updates which are not muted.
+pub async fn check_for_updates() -> Result<Vec<PatchUpdate>> {
+    let installations: Vec<_> = crate::version_manager::get_esp_ide_config()
+        .map(|config| config.idf_installed)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|installation| {
+            let installed = installed_version(&installation);
+            (installation, installed)
+        })
+        .collect();
+    let releases = get_idf_versions().await.map_err(|e| anyhow!(e))?;
+    let mut state = load_state()?;
+    state.updates = find_patch_updates(&installations, &releases);
+    state.last_check = Some(Utc::now());
+    save_state(&state)?;
+    Ok(state.pending())
+}
+
+/// The updates to notify about: refreshed when the last check is older than a day, taken from
+/// the last check otherwise. Empty when notifications are disabled.
+pub async fn pending_updates() -> Result<Vec<PatchUpdate>> {
+    let state = load_state()?;
+    if !state.enabled {
+        return Ok(Vec::new());
+    }
+    if state.is_stale(Utc::now()) {
+        return check_for_updates().await;
+    }
+    Ok(state.pending())
+}
+
+/// Stops notifying about `version`; a later point release is notified again.
+pub fn mute(version: &str) -> Result<()> {
+    let mut state = load_state()?;
+    if !state.muted.iter().any(|muted| muted == version) {
+        state.muted.push(version.to_string());
+    }
+    save_state(&state)
+}
+
+pub fn unmute(version: &str) -> Result<()> {
+    let mut state = load_state()?;
+    state.muted.retain(|muted| muted != version);
+    save_state(&state)
+}
+
+/// Turns the background check on or off; `eim check-updates` keeps working either way.
+pub fn set_enabled(enabled: bool) -> Result<()> {
+    let mut state = load_state()?;
+    state.enabled = enabled;
+    save_state(&state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idf_config::test_installation;
+    use crate::idf_versions::Version;
+    use std::collections::HashMap;
+
+    fn release(name: &str, pre_release: bool) -> Version {
+        Version {
+            name: name.to_string(),
+            pre_release,
+            old: false,
+            end_of_life: false,
+            has_targets: false,
+            supported_targets: Vec::new(),
+        }
+    }
+
+    fn installation(name: &str) -> (IdfInstallation, String) {
+        (test_installation(name), name.to_string())
+    }
+
+    #[test]
+    fn test_find_patch_updates() {
+        let releases = Releases {
+            VERSIONS: vec![
+                release("latest", false),
+                release("v6.0-beta1", true),
+                release("v5.4", false),
+                release("v5.3.3", false),
+                release("v5.3.2", false),
+                release("v5.3.4-rc1", true),
+                release("v5.2.5", false),
+            ],
+            IDF_TARGETS: Vec::new(),
+            RELEASES: HashMap::new(),
+        };
+        let installations = vec![
+            installation("v5.3.1"),
+            installation("v5.2.5"),
+            installation("master"),
+            installation("v5.4"),
+        ];
+
+        let updates = find_patch_updates(&installations, &releases);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].installation_name, "v5.3.1");
+        assert_eq!(updates[0].available, "v5.3.3");
+
+        let state = NotificationState {
+            muted: vec!["v5.3.3".to_string()],
+            updates,
+            ..Default::default()
+        };
+        assert!(state.pending().is_empty());
+    }
+}
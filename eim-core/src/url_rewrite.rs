@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+use crate::settings::Settings;
+
+/// Index pip uses when no `pypi_mirror` is set
+pub const DEFAULT_PYPI_INDEX: &str = "https://pypi.org/simple";
+
+/// A `[[url_rewrites]]` rule of the configuration: a URL matching the regular expression `pattern`
+/// is replaced by `replacement`, in which `$1` or `${name}` insert the groups of the pattern.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UrlRewriteRule {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+static RULES: Lazy<RwLock<Vec<(Regex, String)>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Compiles the patterns of `rules`, failing on the first invalid one.
+pub fn compile(rules: &[UrlRewriteRule]) -> Result<Vec<(Regex, String)>> {
+    rules
+        .iter()
+        .map(|rule| {
+            Regex::new(&rule.pattern)
+                .map(|regex| (regex, rule.replacement.clone()))
+                .map_err(|e| anyhow!("Invalid URL rewrite pattern '{}': {}", rule.pattern, e))
+        })
+        .collect()
+}
+
+/// Applies the `url_rewrites` setting; invalid rules are skipped with a warning.
+pub fn configure_from_settings(settings: &Settings) {
+    let rules = settings
+        .url_rewrites
+        .iter()
+        .flatten()
+        .filter_map(|rule| match compile(std::slice::from_ref(rule)) {
+            Ok(mut compiled) => compiled.pop(),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        })
+        .collect();
+    if let Ok(mut guard) = RULES.write() {
+        *guard = rules;
+    }
+}
+
+/// Applies the first of `rules` matching `url`, `None` if none does.
+fn rewrite_with(rules: &[(Regex, String)], url: &str) -> Option<String> {
+    rules
+        .iter()
+        .find(|(regex, _)| regex.is_match(url))
+        .map(|(regex, replacement)| regex.replace(url, replacement.as_str()).into_owned())
+}
+
+/// Returns `url` rewritten by the first matching `url_rewrites` rule, or unchanged.
+///
+/// Applied to every download: tool archives, ESP-IDF archives and manifests, the ESP-IDF
+/// repository and its submodules, and the Python package index.
+pub fn rewrite(url: &str) -> String {
+    let rules = RULES.read().map(|rules| rules.clone()).unwrap_or_default();
+    match rewrite_with(&rules, url) {
+        Some(rewritten) => {
+            debug!("Rewrote {} to {}", url, rewritten);
+            rewritten
+        }
+        None => url.to_string(),
+    }
+}
+
+/// The index pip uses: `mirror`, or PyPI, rewritten. `None` keeps the default of pip, when no
+/// mirror is set and no rule rewrites PyPI.
+pub fn pypi_index(mirror: Option<&str>) -> Option<String> {
+    match mirror {
+        Some(mirror) => Some(rewrite(mirror)),
+        None => Some(rewrite(DEFAULT_PYPI_INDEX)).filter(|index| index != DEFAULT_PYPI_INDEX),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, replacement: &str) -> UrlRewriteRule {
+        UrlRewriteRule {
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rewrite_with() {
+        let rules = compile(&[
+            rule(
+                r"^https://github\.com/espressif/(.*)$",
+                "https://nexus.example.com/repository/github-espressif/$1",
+            ),
+            rule(r"^https://github\.com/", "https://never.example.com/"),
+            rule(r"^https://pypi\.org/simple", "https://nexus.example.com/repository/pypi/simple"),
+        ])
+        .unwrap();
+        assert_eq!(
+            rewrite_with(&rules, "https://github.com/espressif/esp-idf.git").as_deref(),
+            Some("https://nexus.example.com/repository/github-espressif/esp-idf.git")
+        );
+        // the first matching rule wins
+        assert_eq!(
+            rewrite_with(&rules, "https://github.com/other/tool.tar.gz").as_deref(),
+            Some("https://never.example.com/other/tool.tar.gz")
+        );
+        assert_eq!(
+            rewrite_with(&rules, DEFAULT_PYPI_INDEX).as_deref(),
+            Some("https://nexus.example.com/repository/pypi/simple")
+        );
+        assert_eq!(rewrite_with(&rules, "https://dl.espressif.com/dl/file.zip"), None);
+    }
+
+    #[test]
+    fn test_compile_rejects_invalid_patterns() {
+        let error = compile(&[rule("^https://(github", "x")]).unwrap_err();
+        assert!(error.to_string().contains("^https://(github"));
+    }
+}
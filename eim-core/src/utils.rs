@@ -336,6 +336,53 @@ pub fn remove_directory_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
     Ok(())
 }
 
+/// Checks whether the current process runs with elevated (administrator) privileges.
+///
+/// On non-Windows platforms this always returns `Ok(false)` as elevation is handled
+/// by the system package manager (sudo) where needed.
+#[cfg(windows)]
+pub fn is_elevated() -> Result<bool> {
+    use std::mem;
+    use winapi::shared::minwindef::{DWORD, FALSE};
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
+    use winapi::um::securitybaseapi::GetTokenInformation;
+    use winapi::um::winnt::{TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+
+    unsafe {
+        let process = GetCurrentProcess();
+        let mut token = std::ptr::null_mut();
+
+        if OpenProcessToken(process, TOKEN_QUERY, &mut token) == FALSE {
+            return Err(anyhow!("Failed to open process token"));
+        }
+
+        let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+        let mut return_length: DWORD = 0;
+
+        let result = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut _ as *mut _,
+            mem::size_of::<TOKEN_ELEVATION>() as DWORD,
+            &mut return_length,
+        );
+
+        CloseHandle(token);
+
+        if result == FALSE {
+            return Err(anyhow!("Failed to get token information"));
+        }
+
+        Ok(elevation.TokenIsElevated != 0)
+    }
+}
+
+#[cfg(not(windows))]
+pub fn is_elevated() -> Result<bool> {
+    Ok(false)
+}
+
 /// Retry wrapper function that takes a closure and retries it according to the configuration
 pub fn with_retry<F, T, E>(f: F, max_retries: usize) -> Result<T, E>
 where
@@ -468,6 +515,8 @@ pub fn parse_tool_set_config(config_path: &str) -> Result<()> {
                 .unwrap()
                 .to_string(),
             idf_tools_path: new_idf_tools_path,
+            idf_version: None,
+            label: None,
         };
 
         current_config.idf_installed.push(installation);
@@ -686,7 +735,7 @@ pub fn extract_zst_archive_with_buffer_size(
     let mut archive = Archive::new(decoder);
 
     // Extract the archive directly from the stream
-    archive.unpack(extract_to)?;
+    crate::links::unpack_tar(&mut archive, extract_to)?;
 
     info!("Archive extracted successfully to: {:?}", extract_to);
     Ok(())
@@ -796,7 +845,7 @@ pub async fn measure_url_score_head(url: &str, timeout: Duration) -> Result<u32,
     let base_url = get_base_url(url);
 
     // Build the HTTP client
-    let client = reqwest::Client::builder()
+    let client = crate::http_client::client_builder()
         .timeout(timeout)
         .redirect(reqwest::redirect::Policy::limited(5))
         .build()
@@ -826,7 +875,7 @@ pub async fn measure_url_score_get(url: &str, timeout: Duration) -> Option<u32>
     let base_url = get_base_url(url)?;
 
     // Build the HTTP client
-    let client = reqwest::Client::builder()
+    let client = crate::http_client::client_builder()
         .timeout(timeout)
         .redirect(reqwest::redirect::Policy::limited(5))
         .build()
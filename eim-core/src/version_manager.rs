@@ -12,6 +12,8 @@ use lnk::ShellLink;
 use lnk::encoding::WINDOWS_1252;
 
 
+use crate::errors::{EimError, ErrorKind};
+use crate::install_manifest::{load_manifest_for, uninstall_using_manifest};
 use crate::utils::remove_directory_all;
 use crate::{
     idf_config::{IdfConfig, IdfInstallation},
@@ -89,6 +91,65 @@ pub fn get_selected_version() -> Option<IdfInstallation> {
     }
     None
 }
+/// Finds an installation by its id or name, or the selected one if `identifier` is `None`.
+pub fn find_installation(identifier: Option<&str>) -> Result<IdfInstallation> {
+    match identifier {
+        Some(identifier) => get_esp_ide_config()?
+            .idf_installed
+            .into_iter()
+            .find(|install| install.id == identifier || install.name == identifier)
+            .ok_or_else(|| EimError::new(ErrorKind::NotFound, format!("Version {} not found", identifier)).into()),
+        None => get_selected_version()
+            .ok_or_else(|| EimError::new(ErrorKind::NotFound, "No version selected").into()),
+    }
+}
+
+/// Checks the name given with `--version-name` before installing, e.g. `customer-a` for a second
+/// copy of v5.2 with its own checkout, Python environment and activation script.
+///
+/// The name is the folder of the installation, so it applies to a single version, and it must not
+/// be the name of another installation, which `eim select` and the other commands could not tell
+/// apart. Installing again under the same name into the same place repairs that installation.
+pub fn check_version_name(settings: &Settings) -> Result<()> {
+    let Some(name) = settings.version_name.as_deref() else {
+        return Ok(());
+    };
+    if name.trim().is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(EimError::new(
+            ErrorKind::Usage,
+            format!("'{}' can't be used as the name of an installation, it names its folder", name),
+        )
+        .into());
+    }
+    let versions = settings.idf_versions.clone().unwrap_or_default();
+    if versions.len() > 1 {
+        return Err(EimError::new(
+            ErrorKind::Usage,
+            format!("The name '{}' can only be given to a single version, not to {}", name, versions.join(", ")),
+        )
+        .into());
+    }
+    let Some(version) = versions.first() else {
+        return Ok(());
+    };
+    let idf_path = settings.get_version_paths(version)?.idf_path;
+    let installed = list_installed_versions().unwrap_or_default();
+    if let Some(existing) = installed
+        .iter()
+        .find(|installation| installation.name == name && Path::new(&installation.path) != idf_path)
+    {
+        return Err(EimError::new(
+            ErrorKind::Usage,
+            format!(
+                "An installation named '{}' already exists in {}, choose another name with --version-name",
+                name, existing.path
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
+
 /// Retrieves the ESP-IDF configuration from the default location.
 ///
 /// This function reads the ESP-IDF configuration from the default location specified by the
@@ -163,6 +224,27 @@ pub fn rename_idf_version(identifier: &str, new_name: String) -> Result<String>
     }
 }
 
+/// Sets the label of the specified ESP-IDF version in the configuration file, `None` removes it.
+///
+/// # Parameters
+///
+/// * `identifier` - The ID or the name of the installation.
+/// * `label` - The note to attach, e.g. the product the installation is kept for.
+pub fn set_installation_label(identifier: &str, label: Option<String>) -> Result<String> {
+    let config_path = get_default_config_path();
+    let mut ide_config = IdfConfig::from_file(&config_path)?;
+    let message = match &label {
+        Some(label) => format!("Version {} labeled \"{}\"", identifier, label),
+        None => format!("Label of version {} removed", identifier),
+    };
+    if ide_config.update_installation_label(identifier, label) {
+        ide_config.to_file(config_path, true, false)?;
+        Ok(message)
+    } else {
+        Err(anyhow!("Version {} not installed", identifier))
+    }
+}
+
 /// Searches the user's desktop for a shortcut whose arguments contain the specified custom profile filename.
 ///
 /// # Arguments
@@ -245,10 +327,24 @@ pub fn remove_single_idf_version(identifier: &str, keep_idf_folder: bool) -> Res
             )
         })?;
         if !keep_idf_folder {
-            match remove_directory_all(installation_folder) {
-                Ok(_) => {}
-                Err(e) => {
-                    return Err(anyhow!("Failed to remove installation folder: {}", e));
+            if load_manifest_for(&installation.path).is_some() {
+                // Only remove what the installer created, leaving user files in place
+                match uninstall_using_manifest(&installation.path) {
+                    Ok(kept) => {
+                        for path in kept {
+                            info!("Kept {} as it contains files not created by the installer", path);
+                        }
+                    }
+                    Err(e) => {
+                        return Err(anyhow!("Failed to uninstall using install manifest: {}", e));
+                    }
+                }
+            } else {
+                match remove_directory_all(installation_folder) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        return Err(anyhow!("Failed to remove installation folder: {}", e));
+                    }
                 }
             }
         }
@@ -265,6 +361,11 @@ pub fn remove_single_idf_version(identifier: &str, keep_idf_folder: bool) -> Res
             return Err(anyhow!("Failed to remove installation from config file"));
         }
         ide_config.to_file(config_path, true, false)?;
+        if !keep_idf_folder {
+            if let Err(e) = crate::version_tracking::untrack(&installation.id) {
+                warn!("Failed to stop tracking the version alias of {}: {}", installation.name, e);
+            }
+        }
         if std::env::consts::OS == "windows" {
             // On Windows, also remove the desktop icon associated with the installation
             match find_shortcut_by_profile(&installation.activation_script) {
@@ -332,6 +433,10 @@ pub async fn prepare_settings_for_fix_idf_installation(path_to_fix: PathBuf) ->
     info!("Fixing IDF installation at path: {}", path_to_fix.display());
     // The fix logic is just instalation with use of existing repository
     let mut version_name = None;
+    // Read before the version is removed, so repair does not bring back intentionally omitted components
+    let omitted_components = crate::install_manifest::load_manifest_for(path_to_fix.to_str().unwrap_or_default())
+        .map(|manifest| manifest.omitted_components)
+        .unwrap_or_default();
     match list_installed_versions() {
         Ok(versions) => {
             for v in versions {
@@ -361,6 +466,16 @@ pub async fn prepare_settings_for_fix_idf_installation(path_to_fix: PathBuf) ->
     settings.version_name = version_name;
     settings.install_all_prerequisites = Some(true);
     settings.config_file_save_path = None;
+    if !omitted_components.is_empty() {
+        settings.components = Some(
+            crate::components::OPTIONAL_COMPONENTS
+                .iter()
+                .map(|c| c.name().to_string())
+                .filter(|name| !omitted_components.contains(name))
+                .collect(),
+        );
+        crate::components::apply_to_settings(&mut settings)?;
+    }
     return Ok(settings);
 
 }
@@ -0,0 +1,64 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::idf_versions::VersionAlias;
+use crate::settings::Settings;
+
+/// Registry of the installations following a version alias, next to `eim_idf.json`
+pub const TRACKING_FILE_NAME: &str = "eim_version_tracking.json";
+
+/// An installation moved along a version alias by `eim upgrade`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct TrackedInstallation {
+    /// Id of the installation in eim_idf.json
+    pub installation_id: String,
+    /// `latest`, `stable` or `lts`
+    pub alias: String,
+}
+
+pub fn get_tracking_path() -> PathBuf {
+    PathBuf::from(Settings::default().esp_idf_json_path.unwrap_or_default()).join(TRACKING_FILE_NAME)
+}
+
+/// Returns the tracked installations, empty if none is tracked yet.
+pub fn list_tracked() -> Result<Vec<TrackedInstallation>> {
+    let path = get_tracking_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+}
+
+fn save_tracked(tracked: &[TrackedInstallation]) -> Result<()> {
+    let path = get_tracking_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(tracked)?)?;
+    Ok(())
+}
+
+/// Makes the installation follow `alias`, replacing the alias it followed before.
+pub fn track(installation_id: &str, alias: VersionAlias) -> Result<()> {
+    let mut tracked = list_tracked()?;
+    tracked.retain(|t| t.installation_id != installation_id);
+    tracked.push(TrackedInstallation {
+        installation_id: installation_id.to_string(),
+        alias: alias.name().to_string(),
+    });
+    save_tracked(&tracked)
+}
+
+/// Stops tracking the installation, e.g. because it was removed.
+pub fn untrack(installation_id: &str) -> Result<()> {
+    let mut tracked = list_tracked()?;
+    let count = tracked.len();
+    tracked.retain(|t| t.installation_id != installation_id);
+    if tracked.len() != count {
+        save_tracked(&tracked)?;
+    }
+    Ok(())
+}
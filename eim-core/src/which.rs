@@ -0,0 +1,127 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::idf_config::IdfInstallation;
+use crate::ide_export::InstallationEnvironment;
+
+/// Tools `eim which` shows without an argument
+pub const DEFAULT_TOOLS: &[&str] = &[
+    "idf.py",
+    "python",
+    "xtensa-esp32-elf-gcc",
+    "xtensa-esp32s2-elf-gcc",
+    "xtensa-esp32s3-elf-gcc",
+    "riscv32-esp-elf-gcc",
+    "openocd",
+];
+
+/// Where a tool is in an installation and where the shell finds it.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ToolLocation {
+    pub tool: String,
+    /// Path in the installation, `None` if the installation doesn't have the tool
+    pub installation: Option<PathBuf>,
+    /// Path the `PATH` of the current shell resolves the tool to
+    pub shell: Option<PathBuf>,
+}
+
+impl ToolLocation {
+    /// Whether the shell runs the tool of the installation
+    pub fn shell_matches(&self) -> bool {
+        match (&self.installation, &self.shell) {
+            (Some(installation), Some(shell)) => same_file(installation, shell),
+            _ => false,
+        }
+    }
+}
+
+/// Compares the paths with their folders resolved. The files themselves are not resolved: the
+/// Python of a virtual environment links to the interpreter it was created from.
+pub(crate) fn same_file(a: &Path, b: &Path) -> bool {
+    let resolve = |path: &Path| {
+        let parent = path.parent()?.canonicalize().ok()?;
+        Some(parent.join(path.file_name()?))
+    };
+    match (resolve(a), resolve(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// File names `name` may have on this platform, e.g. `openocd.exe` on Windows.
+fn executable_names(name: &str) -> Vec<String> {
+    if std::env::consts::OS != "windows" || Path::new(name).extension().is_some() {
+        return vec![name.to_string()];
+    }
+    let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".EXE;.BAT;.CMD".to_string());
+    extensions
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(|ext| format!("{}{}", name, ext.to_lowercase()))
+        .collect()
+}
+
+/// Looks up `name` in the directories of a `PATH` value, the way the shell does.
+pub fn find_in_path(name: &str, path_var: &OsStr) -> Option<PathBuf> {
+    let names = executable_names(name);
+    std::env::split_paths(path_var).find_map(|dir| {
+        names.iter().map(|n| dir.join(n)).find(|candidate| candidate.is_file())
+    })
+}
+
+/// Path of `tool` in the installation of `environment`, `None` if it doesn't have the tool.
+pub fn installation_copy(environment: &InstallationEnvironment, tool: &str) -> Option<PathBuf> {
+    match tool {
+        // idf.py is a script, not an executable with .exe on Windows
+        "idf.py" => Some(environment.idf_path.join("tools").join("idf.py")).filter(|p| p.is_file()),
+        "python" | "python3" => Some(environment.python.clone()).filter(|p| p.is_file()),
+        _ => environment.find_executable(tool),
+    }
+}
+
+/// Where `tool` is in the installation of `environment` and in the current shell.
+pub fn locate(environment: &InstallationEnvironment, tool: &str) -> ToolLocation {
+    let installation = installation_copy(environment, tool);
+    let shell = std::env::var_os("PATH").and_then(|path| find_in_path(tool, &path));
+    ToolLocation {
+        tool: tool.to_string(),
+        installation,
+        shell,
+    }
+}
+
+/// The installation the current shell uses, by `IDF_PATH` as set by the activation scripts,
+/// the selected one otherwise.
+pub fn active_installation() -> Result<IdfInstallation> {
+    if let Some(idf_path) = std::env::var_os("IDF_PATH") {
+        let idf_path = PathBuf::from(idf_path);
+        let active = crate::version_manager::get_esp_ide_config()?
+            .idf_installed
+            .into_iter()
+            .find(|installation| same_file(Path::new(&installation.path), &idf_path));
+        if let Some(installation) = active {
+            return Ok(installation);
+        }
+        log::debug!("IDF_PATH {} is not an installation of eim", idf_path.display());
+    }
+    crate::version_manager::find_installation(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_in_path() {
+        let first = tempfile::TempDir::new().unwrap();
+        let second = tempfile::TempDir::new().unwrap();
+        std::fs::write(second.path().join("openocd"), "").unwrap();
+        let path_var = std::env::join_paths([first.path(), second.path()]).unwrap();
+
+        assert_eq!(find_in_path("openocd", &path_var), Some(second.path().join("openocd")));
+        assert_eq!(find_in_path("idf.py", &path_var), None);
+    }
+}
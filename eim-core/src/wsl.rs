@@ -0,0 +1,194 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::errors::{EimError, ErrorKind};
+
+/// Script run inside the distro, installing the eim CLI first if it is not available there.
+const WSL_BOOTSTRAP_SCRIPT: &str = r#"set -e
+if ! command -v eim >/dev/null 2>&1; then
+    case "$(uname -m)" in
+        x86_64) ARCH_PATTERN="linux-x64" ;;
+        aarch64) ARCH_PATTERN="linux-aarch64" ;;
+        *) echo "Unsupported architecture: $(uname -m)" >&2; exit 1 ;;
+    esac
+    EIM_DOWNLOAD_URL=$(curl -fsSL https://api.github.com/repos/espressif/idf-im-ui/releases/latest \
+        | grep -o "https://[^\"]*eim-cli-$ARCH_PATTERN\.zip" | head -n 1)
+    if [ -z "$EIM_DOWNLOAD_URL" ]; then
+        echo "Failed to find download URL for eim-cli-$ARCH_PATTERN.zip" >&2
+        exit 1
+    fi
+    mkdir -p "$HOME/.local/bin"
+    curl -fsSL "$EIM_DOWNLOAD_URL" -o /tmp/eim.zip
+    unzip -o -q /tmp/eim.zip -d /tmp/eim
+    find /tmp/eim -name eim -type f -exec cp {} "$HOME/.local/bin/eim" \;
+    chmod +x "$HOME/.local/bin/eim"
+    rm -rf /tmp/eim.zip /tmp/eim
+    export PATH="$HOME/.local/bin:$PATH"
+fi
+exec eim "$@""#;
+
+/// Returns true if eim runs inside the Windows Subsystem for Linux.
+pub fn is_running_in_wsl() -> bool {
+    if std::env::consts::OS != "linux" {
+        return false;
+    }
+    if std::env::var_os("WSL_DISTRO_NAME").is_some() {
+        return true;
+    }
+    std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|release| release.to_lowercase().contains("microsoft"))
+        .unwrap_or(false)
+}
+
+/// Returns the name of the distro eim runs in, if running inside WSL.
+pub fn current_distro() -> Option<String> {
+    std::env::var("WSL_DISTRO_NAME").ok()
+}
+
+/// Returns true for paths on a Windows drive mounted into WSL (e.g. `/mnt/c/esp`).
+///
+/// Builds on such paths are many times slower than on the Linux filesystem and
+/// executable permissions are not reliable there.
+pub fn is_windows_mount(path: &Path) -> bool {
+    let mut components = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned());
+    matches!(
+        (components.next(), components.next(), components.next()),
+        (Some(root), Some(mnt), Some(drive)) if root == "/" && mnt == "mnt" && drive.len() == 1
+    )
+}
+
+/// Decodes the output of `wsl.exe`, which writes UTF-16LE when its output is redirected.
+fn decode_wsl_output(bytes: &[u8]) -> String {
+    if bytes.len() >= 2 && bytes.len() % 2 == 0 && bytes.iter().skip(1).step_by(2).all(|b| *b == 0)
+    {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+/// Lists the installed WSL distros. Only available on Windows.
+pub fn list_distros() -> Result<Vec<String>> {
+    if std::env::consts::OS != "windows" {
+        return Err(anyhow!("WSL distros can only be listed on Windows"));
+    }
+    let output = Command::new("wsl.exe")
+        .args(["--list", "--quiet"])
+        .output()?;
+    if !output.status.success() {
+        return Err(EimError::new(
+            ErrorKind::Prerequisites,
+            "WSL is not installed or not enabled, see https://learn.microsoft.com/windows/wsl/install",
+        )
+        .into());
+    }
+    Ok(decode_wsl_output(&output.stdout)
+        .lines()
+        .map(|line| line.trim().trim_start_matches('\u{feff}').to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+/// Removes `--wsl <distro>` and `--wsl=<distro>` from command line arguments.
+pub fn strip_wsl_argument(args: &[String]) -> Vec<String> {
+    let mut stripped = Vec::with_capacity(args.len());
+    let mut skip_next = false;
+    for arg in args {
+        if skip_next {
+            skip_next = false;
+        } else if arg == "--wsl" {
+            skip_next = true;
+        } else if !arg.starts_with("--wsl=") {
+            stripped.push(arg.clone());
+        }
+    }
+    stripped
+}
+
+/// Runs eim with `eim_args` inside `distro`, installing the eim CLI in the distro first if needed.
+///
+/// Output is passed through, so the progress of the install inside the distro is shown as is.
+pub fn run_eim_in_distro(distro: &str, eim_args: &[String]) -> Result<()> {
+    let distros = list_distros()?;
+    if !distros.iter().any(|d| d == distro) {
+        return Err(EimError::new(
+            ErrorKind::NotFound,
+            format!(
+                "WSL distro {} not found, installed distros: {}",
+                distro,
+                distros.join(", ")
+            ),
+        )
+        .into());
+    }
+    info!(
+        "Running eim {} in WSL distro {}",
+        eim_args.join(" "),
+        distro
+    );
+    let mut command = Command::new("wsl.exe");
+    command
+        .args([
+            "--distribution",
+            distro,
+            "--",
+            "bash",
+            "-lc",
+            WSL_BOOTSTRAP_SCRIPT,
+            "eim",
+        ])
+        .args(eim_args)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    debug!("Executing {:?}", command);
+    let status = command.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Installation in WSL distro {} failed with {}",
+            distro,
+            status
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows_mount_detection() {
+        assert!(is_windows_mount(Path::new("/mnt/c/esp")));
+        assert!(!is_windows_mount(Path::new("/mnt/data/esp")));
+        assert!(!is_windows_mount(Path::new("/home/user/.espressif")));
+    }
+
+    #[test]
+    fn test_wsl_output_decoding() {
+        let utf16: Vec<u8> = "Ubuntu\r\nDebian\r\n"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        assert_eq!(decode_wsl_output(&utf16), "Ubuntu\r\nDebian\r\n");
+        assert_eq!(decode_wsl_output(b"Ubuntu\n"), "Ubuntu\n");
+    }
+
+    #[test]
+    fn test_strip_wsl_argument() {
+        let args: Vec<String> = ["install", "--wsl", "Ubuntu", "-i", "v5.3.2", "--wsl=Debian"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(strip_wsl_argument(&args), vec!["install", "-i", "v5.3.2"]);
+    }
+}
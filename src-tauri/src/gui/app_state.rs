@@ -5,6 +5,7 @@ use std::sync::Mutex;
 use tauri::AppHandle;
 use idf_im_lib::settings::Settings;
 use idf_im_lib::utils::MirrorEntry;
+use idf_im_lib::install_lock::{InstallLock, LockError};
 
 use tauri::Manager; // dep: fork = "0.1"
 
@@ -25,6 +26,11 @@ pub struct AppState {
     pub idf_mirror_latency_entries: Mutex<Option<Vec<MirrorEntry>>>,
     pub tools_mirror_latency_entries: Mutex<Option<Vec<MirrorEntry>>>,
     pub pypi_mirror_latency_entries: Mutex<Option<Vec<MirrorEntry>>>,
+    #[serde(skip)]
+    pub install_lock: Mutex<Option<InstallLock>>,
+    /// The running installation reported an error, recorded as its result for `eim wait`
+    #[serde(skip)]
+    pub run_failed: Mutex<bool>,
 }
 
 pub fn set_idf_mirror_latency_entries(app_handle: &AppHandle, entries: &Vec<MirrorEntry>) -> Result<(), String> {
@@ -94,6 +100,15 @@ pub fn is_simple_installation(app_handle: &AppHandle) -> bool {
         })
 }
 
+/// Marks the running installation as failed, see [`set_installation_status`]
+pub fn set_run_failed(app_handle: &AppHandle) {
+    let app_state = app_handle.state::<AppState>();
+    match app_state.run_failed.lock() {
+        Ok(mut run_failed) => *run_failed = true,
+        Err(_) => error!("Failed to acquire run_failed lock"),
+    }
+}
+
 /// Gets the current settings from the app state
 ///
 /// This function acquires a lock on the settings mutex, which may block if another
@@ -150,6 +165,17 @@ where
     })?;
     updater(&mut settings);
     log::debug!("Settings after update: {:?}", settings);
+    idf_im_lib::http_client::configure_from_settings(&settings);
+    idf_im_lib::links::configure_from_settings(&settings);
+    idf_im_lib::git_tools::configure_from_settings(&settings);
+    idf_im_lib::retry_policy::configure_from_settings(&settings);
+    idf_im_lib::checksum::configure_from_settings(&settings);
+    idf_im_lib::download_backend::configure_from_settings(&settings);
+    idf_im_lib::display_format::configure_from_settings(&settings);
+    idf_im_lib::url_rewrite::configure_from_settings(&settings);
+    idf_im_lib::metadata_cache::configure_from_settings(&settings);
+    idf_im_lib::python_utils::configure_from_settings(&settings);
+    idf_im_lib::delta_download::configure_from_settings(&settings);
     Ok(())
 }
 
@@ -167,12 +193,76 @@ pub fn is_installation_in_progress(app_handle: &AppHandle) -> bool {
 }
 
 /// Sets the installation status
+///
+/// Starting an installation also takes the cross-process installer lock, so a CLI run
+/// can't modify the same tools directory at the same time. The lock is released when
+/// the status is set back to false.
 pub fn set_installation_status(app_handle: &AppHandle, status: bool) -> Result<(), String> {
     let app_state = app_handle.state::<AppState>();
+    let mut install_lock = app_state
+        .install_lock
+        .lock()
+        .map_err(|_| "Lock error".to_string())?;
+    let mut run_failed = app_state
+        .run_failed
+        .lock()
+        .map_err(|_| "Lock error".to_string())?;
+    if status {
+        if install_lock.is_none() {
+            *install_lock = Some(acquire_install_lock("eim gui installation")?);
+            *run_failed = false;
+            idf_im_lib::cancellation::reset();
+            crate::gui::ui::reset_announcements();
+        }
+        // a repair starts its own run log before
+        if idf_im_lib::logging::current_run_log().is_none() {
+            idf_im_lib::logging::start_run_log("install");
+        }
+    } else {
+        // the result is in the log before the lock is released, `eim wait` stops at either
+        if let Some(run_log) = idf_im_lib::logging::current_run_log() {
+            idf_im_lib::logging::finish_run_log();
+            let failure = (*run_failed).then_some(idf_im_lib::errors::ErrorKind::General);
+            if let Err(e) = idf_im_lib::run_monitor::record_result(&run_log, failure) {
+                error!("Failed to record the result in {}: {}", run_log.display(), e);
+            }
+        }
+        // still holding the lock, so no other installation loses its staging folders
+        if idf_im_lib::cancellation::is_cancelled() && install_lock.is_some() {
+            idf_im_lib::cancellation::clean_up_after_cancel();
+        }
+        // the commands run after a cancelled installation must not fail as cancelled too
+        idf_im_lib::cancellation::reset();
+        *install_lock = None;
+        // the installation ended, it is not resumable anymore
+        idf_im_lib::interrupted_install::mark_finished();
+    }
     let mut is_installing = app_state
         .is_installing
         .lock()
         .map_err(|_| "Lock error".to_string())?;
     *is_installing = status;
+    idf_im_lib::crash_report::set_state("installing", status);
     Ok(())
 }
+
+/// Token of the installer lock held by the running installation, for the processes it spawns
+pub fn install_lock_token(app_handle: &AppHandle) -> Option<String> {
+    let app_state = app_handle.state::<AppState>();
+    let install_lock = app_state.install_lock.lock().ok()?;
+    install_lock.as_ref().map(|lock| lock.token().to_string())
+}
+
+/// Takes the cross-process installer lock without waiting, reporting the holder if it is busy
+pub fn acquire_install_lock(command: &str) -> Result<InstallLock, String> {
+    InstallLock::try_acquire(command).map_err(|err| match err {
+        LockError::Held(holder) => rust_i18n::t!(
+            "lock.held",
+            pid = holder.pid,
+            command = holder.command,
+            started = idf_im_lib::display_format::format_timestamp(&holder.started)
+        )
+        .to_string(),
+        other => other.to_string(),
+    })
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+/// Version of the contract between the frontend and the Tauri commands.
+///
+/// Bump it when a command is removed or renamed, or its arguments or result change in a way an
+/// older frontend can't handle. New commands and new optional fields don't need a bump. The
+/// frontend copy in `src/api/apiVersion.js` is regenerated with `cargo xtask bindings`.
+pub const API_VERSION: u32 = 1;
+
+/// What each side reports in the startup handshake
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+#[serde(rename_all = "camelCase")]
+pub struct ApiInfo {
+  pub api_version: u32,
+  pub app_version: String,
+}
+
+impl ApiInfo {
+  pub fn backend() -> Self {
+    Self {
+      api_version: API_VERSION,
+      app_version: env!("CARGO_PKG_VERSION").to_string(),
+    }
+  }
+}
+
+/// Result of [`api_handshake`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+#[serde(rename_all = "camelCase")]
+pub struct ApiHandshake {
+  pub compatible: bool,
+  pub backend: ApiInfo,
+  /// Which side is outdated, `frontend` or `backend`, when not compatible
+  pub outdated: Option<String>,
+}
+
+fn handshake(frontend: &ApiInfo) -> ApiHandshake {
+  let backend = ApiInfo::backend();
+  let outdated = match frontend.api_version.cmp(&backend.api_version) {
+    std::cmp::Ordering::Less => Some("frontend".to_string()),
+    std::cmp::Ordering::Greater => Some("backend".to_string()),
+    std::cmp::Ordering::Equal => None,
+  };
+  ApiHandshake {
+    compatible: outdated.is_none(),
+    backend,
+    outdated,
+  }
+}
+
+/// Called by the frontend once at startup, before any other command, so a frontend and backend
+/// from different builds (e.g. after a partially applied update) are reported as such instead of
+/// failing on the first payload the other side doesn't understand.
+#[tauri::command]
+pub fn api_handshake(frontend: ApiInfo) -> ApiHandshake {
+  let result = handshake(&frontend);
+  if !result.compatible {
+    log::warn!(
+      "Frontend {} (API {}) doesn't match backend {} (API {})",
+      frontend.app_version,
+      frontend.api_version,
+      result.backend.app_version,
+      result.backend.api_version
+    );
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn frontend(api_version: u32) -> ApiInfo {
+    ApiInfo {
+      api_version,
+      app_version: "0.0.0".to_string(),
+    }
+  }
+
+  #[test]
+  fn test_handshake() {
+    assert!(handshake(&frontend(API_VERSION)).compatible);
+    let result = handshake(&frontend(API_VERSION + 1));
+    assert!(!result.compatible);
+    assert_eq!(result.outdated.as_deref(), Some("backend"));
+    assert_eq!(handshake(&frontend(0)).outdated.as_deref(), Some("frontend"));
+  }
+
+  #[test]
+  fn test_frontend_payload() {
+    let info: ApiInfo = serde_json::from_str(r#"{"apiVersion":1,"appVersion":"0.5.0"}"#).unwrap();
+    assert_eq!(info.api_version, 1);
+  }
+}
@@ -0,0 +1,142 @@
+use std::sync::Mutex;
+
+use idf_im_lib::idf_config::IdfInstallation;
+use once_cell::sync::Lazy;
+use rust_i18n::t;
+use serde::Serialize;
+
+use crate::gui::commands::version_management::get_installed_versions;
+
+/// Results shown by the command palette at most
+const MAX_RESULTS: usize = 20;
+
+/// Stable ESP-IDF versions, fetched once per session for the "install" actions
+static AVAILABLE_VERSIONS: Lazy<Mutex<Option<Vec<String>>>> = Lazy::new(|| Mutex::new(None));
+
+/// An operation the command palette (Ctrl+K) can run.
+///
+/// The frontend runs it by `kind`: `install_version`, `repair`, `select`, `open_terminal`,
+/// `open_logs`, `version_management` or `new_installation`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+pub struct PaletteAction {
+  pub kind: String,
+  pub title: String,
+  /// Version to install, id of the installation or activation script, depending on `kind`
+  pub argument: Option<String>,
+}
+
+impl PaletteAction {
+  fn new(kind: &str, title: String, argument: Option<String>) -> Self {
+    Self {
+      kind: kind.to_string(),
+      title,
+      argument,
+    }
+  }
+}
+
+/// Scores how well `query` matches `text`: all characters of the query have to appear in order,
+/// runs of consecutive characters and matches at word starts score higher. `None` if it doesn't match.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+  let text: Vec<char> = text.to_lowercase().chars().collect();
+  let mut score = 0;
+  let mut position = 0;
+  let mut previous: Option<usize> = None;
+  for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+    let found = (position..text.len()).find(|&i| text[i] == wanted)?;
+    score += 1;
+    if previous.is_some_and(|p| p + 1 == found) {
+      score += 5;
+    }
+    if found == 0 || !text[found - 1].is_alphanumeric() {
+      score += 3;
+    }
+    previous = Some(found);
+    position = found + 1;
+  }
+  // shorter titles first among equally good matches
+  Some(score * 100 - text.len() as i64)
+}
+
+/// Ranks `actions` by how well their titles match `query`, all of them in order for an empty query.
+fn rank(actions: Vec<PaletteAction>, query: &str) -> Vec<PaletteAction> {
+  if query.trim().is_empty() {
+    return actions.into_iter().take(MAX_RESULTS).collect();
+  }
+  let mut scored: Vec<(i64, PaletteAction)> = actions
+    .into_iter()
+    .filter_map(|action| fuzzy_score(query, &action.title).map(|score| (score, action)))
+    .collect();
+  scored.sort_by(|a, b| b.0.cmp(&a.0));
+  scored.into_iter().take(MAX_RESULTS).map(|(_, action)| action).collect()
+}
+
+fn installation_actions(installation: &IdfInstallation) -> Vec<PaletteAction> {
+  let name = installation.name.as_str();
+  vec![
+    PaletteAction::new("select", t!("gui.palette.select", name = name).to_string(), Some(installation.id.clone())),
+    PaletteAction::new(
+      "open_terminal",
+      t!("gui.palette.open_terminal", name = name).to_string(),
+      Some(installation.activation_script.clone()),
+    ),
+    PaletteAction::new("repair", t!("gui.palette.repair", name = name).to_string(), Some(installation.id.clone())),
+  ]
+}
+
+async fn available_versions() -> Vec<String> {
+  if let Some(versions) = AVAILABLE_VERSIONS.lock().unwrap().clone() {
+    return versions;
+  }
+  let versions = idf_im_lib::idf_versions::get_stable_idf_names().await;
+  if !versions.is_empty() {
+    *AVAILABLE_VERSIONS.lock().unwrap() = Some(versions.clone());
+  }
+  versions
+}
+
+/// Actions of the command palette matching `query`, best match first
+#[tauri::command]
+pub async fn search_actions(query: String) -> Vec<PaletteAction> {
+  let mut actions = vec![
+    PaletteAction::new("new_installation", t!("gui.palette.new_installation").to_string(), None),
+    PaletteAction::new("version_management", t!("gui.palette.version_management").to_string(), None),
+    PaletteAction::new("open_logs", t!("gui.palette.open_logs").to_string(), None),
+  ];
+  for installation in get_installed_versions() {
+    actions.extend(installation_actions(&installation));
+  }
+  for version in available_versions().await {
+    actions.push(PaletteAction::new(
+      "install_version",
+      t!("gui.palette.install_version", version = version).to_string(),
+      Some(version),
+    ));
+  }
+  rank(actions, &query)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_fuzzy_score() {
+    assert!(fuzzy_score("inst 5.3", "Install ESP-IDF v5.3.2").is_some());
+    assert!(fuzzy_score("xyz", "Install ESP-IDF v5.3.2").is_none());
+    // consecutive characters beat scattered ones
+    assert!(fuzzy_score("rep", "Repair v5.3").unwrap() > fuzzy_score("rep", "Open the IDF terminal of v5.3").unwrap_or(i64::MIN));
+  }
+
+  #[test]
+  fn test_rank() {
+    let actions = vec![
+      PaletteAction::new("open_logs", "Open the logs folder".to_string(), None),
+      PaletteAction::new("repair", "Repair v5.3".to_string(), Some("esp-idf-1".to_string())),
+    ];
+    let ranked = rank(actions.clone(), "repair");
+    assert_eq!(ranked, vec![actions[1].clone()]);
+    assert_eq!(rank(actions.clone(), "  ").len(), 2);
+  }
+}
@@ -1,8 +1,8 @@
-use crate::gui::{app_state::{get_settings_non_blocking, update_settings}, ui::{InstallationProgress, InstallationStage, MessageLevel, ProgressBar, emit_installation_event, emit_log_message, send_message, send_tools_message}, utils::{get_mirror_to_use, MirrorType}};
+use crate::gui::{app_state::{get_settings_non_blocking, update_settings}, ui::{InstallationProgress, InstallationStage, MessageLevel, ProgressBar, emit_installation_event, emit_transfer_event, emit_log_message, send_message, send_tools_message}, utils::{get_mirror_to_use, MirrorType}};
 use anyhow::{anyhow, Context, Result};
 
 use idf_im_lib::{
-  add_path_to_path, ensure_path, idf_features::{get_requirements_json_url, FeatureInfo, RequirementsMetadata}, idf_tools::{self, get_tools_export_paths}, settings::Settings, DownloadProgress
+  add_path_to_path, ensure_path, idf_features::{get_requirements_json_url, FeatureInfo, RequirementsMetadata}, idf_tools::{self, get_tools_export_paths}, settings::Settings, transfer_rate::TransferRate, DownloadProgress
 };
 use log::{ error, info, warn};
 use serde::{Deserialize, Serialize};
@@ -91,13 +91,15 @@ impl ToolSetup {
 }
 
 /// Sets up ESP-IDF tools based on settings and IDF path
+///
+/// Returns the export paths together with the installed tools, which go into the install report.
 pub async fn setup_tools(
     app_handle: &AppHandle,
     settings: &Settings,
     idf_path: &PathBuf,
     idf_version: &str,
     offline_archive_dir: Option<&Path>,
-) -> Result<Vec<String>> {
+) -> Result<(Vec<String>, HashMap<String, (String, idf_tools::Download)>)> {
     info!("Setting up tools...");
     let is_simple_installation = crate::gui::app_state::is_simple_installation(&app_handle);
 
@@ -106,7 +108,7 @@ pub async fn setup_tools(
         .context("Failed to get parent directory of IDF path")?;
 
     // Initialize tool setup
-    let tool_setup = ToolSetup::new(settings, &PathBuf::from(version_path))
+    let mut tool_setup = ToolSetup::new(settings, &PathBuf::from(version_path))
         .map_err(|e| anyhow!("Failed to initialize tool setup: {}", e))?;
 
     // Create necessary directories
@@ -114,6 +116,21 @@ pub async fn setup_tools(
         .create_directories(app_handle)
         .map_err(|e| anyhow!("Failed to create tool directories: {}", e))?;
 
+    // A custom tools manifest replaces the tools.json shipped with ESP-IDF
+    if let Some(manifest) = &settings.tools_manifest {
+        let manifest_path = idf_tools::resolve_tools_manifest(manifest, Path::new(&tool_setup.download_dir))
+            .await
+            .map_err(|e| {
+                emit_log_message(
+                    app_handle,
+                    MessageLevel::Error,
+                    t!("gui.setup_tools.tools_manifest_failed", error = e.to_string()).to_string(),
+                );
+                anyhow!(t!("gui.setup_tools.tools_manifest_failed", error = e.to_string()).to_string())
+            })?;
+        tool_setup.tools_json_path = manifest_path.to_string_lossy().into_owned();
+    }
+
     // Validate tools.json exists
     tool_setup
         .validate_tools_json()
@@ -129,6 +146,24 @@ pub async fn setup_tools(
             );
             anyhow!(t!("gui.setup_tools.tools_json_parse_failed", error = e.to_string()).to_string())
         })?;
+    let tools = idf_tools::apply_tool_version_overrides(tools, &settings.tool_version_overrides.clone().unwrap_or_default())
+        .map_err(|e| {
+            emit_log_message(
+                app_handle,
+                MessageLevel::Error,
+                t!("gui.setup_tools.tool_overrides_failed", error = e.to_string()).to_string(),
+            );
+            anyhow!(t!("gui.setup_tools.tool_overrides_failed", error = e.to_string()).to_string())
+        })?;
+    let with_qemu = idf_im_lib::components::selected_components(settings)
+        .map(|selected| selected.contains(&idf_im_lib::components::Component::Qemu))
+        .unwrap_or(settings.install_qemu.unwrap_or(false));
+    let tools = if with_qemu {
+        tools
+    } else {
+        idf_im_lib::qemu::without_qemu(tools)
+    };
+    let tools = idf_im_lib::system_tools::without_system_tools(tools, &idf_im_lib::system_tools::selected(settings));
 
     // Start tools installation phase (65% of total progress)
     emit_installation_event(app_handle, InstallationProgress {
@@ -151,6 +186,10 @@ pub async fn setup_tools(
     let idf_version_clone = idf_version.to_string();
     let completed_tools_clone = completed_tools.clone();
     let current_tool_name_clone = current_tool_name.clone();
+    // archives are extracted while the next one downloads, an extraction is shown only when
+    // no download is running
+    let downloading = Arc::new(Mutex::new(false));
+    let transfer_rate = Arc::new(Mutex::new(TransferRate::default()));
 
     let progress_callback = move |progress: DownloadProgress| {
         match progress {
@@ -159,12 +198,13 @@ pub async fn setup_tools(
                     let tool_progress = current * 100 / total;
                     let completed = *completed_tools_clone.lock().unwrap();
                     let tool_name = current_tool_name_clone.lock().unwrap().clone();
+                    let transfer = transfer_rate.lock().unwrap().update(current, total);
 
                     let overall_tool_progress = (completed as f32 / total_tools) * tools_range as f32;
                     let current_tool_contribution = (tool_progress as f32 / 100.0) * (tools_range as f32 / total_tools);
                     let overall_percentage = base_percentage + overall_tool_progress as u32 + current_tool_contribution as u32;
 
-                    emit_installation_event(&app_handle_clone, InstallationProgress {
+                    emit_transfer_event(&app_handle_clone, InstallationProgress {
                         stage: InstallationStage::Tools,
                         percentage: overall_percentage.min(89), // Cap at 89% to leave room for completion
                         message: t!("gui.setup_tools.downloading",
@@ -178,27 +218,57 @@ pub async fn setup_tools(
                             percentage = tool_progress
                         ).to_string()),
                         version: Some(idf_version_clone.clone()),
-                    });
+                    }, transfer);
                 }
             }
 
-            DownloadProgress::Start(url) => {
-                // Extract tool name from URL
-                let tool_name = if let Some(filename) = Path::new(&url).file_name().and_then(|f| f.to_str()) {
-                    // Try to extract tool name from filename (remove version/platform info)
-                    let clean_name = filename
-                        .split('-')
-                        .take(3) // Take first few parts before version numbers
-                        .collect::<Vec<_>>()
-                        .join("-")
-                        .replace(".tar", "")
-                        .replace(".zip", "");
-                    clean_name
-                } else {
-                    t!("gui.setup_tools.unknown_tool").to_string()
-                };
+            DownloadProgress::Extracting(read, size) => {
+                if size > 0 && !*downloading.lock().unwrap() {
+                    let completed = *completed_tools_clone.lock().unwrap();
+                    let tool_name = current_tool_name_clone.lock().unwrap().clone();
+                    let transfer = transfer_rate.lock().unwrap().update(read, size);
 
+                    emit_transfer_event(&app_handle_clone, InstallationProgress {
+                        stage: InstallationStage::Tools,
+                        percentage: (base_percentage + ((completed as f32 / total_tools) * tools_range as f32) as u32 + 2).min(89),
+                        message: t!("gui.setup_tools.extracting",
+                            tool_name = tool_name.replace("-", " ")
+                        ).to_string(),
+                        detail: Some(t!("gui.setup_tools.extraction_progress",
+                            current = completed + 1,
+                            total = total_tools as u32,
+                            percentage = read * 100 / size
+                        ).to_string()),
+                        version: Some(idf_version_clone.clone()),
+                    }, transfer);
+                }
+            }
+
+            DownloadProgress::Hashing(hashed, total) => {
+                if total > 0 {
+                    let completed = *completed_tools_clone.lock().unwrap();
+                    let tool_name = current_tool_name_clone.lock().unwrap().clone();
+
+                    emit_installation_event(&app_handle_clone, InstallationProgress {
+                        stage: InstallationStage::Tools,
+                        percentage: (base_percentage + ((completed as f32 / total_tools) * tools_range as f32) as u32 + 1).min(89),
+                        message: t!("gui.setup_tools.verifying",
+                            tool_name = tool_name.replace("-", " ")
+                        ).to_string(),
+                        detail: Some(t!("gui.setup_tools.hashing_progress",
+                            current = completed + 1,
+                            total = total_tools as u32,
+                            percentage = hashed * 100 / total
+                        ).to_string()),
+                        version: Some(idf_version_clone.clone()),
+                    });
+                }
+            }
+
+            DownloadProgress::Start(tool_name, _url) => {
                 *current_tool_name_clone.lock().unwrap() = tool_name.clone();
+                *downloading.lock().unwrap() = true;
+                transfer_rate.lock().unwrap().reset();
                 let completed = *completed_tools_clone.lock().unwrap();
                 let overall_percentage = base_percentage + ((completed as f32 / total_tools) * tools_range as f32) as u32;
 
@@ -241,6 +311,7 @@ pub async fn setup_tools(
             }
 
             DownloadProgress::Verified(url) => {
+                *downloading.lock().unwrap() = false;
                 let completed = *completed_tools_clone.lock().unwrap();
                 let tool_name = current_tool_name_clone.lock().unwrap().clone();
 
@@ -261,11 +332,11 @@ pub async fn setup_tools(
                     t!("gui.setup_tools.verified", tool_name = tool_name).to_string());
             }
 
-            DownloadProgress::Extracted(url, _dest) => {
+            // extractions run in parallel with the next download, the current tool is another one
+            DownloadProgress::Extracted(tool_name, _url, _dest) => {
                 let mut completed = completed_tools_clone.lock().unwrap();
                 *completed += 1;
                 let completed_count = *completed;
-                let tool_name = current_tool_name_clone.lock().unwrap().clone();
 
                 let overall_percentage = base_percentage + ((completed_count as f32 / total_tools) * tools_range as f32) as u32;
 
@@ -290,7 +361,7 @@ pub async fn setup_tools(
                     ).to_string());
             }
 
-            DownloadProgress::Complete => {
+            DownloadProgress::Complete(_tool_name) => {
                 emit_installation_event(&app_handle_clone, InstallationProgress {
                     stage: InstallationStage::Tools,
                     percentage: 89,
@@ -326,10 +397,16 @@ pub async fn setup_tools(
         &PathBuf::from(&tool_setup.download_dir),
         &PathBuf::from(&tool_setup.install_dir),
         Some(&tools_mirror_to_use),
+        settings.tools_url_rewrite.as_deref(),
         progress_callback,
     )
     .await
     .map_err(|e| {
+        // access denied while extracting is often real-time antivirus scanning, not a broken download
+        if let Some(av_error) = idf_im_lib::antivirus::diagnose(&e, settings) {
+            emit_log_message(app_handle, MessageLevel::Error, av_error.message.clone());
+            return anyhow::Error::new(av_error);
+        }
         emit_installation_event(app_handle, InstallationProgress {
             stage: InstallationStage::Error,
             percentage: 0,
@@ -361,7 +438,10 @@ pub async fn setup_tools(
     };
 
     // Get features for this specific version
-    let features_for_version = settings.get_features_for_version(idf_version);
+    let features_for_version = idf_im_lib::components::filter_features(
+        settings.get_features_for_version(idf_version),
+        &idf_im_lib::components::omitted_components(settings).unwrap_or_default(),
+    );
 
     info!(
         "Installing Python environment for {} with features: {:?}",
@@ -409,7 +489,7 @@ pub async fn setup_tools(
     // Generate export paths
     let export_paths = idf_im_lib::idf_tools::get_tools_export_paths_from_list(
         tools,
-        installed_tools_list,
+        installed_tools_list.clone(),
         tools_install_folder.to_str().unwrap(),
     )
     .into_iter()
@@ -434,7 +514,7 @@ pub async fn setup_tools(
     emit_log_message(app_handle, MessageLevel::Success,
         t!("gui.setup_tools.setup_completed").to_string());
 
-    Ok(export_paths)
+    Ok((export_paths, installed_tools_list))
 }
 
 #[tauri::command]
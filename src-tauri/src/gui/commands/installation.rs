@@ -8,6 +8,7 @@ use std::{
   process::{Command, Stdio},
   sync::mpsc,
   thread,
+  time::Instant,
 };
 
 use anyhow::{anyhow, Context, Result};
@@ -15,6 +16,8 @@ use idf_im_lib::{
   ensure_path,
   expand_tilde,
   idf_config::IdfConfig,
+  hooks::HookStage,
+  install_report::InstallReport,
   offline_installer::{copy_idf_from_offline_archive, install_prerequisites_offline, use_offline_archive},
   utils::{copy_dir_contents, extract_zst_archive, is_valid_idf_directory, parse_cmake_version},
   version_manager::{get_default_config_path, prepare_settings_for_fix_idf_installation},
@@ -22,7 +25,9 @@ use idf_im_lib::{
 use log::{debug, error, info, warn};
 use serde_json::json;
 
+use idf_im_lib::interrupted_install::QueueItemState;
 use idf_im_lib::settings::Settings;
+use idf_im_lib::transaction::InstallTransaction;
 use crate::gui::{
   app_state::{get_locked_settings, get_settings_non_blocking, set_installation_status, set_is_simple_installation},
   commands,
@@ -183,11 +188,12 @@ async fn download_idf(
     }
 }
 
-/// Installs a single ESP-IDF version
+/// Installs a single ESP-IDF version, recording what was installed in `report`
 pub async fn install_single_version(
   app_handle: AppHandle,
   settings: &Settings,
   version: String,
+  report: &mut InstallReport,
 ) -> Result<(), Box<dyn std::error::Error>> {
   info!("Installing IDF version: {}", version);
 
@@ -196,6 +202,14 @@ pub async fn install_single_version(
     err.to_string()
   })?;
 
+  idf_im_lib::hooks::run_hooks(HookStage::PreInstall, settings, &paths).map_err(|err| err.to_string())?;
+
+  let snapshot = idf_im_lib::install_manifest::PreInstallSnapshot::take(&paths);
+  // A version which fails half way through is removed again, so it does not show up as broken install
+  let mut transaction = InstallTransaction::new(&format!("installation of {}", version));
+  if !paths.using_existing_idf && !paths.version_installation_path.exists() {
+    transaction.register_created_path(&paths.version_installation_path);
+  }
 
   if paths.using_existing_idf {
     info!("Using existing IDF directory: {}", paths.idf_path.display());
@@ -207,12 +221,77 @@ pub async fn install_single_version(
 
     debug!("Using IDF version: {}", paths.actual_version);
   } else {
+    let stage_start = Instant::now();
+    idf_im_lib::metrics::set_stage("download_idf", Some(&version));
     download_idf(&app_handle, settings, &version, &paths.idf_path).await?;
+    idf_im_lib::components::apply_to_repository_or_warn(&paths.idf_path, settings);
+    report.record_stage("download_idf", Some(&version), stage_start.elapsed());
   }
 
+  let stage_start = Instant::now();
+  idf_im_lib::metrics::set_stage("tools", Some(&version));
+  let (export_vars, installed_tools) = setup_tools(&app_handle, settings, &paths.idf_path, &paths.actual_version, None).await?;
+  report.record_stage("tools", Some(&version), stage_start.elapsed());
 
-  let export_vars = setup_tools(&app_handle, settings, &paths.idf_path, &paths.actual_version, None).await?;
-
+  let mut env_vars = idf_im_lib::default_environment_variables(
+      paths.tool_install_directory.to_str().unwrap(),
+      paths.idf_path.to_str().unwrap(),
+      paths.python_venv_path.to_str(),
+  );
+  let mut export_vars = export_vars;
+  if settings.enable_ccache.unwrap_or(false) {
+      match idf_im_lib::idf_tools::setup_ccache(
+          &paths.idf_path.join("tools").join("tools.json"),
+          &paths.tool_download_directory,
+          &paths.tool_install_directory,
+          settings.mirror.as_deref(),
+          settings.tools_url_rewrite.as_deref(),
+      )
+      .await
+      {
+          Ok((vars, ccache_paths)) => {
+              env_vars.extend(vars);
+              export_vars.extend(ccache_paths);
+          }
+          Err(err) => emit_log_message(
+              &app_handle,
+              MessageLevel::Warning,
+              rust_i18n::t!("gui.installation.ccache_failed", error = err.to_string()).to_string(),
+          ),
+      }
+  }
+  if settings.rust_toolchain.unwrap_or(false) {
+      emit_log_message(&app_handle, MessageLevel::Info, rust_i18n::t!("gui.installation.rust_installing").to_string());
+      match idf_im_lib::esp_rs::install_rust_toolchain(
+          &paths.tool_install_directory,
+          &settings.target.clone().unwrap_or_default(),
+      )
+      .await
+      {
+          Ok((vars, rust_paths)) => {
+              env_vars.extend(vars);
+              export_vars.extend(rust_paths);
+          }
+          Err(err) => emit_log_message(
+              &app_handle,
+              MessageLevel::Warning,
+              rust_i18n::t!("gui.installation.rust_failed", error = err.to_string()).to_string(),
+          ),
+      }
+  }
+  if idf_im_lib::component_manager::is_enabled(settings) {
+      match idf_im_lib::component_manager::setup(settings, &paths, None) {
+          Ok(vars) => env_vars.extend(vars),
+          Err(err) => emit_log_message(
+              &app_handle,
+              MessageLevel::Warning,
+              rust_i18n::t!("wizard.component_manager.failed", error = err.to_string()).to_string(),
+          ),
+      }
+  }
+  export_vars.splice(0..0, idf_im_lib::custom_toolchains::export_paths());
+  let stage_start = Instant::now();
+  idf_im_lib::metrics::set_stage("post_install", Some(&version));
   idf_im_lib::single_version_post_install(
       &paths.activation_script_path.to_str().unwrap(),
       paths.idf_path.to_str().unwrap(),
@@ -220,12 +299,44 @@ pub async fn install_single_version(
       paths.tool_install_directory.to_str().unwrap(),
       export_vars,
       paths.python_venv_path.to_str(),
-      None, // env_vars
+      Some(env_vars),
   );
+  transaction.commit();
+  if settings.dedupe_tools.unwrap_or(true) {
+    if let Err(err) = idf_im_lib::dedupe::dedupe_new_installation(&paths.tool_install_directory) {
+      warn!("Failed to deduplicate tools: {}", err);
+    }
+  }
+  let manifest_warnings = idf_im_lib::idf_tools::tool_version_override_warnings(
+    &settings.tool_version_overrides.clone().unwrap_or_default(),
+  );
+  let omitted_components = idf_im_lib::components::omitted_components(settings).unwrap_or_default();
+  if let Err(err) = idf_im_lib::install_manifest::record_version_manifest(&paths, &snapshot, settings, &manifest_warnings, &omitted_components) {
+    warn!("Failed to record install manifest: {}", err);
+  }
+  report.record_stage("post_install", Some(&version), stage_start.elapsed());
+  report.add_version(&paths, &installed_tools);
+  report.add_warnings(&manifest_warnings);
+  if let Err(err) = idf_im_lib::hooks::run_hooks(HookStage::PostInstall, settings, &paths) {
+    emit_log_message(&app_handle, MessageLevel::Warning,
+        rust_i18n::t!("gui.installation.post_install_hook_failed", error = err.to_string()).to_string());
+    report.add_warnings(&[err.to_string()]);
+  }
 
   Ok(())
 }
 
+/// Finishes the install report and tells the user where it was written.
+fn write_install_report(app_handle: &AppHandle, settings: &Settings, mut report: InstallReport, result: &std::result::Result<(), String>) {
+  report.finish(result);
+  match report.write(settings.report_signing_key.as_deref()) {
+    Ok(path) => emit_log_message(app_handle, MessageLevel::Info,
+        rust_i18n::t!("gui.installation.report_written", path = path.display().to_string()).to_string()),
+    Err(err) => emit_log_message(app_handle, MessageLevel::Warning,
+        rust_i18n::t!("gui.installation.report_failed", error = err.to_string()).to_string()),
+  }
+}
+
 #[cfg(target_os = "windows")]
 #[tauri::command]
 pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
@@ -247,8 +358,9 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
     settings_clone.non_interactive = Some(true);
     settings_clone.install_all_prerequisites = Some(true);
 
-    // Validate installation path
-    if !is_path_empty_or_nonexistent(settings_clone.path.clone().unwrap().to_str().unwrap(), &settings_clone.clone().idf_versions.unwrap()) {
+    // Validate installation path, a resumed installation continues in the one it left behind
+    let resuming = idf_im_lib::interrupted_install::find_interrupted().is_some();
+    if !resuming && !is_path_empty_or_nonexistent(settings_clone.path.clone().unwrap().to_str().unwrap(), &settings_clone.clone().idf_versions.unwrap()) {
         log::error!("Installation path not available: {:?}", settings_clone.path.clone().unwrap());
 
         emit_installation_event(&app_handle, InstallationProgress {
@@ -262,6 +374,10 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
         return Err(rust_i18n::t!("gui.installation.path_not_available").to_string());
     }
 
+    if let Err(e) = idf_im_lib::interrupted_install::mark_started(&settings) {
+        log::warn!("Failed to record the installation for resuming: {}", e);
+    }
+
     // Save settings to temp file
     if let Err(e) = settings_clone.save() {
         log::error!("Failed to save temporary config: {}", e);
@@ -296,7 +412,14 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
         rust_i18n::t!("gui.installation.starting_separate_process").to_string());
 
     // Start the process with piped stdout and stderr
-    let mut child = Command::new(current_exe)
+    let mut command = Command::new(current_exe);
+    // cancelling terminates the installer, which stops the tools it started itself
+    idf_im_lib::cancellation::own_process_group(&mut command);
+    // the installer works under the lock this installation holds instead of waiting for it
+    if let Some(token) = app_state::install_lock_token(&app_handle) {
+        command.env(idf_im_lib::install_lock::LOCK_TOKEN_ENV, token);
+    }
+    let mut child = command
         .arg("install")
         .arg("-n").arg("true")             // Non-interactive mode
         .arg("-a").arg("true")             // Install prerequisites
@@ -328,6 +451,8 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
 
     std::thread::spawn(move || {
         let pid = child.id();
+        // cancelling terminates the installer process with git and pip it started
+        let _registered = idf_im_lib::cancellation::register(pid);
 
         // Progress tracking state
         let mut current_stage = InstallationStage::Checking;
@@ -585,13 +710,19 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
         let _ = stdout_monitor.join();
         let _ = stderr_monitor.join();
 
+        let success = status.success();
+        // before the status is cleared, which forgets the cancellation
+        let cancelled = !success && idf_im_lib::cancellation::is_cancelled();
+        if cancelled {
+            emit_cancelled(&monitor_handle, current_version.clone());
+        }
+
         // Clean up installation status
         if let Err(e) = set_installation_status(&monitor_handle, false) {
             log::error!("Failed to update installation status: {}", e);
         }
 
         // Emit final completion or error event
-        let success = status.success();
         log::info!("Installation completed with success={}", success);
 
         if success {
@@ -605,7 +736,7 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
 
             emit_log_message(&monitor_handle, MessageLevel::Success,
                 rust_i18n::t!("gui.installation.success_message").to_string());
-        } else {
+        } else if !cancelled {
             let error_msg = rust_i18n::t!("gui.installation.failed_exit_code", code = status.code().unwrap_or(-1)).to_string();
 
             emit_installation_event(&monitor_handle, InstallationProgress {
@@ -647,6 +778,28 @@ fn is_process_running(pid: u32) -> bool {
     }
 }
 
+/// Runs the network preflight and reports every blocked endpoint to the UI.
+#[cfg(not(target_os = "windows"))]
+async fn check_network_preflight(app_handle: &AppHandle, settings: &Settings) -> Result<(), String> {
+    let reports = idf_im_lib::network_preflight::run_network_preflight(settings).await;
+    let failed: Vec<String> = reports.iter().filter(|r| !r.is_ok()).map(|r| r.to_string()).collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+    for report in &failed {
+        emit_log_message(app_handle, MessageLevel::Error,
+            rust_i18n::t!("gui.network_preflight.failed", report = report).to_string());
+    }
+    emit_installation_event(app_handle, InstallationProgress {
+        stage: InstallationStage::Error,
+        percentage: 0,
+        message: rust_i18n::t!("gui.network_preflight.failed", report = failed[0].clone()).to_string(),
+        detail: Some(failed.join("\n")),
+        version: None,
+    });
+    Err(failed.join("\n"))
+}
+
 #[cfg(not(target_os = "windows"))]
 #[tauri::command]
 pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
@@ -679,6 +832,29 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
         }
     };
 
+    if let Err(e) = idf_im_lib::version_manager::check_version_name(&settings) {
+        emit_installation_event(&app_handle, InstallationProgress {
+            stage: InstallationStage::Error,
+            percentage: 0,
+            message: e.to_string(),
+            detail: None,
+            version: None,
+        });
+        set_installation_status(&app_handle, false)?;
+        return Err(e.to_string());
+    }
+
+    if !settings.skip_network_preflight.unwrap_or(false) {
+        if let Err(e) = check_network_preflight(&app_handle, &settings).await {
+            set_installation_status(&app_handle, false)?;
+            return Err(e);
+        }
+    }
+
+    if let Err(e) = idf_im_lib::interrupted_install::mark_started(&settings) {
+        log::warn!("Failed to record the installation for resuming: {}", e);
+    }
+
     emit_installation_plan(&app_handle, InstallationPlan {
       total_versions: versions.len(),
       versions: versions.clone(),
@@ -702,8 +878,13 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
             count = total_versions,
             versions = versions.join(", ")).to_string());
 
+    let mut report = InstallReport::new(&settings);
+
     // Install each version with progress tracking
     for (index, version) in versions.iter().enumerate() {
+        if idf_im_lib::cancellation::is_cancelled() {
+            return Err(finish_cancelled(&app_handle, &settings, report, Some(version.clone())));
+        }
         emit_installation_plan(&app_handle, InstallationPlan {
           total_versions: versions.len(),
           versions: versions.clone(),
@@ -731,8 +912,10 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
                 total = total_versions).to_string());
 
         // Install single version
-        match install_single_version(app_handle.clone(), &settings, version.clone()).await {
+        idf_im_lib::interrupted_install::set_version_state(version, QueueItemState::Installing);
+        match install_single_version(app_handle.clone(), &settings, version.clone(), &mut report).await {
             Ok(_) => {
+                idf_im_lib::interrupted_install::set_version_state(version, QueueItemState::Installed);
                 emit_installation_event(&app_handle, InstallationProgress {
                   stage: if index < versions.len() - 1 { InstallationStage::Configure } else { InstallationStage::Complete },
                   percentage: version_end_percentage as u32,
@@ -749,6 +932,9 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
                         current = index + 1,
                         total = total_versions).to_string());
             }
+            Err(_) if idf_im_lib::cancellation::is_cancelled() => {
+                return Err(finish_cancelled(&app_handle, &settings, report, Some(version.clone())));
+            }
             Err(e) => {
                 error!("Failed to install version {}: {}", version, e);
 
@@ -765,10 +951,12 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
                         version = version,
                         error = e.to_string()).to_string());
 
-                set_installation_status(&app_handle, false)?;
-                return Err(rust_i18n::t!("gui.installation.failed_for_version",
+                let error_msg = rust_i18n::t!("gui.installation.failed_for_version",
                     version = version,
-                    error = e.to_string()).to_string());
+                    error = e.to_string()).to_string();
+                write_install_report(&app_handle, &settings, report, &Err(error_msg.clone()));
+                set_installation_status(&app_handle, false)?;
+                return Err(error_msg);
             }
         }
     }
@@ -840,6 +1028,7 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
 
     emit_log_message(&app_handle, MessageLevel::Success,
         rust_i18n::t!("gui.installation.batch_completed", count = total_versions).to_string());
+    write_install_report(&app_handle, &settings, report, &Ok(()));
 
     // Clear installation flag
     set_installation_status(&app_handle, false)?;
@@ -847,6 +1036,49 @@ pub async fn start_installation(app_handle: AppHandle) -> Result<(), String> {
     Ok(())
 }
 
+/// Tells the UI the operation stopped because it was cancelled, not because it failed
+fn emit_cancelled(app_handle: &AppHandle, version: Option<String>) {
+  emit_installation_event(app_handle, InstallationProgress {
+    stage: InstallationStage::Error,
+    percentage: 0,
+    message: rust_i18n::t!("gui.installation.cancelled").to_string(),
+    detail: Some(rust_i18n::t!("gui.installation.cancelled_detail").to_string()),
+    version,
+  });
+  emit_log_message(app_handle, MessageLevel::Warning, rust_i18n::t!("gui.installation.cancelled").to_string());
+}
+
+/// Ends a cancelled operation: cleans up and returns the error of the command
+fn end_cancelled(app_handle: &AppHandle, version: Option<String>) -> String {
+  // before the status is cleared, which forgets the cancellation
+  emit_cancelled(app_handle, version);
+  if let Err(e) = set_installation_status(app_handle, false) {
+    log::error!("Failed to update installation status: {}", e);
+  }
+  rust_i18n::t!("gui.installation.cancelled").to_string()
+}
+
+/// Ends a cancelled installation like [`end_cancelled`], writing its report first
+fn finish_cancelled(app_handle: &AppHandle, settings: &Settings, report: InstallReport, version: Option<String>) -> String {
+  write_install_report(app_handle, settings, report, &Err(rust_i18n::t!("gui.installation.cancelled").to_string()));
+  end_cancelled(app_handle, version)
+}
+
+/// Cancels the running installation, simple setup, repair or offline installation: downloads and
+/// clones stop, git, pip and the installer process are terminated and the staging folders removed.
+/// Returns whether an operation was running. The prerequisites, Python and driver installations
+/// run in system installers and can't be cancelled.
+#[tauri::command]
+pub fn cancel_operation(app_handle: AppHandle) -> bool {
+  if !is_installation_in_progress(&app_handle) {
+    return false;
+  }
+  let terminated = idf_im_lib::cancellation::cancel();
+  info!("Cancelling the running operation, {} subprocesses terminated", terminated);
+  emit_log_message(&app_handle, MessageLevel::Warning, rust_i18n::t!("gui.installation.cancelling").to_string());
+  true
+}
+
 /// Starts a simple setup process that automates the installation
 #[tauri::command]
 pub async fn start_simple_setup(app_handle: tauri::AppHandle) -> Result<(), String> {
@@ -1040,8 +1272,15 @@ pub async fn start_simple_setup(app_handle: tauri::AppHandle) -> Result<(), Stri
 pub async fn fix_installation(app_handle: AppHandle, id: String) -> Result<(), String> {
     debug!("Fixing installation with id {}", id);
 
+    let run_log_started = idf_im_lib::logging::current_run_log().is_none()
+        && idf_im_lib::logging::start_run_log("repair").is_some();
     // Set installation flag to indicate installation is running
-    set_installation_status(&app_handle, true)?;
+    if let Err(e) = set_installation_status(&app_handle, true) {
+        if run_log_started {
+            idf_im_lib::logging::finish_run_log();
+        }
+        return Err(e);
+    }
 
     // Initial progress - checking installation
     emit_installation_event(&app_handle, InstallationProgress {
@@ -1147,16 +1386,22 @@ pub async fn fix_installation(app_handle: AppHandle, id: String) -> Result<(), S
         rust_i18n::t!("gui.fix.starting_repair_log", version = installation.name.clone()).to_string());
 
     // The actual repair process - this will generate detailed progress events
-    match install_single_version(app_handle.clone(), &settings, installation.name.clone()).await {
+    let mut report = InstallReport::new(&settings);
+    match install_single_version(app_handle.clone(), &settings, installation.name.clone(), &mut report).await {
         Ok(_) => {
+            write_install_report(&app_handle, &settings, report, &Ok(()));
             emit_log_message(&app_handle, MessageLevel::Success,
                 rust_i18n::t!("gui.fix.repair_success", version = installation.name.clone()).to_string());
 
             info!("Successfully fixed installation {}", id);
         }
+        Err(_) if idf_im_lib::cancellation::is_cancelled() => {
+            return Err(finish_cancelled(&app_handle, &settings, report, Some(installation.name.clone())));
+        }
         Err(e) => {
             let error_msg = rust_i18n::t!("gui.fix.repair_failed_detail", error = e.to_string()).to_string();
             error!("{}", error_msg);
+            write_install_report(&app_handle, &settings, report, &Err(error_msg.clone()));
 
             emit_installation_event(&app_handle, InstallationProgress {
                 stage: InstallationStage::Error,
@@ -1368,6 +1613,9 @@ pub async fn start_offline_installation(app_handle: AppHandle, archives: Vec<Str
     let total_archives = archives.len();
 
     for (archive_index, archive) in archives.iter().enumerate() {
+        if idf_im_lib::cancellation::is_cancelled() {
+            return Err(end_cancelled(&app_handle, None));
+        }
         let archive_path = std::path::PathBuf::from(archive);
 
         // Check archive exists
@@ -1581,6 +1829,9 @@ pub async fn start_offline_installation(app_handle: AppHandle, archives: Vec<Str
         // Process each IDF version
         let versions = settings.idf_versions.clone().unwrap_or_default();
         for (version_index, idf_version) in versions.iter().enumerate() {
+            if idf_im_lib::cancellation::is_cancelled() {
+                return Err(end_cancelled(&app_handle, Some(idf_version.clone())));
+            }
             let version_progress_start = ((archive_index * 90 + 40) / total_archives) as u32;
             let version_progress_end = ((archive_index * 90 + 85) / total_archives) as u32;
             let version_progress_range = version_progress_end - version_progress_start;
@@ -1593,11 +1844,11 @@ pub async fn start_offline_installation(app_handle: AppHandle, archives: Vec<Str
                 version: Some(idf_version.clone()),
             });
 
-            let paths = match settings.get_version_paths(idf_version) {
+            let (snapshot, paths) = match settings.get_version_paths(idf_version) {
                 Ok(paths) => {
                     emit_log_message(&app_handle, MessageLevel::Info,
                         rust_i18n::t!("gui.offline.version_paths_configured", version = idf_version, path = paths.idf_path.display().to_string()).to_string());
-                    paths
+                    (idf_im_lib::install_manifest::PreInstallSnapshot::take(&paths), paths)
                 }
                 Err(err) => {
                     let error_msg = rust_i18n::t!("gui.offline.path_config_failed_detail", error = err.to_string()).to_string();
@@ -1614,6 +1865,10 @@ pub async fn start_offline_installation(app_handle: AppHandle, archives: Vec<Str
                 }
             };
 
+            if let Err(err) = idf_im_lib::hooks::run_hooks(HookStage::PreInstall, &settings, &paths) {
+                set_installation_status(&app_handle, false)?;
+                return Err(err.to_string());
+            }
             settings.idf_path = Some(paths.idf_path.clone());
             idf_im_lib::add_path_to_path(paths.idf_path.to_str().unwrap());
 
@@ -1661,11 +1916,14 @@ pub async fn start_offline_installation(app_handle: AppHandle, archives: Vec<Str
             });
 
             let export_vars = match setup_tools(&app_handle, &settings, &paths.idf_path, &paths.actual_version, Some(offline_archive_dir.path())).await {
-                Ok(vars) => {
+                Ok((vars, _)) => {
                     emit_log_message(&app_handle, MessageLevel::Success,
                         rust_i18n::t!("gui.offline.tools_configured").to_string());
                     vars
                 }
+                Err(_) if idf_im_lib::cancellation::is_cancelled() => {
+                    return Err(end_cancelled(&app_handle, Some(idf_version.clone())));
+                }
                 Err(err) => {
                     let error_msg = rust_i18n::t!("gui.offline.tools_setup_failed", error = err.to_string()).to_string();
                     error!("{}", error_msg);
@@ -1699,6 +1957,16 @@ pub async fn start_offline_installation(app_handle: AppHandle, archives: Vec<Str
                 paths.python_venv_path.to_str(),
                 None,
             );
+            let manifest_warnings = idf_im_lib::idf_tools::tool_version_override_warnings(
+                &settings.tool_version_overrides.clone().unwrap_or_default(),
+            );
+            if let Err(err) = idf_im_lib::install_manifest::record_version_manifest(&paths, &snapshot, &settings, &manifest_warnings, &[]) {
+                warn!("Failed to record install manifest: {}", err);
+            }
+            if let Err(err) = idf_im_lib::hooks::run_hooks(HookStage::PostInstall, &settings, &paths) {
+                emit_log_message(&app_handle, MessageLevel::Warning,
+                    rust_i18n::t!("gui.installation.post_install_hook_failed", error = err.to_string()).to_string());
+            }
 
             emit_log_message(&app_handle, MessageLevel::Success,
                 rust_i18n::t!("gui.offline.version_configured", version = idf_version).to_string());
@@ -4,3 +4,6 @@ pub mod installation;
 pub mod settings;
 pub mod idf_tools;
 pub mod version_management;
+pub mod command_palette;
+pub mod recovery;
+pub mod api_version;
@@ -1,4 +1,4 @@
-use crate::gui::{app_state::get_settings_non_blocking, ui::send_message};
+use crate::gui::{app_state::{get_settings_non_blocking, update_settings}, ui::send_message};
 use idf_im_lib;
 use log::{error, warn};
 use log4rs::encode::json;
@@ -159,3 +159,38 @@ pub fn python_install(app_handle: AppHandle) -> bool {
         }
     }
 }
+
+/// Tools found on the PATH which can be used instead of downloading them, with the ones selected
+#[tauri::command]
+pub async fn get_system_tools(app_handle: AppHandle) -> Result<Value, String> {
+    let settings = get_settings_non_blocking(&app_handle)?;
+    let (detected, selected) = tokio::task::spawn_blocking(move || {
+        (
+            idf_im_lib::system_tools::detect(&settings),
+            idf_im_lib::system_tools::selected(&settings),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    let tools: Vec<Value> = detected
+        .iter()
+        .map(|tool| {
+            json!({
+                "name": tool.name,
+                "path": tool.path,
+                "version": tool.version,
+                "minimum_version": tool.minimum_version,
+                "acceptable": tool.is_acceptable(),
+                "selected": selected.iter().any(|s| s.name == tool.name),
+            })
+        })
+        .collect();
+    let configured = get_settings_non_blocking(&app_handle)?.use_system_tools.is_some();
+    Ok(json!({ "configured": configured, "tools": tools }))
+}
+
+/// Selects the tools of the system used instead of downloading them
+#[tauri::command]
+pub fn set_use_system_tools(app_handle: AppHandle, names: Vec<String>) -> Result<(), String> {
+    update_settings(&app_handle, |settings| settings.use_system_tools = Some(names))
+}
@@ -0,0 +1,135 @@
+use idf_im_lib::install_lock::LockError;
+use idf_im_lib::settings::Settings;
+use log::info;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::gui::app_state::{
+  acquire_install_lock, get_settings_non_blocking, is_installation_in_progress, update_settings, AppState,
+  WizardData,
+};
+
+/// Outcome of a recovery action, shown to the user
+#[derive(Serialize)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+pub struct RecoveryResult {
+  pub message: String,
+  #[cfg_attr(feature = "ts-bindings", ts(type = "number"))]
+  pub bytes_reclaimed: u64,
+  /// `bytes_reclaimed` formatted for the selected language and size units
+  pub reclaimed_text: String,
+}
+
+impl RecoveryResult {
+  fn new(message: String, bytes_reclaimed: u64) -> Self {
+    Self {
+      message,
+      bytes_reclaimed,
+      reclaimed_text: idf_im_lib::display_format::format_size(bytes_reclaimed),
+    }
+  }
+}
+
+/// The recovery actions would break an installation running in this window
+fn ensure_not_installing(app_handle: &AppHandle) -> Result<(), String> {
+  if is_installation_in_progress(app_handle) {
+    return Err(rust_i18n::t!("gui.recovery.installation_running").to_string());
+  }
+  Ok(())
+}
+
+/// Removes the half extracted tools, tool backups and partly restored snapshots, whatever their age
+#[tauri::command]
+pub async fn recovery_clear_staging(app_handle: AppHandle) -> Result<RecoveryResult, String> {
+  ensure_not_installing(&app_handle)?;
+  let _lock = acquire_install_lock("eim gui recovery")?;
+  let (count, reclaimed) = tokio::task::spawn_blocking(idf_im_lib::recovery::clear_staging)
+    .await
+    .map_err(|err| err.to_string())?;
+  Ok(RecoveryResult::new(rust_i18n::t!("gui.recovery.staging_cleared", count = count).to_string(), reclaimed))
+}
+
+/// Removes the installer lock left behind by a process which is not running anymore
+#[tauri::command]
+pub fn recovery_release_stale_lock(app_handle: AppHandle) -> Result<RecoveryResult, String> {
+  ensure_not_installing(&app_handle)?;
+  let message = match idf_im_lib::install_lock::release_stale() {
+    Ok(Some(holder)) => rust_i18n::t!("gui.recovery.lock_released", holder = holder.to_string()),
+    Ok(None) => rust_i18n::t!("gui.recovery.no_lock"),
+    Err(LockError::Held(holder)) => {
+      return Err(rust_i18n::t!("gui.recovery.lock_alive", holder = holder.to_string()).to_string())
+    }
+    Err(err) => return Err(err.to_string()),
+  };
+  Ok(RecoveryResult::new(message.to_string(), 0))
+}
+
+/// Forgets the wizard selections, the settings loaded in this window and the interrupted installation
+#[tauri::command]
+pub fn recovery_reset_wizard(app_handle: AppHandle) -> Result<RecoveryResult, String> {
+  ensure_not_installing(&app_handle)?;
+  update_settings(&app_handle, |settings| *settings = Settings::default())?;
+  let app_state = app_handle.state::<AppState>();
+  *app_state.wizard_data.lock().map_err(|_| "Lock error".to_string())? = WizardData::default();
+  idf_im_lib::interrupted_install::mark_finished();
+  info!("Reset the wizard state");
+  Ok(RecoveryResult::new(rust_i18n::t!("gui.recovery.wizard_reset").to_string(), 0))
+}
+
+/// Deletes the downloaded tool archives, which are downloaded again when needed
+#[tauri::command]
+pub async fn recovery_purge_cache(app_handle: AppHandle) -> Result<RecoveryResult, String> {
+  ensure_not_installing(&app_handle)?;
+  let settings = get_settings_non_blocking(&app_handle)?;
+  let _lock = acquire_install_lock("eim gui recovery")?;
+  let reclaimed = tokio::task::spawn_blocking(move || idf_im_lib::recovery::purge_download_cache(&settings))
+    .await
+    .map_err(|err| err.to_string())?;
+  Ok(RecoveryResult::new(rust_i18n::t!("gui.recovery.cache_purged").to_string(), reclaimed))
+}
+
+/// A crash since the GUI or the CLI last ran, offered for a support bundle at startup
+#[derive(Serialize)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+pub struct CrashSummary {
+  pub count: usize,
+  pub time: String,
+  pub binary: String,
+  pub message: String,
+}
+
+/// Summary of the crash reports not offered yet, `None` when eim didn't crash
+#[tauri::command]
+pub fn get_pending_crashes() -> Option<CrashSummary> {
+  let reports = idf_im_lib::crash_report::pending_reports();
+  reports.last().map(|last| CrashSummary {
+    count: reports.len(),
+    time: last.report.time.clone(),
+    binary: last.report.binary.clone(),
+    message: last.report.message.clone(),
+  })
+}
+
+/// Writes the pending crash reports, the logs and the status into a zip in the downloads folder
+/// and returns its path
+#[tauri::command]
+pub async fn create_crash_bundle(app_handle: AppHandle) -> Result<String, String> {
+  let settings = get_settings_non_blocking(&app_handle)?;
+  let dir = dirs::download_dir()
+    .or_else(idf_im_lib::get_log_directory)
+    .ok_or("No folder to write the support bundle to")?;
+  let reports = idf_im_lib::crash_report::pending_reports();
+  let bundle = tokio::task::spawn_blocking(move || {
+    idf_im_lib::crash_report::bundle_pending_reports(&dir, &reports, &settings)
+  })
+  .await
+  .map_err(|err| err.to_string())?
+  .map_err(|err| err.to_string())?;
+  Ok(bundle.to_string_lossy().to_string())
+}
+
+/// Doesn't offer the pending crash reports again, they stay in the log folder
+#[tauri::command]
+pub fn dismiss_crashes() {
+  idf_im_lib::crash_report::mark_seen(&idf_im_lib::crash_report::pending_reports());
+}
@@ -44,6 +44,9 @@ pub fn load_settings(app_handle: AppHandle, path: &str) -> Result<(), String> {
         return Err(e);
     }
 
+    if let Ok(settings) = get_locked_settings(&app_handle) {
+        idf_im_lib::logging::apply_log_level_setting(settings.log_level.as_deref());
+    }
     send_message(&app_handle, t!("gui.settings.loaded_successfully", path = path).to_string(), "info".to_string());
     Ok(())
 }
@@ -237,6 +240,71 @@ pub async fn get_idf_versions(app_handle: AppHandle, include_unstable: bool) ->
   available_versions
 }
 
+/// Gets the chip targets, their revisions and the tool versions an IDF version supports
+#[tauri::command]
+pub async fn get_support_matrix(
+  app_handle: AppHandle,
+  version: String,
+) -> Result<idf_im_lib::support_matrix::SupportMatrix, String> {
+  let settings = get_settings_non_blocking(&app_handle)?;
+  idf_im_lib::support_matrix::get_support_matrix(&version, settings.idf_mirror.as_deref())
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Estimates the download and disk size of installing the selected versions with the current settings
+#[tauri::command]
+pub async fn get_install_size_estimate(app_handle: AppHandle) -> Result<serde_json::Value, String> {
+  let settings = get_settings_non_blocking(&app_handle)?;
+  let mut estimates = Vec::new();
+  for version in settings.idf_versions.clone().unwrap_or_default() {
+    estimates.push(
+      idf_im_lib::size_estimate::estimate(&settings, &version)
+        .await
+        .map_err(|e| e.to_string())?,
+    );
+  }
+  let (download_bytes, disk_bytes) = idf_im_lib::size_estimate::totals(&estimates);
+  Ok(serde_json::json!({
+    "versions": estimates,
+    "download_bytes": download_bytes,
+    "disk_bytes": disk_bytes,
+    "download_text": idf_im_lib::display_format::format_size(download_bytes),
+    "disk_text": idf_im_lib::display_format::format_size(disk_bytes),
+  }))
+}
+
+/// Gets the toolchains per target, debug tools, Python features and extras of a version with their sizes
+#[tauri::command]
+pub async fn get_component_tree(
+  app_handle: AppHandle,
+  version: String,
+  targets: Vec<String>,
+) -> Result<idf_im_lib::component_tree::ComponentTree, String> {
+  let settings = get_settings_non_blocking(&app_handle)?;
+  idf_im_lib::component_tree::component_tree(&settings, &version, targets)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Sets the optional components (examples, docs, qemu) to install
+#[tauri::command]
+pub fn set_components(app_handle: AppHandle, components: Vec<String>) -> Result<(), String> {
+  info!("Setting components: {:?}", components);
+  let mut result = Ok(());
+  update_settings(&app_handle, |settings| {
+      settings.components = Some(components);
+      result = idf_im_lib::components::apply_to_settings(settings).map_err(|e| e.to_string());
+  })?;
+  result
+}
+
+/// Answers the dialog opened when the proxy rejects its password during the downloads, `None` gives up
+#[tauri::command]
+pub fn submit_proxy_password(password: Option<String>) {
+  crate::gui::ui::answer_proxy_password(password);
+}
+
 /// Sets the selected IDF versions
 #[tauri::command]
 pub fn set_versions(app_handle: AppHandle, versions: Vec<String>) -> Result<(), String> {
@@ -534,6 +602,12 @@ pub async fn is_path_idf_directory(_app_handle: AppHandle, path: String) -> bool
    is_valid_idf_directory(&path)
 }
 
+/// Problems of an installation path, shown inline before the installation starts
+#[tauri::command]
+pub fn validate_install_path(path: String) -> Vec<idf_im_lib::path_validation::PathWarning> {
+  idf_im_lib::path_validation::validate_install_path(Path::new(&path))
+}
+
 /// Resets the settings to their default values
 #[tauri::command]
 pub fn reset_settings_to_default(app_handle: AppHandle) -> Result<(), String> {
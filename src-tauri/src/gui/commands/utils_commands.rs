@@ -2,6 +2,7 @@ use gui::ui::send_message;
 use idf_im_lib::{self, ensure_path};
 use idf_im_lib::telemetry::track_event;
 use log::{error, info};
+use serde::Serialize;
 use serde_json::{json,Value};
 use tauri_plugin_store::StoreExt;
 use std::fs;
@@ -16,26 +17,20 @@ use anyhow::{Result};
 use crate::gui;
 use crate::gui::utils::is_path_empty_or_nonexistent;
 
-#[cfg(windows)]
-use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
-#[cfg(windows)]
-use winapi::um::securitybaseapi::GetTokenInformation;
-#[cfg(windows)]
-use winapi::um::winnt::{TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
-#[cfg(windows)]
-use winapi::um::handleapi::CloseHandle;
-#[cfg(windows)]
-use winapi::shared::minwindef::{DWORD, FALSE};
-#[cfg(windows)]
-use std::mem;
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
 const EIM_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Themes of the GUI, `system` follows the appearance of the OS
+const THEMES: [&str; 3] = ["light", "dark", "system"];
+const DEFAULT_THEME: &str = "system";
+
 #[tauri::command]
 pub async fn fetch_json_from_url(url: String) -> Result<Value, String> {
-    let response = reqwest::get(&url)
+    let response = idf_im_lib::http_client::client()
+        .get(&url)
+        .send()
         .await
         .map_err(|e| e.to_string())?;
 
@@ -236,22 +231,131 @@ pub fn get_app_settings(app_handle: AppHandle) -> Value { // TODO: persist
                 .unwrap_or(Value::Bool(false))
                 .clone();
 
+            let theme = store.get("theme")
+                .unwrap_or(Value::String(DEFAULT_THEME.to_string()))
+                .clone();
+
             json!({
                 "first_run": first_run,
                 "skip_welcome": skip_welcome,
-                "usage_statistics": usage_statistics
+                "usage_statistics": usage_statistics,
+                "theme": theme
             })
         }
         Err(_) => {
             // If store doesn't exist or can't be loaded, return defaults
             json!({
                 "first_run": true,
-                "skip_welcome": false
+                "skip_welcome": false,
+                "theme": DEFAULT_THEME
             })
         }
     }
 }
 
+/// Where the GUI starts, see [`get_app_state`].
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum AppStartState {
+    /// Nothing installed yet, the wizard comes first
+    FirstRun,
+    /// Versions are installed, the version manager comes first
+    Installed { count: usize },
+    /// An installation runs in this window or in another eim process
+    Installing { holder: Option<String> },
+    /// An installation was cut short and can be continued with the same settings, `versions`
+    /// are the ones left to install
+    Resumable { versions: Vec<String>, installed: Vec<String>, started: String, path: Option<String> },
+}
+
+/// Tells the frontend where to route on start instead of always opening the wizard.
+#[tauri::command]
+pub fn get_app_state(app_handle: AppHandle) -> AppStartState {
+  if gui::app_state::is_installation_in_progress(&app_handle) {
+    return AppStartState::Installing { holder: None };
+  }
+  if let Some(holder) = idf_im_lib::install_lock::read_lock_holder()
+    .filter(|holder| holder.pid != std::process::id() && idf_im_lib::install_lock::is_process_alive(holder.pid))
+  {
+    return AppStartState::Installing { holder: Some(holder.to_string()) };
+  }
+  if let Some(marker) = idf_im_lib::interrupted_install::find_interrupted() {
+    return AppStartState::Resumable {
+      versions: marker.remaining_versions(),
+      installed: marker.installed_versions(),
+      started: idf_im_lib::display_format::format_timestamp(&marker.started),
+      path: marker.settings.path.map(|p| p.to_string_lossy().into_owned()),
+    };
+  }
+  match idf_im_lib::version_manager::get_esp_ide_config() {
+    Ok(config) if !config.idf_installed.is_empty() => AppStartState::Installed { count: config.idf_installed.len() },
+    _ => AppStartState::FirstRun,
+  }
+}
+
+/// Loads the settings of the interrupted installation, the frontend then starts it again.
+#[tauri::command]
+pub fn resume_installation(app_handle: AppHandle) -> Result<Vec<String>, String> {
+  let marker = idf_im_lib::interrupted_install::find_interrupted()
+    .ok_or_else(|| rust_i18n::t!("gui.resume.nothing_to_resume").to_string())?;
+  let settings = idf_im_lib::interrupted_install::prepare_resume(&marker).map_err(|e| e.to_string())?;
+  gui::app_state::update_settings(&app_handle, |current| *current = settings)?;
+  info!("Resuming the installation of {:?} started at {}", marker.versions, marker.started);
+  Ok(marker.versions)
+}
+
+/// Forgets the interrupted installation, what it left behind stays on disk.
+#[tauri::command]
+pub fn discard_interrupted_installation() {
+  idf_im_lib::interrupted_install::mark_finished();
+}
+
+/// Leftovers of failed installations, offered for cleanup on the welcome screen.
+#[tauri::command]
+pub async fn find_install_remnants() -> Result<Vec<idf_im_lib::remnants::Remnant>, String> {
+  tokio::task::spawn_blocking(|| idf_im_lib::remnants::find_remnants(idf_im_lib::remnants::DEFAULT_MIN_AGE))
+    .await
+    .map_err(|err| err.to_string())
+}
+
+/// Removes the leftovers of failed installations and returns the bytes reclaimed.
+#[tauri::command]
+pub async fn clean_install_remnants() -> Result<u64, String> {
+  let _lock = gui::app_state::acquire_install_lock("eim gui cleanup")?;
+  tokio::task::spawn_blocking(move || {
+    let remnants = idf_im_lib::remnants::find_remnants(idf_im_lib::remnants::DEFAULT_MIN_AGE);
+    let reclaimed = idf_im_lib::remnants::remove_remnants(&remnants);
+    info!("Cleaned up {} remnants of failed installations, {} bytes reclaimed", remnants.len(), reclaimed);
+    reclaimed
+  })
+  .await
+  .map_err(|err| err.to_string())
+}
+
+/// Log file of the newest install, upgrade or repair run, to attach to an issue
+#[tauri::command]
+pub fn get_last_run_log() -> Option<PathBuf> {
+  idf_im_lib::logging::last_run_log()
+}
+
+/// Quick health check of the selected installation, run in the background on startup.
+#[tauri::command]
+pub async fn check_installation_health() -> Result<Option<idf_im_lib::health_check::HealthReport>, String> {
+  tokio::task::spawn_blocking(idf_im_lib::health_check::check_selected)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+/// Other ESP-IDF toolchains on PATH shadowing the tools of the selected installation, checked on startup.
+#[tauri::command]
+pub async fn check_path_conflicts() -> Result<Vec<idf_im_lib::path_conflicts::PathConflict>, String> {
+  tokio::task::spawn_blocking(idf_im_lib::path_conflicts::check_selected)
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| err.to_string())
+}
+
 #[tauri::command]
 pub async fn save_app_settings(app_handle: AppHandle, firstRun: bool, skipWelcome: bool, usageStatistics: bool) {
   let config_dir = dirs::config_dir()
@@ -284,50 +388,44 @@ pub async fn save_app_settings(app_handle: AppHandle, firstRun: bool, skipWelcom
     }
 }
 
-#[cfg(windows)]
-fn is_elevated() -> Result<bool, Box<dyn std::error::Error>> {
-    unsafe {
-        let process = GetCurrentProcess();
-        let mut token = std::ptr::null_mut();
-
-        // Open process token
-        if OpenProcessToken(process, TOKEN_QUERY, &mut token) == FALSE {
-            return Err("Failed to open process token".into());
-        }
-
-        // Get token elevation information
-        let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
-        let mut return_length: DWORD = 0;
-
-        let result = GetTokenInformation(
-            token,
-            TokenElevation,
-            &mut elevation as *mut _ as *mut _,
-            mem::size_of::<TOKEN_ELEVATION>() as DWORD,
-            &mut return_length,
-        );
-
-        // Clean up token handle
-        CloseHandle(token);
-
-        if result == FALSE {
-            return Err("Failed to get token information".into());
-        }
+/// Saves the theme of the GUI and applies it to the window decorations, `system` lets them
+/// follow the OS again.
+#[tauri::command]
+pub fn set_theme(app_handle: AppHandle, window: tauri::Window, theme: String) -> Result<(), String> {
+  if !THEMES.contains(&theme.as_str()) {
+    return Err(format!("Unknown theme '{}', expected one of {}", theme, THEMES.join(", ")));
+  }
+  let window_theme = match theme.as_str() {
+    "light" => Some(tauri::Theme::Light),
+    "dark" => Some(tauri::Theme::Dark),
+    _ => None,
+  };
+  if let Err(e) = window.set_theme(window_theme) {
+    error!("Failed to set the window theme: {}", e);
+  }
 
-        Ok(elevation.TokenIsElevated != 0)
-    }
+  let config_dir = dirs::config_dir()
+    .ok_or("Failed to get config directory")?
+    .join("eim");
+  ensure_path(config_dir.to_str().unwrap()).map_err(|e| e.to_string())?;
+  let store = app_handle.store_builder(config_dir.join("eim.json")).build().map_err(|e| e.to_string())?;
+  store.set("theme".to_string(), Value::String(theme.clone()));
+  store.save().map_err(|e| e.to_string())?;
+  info!("Theme set to {}", theme);
+  Ok(())
 }
 
-#[cfg(not(windows))]
-fn is_elevated() -> Result<bool, Box<dyn std::error::Error>> {
-    Ok(false)
+/// Appearance of the OS, `light` or `dark`, changes are sent as `system-theme-changed` events.
+#[tauri::command]
+pub fn get_system_theme(window: tauri::Window) -> String {
+  gui::ui::theme_name(window.theme().unwrap_or(tauri::Theme::Light)).to_string()
 }
 
 #[tauri::command]
 pub fn check_elevation() -> Result<bool, String> {
     match std::env::consts::OS {
       "windows" => {
-        match is_elevated() {
+        match idf_im_lib::utils::is_elevated() {
           Ok(elevated) => Ok(elevated),
           Err(err) => {
             error!("Failed to check elevation: {}", err);
@@ -348,6 +446,16 @@ pub async fn install_drivers() -> Result<(), String> {
     match std::env::consts::OS {
       "windows" => {
         info!("Installing drivers...");
+        // the GUI runs as the user, only the driver installation is elevated
+        if !idf_im_lib::utils::is_elevated().unwrap_or(false) {
+          return tokio::task::spawn_blocking(|| idf_im_lib::drivers::run_elevated_driver_install(false))
+            .await
+            .map_err(|err| err.to_string())?
+            .map_err(|err| {
+              error!("Failed to install drivers: {}", err);
+              format!("Failed to install drivers: {}", err)
+            });
+        }
         match idf_im_lib::install_drivers().await {
           Ok(_) => {
             info!("Drivers installed successfully.");
@@ -365,6 +473,60 @@ pub async fn install_drivers() -> Result<(), String> {
     }
 }
 
+/// Lists connected Espressif USB devices together with their driver status
+#[tauri::command]
+pub fn detect_usb_devices() -> Result<Vec<idf_im_lib::drivers::DetectedUsbDevice>, String> {
+    idf_im_lib::drivers::detect_usb_devices().map_err(|err| {
+        error!("Failed to detect USB devices: {}", err);
+        format!("Failed to detect USB devices: {}", err)
+    })
+}
+
+/// Lists the serial ports of connected boards with their driver and permission status
+#[tauri::command]
+pub fn list_serial_devices() -> Result<Vec<idf_im_lib::serial_devices::SerialDevice>, String> {
+    idf_im_lib::serial_devices::list_serial_devices().map_err(|err| {
+        error!("Failed to list serial devices: {}", err);
+        format!("Failed to list serial devices: {}", err)
+    })
+}
+
+/// Builds and flashes the hello_world example to a connected board and checks its boot log
+#[tauri::command]
+pub async fn run_flash_test(
+    app_handle: AppHandle,
+    version: Option<String>,
+    port: Option<String>,
+) -> Result<idf_im_lib::flash_test::FlashTestReport, String> {
+    let installation = idf_im_lib::version_manager::find_installation(version.as_deref())
+        .map_err(|err| err.to_string())?;
+    let handle = app_handle.clone();
+    tokio::task::spawn_blocking(move || {
+        idf_im_lib::flash_test::run_flash_test(&installation, port.as_deref(), None, |step| {
+            let message = match step {
+                idf_im_lib::flash_test::FlashTestStep::DetectChip => rust_i18n::t!("verify.flash.step.detect_chip"),
+                idf_im_lib::flash_test::FlashTestStep::Build => rust_i18n::t!("verify.flash.step.build"),
+                idf_im_lib::flash_test::FlashTestStep::Flash => rust_i18n::t!("verify.flash.step.flash"),
+                idf_im_lib::flash_test::FlashTestStep::ReadBootLog => rust_i18n::t!("verify.flash.step.read_boot_log"),
+            };
+            send_message(&handle, message.to_string(), "info".to_string());
+        })
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| {
+        error!("Flash test failed: {}", err);
+        format!("Flash test failed: {}", err)
+    })
+}
+
+/// Checks DNS, proxy and HTTPS access to every endpoint the installation downloads from
+#[tauri::command]
+pub async fn network_preflight(app_handle: AppHandle) -> Result<Vec<idf_im_lib::network_preflight::EndpointReport>, String> {
+    let settings = gui::app_state::get_settings_non_blocking(&app_handle)?;
+    Ok(idf_im_lib::network_preflight::run_network_preflight(&settings).await)
+}
+
 #[tauri::command]
 pub async fn track_event_command(app_handle: AppHandle,name: &str, additional_data: Option<serde_json::Value>) -> Result<(), String> {
   let app_settings = get_app_settings(app_handle);
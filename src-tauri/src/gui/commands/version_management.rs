@@ -1,5 +1,9 @@
 use idf_im_lib::idf_config::IdfInstallation;
+use idf_im_lib::update_notifications::PatchUpdate;
 use log::{debug, error, info};
+use tauri::AppHandle;
+
+use crate::gui::app_state::{acquire_install_lock, update_settings};
 
 
 #[tauri::command]
@@ -26,6 +30,13 @@ pub fn get_installed_versions() -> Vec<IdfInstallation>{
 #[tauri::command]
 pub fn rename_installation(id: String, new_name: String) -> bool {
   debug!("Renaming installation with id {} to {}", id, new_name);
+  let _lock = match acquire_install_lock("eim gui rename") {
+    Ok(lock) => lock,
+    Err(e) => {
+      error!("{}", e);
+      return false;
+    }
+  };
 
   match idf_im_lib::version_manager::rename_idf_version(&id, new_name) {
     Ok(_) => {
@@ -38,9 +49,58 @@ pub fn rename_installation(id: String, new_name: String) -> bool {
     }
   }
 }
+
+#[tauri::command]
+pub fn set_installation_label(id: String, label: Option<String>) -> bool {
+  debug!("Setting the label of installation {} to {:?}", id, label);
+  let _lock = match acquire_install_lock("eim gui label") {
+    Ok(lock) => lock,
+    Err(e) => {
+      error!("{}", e);
+      return false;
+    }
+  };
+
+  match idf_im_lib::version_manager::set_installation_label(&id, label) {
+    Ok(_) => true,
+    Err(e) => {
+      error!("Failed to set the installation label: {}", e);
+      false
+    }
+  }
+}
+
+/// Makes the installation the default one, activated by `eim activate` and the IDE integrations
+#[tauri::command]
+pub fn select_installation(id: String) -> bool {
+  debug!("Selecting installation {}", id);
+  let _lock = match acquire_install_lock("eim gui select") {
+    Ok(lock) => lock,
+    Err(e) => {
+      error!("{}", e);
+      return false;
+    }
+  };
+
+  match idf_im_lib::version_manager::select_idf_version(&id) {
+    Ok(_) => true,
+    Err(e) => {
+      error!("Failed to select installation: {}", e);
+      false
+    }
+  }
+}
+
 #[tauri::command]
 pub fn remove_installation(id: String) -> bool {
   debug!("Removing installation with id {}", id);
+  let _lock = match acquire_install_lock("eim gui remove") {
+    Ok(lock) => lock,
+    Err(e) => {
+      error!("{}", e);
+      return false;
+    }
+  };
 
   match idf_im_lib::version_manager::remove_single_idf_version(&id, false) {
     Ok(_) => {
@@ -57,6 +117,13 @@ pub fn remove_installation(id: String) -> bool {
 #[tauri::command]
 pub fn purge_all_installations() -> bool {
   debug!("Purging all installations");
+  let _lock = match acquire_install_lock("eim gui purge") {
+    Ok(lock) => lock,
+    Err(e) => {
+      error!("{}", e);
+      return false;
+    }
+  };
 
   match idf_im_lib::version_manager::list_installed_versions() {
       Ok(versions) => {
@@ -92,3 +159,41 @@ pub fn purge_all_installations() -> bool {
       }
   }
 }
+
+/// Point releases superseding installed versions, shown as a banner until muted
+#[tauri::command]
+pub async fn get_patch_updates() -> Vec<PatchUpdate> {
+  match idf_im_lib::update_notifications::pending_updates().await {
+    Ok(updates) => updates,
+    Err(err) => {
+      debug!("Checking for patch releases failed: {}", err);
+      vec![]
+    }
+  }
+}
+
+#[tauri::command]
+pub fn mute_patch_update(version: String) -> bool {
+  match idf_im_lib::update_notifications::mute(&version) {
+    Ok(()) => true,
+    Err(err) => {
+      error!("Failed to mute {}: {}", version, err);
+      false
+    }
+  }
+}
+
+/// Pre-fills the settings with those recorded by an installation, for installing another version the same way
+#[tauri::command]
+pub fn clone_installation_settings(app_handle: AppHandle, id: String) -> Result<(), String> {
+  let recorded = idf_im_lib::install_manifest::recorded_settings_for(&id).map_err(|err| {
+    error!("Failed to load the settings of installation {}: {}", id, err);
+    err.to_string()
+  })?;
+  update_settings(&app_handle, |settings| {
+    recorded.apply_to(settings);
+    settings.idf_versions = None;
+  })?;
+  info!("Settings pre-filled from installation {}", id);
+  Ok(())
+}
@@ -3,9 +3,10 @@ use anyhow::Result;
 use fork::{daemon, Fork};
 use idf_im_lib::{
     add_path_to_path, ensure_path,
+    logging::{init_logging, LogLevels, LoggingOptions, GUI_LOG_FILE_NAME},
     settings::Settings,
 };
-use log::{debug, error, info};
+use log::{debug, info};
 use std::process::Command;
 use std::{
     env,
@@ -21,7 +22,7 @@ pub mod utils;
 
 use app_state::{AppState};
 use ui::{send_message, ProgressBar};
-use commands::{utils_commands::*, prequisites::*, installation::*, settings::*, idf_tools::*, version_management::*};
+use commands::{utils_commands::*, api_version::*, prequisites::*, installation::*, settings::*, idf_tools::*, version_management::*, command_palette::*, recovery::*};
 
 fn prepare_installation_directories(
     app_handle: AppHandle,
@@ -180,58 +181,90 @@ fn is_process_running(pid: u32) -> bool {
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run(leg_level_override: Option<log::LevelFilter>) {
+pub fn run(leg_level_override: Option<log::LevelFilter>, log_levels: Option<String>) {
     // this is here because macos bundled .app does not inherit path
     #[cfg(target_os = "macos")]
     {
         env::set_var("PATH", "/opt/homebrew/bin:/usr/local/bin:/usr/bin:/bin:/usr/sbin:/sbin:/opt/local/bin:/opt/local/sbin");
     }
-    let log_dir = idf_im_lib::get_log_directory().unwrap_or_else(|| {
-        error!("Failed to get log directory.");
-        PathBuf::from("")
-    });
+    let mut logging = LoggingOptions::new(GUI_LOG_FILE_NAME, None);
+    logging.console_level = Some(leg_level_override.unwrap_or(log::LevelFilter::Info));
+    logging.levels.default = Some(leg_level_override.unwrap_or(log::LevelFilter::Info));
+    // tauri and webview internals are noisy below warn
+    logging.levels.modules = vec![
+        ("tauri".to_string(), log::LevelFilter::Warn),
+        ("wry".to_string(), log::LevelFilter::Warn),
+        ("tao".to_string(), log::LevelFilter::Warn),
+    ];
+    if let Some(spec) = log_levels.as_deref() {
+        match spec.parse::<LogLevels>() {
+            Ok(levels) => {
+                logging.levels.default = levels.default.or(logging.levels.default);
+                logging.levels.modules.extend(levels.modules);
+            }
+            Err(e) => eprintln!("Ignoring --log-level: {}", e),
+        }
+    }
+    let log_file = logging.log_file.clone();
+    if let Err(e) = init_logging(logging) {
+        eprintln!("{}", e);
+    }
+    idf_im_lib::crash_report::install("eim-gui", log_file);
+    idf_im_lib::elevation::warn_if_root();
     tauri::Builder::default()
-        .plugin(
-            tauri_plugin_log::Builder::new()
-                .target(tauri_plugin_log::Target::new(
-                    tauri_plugin_log::TargetKind::Stdout,
-                ))
-                .target(tauri_plugin_log::Target::new(
-                    // this actually can not keep pace with the console, so maybe we should disable it for production build
-                    tauri_plugin_log::TargetKind::Webview,
-                ))
-                // Add new file target with path configuration
-                .target(tauri_plugin_log::Target::new(
-                    tauri_plugin_log::TargetKind::Folder {
-                        path: log_dir,
-                        file_name: Some("eim_gui_log".to_string()),
-                    },
-                ))
-                .level(leg_level_override.unwrap_or(log::LevelFilter::Info))
-                .level_for("idf_im_lib", leg_level_override.unwrap_or(log::LevelFilter::Info))
-                .level_for("eim_lib", leg_level_override.unwrap_or(log::LevelFilter::Info))
-                .build(),
-        )
+        // the plugin is kept for its frontend API, the logger itself is the shared one
+        .plugin(tauri_plugin_log::Builder::new().skip_logger().build())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_store::Builder::default().build())
         .setup(|app| {
             let app_state = AppState::default();
             app.manage(app_state);
+            let app_handle = app.handle().clone();
+            idf_im_lib::proxy_auth::set_prompt(Some(std::sync::Arc::new(move |user: &str| {
+                ui::ask_proxy_password(&app_handle, user)
+            })));
             Ok(())
         })
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_opener::init())
+        .on_window_event(|window, event| {
+            match event {
+                // closing the window cancels the running operation instead of orphaning git and pip
+                tauri::WindowEvent::CloseRequested { .. } => {
+                    let app_handle = window.app_handle();
+                    if app_state::is_installation_in_progress(app_handle) {
+                        info!("Window closed during an installation, cancelling it");
+                        idf_im_lib::cancellation::cancel_and_wait();
+                        if let Err(e) = app_state::set_installation_status(app_handle, false) {
+                            log::error!("Failed to update installation status: {}", e);
+                        }
+                    }
+                }
+                tauri::WindowEvent::ThemeChanged(theme) => {
+                    debug!("OS theme changed to {:?}", theme);
+                    ui::emit_system_theme(window.app_handle(), *theme);
+                }
+                _ => {}
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             get_settings,
             check_prequisites,
             install_prerequisites,
             get_prequisites,
+            get_system_tools,
+            set_use_system_tools,
             get_operating_system,
             python_sanity_check,
             python_install,
             get_available_targets,
             set_targets,
             get_idf_versions,
+            get_support_matrix,
+            get_install_size_estimate,
+            get_component_tree,
+            set_components,
+            submit_proxy_password,
             set_versions,
             get_idf_mirror_latency_entries,
             get_idf_mirror_urls,
@@ -251,20 +284,38 @@ pub fn run(leg_level_override: Option<log::LevelFilter>) {
             show_in_folder,
             is_path_empty_or_nonexistent_command,
             is_path_idf_directory,
+            validate_install_path,
             get_app_info,
             get_system_arch,
             get_installed_versions,
             scan_for_archives,
             check_prerequisites_detailed,
             rename_installation,
+            set_installation_label,
             remove_installation,
             purge_all_installations,
             fix_installation,
             get_app_settings,
+            get_app_state,
+            resume_installation,
+            discard_interrupted_installation,
+            find_install_remnants,
+            clean_install_remnants,
+            check_installation_health,
+            check_path_conflicts,
+            recovery_clear_staging,
+            recovery_release_stale_lock,
+            recovery_reset_wizard,
+            recovery_purge_cache,
+            get_last_run_log,
             save_app_settings,
             start_offline_installation,
             check_elevation,
             install_drivers,
+            detect_usb_devices,
+            list_serial_devices,
+            run_flash_test,
+            network_preflight,
             get_system_info,
             cpu_count,
             track_event_command,
@@ -278,6 +329,18 @@ pub fn run(leg_level_override: Option<log::LevelFilter>) {
             set_selected_features_per_version,
             get_selected_features_per_version,
             reset_settings_to_default,
+            get_patch_updates,
+            mute_patch_update,
+            clone_installation_settings,
+            select_installation,
+            search_actions,
+            api_handshake,
+            get_pending_crashes,
+            create_crash_bundle,
+            dismiss_crashes,
+            cancel_operation,
+            set_theme,
+            get_system_theme,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,8 +1,38 @@
+use idf_im_lib::announcements::{AnnounceDecision, Announcer, Milestone};
+use idf_im_lib::transfer_rate::TransferStats;
 use log::{debug, info};
+use once_cell::sync::Lazy;
 use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter}; // dep: fork = "0.1"
 use serde::{Serialize, Deserialize};
 
+/// Minimum time between two progress events of the same channel, 10 events per second
+pub const PROGRESS_EVENT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where the answer of the open proxy password dialog goes
+static PROXY_PASSWORD_ANSWER: Lazy<Mutex<Option<std::sync::mpsc::Sender<Option<String>>>>> =
+    Lazy::new(|| Mutex::new(None));
+
+/// Opens the proxy password dialog and waits until it is answered, the downloads run meanwhile
+/// are paused by the caller.
+pub fn ask_proxy_password(app_handle: &AppHandle, user: &str) -> Option<String> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    *PROXY_PASSWORD_ANSWER.lock().ok()? = Some(sender);
+    let _ = app_handle.emit("proxy-credentials-required", json!({ "user": user }));
+    receiver.recv().ok().flatten()
+}
+
+/// Passes the answer of the proxy password dialog, `None` when it was cancelled.
+pub fn answer_proxy_password(password: Option<String>) {
+    let sender = PROXY_PASSWORD_ANSWER.lock().ok().and_then(|mut answer| answer.take());
+    if let Some(sender) = sender {
+        let _ = sender.send(password);
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum MessageLevel {
@@ -43,17 +73,184 @@ pub struct ToolProgress {
     pub percentage: Option<u32>,
 }
 
+struct ChannelState {
+    key: String,
+    last_emit: Instant,
+    pending: Option<Value>,
+    flush_scheduled: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum ThrottleDecision {
+    Emit(Value),
+    Defer,
+    /// Deferred, and the pending event has to be flushed after the delay
+    DeferAndFlushIn(Duration),
+}
+
+/// Coalesces progress events, so per-chunk updates of downloads and extractions don't flood the webview.
+///
+/// Events of a channel which share a key (same stage and message) are emitted at most once per
+/// `interval`, the latest one winning. An event with a new key is emitted at once, so step
+/// changes are never delayed, and the last event of a burst is flushed after the interval.
+struct EventThrottle {
+    interval: Duration,
+    channels: HashMap<String, ChannelState>,
+}
+
+impl EventThrottle {
+    fn new(interval: Duration) -> Self {
+        Self { interval, channels: HashMap::new() }
+    }
+
+    fn submit(&mut self, channel: &str, key: &str, payload: Value, now: Instant) -> ThrottleDecision {
+        let Some(state) = self.channels.get_mut(channel) else {
+            self.channels.insert(channel.to_string(), ChannelState {
+                key: key.to_string(),
+                last_emit: now,
+                pending: None,
+                flush_scheduled: false,
+            });
+            return ThrottleDecision::Emit(payload);
+        };
+        let elapsed = now.saturating_duration_since(state.last_emit);
+        if state.key != key || elapsed >= self.interval {
+            state.key = key.to_string();
+            state.last_emit = now;
+            state.pending = None;
+            return ThrottleDecision::Emit(payload);
+        }
+        state.pending = Some(payload);
+        if state.flush_scheduled {
+            ThrottleDecision::Defer
+        } else {
+            state.flush_scheduled = true;
+            ThrottleDecision::DeferAndFlushIn(self.interval - elapsed)
+        }
+    }
+
+    fn take_pending(&mut self, channel: &str, now: Instant) -> Option<Value> {
+        let state = self.channels.get_mut(channel)?;
+        state.flush_scheduled = false;
+        let pending = state.pending.take()?;
+        state.last_emit = now;
+        Some(pending)
+    }
+}
+
+static PROGRESS_THROTTLE: Lazy<Mutex<EventThrottle>> =
+    Lazy::new(|| Mutex::new(EventThrottle::new(PROGRESS_EVENT_INTERVAL)));
+
+/// Emits a progress event through the throttle; events of `event_name` with the same `key` are coalesced.
+pub fn emit_coalesced(app_handle: &AppHandle, event_name: &str, key: &str, json_data: Value) {
+    // the lock is held while emitting, so a flush can't overtake a newer event
+    let mut throttle = PROGRESS_THROTTLE.lock().unwrap();
+    match throttle.submit(event_name, key, json_data, Instant::now()) {
+        ThrottleDecision::Emit(payload) => {
+            let _ = app_handle.emit(event_name, payload);
+        }
+        ThrottleDecision::Defer => {}
+        ThrottleDecision::DeferAndFlushIn(delay) => {
+            let app_handle = app_handle.clone();
+            let event_name = event_name.to_string();
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let mut throttle = PROGRESS_THROTTLE.lock().unwrap();
+                if let Some(payload) = throttle.take_pending(&event_name, Instant::now()) {
+                    let _ = app_handle.emit(&event_name, payload);
+                }
+            });
+        }
+    }
+}
+
 /// Emits a message to the frontend
+/// Name of the OS appearance as the frontend themes know it
+pub fn theme_name(theme: tauri::Theme) -> &'static str {
+    match theme {
+        tauri::Theme::Dark => "dark",
+        _ => "light",
+    }
+}
+
+/// Forwards a change of the OS appearance, the frontend follows it when its theme is `system`
+pub fn emit_system_theme(app_handle: &AppHandle, theme: tauri::Theme) {
+    let _ = app_handle.emit("system-theme-changed", json!({ "theme": theme_name(theme) }));
+}
+
 pub fn emit_to_fe(app_handle: &AppHandle, event_name: &str, json_data: Value) {
     let _ = app_handle.emit(event_name, json_data);
 }
 
+static ANNOUNCER: Lazy<Mutex<Announcer>> = Lazy::new(|| Mutex::new(Announcer::default()));
+
+fn milestone(stage: &InstallationStage) -> Milestone {
+    match stage {
+        InstallationStage::Checking => Milestone::Checking,
+        InstallationStage::Prerequisites => Milestone::Prerequisites,
+        InstallationStage::Download => Milestone::Download,
+        InstallationStage::Extract | InstallationStage::Tools => Milestone::Tools,
+        InstallationStage::Python => Milestone::Python,
+        InstallationStage::Configure => Milestone::Configure,
+        InstallationStage::Complete => Milestone::Complete,
+        InstallationStage::Error if idf_im_lib::cancellation::is_cancelled() => Milestone::Cancelled,
+        InstallationStage::Error => Milestone::Failed,
+    }
+}
+
+/// Forgets the milestones announced, called when an installation starts.
+pub fn reset_announcements() {
+    ANNOUNCER.lock().unwrap().reset();
+}
+
+/// Emits the milestone of `stage` for the ARIA live regions of the frontend, once per stage
+/// and version and rate limited, see [`Announcer`].
+fn announce(app_handle: &AppHandle, stage: &InstallationStage, version: Option<&str>) {
+    let mut announcer = ANNOUNCER.lock().unwrap();
+    match announcer.submit(milestone(stage), version, Instant::now()) {
+        AnnounceDecision::Announce(announcement) => {
+            let _ = app_handle.emit("accessibility-announcement", json!(announcement));
+        }
+        AnnounceDecision::Skip | AnnounceDecision::Defer => {}
+        AnnounceDecision::DeferAndFlushIn(delay) => {
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let mut announcer = ANNOUNCER.lock().unwrap();
+                if let Some(announcement) = announcer.take_pending(Instant::now()) {
+                    let _ = app_handle.emit("accessibility-announcement", json!(announcement));
+                }
+            });
+        }
+    }
+}
+
 /// Unified message emitter for all installation events
 pub fn emit_installation_event(
     app_handle: &AppHandle,
     progress: InstallationProgress
 ) {
-    let _ = app_handle.emit("installation-progress", &progress);
+    if matches!(progress.stage, InstallationStage::Error) {
+        crate::gui::app_state::set_run_failed(app_handle);
+    }
+    announce(app_handle, &progress.stage, progress.version.as_deref());
+    let key = format!("{:?}:{}:{}", progress.stage, progress.version.as_deref().unwrap_or_default(), progress.message);
+    emit_coalesced(app_handle, "installation-progress", &key, json!(progress));
+}
+
+/// Emit installation progress of a download or an extraction, with its speed and time left
+/// in `transfer` once they are known
+pub fn emit_transfer_event(
+    app_handle: &AppHandle,
+    progress: InstallationProgress,
+    transfer: Option<TransferStats>,
+) {
+    announce(app_handle, &progress.stage, progress.version.as_deref());
+    let key = format!("{:?}:{}:{}", progress.stage, progress.version.as_deref().unwrap_or_default(), progress.message);
+    let mut payload = json!(progress);
+    payload["transfer_text"] = json!(transfer.as_ref().map(|t| t.to_string()));
+    payload["transfer"] = json!(transfer);
+    emit_coalesced(app_handle, "installation-progress", &key, payload);
 }
 
 /// Emit tool-specific progress
@@ -61,7 +258,8 @@ pub fn emit_tool_event(
     app_handle: &AppHandle,
     tool_progress: ToolProgress
 ) {
-    let _ = app_handle.emit("tool-progress", &tool_progress);
+    let key = format!("{}:{}", tool_progress.tool_name, tool_progress.action);
+    emit_coalesced(app_handle, "tool-progress", &key, json!(tool_progress));
 }
 
 /// Emit log messages (for detailed output)
@@ -133,9 +331,10 @@ impl ProgressBar {
 
     /// Initializes the progress bar display
     pub fn create(&self, message: &str) {
-        emit_to_fe(
+        emit_coalesced(
             &self.app_handle,
             "progress-message",
+            "create",
             json!({
                 "message": message,
                 "status": "info",
@@ -147,9 +346,10 @@ impl ProgressBar {
 
     /// Updates the progress bar with a new percentage and optional message
     pub fn update(&self, percentage: u64, message: Option<&str>) {
-        emit_to_fe(
+        emit_coalesced(
             &self.app_handle,
             "progress-message",
+            message.unwrap_or_default(),
             json!({
                 "percentage": percentage,
                 "message": message.unwrap_or_default(),
@@ -162,9 +362,10 @@ impl ProgressBar {
     /// Completes the progress bar and hides it
     pub fn finish(&self) {
         debug!("finish_progress_bar called");
-        emit_to_fe(
+        emit_coalesced(
             &self.app_handle,
             "progress-message",
+            "finish",
             json!({
                 "message": "",
                 "percentage": 100,
@@ -173,3 +374,31 @@ impl ProgressBar {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_coalesces_events_with_the_same_key() {
+        let mut throttle = EventThrottle::new(Duration::from_millis(100));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert_eq!(throttle.submit("p", "tools", json!(1), at(0)), ThrottleDecision::Emit(json!(1)));
+        assert_eq!(
+            throttle.submit("p", "tools", json!(2), at(10)),
+            ThrottleDecision::DeferAndFlushIn(Duration::from_millis(90))
+        );
+        assert_eq!(throttle.submit("p", "tools", json!(3), at(20)), ThrottleDecision::Defer);
+        // the flush emits only the latest event
+        assert_eq!(throttle.take_pending("p", at(100)), Some(json!(3)));
+        assert_eq!(throttle.take_pending("p", at(100)), None);
+        // a new step is emitted at once and drops the pending update
+        throttle.submit("p", "tools", json!(4), at(120));
+        assert_eq!(throttle.submit("p", "python", json!(5), at(130)), ThrottleDecision::Emit(json!(5)));
+        assert_eq!(throttle.take_pending("p", at(200)), None);
+        // channels are throttled independently
+        assert_eq!(throttle.submit("q", "tools", json!(6), at(130)), ThrottleDecision::Emit(json!(6)));
+    }
+}
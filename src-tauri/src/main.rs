@@ -1,33 +1,23 @@
-// Apply windows_subsystem = "windows" only if "gui" feature is present and not in debug mode
-#![cfg_attr(
-    all(feature = "gui", not(debug_assertions)),
-    windows_subsystem = "windows"
-)]
+// Apply windows_subsystem = "windows" only in release builds
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+//! `eim-gui`, the Tauri GUI. The bundles install it as `eim`, so it also runs the CLI commands
+//! of eim-cli and starts the GUI when run without a command.
 
-#[cfg(feature = "gui")]
 pub mod gui;
 
-#[cfg(feature = "cli")]
 use clap::Parser;
+use eim_cli::cli;
 use idf_im_lib::settings::Settings;
-#[cfg(feature = "cli")]
-use log::{debug, info};
-#[cfg(feature = "cli")]
-pub mod cli;
-
-rust_i18n::i18n!("locales", fallback = "en");
-
-fn set_locale(locale: &Option<String>) {
-    match locale {
-        Some(l) => {
-            rust_i18n::set_locale(l);
-            info!("Set locale to: {}", l);
-        }
-        None => debug!("No locale specified, defaulting to en"),
-    }
+use log::LevelFilter;
+
+rust_i18n::i18n!("../eim-core/locales", fallback = "en");
+
+fn launch_gui(log_level: LevelFilter, log_levels: Option<String>) {
+    gui::run(Some(log_level), log_levels)
 }
 
-#[cfg(all(target_os = "windows", feature = "cli"))]
+#[cfg(target_os = "windows")]
 fn has_console() -> bool {
     use winapi::um::handleapi::INVALID_HANDLE_VALUE;
     use winapi::um::processenv::GetStdHandle;
@@ -39,13 +29,13 @@ fn has_console() -> bool {
     }
 }
 
-#[cfg(all(target_os = "windows", feature = "cli"))]
+#[cfg(target_os = "windows")]
 fn attach_console() -> bool {
     use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
     unsafe { AttachConsole(ATTACH_PARENT_PROCESS) != 0 }
 }
 
-#[cfg(all(target_os = "windows", feature = "cli"))]
+#[cfg(target_os = "windows")]
 fn detach_console() {
     use winapi::um::wincon::{FreeConsole, ATTACH_PARENT_PROCESS};
     unsafe {
@@ -53,7 +43,7 @@ fn detach_console() {
     }
 }
 
-#[cfg(all(target_os = "windows", feature = "gui", feature = "cli"))]
+#[cfg(target_os = "windows")]
 fn setup_interactive_console() -> bool {
     use winapi::um::consoleapi::{AllocConsole, GetConsoleMode, SetConsoleMode};
     use winapi::um::processenv::GetStdHandle;
@@ -81,7 +71,7 @@ fn setup_interactive_console() -> bool {
     true
 }
 
-#[cfg(all(target_os = "windows", feature = "cli"))]
+#[cfg(target_os = "windows")]
 fn is_interactive_command() -> bool {
     let args: Vec<String> = std::env::args().collect();
     if args.len() <= 1 {
@@ -106,12 +96,8 @@ fn is_interactive_command() -> bool {
 
 #[tokio::main]
 async fn main() {
-    #[cfg(not(any(feature = "gui", feature = "cli")))]
-    {
-        eprintln!("Error: Neither GUI nor CLI features are enabled!");
-        return;
-    }
-    #[cfg(all(target_os = "windows", feature = "gui", feature = "cli"))]
+    idf_im_lib::crash_report::run_monitor_if_requested();
+    #[cfg(target_os = "windows")]
     if is_interactive_command() {
         setup_interactive_console()
     } else {
@@ -121,32 +107,12 @@ async fn main() {
         Ok(_) => log::debug!("ESP-IDF JSON initialized successfully."),
         Err(e) => log::warn!("Failed to initialize ESP-IDF JSON: {}. It is possible you will face issues with persistance and IDE integration.", e),
     };
-    #[cfg(not(feature = "gui"))]
-    {
-        let cli = cli::cli_args::Cli::parse();
-        set_locale(&cli.locale);
-
-        match cli::run_cli(cli).await {
-            Ok(_) => std::process::exit(0),
-            Err(e) => {
-                eprintln!("Error executing CLI: {}", e);
-                std::process::exit(1);
-            }
-        }
-    }
-    #[cfg(not(feature = "cli"))]
-    {
-        set_locale(&None);
-        gui::run(None);
-    }
-    // both GUI and CLI features are enabled
     #[cfg(target_os = "windows")]
     let mut console_attached_or_allocated = false;
 
-    let has_args = std::env::args().len() > 1;
-
     #[cfg(target_os = "windows")]
     {
+        let has_args = std::env::args().len() > 1;
         if has_args {
             let has_existing_console = has_console();
             if !has_existing_console {
@@ -163,23 +129,21 @@ async fn main() {
         }
     }
     let cli = cli::cli_args::Cli::parse();
-    set_locale(&cli.locale);
+    eim_cli::set_locale(&cli.locale);
+    let json_output = cli.json;
 
-    let result = cli::run_cli(cli).await; // Run the GUI by default if no arguments are provided
+    let result = cli::run_cli(cli, Some(launch_gui)).await; // Run the GUI by default if no arguments are provided
 
     #[cfg(target_os = "windows")]
     if console_attached_or_allocated {
         println!("Pressing Enter to exit...");
         detach_console();
     } else {
-        debug!("This is the end...");
+        log::debug!("This is the end...");
     }
     match result {
         Ok(_) => std::process::exit(0),
-        Err(e) => {
-          eprintln!("Error executing CLI: {}", e);
-          std::process::exit(1);
-        }
+        Err(e) => std::process::exit(cli::report_error(&e, json_output)),
     }
 
 }
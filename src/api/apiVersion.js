@@ -0,0 +1,2 @@
+// Generated by `cargo xtask bindings` from API_VERSION in src-tauri/src/gui/commands/api_version.rs, do not edit
+export const API_VERSION = 1;
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiInfo } from "./ApiInfo";
+
+/**
+ * Result of [`api_handshake`]
+ */
+export type ApiHandshake = { compatible: boolean, backend: ApiInfo, 
+/**
+ * Which side is outdated, `frontend` or `backend`, when not compatible
+ */
+outdated: string | null, };
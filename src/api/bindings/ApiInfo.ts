@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What each side reports in the startup handshake
+ */
+export type ApiInfo = { apiVersion: number, appVersion: string, };
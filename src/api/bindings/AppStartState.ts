@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where the GUI starts, see [`get_app_state`].
+ */
+export type AppStartState = { "state": "first_run" } | { "state": "installed", count: number, } | { "state": "installing", holder: string | null, } | { "state": "resumable", versions: Array<string>, installed: Array<string>, started: string, path: string | null, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where the copy shadowing a tool of eim comes from, to tell the user what to remove.
+ */
+export type ConflictSource = { "kind": "msys2" } | { "kind": "platform_io" } | { "kind": "other_installation", name: string, } | { "kind": "legacy_installer" } | { "kind": "other" };
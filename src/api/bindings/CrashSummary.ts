@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A crash since the GUI or the CLI last ran, offered for a support bundle at startup
+ */
+export type CrashSummary = { count: number, time: string, binary: string, message: string, };
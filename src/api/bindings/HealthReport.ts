@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Result of the quick startup check of the default installation.
+ */
+export type HealthReport = { id: string, name: string, 
+/**
+ * Empty when the installation is healthy
+ */
+problems: Array<string>, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type IdfInstallation = { activationScript: string, id: string, idfToolsPath: string, name: string, path: string, python: string, 
+/**
+ * ESP-IDF version of an installation named with `--version-name`, e.g. a second copy of
+ * v5.2 named `customer-a`; not set when the name is the version
+ */
+idfVersion: string | null, 
+/**
+ * Free-form note set with `eim label`, e.g. the product the installation is kept for
+ */
+label: string | null, };
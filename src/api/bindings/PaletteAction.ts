@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * An operation the command palette (Ctrl+K) can run.
+ * 
+ * The frontend runs it by `kind`: `install_version`, `repair`, `select`, `open_terminal`,
+ * `open_logs`, `version_management` or `new_installation`.
+ */
+export type PaletteAction = { kind: string, title: string, 
+/**
+ * Version to install, id of the installation or activation script, depending on `kind`
+ */
+argument: string | null, };
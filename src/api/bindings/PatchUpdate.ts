@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A point release of the same release series superseding an installed version.
+ */
+export type PatchUpdate = { 
+/**
+ * Id of the installation in eim_idf.json
+ */
+installation_id: string, installation_name: string, 
+/**
+ * Installed version, e.g. `v5.3.1`
+ */
+installed: string, 
+/**
+ * Newest point release of the series, e.g. `v5.3.3`
+ */
+available: string, };
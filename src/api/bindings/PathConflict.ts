@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConflictSource } from "./ConflictSource";
+
+/**
+ * A `PATH` entry whose copy of a tool is used instead of the one of the installation.
+ */
+export type PathConflict = { tool: string, 
+/**
+ * The copy the shell runs
+ */
+shadowing: string, 
+/**
+ * The `PATH` entry it is in
+ */
+entry: string, 
+/**
+ * The copy of the installation, which is not run
+ */
+installation: string, 
+/**
+ * Whether the directory of the installation copy is on `PATH`, behind `entry`
+ */
+installation_on_path: boolean, source: ConflictSource, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PathWarningKind } from "./PathWarningKind";
+import type { Severity } from "./Severity";
+
+/**
+ * A problem of an installation path, shown next to the path before installing.
+ */
+export type PathWarning = { kind: PathWarningKind, severity: Severity, message: string, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Outcome of a recovery action, shown to the user
+ */
+export type RecoveryResult = { message: string, bytes_reclaimed: number, 
+/**
+ * `bytes_reclaimed` formatted for the selected language and size units
+ */
+reclaimed_text: string, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RemnantKind } from "./RemnantKind";
+
+/**
+ * Something a crashed or killed installation left behind.
+ */
+export type Remnant = { path: string, kind: RemnantKind, size: number, };
@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A serial port of a connected board, with everything needed to use it from ESP-IDF.
+ */
+export type SerialDevice = { 
+/**
+ * `/dev/ttyUSB0`, `/dev/cu.usbserial-0001` or `COM3`, `None` while Windows has no driver bound
+ */
+port: string | null, description: string, vid: number | null, pid: number | null, 
+/**
+ * The native USB of an Espressif chip or a USB-UART bridge used on Espressif boards
+ */
+is_espressif: boolean, 
+/**
+ * Driver the device needs on Windows
+ */
+driver: string | null, driver_ok: boolean, 
+/**
+ * Whether the current user may open the port
+ */
+accessible: boolean, 
+/**
+ * What to do to make the device usable, if it is not
+ */
+hint: string | null, };
@@ -0,0 +1,81 @@
+// Typed wrappers of the Tauri commands, with the types `cargo xtask bindings` exports from the Rust
+// payloads into ./bindings. `cargo xtask scaffold tauri-command` appends the ones of new commands.
+import { invoke } from "./invoke.js";
+
+/** @typedef {import("./bindings/AppStartState").AppStartState} AppStartState */
+/** @typedef {import("./bindings/CrashSummary").CrashSummary} CrashSummary */
+/** @typedef {import("./bindings/HealthReport").HealthReport} HealthReport */
+/** @typedef {import("./bindings/IdfInstallation").IdfInstallation} IdfInstallation */
+/** @typedef {import("./bindings/PaletteAction").PaletteAction} PaletteAction */
+/** @typedef {import("./bindings/PatchUpdate").PatchUpdate} PatchUpdate */
+/** @typedef {import("./bindings/PathConflict").PathConflict} PathConflict */
+/** @typedef {import("./bindings/PathWarning").PathWarning} PathWarning */
+/** @typedef {import("./bindings/RecoveryResult").RecoveryResult} RecoveryResult */
+/** @typedef {import("./bindings/Remnant").Remnant} Remnant */
+/** @typedef {import("./bindings/SerialDevice").SerialDevice} SerialDevice */
+
+/** @returns {Promise<AppStartState>} */
+export function getAppState() {
+  return invoke("get_app_state");
+}
+
+/** @returns {Promise<IdfInstallation[]>} */
+export function getInstalledVersions() {
+  return invoke("get_installed_versions");
+}
+
+/** @returns {Promise<PatchUpdate[]>} */
+export function getPatchUpdates() {
+  return invoke("get_patch_updates");
+}
+
+/** @returns {Promise<HealthReport | null>} */
+export function checkInstallationHealth() {
+  return invoke("check_installation_health");
+}
+
+/** @returns {Promise<PathConflict[]>} */
+export function checkPathConflicts() {
+  return invoke("check_path_conflicts");
+}
+
+/** @returns {Promise<CrashSummary | null>} */
+export function getPendingCrashes() {
+  return invoke("get_pending_crashes");
+}
+
+/** @returns {Promise<Remnant[]>} */
+export function findInstallRemnants() {
+  return invoke("find_install_remnants");
+}
+
+/**
+ * @param {string} query
+ * @returns {Promise<PaletteAction[]>}
+ */
+export function searchActions(query) {
+  return invoke("search_actions", { query });
+}
+
+/**
+ * @param {string} path
+ * @returns {Promise<PathWarning[]>}
+ */
+export function validateInstallPath(path) {
+  return invoke("validate_install_path", { path });
+}
+
+/** @returns {Promise<SerialDevice[]>} */
+export function listSerialDevices() {
+  return invoke("list_serial_devices", {});
+}
+
+/**
+ * One of `recovery_clear_staging`, `recovery_release_stale_lock`, `recovery_reset_wizard` or
+ * `recovery_purge_cache`
+ * @param {string} command
+ * @returns {Promise<RecoveryResult>}
+ */
+export function runRecoveryAction(command) {
+  return invoke(command);
+}
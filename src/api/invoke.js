@@ -0,0 +1,10 @@
+// Every command of the frontend goes through here, so none reaches a backend of another build
+// before the API handshake passed, see version.js
+import { invoke as tauriInvoke } from "@tauri-apps/api/core";
+import { apiReady } from "./version.js";
+
+/** `invoke` of Tauri, held back until the handshake passed or the user chose to continue anyway */
+export async function invoke(command, args, options) {
+  await apiReady();
+  return tauriInvoke(command, args, options);
+}
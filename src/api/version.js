@@ -0,0 +1,50 @@
+// Startup handshake between this frontend and the Rust commands, see api_version.rs
+import { ref } from "vue";
+import { invoke } from "@tauri-apps/api/core";
+import { version } from "../../package.json";
+import { API_VERSION } from "./apiVersion.js";
+
+/** Set when the frontend and the backend come from different builds: `{ outdated, frontend, backend }` */
+export const apiMismatch = ref(null);
+
+/**
+ * Checks that the backend speaks the same API version as this frontend, and sets `apiMismatch`
+ * otherwise. A backend too old to have the handshake command counts as outdated. It calls Tauri
+ * directly, as the handshake itself must not wait for `apiReady`.
+ */
+async function checkApiVersion() {
+  const frontend = { apiVersion: API_VERSION, appVersion: version };
+  try {
+    const result = await invoke("api_handshake", { frontend });
+    if (!result.compatible) {
+      apiMismatch.value = { outdated: result.outdated, frontend, backend: result.backend };
+    }
+  } catch (error) {
+    console.error("API handshake failed:", error);
+    apiMismatch.value = { outdated: "backend", frontend, backend: null };
+  }
+  return apiMismatch.value === null;
+}
+
+let continueAnyway;
+const continued = new Promise((resolve) => {
+  continueAnyway = resolve;
+});
+
+/** Lets the held back commands through after the user chose to continue despite a mismatch */
+export function continueDespiteMismatch() {
+  continueAnyway();
+}
+
+let ready = null;
+
+/**
+ * Runs the handshake once and resolves when it passed, or when the user chose to continue with
+ * a mismatch. `invoke` of ./invoke.js waits for it before every command.
+ */
+export function apiReady() {
+  if (ready === null) {
+    ready = checkApiVersion().then((compatible) => compatible || continued);
+  }
+  return ready;
+}
@@ -7,6 +7,7 @@ import SimpleSetup from "./components/SimpleSetup.vue";
 import InstallationProgress from "./components/wizard_steps/InstalationProgress.vue";
 import SimpleInstallatioProgressWrapper from "./components/SimpleInstallatioProgressWrapper.vue";
 import WizardStep from "./components/WizardStep.vue";
+import Recovery from "./components/Recovery.vue";
 
 export const navigationState = {
   installationRunning: false,
@@ -70,6 +71,13 @@ const routes = [
     props: true,
     meta: { title: "routes.configurationWizard" },
   },
+  {
+    // not in any menu, reached from the error screens of the installations
+    path: "/recovery",
+    name: "Recovery",
+    component: Recovery,
+    meta: { title: "routes.recovery" },
+  },
   {
     path: "/:pathMatch(.*)*",
     redirect: "/welcome",
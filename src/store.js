@@ -1,12 +1,14 @@
 import { defineStore } from "pinia";
-import { invoke } from '@tauri-apps/api/core'
+import { invoke } from './api/invoke.js'
 
 export const useAppStore = defineStore("app", {
   state: () => ({
     // App settings
     firstRun: true,
     skipWelcome: false,
-    theme: "light",
+    theme: "system",
+    // appearance of the OS, followed when theme is "system"
+    systemTheme: "light",
 
     // System info
     os: "unknown",
@@ -48,6 +50,10 @@ export const useAppStore = defineStore("app", {
   }),
 
   getters: {
+    // "light" or "dark", resolving "system" to the appearance of the OS
+    effectiveTheme: (state) =>
+      state.theme === "system" ? state.systemTheme : state.theme,
+
     hasInstalledVersions: (state) => state.installedVersions.length > 0,
     hasOfflineArchives: (state) => state.offlineArchives.length > 0,
     isWindows: (state) => state.os === "windows",
@@ -139,6 +145,15 @@ export const useAppStore = defineStore("app", {
       if (settings.theme !== undefined) this.theme = settings.theme;
     },
 
+    async setTheme(theme) {
+      this.theme = theme;
+      try {
+        await invoke("set_theme", { theme });
+      } catch (error) {
+        console.error("Failed to save the theme:", error);
+      }
+    },
+
     setDefaultConfig(config) {
       this.defaultConfig = config;
     },
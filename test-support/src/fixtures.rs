@@ -0,0 +1,94 @@
+use anyhow::Result;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::server::MockServer;
+
+/// A fake tool for tools.json: a `.tar.gz` archive with the given files, published on the mock server.
+#[derive(Debug, Clone)]
+pub struct ToolFixture {
+    pub name: String,
+    pub version: String,
+    /// Files in the archive as (path, content), e.g. `("bin/fake-gcc", ...)`
+    pub files: Vec<(String, Vec<u8>)>,
+    pub export_paths: Vec<Vec<String>>,
+}
+
+impl ToolFixture {
+    pub fn new(name: &str, version: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            files: Vec::new(),
+            export_paths: vec![vec!["bin".to_string()]],
+        }
+    }
+
+    pub fn with_file(mut self, path: &str, content: impl Into<Vec<u8>>) -> Self {
+        self.files.push((path.to_string(), content.into()));
+        self
+    }
+
+    /// Path of the archive on the mock server
+    pub fn archive_path(&self) -> String {
+        format!("dist/{}-{}.tar.gz", self.name, self.version)
+    }
+
+    /// Builds the `.tar.gz` archive; files are executable, so fake binaries can be run.
+    pub fn archive(&self) -> Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for (path, content) in &self.files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content.as_slice())?;
+        }
+        Ok(builder.into_inner()?.finish()?)
+    }
+
+    /// The tools.json entry downloading the archive from `server`, for all platforms.
+    pub fn tools_json_entry(&self, archive: &[u8], server: &MockServer) -> Value {
+        json!({
+            "description": format!("{} fixture", self.name),
+            "export_paths": self.export_paths,
+            "export_vars": {},
+            "info_url": server.url(),
+            "install": "always",
+            "license": "Apache-2.0",
+            "name": self.name,
+            "version_cmd": [format!("{}-version", self.name)],
+            "version_regex": "([0-9.]+)",
+            "versions": [{
+                "name": self.version,
+                "status": "recommended",
+                "any": {
+                    "sha256": sha256_hex(archive),
+                    "size": archive.len(),
+                    "url": server.url_for(&self.archive_path()),
+                }
+            }]
+        })
+    }
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Publishes the archives of `tools` on `server` and returns the matching tools.json.
+///
+/// The tools.json itself is also served as `tools/tools.json`.
+pub fn publish_tools(server: &MockServer, tools: &[ToolFixture]) -> Result<Value> {
+    let mut entries = Vec::new();
+    for tool in tools {
+        let archive = tool.archive()?;
+        entries.push(tool.tools_json_entry(&archive, server));
+        server.serve(&tool.archive_path(), archive);
+    }
+    let tools_json = json!({ "tools": entries, "version": 3 });
+    server.serve("tools/tools.json", serde_json::to_vec_pretty(&tools_json)?);
+    Ok(tools_json)
+}
@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+/// A local bare repository standing in for github.com/espressif/esp-idf.
+///
+/// It holds a single commit with a minimal ESP-IDF tree (`tools/tools.json`,
+/// `tools/cmake/version.cmake` and an `idf.py` stub) tagged with the version. Clones use the
+/// `file://` transport, which needs the `git` executable.
+pub struct FakeGitRemote {
+    dir: TempDir,
+}
+
+impl FakeGitRemote {
+    pub fn new(tag: &str, tools_json: &Value) -> Result<Self> {
+        let dir = TempDir::new()?;
+        let work = dir.path().join("work");
+        let tools = work.join("tools");
+        std::fs::create_dir_all(tools.join("cmake"))?;
+        std::fs::write(tools.join("tools.json"), serde_json::to_vec_pretty(tools_json)?)?;
+        std::fs::write(tools.join("cmake").join("version.cmake"), version_cmake(tag))?;
+        std::fs::write(tools.join("idf.py"), "#!/usr/bin/env python\nprint('idf.py fixture')\n")?;
+        std::fs::write(work.join("README.md"), format!("ESP-IDF {} fixture\n", tag))?;
+
+        git(&work, &["init", "--quiet", "--initial-branch=master"])?;
+        git(&work, &["add", "."])?;
+        git(
+            &work,
+            &[
+                "-c",
+                "user.name=eim tests",
+                "-c",
+                "user.email=eim-tests@localhost",
+                "commit",
+                "--quiet",
+                "-m",
+                &format!("ESP-IDF {}", tag),
+            ],
+        )?;
+        git(&work, &["tag", tag])?;
+
+        let bare = dir.path().join("espressif").join("esp-idf.git");
+        std::fs::create_dir_all(bare.parent().unwrap())?;
+        git(
+            dir.path(),
+            &["clone", "--quiet", "--bare", &work.to_string_lossy(), &bare.to_string_lossy()],
+        )?;
+        Ok(Self { dir })
+    }
+
+    /// Mirror to pass as `idf_mirror`: the repository URL built from it is `<mirror>/espressif/esp-idf.git`.
+    pub fn mirror_url(&self) -> String {
+        format!("file://{}", self.dir.path().display())
+    }
+
+    pub fn repository_path(&self) -> PathBuf {
+        self.dir.path().join("espressif").join("esp-idf.git")
+    }
+}
+
+/// `version.cmake` as ESP-IDF has it, parsed by the activation script
+fn version_cmake(tag: &str) -> String {
+    let mut numbers = tag
+        .trim_start_matches('v')
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|n| n.parse::<u32>().unwrap_or(0));
+    format!(
+        "set(IDF_VERSION_MAJOR {})\nset(IDF_VERSION_MINOR {})\nset(IDF_VERSION_PATCH {})\n",
+        numbers.next().unwrap_or(0),
+        numbers.next().unwrap_or(0),
+        numbers.next().unwrap_or(0)
+    )
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("git").args(args).current_dir(dir).output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,13 @@
+//! Test support for the eim integration tests.
+//!
+//! [`MockServer`] serves fixture tools.json files and tool archives over HTTP and
+//! [`FakeGitRemote`] provides a local ESP-IDF repository, so the install pipeline runs in CI
+//! without network access. Run the integration tests with `cargo xtask test --integration`.
+
+mod fixtures;
+mod git_remote;
+mod server;
+
+pub use fixtures::{publish_tools, sha256_hex, ToolFixture};
+pub use git_remote::FakeGitRemote;
+pub use server::MockServer;
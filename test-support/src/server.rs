@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+#[derive(Clone)]
+enum Route {
+    Body(Vec<u8>),
+    Status(u16),
+}
+
+#[derive(Default)]
+struct State {
+    routes: HashMap<String, Route>,
+    requests: Vec<String>,
+}
+
+/// A local HTTP server serving fixture files, standing in for dl.espressif.com, GitHub releases and mirrors.
+///
+/// Files are served from memory with `GET` and `HEAD`, including `Range` requests for resumed
+/// downloads. Unknown paths return 404. The server stops when dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Starts the server on a free port of 127.0.0.1.
+    pub fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handle = thread::spawn({
+            let state = state.clone();
+            let shutdown = shutdown.clone();
+            move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        let state = state.clone();
+                        thread::spawn(move || {
+                            let _ = handle_connection(stream, &state);
+                        });
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            addr,
+            state,
+            shutdown,
+            handle: Some(handle),
+        })
+    }
+
+    /// Base URL without a trailing slash, e.g. `http://127.0.0.1:40123`
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// URL of `path` on the server.
+    pub fn url_for(&self, path: &str) -> String {
+        format!("{}/{}", self.url(), path.trim_start_matches('/'))
+    }
+
+    /// Serves `body` at `path`.
+    pub fn serve(&self, path: &str, body: impl Into<Vec<u8>>) {
+        self.state
+            .lock()
+            .unwrap()
+            .routes
+            .insert(normalize(path), Route::Body(body.into()));
+    }
+
+    /// Answers requests for `path` with `status` and no body, e.g. to simulate a broken mirror.
+    pub fn fail(&self, path: &str, status: u16) {
+        self.state
+            .lock()
+            .unwrap()
+            .routes
+            .insert(normalize(path), Route::Status(status));
+    }
+
+    /// Paths requested so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // wakes up the blocking accept
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn normalize(path: &str) -> String {
+    format!("/{}", path.trim_start_matches('/'))
+}
+
+fn handle_connection(mut stream: TcpStream, state: &Mutex<State>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or("/");
+    let path = normalize(target.split('?').next().unwrap_or_default());
+
+    let mut range_start = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("range") {
+                range_start = value
+                    .trim()
+                    .strip_prefix("bytes=")
+                    .and_then(|range| range.split('-').next())
+                    .and_then(|start| start.parse::<usize>().ok());
+            }
+        }
+    }
+
+    let route = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(path.clone());
+        state.routes.get(&path).cloned()
+    };
+    let (status, headers, body) = match route {
+        Some(Route::Body(body)) => match range_start {
+            Some(start) if start > 0 && start < body.len() => (
+                206,
+                format!("Content-Range: bytes {}-{}/{}\r\n", start, body.len() - 1, body.len()),
+                body[start..].to_vec(),
+            ),
+            _ => (200, String::new(), body),
+        },
+        Some(Route::Status(status)) => (status, String::new(), Vec::new()),
+        None => (404, String::new(), Vec::new()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nContent-Type: application/octet-stream\r\n{}Connection: close\r\n\r\n",
+        status,
+        reason(status),
+        body.len(),
+        headers
+    )?;
+    if method != "HEAD" {
+        stream.write_all(&body)?;
+    }
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        206 => "Partial Content",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Status",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn get(server: &MockServer, path: &str, extra_headers: &str) -> String {
+        let mut stream = TcpStream::connect(server.addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n", path, extra_headers).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serves_files_and_ranges() {
+        let server = MockServer::start().unwrap();
+        server.serve("dist/tool.tar.gz", "0123456789");
+        server.fail("broken", 503);
+
+        let response = get(&server, "/dist/tool.tar.gz?x=1", "");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("\r\n\r\n0123456789"));
+
+        let response = get(&server, "/dist/tool.tar.gz", "Range: bytes=4-\r\n");
+        assert!(response.starts_with("HTTP/1.1 206"));
+        assert!(response.contains("Content-Range: bytes 4-9/10"));
+        assert!(response.ends_with("456789"));
+
+        assert!(get(&server, "/broken", "").starts_with("HTTP/1.1 503"));
+        assert!(get(&server, "/missing", "").starts_with("HTTP/1.1 404"));
+        assert_eq!(server.requests().len(), 4);
+    }
+}
@@ -0,0 +1,73 @@
+//! Runs the install pipeline against the mock artifact server and a local ESP-IDF remote.
+//!
+//! The tests need `git` and are ignored by `cargo test`; run them with `cargo xtask test --integration`.
+
+use eim_test_support::{publish_tools, FakeGitRemote, MockServer, ToolFixture};
+use idf_im_lib::idf_tools::{read_and_parse_tools_file, setup_tools};
+use std::path::Path;
+use tempfile::TempDir;
+
+const IDF_VERSION: &str = "v5.3.2";
+
+fn fake_toolchain() -> ToolFixture {
+    ToolFixture::new("fake-gcc", "1.0.0").with_file("bin/fake-gcc", "#!/bin/sh\necho fake-gcc 1.0.0\n")
+}
+
+fn clone_fake_idf(remote: &FakeGitRemote, install: &Path) -> std::path::PathBuf {
+    let idf_path = install.join(IDF_VERSION).join("esp-idf");
+    let (tx, _rx) = std::sync::mpsc::channel();
+    idf_im_lib::git_tools::get_esp_idf(
+        idf_path.to_str().unwrap(),
+        None,
+        IDF_VERSION,
+        Some(&remote.mirror_url()),
+        false,
+        tx,
+    )
+    .unwrap();
+    idf_path
+}
+
+#[tokio::test]
+#[ignore = "integration test, run with cargo xtask test --integration"]
+async fn test_install_from_mock_server() {
+    let server = MockServer::start().unwrap();
+    let tools_json = publish_tools(&server, &[fake_toolchain()]).unwrap();
+    let remote = FakeGitRemote::new(IDF_VERSION, &tools_json).unwrap();
+    let install = TempDir::new().unwrap();
+
+    let idf_path = clone_fake_idf(&remote, install.path());
+    assert!(idf_im_lib::utils::is_valid_idf_directory(idf_path.to_str().unwrap()));
+
+    let tools = read_and_parse_tools_file(idf_path.join("tools").join("tools.json").to_str().unwrap()).unwrap();
+    let download_dir = install.path().join("dist");
+    let tools_dir = install.path().join("tools");
+    setup_tools(&tools, vec!["all".to_string()], &download_dir, &tools_dir, None, None, |_| {})
+        .await
+        .unwrap();
+
+    assert!(tools_dir.join("fake-gcc").join("1.0.0").join("bin").join("fake-gcc").exists());
+    assert!(server.requests().contains(&"/dist/fake-gcc-1.0.0.tar.gz".to_string()));
+}
+
+#[tokio::test]
+#[ignore = "integration test, run with cargo xtask test --integration"]
+async fn test_corrupted_archive_is_rejected() {
+    let server = MockServer::start().unwrap();
+    let tool = fake_toolchain();
+    let tools_json = publish_tools(&server, &[tool.clone()]).unwrap();
+    // the mirror serves something else than tools.json promises
+    server.serve(&tool.archive_path(), "not the archive");
+    let remote = FakeGitRemote::new(IDF_VERSION, &tools_json).unwrap();
+    let install = TempDir::new().unwrap();
+
+    let idf_path = clone_fake_idf(&remote, install.path());
+    let tools = read_and_parse_tools_file(idf_path.join("tools").join("tools.json").to_str().unwrap()).unwrap();
+    let tools_dir = install.path().join("tools");
+    let err = setup_tools(&tools, vec!["all".to_string()], &install.path().join("dist"), &tools_dir, None, None, |_| {})
+        .await
+        .unwrap_err();
+
+    assert!(format!("{:#}", err).contains("is corrupted"));
+    assert!(!tools_dir.join("fake-gcc").join("1.0.0").exists());
+}
@@ -0,0 +1,71 @@
+//! TypeScript definitions of the command payloads and the frontend copy of the API version, so
+//! the frontend can't silently drift from the Rust commands.
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const API_VERSION_RS: &str = "src-tauri/src/gui/commands/api_version.rs";
+const API_VERSION_JS: &str = "src/api/apiVersion.js";
+const BINDINGS_DIR: &str = "src/api/bindings";
+
+fn repo_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask lives in the repository")
+        .to_path_buf()
+}
+
+/// Reads `pub const API_VERSION: u32 = N;` from the backend.
+fn backend_api_version(source: &str) -> Result<u32> {
+    source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("pub const API_VERSION: u32 = "))
+        .and_then(|rest| rest.strip_suffix(';'))
+        .context("No `pub const API_VERSION: u32 = N;` found")?
+        .parse()
+        .context("API_VERSION is not a number")
+}
+
+fn frontend_api_version(api_version: u32) -> String {
+    format!(
+        "// Generated by `cargo xtask bindings` from API_VERSION in {}, do not edit\nexport const API_VERSION = {};\n",
+        API_VERSION_RS.trim_start_matches("src-tauri/"),
+        api_version
+    )
+}
+
+/// Exports the TypeScript definitions of the types deriving `ts_rs::TS` and writes the frontend
+/// API version. With `check`, only verifies that the frontend API version is up to date.
+pub fn bindings(check: bool) -> Result<()> {
+    let root = repo_root();
+    let source = root.join(API_VERSION_RS);
+    let api_version = backend_api_version(
+        &fs::read_to_string(&source).with_context(|| format!("Failed to read {}", source.display()))?,
+    )?;
+    let expected = frontend_api_version(api_version);
+    let js = root.join(API_VERSION_JS);
+
+    if check {
+        if fs::read_to_string(&js).unwrap_or_default() != expected {
+            bail!("{} is not up to date with API_VERSION {}, run `cargo xtask bindings`", API_VERSION_JS, api_version);
+        }
+        println!("✅ Frontend API version {} is up to date", api_version);
+        return Ok(());
+    }
+
+    println!("📜 Exporting the TypeScript bindings to {}...", BINDINGS_DIR);
+    let status = Command::new("cargo")
+        .args(["test", "--package", "eim-core", "--package", "eim-gui", "--features", "eim-gui/ts-bindings", "export_bindings"])
+        .env("TS_RS_EXPORT_DIR", root.join(BINDINGS_DIR))
+        .status()?;
+    if !status.success() {
+        bail!("Exporting the TypeScript bindings failed");
+    }
+
+    fs::write(&js, expected).with_context(|| format!("Failed to write {}", js.display()))?;
+    println!("✅ Bindings exported, frontend API version {}", api_version);
+    Ok(())
+}
+
@@ -0,0 +1,471 @@
+//! `cargo xtask dist`: builds, packages, signs and describes a release in `dist/vX.Y.Z/`.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::run_command;
+
+/// Timestamp server used for Windows signatures
+const TIMESTAMP_URL: &str = "http://timestamp.globalsign.com/tsa/advanced";
+/// Extensions of the Tauri bundles copied into the release
+const BUNDLE_EXTENSIONS: &[&str] = &["deb", "rpm", "AppImage", "dmg", "msi", "exe"];
+/// Targets of `--linux-arm`: 64-bit ARM and the 32-bit hard-float ARM of Raspberry Pi OS
+pub const LINUX_ARM_TARGETS: [&str; 2] = ["aarch64-unknown-linux-gnu", "armv7-unknown-linux-gnueabihf"];
+
+/// Options of `cargo xtask dist`.
+pub struct DistOptions {
+    pub version: Option<String>,
+    pub targets: Vec<String>,
+    pub linux_arm: bool,
+    pub gui: bool,
+    pub offline_builder: bool,
+    pub no_sign: bool,
+    pub out_dir: PathBuf,
+}
+
+/// One file of the release, as listed in `index.json`.
+struct Artifact {
+    file: String,
+    kind: &'static str,
+    target: String,
+    platform: String,
+    signed: bool,
+}
+
+/// Name used for the release assets of a Rust target, the same as in the CI workflows.
+fn platform_name(target: &str) -> String {
+    let arch = match target.split('-').next().unwrap_or_default() {
+        "x86_64" => "x64",
+        "armv7" => "armhf",
+        other => other,
+    };
+    let os = if target.contains("windows") {
+        "windows"
+    } else if target.contains("apple") {
+        "macos"
+    } else if target.contains("linux") {
+        "linux"
+    } else {
+        target.split('-').nth(2).unwrap_or("unknown")
+    };
+    format!("{}-{}", os, arch)
+}
+
+fn host_target() -> Result<String> {
+    let output = Command::new("rustc").arg("-vV").output()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::to_string)
+        .context("Failed to read the host target from rustc -vV")
+}
+
+fn cargo_metadata() -> Result<Value> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1", "--locked"])
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+fn eim_version(metadata: &Value) -> Option<String> {
+    metadata["packages"]
+        .as_array()?
+        .iter()
+        .find(|package| package["name"] == "eim-cli" && package["source"].is_null())
+        .and_then(|package| package["version"].as_str())
+        .map(str::to_string)
+}
+
+fn git_commit() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+pub(crate) fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Signs a binary with the credentials from the environment; `false` if none are configured
+/// for the platform of `target` or it cannot be signed on this host.
+///
+/// * macOS: `EIM_MACOS_SIGNING_IDENTITY`, signed with the hardened runtime and `eim.entitlement`
+/// * Windows: `EIM_WINDOWS_CERTIFICATE` (a .pfx file) and `EIM_WINDOWS_CERTIFICATE_PASSWORD`
+fn sign_binary(binary: &Path, target: &str) -> Result<bool> {
+    let binary = binary.to_string_lossy();
+    if target.contains("apple") && std::env::consts::OS == "macos" {
+        let Ok(identity) = std::env::var("EIM_MACOS_SIGNING_IDENTITY") else {
+            return Ok(false);
+        };
+        run_command(
+            "codesign",
+            &[
+                "--entitlements",
+                "eim.entitlement",
+                "--options",
+                "runtime",
+                "--force",
+                "-s",
+                &identity,
+                &binary,
+            ],
+        )?;
+        run_command("codesign", &["-v", "-vvv", "--deep", &binary])?;
+        Ok(true)
+    } else if target.contains("windows") && std::env::consts::OS == "windows" {
+        let Ok(certificate) = std::env::var("EIM_WINDOWS_CERTIFICATE") else {
+            return Ok(false);
+        };
+        let password = std::env::var("EIM_WINDOWS_CERTIFICATE_PASSWORD").unwrap_or_default();
+        run_command(
+            "signtool",
+            &[
+                "sign",
+                "/f",
+                &certificate,
+                "/p",
+                &password,
+                "/fd",
+                "sha256",
+                "/tr",
+                TIMESTAMP_URL,
+                "/td",
+                "sha256",
+                &binary,
+            ],
+        )?;
+        run_command("signtool", &["verify", "/pa", &binary])?;
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Packs `binary` alone into `archive`, the layout of the `eim-cli-*.zip` release assets.
+fn zip_binary(binary: &Path, archive: &Path) -> Result<()> {
+    let archive = std::path::absolute(archive)?;
+    let _ = fs::remove_file(&archive);
+    let dir = binary.parent().context("Binary without a folder")?;
+    let name = binary
+        .file_name()
+        .context("Binary without a name")?
+        .to_string_lossy();
+    let status = if std::env::consts::OS == "windows" {
+        Command::new("powershell")
+            .args(["-NoProfile", "-Command", "Compress-Archive", "-Path"])
+            .arg(&*name)
+            .arg("-DestinationPath")
+            .arg(&archive)
+            .current_dir(dir)
+            .status()?
+    } else {
+        Command::new("zip")
+            .arg("-q")
+            .arg(&archive)
+            .arg(&*name)
+            .current_dir(dir)
+            .status()?
+    };
+    if !status.success() {
+        anyhow::bail!(
+            "Failed to pack {} into {}",
+            binary.display(),
+            archive.display()
+        );
+    }
+    Ok(())
+}
+
+/// Whether `target` is built with `cross`: a Linux target of another architecture than the
+/// host, which needs a cross toolchain and the target's system libraries.
+fn needs_cross(target: &str) -> Result<bool> {
+    let host = host_target()?;
+    Ok(target != host && target.contains("linux") && host.contains("linux"))
+}
+
+/// Builds one binary of the eim-cli package for `target` and returns its path.
+fn build_binary(target: &str, bin: &str, features: &[&str]) -> Result<PathBuf> {
+    println!("🔨 Building {} for {}...", bin, target);
+    let builder = if needs_cross(target)? {
+        if Command::new("cross").arg("--version").output().is_err() {
+            anyhow::bail!(
+                "Building {} on this host needs cross: cargo install cross, and a container runtime (docker or podman)",
+                target
+            );
+        }
+        "cross"
+    } else {
+        "cargo"
+    };
+    let features = features.join(",");
+    let mut args = vec![
+        "build",
+        "--release",
+        "--locked",
+        "--package",
+        "eim-cli",
+        "--bin",
+        bin,
+        "--target",
+        target,
+    ];
+    if !features.is_empty() {
+        args.extend(["--features", &features]);
+    }
+    run_command(builder, &args)?;
+    let extension = if target.contains("windows") {
+        ".exe"
+    } else {
+        ""
+    };
+    Ok(PathBuf::from("target")
+        .join(target)
+        .join("release")
+        .join(format!("{}{}", bin, extension)))
+}
+
+/// Builds, signs and packs the binaries of `target` into `release_dir`.
+fn dist_target(target: &str, options: &DistOptions, release_dir: &Path) -> Result<Vec<Artifact>> {
+    let platform = platform_name(target);
+    let mut artifacts = Vec::new();
+    let mut binaries = vec![("eim-cli", build_binary(target, "eim", &[])?)];
+    if options.offline_builder {
+        binaries.push((
+            "offline_installer_builder",
+            build_binary(target, "offline_installer_builder", &["offline"])?,
+        ));
+    }
+    for (kind, built) in binaries {
+        // packed alone, like the release assets of the CI workflows
+        let staging = release_dir.join(".staging").join(&platform).join(kind);
+        fs::create_dir_all(&staging)?;
+        let binary = staging.join(built.file_name().context("Binary without a name")?);
+        fs::copy(&built, &binary).with_context(|| format!("Failed to copy {}", built.display()))?;
+        let signed = !options.no_sign && sign_binary(&binary, target)?;
+        if !signed {
+            println!("   ⚠️  {} for {} is not signed", kind, target);
+        }
+        let file = format!("{}-{}.zip", kind, platform);
+        zip_binary(&binary, &release_dir.join(&file))?;
+        artifacts.push(Artifact {
+            file,
+            kind,
+            target: target.to_string(),
+            platform: platform.clone(),
+            signed,
+        });
+    }
+
+    if options.gui && needs_cross(target)? {
+        // WebKitGTK of the target is needed to link the GUI, which cross images don't have
+        println!("   ⚠️  The GUI bundles of {} are built on a host of its architecture, skipping them", target);
+    } else if options.gui {
+        println!("🔨 Building the GUI bundles for {}...", target);
+        env_bundle_signing();
+        run_command("cargo", &["tauri", "build", "--target", target])?;
+        let bundle_dir = PathBuf::from("target")
+            .join(target)
+            .join("release")
+            .join("bundle");
+        for format_dir in fs::read_dir(&bundle_dir).into_iter().flatten().flatten() {
+            for entry in fs::read_dir(format_dir.path())
+                .into_iter()
+                .flatten()
+                .flatten()
+            {
+                let path = entry.path();
+                let bundled = path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| BUNDLE_EXTENSIONS.contains(&ext));
+                if !bundled || !path.is_file() {
+                    continue;
+                }
+                let file = path.file_name().unwrap().to_string_lossy().into_owned();
+                fs::copy(&path, release_dir.join(&file))?;
+                artifacts.push(Artifact {
+                    file,
+                    kind: "gui",
+                    target: target.to_string(),
+                    platform: platform.clone(),
+                    // Tauri signs the bundles itself when its signing variables are set
+                    signed: std::env::var_os("APPLE_SIGNING_IDENTITY").is_some()
+                        || std::env::var_os("TAURI_SIGNING_PRIVATE_KEY").is_some(),
+                });
+            }
+        }
+    }
+    Ok(artifacts)
+}
+
+/// Hands the macOS identity to Tauri, which signs the .app it bundles.
+fn env_bundle_signing() {
+    if let (Ok(identity), None) = (
+        std::env::var("EIM_MACOS_SIGNING_IDENTITY"),
+        std::env::var_os("APPLE_SIGNING_IDENTITY"),
+    ) {
+        std::env::set_var("APPLE_SIGNING_IDENTITY", identity);
+    }
+}
+
+/// CycloneDX bill of materials of the Rust dependencies, from `cargo metadata`.
+fn sbom(metadata: &Value, version: &str) -> Value {
+    let mut components: Vec<Value> = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|package| !package["source"].is_null())
+        .map(|package| {
+            let name = package["name"].as_str().unwrap_or_default();
+            let version = package["version"].as_str().unwrap_or_default();
+            let mut component = json!({
+                "type": "library",
+                "name": name,
+                "version": version,
+                "purl": format!("pkg:cargo/{}@{}", name, version),
+            });
+            if let Some(license) = package["license"].as_str() {
+                component["licenses"] = json!([{ "expression": license }]);
+            }
+            component
+        })
+        .collect();
+    components.sort_by_key(|component| component["purl"].as_str().unwrap_or_default().to_string());
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "component": {
+                "type": "application",
+                "name": "eim",
+                "version": version,
+                "purl": format!("pkg:github/espressif/idf-im-ui@v{}", version),
+            },
+        },
+        "components": components,
+    })
+}
+
+pub fn dist(options: DistOptions) -> Result<()> {
+    let metadata = cargo_metadata()?;
+    let version = match &options.version {
+        Some(version) => version.trim_start_matches('v').to_string(),
+        None => eim_version(&metadata).context("Failed to find the version of the eim-cli package")?,
+    };
+    let mut targets = if options.targets.is_empty() {
+        vec![host_target()?]
+    } else {
+        options.targets.clone()
+    };
+    if options.linux_arm {
+        for target in LINUX_ARM_TARGETS {
+            if !targets.iter().any(|t| t == target) {
+                targets.push(target.to_string());
+            }
+        }
+    }
+    let release_dir = options.out_dir.join(format!("v{}", version));
+    if release_dir.exists() {
+        fs::remove_dir_all(&release_dir)?;
+    }
+    fs::create_dir_all(&release_dir)?;
+    println!(
+        "📦 Building release v{} for {} into {}",
+        version,
+        targets.join(", "),
+        release_dir.display()
+    );
+
+    let mut artifacts = Vec::new();
+    for target in &targets {
+        run_command("rustup", &["target", "add", target])?;
+        artifacts.extend(dist_target(target, &options, &release_dir)?);
+    }
+    fs::remove_dir_all(release_dir.join(".staging"))?;
+
+    println!("📋 Writing the SBOM...");
+    let sbom_file = "sbom.cdx.json";
+    fs::write(
+        release_dir.join(sbom_file),
+        serde_json::to_string_pretty(&sbom(&metadata, &version))?,
+    )?;
+
+    println!("🔐 Computing checksums...");
+    let mut entries = Vec::new();
+    let mut checksums = String::new();
+    for artifact in &artifacts {
+        let path = release_dir.join(&artifact.file);
+        let sha256 = sha256_file(&path)?;
+        checksums.push_str(&format!("{}  {}\n", sha256, artifact.file));
+        entries.push(json!({
+            "file": artifact.file,
+            "kind": artifact.kind,
+            "target": artifact.target,
+            "platform": artifact.platform,
+            "size": fs::metadata(&path)?.len(),
+            "sha256": sha256,
+            "signed": artifact.signed,
+        }));
+    }
+    checksums.push_str(&format!(
+        "{}  {}\n",
+        sha256_file(&release_dir.join(sbom_file))?,
+        sbom_file
+    ));
+    fs::write(release_dir.join("SHA256SUMS"), checksums)?;
+
+    let index = json!({
+        "version": version,
+        "commit": git_commit(),
+        "created": unix_time(),
+        "targets": targets,
+        "artifacts": entries,
+        "checksums": "SHA256SUMS",
+        "sbom": sbom_file,
+    });
+    fs::write(
+        release_dir.join("index.json"),
+        serde_json::to_string_pretty(&index)?,
+    )?;
+
+    println!(
+        "✅ Release v{} is ready in {}",
+        version,
+        release_dir.display()
+    );
+    for artifact in &artifacts {
+        println!(
+            "   {}{}",
+            artifact.file,
+            if artifact.signed { "" } else { " (unsigned)" }
+        );
+    }
+    Ok(())
+}
@@ -1,8 +1,15 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use std::env;
+use std::path::PathBuf;
 use std::process::Command;
 
+mod bindings;
+mod dist;
+mod pins;
+mod scaffold;
+mod vendor;
+
 #[derive(Parser)]
 #[command(name = "xtask")]
 #[command(about = "Build automation for idf-im-ui", long_about = None)]
@@ -19,6 +26,10 @@ enum Commands {
         /// Build target (x86_64, aarch64, etc.)
         #[arg(long)]
         target: Option<String>,
+
+        /// Build a universal macOS app (arm64 + x86_64) in a single .app/.dmg
+        #[arg(long, conflicts_with = "target")]
+        universal: bool,
     },
 
     /// Run Tauri in development mode
@@ -39,7 +50,11 @@ enum Commands {
 
     /// Run tests
     #[command(name = "test")]
-    Test,
+    Test {
+        /// Also run the integration tests against the mock artifact server (needs git)
+        #[arg(long)]
+        integration: bool,
+    },
 
     /// Clean build artifacts
     #[command(name = "clean")]
@@ -55,7 +70,76 @@ enum Commands {
 
     /// Setup system dependencies
     #[command(name = "setup")]
-    Setup,
+    Setup {
+        /// Record the checksums of the downloads in xtask/pins.json instead of verifying them,
+        /// after changing a pinned version
+        #[arg(long)]
+        update_pins: bool,
+    },
+
+    /// Build, package, sign and checksum a release into dist/vX.Y.Z/ with an SBOM and index.json
+    #[command(name = "dist")]
+    Dist {
+        /// Version of the release, the version of the eim package by default
+        #[arg(long)]
+        version: Option<String>,
+
+        /// Rust targets to build, can be repeated (the host target by default)
+        #[arg(long = "target")]
+        targets: Vec<String>,
+
+        /// Also build the GUI bundles (deb, rpm, AppImage, dmg, msi) with Tauri
+        #[arg(long)]
+        gui: bool,
+
+        /// Also build the ARM Linux targets aarch64 and armv7 (Raspberry Pi and similar hosts)
+        #[arg(long)]
+        linux_arm: bool,
+
+        /// Also build the offline_installer_builder
+        #[arg(long)]
+        offline_builder: bool,
+
+        /// Do not sign, even if signing credentials are set
+        #[arg(long)]
+        no_sign: bool,
+
+        /// Folder the versioned release folder is created in
+        #[arg(long, default_value = "dist")]
+        out_dir: PathBuf,
+    },
+
+    /// Generate the boilerplate of a new Tauri command or CLI subcommand, registered where it has to be
+    #[command(name = "scaffold")]
+    Scaffold {
+        #[command(subcommand)]
+        kind: scaffold::ScaffoldKind,
+    },
+
+    /// Export the TypeScript definitions of the command payloads and the frontend API version
+    #[command(name = "bindings")]
+    Bindings {
+        /// Only verify that src/api/apiVersion.js matches API_VERSION of the backend
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Vendor the crates and the npm and yarn packages and point the build configuration at
+    /// them, to build eim without network access
+    #[command(name = "vendor")]
+    Vendor {
+        /// Folder the sources are vendored into, relative to the repository
+        #[arg(long, default_value = "vendor")]
+        out_dir: PathBuf,
+
+        /// Only vendor the crates, e.g. to build the CLI without the GUI
+        #[arg(long)]
+        skip_frontend: bool,
+
+        /// Remove the vendored sources from the build configuration again, keeping the folder
+        #[arg(long)]
+        remove: bool,
+    },
 
     /// Full build pipeline (check → fmt → lint → build)
     #[command(name = "all")]
@@ -70,16 +154,25 @@ fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Build { target } => build_app(target)?,
+        Commands::Build { target, universal: false } => build_app(target)?,
+        Commands::Build { universal: true, .. } => build_universal_macos()?,
         Commands::Dev => dev_app()?,
         Commands::Check => check_code()?,
         Commands::Fmt => format_code()?,
         Commands::Lint => lint_code()?,
-        Commands::Test => test_code()?,
+        Commands::Test { integration } => test_code(integration)?,
         Commands::Clean => clean_build()?,
         Commands::Install => install_app()?,
         Commands::InstallSystem => install_system()?,
-        Commands::Setup => setup_system()?,
+        Commands::Setup { update_pins } => setup_system(update_pins)?,
+        Commands::Dist { version, targets, linux_arm, gui, offline_builder, no_sign, out_dir } => {
+            dist::dist(dist::DistOptions { version, targets, linux_arm, gui, offline_builder, no_sign, out_dir })?
+        }
+        Commands::Scaffold { kind } => scaffold::scaffold(kind)?,
+        Commands::Bindings { check } => bindings::bindings(check)?,
+        Commands::Vendor { out_dir, skip_frontend, remove } => {
+            vendor::vendor(vendor::VendorOptions { out_dir, skip_frontend, remove })?
+        }
         Commands::All { target } => {
             println!("Running full build pipeline...\n");
             check_code()?;
@@ -114,6 +207,34 @@ fn build_app(target: Option<String>) -> Result<()> {
     Ok(())
 }
 
+/// Rust targets merged into the universal macOS binary
+const UNIVERSAL_MACOS_TARGETS: [&str; 2] = ["aarch64-apple-darwin", "x86_64-apple-darwin"];
+
+fn build_universal_macos() -> Result<()> {
+    if std::env::consts::OS != "macos" {
+        anyhow::bail!("Universal builds need macOS, lipo and the Apple SDKs are not available elsewhere");
+    }
+    println!("🔨 Building universal macOS application...");
+
+    let mut args = vec!["target", "add"];
+    args.extend(UNIVERSAL_MACOS_TARGETS);
+    run_command("rustup", &args)?;
+
+    env::set_var("TAURI_SKIP_WEBVIEW_DOWNLOAD", "false");
+    // Tauri builds both architectures and merges them with lipo before bundling the .app and .dmg
+    run_command("cargo", &["tauri", "build", "--target", "universal-apple-darwin"])?;
+
+    let binary = "target/universal-apple-darwin/release/eim";
+    let output = Command::new("lipo").args(["-archs", binary]).output()?;
+    let archs = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() || !archs.contains("arm64") || !archs.contains("x86_64") {
+        anyhow::bail!("{} is not a universal binary (architectures: {})", binary, archs.trim());
+    }
+    println!("✅ Universal build completed ({})", archs.trim());
+    println!("   Bundles: target/universal-apple-darwin/release/bundle/");
+    Ok(())
+}
+
 fn dev_app() -> Result<()> {
     println!("🚀 Starting development server...");
     
@@ -154,11 +275,16 @@ fn lint_code() -> Result<()> {
     Ok(())
 }
 
-fn test_code() -> Result<()> {
+fn test_code(integration: bool) -> Result<()> {
     println!("🧪 Running tests...");
     
     // Run all tests
     run_command("cargo", &["test", "--all"])?;
+
+    if integration {
+        println!("🧪 Running integration tests against the mock artifact server...");
+        run_command("cargo", &["test", "--package", "eim-test-support", "--", "--include-ignored"])?;
+    }
     
     println!("✅ Tests passed!");
     Ok(())
@@ -210,14 +336,15 @@ fn install_system() -> Result<()> {
     Ok(())
 }
 
-fn setup_system() -> Result<()> {
+fn setup_system(update_pins: bool) -> Result<()> {
     println!("🔧 Setting up system dependencies...\n");
     
     let os = std::env::consts::OS;
     match os {
-        "linux" => setup_linux()?,
-        "macos" => setup_macos()?,
+        "linux" => setup_linux(update_pins)?,
+        "macos" => setup_macos(update_pins)?,
         "windows" => setup_windows()?,
+        "freebsd" => setup_freebsd()?,
         _ => {
             eprintln!("❌ Unsupported OS: {}", os);
             anyhow::bail!("Setup not available for this OS");
@@ -229,18 +356,18 @@ fn setup_system() -> Result<()> {
     Ok(())
 }
 
-fn setup_linux() -> Result<()> {
+fn setup_linux(update_pins: bool) -> Result<()> {
     println!("📦 Detecting Linux distribution...");
     
     let os_release = std::fs::read_to_string("/etc/os-release")
         .unwrap_or_default();
     
     if os_release.contains("ubuntu") || os_release.contains("debian") {
-        setup_debian_ubuntu()?;
+        setup_debian_ubuntu(update_pins)?;
     } else if os_release.contains("fedora") || os_release.contains("rhel") || os_release.contains("centos") {
-        setup_fedora_rhel()?;
+        setup_fedora_rhel(update_pins)?;
     } else if os_release.contains("arch") || os_release.contains("cachyos") || os_release.contains("manjaro") {
-        setup_arch()?;
+        setup_arch(update_pins)?;
     } else {
         println!("⚠️  Unknown Linux distribution. Please install the following packages:");
         println!("   - libwebkit2gtk-4.1-dev (or webkit2gtk3-devel)");
@@ -252,7 +379,7 @@ fn setup_linux() -> Result<()> {
     Ok(())
 }
 
-fn setup_debian_ubuntu() -> Result<()> {
+fn setup_debian_ubuntu(update_pins: bool) -> Result<()> {
     println!("📦 Installing dependencies for Debian/Ubuntu...");
     println!("   (This will require sudo)");
     
@@ -275,12 +402,12 @@ fn setup_debian_ubuntu() -> Result<()> {
     args.extend(&deps);
     run_command("sudo", &args)?;
     
-    setup_linuxdeploy()?;
+    setup_linuxdeploy(update_pins)?;
     
     Ok(())
 }
 
-fn setup_fedora_rhel() -> Result<()> {
+fn setup_fedora_rhel(update_pins: bool) -> Result<()> {
     println!("📦 Installing dependencies for Fedora/RHEL/CentOS...");
     println!("   (This will require sudo)");
     
@@ -302,12 +429,12 @@ fn setup_fedora_rhel() -> Result<()> {
     args.extend(&deps);
     run_command("sudo", &args)?;
     
-    setup_linuxdeploy()?;
+    setup_linuxdeploy(update_pins)?;
     
     Ok(())
 }
 
-fn setup_arch() -> Result<()> {
+fn setup_arch(update_pins: bool) -> Result<()> {
     println!("📦 Installing dependencies for Arch/CachyOS/Manjaro...");
     println!("   (This will require sudo)");
     
@@ -336,12 +463,12 @@ fn setup_arch() -> Result<()> {
         println!("   ⚠️  Some packages were already installed or not found (this is OK)");
     }
     
-    setup_linuxdeploy()?;
+    setup_linuxdeploy(update_pins)?;
     
     Ok(())
 }
 
-fn setup_macos() -> Result<()> {
+fn setup_macos(update_pins: bool) -> Result<()> {
     println!("📦 Installing dependencies for macOS...");
     
     // Check if Homebrew is installed
@@ -351,8 +478,10 @@ fn setup_macos() -> Result<()> {
     
     if homebrew_check.is_err() || !homebrew_check?.success() {
         println!("⚠️  Homebrew not found. Installing Homebrew first...");
-        let install_script = "/bin/bash -c \"$(curl -fsSL https://raw.githubusercontent.com/Homebrew/install/HEAD/install.sh)\"";
-        run_command("/bin/bash", &["-c", install_script])?;
+        let install_script = env::temp_dir().join("homebrew-install.sh");
+        pins::download("homebrew-install", &install_script, update_pins)?;
+        run_command("/bin/bash", &[&install_script.to_string_lossy()])?;
+        let _ = std::fs::remove_file(&install_script);
     }
     
     let deps = vec![
@@ -371,6 +500,36 @@ fn setup_macos() -> Result<()> {
     Ok(())
 }
 
+fn setup_freebsd() -> Result<()> {
+    println!("📦 Installing dependencies for FreeBSD...");
+    println!("   (This will require root, through sudo or doas)");
+
+    let deps = vec![
+        "webkit2-gtk_41",
+        "gtk3",
+        "libsoup3",
+        "pkgconf",
+        "curl",
+        "wget",
+        "gmake",
+    ];
+
+    let elevate = if Command::new("which").arg("sudo").status().is_ok_and(|s| s.success()) {
+        "sudo"
+    } else {
+        "doas"
+    };
+    println!("   Running: {} pkg install -y {:?}", elevate, deps.join(" "));
+    let mut args = vec!["pkg", "install", "-y"];
+    args.extend(&deps);
+    run_command(elevate, &args)?;
+
+    // no AppImage on FreeBSD, the GUI is built as a plain binary
+    println!("   💡 Build with cargo xtask build; the deb, rpm and AppImage bundles are Linux only");
+
+    Ok(())
+}
+
 fn setup_windows() -> Result<()> {
     println!("❌ Automatic setup not available for Windows");
     println!("\n📖 Please follow the official Tauri setup guide:");
@@ -383,7 +542,7 @@ fn setup_windows() -> Result<()> {
     Ok(())
 }
 
-fn setup_linuxdeploy() -> Result<()> {
+fn setup_linuxdeploy(update_pins: bool) -> Result<()> {
     println!("📦 Setting up linuxdeploy for AppImage support...");
     
     // Check if linuxdeploy is already installed
@@ -405,20 +564,14 @@ fn setup_linuxdeploy() -> Result<()> {
     // Create .local/bin if it doesn't exist
     std::fs::create_dir_all(&local_bin_dir)?;
     
-    let linuxdeploy_path = format!("{}/linuxdeploy-x86_64.AppImage", local_bin_dir);
+    // linuxdeploy names its builds after the architecture as Debian does
+    let arch = match std::env::consts::ARCH {
+        "arm" => "armhf",
+        other => other,
+    };
+    let linuxdeploy_path = format!("{}/linuxdeploy-{}.AppImage", local_bin_dir, arch);
     
-    println!("   Downloading linuxdeploy...");
-    let status = Command::new("curl")
-        .args(&[
-            "-L",
-            "-o", &linuxdeploy_path,
-            "https://github.com/linuxdeploy/linuxdeploy/releases/download/continuous/linuxdeploy-x86_64.AppImage"
-        ])
-        .status()?;
-    
-    if !status.success() {
-        anyhow::bail!("Failed to download linuxdeploy");
-    }
+    pins::download(&format!("linuxdeploy-{}", arch), std::path::Path::new(&linuxdeploy_path), update_pins)?;
     
     // Make it executable
     println!("   Making linuxdeploy executable...");
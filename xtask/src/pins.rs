@@ -0,0 +1,97 @@
+//! Version and checksum pins of the binaries and scripts `cargo xtask setup` downloads, kept in `xtask/pins.json`.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::dist::sha256_file;
+
+/// A download pinned to a version and the SHA-256 of its content.
+pub struct Pin {
+    pub name: String,
+    pub version: String,
+    pub url: String,
+    /// `None` until recorded with `--update-pins`
+    pub sha256: Option<String>,
+}
+
+fn pins_file() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("pins.json")
+}
+
+fn read_pins() -> Result<Value> {
+    let path = pins_file();
+    let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// The pin of `name` in `pins.json`.
+pub fn pin(name: &str) -> Result<Pin> {
+    let pins = read_pins()?;
+    let entry = pins
+        .get(name)
+        .with_context(|| format!("{} has no pin in {}", name, pins_file().display()))?;
+    let field = |key: &str| entry.get(key).and_then(Value::as_str).map(str::to_string);
+    Ok(Pin {
+        name: name.to_string(),
+        version: field("version").with_context(|| format!("The pin of {} has no version", name))?,
+        url: field("url").with_context(|| format!("The pin of {} has no url", name))?,
+        sha256: field("sha256").filter(|hash| !hash.is_empty()),
+    })
+}
+
+fn record_sha256(name: &str, sha256: &str) -> Result<()> {
+    let mut pins = read_pins()?;
+    pins[name]["sha256"] = Value::String(sha256.to_string());
+    fs::write(pins_file(), serde_json::to_string_pretty(&pins)? + "\n")?;
+    Ok(())
+}
+
+/// Downloads the pinned `name` to `destination` and verifies its checksum, deleting it on a mismatch.
+///
+/// With `update_pins` the checksum of the download is recorded in `pins.json` instead, to move to
+/// the version the pin was changed to. Review and commit the changed file.
+pub fn download(name: &str, destination: &Path, update_pins: bool) -> Result<()> {
+    let pin = pin(name)?;
+    println!("   Downloading {} {}...", pin.name, pin.version);
+    let status = Command::new("curl")
+        .args(["-fL", "-o"])
+        .arg(destination)
+        .arg(&pin.url)
+        .status()?;
+    if !status.success() {
+        anyhow::bail!("Failed to download {} from {}", pin.name, pin.url);
+    }
+    let actual = sha256_file(destination)?;
+    if update_pins {
+        record_sha256(name, &actual)?;
+        println!("   📌 Recorded sha256 {} of {} {} in {}", actual, pin.name, pin.version, pins_file().display());
+        return Ok(());
+    }
+    match pin.sha256 {
+        Some(expected) if expected.eq_ignore_ascii_case(&actual) => {
+            println!("   ✅ Verified sha256 of {}", pin.name);
+            Ok(())
+        }
+        Some(expected) => {
+            let _ = fs::remove_file(destination);
+            anyhow::bail!(
+                "Checksum mismatch of {} {}: expected {}, got {}. Upstream changed the file; check it and rerun with --update-pins to accept it",
+                pin.name,
+                pin.version,
+                expected,
+                actual
+            )
+        }
+        None => {
+            let _ = fs::remove_file(destination);
+            anyhow::bail!(
+                "No checksum is pinned for {} {}; rerun with --update-pins to record it",
+                pin.name,
+                pin.version
+            )
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Boilerplate of new Tauri commands and CLI subcommands, generated with their registrations so
+//! none of the places a command has to be wired into is forgotten.
+
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand)]
+pub enum ScaffoldKind {
+    /// A Tauri command with typed request and response, its registration, a test skeleton and a
+    /// frontend binding in src/api/commands.js
+    #[command(name = "tauri-command")]
+    TauriCommand {
+        /// Name of the command, e.g. get_cache_size
+        name: String,
+
+        /// File in src-tauri/src/gui/commands the command is added to, created if missing
+        #[arg(long, default_value = "utils_commands")]
+        module: String,
+    },
+
+    /// An `eim` subcommand with its arguments, its dispatch, a library module with a test skeleton
+    /// and a translation
+    #[command(name = "cli-subcommand")]
+    CliSubcommand {
+        /// Name of the subcommand, e.g. prune-cache
+        name: String,
+    },
+}
+
+pub fn scaffold(kind: ScaffoldKind) -> Result<()> {
+    match kind {
+        ScaffoldKind::TauriCommand { name, module } => tauri_command(&name, &module),
+        ScaffoldKind::CliSubcommand { name } => cli_subcommand(&name),
+    }
+}
+
+fn repo_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask lives in the repository")
+        .to_path_buf()
+}
+
+/// Accepts lower case names of letters, digits, `-` and `_`, starting with a letter.
+fn validate_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        bail!("Invalid name '{}', use lower case letters, digits, '-' and '_', e.g. prune-cache", name);
+    }
+    Ok(())
+}
+
+fn snake_case(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+fn words(name: &str) -> impl Iterator<Item = &str> {
+    name.split(['-', '_']).filter(|word| !word.is_empty())
+}
+
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+fn pascal_case(name: &str) -> String {
+    words(name).map(capitalized).collect()
+}
+
+fn camel_case(name: &str) -> String {
+    let pascal = pascal_case(name);
+    let mut chars = pascal.chars();
+    chars
+        .next()
+        .map(|first| first.to_ascii_lowercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn write(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("   {}", path.strip_prefix(repo_root()).unwrap_or(path).display());
+    Ok(())
+}
+
+fn create(path: &Path, content: &str) -> Result<()> {
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+    write(path, content)
+}
+
+/// Inserts `text` in front of the line starting with `anchor`, searching from `after` on.
+fn insert_before_line(content: &str, after: &str, anchor: &str, text: &str) -> Result<String> {
+    let start = content.find(after).with_context(|| format!("'{}' not found", after))?;
+    let offset = content[start..]
+        .match_indices('\n')
+        .map(|(i, _)| start + i + 1)
+        .find(|&line| content[line..].starts_with(anchor))
+        .with_context(|| format!("No line starting with '{}' after '{}'", anchor, after))?;
+    Ok(format!("{}{}{}", &content[..offset], text, &content[offset..]))
+}
+
+/// Adds `pub mod <module>;` in alphabetical order among the other `pub mod` lines.
+fn add_module_declaration(content: &str, module: &str) -> Result<String> {
+    let declaration = format!("pub mod {};\n", module);
+    if content.contains(&declaration) {
+        return Ok(content.to_string());
+    }
+    let lines: Vec<(usize, &str)> = content
+        .match_indices("pub mod ")
+        .filter(|(i, _)| *i == 0 || content[..*i].ends_with('\n'))
+        .map(|(i, _)| (i, content[i + 8..].split(';').next().unwrap_or_default()))
+        .collect();
+    let offset = match lines.iter().find(|(_, name)| *name > module) {
+        Some((i, _)) => *i,
+        None => {
+            let (last, _) = lines.last().context("No module declarations found")?;
+            last + content[*last..].find('\n').map_or(content.len() - last, |i| i + 1)
+        }
+    };
+    Ok(format!("{}{}{}", &content[..offset], declaration, &content[offset..]))
+}
+
+fn tauri_command(name: &str, module: &str) -> Result<()> {
+    validate_name(name)?;
+    validate_name(module)?;
+    let command = snake_case(name);
+    let module = snake_case(module);
+    let pascal = pascal_case(name);
+    let root = repo_root();
+    let gui_dir = root.join("src-tauri/src/gui");
+    let gui_mod = gui_dir.join("mod.rs");
+    let mut registrations = read(&gui_mod)?;
+    if registrations.contains(&format!("            {},\n", command)) {
+        bail!("The command {} is already registered in {}", command, gui_mod.display());
+    }
+    println!("🏗️  Scaffolding the Tauri command {}...", command);
+
+    let module_file = gui_dir.join("commands").join(format!("{}.rs", module));
+    let mut content = if module_file.exists() {
+        read(&module_file)?
+    } else {
+        let commands_mod = gui_dir.join("commands/mod.rs");
+        write(&commands_mod, &add_module_declaration(&read(&commands_mod)?, &module)?)?;
+        registrations = registrations.replacen("use commands::{", &format!("use commands::{{{}::*, ", module), 1);
+        String::new()
+    };
+    let generated = format!(
+        r#"
+/// Arguments of [`{command}`], sent by the frontend as `request`
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+#[serde(rename_all = "camelCase")]
+pub struct {pascal}Request {{}}
+
+/// Result of [`{command}`]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "ts-bindings", derive(ts_rs::TS), ts(export))]
+#[serde(rename_all = "camelCase")]
+pub struct {pascal}Response {{}}
+
+/// TODO: describe what the command does
+#[tauri::command]
+pub async fn {command}(
+  app_handle: tauri::AppHandle,
+  request: {pascal}Request,
+) -> Result<{pascal}Response, String> {{
+  let _settings = crate::gui::app_state::get_settings_non_blocking(&app_handle)?;
+  log::debug!("{command} {{:?}}", request);
+  Ok({pascal}Response::default())
+}}
+
+#[cfg(test)]
+mod {command}_tests {{
+  use super::*;
+
+  #[test]
+  fn test_{command}_request_from_frontend() {{
+    let request: {pascal}Request = serde_json::from_str("{{}}").unwrap();
+    assert_eq!(request, {pascal}Request::default());
+  }}
+}}
+"#
+    );
+    // a new file starts right away
+    content.push_str(if content.is_empty() { generated.trim_start() } else { &generated });
+    write(&module_file, &content)?;
+
+    let registrations = insert_before_line(
+        &registrations,
+        "tauri::generate_handler![",
+        "        ])",
+        &format!("            {},\n", command),
+    )?;
+    write(&gui_mod, &registrations)?;
+
+    let bindings = root.join("src/api/commands.js");
+    let mut content = if bindings.exists() {
+        read(&bindings)?
+    } else {
+        "// Typed wrappers of the Tauri commands, with the types `cargo xtask bindings` exports from the Rust\n// payloads into ./bindings. `cargo xtask scaffold tauri-command` appends the ones of new commands.\nimport { invoke } from \"./invoke.js\";\n".to_string()
+    };
+    content.push_str(&format!(
+        "\n/**\n * TODO: describe, see `{command}` in src-tauri/src/gui/commands/{module}.rs\n * @param {{import(\"./bindings/{pascal}Request\").{pascal}Request}} request\n * @returns {{Promise<import(\"./bindings/{pascal}Response\").{pascal}Response>}}\n */\nexport function {camel}(request = {{}}) {{\n  return invoke(\"{command}\", {{ request }});\n}}\n",
+        camel = camel_case(name)
+    ));
+    write(&bindings, &content)?;
+
+    println!("✅ Scaffolded {}, fill in the TODOs and the request and response fields", command);
+    Ok(())
+}
+
+fn cli_subcommand(name: &str) -> Result<()> {
+    validate_name(name)?;
+    let name = name.replace('_', "-");
+    let module = snake_case(&name);
+    let pascal = pascal_case(&name);
+    let root = repo_root();
+    let cli_dir = root.join("eim-cli/src/cli");
+    let lib_dir = root.join("eim-core/src");
+    let cli_args = cli_dir.join("cli_args.rs");
+    let args_content = read(&cli_args)?;
+    if args_content.contains(&format!("\n    {} {{", pascal)) || args_content.contains(&format!("\n    {}(", pascal)) {
+        bail!("eim {} already exists in {}", name, cli_args.display());
+    }
+    println!("🏗️  Scaffolding the subcommand eim {}...", name);
+
+    create(
+        &lib_dir.join(format!("{}.rs", module)),
+        &format!(
+            r#"use anyhow::Result;
+
+/// TODO: describe what `eim {name}` does. Returns what was changed, or would be with `dry_run`.
+pub fn run(dry_run: bool) -> Result<Vec<String>> {{
+    // TODO: collect the changes, and make them unless `dry_run`
+    let _ = dry_run;
+    Ok(Vec::new())
+}}
+
+#[cfg(test)]
+mod tests {{
+    use super::*;
+
+    #[test]
+    fn test_dry_run() {{
+        assert!(run(true).unwrap().is_empty());
+    }}
+}}
+"#
+        ),
+    )?;
+    let lib_mod = lib_dir.join("lib.rs");
+    write(&lib_mod, &add_module_declaration(&read(&lib_mod)?, &module)?)?;
+
+    let args_content = insert_before_line(
+        &args_content,
+        "pub enum Commands {",
+        "}",
+        &format!(
+            "\n    /// TODO: describe what `eim {name}` does\n    {pascal} {{\n        #[arg(long, help = \"Show what would be done without changing anything\")]\n        dry_run: bool,\n    }},\n"
+        ),
+    )?;
+    write(&cli_args, &args_content)?;
+
+    let cli_mod = cli_dir.join("mod.rs");
+    let content = read(&cli_mod)?;
+    let content = insert_before_line(
+        &content,
+        "    let result = match command {",
+        "    };",
+        &format!("        Commands::{pascal} {{ dry_run }} => {module}(dry_run),\n"),
+    )?;
+    let content = insert_before_line(
+        &content,
+        "use ",
+        "pub async fn run_cli(",
+        &format!(
+            "/// TODO: describe what `eim {name}` does\nfn {module}(dry_run: bool) -> anyhow::Result<()> {{\n    let changes = idf_im_lib::{module}::run(dry_run)?;\n    for change in &changes {{\n        println!(\"  {{}}\", change);\n    }}\n    println!(\"{{}}\", t!(\"{module}.done\", count = changes.len()));\n    Ok(())\n}}\n\n"
+        ),
+    )?;
+    write(&cli_mod, &content)?;
+
+    let locales = root.join("eim-core/locales/app.yml");
+    let mut content = read(&locales)?;
+    content.push_str(&format!(
+        "{module}.done:\n  en: \"TODO: %{{count}} changes\"\n  cn: \"TODO: %{{count}} 项更改\"\n"
+    ));
+    write(&locales, &content)?;
+
+    println!("✅ Scaffolded eim {}. Still to do:", name);
+    println!("   - fill in the TODOs and the arguments");
+    println!("   - add it to is_mutating_command in eim-cli/src/cli/mod.rs if it changes installations");
+    println!("   - document it in docs/src/cli_commands.md");
+    Ok(())
+}
@@ -0,0 +1,183 @@
+//! `cargo xtask vendor`: copies the crates and the frontend packages next to the repository and
+//! points cargo, npm and yarn at them, so eim builds without network access.
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Markers around the configuration written by `cargo xtask vendor`, replaced when run again
+const BEGIN_MARKER: &str = "# BEGIN cargo xtask vendor";
+const END_MARKER: &str = "# END cargo xtask vendor";
+
+/// Options of `cargo xtask vendor`.
+pub struct VendorOptions {
+    pub out_dir: PathBuf,
+    pub skip_frontend: bool,
+    pub remove: bool,
+}
+
+fn repo_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask lives in the repository")
+        .to_path_buf()
+}
+
+/// `path` as written into the configuration files: relative to the repository when inside it,
+/// with forward slashes, which cargo, npm and yarn accept on every OS.
+fn config_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Replaces the block between the markers in `content` with `block`, appends it if there is
+/// none, and removes it if `block` is `None`.
+fn replace_block(content: &str, block: Option<&str>) -> String {
+    let mut kept = String::new();
+    let mut inside = false;
+    for line in content.lines() {
+        if line.trim() == BEGIN_MARKER {
+            inside = true;
+        } else if line.trim() == END_MARKER {
+            inside = false;
+        } else if !inside {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    let mut result = kept.trim_end().to_string();
+    if let Some(block) = block {
+        if !result.is_empty() {
+            result.push_str("\n\n");
+        }
+        result.push_str(&format!("{}\n{}\n{}", BEGIN_MARKER, block.trim_end(), END_MARKER));
+    }
+    if !result.is_empty() {
+        result.push('\n');
+    }
+    result
+}
+
+fn update_config_file(path: &Path, block: Option<&str>) -> Result<()> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    let updated = replace_block(&content, block);
+    if updated.is_empty() {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, updated).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("   Updated {}", path.display());
+    Ok(())
+}
+
+fn has_program(program: &str) -> bool {
+    Command::new(program)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Runs `program` in the repository root, failing with its name and arguments.
+fn run_in(root: &Path, program: &str, args: &[&str]) -> Result<()> {
+    let status = Command::new(program).args(args).current_dir(root).status()?;
+    if !status.success() {
+        bail!("Command failed: {} {:?}", program, args);
+    }
+    Ok(())
+}
+
+/// Vendors the crates of `Cargo.lock`, git dependencies included, and replaces their sources
+/// in `.cargo/config.toml` with the vendored copy.
+fn vendor_cargo(root: &Path, out_dir: &Path) -> Result<()> {
+    let cargo_dir = out_dir.join("cargo");
+    println!("📦 Vendoring the crates into {}...", cargo_dir.display());
+    // with the previous source replacement cargo would vendor from the old copy
+    let config_toml = root.join(".cargo").join("config.toml");
+    update_config_file(&config_toml, None)?;
+    let cargo_dir_arg = config_path(root, &cargo_dir);
+    let output = Command::new("cargo")
+        .args(["vendor", "--locked", "--versioned-dirs", &cargo_dir_arg])
+        .current_dir(root)
+        .output()?;
+    if !output.status.success() {
+        bail!("cargo vendor failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    // the source replacement cargo vendor prints, for crates.io and every git dependency
+    let config = String::from_utf8_lossy(&output.stdout);
+    update_config_file(&config_toml, Some(&config))
+}
+
+/// Fills an npm cache with the packages of `package-lock.json` and makes npm use only it.
+fn vendor_npm(root: &Path, out_dir: &Path) -> Result<()> {
+    let cache = out_dir.join("npm-cache");
+    println!("📦 Caching the npm packages in {}...", cache.display());
+    // the previous configuration would make npm ci offline
+    update_config_file(&root.join(".npmrc"), None)?;
+    run_in(root, "npm", &["ci", "--ignore-scripts", "--cache", &cache.to_string_lossy()])?;
+    let block = format!("cache={}\noffline=true", config_path(root, &cache));
+    update_config_file(&root.join(".npmrc"), Some(&block))
+}
+
+/// Copies the package tarballs of `yarn.lock` into a yarn offline mirror, used by the build
+/// commands of `tauri.conf.json`.
+fn vendor_yarn(root: &Path, out_dir: &Path) -> Result<()> {
+    let mirror = out_dir.join("yarn");
+    println!("📦 Mirroring the yarn packages into {}...", mirror.display());
+    let block = format!(
+        "yarn-offline-mirror \"{}\"\nyarn-offline-mirror-pruning true",
+        config_path(root, &mirror)
+    );
+    update_config_file(&root.join(".yarnrc"), Some(&block))?;
+    // --force fetches the packages found in the global cache too, otherwise they are not mirrored
+    run_in(root, "yarn", &["install", "--frozen-lockfile", "--ignore-scripts", "--force"])
+}
+
+pub fn vendor(options: VendorOptions) -> Result<()> {
+    let root = repo_root();
+    let out_dir = if options.out_dir.is_absolute() {
+        options.out_dir.clone()
+    } else {
+        root.join(&options.out_dir)
+    };
+
+    if options.remove {
+        println!("🧹 Removing the vendored sources from the build configuration...");
+        update_config_file(&root.join(".cargo").join("config.toml"), None)?;
+        update_config_file(&root.join(".npmrc"), None)?;
+        update_config_file(&root.join(".yarnrc"), None)?;
+        println!("✅ cargo, npm and yarn use the network again, {} was kept", out_dir.display());
+        return Ok(());
+    }
+
+    fs::create_dir_all(&out_dir)?;
+    vendor_cargo(&root, &out_dir)?;
+    if !options.skip_frontend {
+        if has_program("npm") {
+            vendor_npm(&root, &out_dir)?;
+        } else {
+            println!("⚠️  npm not found, the npm packages were not vendored");
+        }
+        if has_program("yarn") && root.join("yarn.lock").exists() {
+            vendor_yarn(&root, &out_dir)?;
+        } else {
+            println!("⚠️  yarn not found, the yarn packages were not vendored");
+        }
+    }
+
+    println!("\n✅ Vendored into {}", out_dir.display());
+    println!("💡 Build without network with:");
+    println!("   cargo build --offline");
+    if !options.skip_frontend {
+        println!("   npm ci --offline   or   yarn install --offline --frozen-lockfile");
+    }
+    println!("   Undo the configuration with: cargo xtask vendor --remove");
+    Ok(())
+}